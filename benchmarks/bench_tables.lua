@@ -23,6 +23,30 @@ end
 elapsed = os.clock() - start
 print(string.format("Table insertion: %.3f seconds (%.2f M inserts/sec)", elapsed, iterations / elapsed / 1000000))
 
+-- Growing vs presized fill (table.create is Lua 5.5)
+if table.create then
+    local fill_size = 1000000
+    start = os.clock()
+    for _ = 1, 10 do
+        local grown = {}
+        for i = 1, fill_size do
+            grown[i] = i
+        end
+    end
+    elapsed = os.clock() - start
+    print(string.format("Fill 1M (growing): %.3f seconds", elapsed / 10))
+
+    start = os.clock()
+    for _ = 1, 10 do
+        local presized = table.create(fill_size)
+        for i = 1, fill_size do
+            presized[i] = i
+        end
+    end
+    elapsed = os.clock() - start
+    print(string.format("Fill 1M (table.create): %.3f seconds", elapsed / 10))
+end
+
 -- Table iteration
 start = os.clock()
 local sum = 0
@@ -0,0 +1,15 @@
+fn main() {
+    println!("cargo:rerun-if-changed=src/vararg.c");
+    println!("cargo:rerun-if-changed=include");
+
+    cc::Build::new()
+        .file("src/vararg.c")
+        .include("include")
+        // luaL_error unwinds through the C frames back to the Rust bridge
+        .flag_if_supported("-fexceptions")
+        .compile("luars_capi_vararg");
+
+    // Lets the tests build C programs for the same target
+    let target = std::env::var("TARGET").unwrap();
+    println!("cargo:rustc-env=LUARS_CAPI_TARGET={target}");
+}
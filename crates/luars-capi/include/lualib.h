@@ -0,0 +1,14 @@
+/*
+** lualib.h for luars-capi
+** Lua standard libraries.
+*/
+
+#ifndef lualib_h
+#define lualib_h
+
+#include "lua.h"
+
+/* open all standard libraries */
+LUALIB_API void (luaL_openlibs) (lua_State *L);
+
+#endif
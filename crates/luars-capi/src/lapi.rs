@@ -0,0 +1,729 @@
+//! The `lua_*` functions of lua.h.
+
+use std::ffi::{c_char, c_int, c_void};
+
+use luars::{LUA_MULTRET, LuaApi, LuaStackApi, LuaState, LuaValue};
+
+use crate::state::*;
+
+// ==================== State manipulation ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_close(l: *mut lua_State) {
+    let cs = cstate(unsafe { &*l });
+    unsafe {
+        // Finalizers may still call C functions, which reach the shim data
+        let owner = Box::from_raw((*cs).owner);
+        let _ = owner.close();
+        drop(Box::from_raw(cs));
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_newthread(l: *mut lua_State) -> *mut lua_State {
+    unsafe {
+        error(
+            l,
+            "lua_newthread is not supported by luars-capi".to_string(),
+        )
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_atpanic(
+    l: *mut lua_State,
+    panicf: lua_CFunction,
+) -> lua_CFunction {
+    let cs = cstate(unsafe { &*l });
+    unsafe { std::mem::replace(&mut (*cs).panic, panicf) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_version(_l: *mut lua_State) -> lua_Number {
+    505.0
+}
+
+// ==================== Basic stack manipulation ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_absindex(l: *mut lua_State, idx: c_int) -> c_int {
+    if idx > 0 || idx <= C_REGISTRYINDEX {
+        idx
+    } else {
+        unsafe { lua_gettop(l) + idx + 1 }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_gettop(l: *mut lua_State) -> c_int {
+    unsafe { (*l).lua_gettop() as c_int }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_settop(l: *mut lua_State, idx: c_int) {
+    unsafe { api(l, |l| l.lua_settop(idx as isize)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushvalue(l: *mut lua_State, idx: c_int) {
+    unsafe { api(l, |l| l.lua_pushvalue(index(idx))) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_rotate(l: *mut lua_State, idx: c_int, n: c_int) {
+    unsafe { api(l, |l| l.lua_rotate(index(idx), n as isize)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_copy(l: *mut lua_State, fromidx: c_int, toidx: c_int) {
+    unsafe { api(l, |l| l.lua_copy(index(fromidx), index(toidx))) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_checkstack(l: *mut lua_State, n: c_int) -> c_int {
+    let l = unsafe { &mut *l };
+    (n >= 0 && l.check_stack(n as usize) && l.ensure_stack_capacity(n as usize).is_ok()) as c_int
+}
+
+// ==================== Access functions (stack -> C) ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_isnumber(l: *mut lua_State, idx: c_int) -> c_int {
+    unsafe { (*l).lua_isnumber(index(idx)) as c_int }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_isstring(l: *mut lua_State, idx: c_int) -> c_int {
+    unsafe { (*l).lua_isstring(index(idx)) as c_int }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_iscfunction(l: *mut lua_State, idx: c_int) -> c_int {
+    unsafe { (*l).lua_iscfunction(index(idx)) as c_int }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_isinteger(l: *mut lua_State, idx: c_int) -> c_int {
+    unsafe { (*l).lua_isinteger(index(idx)) as c_int }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_isuserdata(l: *mut lua_State, idx: c_int) -> c_int {
+    unsafe { (*l).lua_isuserdata(index(idx)) as c_int }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_type(l: *mut lua_State, idx: c_int) -> c_int {
+    type_code(unsafe { &mut *l }, index(idx))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_typename(_l: *mut lua_State, tp: c_int) -> *const c_char {
+    let name = match tp {
+        LUA_TNIL => c"nil",
+        LUA_TBOOLEAN => c"boolean",
+        LUA_TLIGHTUSERDATA | LUA_TUSERDATA => c"userdata",
+        LUA_TNUMBER => c"number",
+        LUA_TSTRING => c"string",
+        LUA_TTABLE => c"table",
+        LUA_TFUNCTION => c"function",
+        LUA_TTHREAD => c"thread",
+        _ => c"no value",
+    };
+    name.as_ptr()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_tonumberx(
+    l: *mut lua_State,
+    idx: c_int,
+    isnum: *mut c_int,
+) -> lua_Number {
+    let n = unsafe { (*l).lua_tonumberx(index(idx)) };
+    if !isnum.is_null() {
+        unsafe { *isnum = n.is_some() as c_int };
+    }
+    n.unwrap_or(0.0)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_tointegerx(
+    l: *mut lua_State,
+    idx: c_int,
+    isnum: *mut c_int,
+) -> lua_Integer {
+    let n = unsafe { (*l).lua_tointegerx(index(idx)) };
+    if !isnum.is_null() {
+        unsafe { *isnum = n.is_some() as c_int };
+    }
+    n.unwrap_or(0)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_toboolean(l: *mut lua_State, idx: c_int) -> c_int {
+    unsafe { (*l).lua_toboolean(index(idx)) as c_int }
+}
+
+/// Numbers are converted to strings in place, as in the reference
+/// implementation. The text stays valid while the C function that asked
+/// for it runs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_tolstring(
+    l: *mut lua_State,
+    idx: c_int,
+    len: *mut usize,
+) -> *const c_char {
+    let text = unsafe {
+        api(l, |l| {
+            let idx = index(idx);
+            let Some(mut value) = value_at(l, idx) else {
+                return Ok(None);
+            };
+            if value.is_number() {
+                value = l.tostring_value(&value)?;
+                let abs = l.lua_absindex(idx).unwrap_or(idx);
+                l.push_value(value)?;
+                l.lua_copy(-1, abs)?;
+                l.lua_pop(1)?;
+            } else if !value.is_string() {
+                return Ok(None);
+            }
+            Ok(Some(value))
+        })
+    };
+    let Some(value) = text else {
+        if !len.is_null() {
+            unsafe { *len = 0 };
+        }
+        return std::ptr::null();
+    };
+    if !len.is_null() {
+        unsafe { *len = value.as_bytes().map_or(0, <[u8]>::len) };
+    }
+    unsafe { (*cstate(&*l)).c_string(&*l, value) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_rawlen(l: *mut lua_State, idx: c_int) -> lua_Unsigned {
+    unsafe {
+        api(l, |l| {
+            let idx = index(idx);
+            let value = value_at(l, idx).unwrap_or_default();
+            if let Some(ud) = value.as_userdata_mut()
+                && let Some(block) = ud.downcast_ref::<CBlock>()
+            {
+                return Ok(block.size as lua_Unsigned);
+            }
+            Ok(l.lua_rawlen(idx)? as lua_Unsigned)
+        })
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_touserdata(l: *mut lua_State, idx: c_int) -> *mut c_void {
+    let Some(value) = value_at(unsafe { &mut *l }, index(idx)) else {
+        return std::ptr::null_mut();
+    };
+    userdata_ptr(&value)
+}
+
+pub(crate) fn userdata_ptr(value: &LuaValue) -> *mut c_void {
+    if let Some(p) = value.as_lightuserdata() {
+        return p;
+    }
+    let Some(ud) = value.as_userdata_mut() else {
+        return std::ptr::null_mut();
+    };
+    if let Some(block) = ud.downcast_mut::<CBlock>() {
+        return block.data.as_mut_ptr().cast();
+    }
+    match ud.get_data_mut() {
+        Ok(data) => (data as *mut dyn std::any::Any).cast(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_tothread(l: *mut lua_State, idx: c_int) -> *mut lua_State {
+    let value = value_at(unsafe { &mut *l }, index(idx)).unwrap_or_default();
+    match value.as_thread_mut() {
+        Some(thread) => thread,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_topointer(l: *mut lua_State, idx: c_int) -> *const c_void {
+    let Some(value) = value_at(unsafe { &mut *l }, index(idx)) else {
+        return std::ptr::null();
+    };
+    if value.is_userdata() {
+        userdata_ptr(&value)
+    } else if value.as_gc_ptr().is_some() || value.is_function() {
+        value.raw_ptr_repr().cast()
+    } else {
+        std::ptr::null()
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_rawequal(l: *mut lua_State, idx1: c_int, idx2: c_int) -> c_int {
+    let l = unsafe { &mut *l };
+    match (value_at(l, index(idx1)), value_at(l, index(idx2))) {
+        (Some(a), Some(b)) => (a == b) as c_int,
+        _ => 0,
+    }
+}
+
+// ==================== Push functions (C -> stack) ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushnil(l: *mut lua_State) {
+    unsafe { api(l, |l| l.lua_pushnil()) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushnumber(l: *mut lua_State, n: lua_Number) {
+    unsafe { api(l, |l| l.lua_pushnumber(n)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushinteger(l: *mut lua_State, n: lua_Integer) {
+    unsafe { api(l, |l| l.lua_pushinteger(n)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushlstring(
+    l: *mut lua_State,
+    s: *const c_char,
+    len: usize,
+) -> *const c_char {
+    let bytes = if len == 0 {
+        &[][..]
+    } else {
+        unsafe { std::slice::from_raw_parts(s.cast::<u8>(), len) }
+    };
+    unsafe {
+        api(l, |l| l.lua_pushlstring(bytes));
+        lua_tolstring(l, -1, std::ptr::null_mut())
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushstring(
+    l: *mut lua_State,
+    s: *const c_char,
+) -> *const c_char {
+    if s.is_null() {
+        unsafe { lua_pushnil(l) };
+        return std::ptr::null();
+    }
+    unsafe {
+        let bytes = c_bytes(s);
+        lua_pushlstring(l, s, bytes.len())
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushcclosure(l: *mut lua_State, f: lua_CFunction, n: c_int) {
+    let Some(f) = f else {
+        unsafe { error(l, "lua_pushcclosure: function is NULL".to_string()) }
+    };
+    unsafe { api(l, |l| push_cfunction(l, f, n.max(0) as usize)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushboolean(l: *mut lua_State, b: c_int) {
+    unsafe { api(l, |l| l.lua_pushboolean(b != 0)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushlightuserdata(l: *mut lua_State, p: *mut c_void) {
+    unsafe { api(l, |l| l.lua_pushlightuserdata(p)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pushthread(l: *mut lua_State) -> c_int {
+    unsafe { api(l, |l| l.lua_pushthread()) as c_int }
+}
+
+// ==================== Get functions (Lua -> stack) ====================
+
+/// Push `t[k]` for the table at `idx` and a C string key, which need not
+/// be UTF-8.
+unsafe fn get_field(l: &mut LuaState, idx: isize, k: *const c_char) -> c_int {
+    let key = unsafe { c_bytes(k) };
+    unsafe {
+        api(l, |l| match std::str::from_utf8(key) {
+            Ok(key) => l.lua_getfield(idx, key),
+            Err(_) => {
+                let abs = l.lua_absindex(idx).unwrap_or(idx);
+                l.lua_pushlstring(key)?;
+                l.lua_gettable(abs)
+            }
+        });
+    }
+    type_code(l, -1)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_getglobal(l: *mut lua_State, name: *const c_char) -> c_int {
+    unsafe { get_field(&mut *l, luars::LUA_GLOBALSINDEX, name) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_gettable(l: *mut lua_State, idx: c_int) -> c_int {
+    unsafe {
+        api(l, |l| l.lua_gettable(index(idx)));
+        type_code(&mut *l, -1)
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_getfield(
+    l: *mut lua_State,
+    idx: c_int,
+    k: *const c_char,
+) -> c_int {
+    unsafe { get_field(&mut *l, index(idx), k) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_geti(l: *mut lua_State, idx: c_int, n: lua_Integer) -> c_int {
+    unsafe {
+        api(l, |l| l.lua_geti(index(idx), n));
+        type_code(&mut *l, -1)
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_rawget(l: *mut lua_State, idx: c_int) -> c_int {
+    unsafe {
+        api(l, |l| l.lua_rawget(index(idx)));
+        type_code(&mut *l, -1)
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_rawgeti(
+    l: *mut lua_State,
+    idx: c_int,
+    n: lua_Integer,
+) -> c_int {
+    unsafe {
+        api(l, |l| l.lua_rawgeti(index(idx), n));
+        type_code(&mut *l, -1)
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_rawgetp(
+    l: *mut lua_State,
+    idx: c_int,
+    p: *const c_void,
+) -> c_int {
+    unsafe {
+        api(l, |l| {
+            let idx = index(idx);
+            let abs = l.lua_absindex(idx).unwrap_or(idx);
+            l.lua_pushlightuserdata(p.cast_mut())?;
+            l.lua_rawget(abs)
+        });
+        type_code(&mut *l, -1)
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_createtable(l: *mut lua_State, narr: c_int, nrec: c_int) {
+    unsafe {
+        api(l, |l| {
+            l.lua_createtable(narr.max(0) as usize, nrec.max(0) as usize)
+        })
+    }
+}
+
+/// User values are not supported; `nuvalue` is ignored.
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_newuserdatauv(
+    l: *mut lua_State,
+    size: usize,
+    _nuvalue: c_int,
+) -> *mut c_void {
+    unsafe {
+        api(l, |l| {
+            l.lua_pushuserdata(CBlock::new(size))?;
+            let value = value_at(l, -1).unwrap_or_default();
+            Ok(userdata_ptr(&value))
+        })
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_getmetatable(l: *mut lua_State, objindex: c_int) -> c_int {
+    unsafe { api(l, |l| l.lua_getmetatable(index(objindex))) as c_int }
+}
+
+// ==================== Set functions (stack -> Lua) ====================
+
+/// `t[k] = v` for the table at `idx`, a C string key and the value on top.
+unsafe fn set_field(l: &mut LuaState, idx: isize, k: *const c_char) {
+    let key = unsafe { c_bytes(k) };
+    unsafe {
+        api(l, |l| match std::str::from_utf8(key) {
+            Ok(key) => l.lua_setfield(idx, key),
+            Err(_) => {
+                let abs = l.lua_absindex(idx).unwrap_or(idx);
+                l.lua_pushlstring(key)?;
+                l.lua_insert(-2)?;
+                l.lua_settable(abs)
+            }
+        })
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_setglobal(l: *mut lua_State, name: *const c_char) {
+    unsafe { set_field(&mut *l, luars::LUA_GLOBALSINDEX, name) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_settable(l: *mut lua_State, idx: c_int) {
+    unsafe { api(l, |l| l.lua_settable(index(idx))) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_setfield(l: *mut lua_State, idx: c_int, k: *const c_char) {
+    unsafe { set_field(&mut *l, index(idx), k) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_seti(l: *mut lua_State, idx: c_int, n: lua_Integer) {
+    unsafe { api(l, |l| l.lua_seti(index(idx), n)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_rawset(l: *mut lua_State, idx: c_int) {
+    unsafe { api(l, |l| l.lua_rawset(index(idx))) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_rawseti(l: *mut lua_State, idx: c_int, n: lua_Integer) {
+    unsafe { api(l, |l| l.lua_rawseti(index(idx), n)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_rawsetp(l: *mut lua_State, idx: c_int, p: *const c_void) {
+    unsafe {
+        api(l, |l| {
+            let idx = index(idx);
+            let abs = l.lua_absindex(idx).unwrap_or(idx);
+            l.lua_pushlightuserdata(p.cast_mut())?;
+            l.lua_insert(-2)?;
+            l.lua_rawset(abs)
+        })
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_setmetatable(l: *mut lua_State, objindex: c_int) -> c_int {
+    unsafe { api(l, |l| l.lua_setmetatable(index(objindex))) };
+    1
+}
+
+// ==================== Load and call ====================
+
+/// Continuations are not supported: `k` is never called.
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_callk(
+    l: *mut lua_State,
+    nargs: c_int,
+    nresults: c_int,
+    _ctx: lua_KContext,
+    _k: lua_KFunction,
+) {
+    unsafe { api(l, |l| l.lua_call(nargs.max(0) as usize, nresults as isize)) }
+}
+
+/// Continuations are not supported: `k` is never called.
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_pcallk(
+    l: *mut lua_State,
+    nargs: c_int,
+    nresults: c_int,
+    errfunc: c_int,
+    _ctx: lua_KContext,
+    _k: lua_KFunction,
+) -> c_int {
+    let nargs = nargs.max(0) as usize;
+    let ok = unsafe {
+        api(l, |l| {
+            if errfunc == 0 {
+                return l.lua_pcall(nargs, nresults as isize);
+            }
+
+            let handler = value_at(l, index(errfunc)).unwrap_or_default();
+            let top = l.get_top();
+            if top < nargs + 1 {
+                return Err(l.error(format!(
+                    "lua_pcall: expected function plus {} arguments on stack",
+                    nargs
+                )));
+            }
+            let func_idx = top - nargs - 1;
+            let func = l.stack_get(func_idx).unwrap_or_default();
+            let args = (func_idx + 1..top)
+                .map(|i| l.stack_get(i).unwrap_or_default())
+                .collect();
+            l.set_top(func_idx)?;
+            let (ok, results) = l.xpcall(func, args, handler)?;
+            let keep = if ok && nresults != LUA_MULTRET as c_int {
+                nresults.max(0) as usize
+            } else {
+                results.len()
+            };
+            for i in 0..keep {
+                l.push_value(results.get(i).copied().unwrap_or_default())?;
+            }
+            Ok(ok)
+        })
+    };
+    if ok { LUA_OK } else { LUA_ERRRUN }
+}
+
+/// Load a chunk from memory, as `luaL_loadbufferx`.
+pub(crate) fn load_buffer(l: &mut LuaState, bytes: &[u8], name: &str, mode: &[u8]) -> c_int {
+    let binary = bytes.first() == Some(&0x1b);
+    let refused = if binary && !mode.contains(&b'b') {
+        Some("attempt to load a binary chunk (mode is 't')")
+    } else if !binary && !mode.contains(&b't') {
+        Some("attempt to load a text chunk (mode is 'b')")
+    } else {
+        None
+    };
+    if let Some(msg) = refused {
+        unsafe { api(l, |l| l.lua_pushstring(msg)) };
+        return LUA_ERRSYNTAX;
+    }
+
+    let chunk = if binary {
+        l.load_file_bytes(bytes, name)
+    } else {
+        match std::str::from_utf8(bytes) {
+            Ok(source) => l.load_with_name(source, name),
+            Err(_) => Err(l.error(format!("{name}: source is not valid UTF-8"))),
+        }
+    };
+    unsafe {
+        api(l, |l| match chunk {
+            Ok(function) => l.push_value(function).map(|()| LUA_OK),
+            Err(e) => {
+                let msg = l.get_error_msg(e);
+                l.lua_pushstring(&msg).map(|()| LUA_ERRSYNTAX)
+            }
+        })
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_load(
+    l: *mut lua_State,
+    reader: lua_Reader,
+    data: *mut c_void,
+    chunkname: *const c_char,
+    mode: *const c_char,
+) -> c_int {
+    let mut source = Vec::new();
+    if let Some(reader) = reader {
+        loop {
+            let mut size = 0;
+            let piece = unsafe { reader(l, data, &mut size) };
+            if piece.is_null() || size == 0 {
+                break;
+            }
+            source.extend_from_slice(unsafe { std::slice::from_raw_parts(piece.cast(), size) });
+        }
+    }
+    let name = if chunkname.is_null() {
+        "?".to_string()
+    } else {
+        String::from_utf8_lossy(unsafe { c_bytes(chunkname) }).into_owned()
+    };
+    let mode = if mode.is_null() {
+        &b"bt"[..]
+    } else {
+        unsafe { c_bytes(mode) }
+    };
+    load_buffer(unsafe { &mut *l }, &source, &name, mode)
+}
+
+// ==================== Garbage collection ====================
+
+const LUA_GCSTOP: c_int = 0;
+const LUA_GCRESTART: c_int = 1;
+const LUA_GCCOLLECT: c_int = 2;
+const LUA_GCCOUNT: c_int = 3;
+const LUA_GCCOUNTB: c_int = 4;
+const LUA_GCSTEP: c_int = 5;
+const LUA_GCISRUNNING: c_int = 6;
+
+/// Non-variadic part of `lua_gc` (see vararg.c). Steps run a full
+/// collection.
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luars_capi_gc_option(
+    l: *mut lua_State,
+    what: c_int,
+    _arg: c_int,
+) -> c_int {
+    let cs = cstate(unsafe { &*l });
+    let bytes = || unsafe {
+        (*(*cs).owner)
+            .global_state_mut()
+            .gc_stats_struct()
+            .bytes_allocated
+    };
+    match what {
+        LUA_GCSTOP => unsafe {
+            (*l).gc_stop();
+            (*cs).gc_stopped = true;
+            0
+        },
+        LUA_GCRESTART => unsafe {
+            (*l).gc_restart();
+            (*cs).gc_stopped = false;
+            0
+        },
+        LUA_GCCOLLECT | LUA_GCSTEP => unsafe {
+            api(l, |l| l.collect_garbage());
+            (what == LUA_GCSTEP) as c_int
+        },
+        LUA_GCCOUNT => (bytes() >> 10) as c_int,
+        LUA_GCCOUNTB => (bytes() & 0x3ff) as c_int,
+        LUA_GCISRUNNING => unsafe { !(*cs).gc_stopped as c_int },
+        _ => -1,
+    }
+}
+
+// ==================== Miscellaneous ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_error(l: *mut lua_State) -> c_int {
+    let l = unsafe { &mut *l };
+    let value = value_at(l, -1).unwrap_or_default();
+    let error = l.error_with_object(value);
+    raise(l, error)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_next(l: *mut lua_State, idx: c_int) -> c_int {
+    unsafe { api(l, |l| l.lua_next(index(idx))) as c_int }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_concat(l: *mut lua_State, n: c_int) {
+    unsafe { api(l, |l| l.lua_concat(n.max(0) as usize)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn lua_len(l: *mut lua_State, idx: c_int) {
+    unsafe { api(l, |l| l.lua_len(index(idx))) }
+}
@@ -0,0 +1,586 @@
+//! The `luaL_*` functions of lauxlib.h and lualib.h, built on the `lua_*`
+//! functions like the reference lauxlib.c.
+
+use std::ffi::{c_char, c_int, c_void};
+
+use luars::{LUA_REGISTRYINDEX, Lua, LuaApi, LuaStackApi, SafeOption, Stdlib};
+
+use crate::lapi::*;
+use crate::state::*;
+
+#[repr(C)]
+pub struct luaL_Reg {
+    pub name: *const c_char,
+    pub func: lua_CFunction,
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_newstate() -> *mut lua_State {
+    let mut lua = Box::new(Lua::new(SafeOption::default()));
+    let l: *mut lua_State = lua.main_state();
+    let cs = Box::into_raw(Box::new(CState::new()));
+    unsafe {
+        (*cs).owner = Box::into_raw(lua);
+        (*l).set_extra_space(cs.cast());
+        // LUA_RIDX_MAINTHREAD and LUA_RIDX_GLOBALS
+        api(l, |l| {
+            l.lua_pushthread()?;
+            let main_thread = l.lua_l_ref(LUA_REGISTRYINDEX)?;
+            l.lua_pushglobaltable()?;
+            let globals = l.lua_l_ref(LUA_REGISTRYINDEX)?;
+            debug_assert_eq!((main_thread, globals), (1, 2));
+            Ok(())
+        });
+    }
+    l
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_openlibs(l: *mut lua_State) {
+    unsafe { api(l, |l| l.open_stdlib(Stdlib::All)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_checkversion_(l: *mut lua_State, ver: lua_Number, sz: usize) {
+    if sz != size_of::<lua_Integer>() * 16 + size_of::<lua_Number>() {
+        unsafe {
+            error(
+                l,
+                "core and library have incompatible numeric types".to_string(),
+            )
+        }
+    }
+    if ver != 505.0 {
+        unsafe {
+            error(
+                l,
+                format!("version mismatch: app. needs {ver}, Lua core provides 505"),
+            )
+        }
+    }
+}
+
+// ==================== Errors ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_where(l: *mut lua_State, lvl: c_int) {
+    let location = unsafe { (*l).get_info_by_level(lvl.max(0) as usize, "Sl") }
+        .and_then(|ar| match (ar.short_src, ar.currentline) {
+            (Some(src), Some(line)) if line > 0 => Some(format!("{src}:{line}: ")),
+            _ => None,
+        })
+        .unwrap_or_default();
+    unsafe { api(l, |l| l.lua_pushstring(&location)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_argerror(
+    l: *mut lua_State,
+    arg: c_int,
+    extramsg: *const c_char,
+) -> c_int {
+    let extramsg = String::from_utf8_lossy(unsafe { c_bytes(extramsg) }).into_owned();
+    let mut arg = arg;
+    let ar = unsafe { (*l).get_info_by_level(0, "n") };
+    let name = ar.as_ref().and_then(|ar| ar.name.clone());
+    let namewhat = ar.as_ref().and_then(|ar| ar.namewhat.clone());
+    if namewhat.as_deref() == Some("method") {
+        arg -= 1;
+        if arg == 0 {
+            let name = name.as_deref().unwrap_or("?");
+            unsafe { error(l, format!("calling '{name}' on bad self ({extramsg})")) }
+        }
+    }
+    let name = name
+        .or_else(|| unsafe { global_func_name(l) })
+        .unwrap_or_else(|| "?".to_string());
+    unsafe { error(l, format!("bad argument #{arg} to '{name}' ({extramsg})")) }
+}
+
+/// Search `package.loaded` for the running function, two levels deep,
+/// like `pushglobalfuncname`; `_G.print` is reported as `print`.
+unsafe fn global_func_name(l: *mut lua_State) -> Option<String> {
+    let func = {
+        let l = unsafe { &mut *l };
+        l.get_frame_func(l.call_depth().checked_sub(1)?)?
+    };
+    unsafe {
+        let top = lua_gettop(l);
+        api(l, |l| l.push_value(func));
+        lua_getfield(l, C_REGISTRYINDEX, c"_LOADED".as_ptr());
+        let name = if find_field(l, top + 1, 2) {
+            let name = c_bytes(lua_tolstring(l, -1, std::ptr::null_mut()));
+            let name = String::from_utf8_lossy(name);
+            Some(name.strip_prefix("_G.").unwrap_or(&name).to_string())
+        } else {
+            None
+        };
+        lua_settop(l, top);
+        name
+    }
+}
+
+/// Look for the value at `objidx` in the table on top of the stack; on
+/// success leaves its dotted name on top.
+unsafe fn find_field(l: *mut lua_State, objidx: c_int, level: c_int) -> bool {
+    unsafe {
+        if level == 0 || lua_type(l, -1) != LUA_TTABLE {
+            return false;
+        }
+        lua_pushnil(l);
+        while lua_next(l, -2) != 0 {
+            if lua_type(l, -2) == LUA_TSTRING {
+                if lua_rawequal(l, objidx, -1) != 0 {
+                    lua_settop(l, -2);
+                    return true;
+                }
+                if find_field(l, objidx, level - 1) {
+                    // stack: table, key, value, name
+                    lua_pushlstring(l, c".".as_ptr(), 1);
+                    lua_copy(l, -1, -3);
+                    lua_settop(l, -2);
+                    lua_concat(l, 3);
+                    return true;
+                }
+            }
+            lua_settop(l, -2);
+        }
+        false
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_typeerror(
+    l: *mut lua_State,
+    arg: c_int,
+    tname: *const c_char,
+) -> c_int {
+    unsafe {
+        let actual = if luaL_getmetafield(l, arg, c"__name".as_ptr()) == LUA_TSTRING {
+            let name = c_bytes(lua_tolstring(l, -1, std::ptr::null_mut())).to_vec();
+            lua_settop(l, -2);
+            String::from_utf8_lossy(&name).into_owned()
+        } else if lua_type(l, arg) == LUA_TLIGHTUSERDATA {
+            "light userdata".to_string()
+        } else {
+            let name = c_bytes(lua_typename(l, lua_type(l, arg)));
+            String::from_utf8_lossy(name).into_owned()
+        };
+        let tname = String::from_utf8_lossy(c_bytes(tname));
+        let msg = format!("{tname} expected, got {actual}\0");
+        luaL_argerror(l, arg, msg.as_ptr().cast())
+    }
+}
+
+unsafe fn tag_error(l: *mut lua_State, arg: c_int, tag: c_int) -> ! {
+    unsafe {
+        luaL_typeerror(l, arg, lua_typename(l, tag));
+    }
+    unreachable!("luaL_typeerror raises")
+}
+
+// ==================== Argument checks ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_checklstring(
+    l: *mut lua_State,
+    arg: c_int,
+    len: *mut usize,
+) -> *const c_char {
+    let s = unsafe { lua_tolstring(l, arg, len) };
+    if s.is_null() {
+        unsafe { tag_error(l, arg, LUA_TSTRING) }
+    }
+    s
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_optlstring(
+    l: *mut lua_State,
+    arg: c_int,
+    def: *const c_char,
+    len: *mut usize,
+) -> *const c_char {
+    unsafe {
+        if lua_type(l, arg) <= LUA_TNIL {
+            if !len.is_null() {
+                *len = c_bytes(def).len();
+            }
+            return def;
+        }
+        luaL_checklstring(l, arg, len)
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_checknumber(l: *mut lua_State, arg: c_int) -> lua_Number {
+    let mut isnum = 0;
+    let n = unsafe { lua_tonumberx(l, arg, &mut isnum) };
+    if isnum == 0 {
+        unsafe { tag_error(l, arg, LUA_TNUMBER) }
+    }
+    n
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_optnumber(
+    l: *mut lua_State,
+    arg: c_int,
+    def: lua_Number,
+) -> lua_Number {
+    unsafe {
+        if lua_type(l, arg) <= LUA_TNIL {
+            def
+        } else {
+            luaL_checknumber(l, arg)
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_checkinteger(l: *mut lua_State, arg: c_int) -> lua_Integer {
+    let mut isnum = 0;
+    let n = unsafe { lua_tointegerx(l, arg, &mut isnum) };
+    if isnum == 0 {
+        unsafe {
+            if lua_isnumber(l, arg) != 0 {
+                luaL_argerror(l, arg, c"number has no integer representation".as_ptr());
+            }
+            tag_error(l, arg, LUA_TNUMBER)
+        }
+    }
+    n
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_optinteger(
+    l: *mut lua_State,
+    arg: c_int,
+    def: lua_Integer,
+) -> lua_Integer {
+    unsafe {
+        if lua_type(l, arg) <= LUA_TNIL {
+            def
+        } else {
+            luaL_checkinteger(l, arg)
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_checkstack(l: *mut lua_State, sz: c_int, msg: *const c_char) {
+    let msg = if msg.is_null() {
+        "no message".to_string()
+    } else {
+        String::from_utf8_lossy(unsafe { c_bytes(msg) }).into_owned()
+    };
+    unsafe { api(l, |l| l.check_stack_msg(sz.max(0) as usize, &msg)) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_checktype(l: *mut lua_State, arg: c_int, t: c_int) {
+    if unsafe { lua_type(l, arg) } != t {
+        unsafe { tag_error(l, arg, t) }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_checkany(l: *mut lua_State, arg: c_int) {
+    if unsafe { lua_type(l, arg) } == LUA_TNONE {
+        unsafe { luaL_argerror(l, arg, c"value expected".as_ptr()) };
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_checkoption(
+    l: *mut lua_State,
+    arg: c_int,
+    def: *const c_char,
+    lst: *const *const c_char,
+) -> c_int {
+    unsafe {
+        let name = if def.is_null() {
+            luaL_checklstring(l, arg, std::ptr::null_mut())
+        } else {
+            luaL_optlstring(l, arg, def, std::ptr::null_mut())
+        };
+        let name = c_bytes(name);
+        let mut i = 0;
+        while !(*lst.add(i)).is_null() {
+            if c_bytes(*lst.add(i)) == name {
+                return i as c_int;
+            }
+            i += 1;
+        }
+        let msg = format!("invalid option '{}'\0", String::from_utf8_lossy(name));
+        luaL_argerror(l, arg, msg.as_ptr().cast())
+    }
+}
+
+// ==================== Metatables and userdata ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_getmetafield(
+    l: *mut lua_State,
+    obj: c_int,
+    e: *const c_char,
+) -> c_int {
+    unsafe {
+        if lua_getmetatable(l, obj) == 0 {
+            return LUA_TNIL;
+        }
+        lua_pushstring(l, e);
+        let tt = lua_rawget(l, -2);
+        if tt == LUA_TNIL {
+            lua_settop(l, -3);
+        } else {
+            lua_rotate(l, -2, -1);
+            lua_settop(l, -2);
+        }
+        tt
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_newmetatable(
+    l: *mut lua_State,
+    tname: *const c_char,
+) -> c_int {
+    unsafe {
+        if lua_getfield(l, C_REGISTRYINDEX, tname) != LUA_TNIL {
+            return 0;
+        }
+        lua_settop(l, -2);
+        lua_createtable(l, 0, 2);
+        lua_pushstring(l, tname);
+        lua_setfield(l, -2, c"__name".as_ptr());
+        lua_pushvalue(l, -1);
+        lua_setfield(l, C_REGISTRYINDEX, tname);
+        1
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_setmetatable(l: *mut lua_State, tname: *const c_char) {
+    unsafe {
+        lua_getfield(l, C_REGISTRYINDEX, tname);
+        lua_setmetatable(l, -2);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_testudata(
+    l: *mut lua_State,
+    ud: c_int,
+    tname: *const c_char,
+) -> *mut c_void {
+    unsafe {
+        let p = lua_touserdata(l, ud);
+        if p.is_null() || lua_getmetatable(l, ud) == 0 {
+            return std::ptr::null_mut();
+        }
+        lua_getfield(l, C_REGISTRYINDEX, tname);
+        let same = lua_rawequal(l, -1, -2) != 0;
+        lua_settop(l, -3);
+        if same { p } else { std::ptr::null_mut() }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_checkudata(
+    l: *mut lua_State,
+    ud: c_int,
+    tname: *const c_char,
+) -> *mut c_void {
+    let p = unsafe { luaL_testudata(l, ud, tname) };
+    if p.is_null() {
+        unsafe { luaL_typeerror(l, ud, tname) };
+    }
+    p
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_tolstring(
+    l: *mut lua_State,
+    idx: c_int,
+    len: *mut usize,
+) -> *const c_char {
+    unsafe {
+        let idx = lua_absindex(l, idx);
+        if luaL_getmetafield(l, idx, c"__tostring".as_ptr()) != LUA_TNIL {
+            lua_pushvalue(l, idx);
+            lua_callk(l, 1, 1, 0, None);
+            if lua_isstring(l, -1) == 0 {
+                error(l, "'__tostring' must return a string".to_string());
+            }
+        } else {
+            api(l, |l| {
+                let value = value_at(l, index(idx)).unwrap_or_default();
+                let text = l.tostring_value(&value)?;
+                l.push_value(text)
+            });
+        }
+        lua_tolstring(l, -1, len)
+    }
+}
+
+// ==================== References ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_ref(l: *mut lua_State, t: c_int) -> c_int {
+    unsafe { api(l, |l| l.lua_l_ref(index(t))) as c_int }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_unref(l: *mut lua_State, t: c_int, reference: c_int) {
+    unsafe { api(l, |l| l.lua_l_unref(index(t), reference as _)) }
+}
+
+// ==================== Loading ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_loadfilex(
+    l: *mut lua_State,
+    filename: *const c_char,
+    mode: *const c_char,
+) -> c_int {
+    use std::io::Read;
+
+    let (name, contents) = if filename.is_null() {
+        let mut contents = Vec::new();
+        let read = std::io::stdin().read_to_end(&mut contents);
+        ("stdin".to_string(), read.map(|_| contents))
+    } else {
+        let name = String::from_utf8_lossy(unsafe { c_bytes(filename) }).into_owned();
+        let read = std::fs::read(&name);
+        (name, read)
+    };
+    let bytes = match contents {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let verb = if filename.is_null() { "read" } else { "open" };
+            let msg = format!("cannot {verb} {name}: {e}");
+            unsafe { api(l, |l| l.lua_pushstring(&msg)) };
+            return LUA_ERRFILE;
+        }
+    };
+    let mode = if mode.is_null() {
+        &b"bt"[..]
+    } else {
+        unsafe { c_bytes(mode) }
+    };
+    let chunkname = if filename.is_null() {
+        "=stdin".to_string()
+    } else {
+        format!("@{name}")
+    };
+
+    // Skip a '#' first line like the reference loader; the VM's file
+    // loader also drops a UTF-8 BOM
+    let l = unsafe { &mut *l };
+    let binary = bytes.first() == Some(&0x1b);
+    if binary && !mode.contains(&b'b') || !binary && !mode.contains(&b't') {
+        return load_buffer(l, &bytes, &chunkname, mode);
+    }
+    match l.load_file_bytes(&bytes, &chunkname) {
+        Ok(function) => {
+            unsafe { api(l, |l| l.push_value(function)) };
+            LUA_OK
+        }
+        Err(e) => {
+            let msg = l.get_error_msg(e);
+            unsafe { api(l, |l| l.lua_pushstring(&msg)) };
+            LUA_ERRSYNTAX
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_loadbufferx(
+    l: *mut lua_State,
+    buff: *const c_char,
+    sz: usize,
+    name: *const c_char,
+    mode: *const c_char,
+) -> c_int {
+    let bytes = if sz == 0 {
+        &[][..]
+    } else {
+        unsafe { std::slice::from_raw_parts(buff.cast::<u8>(), sz) }
+    };
+    let name = String::from_utf8_lossy(unsafe { c_bytes(name) }).into_owned();
+    let mode = if mode.is_null() {
+        &b"bt"[..]
+    } else {
+        unsafe { c_bytes(mode) }
+    };
+    load_buffer(unsafe { &mut *l }, bytes, &name, mode)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_loadstring(l: *mut lua_State, s: *const c_char) -> c_int {
+    unsafe {
+        let len = c_bytes(s).len();
+        luaL_loadbufferx(l, s, len, s, std::ptr::null())
+    }
+}
+
+// ==================== Miscellaneous ====================
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_len(l: *mut lua_State, idx: c_int) -> lua_Integer {
+    unsafe {
+        lua_len(l, idx);
+        let mut isnum = 0;
+        let len = lua_tointegerx(l, -1, &mut isnum);
+        if isnum == 0 {
+            error(l, "object length is not an integer".to_string());
+        }
+        lua_settop(l, -2);
+        len
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_setfuncs(
+    l: *mut lua_State,
+    mut reg: *const luaL_Reg,
+    nup: c_int,
+) {
+    unsafe {
+        luaL_checkstack(l, nup, c"too many upvalues".as_ptr());
+        while !(*reg).name.is_null() {
+            match (*reg).func {
+                None => lua_pushboolean(l, 0),
+                Some(func) => {
+                    for _ in 0..nup {
+                        lua_pushvalue(l, -nup);
+                    }
+                    lua_pushcclosure(l, Some(func), nup);
+                }
+            }
+            lua_setfield(l, -(nup + 2), (*reg).name);
+            reg = reg.add(1);
+        }
+        lua_settop(l, -nup - 1);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C-unwind" fn luaL_getsubtable(
+    l: *mut lua_State,
+    idx: c_int,
+    fname: *const c_char,
+) -> c_int {
+    unsafe {
+        if lua_getfield(l, idx, fname) == LUA_TTABLE {
+            return 1;
+        }
+        lua_settop(l, -2);
+        let idx = lua_absindex(l, idx);
+        lua_createtable(l, 0, 0);
+        lua_pushvalue(l, -1);
+        lua_setfield(l, idx, fname);
+        0
+    }
+}
@@ -0,0 +1,55 @@
+//! luars-capi — the Lua C API over the luars runtime.
+//!
+//! Builds `libluars_capi` (shared and static) exporting the core subset of
+//! the Lua 5.5 C API, so a host written against `lua.h` can embed luars
+//! without rewriting its binding layer. Compile against the headers in
+//! `include/` and link the library in place of `liblua`.
+//!
+//! ```c
+//! lua_State *L = luaL_newstate();
+//! luaL_openlibs(L);
+//! lua_register(L, "add", add);
+//! if (luaL_dostring(L, "return add(1, 2)") != LUA_OK)
+//!   fprintf(stderr, "%s\n", lua_tostring(L, -1));
+//! lua_close(L);
+//! ```
+//!
+//! `lua_State *` is a [`LuaState`](luars::LuaState); C functions are
+//! wrapped in closures that call them with the usual stack protocol.
+//! Differences from the reference implementation:
+//!
+//! - Errors raised in C (`lua_error`, `luaL_error`, failed checks) unwind
+//!   to the C function's caller instead of `longjmp`ing, so C code calling
+//!   the API must be compiled with unwind tables (`-fexceptions`). Outside
+//!   any C function an error calls the `lua_atpanic` handler and aborts.
+//! - `lua_tolstring` returns a NUL-terminated copy that stays valid while
+//!   the calling C function runs (on the host, while the string stays on
+//!   the stack).
+//! - Coroutines (`lua_newthread`, `lua_resume`, `lua_yield`), continuations,
+//!   user values, `luaL_Buffer` and the debug interface are not provided;
+//!   `lua_newthread` raises an error.
+//! - The variadic functions (`lua_pushfstring`, `luaL_error`, `lua_gc`)
+//!   are written in C and exported through jump stubs, available on x86,
+//!   ARM and RISC-V targets.
+
+#![allow(non_camel_case_types, non_snake_case, clippy::missing_safety_doc)]
+
+mod lapi;
+mod lauxlib;
+mod state;
+#[cfg(any(
+    target_arch = "x86_64",
+    target_arch = "x86",
+    target_arch = "aarch64",
+    target_arch = "arm",
+    target_arch = "riscv64",
+    target_arch = "riscv32"
+))]
+mod vararg;
+
+pub use lapi::*;
+pub use lauxlib::*;
+pub use state::{
+    lua_CFunction, lua_Integer, lua_KContext, lua_KFunction, lua_Number, lua_Reader, lua_State,
+    lua_Unsigned,
+};
@@ -0,0 +1,280 @@
+use std::any::Any;
+use std::ffi::{c_char, c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+
+use luars::{
+    LUA_REGISTRYINDEX, Lua, LuaApi, LuaError, LuaResult, LuaStackApi, LuaState, LuaValue,
+    UserDataTrait, lua_upvalueindex,
+};
+
+/// `lua_State *` in C is any thread of the VM.
+pub type lua_State = LuaState;
+pub type lua_Number = f64;
+pub type lua_Integer = i64;
+pub type lua_Unsigned = u64;
+pub type lua_KContext = isize;
+pub type lua_CFunction = Option<CFunction>;
+pub type lua_KFunction =
+    Option<unsafe extern "C-unwind" fn(*mut lua_State, c_int, lua_KContext) -> c_int>;
+pub type lua_Reader =
+    Option<unsafe extern "C-unwind" fn(*mut lua_State, *mut c_void, *mut usize) -> *const c_char>;
+
+pub(crate) type CFunction = unsafe extern "C-unwind" fn(*mut lua_State) -> c_int;
+
+pub(crate) const LUA_OK: c_int = 0;
+pub(crate) const LUA_ERRRUN: c_int = 2;
+pub(crate) const LUA_ERRSYNTAX: c_int = 3;
+pub(crate) const LUA_ERRFILE: c_int = 6;
+
+pub(crate) const LUA_TNONE: c_int = -1;
+pub(crate) const LUA_TNIL: c_int = 0;
+pub(crate) const LUA_TBOOLEAN: c_int = 1;
+pub(crate) const LUA_TLIGHTUSERDATA: c_int = 2;
+pub(crate) const LUA_TNUMBER: c_int = 3;
+pub(crate) const LUA_TSTRING: c_int = 4;
+pub(crate) const LUA_TTABLE: c_int = 5;
+pub(crate) const LUA_TFUNCTION: c_int = 6;
+pub(crate) const LUA_TUSERDATA: c_int = 7;
+pub(crate) const LUA_TTHREAD: c_int = 8;
+
+/// C `LUA_REGISTRYINDEX`; the VM uses the same value.
+pub(crate) const C_REGISTRYINDEX: c_int = LUA_REGISTRYINDEX as c_int;
+
+/// Cached strings per level before the ones no longer on the stack are
+/// dropped.
+const STRING_CACHE_MIN: usize = 64;
+
+/// Per-VM data of the shim, kept in the VM's extra space so every thread
+/// of the state reaches it.
+pub(crate) struct CState {
+    /// The runtime created by `luaL_newstate`, freed by `lua_close`.
+    pub(crate) owner: *mut Lua,
+    /// Number of C functions currently running.
+    depth: usize,
+    pub(crate) panic: lua_CFunction,
+    pub(crate) gc_stopped: bool,
+    /// NUL-terminated copies handed out by `lua_tolstring`.
+    strings: Vec<CString>,
+    prune_at: usize,
+}
+
+/// Lua strings are not NUL-terminated, so C gets a copy that lives as
+/// long as the C function that asked for it.
+struct CString {
+    key: *const u8,
+    depth: usize,
+    text: Box<[u8]>,
+}
+
+impl CState {
+    pub(crate) fn new() -> Self {
+        CState {
+            owner: std::ptr::null_mut(),
+            depth: 0,
+            panic: None,
+            gc_stopped: false,
+            strings: Vec::new(),
+            prune_at: STRING_CACHE_MIN,
+        }
+    }
+
+    /// NUL-terminated text of the string `value`, which is on `l`'s stack.
+    pub(crate) fn c_string(&mut self, l: &LuaState, value: LuaValue) -> *const c_char {
+        let bytes = value.as_bytes().unwrap_or_default();
+        let key = value.raw_ptr_repr();
+        // Compare the text too: a collected string's address can be reused
+        if let Some(cached) = self
+            .strings
+            .iter()
+            .rev()
+            .find(|s| s.key == key && s.text[..s.text.len() - 1] == *bytes)
+        {
+            return cached.text.as_ptr().cast();
+        }
+
+        if self.strings.len() >= self.prune_at {
+            self.prune(l);
+        }
+        let mut text = Vec::with_capacity(bytes.len() + 1);
+        text.extend_from_slice(bytes);
+        text.push(0);
+        let text = text.into_boxed_slice();
+        let ptr = text.as_ptr().cast();
+        self.strings.push(CString {
+            key,
+            depth: self.depth,
+            text,
+        });
+        ptr
+    }
+
+    /// Drop this level's copies whose string has left the stack.
+    fn prune(&mut self, l: &LuaState) {
+        let depth = self.depth;
+        let top = l.get_top();
+        self.strings.retain(|s| {
+            s.depth != depth
+                || (0..top).any(|i| {
+                    l.stack_get(i)
+                        .is_some_and(|v| v.is_string() && v.raw_ptr_repr() == s.key)
+                })
+        });
+        self.prune_at = (self.strings.len() * 2).max(STRING_CACHE_MIN);
+    }
+}
+
+pub(crate) fn cstate(l: &LuaState) -> *mut CState {
+    l.extra_space().cast()
+}
+
+/// Error raised by the C API, carried by unwinding to the bridge of the
+/// running C function.
+struct Raise(LuaError);
+
+/// Raise `error` like `lua_error`: unwind to the C function bridge, or
+/// call the panic function and abort outside any C function.
+pub(crate) fn raise(l: &mut LuaState, error: LuaError) -> ! {
+    let cs = cstate(l);
+    if unsafe { (*cs).depth } == 0 {
+        unprotected(l, error);
+    }
+    panic::resume_unwind(Box::new(Raise(error)))
+}
+
+#[cold]
+fn unprotected(l: &mut LuaState, error: LuaError) -> ! {
+    let msg = l.get_error_msg(error);
+    if let Some(panicf) = unsafe { (*cstate(l)).panic } {
+        let _ = l.lua_pushstring(&msg);
+        unsafe { panicf(l) };
+    }
+    eprintln!("PANIC: unprotected error in call to Lua API ({msg})");
+    std::process::abort()
+}
+
+/// Run a stack operation for a C caller, raising its error.
+pub(crate) unsafe fn api<T>(l: *mut lua_State, f: impl FnOnce(&mut LuaState) -> LuaResult<T>) -> T {
+    let l = unsafe { &mut *l };
+    match f(l) {
+        Ok(value) => value,
+        Err(error) => raise(l, error),
+    }
+}
+
+/// Raise `msg` with the position of the calling Lua code, as luaL_error.
+pub(crate) unsafe fn error(l: *mut lua_State, msg: String) -> ! {
+    let l = unsafe { &mut *l };
+    let error = l.error(msg);
+    raise(l, error)
+}
+
+/// Push `f` as a closure over the `n` values on top of the stack.
+pub(crate) fn push_cfunction(l: &mut LuaState, f: CFunction, n: usize) -> LuaResult<()> {
+    l.lua_pushrclosure(move |l| call_cfunction(l, f), n)
+}
+
+fn call_cfunction(l: &mut LuaState, f: CFunction) -> LuaResult<usize> {
+    let cs = cstate(l);
+    let depth = unsafe {
+        (*cs).depth += 1;
+        (*cs).depth
+    };
+    let ptr: *mut LuaState = l;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe { f(ptr) }));
+    unsafe {
+        (*cs).depth -= 1;
+        (*cs).strings.retain(|s| s.depth < depth);
+    }
+    match result {
+        Ok(n) if n >= 0 => Ok(n as usize),
+        Ok(_) => Err(l.error("C function returned a negative result count".to_string())),
+        Err(payload) => Err(take_raise(payload)),
+    }
+}
+
+fn take_raise(payload: Box<dyn Any + Send>) -> LuaError {
+    match payload.downcast::<Raise>() {
+        Ok(raise) => raise.0,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// Stack index of the VM for C index `idx`: the VM numbers upvalue
+/// pseudo-indices from its own globals pseudo-index.
+pub(crate) fn index(idx: c_int) -> isize {
+    if idx < C_REGISTRYINDEX {
+        lua_upvalueindex((C_REGISTRYINDEX - idx) as usize)
+    } else {
+        idx as isize
+    }
+}
+
+/// The value at `idx`, `None` for an unacceptable index.
+pub(crate) fn value_at(l: &mut LuaState, idx: isize) -> Option<LuaValue> {
+    if l.lua_isnone(idx) {
+        return None;
+    }
+    l.lua_pushvalue(idx).ok()?;
+    let top = l.get_top() - 1;
+    let value = l.stack_get(top);
+    l.set_top_raw(top);
+    value
+}
+
+/// Type code of the value at `idx` (`LUA_TNONE` for none).
+pub(crate) fn type_code(l: &mut LuaState, idx: isize) -> c_int {
+    match value_at(l, idx) {
+        None => LUA_TNONE,
+        Some(v) if v.is_nil() => LUA_TNIL,
+        Some(v) if v.is_boolean() => LUA_TBOOLEAN,
+        Some(v) if v.as_lightuserdata().is_some() => LUA_TLIGHTUSERDATA,
+        Some(v) if v.is_number() => LUA_TNUMBER,
+        Some(v) if v.is_string() => LUA_TSTRING,
+        Some(v) if v.is_table() => LUA_TTABLE,
+        Some(v) if v.is_function() => LUA_TFUNCTION,
+        Some(v) if v.is_userdata() => LUA_TUSERDATA,
+        Some(_) => LUA_TTHREAD,
+    }
+}
+
+/// Bytes of a C string; a null pointer reads as empty.
+pub(crate) unsafe fn c_bytes<'a>(s: *const c_char) -> &'a [u8] {
+    if s.is_null() {
+        return &[];
+    }
+    unsafe { std::ffi::CStr::from_ptr(s) }.to_bytes()
+}
+
+/// Memory block of a userdata created by `lua_newuserdatauv`, aligned like
+/// `LUAI_MAXALIGN`.
+pub(crate) struct CBlock {
+    pub(crate) size: usize,
+    pub(crate) data: Box<[u128]>,
+}
+
+impl CBlock {
+    pub(crate) fn new(size: usize) -> Self {
+        CBlock {
+            size,
+            data: vec![0; size.div_ceil(size_of::<u128>())].into_boxed_slice(),
+        }
+    }
+}
+
+impl UserDataTrait for CBlock {
+    fn type_name(&self) -> &'static str {
+        "userdata"
+    }
+
+    fn size_hint(&self) -> usize {
+        size_of::<Self>() + self.size
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
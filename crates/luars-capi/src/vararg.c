@@ -0,0 +1,131 @@
+/*
+** Variadic functions of the C API. Rust cannot define C-variadic
+** functions, so their bodies live here, built on the non-variadic API.
+** The public names are exported by src/vararg.rs, which jumps here with
+** the caller's arguments untouched.
+*/
+
+#include <stdio.h>
+#include <string.h>
+
+#include "lua.h"
+#include "lauxlib.h"
+
+/* implemented in src/lapi.rs */
+extern int luars_capi_gc_option (lua_State *L, int what, int arg);
+
+int luars_capi_error (lua_State *L, const char *fmt, ...);
+
+/* maximum length of a single UTF-8 sequence produced by '%U' */
+#define UTF8BUFFSZ	8
+
+static int utf8esc (char *buff, unsigned long x) {
+  int n = 1;  /* number of bytes put in buffer (backwards) */
+  if (x < 0x80)  /* ascii? */
+    buff[UTF8BUFFSZ - 1] = (char)x;
+  else {  /* need continuation bytes */
+    unsigned int mfb = 0x3f;  /* maximum that fits in first byte */
+    do {  /* add continuation bytes */
+      buff[UTF8BUFFSZ - (n++)] = (char)(0x80 | (x & 0x3f));
+      x >>= 6;  /* remove added bits */
+      mfb >>= 1;  /* now there is one less bit available in first byte */
+    } while (x > mfb);  /* still needs continuation byte? */
+    buff[UTF8BUFFSZ - n] = (char)((~mfb << 1) | x);  /* add first byte */
+  }
+  return n;
+}
+
+/*
+** Push each piece of the formatted message and join them with
+** lua_concat, which converts numbers the same way Lua code does.
+** Supports '%%', '%s', '%c', '%d', '%I', '%f', '%p' and '%U'.
+*/
+const char *luars_capi_pushvfstring (lua_State *L, const char *fmt,
+                                      va_list argp) {
+  int n = 0;
+  const char *e;
+  while ((e = strchr(fmt, '%')) != NULL) {
+    lua_pushlstring(L, fmt, (size_t)(e - fmt));
+    n++;
+    switch (*(e + 1)) {
+      case 's': {
+        const char *s = va_arg(argp, char *);
+        if (s == NULL) s = "(null)";
+        lua_pushstring(L, s);
+        break;
+      }
+      case 'c': {
+        char c = (char)va_arg(argp, int);
+        lua_pushlstring(L, &c, 1);
+        break;
+      }
+      case 'd': {
+        lua_pushinteger(L, va_arg(argp, int));
+        break;
+      }
+      case 'I': {
+        lua_pushinteger(L, (lua_Integer)va_arg(argp, lua_Integer));
+        break;
+      }
+      case 'f': {
+        lua_pushnumber(L, (lua_Number)va_arg(argp, double));
+        break;
+      }
+      case 'p': {
+        char buff[3 * sizeof(void *) + 8];
+        void *p = va_arg(argp, void *);
+        int len = snprintf(buff, sizeof(buff), "%p", p);
+        lua_pushlstring(L, buff, (size_t)len);
+        break;
+      }
+      case 'U': {
+        char buff[UTF8BUFFSZ];
+        int len = utf8esc(buff, (unsigned long)va_arg(argp, long));
+        lua_pushlstring(L, buff + UTF8BUFFSZ - len, (size_t)len);
+        break;
+      }
+      case '%': {
+        lua_pushlstring(L, "%", 1);
+        break;
+      }
+      default: {
+        return luars_capi_error(L, "invalid option '%%%c' to 'lua_pushfstring'",
+                             *(e + 1)), (const char *)NULL;
+      }
+    }
+    n++;
+    fmt = e + 2;
+  }
+  lua_pushstring(L, fmt);
+  lua_concat(L, n + 1);
+  return lua_tolstring(L, -1, NULL);
+}
+
+const char *luars_capi_pushfstring (lua_State *L, const char *fmt, ...) {
+  const char *ret;
+  va_list argp;
+  va_start(argp, fmt);
+  ret = luars_capi_pushvfstring(L, fmt, argp);
+  va_end(argp);
+  return ret;
+}
+
+int luars_capi_error (lua_State *L, const char *fmt, ...) {
+  va_list argp;
+  va_start(argp, fmt);
+  luaL_where(L, 1);
+  luars_capi_pushvfstring(L, fmt, argp);
+  va_end(argp);
+  lua_concat(L, 2);
+  return lua_error(L);
+}
+
+int luars_capi_gc (lua_State *L, int what, ...) {
+  va_list argp;
+  int arg = 0;
+  va_start(argp, what);
+  if (what == LUA_GCSTEP)
+    arg = va_arg(argp, int);
+  va_end(argp);
+  return luars_capi_gc_option(L, what, arg);
+}
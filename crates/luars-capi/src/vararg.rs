@@ -0,0 +1,53 @@
+//! Public names of the variadic functions defined in vararg.c.
+//!
+//! A shared library built by rustc only exports Rust symbols, so each of
+//! these is a Rust entry point that jumps to its C body. The jump leaves
+//! registers and stack exactly as the caller set them up, which is all a
+//! variadic (or `va_list`) callee needs.
+
+use std::arch::naked_asm;
+
+unsafe extern "C" {
+    fn luars_capi_pushvfstring();
+    fn luars_capi_pushfstring();
+    fn luars_capi_error();
+    fn luars_capi_gc();
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+macro_rules! jump {
+    ($target:ident) => {
+        naked_asm!("jmp {}", sym $target)
+    };
+}
+
+#[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
+macro_rules! jump {
+    ($target:ident) => {
+        naked_asm!("b {}", sym $target)
+    };
+}
+
+#[cfg(any(target_arch = "riscv64", target_arch = "riscv32"))]
+macro_rules! jump {
+    ($target:ident) => {
+        naked_asm!("tail {}", sym $target)
+    };
+}
+
+macro_rules! forward {
+    ($($name:ident => $target:ident),* $(,)?) => {$(
+        #[unsafe(naked)]
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn $name() {
+            jump!($target)
+        }
+    )*};
+}
+
+forward! {
+    lua_pushvfstring => luars_capi_pushvfstring,
+    lua_pushfstring => luars_capi_pushfstring,
+    luaL_error => luars_capi_error,
+    lua_gc => luars_capi_gc,
+}
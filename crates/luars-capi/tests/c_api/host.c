@@ -0,0 +1,464 @@
+/*
+** Host program for the C API acceptance test: embeds luars through
+** lua.h the way a hand-written binding layer does.
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "lua.h"
+#include "lauxlib.h"
+#include "lualib.h"
+
+static int checks = 0;
+
+#define CHECK(cond) do { \
+    checks++; \
+    if (!(cond)) { \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+      exit(1); \
+    } \
+  } while (0)
+
+#define CHECK_STR(L, idx, expected) do { \
+    const char *s_ = lua_tostring(L, idx); \
+    checks++; \
+    if (s_ == NULL || strcmp(s_, expected) != 0) { \
+      fprintf(stderr, "%s:%d: expected \"%s\", got \"%s\"\n", __FILE__, \
+              __LINE__, expected, s_ ? s_ : "(null)"); \
+      exit(1); \
+    } \
+  } while (0)
+
+static void run (lua_State *L, const char *code) {
+  if (luaL_dostring(L, code) != LUA_OK) {
+    fprintf(stderr, "error running [%s]: %s\n", code, lua_tostring(L, -1));
+    exit(1);
+  }
+  lua_settop(L, 0);
+}
+
+/* ---------- plain functions ---------- */
+
+static int add (lua_State *L) {
+  lua_Integer a = luaL_checkinteger(L, 1);
+  lua_Integer b = luaL_optinteger(L, 2, 10);
+  lua_pushinteger(L, a + b);
+  return 1;
+}
+
+static int fail (lua_State *L) {
+  return luaL_error(L, "failed with %d and %s", 42, luaL_checkstring(L, 1));
+}
+
+static int throw_table (lua_State *L) {
+  lua_createtable(L, 0, 1);
+  lua_pushliteral(L, "boom");
+  lua_setfield(L, -2, "code");
+  return lua_error(L);
+}
+
+/* calls its argument from C, so errors cross a Lua -> C -> Lua boundary */
+static int call_back (lua_State *L) {
+  luaL_checktype(L, 1, LUA_TFUNCTION);
+  lua_pushvalue(L, 1);
+  lua_pushinteger(L, 5);
+  lua_call(L, 1, 1);
+  lua_pushinteger(L, 1);
+  return 1;
+}
+
+static int counter (lua_State *L) {
+  lua_Integer n = lua_tointeger(L, lua_upvalueindex(1)) + 1;
+  lua_pushinteger(L, n);
+  lua_copy(L, -1, lua_upvalueindex(1));
+  return 1;
+}
+
+static int concat_all (lua_State *L) {
+  lua_concat(L, lua_gettop(L));
+  return 1;
+}
+
+static int pick (lua_State *L) {
+  static const char *const modes[] = {"read", "write", NULL};
+  lua_pushinteger(L, luaL_checkoption(L, 1, "read", modes));
+  return 1;
+}
+
+/* ---------- userdata ---------- */
+
+typedef struct Point { double x, y; } Point;
+
+static int collected = 0;
+
+static int point_new (lua_State *L) {
+  Point *p = (Point *)lua_newuserdatauv(L, sizeof(Point), 0);
+  p->x = luaL_checknumber(L, 1);
+  p->y = luaL_checknumber(L, 2);
+  luaL_setmetatable(L, "Point");
+  return 1;
+}
+
+static int point_len (lua_State *L) {
+  Point *p = (Point *)luaL_checkudata(L, 1, "Point");
+  lua_pushnumber(L, p->x * p->x + p->y * p->y);
+  return 1;
+}
+
+static int point_tostring (lua_State *L) {
+  Point *p = (Point *)luaL_checkudata(L, 1, "Point");
+  lua_pushfstring(L, "Point(%f, %f)", p->x, p->y);
+  return 1;
+}
+
+static int point_gc (lua_State *L) {
+  (void)luaL_checkudata(L, 1, "Point");
+  collected++;
+  return 0;
+}
+
+static const luaL_Reg point_methods[] = {
+  {"len2", point_len},
+  {NULL, NULL}
+};
+
+static const luaL_Reg point_meta[] = {
+  {"__tostring", point_tostring},
+  {"__gc", point_gc},
+  {NULL, NULL}
+};
+
+static int open_point (lua_State *L) {
+  static const luaL_Reg lib[] = {
+    {"new", point_new},
+    {NULL, NULL}
+  };
+  luaL_newmetatable(L, "Point");
+  luaL_setfuncs(L, point_meta, 0);
+  luaL_newlib(L, point_methods);
+  lua_setfield(L, -2, "__index");
+  lua_pop(L, 1);
+  luaL_newlib(L, lib);
+  return 1;
+}
+
+static int handler (lua_State *L) {
+  lua_pushfstring(L, "handled: %s", lua_tostring(L, 1));
+  return 1;
+}
+
+static int on_panic (lua_State *L) {
+  printf("panic: %s\n", lua_tostring(L, -1));
+  fflush(stdout);
+  exit(3);
+}
+
+static const char *reader (lua_State *L, void *ud, size_t *size) {
+  const char **pieces = (const char **)ud;
+  const char *piece = *pieces;
+  (void)L;
+  if (piece == NULL) return NULL;
+  *pieces = NULL;
+  *size = strlen(piece);
+  return piece;
+}
+
+static void test_stack (lua_State *L) {
+  size_t len;
+  int isnum;
+
+  CHECK(lua_gettop(L) == 0);
+  lua_pushnil(L);
+  lua_pushboolean(L, 1);
+  lua_pushinteger(L, 7);
+  lua_pushnumber(L, 2.5);
+  lua_pushlstring(L, "a\0b", 3);
+  lua_pushstring(L, "12");
+  lua_pushlightuserdata(L, &checks);
+  CHECK(lua_gettop(L) == 7);
+
+  CHECK(lua_type(L, 1) == LUA_TNIL);
+  CHECK(lua_type(L, 2) == LUA_TBOOLEAN);
+  CHECK(lua_type(L, 3) == LUA_TNUMBER);
+  CHECK(lua_type(L, 5) == LUA_TSTRING);
+  CHECK(lua_type(L, 7) == LUA_TLIGHTUSERDATA);
+  CHECK(lua_type(L, 8) == LUA_TNONE);
+  CHECK(strcmp(luaL_typename(L, 4), "number") == 0);
+  CHECK(lua_isinteger(L, 3) && !lua_isinteger(L, 4));
+  CHECK(lua_isnumber(L, 6) && lua_isstring(L, 3));
+
+  CHECK(lua_toboolean(L, 2) && !lua_toboolean(L, 1));
+  CHECK(lua_tointegerx(L, 3, &isnum) == 7 && isnum);
+  CHECK(lua_tonumber(L, 4) == 2.5);
+  CHECK(lua_tointeger(L, 6) == 12);
+  lua_tointegerx(L, 4, &isnum);
+  CHECK(!isnum);
+  CHECK(lua_touserdata(L, 7) == &checks);
+
+  CHECK(memcmp(lua_tolstring(L, 5, &len), "a\0b", 4) == 0 && len == 3);
+  CHECK(lua_rawlen(L, 5) == 3);
+  /* numbers become strings in place */
+  CHECK_STR(L, 3, "7");
+  CHECK(lua_type(L, 3) == LUA_TSTRING);
+  CHECK_STR(L, 4, "2.5");
+
+  lua_pushvalue(L, 2);
+  CHECK(lua_rawequal(L, -1, 2));
+  lua_remove(L, 1);
+  CHECK(lua_type(L, 1) == LUA_TBOOLEAN);
+  lua_insert(L, 1);
+  CHECK(lua_type(L, 1) == LUA_TBOOLEAN && lua_gettop(L) == 7);
+  lua_pushinteger(L, 99);
+  lua_replace(L, 1);
+  CHECK(lua_tointeger(L, 1) == 99);
+  CHECK(lua_absindex(L, -1) == 7);
+  CHECK(lua_checkstack(L, 100));
+  lua_settop(L, 2);
+  CHECK(lua_gettop(L) == 2);
+  lua_pop(L, 2);
+  CHECK(lua_gettop(L) == 0);
+
+  lua_pushfstring(L, "%s=%d %I %f %c%% %p %U", "x", -3, (lua_Integer)1 << 40,
+                  1.5, 'z', (void *)NULL, 0x20AC);
+  CHECK(strncmp(lua_tostring(L, -1), "x=-3 1099511627776 1.5 z% ", 26) == 0);
+  CHECK(strcmp(lua_tostring(L, -1) + strlen(lua_tostring(L, -1)) - 3,
+               "\xE2\x82\xAC") == 0);
+  lua_pop(L, 1);
+}
+
+static void test_tables (lua_State *L) {
+  int n = 0;
+  lua_Integer sum = 0;
+  static int key;
+
+  lua_createtable(L, 3, 1);
+  lua_pushinteger(L, 10);
+  lua_rawseti(L, -2, 1);
+  lua_pushinteger(L, 20);
+  lua_rawseti(L, -2, 2);
+  lua_pushinteger(L, 30);
+  lua_seti(L, -2, 3);
+  lua_pushstring(L, "value");
+  lua_setfield(L, -2, "name");
+  lua_pushstring(L, "secret");
+  lua_rawsetp(L, -2, &key);
+
+  CHECK(lua_rawlen(L, -1) == 3);
+  CHECK(luaL_len(L, -1) == 3);
+  CHECK(lua_rawgeti(L, -1, 2) == LUA_TNUMBER && lua_tointeger(L, -1) == 20);
+  lua_pop(L, 1);
+  CHECK(lua_geti(L, -1, 4) == LUA_TNIL);
+  lua_pop(L, 1);
+  CHECK(lua_getfield(L, -1, "name") == LUA_TSTRING);
+  CHECK_STR(L, -1, "value");
+  lua_pop(L, 1);
+  CHECK(lua_rawgetp(L, -1, &key) == LUA_TSTRING);
+  CHECK_STR(L, -1, "secret");
+  lua_pop(L, 1);
+  lua_pushstring(L, "name");
+  CHECK(lua_rawget(L, -2) == LUA_TSTRING);
+  lua_pop(L, 1);
+
+  lua_pushnil(L);
+  while (lua_next(L, -2)) {
+    if (lua_type(L, -2) == LUA_TNUMBER) sum += lua_tointeger(L, -1);
+    n++;
+    lua_pop(L, 1);
+  }
+  CHECK(n == 5 && sum == 60);
+
+  lua_setglobal(L, "tbl");
+  run(L, "assert(tbl[1] == 10 and tbl.name == 'value' and #tbl == 3)");
+  CHECK(lua_getglobal(L, "tbl") == LUA_TTABLE);
+  lua_pushstring(L, "set");
+  lua_pushboolean(L, 1);
+  lua_settable(L, -3);
+  lua_pushstring(L, "set");
+  CHECK(lua_gettable(L, -2) == LUA_TBOOLEAN);
+  lua_pop(L, 2);
+
+  /* registry slots of the reference implementation */
+  lua_pushglobaltable(L);
+  CHECK(lua_getfield(L, -1, "tbl") == LUA_TTABLE);
+  lua_pop(L, 2);
+  CHECK(lua_rawgeti(L, LUA_REGISTRYINDEX, LUA_RIDX_MAINTHREAD) == LUA_TTHREAD);
+  CHECK(lua_tothread(L, -1) == L);
+  lua_pop(L, 1);
+
+  CHECK(luaL_getsubtable(L, LUA_REGISTRYINDEX, "host.sub") == 0);
+  CHECK(luaL_getsubtable(L, LUA_REGISTRYINDEX, "host.sub") == 1);
+  lua_pop(L, 2);
+}
+
+static void test_functions (lua_State *L) {
+  lua_register(L, "add", add);
+  lua_register(L, "fail", fail);
+  lua_register(L, "throw_table", throw_table);
+  lua_register(L, "call_back", call_back);
+  lua_register(L, "concat_all", concat_all);
+  lua_register(L, "pick", pick);
+  lua_pushinteger(L, 100);
+  lua_pushcclosure(L, counter, 1);
+  lua_setglobal(L, "counter");
+
+  run(L, "assert(add(1, 2) == 3 and add(5) == 15)");
+  run(L, "assert(counter() == 101 and counter() == 102)");
+  run(L, "assert(concat_all('a', 1, 'b', 2.5) == 'a1b2.5')");
+  run(L, "assert(pick() == 0 and pick('write') == 1)");
+
+  /* argument errors carry the function name */
+  run(L, "local ok, e = pcall(add, 'x')\n"
+         "assert(e == \"bad argument #1 to 'add' (number expected, got string)\", e)");
+  run(L, "local ok, e = pcall(add, 1.5)\n"
+         "assert(e:find('number has no integer representation'), e)");
+  run(L, "local ok, e = pcall(pick, 'exec')\n"
+         "assert(e:find(\"invalid option 'exec'\"), e)");
+
+  /* luaL_error adds the position of the calling Lua code */
+  run(L, "local ok, e = pcall(function() fail('x') end)\n"
+         "assert(not ok and e:find('^%[string .-%]:1: failed with 42 and x$'), e)");
+  run(L, "local ok, e = pcall(throw_table)\n"
+         "assert(not ok and type(e) == 'table' and e.code == 'boom')");
+
+  /* errors crossing C frames in both directions */
+  run(L, "assert(call_back(function(n) return n * 2 end) == 1)");
+  run(L, "local ok, e = pcall(call_back, function() error('inner') end)\n"
+         "assert(not ok and e:find('inner'), e)");
+  run(L, "local ok, e = pcall(call_back, function() fail('deep') end)\n"
+         "assert(not ok and e:find('failed with 42 and deep'), e)");
+  run(L, "for i = 1, 200 do pcall(fail, 'loop') end");
+
+  /* calling into Lua from the host */
+  CHECK(lua_getglobal(L, "add") == LUA_TFUNCTION);
+  lua_pushinteger(L, 20);
+  lua_pushinteger(L, 22);
+  CHECK(lua_pcall(L, 2, 1, 0) == LUA_OK);
+  CHECK(lua_tointeger(L, -1) == 42);
+  lua_pop(L, 1);
+
+  run(L, "function multi() return 1, 2, 3 end");
+  lua_getglobal(L, "multi");
+  lua_call(L, 0, LUA_MULTRET);
+  CHECK(lua_gettop(L) == 3);
+  lua_settop(L, 0);
+  lua_getglobal(L, "multi");
+  lua_call(L, 0, 5);
+  CHECK(lua_gettop(L) == 5 && lua_isnil(L, 5));
+  lua_settop(L, 0);
+
+  lua_getglobal(L, "fail");
+  lua_pushstring(L, "host");
+  CHECK(lua_pcall(L, 1, 0, 0) == LUA_ERRRUN);
+  CHECK_STR(L, -1, "failed with 42 and host");
+  lua_pop(L, 1);
+
+  lua_pushcfunction(L, handler);
+  lua_getglobal(L, "error");
+  lua_pushstring(L, "oops");
+  lua_pushinteger(L, 0);
+  CHECK(lua_pcall(L, 2, 0, 1) == LUA_ERRRUN);
+  CHECK_STR(L, -1, "handled: oops");
+  lua_settop(L, 0);
+}
+
+static void test_userdata (lua_State *L) {
+  CHECK(open_point(L) == 1);
+  lua_setglobal(L, "Point");
+  CHECK(luaL_newmetatable(L, "Point") == 0);
+  lua_pop(L, 1);
+
+  run(L, "local p = Point.new(3, 4)\n"
+         "assert(p:len2() == 25)\n"
+         "assert(tostring(p) == 'Point(3.0, 4.0)', tostring(p))\n"
+         "assert(type(p) == 'userdata')");
+  run(L, "local ok, e = pcall(Point.new(1, 2).len2, {})\n"
+         "assert(e:find('Point expected, got table'), e)");
+
+  lua_getglobal(L, "Point");
+  lua_getfield(L, -1, "new");
+  lua_pushnumber(L, 1);
+  lua_pushnumber(L, 2);
+  lua_call(L, 2, 1);
+  CHECK(luaL_testudata(L, -1, "Point") != NULL);
+  CHECK(luaL_testudata(L, -1, "Other") == NULL);
+  CHECK(((Point *)lua_touserdata(L, -1))->y == 2.0);
+  CHECK(lua_rawlen(L, -1) == sizeof(Point));
+  CHECK(luaL_getmetafield(L, -1, "__name") == LUA_TSTRING);
+  CHECK_STR(L, -1, "Point");
+  lua_pop(L, 1);
+  luaL_tolstring(L, -1, NULL);
+  CHECK_STR(L, -1, "Point(1.0, 2.0)");
+  lua_settop(L, 0);
+
+  lua_gc(L, LUA_GCCOLLECT);
+  collected = 0;
+  run(L, "for i = 1, 10 do Point.new(i, i) end");
+  lua_gc(L, LUA_GCCOLLECT);
+  lua_gc(L, LUA_GCCOLLECT);
+  CHECK(collected == 10);
+  CHECK(lua_gc(L, LUA_GCCOUNT) > 0);
+}
+
+static void test_refs_and_loading (lua_State *L) {
+  const char *pieces = "return ... + 1";
+  int ref;
+
+  lua_pushstring(L, "kept");
+  ref = luaL_ref(L, LUA_REGISTRYINDEX);
+  CHECK(ref > LUA_RIDX_LAST);
+  CHECK(lua_gettop(L) == 0);
+  CHECK(lua_rawgeti(L, LUA_REGISTRYINDEX, ref) == LUA_TSTRING);
+  CHECK_STR(L, -1, "kept");
+  lua_pop(L, 1);
+  luaL_unref(L, LUA_REGISTRYINDEX, ref);
+  lua_pushnil(L);
+  CHECK(luaL_ref(L, LUA_REGISTRYINDEX) == LUA_REFNIL);
+
+  CHECK(luaL_loadstring(L, "return 1 +") == LUA_ERRSYNTAX);
+  CHECK(lua_isstring(L, -1));
+  lua_pop(L, 1);
+  CHECK(luaL_loadbuffer(L, "return 6 * 7", 12, "=calc") == LUA_OK);
+  lua_call(L, 0, 1);
+  CHECK(lua_tointeger(L, -1) == 42);
+  lua_pop(L, 1);
+  CHECK(luaL_loadbufferx(L, "return 1", 8, "=t", "b") == LUA_ERRSYNTAX);
+  lua_pop(L, 1);
+  CHECK(lua_load(L, reader, (void *)&pieces, "=reader", NULL) == LUA_OK);
+  lua_pushinteger(L, 41);
+  lua_call(L, 1, 1);
+  CHECK(lua_tointeger(L, -1) == 42);
+  lua_pop(L, 1);
+  CHECK(luaL_loadfile(L, "/nonexistent/file.lua") == LUA_ERRFILE);
+  lua_pop(L, 1);
+  CHECK(lua_gettop(L) == 0);
+}
+
+int main (int argc, char **argv) {
+  lua_State *L = luaL_newstate();
+  CHECK(L != NULL);
+  CHECK(lua_version(L) == LUA_VERSION_NUM);
+
+  if (argc > 1 && strcmp(argv[1], "panic") == 0) {
+    lua_atpanic(L, on_panic);
+    luaL_error(L, "outside any C function");
+    return 0;
+  }
+
+  luaL_openlibs(L);
+  luaL_checkversion(L);
+  test_stack(L);
+  test_tables(L);
+  test_functions(L);
+  test_userdata(L);
+  test_refs_and_loading(L);
+
+  collected = 0;
+  run(L, "keep = Point.new(0, 0)");
+  lua_close(L);
+  CHECK(collected == 1);
+
+  printf("ok %d checks\n", checks);
+  return 0;
+}
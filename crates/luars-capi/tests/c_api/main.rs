@@ -0,0 +1,100 @@
+//! Acceptance test for the C API: compiles `host.c` against the shipped
+//! headers, links it to the `cdylib` cargo built alongside this test and
+//! runs it. The host checks every call itself and prints `ok N checks` on
+//! success; a failed check names its line on stderr.
+
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::OnceLock;
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Directory holding `libluars_capi.so`: cargo puts the library next to
+/// this test binary in `target/<profile>/deps`.
+fn library_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    for dir in [deps, deps.parent().unwrap()] {
+        let found = std::fs::read_dir(dir).unwrap().flatten().any(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with("libluars_capi.") && !name.ends_with(".rlib") && !name.ends_with(".a")
+        });
+        if found {
+            return dir.to_path_buf();
+        }
+    }
+    panic!(
+        "libluars_capi shared library not found near {}",
+        exe.display()
+    );
+}
+
+fn host_binary() -> &'static Path {
+    static HOST: OnceLock<PathBuf> = OnceLock::new();
+    HOST.get_or_init(|| {
+        let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("c_api");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let exe = out_dir.join("host");
+        let lib_dir = library_dir();
+
+        let target = env!("LUARS_CAPI_TARGET");
+        let compiler = cc::Build::new()
+            .target(target)
+            .host(target)
+            .opt_level(0)
+            .out_dir(&out_dir)
+            .cargo_metadata(false)
+            .get_compiler();
+        let status = compiler
+            .to_command()
+            .arg(manifest_dir().join("tests/c_api/host.c"))
+            .arg("-I")
+            .arg(manifest_dir().join("include"))
+            .arg("-fexceptions")
+            .arg("-o")
+            .arg(&exe)
+            .arg("-L")
+            .arg(&lib_dir)
+            .arg("-lluars_capi")
+            .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+            .status()
+            .expect("failed to run the C compiler");
+        assert!(status.success(), "compiling host.c failed");
+        exe
+    })
+}
+
+fn run_host(args: &[&str]) -> Output {
+    // cargo points LD_LIBRARY_PATH at older copies of the library too
+    Command::new(host_binary())
+        .args(args)
+        .env_remove("LD_LIBRARY_PATH")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn c_host_exercises_the_api() {
+    let output = run_host(&[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "host failed ({}):\n{stdout}{stderr}",
+        output.status
+    );
+    assert!(stdout.starts_with("ok "), "unexpected output: {stdout}");
+}
+
+#[test]
+fn unprotected_error_calls_the_panic_function() {
+    let output = run_host(&["panic"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+    assert_eq!(stdout, "panic: outside any C function\n");
+}
@@ -12,6 +12,10 @@
 //! - `#[lua_impl(Display, PartialEq, PartialOrd)]` — map Rust traits to Lua metamethods
 //! - `#[lua(close = "method_name")]` — delegate `lua_close()` to a method on the struct
 //! - `#[lua(pow = "method_name")]` — delegate `lua_pow()` to a method
+//! - `#[lua(index_with = "get_at", newindex_with = "set_at")]` — delegate integer-key
+//!   `get_index()` / `set_index()` to methods with the same signatures
+//! - `#[lua(len = "method_name")]` — delegate `lua_len()` (`#obj`) to a method
+//!   returning a `usize`
 //!
 //! # Enum support
 //! - C-like enums still implement `LuaEnum` for `register_enum::<T>()`
@@ -359,6 +363,15 @@ pub fn derive_lua_userdata_impl(input: DeriveInput) -> TokenStream {
     // Generate delegation impls from #[lua(close = "...")] etc.
     let delegate_impls = gen_delegate_methods(&delegates);
 
+    if iter_field.is_some() && delegates.len.is_some() {
+        return syn::Error::new_spanned(
+            name,
+            "#[lua(len = \"...\")] conflicts with the length a #[lua(iter)] field provides",
+        )
+        .to_compile_error()
+        .into();
+    }
+
     // Generate lua_next + lua_len if #[lua(iter)] is present
     let iter_impls = if let Some(ref iter_info) = iter_field {
         let field_ident = &iter_info.ident;
@@ -421,7 +434,7 @@ pub fn derive_lua_userdata_impl(input: DeriveInput) -> TokenStream {
             }
 
             fn field_names(&self) -> &'static [&'static str] {
-                &[#(#field_name_strs),*]
+                Self::__LUA_FIELD_NAMES
             }
 
             #metamethod_impls
@@ -440,6 +453,13 @@ pub fn derive_lua_userdata_impl(input: DeriveInput) -> TokenStream {
         }
 
         #lua_convert_impls
+
+        // Shadows the LuaFieldNameProvider default; #[lua_methods] checks its
+        // instance method names against this list.
+        impl #impl_generics #name #ty_generics #where_clause {
+            #[doc(hidden)]
+            pub const __LUA_FIELD_NAMES: &'static [&'static str] = &[#(#field_name_strs),*];
+        }
     };
 
     expanded.into()
@@ -471,6 +491,9 @@ struct LuaDelegates {
     pow: Option<String>,
     idiv: Option<String>,
     concat: Option<String>,
+    index_with: Option<String>,
+    newindex_with: Option<String>,
+    len: Option<String>,
 }
 
 /// Parse `#[lua(...)]` struct-level attributes for method delegation.
@@ -480,6 +503,9 @@ fn parse_lua_delegate_attrs(input: &DeriveInput) -> LuaDelegates {
         pow: None,
         idiv: None,
         concat: None,
+        index_with: None,
+        newindex_with: None,
+        len: None,
     };
     for attr in &input.attrs {
         if attr.path().is_ident("lua")
@@ -499,6 +525,15 @@ fn parse_lua_delegate_attrs(input: &DeriveInput) -> LuaDelegates {
                 } else if path.is_ident("concat") {
                     let val: syn::LitStr = meta.value()?.parse()?;
                     delegates.concat = Some(val.value());
+                } else if path.is_ident("index_with") {
+                    let val: syn::LitStr = meta.value()?.parse()?;
+                    delegates.index_with = Some(val.value());
+                } else if path.is_ident("newindex_with") {
+                    let val: syn::LitStr = meta.value()?.parse()?;
+                    delegates.newindex_with = Some(val.value());
+                } else if path.is_ident("len") {
+                    let val: syn::LitStr = meta.value()?.parse()?;
+                    delegates.len = Some(val.value());
                 }
                 // else: field-level attrs (skip/readonly/name/iter) — silently ignored here
                 Ok(())
@@ -527,6 +562,20 @@ fn gen_delegate_methods(d: &LuaDelegates) -> proc_macro2::TokenStream {
         let ident = syn::Ident::new(m, proc_macro2::Span::call_site());
         methods.push(quote! { fn lua_concat(&self, other: &luars::UdValue) -> Option<luars::UdValue> { Some(self.#ident(other)) } });
     }
+    if let Some(ref m) = d.index_with {
+        let ident = syn::Ident::new(m, proc_macro2::Span::call_site());
+        methods.push(
+            quote! { fn get_index(&self, idx: i64) -> Option<luars::UdValue> { self.#ident(idx) } },
+        );
+    }
+    if let Some(ref m) = d.newindex_with {
+        let ident = syn::Ident::new(m, proc_macro2::Span::call_site());
+        methods.push(quote! { fn set_index(&mut self, idx: i64, value: luars::UdValue) -> Option<Result<(), String>> { self.#ident(idx, value) } });
+    }
+    if let Some(ref m) = d.len {
+        let ident = syn::Ident::new(m, proc_macro2::Span::call_site());
+        methods.push(quote! { fn lua_len(&self) -> Option<luars::UdValue> { Some(luars::UdValue::Integer(self.#ident() as i64)) } });
+    }
     quote! { #(#methods)* }
 }
 
@@ -544,6 +593,8 @@ fn gen_delegate_methods(d: &LuaDelegates) -> proc_macro2::TokenStream {
 /// - `Div`        → `lua_div`  (same-type division via `std::ops::Div`)
 /// - `Rem`        → `lua_mod`  (same-type modulo via `std::ops::Rem`)
 /// - `Neg`        → `lua_unm`  (unary negation via `std::ops::Neg`)
+/// - `Concat`     → `lua_concat`, `lua_concat_rhs` (joins the `Display` text
+///   with the other operand's, on either side of `..`)
 fn gen_metamethods(name: &Ident, trait_impls: &[String]) -> proc_macro2::TokenStream {
     let mut methods = Vec::new();
 
@@ -556,6 +607,20 @@ fn gen_metamethods(name: &Ident, trait_impls: &[String]) -> proc_macro2::TokenSt
         });
     }
 
+    // Concat → lua_concat / lua_concat_rhs, from Display
+    if trait_impls.contains(&"Concat".to_string()) {
+        methods.push(quote! {
+            fn lua_concat(&self, other: &luars::UdValue) -> Option<luars::UdValue> {
+                other.to_concat_string()
+                    .map(|s| luars::UdValue::Str(format!("{}{}", self, s)))
+            }
+            fn lua_concat_rhs(&self, other: &luars::UdValue) -> Option<luars::UdValue> {
+                other.to_concat_string()
+                    .map(|s| luars::UdValue::Str(format!("{}{}", s, self)))
+            }
+        });
+    }
+
     // PartialEq → lua_eq
     if trait_impls.contains(&"PartialEq".to_string()) {
         methods.push(quote! {
@@ -38,6 +38,10 @@ use syn::parse_macro_input;
 ///
 /// # Struct attributes
 /// - `#[lua_impl(Display, PartialEq, PartialOrd)]` — metamethods from Rust traits
+/// - `#[lua(index_with = "get_at", newindex_with = "set_at")]` — integer indexing
+///   (`obj[i]`, `obj[i] = v`) through inherent methods with the signatures of
+///   `UserDataTrait::get_index` / `set_index`
+/// - `#[lua(len = "len")]` — `#obj` through a method returning `usize`
 ///
 /// # Enum behavior
 /// - C-like enums also implement `LuaEnum`, so they can be exported with `register_enum::<T>()`
@@ -72,7 +76,17 @@ pub fn derive_lua_userdata(input: TokenStream) -> TokenStream {
 /// 2. Automatic parameter extraction from Lua stack
 /// 3. Automatic return value conversion to Lua
 ///
-/// Methods are accessible from Lua via `obj:method(args)` syntax.
+/// Methods are accessible from Lua via `obj:method(args)` syntax. `pub fn`
+/// items without `self` become entries on the type table.
+///
+/// # Method attributes
+/// - `#[lua(skip)]` — do not export this `pub` function
+/// - `#[lua(name = "...")]` / `#[lua(rename = "...")]` — custom Lua name
+/// - `#[lua(static)]` — explicit marker for an exported associated function
+/// - `#[lua(constructor)]` — also callable as `Type(...)` via `__call` on the type table
+///
+/// Private and `pub(crate)` functions are never exported. An instance method
+/// whose Lua name matches a derived field is a compile error.
 ///
 /// # Example
 /// ```ignore
@@ -19,6 +19,18 @@
 //! 2. If the return type is `Self`, the result is wrapped in `LuaUserdata::new()`
 //!    and pushed as userdata
 //! 3. Registered via `__lua_static_methods()` for use with `register_type`
+//! 4. With `#[lua(constructor)]`, also installed as `__call` on the type table
+//!
+//! # Method attributes
+//! - `#[lua(skip)]` — exclude a `pub` function
+//! - `#[lua(name = "...")]` / `#[lua(rename = "...")]` — custom Lua name
+//! - `#[lua(static)]` — explicit marker; only valid without `self`
+//! - `#[lua(constructor)]` — at most one per type, only valid without `self`
+//!
+//! Only `pub` functions are exported; the options above on a private function
+//! are errors. Each instance method name is checked at compile time against
+//! the `__LUA_FIELD_NAMES` of `#[derive(LuaUserData)]`, because a field of the
+//! same name would hide it.
 //!
 //! # Supported parameter types
 //! - `i8..i64`, `u8..u64`, `isize`, `usize` — extracted via `as_integer()`
@@ -51,8 +63,12 @@
 //! }
 //! ```
 
+use std::collections::HashSet;
+
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
+use proc_macro2::Span;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
 use syn::{FnArg, ItemImpl, Pat, ReturnType, parse_macro_input};
 
 use crate::type_utils::{
@@ -184,6 +200,10 @@ struct MethodInfo {
     rust_name: syn::Ident,
     /// Lua-visible name (same as rust_name unless overridden)
     lua_name: String,
+    /// Span of the Lua name: the `rename` literal, or the Rust name
+    name_span: Span,
+    /// `#[lua(constructor)]`: also callable as `Type(...)`
+    constructor: bool,
     /// Kind of method
     kind: MethodKind,
     /// Parameter names and types (excluding self)
@@ -192,6 +212,50 @@ struct MethodInfo {
     return_type: Option<syn::Type>,
 }
 
+/// `#[lua(...)]` options on a function in a `#[lua_methods]` block.
+#[derive(Default)]
+struct MethodAttrs {
+    skip: bool,
+    /// `name = "..."` / `rename = "..."`
+    name: Option<syn::LitStr>,
+    constructor: bool,
+    /// Span of `static` or `constructor`, which need a function without `self`
+    static_span: Option<Span>,
+    /// Span of the first option that only makes sense on an exported function
+    export_span: Option<Span>,
+}
+
+fn parse_method_attrs(attrs: &[syn::Attribute]) -> syn::Result<MethodAttrs> {
+    let mut out = MethodAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("lua")) {
+        attr.parse_nested_meta(|meta| {
+            let span = meta.path.span();
+            if meta.path.is_ident("skip") {
+                out.skip = true;
+                return Ok(());
+            }
+            if meta.path.is_ident("name") || meta.path.is_ident("rename") {
+                if out.name.is_some() {
+                    return Err(meta.error("the Lua name is already set"));
+                }
+                out.name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("static") {
+                out.static_span.get_or_insert(span);
+            } else if meta.path.is_ident("constructor") {
+                out.constructor = true;
+                out.static_span.get_or_insert(span);
+            } else {
+                return Err(meta.error(
+                    "unknown #[lua] option; expected `skip`, `name`, `rename`, `static` or `constructor`",
+                ));
+            }
+            out.export_span.get_or_insert(span);
+            Ok(())
+        })?;
+    }
+    Ok(out)
+}
+
 /// Entry point for `#[lua_methods]`.
 pub fn lua_methods_impl(input: TokenStream) -> TokenStream {
     let item_impl = parse_macro_input!(input as ItemImpl);
@@ -200,94 +264,10 @@ pub fn lua_methods_impl(input: TokenStream) -> TokenStream {
     let self_ty = &item_impl.self_ty;
 
     // Collect method info from all pub methods
-    let mut methods: Vec<MethodInfo> = Vec::new();
-
-    for item in &item_impl.items {
-        if let syn::ImplItem::Fn(method) = item {
-            // Only process public methods
-            if !matches!(method.vis, syn::Visibility::Public(_)) {
-                continue;
-            }
-
-            let sig = &method.sig;
-
-            // Skip async methods
-            if sig.asyncness.is_some() {
-                continue;
-            }
-
-            // Parse #[lua(...)] attributes on this method
-            let mut skip = false;
-            let mut lua_name_override: Option<String> = None;
-            for attr in &method.attrs {
-                if attr.path().is_ident("lua")
-                    && let Ok(list) = attr.meta.require_list()
-                {
-                    let _ = list.parse_nested_meta(|meta| {
-                        if meta.path.is_ident("skip") {
-                            skip = true;
-                        } else if meta.path.is_ident("name")
-                            && let Ok(value) = meta.value()
-                            && let Ok(lit) = value.parse::<syn::LitStr>()
-                        {
-                            lua_name_override = Some(lit.value());
-                        }
-                        Ok(())
-                    });
-                }
-            }
-
-            if skip {
-                continue;
-            }
-
-            // Determine method kind
-            let kind = match sig.inputs.first() {
-                Some(FnArg::Receiver(r)) => {
-                    if r.mutability.is_some() {
-                        MethodKind::RefMut
-                    } else {
-                        MethodKind::Ref
-                    }
-                }
-                _ => MethodKind::Static, // no self → associated function
-            };
-
-            // Collect non-self parameters
-            let skip_count = match &kind {
-                MethodKind::Static => 0,
-                _ => 1,
-            };
-            let mut params = Vec::new();
-            for arg in sig.inputs.iter().skip(skip_count) {
-                if let FnArg::Typed(pat_type) = arg {
-                    let param_name = if let Pat::Ident(pat_ident) = pat_type.pat.as_ref() {
-                        pat_ident.ident.clone()
-                    } else {
-                        format_ident!("__arg")
-                    };
-                    params.push((param_name, (*pat_type.ty).clone()));
-                }
-            }
-
-            // Extract return type
-            let return_type = match &sig.output {
-                ReturnType::Default => None,
-                ReturnType::Type(_, ty) => Some((**ty).clone()),
-            };
-
-            let rust_name = sig.ident.clone();
-            let lua_name = lua_name_override.unwrap_or_else(|| rust_name.to_string());
-
-            methods.push(MethodInfo {
-                rust_name,
-                lua_name,
-                kind,
-                params,
-                return_type,
-            });
-        }
-    }
+    let methods = match collect_methods(&item_impl) {
+        Ok(methods) => methods,
+        Err(e) => return error_with_fallback(&item_impl, e),
+    };
 
     // Split into instance methods and static methods
     let instance_methods: Vec<&MethodInfo> = methods
@@ -326,9 +306,56 @@ pub fn lua_methods_impl(input: TokenStream) -> TokenStream {
     // Generate wrapper functions for static methods
     let static_wrapper_fns: Vec<proc_macro2::TokenStream> = static_methods
         .iter()
-        .map(|m| gen_static_wrapper_fn(self_ty, m))
+        .map(|m| {
+            gen_static_wrapper_fn(
+                self_ty,
+                m,
+                &format_ident!("__lua_static_{}", m.rust_name),
+                1,
+            )
+        })
         .collect();
 
+    // The constructor's `__call` wrapper skips the type table in arg 1
+    let constructor = match static_methods.iter().find(|m| m.constructor) {
+        Some(m) => {
+            let wrapper_name = format_ident!("__lua_constructor_{}", m.rust_name);
+            let wrapper = gen_static_wrapper_fn(self_ty, m, &wrapper_name, 2);
+            quote! {
+                #wrapper
+                Some(#wrapper_name)
+            }
+        }
+        None => quote! { None },
+    };
+
+    // A field with the same Lua name would hide an instance method, so each
+    // name is checked against the derive's field list at compile time. Generic
+    // impls cannot be named in a `const` item and are not checked.
+    let field_checks: Vec<proc_macro2::TokenStream> = if item_impl.generics.params.is_empty() {
+        instance_methods
+            .iter()
+            .map(|m| {
+                let lua_name = &m.lua_name;
+                let msg = format!(
+                    "Lua method `{lua_name}` has the same name as a field, which would hide it; \
+                     rename one of them with #[lua(rename = \"...\")] or #[lua(name = \"...\")]"
+                );
+                quote_spanned! {m.name_span=>
+                    const _: () = {
+                        #[allow(unused_imports)]
+                        use luars::LuaFieldNameProvider as _;
+                        if luars::__lua_name_in(<#self_ty>::__LUA_FIELD_NAMES, #lua_name) {
+                            panic!(#msg);
+                        }
+                    };
+                }
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
     // Generate static methods table entries
     let static_entries: Vec<proc_macro2::TokenStream> = static_methods
         .iter()
@@ -366,6 +393,12 @@ pub fn lua_methods_impl(input: TokenStream) -> TokenStream {
 
                 &[#(#static_entries)*]
             }
+
+            /// `__call` handler for the type table (`#[lua(constructor)]`).
+            #[allow(unused)]
+            pub fn __lua_constructor() -> Option<luars::CFunction> {
+                #constructor
+            }
         }
 
         // Explicit trait impl for LuaRegistrable.
@@ -373,12 +406,165 @@ pub fn lua_methods_impl(input: TokenStream) -> TokenStream {
             fn lua_static_methods() -> &'static [(&'static str, luars::CFunction)] {
                 #self_ty::__lua_static_methods()
             }
+
+            fn lua_constructor() -> Option<luars::CFunction> {
+                #self_ty::__lua_constructor()
+            }
         }
+
+        #(#field_checks)*
     };
 
     expanded.into()
 }
 
+/// Collect the exported functions of the impl block, rejecting attribute
+/// misuse and duplicate Lua names.
+fn collect_methods(item_impl: &ItemImpl) -> syn::Result<Vec<MethodInfo>> {
+    let mut methods: Vec<MethodInfo> = Vec::new();
+
+    let mut constructor_seen = false;
+    let mut instance_names = HashSet::new();
+    let mut static_names = HashSet::new();
+
+    for item in &item_impl.items {
+        let syn::ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let sig = &method.sig;
+
+        let attrs = parse_method_attrs(&method.attrs)?;
+
+        // Determine method kind
+        let kind = match sig.inputs.first() {
+            Some(FnArg::Receiver(r)) => {
+                if r.mutability.is_some() {
+                    MethodKind::RefMut
+                } else {
+                    MethodKind::Ref
+                }
+            }
+            _ => MethodKind::Static, // no self → associated function
+        };
+
+        if let Some(span) = attrs.static_span
+            && !matches!(kind, MethodKind::Static)
+        {
+            return Err(syn::Error::new(
+                span,
+                "`static` and `constructor` apply to associated functions without `self`",
+            ));
+        }
+
+        // Only public functions are exported; naming or marking a private one
+        // is a mistake rather than a way to export it
+        if !matches!(method.vis, syn::Visibility::Public(_)) {
+            if let Some(span) = attrs.export_span {
+                return Err(syn::Error::new(
+                    span,
+                    "only `pub` functions are exported to Lua; make this function `pub`",
+                ));
+            }
+            continue;
+        }
+
+        // Skip async methods
+        if attrs.skip || sig.asyncness.is_some() {
+            continue;
+        }
+
+        if attrs.constructor {
+            if constructor_seen {
+                return Err(syn::Error::new_spanned(
+                    &sig.ident,
+                    "only one function per type can be `#[lua(constructor)]`",
+                ));
+            }
+            constructor_seen = true;
+        }
+
+        // Collect non-self parameters
+        let skip_count = match &kind {
+            MethodKind::Static => 0,
+            _ => 1,
+        };
+        let mut params = Vec::new();
+        for arg in sig.inputs.iter().skip(skip_count) {
+            if let FnArg::Typed(pat_type) = arg {
+                let param_name = if let Pat::Ident(pat_ident) = pat_type.pat.as_ref() {
+                    pat_ident.ident.clone()
+                } else {
+                    format_ident!("__arg")
+                };
+                params.push((param_name, (*pat_type.ty).clone()));
+            }
+        }
+
+        // Extract return type
+        let return_type = match &sig.output {
+            ReturnType::Default => None,
+            ReturnType::Type(_, ty) => Some((**ty).clone()),
+        };
+
+        let rust_name = sig.ident.clone();
+        let (lua_name, name_span) = match &attrs.name {
+            Some(lit) => (lit.value(), lit.span()),
+            None => (rust_name.to_string(), rust_name.span()),
+        };
+
+        let names = match kind {
+            MethodKind::Static => &mut static_names,
+            _ => &mut instance_names,
+        };
+        if !names.insert(lua_name.clone()) {
+            return Err(syn::Error::new(
+                name_span,
+                format!(
+                    "another function in this block is already exported to Lua as `{lua_name}`"
+                ),
+            ));
+        }
+
+        methods.push(MethodInfo {
+            rust_name,
+            lua_name,
+            name_span,
+            constructor: attrs.constructor,
+            kind,
+            params,
+            return_type,
+        });
+    }
+    Ok(methods)
+}
+
+/// Report `err` but still emit the impl block and empty lookups, so the
+/// derive and the rest of the crate do not pile up follow-on errors.
+fn error_with_fallback(item_impl: &ItemImpl, err: syn::Error) -> TokenStream {
+    let mut cleaned_impl = item_impl.clone();
+    for item in &mut cleaned_impl.items {
+        if let syn::ImplItem::Fn(method) = item {
+            method.attrs.retain(|attr| !attr.path().is_ident("lua"));
+        }
+    }
+    let self_ty = &item_impl.self_ty;
+    let (impl_generics, _ty_generics, where_clause) = item_impl.generics.split_for_impl();
+    let err = err.to_compile_error();
+    quote! {
+        #cleaned_impl
+
+        impl #impl_generics #self_ty #where_clause {
+            #[allow(unused)]
+            pub fn __lua_lookup_method(key: &str) -> Option<luars::CFunction> {
+                None
+            }
+        }
+
+        #err
+    }
+    .into()
+}
+
 /// Generate a static wrapper function for an instance method (`&self` / `&mut self`).
 fn gen_instance_wrapper_fn(self_ty: &syn::Type, method: &MethodInfo) -> proc_macro2::TokenStream {
     let wrapper_name = format_ident!("__lua_method_{}", method.rust_name);
@@ -412,17 +598,23 @@ fn gen_instance_wrapper_fn(self_ty: &syn::Type, method: &MethodInfo) -> proc_mac
 }
 
 /// Generate a static wrapper function for an associated function (no `self`).
-fn gen_static_wrapper_fn(self_ty: &syn::Type, method: &MethodInfo) -> proc_macro2::TokenStream {
-    let wrapper_name = format_ident!("__lua_static_{}", method.rust_name);
+///
+/// Parameters are read from `first_arg` on: 1 for `Type.func(...)`, 2 for the
+/// `__call` handler, which gets the type table first.
+fn gen_static_wrapper_fn(
+    self_ty: &syn::Type,
+    method: &MethodInfo,
+    wrapper_name: &syn::Ident,
+    first_arg: usize,
+) -> proc_macro2::TokenStream {
     let rust_name = &method.rust_name;
 
-    // Static functions: params start at arg 1 (no self)
     let param_extractions: Vec<proc_macro2::TokenStream> = method
         .params
         .iter()
         .enumerate()
         .map(|(i, (name, ty))| {
-            let arg_index = i + 1; // 1-based, no self to skip
+            let arg_index = i + first_arg;
             let param_name_str = name.to_string();
             gen_from_lua_extraction(name, ty, arg_index, &param_name_str)
         })
@@ -648,12 +840,22 @@ fn gen_ref_call(
         let __self_val = __l.get_arg(1)
             .ok_or_else(|| __l.error(format!("{}:{} — missing self argument", #type_name, #method_name_str)))?;
         #token_extract
-        if let Some(__ud) = __self_val.as_userdata_mut() {
-            if let Some(__this) = __ud.downcast_ref::<#self_ty>() {
-                return #return_block;
+        let __ud = __self_val.as_userdata_mut()
+            .ok_or_else(|| __l.error(format!("{}:{} — invalid self", #type_name, #method_name_str)))?;
+        // A shared userdata is borrowed for the duration of the call
+        let __shared_guard;
+        let __this: &#self_ty = match __ud.downcast_ref::<#self_ty>() {
+            Some(__this) => __this,
+            None => {
+                // Reports a sub-reference whose shared value is borrowed elsewhere
+                __ud.get_trait()?;
+                let __shared = __ud.downcast_ref::<luars::SharedUserData<#self_ty>>()
+                    .ok_or_else(|| __l.error(format!("{}:{} — invalid self", #type_name, #method_name_str)))?;
+                __shared_guard = __shared.try_borrow().map_err(|__e| __l.error(__e.to_string()))?;
+                &*__shared_guard
             }
-        }
-        Err(__l.error(format!("{}:{} — invalid self", #type_name, #method_name_str)))
+        };
+        #return_block
     }
 }
 
@@ -702,11 +904,21 @@ fn gen_mut_call(
         let __self_val = __l.get_arg(1)
             .ok_or_else(|| __l.error(format!("{}:{} — missing self argument", #type_name, #method_name_str)))?;
         #token_extract
-        if let Some(__ud) = __self_val.as_userdata_mut() {
-            if let Some(__this) = __ud.downcast_mut::<#self_ty>() {
-                return #return_block;
+        let __ud = __self_val.as_userdata_mut()
+            .ok_or_else(|| __l.error(format!("{}:{} — invalid self", #type_name, #method_name_str)))?;
+        // A shared userdata is borrowed for the duration of the call
+        let mut __shared_guard;
+        let __this: &mut #self_ty = match __ud.downcast_mut::<#self_ty>() {
+            Some(__this) => __this,
+            None => {
+                // Reports a sub-reference whose shared value is borrowed elsewhere
+                __ud.get_trait_mut()?;
+                let __shared = __ud.downcast_ref::<luars::SharedUserData<#self_ty>>()
+                    .ok_or_else(|| __l.error(format!("{}:{} — invalid self", #type_name, #method_name_str)))?;
+                __shared_guard = __shared.try_borrow_mut().map_err(|__e| __l.error(__e.to_string()))?;
+                &mut *__shared_guard
             }
-        }
-        Err(__l.error(format!("{}:{} — invalid self", #type_name, #method_name_str)))
+        };
+        #return_block
     }
 }
@@ -0,0 +1,188 @@
+//! Criterion benchmarks for the `json` library against a pure-Lua encoder
+//! and decoder doing the same work.
+//!
+//! ```text
+//! cargo bench -p luars --features json,bench-util --bench json
+//! ```
+
+use std::time::Duration;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use luars::bench_util::{COMPUTE_LIBS, compile, new_lua};
+use luars::{LuaFunction, Stdlib};
+
+/// Builds the shared document as the global `DOC` and its encoding as `TEXT`.
+const SETUP: &str = r#"
+local items = {}
+for i = 1, 2000 do
+    items[i] = {
+        id = i,
+        name = "item \"" .. i .. "\"\n",
+        price = i * 0.25,
+        tags = { "a", "b", "c" },
+        active = i % 2 == 0,
+    }
+end
+DOC = { items = items, total = 2000 }
+TEXT = json.encode(DOC)
+"#;
+
+/// A compact pure-Lua JSON implementation, the kind a project carries before
+/// it has a native one.
+const PURE_LUA: &str = r#"
+local escapes = { ['"'] = '\\"', ['\\'] = '\\\\', ['\b'] = '\\b', ['\f'] = '\\f',
+                  ['\n'] = '\\n', ['\r'] = '\\r', ['\t'] = '\\t' }
+
+local encode
+local function encode_table(t, out)
+    local n = #t
+    if n > 0 or next(t) == nil then
+        out[#out + 1] = "["
+        for i = 1, n do
+            if i > 1 then out[#out + 1] = "," end
+            encode(t[i], out)
+        end
+        out[#out + 1] = "]"
+    else
+        out[#out + 1] = "{"
+        local first = true
+        for k, v in pairs(t) do
+            if not first then out[#out + 1] = "," end
+            first = false
+            encode(tostring(k), out)
+            out[#out + 1] = ":"
+            encode(v, out)
+        end
+        out[#out + 1] = "}"
+    end
+end
+
+encode = function(v, out)
+    local tv = type(v)
+    if tv == "table" then
+        encode_table(v, out)
+    elseif tv == "string" then
+        out[#out + 1] = '"' .. v:gsub('[%c"\\]', function(c)
+            return escapes[c] or string.format("\\u%04x", c:byte())
+        end) .. '"'
+    elseif tv == "number" then
+        out[#out + 1] = math.type(v) == "integer" and tostring(v) or string.format("%.17g", v)
+    elseif tv == "boolean" then
+        out[#out + 1] = tostring(v)
+    else
+        out[#out + 1] = "null"
+    end
+end
+
+local function lua_encode(v)
+    local out = {}
+    encode(v, out)
+    return table.concat(out)
+end
+
+local unescapes = { b = "\b", f = "\f", n = "\n", r = "\r", t = "\t" }
+local decode
+local function skip(s, i) return s:find("[^ \t\r\n]", i) or #s + 1 end
+
+decode = function(s, i)
+    i = skip(s, i)
+    local c = s:sub(i, i)
+    if c == "{" then
+        local t = {}
+        i = skip(s, i + 1)
+        if s:sub(i, i) == "}" then return t, i + 1 end
+        while true do
+            local k
+            k, i = decode(s, i)
+            i = skip(s, i) + 1
+            t[k], i = decode(s, i)
+            i = skip(s, i)
+            c = s:sub(i, i)
+            i = i + 1
+            if c == "}" then return t, i end
+        end
+    elseif c == "[" then
+        local t, n = {}, 0
+        i = skip(s, i + 1)
+        if s:sub(i, i) == "]" then return t, i + 1 end
+        while true do
+            n = n + 1
+            t[n], i = decode(s, i)
+            i = skip(s, i)
+            c = s:sub(i, i)
+            i = i + 1
+            if c == "]" then return t, i end
+        end
+    elseif c == '"' then
+        local j = i + 1
+        local parts = {}
+        while true do
+            local k = s:find('["\\]', j)
+            parts[#parts + 1] = s:sub(j, k - 1)
+            if s:sub(k, k) == '"' then return table.concat(parts), k + 1 end
+            local e = s:sub(k + 1, k + 1)
+            if e == "u" then
+                parts[#parts + 1] = utf8.char(tonumber(s:sub(k + 2, k + 5), 16))
+                j = k + 6
+            else
+                parts[#parts + 1] = unescapes[e] or e
+                j = k + 2
+            end
+        end
+    elseif s:sub(i, i + 3) == "true" then
+        return true, i + 4
+    elseif s:sub(i, i + 4) == "false" then
+        return false, i + 5
+    elseif s:sub(i, i + 3) == "null" then
+        return nil, i + 4
+    else
+        local num = s:match("^-?%d+%.?%d*[eE]?[-+]?%d*", i)
+        return math.tointeger(tonumber(num)) or tonumber(num), i + #num
+    end
+end
+
+LUA_ENCODE = lua_encode
+LUA_DECODE = function(s) return (decode(s, 1)) end
+"#;
+
+fn config() -> Criterion {
+    Criterion::default()
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10))
+        .noise_threshold(0.03)
+}
+
+fn bench_script(c: &mut Criterion, name: &str, func: &LuaFunction) {
+    c.bench_function(name, |b| b.iter(|| func.call::<_, ()>(()).unwrap()));
+}
+
+fn json(c: &mut Criterion) {
+    let mut libs = COMPUTE_LIBS.to_vec();
+    libs.extend([Stdlib::Utf8, Stdlib::Json]);
+    let mut lua = new_lua(&libs).unwrap();
+    compile(&mut lua, "pure_lua_json", PURE_LUA)
+        .unwrap()
+        .call::<_, ()>(())
+        .unwrap();
+    compile(&mut lua, "json_setup", SETUP)
+        .unwrap()
+        .call::<_, ()>(())
+        .unwrap();
+
+    for (name, source) in [
+        ("json_encode_native", "json.encode(DOC)"),
+        ("json_encode_pure_lua", "LUA_ENCODE(DOC)"),
+        ("json_decode_native", "json.decode(TEXT)"),
+        ("json_decode_pure_lua", "LUA_DECODE(TEXT)"),
+    ] {
+        let func = compile(&mut lua, name, source).unwrap();
+        bench_script(c, name, &func);
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = json
+}
+criterion_main!(benches);
@@ -0,0 +1,289 @@
+//! Criterion benchmarks for the interpreter.
+//!
+//! Every script is compiled once and the compiled function is called in the
+//! measured loop, through the same public API a host would use.
+//!
+//! Regression check against a saved run:
+//!
+//! ```text
+//! cargo bench -p luars --features bench-util --bench vm -- --save-baseline main
+//! # ...make changes...
+//! cargo bench -p luars --features bench-util --bench vm -- --baseline main
+//! ```
+//!
+//! Criterion then reports "Performance has regressed" for any benchmark whose
+//! change exceeds the noise threshold configured in `config()`.
+
+use std::time::Duration;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use luars::bench_util::{COMPUTE_LIBS, compile, config_source, new_lua};
+use luars::{LuaApi, LuaFunction, LuaTable, LuaUserData, Stdlib, UdValue, lua_methods};
+
+#[derive(LuaUserData)]
+struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[lua_methods]
+impl Vec2 {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    pub fn length(&self) -> f64 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+
+    pub fn scale(&mut self, factor: f64) {
+        self.x *= factor;
+        self.y *= factor;
+    }
+}
+
+#[derive(LuaUserData)]
+#[lua(index_with = "get", newindex_with = "set", len = "len")]
+struct Buffer {
+    data: Vec<f64>,
+}
+
+impl Buffer {
+    fn get(&self, idx: i64) -> Option<UdValue> {
+        let i = usize::try_from(idx).ok()?.checked_sub(1)?;
+        self.data.get(i).map(|&v| UdValue::Number(v))
+    }
+
+    fn set(&mut self, idx: i64, value: UdValue) -> Option<Result<(), String>> {
+        let i = usize::try_from(idx).ok()?.checked_sub(1)?;
+        let slot = self.data.get_mut(i)?;
+        Some(match value.to_number() {
+            Some(v) => {
+                *slot = v;
+                Ok(())
+            }
+            None => Err("buffer value must be a number".to_string()),
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+#[lua_methods]
+impl Buffer {
+    pub fn new(size: i64) -> Self {
+        Self {
+            data: vec![0.0; size.max(0) as usize],
+        }
+    }
+}
+
+const FIB: &str = r#"
+local function fib(n)
+    if n < 2 then return n end
+    return fib(n - 1) + fib(n - 2)
+end
+return fib(30)
+"#;
+
+const BINARY_TREES: &str = r#"
+local function bottom_up(depth)
+    if depth == 0 then return {} end
+    depth = depth - 1
+    return { bottom_up(depth), bottom_up(depth) }
+end
+
+local function check(tree)
+    if tree[1] then return 1 + check(tree[1]) + check(tree[2]) end
+    return 1
+end
+
+local total = 0
+for d = 4, 12, 2 do
+    for _ = 1, 2 ^ (12 - d) do
+        total = total + check(bottom_up(d))
+    end
+end
+return total
+"#;
+
+const TABLE_CHURN: &str = r#"
+local t = {}
+for i = 1, 100000 do
+    t[#t + 1] = i
+    t["k" .. (i % 1000)] = i
+    if i % 3 == 0 then table.remove(t) end
+end
+for k in pairs(t) do t[k] = nil end
+return next(t) == nil
+"#;
+
+const TABLE_SHIFT: &str = r#"
+local t = {}
+for i = 1, 100000 do t[i] = i end
+for i = 1, 200 do table.insert(t, 1, i) end
+for i = 1, 200 do table.remove(t, 1) end
+return #t == 100000
+"#;
+
+const STRING_SCAN: &str = r#"
+local text = ...
+local words = 0
+for _ in text:gmatch("%a+") do words = words + 1 end
+local _, n = text:gsub("lorem", "LOREM")
+return words + n
+"#;
+
+const LOG_SUB_COMPARE: &str = r#"
+local text, needle = ...
+local limit, pos, hits = #text - 100, 1, 0
+for _ = 1, 1000000 do
+    if text:sub(pos, pos + 63) == needle then hits = hits + 1 end
+    pos = (pos * 31 + 7) % limit + 1
+end
+return hits
+"#;
+
+const SORT_WORDS: &str = r#"
+local words, x = {}, 1
+for i = 1, 100000 do
+    x = (x * 1103515245 + 12345) % 2147483648
+    words[i] = string.format("key_%08x", x)
+end
+return words
+"#;
+
+const STRING_SORT: &str = r#"
+local words = ...
+local t = table.move(words, 1, #words, 1, {})
+table.sort(t)
+"#;
+
+const STRING_SORT_LT: &str = r#"
+local words = ...
+local t = table.move(words, 1, #words, 1, {})
+table.sort(t, function(a, b) return a < b end)
+"#;
+
+const COROUTINE_PING_PONG: &str = r#"
+local co = coroutine.wrap(function()
+    local v = 0
+    while true do v = coroutine.yield(v + 1) end
+end)
+local v = 0
+for _ = 1, 1000000 do v = co(v) end
+return v
+"#;
+
+const USERDATA_ACCESS: &str = r#"
+local v = Vec2.new(3.0, 4.0)
+local sum = 0.0
+for _ = 1, 200000 do
+    sum = sum + v.x + v:length()
+    v:scale(1.0)
+end
+return sum
+"#;
+
+const USERDATA_INDEX: &str = r#"
+local buf = Buffer.new(1000)
+for _ = 1, 200 do
+    for i = 1, #buf do
+        buf[i] = buf[i] + i
+    end
+end
+return buf[1000]
+"#;
+
+const GC_STRESS: &str = r#"
+for i = 1, 1000000 do
+    local t = { i, i + 1, x = i }
+end
+"#;
+
+fn config() -> Criterion {
+    Criterion::default()
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(10))
+        .noise_threshold(0.03)
+}
+
+fn bench_script(c: &mut Criterion, name: &str, func: &LuaFunction) {
+    c.bench_function(name, |b| b.iter(|| func.call::<_, ()>(()).unwrap()));
+}
+
+fn interpreter(c: &mut Criterion) {
+    let mut lua = new_lua(COMPUTE_LIBS).unwrap();
+    for (name, source) in [
+        ("fibonacci_30", FIB),
+        ("binary_trees_12", BINARY_TREES),
+        ("table_churn", TABLE_CHURN),
+        ("table_insert_remove_front_100k", TABLE_SHIFT),
+        ("coroutine_ping_pong_1m", COROUTINE_PING_PONG),
+    ] {
+        let func = compile(&mut lua, name, source).unwrap();
+        bench_script(c, name, &func);
+    }
+}
+
+fn strings(c: &mut Criterion) {
+    let mut lua = new_lua(COMPUTE_LIBS).unwrap();
+    let line = "lorem ipsum dolor sit amet, consectetur adipiscing elit 0123456789\n";
+    let text = line.repeat((1 << 20) / line.len() + 1);
+    let func = compile(&mut lua, "string_scan", STRING_SCAN).unwrap();
+    c.bench_function("string_gsub_gmatch_1mb", |b| {
+        b.iter(|| func.call::<_, ()>(text.as_str()).unwrap())
+    });
+
+    let line = "2024-05-01T12:00:00Z INFO request served path=/api/items status=200 ms=12\n";
+    let log = line.repeat((10 << 20) / line.len() + 1);
+    let func = compile(&mut lua, "log_sub_compare", LOG_SUB_COMPARE).unwrap();
+    c.bench_function("string_sub_compare_10mb", |b| {
+        b.iter(|| func.call::<_, ()>((log.as_str(), &line[..64])).unwrap())
+    });
+
+    let words = lua.load(SORT_WORDS).eval::<LuaTable>().unwrap();
+    for (name, source) in [
+        ("table_sort_strings_100k", STRING_SORT),
+        ("table_sort_strings_lt_100k", STRING_SORT_LT),
+    ] {
+        let func = compile(&mut lua, name, source).unwrap();
+        c.bench_function(name, |b| {
+            b.iter(|| func.call::<_, ()>(words.clone()).unwrap())
+        });
+    }
+}
+
+fn userdata(c: &mut Criterion) {
+    let mut lua = new_lua(&[Stdlib::Basic]).unwrap();
+    lua.register_type_of::<Vec2>("Vec2").unwrap();
+    let func = compile(&mut lua, "userdata_access", USERDATA_ACCESS).unwrap();
+    bench_script(c, "userdata_field_method", &func);
+
+    lua.register_type_of::<Buffer>("Buffer").unwrap();
+    let func = compile(&mut lua, "userdata_index", USERDATA_INDEX).unwrap();
+    bench_script(c, "userdata_integer_index", &func);
+}
+
+fn gc(c: &mut Criterion) {
+    let mut lua = new_lua(&[Stdlib::Basic]).unwrap();
+    let func = compile(&mut lua, "gc_stress", GC_STRESS).unwrap();
+    bench_script(c, "gc_stress_1m_tables", &func);
+}
+
+fn compiler(c: &mut Criterion) {
+    let mut lua = new_lua(&[Stdlib::Basic]).unwrap();
+    let source = config_source(3 << 20);
+    c.bench_function("compile_config_3mb", |b| {
+        b.iter(|| compile(&mut lua, "config", &source).unwrap())
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = interpreter, strings, userdata, gc, compiler
+}
+criterion_main!(benches);
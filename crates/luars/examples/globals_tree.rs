@@ -0,0 +1,136 @@
+//! Render the globals defined by a Lua script as a tree, using the read-only
+//! inspection API (`iter_globals`, `iter_table`, `function_info`,
+//! `thread_stack`). No Lua code is run to produce the listing.
+//!
+//! ```text
+//! cargo run -p luars --example globals_tree [script.lua]
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use luars::{Lua, LuaApi, LuaFunction, LuaResult, LuaState, LuaValue, SafeOption, Stdlib};
+
+const DEMO_SCRIPT: &str = r#"
+config = { name = "demo", retries = 3, ports = { 80, 443 } }
+config.self = config
+
+local prefix = ">> "
+function greet(who)
+    return prefix .. who
+end
+
+worker = coroutine.create(function(jobs)
+    local done = 0
+    for _, job in ipairs(jobs) do
+        done = done + 1
+        coroutine.yield(job)
+    end
+    return done
+end)
+coroutine.resume(worker, { "a", "b" })
+"#;
+
+const MAX_DEPTH: usize = 3;
+
+fn describe(state: &LuaState, value: &LuaValue) -> String {
+    if let Some(info) = state.function_info(value) {
+        let params = if info.is_vararg {
+            format!("{}+...", info.params)
+        } else {
+            info.params.to_string()
+        };
+        let (first, last) = info.line_range;
+        let location = if first < 0 {
+            info.source
+        } else {
+            format!("{}:{first}-{last}", info.source)
+        };
+        return format!(
+            "function({params}) {location} upvalues {:?}",
+            info.upvalue_names
+        );
+    }
+    if value.is_string() {
+        return format!("{:?}", value.to_string());
+    }
+    value.to_string()
+}
+
+fn render(
+    state: &LuaState,
+    label: &str,
+    value: &LuaValue,
+    depth: usize,
+    seen: &mut HashSet<*const u8>,
+) {
+    let indent = "  ".repeat(depth);
+    println!("{indent}{label} = {}", describe(state, value));
+    if value.is_table() {
+        if !seen.insert(value.raw_ptr_repr()) {
+            println!("{indent}  (already shown)");
+            return;
+        }
+        if depth + 1 >= MAX_DEPTH {
+            return;
+        }
+        for (k, v) in state.iter_table(value) {
+            render(
+                state,
+                &format!("[{}]", describe(state, &k)),
+                &v,
+                depth + 1,
+                seen,
+            );
+        }
+    } else if value.is_thread() {
+        for frame in state.thread_stack(value) {
+            println!(
+                "{indent}  frame {} {}:{} ({})",
+                frame.name.as_deref().unwrap_or("?"),
+                frame.source,
+                frame.current_line,
+                frame.what
+            );
+            for (name, local) in &frame.locals {
+                println!("{indent}    local {name} = {}", describe(state, local));
+            }
+        }
+    }
+}
+
+fn main() -> LuaResult<()> {
+    let source = match std::env::args().nth(1) {
+        Some(path) => std::fs::read_to_string(&path).expect("cannot read script"),
+        None => DEMO_SCRIPT.to_string(),
+    };
+
+    let mut lua = Lua::new(SafeOption::default());
+    lua.open_stdlib(Stdlib::All)?;
+
+    // The first call records the names the standard library defines, the
+    // second renders everything the script added on top of them.
+    let baseline: Rc<RefCell<Option<HashSet<String>>>> = Rc::default();
+    lua.global_state_mut()
+        .register_function("__inspect_globals", move |state| {
+            let mut baseline = baseline.borrow_mut();
+            let mut globals: Vec<(LuaValue, LuaValue)> = state.iter_globals().collect();
+            let Some(known) = baseline.as_ref() else {
+                *baseline = Some(globals.iter().map(|(k, _)| k.to_string()).collect());
+                return Ok(0);
+            };
+            globals.retain(|(k, _)| !known.contains(&k.to_string()));
+            globals.sort_by_key(|(k, _)| k.to_string());
+            let mut seen = HashSet::new();
+            for (name, value) in &globals {
+                render(state, &name.to_string(), value, 0, &mut seen);
+            }
+            Ok(0)
+        })?;
+    let inspect: LuaFunction = lua.globals().get("__inspect_globals")?;
+    inspect.call::<_, ()>(())?;
+
+    lua.load(&source).exec()?;
+    inspect.call::<_, ()>(())
+}
@@ -0,0 +1,53 @@
+//! Support code for the criterion benches in `benches/`.
+//!
+//! Not a stable API: it only exists, behind the `bench-util` feature, so the
+//! benches can build a VM through the public entry points without repeating
+//! the setup in every harness.
+
+use crate::{Lua, LuaApi, LuaFunction, LuaResult, SafeOption, Stdlib};
+
+/// Libraries for pure computation benches. Leaves out io/os/package/debug so
+/// a bench can neither touch the host nor pay for loading them.
+pub const COMPUTE_LIBS: &[Stdlib] = &[
+    Stdlib::Basic,
+    Stdlib::String,
+    Stdlib::Table,
+    Stdlib::Math,
+    Stdlib::Coroutine,
+];
+
+/// Create a `Lua` with only `libs` opened.
+pub fn new_lua(libs: &[Stdlib]) -> LuaResult<Lua> {
+    let mut lua = Lua::new(SafeOption::default());
+    lua.open_stdlibs(libs)?;
+    Ok(lua)
+}
+
+/// Compile `source` once and return it as a callable function, so the
+/// measured loop only covers execution.
+pub fn compile(lua: &mut Lua, name: &str, source: &str) -> LuaResult<LuaFunction> {
+    lua.load(source).set_name(name).into_function()
+}
+
+/// A machine-written config chunk of about `bytes` bytes: one big table
+/// constructor whose records repeat the same field names and tag strings,
+/// the shape that makes compile time matter.
+pub fn config_source(bytes: usize) -> String {
+    let mut source = String::from("return {\n");
+    let mut i = 0;
+    while source.len() < bytes {
+        source.push_str(&format!(
+            "  {{ id = {i}, name = \"item_{i}\", enabled = {}, weight = {}.5, \
+             tags = {{ \"alpha\", \"beta\", \"gamma\" }}, \
+             position = {{ x = {}, y = {}, z = 0 }}, parent = \"group_{}\" }},\n",
+            i % 2 == 0,
+            i % 100,
+            i % 640,
+            i % 480,
+            i % 50,
+        ));
+        i += 1;
+    }
+    source.push_str("}\n");
+    source
+}
@@ -0,0 +1,556 @@
+// Direct bytecode construction for code generators that do not go through
+// the Lua parser. Mirrors the parts of lcode.c that do not depend on
+// expression state: instruction encoding, constant deduplication (k2proto),
+// EXTRAARG emission and the final fixups of luaK_finish.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::compiler::ConstantKey;
+use crate::lua_value::{LocVar, LuaProto, LuaValue, UpvalueDesc, verify_proto};
+use crate::lua_vm::lua_limits::{MAXINDEXRK, MAXUPVAL, MAXVARS};
+use crate::lua_vm::opcode::OpMode;
+use crate::lua_vm::{GlobalState, Instruction, OpCode};
+
+struct FuncBuilder {
+    proto: LuaProto,
+    kcache: HashMap<ConstantKey, u32>,
+    /// Indices into `proto.locals` of the variables still in scope.
+    active_locals: Vec<usize>,
+    needclose: bool,
+}
+
+impl FuncBuilder {
+    fn new(source_name: Arc<str>, param_count: usize, is_vararg: bool, line: u32) -> Self {
+        let mut proto = LuaProto::new();
+        proto.source_name = Some(source_name);
+        proto.param_count = param_count;
+        proto.is_vararg = is_vararg;
+        proto.use_hidden_vararg = is_vararg;
+        proto.max_stack_size = param_count.max(2);
+        proto.linedefined = line as usize;
+        FuncBuilder {
+            proto,
+            kcache: HashMap::new(),
+            active_locals: Vec::new(),
+            needclose: false,
+        }
+    }
+}
+
+/// Builds a [`LuaProto`] instruction by instruction.
+///
+/// The builder keeps a stack of open functions: [`BytecodeBuilder::new`]
+/// opens the main chunk, [`open_function`](BytecodeBuilder::open_function)
+/// starts a nested prototype and
+/// [`close_function`](BytecodeBuilder::close_function) attaches it to its
+/// parent, returning the index `CLOSURE` expects. Every `emit_*` call
+/// checks that the opcode belongs to the instruction format and that each
+/// operand fits its field; [`finish`](BytecodeBuilder::finish) then checks
+/// the function as a whole (constant, upvalue and prototype references,
+/// jump targets, instructions that must be followed by `EXTRAARG`, `JMP`
+/// or `MMBIN*`) before handing out a prototype the VM can run, for
+/// instance through `LuaState::load_proto`.
+///
+/// Registers are not allocated by the builder. The frame size grows to
+/// cover every register named by an `A` operand; instructions that write a
+/// range of registers (`CALL` results, `VARARG`, `LOADNIL`, loops) need
+/// [`reserve_registers`](BytecodeBuilder::reserve_registers).
+pub struct BytecodeBuilder<'vm> {
+    vm: &'vm mut GlobalState,
+    source_name: Arc<str>,
+    line: u32,
+    funcs: Vec<FuncBuilder>,
+}
+
+impl<'vm> BytecodeBuilder<'vm> {
+    /// Start a main chunk named `chunk_name` (e.g. `"=dsl"` or `"@file"`).
+    ///
+    /// Like a chunk produced by the compiler, it is vararg, has `_ENV` as
+    /// its only upvalue and starts with `VARARGPREP`.
+    pub fn new(vm: &'vm mut GlobalState, chunk_name: &str) -> Self {
+        let source_name: Arc<str> = Arc::from(chunk_name);
+        let mut main = FuncBuilder::new(source_name.clone(), 0, true, 0);
+        main.proto.upvalue_descs.push(UpvalueDesc {
+            name: "_ENV".to_string(),
+            is_local: true,
+            index: 0,
+        });
+        let mut builder = BytecodeBuilder {
+            vm,
+            source_name,
+            line: 1,
+            funcs: vec![main],
+        };
+        builder.push(Instruction::create_abc(OpCode::VarargPrep, 0, 0, 0));
+        builder
+    }
+
+    fn current(&self) -> &FuncBuilder {
+        self.funcs
+            .last()
+            .expect("builder always has an open function")
+    }
+
+    fn current_mut(&mut self) -> &mut FuncBuilder {
+        self.funcs
+            .last_mut()
+            .expect("builder always has an open function")
+    }
+
+    fn push(&mut self, instr: Instruction) -> usize {
+        let line = self.line;
+        let proto = &mut self.current_mut().proto;
+        proto.code.push(instr);
+        proto.line_info.push(line);
+        proto.code.len() - 1
+    }
+
+    /// Source line recorded for the instructions emitted from now on.
+    pub fn set_line(&mut self, line: u32) {
+        self.line = line;
+    }
+
+    /// Position of the next instruction of the current function.
+    pub fn pc(&self) -> usize {
+        self.current().proto.code.len()
+    }
+
+    // ======== Functions ========
+
+    /// Open a nested function; following calls apply to it until the
+    /// matching [`close_function`](Self::close_function). A vararg function
+    /// starts with `VARARGPREP`, as the compiler emits it.
+    pub fn open_function(&mut self, param_count: usize, is_vararg: bool) -> Result<(), String> {
+        if param_count > MAXVARS {
+            return Err(format!("too many parameters ({param_count} > {MAXVARS})"));
+        }
+        let func = FuncBuilder::new(self.source_name.clone(), param_count, is_vararg, self.line);
+        self.funcs.push(func);
+        if is_vararg {
+            self.push(Instruction::create_abc(OpCode::VarargPrep, 0, 0, 0));
+        }
+        Ok(())
+    }
+
+    /// Close the current nested function and add it to its parent's
+    /// prototypes. Returns its index, the `Bx` operand of `CLOSURE`.
+    pub fn close_function(&mut self) -> Result<u32, String> {
+        if self.funcs.len() < 2 {
+            return Err("no nested function to close".to_string());
+        }
+        let mut func = self.funcs.pop().expect("checked above");
+        func.proto.lastlinedefined = self.line as usize;
+        let parent = self.current_mut();
+        for desc in &func.proto.upvalue_descs {
+            // A captured local must be closed when the parent returns
+            if desc.is_local {
+                parent.needclose = true;
+            } else if desc.index as usize >= parent.proto.upvalue_descs.len() {
+                return Err(format!(
+                    "upvalue '{}' refers to missing enclosing upvalue {}",
+                    desc.name, desc.index
+                ));
+            }
+        }
+        let proto = finish_function(func)?;
+        let index = self.current().proto.child_protos.len();
+        if index > Instruction::MAX_BX as usize {
+            return Err("too many nested functions".to_string());
+        }
+        let ptr = self.vm.create_proto(proto).map_err(|e| e.to_string())?;
+        self.current_mut().proto.child_protos.push(ptr);
+        Ok(index as u32)
+    }
+
+    /// Finish the main chunk. Fails if a nested function is still open or
+    /// the bytecode does not pass validation.
+    pub fn finish(mut self) -> Result<LuaProto, String> {
+        if self.funcs.len() != 1 {
+            return Err(format!(
+                "{} nested function(s) still open",
+                self.funcs.len() - 1
+            ));
+        }
+        finish_function(self.funcs.pop().expect("checked above"))
+    }
+
+    // ======== Debug information and registers ========
+
+    /// Declare an upvalue of the current function with its debug name.
+    /// `in_parent_stack` selects whether `index` is a register of the
+    /// enclosing function or one of its upvalues. Returns the upvalue index.
+    pub fn add_upvalue(
+        &mut self,
+        name: &str,
+        in_parent_stack: bool,
+        index: u32,
+    ) -> Result<u32, String> {
+        if self.funcs.len() < 2 {
+            return Err("the main chunk has '_ENV' as its only upvalue".to_string());
+        }
+        if in_parent_stack && index as usize >= MAXINDEXRK {
+            return Err(format!(
+                "register {index} out of range for upvalue '{name}'"
+            ));
+        }
+        let descs = &mut self.current_mut().proto.upvalue_descs;
+        if descs.len() >= MAXUPVAL {
+            return Err(format!("too many upvalues (limit is {MAXUPVAL})"));
+        }
+        descs.push(UpvalueDesc {
+            name: name.to_string(),
+            is_local: in_parent_stack,
+            index,
+        });
+        Ok((descs.len() - 1) as u32)
+    }
+
+    /// Start a local variable at the current pc. Locals are stacked like
+    /// in the parser, so the variable lives in the register after the
+    /// locals still in scope; that register is returned. Parameters are
+    /// the first locals of a function: name them with this too.
+    pub fn open_local(&mut self, name: &str) -> Result<u32, String> {
+        let startpc = self.pc() as u32;
+        let func = self.current_mut();
+        let reg = func.active_locals.len();
+        if reg >= MAXVARS {
+            return Err(format!("too many local variables (limit is {MAXVARS})"));
+        }
+        func.active_locals.push(func.proto.locals.len());
+        func.proto.locals.push(LocVar {
+            name: name.to_string(),
+            startpc,
+            endpc: startpc,
+        });
+        func.proto.max_stack_size = func.proto.max_stack_size.max(reg + 1);
+        Ok(reg as u32)
+    }
+
+    /// End the most recently opened local variable at the current pc.
+    pub fn close_local(&mut self) -> Result<(), String> {
+        let endpc = self.pc() as u32;
+        let func = self.current_mut();
+        let index = func
+            .active_locals
+            .pop()
+            .ok_or_else(|| "no open local variable to close".to_string())?;
+        func.proto.locals[index].endpc = endpc;
+        Ok(())
+    }
+
+    /// Make sure the frame has at least `count` registers.
+    pub fn reserve_registers(&mut self, count: usize) -> Result<(), String> {
+        if count > MAXINDEXRK {
+            return Err("function or expression needs too many registers".to_string());
+        }
+        let proto = &mut self.current_mut().proto;
+        proto.max_stack_size = proto.max_stack_size.max(count);
+        Ok(())
+    }
+
+    // ======== Constants ========
+
+    /// Add a constant (nil, boolean, number or string), reusing an equal
+    /// one already in the current function. Returns its index.
+    pub fn add_constant(&mut self, value: LuaValue) -> Result<u32, String> {
+        let Some(key) = ConstantKey::of(value) else {
+            return Err(format!("{:?} values cannot be constants", value.kind()));
+        };
+        let func = self.current_mut();
+        if let Some(&index) = func.kcache.get(&key) {
+            return Ok(index);
+        }
+        let index = func.proto.constants.len();
+        if index > Instruction::MAX_AX as usize {
+            return Err("too many constants".to_string());
+        }
+        func.proto.constants.push(value);
+        func.kcache.insert(key, index as u32);
+        Ok(index as u32)
+    }
+
+    /// Add a string constant. Returns its index.
+    pub fn add_string_constant(&mut self, s: &str) -> Result<u32, String> {
+        let value = self.vm.create_string(s).map_err(|e| e.to_string())?;
+        self.add_constant(value)
+    }
+
+    // ======== Instruction formats ========
+
+    /// Emit an iABC instruction.
+    pub fn emit_abc(
+        &mut self,
+        op: OpCode,
+        a: u32,
+        b: u32,
+        c: u32,
+        k: bool,
+    ) -> Result<usize, String> {
+        check_mode(op, OpMode::IABC)?;
+        check_arg(op, "A", a, Instruction::MAX_A)?;
+        check_arg(op, "B", b, Instruction::MAX_B)?;
+        check_arg(op, "C", c, Instruction::MAX_C)?;
+        self.grow_frame(op, a);
+        if op == OpCode::Tbc {
+            self.current_mut().needclose = true;
+        }
+        Ok(self.push(Instruction::create_abck(op, a, b, c, k)))
+    }
+
+    /// Emit an ivABC instruction (`NEWTABLE`, `SETLIST`).
+    pub fn emit_vabc(
+        &mut self,
+        op: OpCode,
+        a: u32,
+        vb: u32,
+        vc: u32,
+        k: bool,
+    ) -> Result<usize, String> {
+        check_mode(op, OpMode::IvABC)?;
+        check_arg(op, "A", a, Instruction::MAX_A)?;
+        check_arg(op, "vB", vb, Instruction::MAX_V_B)?;
+        check_arg(op, "vC", vc, Instruction::MAX_V_C)?;
+        self.grow_frame(op, a);
+        Ok(self.push(Instruction::create_vabck(op, a, vb, vc, k)))
+    }
+
+    /// Emit an iABx instruction.
+    pub fn emit_abx(&mut self, op: OpCode, a: u32, bx: u32) -> Result<usize, String> {
+        check_mode(op, OpMode::IABx)?;
+        check_arg(op, "A", a, Instruction::MAX_A)?;
+        check_arg(op, "Bx", bx, Instruction::MAX_BX)?;
+        self.grow_frame(op, a);
+        Ok(self.push(Instruction::create_abx(op, a, bx)))
+    }
+
+    /// Emit an iAsBx instruction.
+    pub fn emit_asbx(&mut self, op: OpCode, a: u32, sbx: i32) -> Result<usize, String> {
+        check_mode(op, OpMode::IAsBx)?;
+        check_arg(op, "A", a, Instruction::MAX_A)?;
+        check_signed_arg(op, "sBx", sbx, Instruction::MAX_BX, Instruction::OFFSET_SBX)?;
+        self.grow_frame(op, a);
+        Ok(self.push(Instruction::create_asbx(op, a, sbx)))
+    }
+
+    /// Emit an iAx instruction (`EXTRAARG`).
+    pub fn emit_ax(&mut self, op: OpCode, ax: u32) -> Result<usize, String> {
+        check_mode(op, OpMode::IAx)?;
+        check_arg(op, "Ax", ax, Instruction::MAX_AX)?;
+        Ok(self.push(Instruction::create_ax(op, ax)))
+    }
+
+    /// Emit an isJ instruction (`JMP`) with a raw offset.
+    pub fn emit_sj(&mut self, op: OpCode, sj: i32) -> Result<usize, String> {
+        check_mode(op, OpMode::IsJ)?;
+        check_signed_arg(op, "sJ", sj, Instruction::MAX_SJ, Instruction::OFFSET_SJ)?;
+        Ok(self.push(Instruction::create_sj(op, sj)))
+    }
+
+    fn grow_frame(&mut self, op: OpCode, a: u32) {
+        if a_is_register(op) {
+            let proto = &mut self.current_mut().proto;
+            proto.max_stack_size = proto.max_stack_size.max(a as usize + 1);
+        }
+    }
+
+    // ======== Common sequences ========
+
+    /// `R[reg] := K[k]`, as `LOADK` or, past the reach of Bx, `LOADKX`
+    /// followed by `EXTRAARG` (luaK_codek).
+    pub fn load_constant(&mut self, reg: u32, k: u32) -> Result<usize, String> {
+        if k <= Instruction::MAX_BX {
+            self.emit_abx(OpCode::LoadK, reg, k)
+        } else {
+            let pc = self.emit_abx(OpCode::LoadKX, reg, 0)?;
+            self.emit_ax(OpCode::ExtraArg, k)?;
+            Ok(pc)
+        }
+    }
+
+    /// `R[reg] := i`, as `LOADI` when it fits sBx and through a constant
+    /// otherwise (luaK_int).
+    pub fn load_integer(&mut self, reg: u32, i: i64) -> Result<usize, String> {
+        let fits = -(Instruction::OFFSET_SBX as i64)
+            ..=(Instruction::MAX_BX as i64 - Instruction::OFFSET_SBX as i64);
+        if fits.contains(&i) {
+            self.emit_asbx(OpCode::LoadI, reg, i as i32)
+        } else {
+            let k = self.add_constant(LuaValue::integer(i))?;
+            self.load_constant(reg, k)
+        }
+    }
+
+    /// `R[reg] := {}` sized for `array_size` list items and `hash_size`
+    /// fields, followed by its `EXTRAARG` (luaK_settablesize).
+    pub fn new_table(
+        &mut self,
+        reg: u32,
+        array_size: u32,
+        hash_size: u32,
+    ) -> Result<usize, String> {
+        let rb = if hash_size != 0 {
+            32 - (hash_size - 1).leading_zeros() + 1
+        } else {
+            0
+        };
+        let extra = array_size / (Instruction::MAX_V_C + 1);
+        let rc = array_size % (Instruction::MAX_V_C + 1);
+        let pc = self.emit_vabc(OpCode::NewTable, reg, rb, rc, extra > 0)?;
+        self.emit_ax(OpCode::ExtraArg, extra)?;
+        Ok(pc)
+    }
+
+    /// `R[base][first+i] := R[base+i]` for `1 <= i <= count`; `count` of
+    /// `None` stores up to the top (luaK_setlist).
+    pub fn set_list(&mut self, base: u32, first: u32, count: Option<u32>) -> Result<usize, String> {
+        let vb = count.unwrap_or(0);
+        if first <= Instruction::MAX_V_C {
+            self.emit_vabc(OpCode::SetList, base, vb, first, false)
+        } else {
+            let extra = first / (Instruction::MAX_V_C + 1);
+            let rc = first % (Instruction::MAX_V_C + 1);
+            let pc = self.emit_vabc(OpCode::SetList, base, vb, rc, true)?;
+            self.emit_ax(OpCode::ExtraArg, extra)?;
+            Ok(pc)
+        }
+    }
+
+    /// Return `count` values starting at `first`, or everything up to the
+    /// top for `None`, picking `RETURN0`/`RETURN1` when possible (luaK_ret).
+    pub fn ret(&mut self, first: u32, count: Option<u32>) -> Result<usize, String> {
+        match count {
+            Some(0) => self.emit_abc(OpCode::Return0, first, 1, 0, false),
+            Some(1) => self.emit_abc(OpCode::Return1, first, 2, 0, false),
+            Some(n) => self.emit_abc(OpCode::Return, first, n + 1, 0, false),
+            None => self.emit_abc(OpCode::Return, first, 0, 0, false),
+        }
+    }
+
+    /// Emit a `JMP` to be patched later with [`patch_jump`](Self::patch_jump).
+    pub fn jump(&mut self) -> Result<usize, String> {
+        self.emit_sj(OpCode::Jmp, 0)
+    }
+
+    /// Point the jump at `pc` to `target`. Handles `JMP` and the loop
+    /// instructions (`FORPREP`, `FORLOOP`, `TFORPREP`, `TFORLOOP`), each
+    /// with its own offset convention.
+    pub fn patch_jump(&mut self, pc: usize, target: usize) -> Result<(), String> {
+        let code = &mut self.current_mut().proto.code;
+        let instr = code
+            .get(pc)
+            .copied()
+            .ok_or_else(|| format!("no instruction at pc {pc}"))?;
+        let op = instr.get_opcode();
+        let (pc, target) = (pc as i64, target as i64);
+        let patched = match op {
+            OpCode::Jmp => {
+                let sj = target - (pc + 1);
+                check_signed_arg(
+                    op,
+                    "sJ",
+                    sj as i32,
+                    Instruction::MAX_SJ,
+                    Instruction::OFFSET_SJ,
+                )?;
+                Instruction::create_sj(op, sj as i32)
+            }
+            OpCode::ForPrep | OpCode::TForPrep | OpCode::ForLoop | OpCode::TForLoop => {
+                let bx = match op {
+                    OpCode::ForPrep => target - (pc + 2),
+                    OpCode::TForPrep => target - (pc + 1),
+                    _ => (pc + 1) - target,
+                };
+                if !(0..=Instruction::MAX_BX as i64).contains(&bx) {
+                    return Err(format!("{op:?} at pc {pc} cannot reach pc {target}"));
+                }
+                Instruction::create_abx(op, instr.get_a(), bx as u32)
+            }
+            _ => return Err(format!("instruction at pc {pc} is not a jump")),
+        };
+        code[pc as usize] = patched;
+        Ok(())
+    }
+}
+
+fn check_mode(op: OpCode, mode: OpMode) -> Result<(), String> {
+    if op == OpCode::None || op.get_mode() != mode {
+        return Err(format!("{op:?} is not an {mode:?} instruction"));
+    }
+    Ok(())
+}
+
+fn check_arg(op: OpCode, field: &str, value: u32, max: u32) -> Result<(), String> {
+    if value > max {
+        return Err(format!(
+            "operand {field} of {op:?} out of range ({value} > {max})"
+        ));
+    }
+    Ok(())
+}
+
+fn check_signed_arg(
+    op: OpCode,
+    field: &str,
+    value: i32,
+    max: u32,
+    offset: i32,
+) -> Result<(), String> {
+    let range = -offset..=(max as i32 - offset);
+    if !range.contains(&value) {
+        return Err(format!(
+            "operand {field} of {op:?} out of range ({value} not in {}..={})",
+            range.start(),
+            range.end()
+        ));
+    }
+    Ok(())
+}
+
+/// Whether operand A names a register of the frame (see the executor's
+/// `register_in_frame` check).
+fn a_is_register(op: OpCode) -> bool {
+    !matches!(
+        op,
+        OpCode::Jmp
+            | OpCode::ExtraArg
+            | OpCode::SetTabUp
+            | OpCode::Return
+            | OpCode::Return0
+            | OpCode::VarargPrep
+    )
+}
+
+// Port of luaK_finish (lcode.c), then the verifier binary chunks go
+// through: a code generator has none of the parser's guarantees. Nested
+// functions were verified when they were closed.
+fn finish_function(mut func: FuncBuilder) -> Result<LuaProto, String> {
+    for &index in &func.active_locals {
+        func.proto.locals[index].endpc = func.proto.code.len() as u32;
+    }
+    func.proto.upvalue_count = func.proto.upvalue_descs.len();
+    let mut proto = func.proto;
+
+    let use_hidden_vararg = proto.use_hidden_vararg;
+    for instr in &mut proto.code {
+        let op = instr.get_opcode();
+        if matches!(op, OpCode::Return0 | OpCode::Return1) && (func.needclose || use_hidden_vararg)
+        {
+            let b = if op == OpCode::Return0 { 1 } else { 2 };
+            *instr = Instruction::create_abc(OpCode::Return, instr.get_a(), b, 0);
+        }
+        if matches!(instr.get_opcode(), OpCode::Return | OpCode::TailCall) {
+            if func.needclose {
+                instr.set_k(true);
+            }
+            if use_hidden_vararg {
+                instr.set_c((proto.param_count + 1) as u32);
+            }
+        }
+    }
+
+    verify_proto(&proto)?;
+    proto.init_key_hints();
+    proto.compute_proto_data_size();
+    Ok(proto)
+}
@@ -2,12 +2,14 @@
 // This file corresponds to lua-5.5.0/src/lcode.c
 use crate::LuaValue;
 use crate::compiler::expression::{ExpDesc, ExpKind};
-use crate::compiler::func_state::FuncState;
+use crate::compiler::func_state::{ConstantKey, FuncState};
 use crate::compiler::parser::BinaryOperator;
 use crate::compiler::{ExpUnion, IndVars};
 use crate::lua_value::LuaValueKind;
 use crate::lua_vm::lua_limits::{MAXINDEXRK, NO_REG};
-use crate::lua_vm::{Instruction, OpCode, TmKind, lua_shiftl, luai_numpow};
+use crate::lua_vm::{
+    Instruction, OpCode, TmKind, lua_fmod, lua_idiv, lua_imod, lua_shiftl, luai_numpow,
+};
 
 // Port of int2sC from lcode.c (macro)
 // Convert integer to sC format (with OFFSET_sC = 128)
@@ -774,10 +776,13 @@ pub fn free_regs(fs: &mut FuncState, r1: u8, r2: u8) {
 pub fn checkstack(fs: &mut FuncState, n: u8) {
     let newstack = (fs.freereg as usize) + (n as usize);
     if newstack > MAXINDEXRK {
-        // MAX_FSTACK = MAXARG_A = 255
-        // Port of luaY_checklimit(fs, newstack, MAX_FSTACK, "registers")
+        // MAX_FSTACK = MAXARG_A = 255: register operands are 8 bits wide, so a
+        // frame can never address more than 255 slots.
+        // Reported through the deferred checklimit error; `statement` surfaces
+        // it before any instruction using the wrapped register can run.
         if fs.checklimit_error.is_none() {
-            fs.checklimit_error = Some(fs.errorlimit(MAXINDEXRK, "registers"));
+            fs.checklimit_error =
+                Some(fs.syntax_error("function or expression needs too many registers"));
         }
     }
     if newstack > fs.chunk.max_stack_size {
@@ -903,17 +908,9 @@ fn bool_f(fs: &mut FuncState) -> usize {
 // Add nil to constants
 // Port of nilK from lcode.c:665-671
 fn nil_k(fs: &mut FuncState) -> usize {
-    // Cannot use nil as key; instead use table itself as key (lcode.c:669)
-    // static int nilK (FuncState *fs) {
-    //   TValue k, v;
-    //   setnilvalue(&v);
-    //   /* cannot use nil as key; instead use table itself */
-    //   sethvalue(fs->ls->L, &k, fs->kcache);
-    //   return k2proto(fs, &k, &v);
-    // }
-    let key = fs.kcache;
-    let val = LuaValue::nil();
-    add_constant_with_key(fs, key, val)
+    // lcode.c keys nil with the kcache table itself, since nil cannot be a
+    // table key; ConstantKey has a variant for it instead.
+    add_constant(fs, LuaValue::nil())
 }
 
 // Add integer to constants
@@ -925,55 +922,10 @@ fn int_k(fs: &mut FuncState, i: i64) -> usize {
 // Add number to constants
 // Port of luaK_numberK from lcode.c:619-639
 fn number_k(fs: &mut FuncState, n: f64) -> usize {
-    let val = LuaValue::float(n);
-
-    // Special handling for float 0.0 to avoid collision with integer 0
-    // Port of lcode.c:621-624:
-    //   if (r == 0) {
-    //     setpvalue(&kv, fs);  /* use FuncState as index */
-    //     return k2proto(fs, &kv, &o);  /* cannot collide */
-    //   }
-    if n == 0.0 {
-        // Use FuncState pointer as key (like official Lua does)
-        // This prevents collision with integer 0 AND with nil (which uses kcache table as key)
-        let fs_ptr = fs as *const FuncState as *mut std::ffi::c_void;
-        let key = LuaValue::lightuserdata(fs_ptr);
-        return add_constant_with_key(fs, key, val);
-    }
-
-    // Port of lcode.c:626-638: use perturbed key to avoid integer collision
-    // const int nbm = l_floatatt(MANT_DIG);  // 53 for double
-    // const lua_Number q = l_mathop(ldexp)(l_mathop(1.0), -nbm + 1);  // 2^-52
-    // const lua_Number k =  r * (1 + q);  /* key */
-    const MANT_DIG: i32 = 53; // mantissa digits for f64
-    let q = 2.0_f64.powi(-MANT_DIG + 1); // 2^-52
-    let k = n * (1.0 + q); // perturbed key
-
-    // Check if perturbed key can be converted to integer (lcode.c:630)
-    // If yes, don't use kcache (would collide), just create new entry
-    if k.floor() == k && k >= i64::MIN as f64 && k < -(i64::MIN as f64) {
-        // Key is still an integer, would collide - create new entry directly
-        // Port of lcode.c:636: return addk(fs, fs->f, &o);
-        let idx = fs.chunk.constants.len();
-        fs.chunk.constants.push(val);
-        return idx;
-    }
-
-    // Use perturbed key for kcache lookup (lcode.c:631-633)
-    let key = LuaValue::float(k);
-    let idx = add_constant_with_key(fs, key, val);
-
-    // Verify the stored value matches (lcode.c:632)
-    if let Some(existing) = fs.chunk.constants.get(idx)
-        && val == *existing
-    {
-        return idx;
-    }
-
-    // Collision detected - create new entry without caching (lcode.c:636)
-    let idx = fs.chunk.constants.len();
-    fs.chunk.constants.push(val);
-    idx
+    // lcode.c perturbs float keys so they cannot collide with integer keys
+    // (or each other, for 0.0 and -0.0) in the kcache table. ConstantKey
+    // keys floats by their bits, which keeps them apart on its own.
+    add_constant(fs, LuaValue::float(n))
 }
 
 // Add an integer constant to the constant table
@@ -986,19 +938,19 @@ fn integer_k(fs: &mut FuncState, i: i64) -> usize {
 // Port of stringK from lcode.c:576-580
 // static int stringK (FuncState *fs, TString *s)
 pub fn string_k(fs: &mut FuncState, s: &str) -> usize {
-    // Intern string to ObjectPool and get StringId
+    // Intern the string (short strings are deduplicated by the interner)
     let string = fs.vm.create_string(s).unwrap();
 
-    // Add LuaValue with StringId to constants (check for duplicates)
+    // Add the string value to constants (check for duplicates)
     // For strings, key == value (strings are deduplicated globally)
     add_constant(fs, string)
 }
 
 pub fn binary_k(fs: &mut FuncState, v: Vec<u8>) -> usize {
-    // Intern binary data to ObjectPool and get BinaryId
+    // Allocate the binary data as a GC object
     let binary = fs.vm.create_binary(v).unwrap();
 
-    // Add LuaValue with BinaryId to constants (check for duplicates)
+    // Add the binary value to constants (check for duplicates)
     // For binaries, key == value (binaries are deduplicated globally)
     add_constant(fs, binary)
 }
@@ -1029,64 +981,19 @@ fn str2k(fs: &mut FuncState, e: &mut ExpDesc) -> usize {
     k
 }
 
-// Helper to add constant to chunk
-// Port of addk from lcode.c:544-571
-// key: The key used for lookup in scanner table (for deduplication)
-// value: The actual value to store in the constant table
-//
-// For nil/false/true, key should be a special sentinel value to ensure global deduplication
-// For strings, key == value (strings are deduplicated globally)
-// For other types (numbers), key == value (numbers are deduplicated per-function)
-// but we use rust's HashMap to handle that, so we just pass the same value for both key and value.
-// Port of k2proto from lcode.c:565-575 (with separate key)
-// static int k2proto (FuncState *fs, TValue *key, TValue *v)
-fn add_constant_with_key(fs: &mut FuncState, key: LuaValue, value: LuaValue) -> usize {
-    // Lazy-init kcache table (only when first constant needs deduplication)
-    if fs.kcache.is_nil() {
-        fs.kcache = fs.vm.create_table(0, 0).unwrap();
-    }
-
-    // Query kcache table with key (lcode.c:567)
-    let found_idx: Option<usize> = {
-        if let Some(kcache_table) = fs.kcache.as_table_mut() {
-            // luaH_get returns the value if found (stored as integer index)
-            if let Some(idx_value) = kcache_table.raw_get(&key) {
-                idx_value.as_integer().map(|i| i as usize)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    };
-
-    if let Some(idx) = found_idx {
-        // Check if we can reuse this constant (lcode.c:568-572)
-        if idx < fs.chunk.constants.len()
-            && let Some(existing) = fs.chunk.constants.get(idx)
-        {
-            // Must match value (lcode.c:570-571)
-            // Note: For floats, collisions can happen; for non-floats, must be equal
-            if value == *existing {
-                return idx;
-            }
-        }
-    }
-
-    // Constant not found or cannot be reused; create a new entry (lcode.c:573-577)
-    let idx = fs.chunk.constants.len();
-    fs.chunk.constants.push(value);
-
-    // Store key->index mapping in kcache table (lcode.c:575-576)
-    fs.vm
-        .raw_set(&fs.kcache, key, LuaValue::integer(idx as i64));
-
-    idx
-}
-
-// Port of k2proto from lcode.c:565-575 (when key == value)
+// Helper to add constant to chunk, reusing an equal constant already there
+// Port of k2proto/addk from lcode.c:544-575
 fn add_constant(fs: &mut FuncState, value: LuaValue) -> usize {
-    add_constant_with_key(fs, value, value)
+    let Some(key) = ConstantKey::of(value) else {
+        // Not a constant type; never shared
+        fs.chunk.constants.push(value);
+        return fs.chunk.constants.len() - 1;
+    };
+    let constants = &mut fs.chunk.constants;
+    *fs.kcache.entry(key).or_insert_with(|| {
+        constants.push(value);
+        constants.len() - 1
+    })
 }
 
 // Port of luaK_exp2K from lcode.c:1000-1026
@@ -1648,32 +1555,10 @@ fn constfolding(_fs: &FuncState, op: BinaryOperator, e1: &mut ExpDesc, e2: &ExpD
                     OpAdd => i1.checked_add(i2),
                     OpSub => i1.checked_sub(i2),
                     OpMul => i1.checked_mul(i2),
-                    OpIDiv => {
-                        // Lua floor division: a // b = floor(a/b)
-                        if i2 == -1 {
-                            i1.checked_neg() // handles MIN_INT overflow
-                        } else {
-                            let q = i1 / i2;
-                            if (i1 ^ i2) < 0 && i1 % i2 != 0 {
-                                Some(q - 1)
-                            } else {
-                                Some(q)
-                            }
-                        }
-                    }
-                    OpMod => {
-                        // Lua modulo: m = a % b; if m != 0 && (m ^ b) < 0 then m += b
-                        if i2 == -1 {
-                            Some(0) // MIN_INT % -1 = 0, any x % -1 = 0
-                        } else {
-                            let m = i1 % i2;
-                            if m != 0 && (m ^ i2) < 0 {
-                                Some(m + i2)
-                            } else {
-                                Some(m)
-                            }
-                        }
-                    }
+                    // validop has ruled out a zero divisor; minint // -1
+                    // wraps like luaV_idiv does at run time
+                    OpIDiv => Some(lua_idiv(i1, i2)),
+                    OpMod => Some(lua_imod(i1, i2)),
                     _ => unreachable!(),
                 };
 
@@ -1691,7 +1576,7 @@ fn constfolding(_fs: &FuncState, op: BinaryOperator, e1: &mut ExpDesc, e2: &ExpD
                 OpSub => v1 - v2,
                 OpMul => v1 * v2,
                 OpIDiv => (v1 / v2).floor(),
-                OpMod => v1 - (v1 / v2).floor() * v2,
+                OpMod => lua_fmod(v1, v2),
                 _ => unreachable!(),
             };
 
@@ -1722,7 +1607,10 @@ pub fn posfix(
     discharge_vars(fs, e2);
 
     // Try constant folding first (lcode.c:1709)
-    if foldbinop(op) && constfolding(fs, op, e1, e2) {
+    if foldbinop(op)
+        && fs.vm.safe_option.compile_options.constant_folding
+        && constfolding(fs, op, e1, e2)
+    {
         return; // done by folding
     }
 
@@ -1745,7 +1633,8 @@ pub fn posfix(
             // from a string constant via LOADK, fold them at compile time.
             // This handles chains like "a" .. "b" .. "c" (right-associative),
             // folding them into a single constant with zero runtime cost.
-            if e2.kind == ExpKind::VKSTR
+            if fs.vm.safe_option.compile_options.concat_folding
+                && e2.kind == ExpKind::VKSTR
                 && e1.kind == ExpKind::VNONRELOC
                 && !e1.has_jumps()
                 && fs.pc > 0
@@ -2235,7 +2124,7 @@ pub fn prefix(fs: &mut FuncState, op: OpCode, e: &mut ExpDesc, line: usize) {
             // But only if expression has no jumps (lcode.c:1704: if (!hasjumps(e)))
             // luaO_rawarith(L, LUA_OPUNM, v1, v2, res) where v2 = 0
             // intarith: case LUA_OPUNM: return intop(-, 0, v1)
-            if !e.has_jumps() {
+            if !e.has_jumps() && fs.vm.safe_option.compile_options.constant_folding {
                 match e.kind {
                     ExpKind::VKINT => {
                         // Integer negation
@@ -2266,7 +2155,7 @@ pub fn prefix(fs: &mut FuncState, op: OpCode, e: &mut ExpDesc, line: usize) {
             // luaO_rawarith(L, LUA_OPBNOT, v1, v2, res) where v2 = 0
             // For BNOT, both operands must be convertible to integer (lobject.c:158)
             // intarith: case LUA_OPBNOT: return intop(^, ~l_castS2U(0), v1) = ~v1
-            if !e.has_jumps() {
+            if !e.has_jumps() && fs.vm.safe_option.compile_options.constant_folding {
                 match e.kind {
                     ExpKind::VKINT => {
                         // Integer bitwise not
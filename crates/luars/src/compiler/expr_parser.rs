@@ -293,11 +293,12 @@ fn funcargs(fs: &mut FuncState, f: &mut ExpDesc) -> Result<(), String> {
         }
         LuaTokenKind::TkString | LuaTokenKind::TkLongString => {
             // funcargs -> STRING
-            let text = fs.lexer.current_token_text();
-            let vec8 = parse_string_token_value(text, fs.lexer.current_token())?;
-            let k_idx = match String::from_utf8(vec8) {
-                Ok(s) => string_k(fs, &s),
-                Err(e) => binary_k(fs, e.into_bytes()),
+            let range = fs.lexer.current_token_range();
+            let text = &fs.lexer.origin_text()[range.start_offset..range.end_offset()];
+            let bytes = parse_string_token_value(text, fs.lexer.current_token())?;
+            let k_idx = match std::str::from_utf8(&bytes) {
+                Ok(s) => string_k(fs, s),
+                Err(_) => binary_k(fs, bytes.into_owned()),
             };
             fs.lexer.bump();
             args = ExpDesc::new_k(k_idx);
@@ -645,15 +646,12 @@ fn field(fs: &mut FuncState, cc: &mut ConsControl) -> Result<(), String> {
         // Save freereg to restore after processing field
         let saved_freereg = fs.freereg;
 
-        fs.lexer.bump();
+        // The key is fully evaluated (yindex) and indexed before the value
+        // expression, so side effects happen in source order.
         let mut key = ExpDesc::new_void();
-        expr_internal(fs, &mut key)?;
-        expect(fs, LuaTokenKind::TkRightBracket)?;
+        yindex(fs, &mut key)?;
         expect(fs, LuaTokenKind::TkAssign)?;
 
-        let mut val = ExpDesc::new_void();
-        expr_internal(fs, &mut val)?;
-
         // Port of recfield logic from lparser.c:847-867
         // Use indexed to determine VINDEXSTR vs VINDEXED based on key
         let mut tab = ExpDesc::new_void();
@@ -662,6 +660,9 @@ fn field(fs: &mut FuncState, cc: &mut ConsControl) -> Result<(), String> {
 
         code::indexed(fs, &mut tab, &mut key);
 
+        let mut val = ExpDesc::new_void();
+        expr_internal(fs, &mut val)?;
+
         // Generate appropriate store instruction based on tab.kind
         match tab.kind {
             ExpKind::VINDEXSTR => {
@@ -824,6 +825,17 @@ fn constructor(fs: &mut FuncState, v: &mut ExpDesc) -> Result<(), String> {
 
 // Port of body from lparser.c
 pub fn body(fs: &mut FuncState, v: &mut ExpDesc, is_method: bool) -> Result<(), String> {
+    named_body(fs, v, is_method, None)
+}
+
+/// `body` for a function statement, recording the name it is defined under
+/// (e.g. "M.sub.f" or "obj:method") for tracebacks.
+pub fn named_body(
+    fs: &mut FuncState,
+    v: &mut ExpDesc,
+    is_method: bool,
+    name: Option<String>,
+) -> Result<(), String> {
     // Record the line where function is defined
     let linedefined = fs.lexer.line;
 
@@ -994,6 +1006,7 @@ pub fn body(fs: &mut FuncState, v: &mut ExpDesc, is_method: bool) -> Result<(),
     child_chunk.linedefined = linedefined;
     child_chunk.lastlinedefined = lastlinedefined;
     child_chunk.source_name = Some(child_fs.source_name.clone());
+    child_chunk.name = name.map(std::sync::Arc::from);
     let child_upvalues = child_fs.upvalues;
 
     // Port of lparser.c:722-726 (codeclosure)
@@ -1009,6 +1022,7 @@ pub fn body(fs: &mut FuncState, v: &mut ExpDesc, is_method: bool) -> Result<(),
     child_chunk.upvalue_count = child_upvalues.len();
 
     // Cache proto data size for GC (avoid per-closure recalculation)
+    child_chunk.init_key_hints();
     child_chunk.compute_proto_data_size();
 
     // lparser.c:1005: Add child proto to parent (addprototype)
@@ -1,8 +1,11 @@
 use crate::LuaProto;
 use crate::compiler::{ExpDesc, ExpKind, ExpUnion, statement};
+use crate::lua_value::LuaValueKind;
 use crate::lua_vm::GlobalState;
 use crate::lua_vm::lua_limits::{MAX_SRC_LEN, MAXCCALLS, MAXUPVAL, MAXVARS};
 use crate::{LuaValue, compiler::parser::LuaLexer};
+use ahash::RandomState;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 // Upvalue descriptor
@@ -43,10 +46,38 @@ pub struct FuncState<'a> {
     pub numparams: u8,                 // number of fixed parameters (excluding vararg parameter)
     pub first_local: usize,            // index of first local variable in prev
     pub source_name: Arc<str>,         // source file name for error messages
-    pub kcache: LuaValue, // cache table for constant deduplication (per-function, like Lua 5.5's fs->kcache)
+    pub kcache: HashMap<ConstantKey, usize, RandomState>, // constant deduplication (per-function, like Lua 5.5's fs->kcache)
     pub checklimit_error: Option<String>, // deferred error from checkstack/checklimit
 }
 
+/// Deduplication key for a function's constants (the role of `fs->kcache`
+/// in lcode.c). Numbers are keyed by type and bits, so `1` and `1.0`, or
+/// `0.0` and `-0.0`, stay distinct constants without lcode.c's perturbed
+/// float keys. Strings hash with their precomputed hash and compare like
+/// Lua values, so a lookup neither copies nor rehashes the text.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConstantKey {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Float(u64),
+    String(LuaValue),
+}
+
+impl ConstantKey {
+    /// The key of `value`, or `None` if it cannot be a constant.
+    pub fn of(value: LuaValue) -> Option<Self> {
+        Some(match value.kind() {
+            LuaValueKind::Nil => ConstantKey::Nil,
+            LuaValueKind::Boolean => ConstantKey::Boolean(value.as_boolean() == Some(true)),
+            LuaValueKind::Integer => ConstantKey::Integer(value.as_integer_strict()?),
+            LuaValueKind::Float => ConstantKey::Float(value.as_float()?.to_bits()),
+            LuaValueKind::String => ConstantKey::String(value),
+            _ => return None,
+        })
+    }
+}
+
 pub struct CompilerState {
     // pool of BlockCnt structures (Option to allow safe take without invalidating indices)
     pub block_cnt_pool: Vec<Option<BlockCnt>>,
@@ -177,6 +208,47 @@ impl VarKind {
     }
 }
 
+/// The lookup half of lparser.c's searchvar: scan the first `nactvar`
+/// declarations of one function, innermost first, for `name`. Shared by
+/// the code generator and the syntax tree resolver so both agree on what
+/// a name refers to.
+///
+/// `decl(i)` gives the name and kind of declaration `i`. Returns the
+/// index of the matching local or named global declaration. On the way,
+/// `mark` is updated like searchvar's `var->u.info` for a global: -1 while
+/// global-by-default holds, -2 once a named global declaration voids it,
+/// and `first_local + i` for the innermost collective (`global *`)
+/// declaration.
+pub fn search_declarations<'a>(
+    nactvar: usize,
+    first_local: usize,
+    name: &str,
+    mark: &mut i32,
+    decl: impl Fn(usize) -> Option<(&'a str, VarKind)>,
+) -> Option<usize> {
+    for i in (0..nactvar).rev() {
+        let Some((vname, kind)) = decl(i) else {
+            continue;
+        };
+        if kind.is_global() {
+            if vname.is_empty() {
+                // lparser.c:420-422: first collective declaration?
+                if *mark < 0 {
+                    *mark = (first_local + i) as i32;
+                }
+            } else if vname == name {
+                return Some(i);
+            } else if *mark == -1 {
+                // lparser.c:428-429: invalidate preambular declaration
+                *mark = -2;
+            }
+        } else if vname == name {
+            return Some(i);
+        }
+    }
+    None
+}
+
 pub struct VarDesc {
     pub name: String,
     pub kind: VarKind, // variable kind
@@ -195,7 +267,6 @@ impl<'a> FuncState<'a> {
         is_vararg: bool,
         source_name: String,
     ) -> Self {
-        // kcache table created lazily in addk (only when constants need deduplication)
         FuncState {
             chunk: LuaProto::new(),
             prev: None,
@@ -218,7 +289,7 @@ impl<'a> FuncState<'a> {
             numparams: 0,
             source_name: Arc::<str>::from(source_name),
             first_local: 0,
-            kcache: LuaValue::nil(),
+            kcache: HashMap::default(),
             checklimit_error: None,
         }
     }
@@ -360,7 +431,7 @@ impl<'a> FuncState<'a> {
             numparams: 0,
             first_local: parent_ref.actvar.len(),
             source_name: parent_ref.source_name.clone(),
-            kcache: LuaValue::nil(),
+            kcache: HashMap::default(),
             checklimit_error: None,
         }
     }
@@ -464,56 +535,45 @@ impl<'a> FuncState<'a> {
 
     // Port of searchvar from lparser.c (lines 414-443)
     pub fn searchvar(&self, name: &str, var: &mut ExpDesc) -> i32 {
-        for i in (0..self.nactvar as usize).rev() {
-            if let Some(vd) = self.actvar.get(i) {
-                // Check for global declaration (lparser.c:419)
-                // Note: Lua 5.5 checks global declarations in all scopes,
-                // the base parameter is used for other purposes
-                if vd.kind.is_global() {
-                    // lparser.c:420-421: collective declaration?
-                    if vd.name.is_empty() {
-                        // lparser.c:421-422: no previous collective declaration?
-                        if var.u.info() < 0 {
-                            // This is the first one - record its position
-                            var.u = ExpUnion::Info((self.first_local + i) as i32);
-                        }
-                    } else {
-                        // lparser.c:424: global name
-                        if vd.name == name {
-                            // lparser.c:425-426: found!
-                            *var = ExpDesc::new_void();
-                            var.kind = ExpKind::VGLOBAL;
-                            var.u = ExpUnion::Info((self.first_local + i) as i32);
-                            return ExpKind::VGLOBAL as i32;
-                        } else if var.u.info() == -1 {
-                            // lparser.c:428-429: active preambular declaration?
-                            // Invalidate preambular declaration
-                            var.u = ExpUnion::Info(-2);
-                        }
-                    }
-                } else if vd.name == name {
-                    // lparser.c:432: local variable found
-                    if vd.kind == VarKind::RDKCTC {
-                        // lparser.c:433: compile-time constant
-                        *var = ExpDesc::new_void();
-                        var.kind = ExpKind::VCONST;
-                        // Use i (relative index) for per-function actvar array
-                        var.u = ExpUnion::Info(i as i32);
-                        return ExpKind::VCONST as i32;
-                    } else {
-                        // lparser.c:435-439: regular local variable
-                        let ridx = vd.ridx as u8;
-                        *var = ExpDesc::new_local(ridx, i as u16);
-                        // lparser.c:437-438: vararg parameter?
-                        if vd.kind == VarKind::RDKVAVAR {
-                            var.kind = ExpKind::VVARGVAR;
-                        }
-                        return var.kind as i32;
-                    }
-                }
+        let entry_mark = match var.u {
+            ExpUnion::Info(info) => info,
+            _ => -1,
+        };
+        let mut mark = entry_mark;
+        let found = search_declarations(
+            self.nactvar as usize,
+            self.first_local,
+            name,
+            &mut mark,
+            |i| self.actvar.get(i).map(|vd| (vd.name.as_str(), vd.kind)),
+        );
+        let Some(i) = found else {
+            if mark != entry_mark {
+                var.u = ExpUnion::Info(mark);
+            }
+            return -1; // lparser.c:442: not found
+        };
+        let vd = &self.actvar[i];
+        if vd.kind.is_global() {
+            // lparser.c:425-426: global name found
+            *var = ExpDesc::new_void();
+            var.kind = ExpKind::VGLOBAL;
+            var.u = ExpUnion::Info((self.first_local + i) as i32);
+        } else if vd.kind == VarKind::RDKCTC {
+            // lparser.c:433: compile-time constant
+            *var = ExpDesc::new_void();
+            var.kind = ExpKind::VCONST;
+            // Use i (relative index) for per-function actvar array
+            var.u = ExpUnion::Info(i as i32);
+        } else {
+            // lparser.c:435-439: regular local variable
+            *var = ExpDesc::new_local(vd.ridx as u8, i as u16);
+            // lparser.c:437-438: vararg parameter?
+            if vd.kind == VarKind::RDKVAVAR {
+                var.kind = ExpKind::VVARGVAR;
             }
         }
-        -1 // lparser.c:442: not found
+        var.kind as i32
     }
 
     // Port of searchupvalue from lparser.c (lines 340-351)
@@ -2,6 +2,7 @@
 // Port of Lua 5.5 lparser.c and lcode.c
 
 // Submodules
+mod bytecode_builder; // Direct bytecode construction for code generators
 mod code; // Code generation (lcode.c)
 mod expr_parser; // Expression parser functions (lparser.c)
 mod expression; // Expression parsing and expdesc
@@ -9,6 +10,7 @@ mod func_state; // FuncState and related structures (lparser.h)
 mod parse_literal; // Number parsing utilities
 mod parser; // Lexer/token provider
 mod statement; // Statement parsing (lparser.c)
+pub mod syntax; // Syntax tree front end for editor tooling
 
 // Re-exports
 pub use crate::compiler::parser::LuaLanguageLevel;
@@ -16,6 +18,7 @@ use crate::compiler::parser::{LuaLexer, LuaTokenKind};
 use crate::lua_value::{LuaProto, UpvalueDesc};
 use crate::lua_vm::GlobalState;
 use crate::lua_vm::OpCode;
+pub use bytecode_builder::BytecodeBuilder;
 pub use code::*;
 pub use expression::*;
 pub use func_state::*;
@@ -33,6 +36,7 @@ pub fn compile_code_with_name(
     chunk_name: &str,
 ) -> Result<LuaProto, String> {
     let level = vm.version;
+    let strict_globals = vm.safe_option.strict_globals;
     let mut lexer = match LuaLexer::new(source, level) {
         Ok(lexer) => lexer,
         Err(err) => {
@@ -92,6 +96,14 @@ pub fn compile_code_with_name(
     let block_id = fs.compiler_state.alloc_blockcnt(bl);
     fs.block_cnt_id = Some(block_id);
 
+    // Strict mode: an implicit named global declaration in the main block
+    // voids global-by-default (lparser.c searchvar), exactly like a leading
+    // `global` statement. The name is not an identifier, so it never matches.
+    if strict_globals {
+        fs.new_localvar("(strict)".to_string(), VarKind::GDKREG);
+        fs.nactvar += 1;
+    }
+
     // Parse statements (statlist)
     if let Err(err) = statement::statlist(&mut fs) {
         if let Some(lex_err) = fs.lexer.take_error() {
@@ -149,6 +161,7 @@ pub fn compile_code_with_name(
     fs.chunk.lastlinedefined = 0; // Main function ends at line 0 (convention)
 
     // Cache proto data size for GC
+    fs.chunk.init_key_hints();
     fs.chunk.compute_proto_data_size();
 
     Ok(fs.chunk)
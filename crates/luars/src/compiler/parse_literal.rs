@@ -1,4 +1,7 @@
+use std::borrow::Cow;
+
 use crate::compiler::parser::LuaTokenKind;
+use crate::stdlib::basic::parse_number::lua_str2number;
 
 enum IntegerRepr {
     Normal,
@@ -66,6 +69,16 @@ pub fn parse_int_token_value(num_text: &str) -> Result<NumberResult, String> {
         }
     }
 
+    // Plain Lua numerals go through the same conversion as tonumber; only
+    // the LuaJIT forms below need their own handling.
+    if suffix_count == 0 && !matches!(repr, IntegerRepr::Bin) {
+        return match lua_str2number(num_text.as_bytes()) {
+            Some(n) if n.is_integer() => Ok(NumberResult::Int(n.ivalue())),
+            Some(n) => Ok(NumberResult::Float(n.fltvalue())),
+            None => Err("malformed number".to_string()),
+        };
+    }
+
     let text = &num_text[..num_text.len() - suffix_count];
 
     // 首先尝试解析为有符号整数
@@ -155,63 +168,13 @@ pub fn parse_int_token_value(num_text: &str) -> Result<NumberResult, String> {
 }
 
 pub fn parse_float_token_value(num_text: &str) -> Result<f64, String> {
-    let hex = num_text.starts_with("0x") || num_text.starts_with("0X");
-
-    // This section handles the parsing of hexadecimal floating-point numbers.
-    // Hexadecimal floating-point literals are of the form 0x1.8p3, where:
-    // - "0x1.8" is the significand (integer and fractional parts in hexadecimal)
-    // - "p3" is the exponent (in decimal, base 2 exponent)
-    let value = if hex {
-        let hex_float_text = &num_text[2..];
-        let exponent_position = hex_float_text
-            .find('p')
-            .or_else(|| hex_float_text.find('P'));
-        let (float_part, exponent_part) = if let Some(pos) = exponent_position {
-            (&hex_float_text[..pos], &hex_float_text[(pos + 1)..])
-        } else {
-            (hex_float_text, "")
-        };
-
-        let (integer_part, fraction_value) = if let Some(dot_pos) = float_part.find('.') {
-            let (int_part, frac_part) = float_part.split_at(dot_pos);
-            let int_value = if !int_part.is_empty() {
-                i64::from_str_radix(int_part, 16).unwrap_or(0)
-            } else {
-                0
-            };
-            let frac_part = &frac_part[1..];
-            let frac_value = if !frac_part.is_empty() {
-                let frac_part_value = i64::from_str_radix(frac_part, 16).unwrap_or(0);
-                frac_part_value as f64 * 16f64.powi(-(frac_part.len() as i32))
-            } else {
-                0.0
-            };
-            (int_value, frac_value)
-        } else {
-            (i64::from_str_radix(float_part, 16).unwrap_or(0), 0.0)
-        };
-
-        let mut value = integer_part as f64 + fraction_value;
-        if !exponent_part.is_empty()
-            && let Ok(exp) = exponent_part.parse::<i32>()
-        {
-            value *= 2f64.powi(exp);
-        }
-        value
-    } else {
-        // For decimal float literals, use Rust's std parser which gives
-        // correctly-rounded results (matching C's strtod / Lua's lua_getlocaledecpoint).
-        // Splitting mantissa and exponent and computing separately introduces
-        // intermediate rounding error.
-        num_text
-            .parse::<f64>()
-            .map_err(|e| format!("Failed to parse float literal '{}': {}", num_text, e))?
-    };
-
-    Ok(value)
+    lua_str2number(num_text.as_bytes())
+        .and_then(|n| n.as_float())
+        .ok_or_else(|| "malformed number".to_string())
 }
 
-pub fn parse_string_token_value(text: &str, kind: LuaTokenKind) -> Result<Vec<u8>, String> {
+// Borrows from `text` when the literal needs no unescaping
+pub fn parse_string_token_value(text: &str, kind: LuaTokenKind) -> Result<Cow<'_, [u8]>, String> {
     match kind {
         LuaTokenKind::TkString => normal_string_value(text),
         LuaTokenKind::TkLongString => long_string_value(text),
@@ -219,7 +182,7 @@ pub fn parse_string_token_value(text: &str, kind: LuaTokenKind) -> Result<Vec<u8
     }
 }
 
-fn long_string_value(text: &str) -> Result<Vec<u8>, String> {
+fn long_string_value(text: &str) -> Result<Cow<'_, [u8]>, String> {
     if text.len() < 4 {
         return Err("String too short".to_string());
     }
@@ -287,6 +250,10 @@ fn long_string_value(text: &str) -> Result<Vec<u8>, String> {
     }
 
     let content = &text[i..(text.len() - equal_num - 2)];
+    if !content.contains('\r') {
+        // Only '\n' line breaks, which need no normalizing
+        return Ok(Cow::Borrowed(content.as_bytes()));
+    }
 
     // Port of llex.c:302-306: normalize all line endings to '\n'
     // In Lua, the following sequences are treated as a single line break:
@@ -315,12 +282,21 @@ fn long_string_value(text: &str) -> Result<Vec<u8>, String> {
         }
     }
 
-    Ok(normalized)
+    Ok(Cow::Owned(normalized))
 }
 
-fn normal_string_value(text: &str) -> Result<Vec<u8>, String> {
+fn normal_string_value(text: &str) -> Result<Cow<'_, [u8]>, String> {
     if text.len() < 2 {
-        return Ok(Vec::new());
+        return Ok(Cow::Borrowed(&[]));
+    }
+
+    // Fast path: no escape before the closing quote, so the value is the text
+    let bytes = text.as_bytes();
+    let body = &bytes[1..];
+    match body.iter().position(|&b| b == b'\\' || b == bytes[0]) {
+        None => return Ok(Cow::Borrowed(body)),
+        Some(end) if body[end] == bytes[0] => return Ok(Cow::Borrowed(&body[..end])),
+        Some(_) => {}
     }
 
     // Use Vec<u8> to handle raw bytes, then convert to String
@@ -404,9 +380,9 @@ fn normal_string_value(text: &str) -> Result<Vec<u8>, String> {
                         }
                         '\\' | '\'' | '\"' => result.push(next_char as u8),
                         'z' => {
-                            // Skip whitespace
+                            // Skip whitespace, ASCII only like lisspace in llex.c
                             while let Some(c) = chars.peek() {
-                                if !c.is_whitespace() {
+                                if !matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0B' | '\x0C') {
                                     break;
                                 }
                                 chars.next();
@@ -447,5 +423,5 @@ fn normal_string_value(text: &str) -> Result<Vec<u8>, String> {
     }
 
     // Return raw bytes - caller will decide whether to create String or Binary
-    Ok(result)
+    Ok(Cow::Owned(result))
 }
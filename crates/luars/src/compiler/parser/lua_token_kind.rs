@@ -86,6 +86,19 @@ impl fmt::Display for LuaTokenKind {
 }
 
 impl LuaTokenKind {
+    /// Whitespace, line breaks, comments and the shebang line: tokens the
+    /// parser never sees.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            LuaTokenKind::TkShortComment
+                | LuaTokenKind::TkLongComment
+                | LuaTokenKind::TkEndOfLine
+                | LuaTokenKind::TkWhitespace
+                | LuaTokenKind::TkShebang
+        )
+    }
+
     /// Convert token kind to user-readable string (like Lua's luaX_token2str)
     pub fn to_user_string(self) -> &'static str {
         match self {
@@ -3,11 +3,12 @@ use crate::compiler::parser::{
 };
 
 use super::tokenize_config::TokensizeConfig;
+use crate::stdlib::basic::parse_number::lua_str2number;
 
 pub struct LuaTokenize<'a> {
     reader: Reader<'a>,
     lexer_config: TokensizeConfig,
-    error: Option<String>,
+    error: Option<(usize, String)>,
     line: usize,
 }
 
@@ -23,36 +24,33 @@ impl<'a> LuaTokenize<'a> {
 
     pub fn next_token_data(&mut self) -> Result<LuaTokenData, String> {
         loop {
-            if self.reader.is_eof() {
-                return Ok(LuaTokenData::with_line(
-                    LuaTokenKind::TkEof,
-                    self.reader.current_range(),
-                    self.line,
-                ));
+            let (token, error) = self.next_raw_token();
+            if let Some((line, msg)) = error {
+                return Err(format!("{}: {}", line, msg));
             }
-
-            let kind = self.lex();
-            if let Some(err) = &self.error {
-                return Err(err.clone());
-            }
-
-            if matches!(
-                kind,
-                LuaTokenKind::TkShortComment
-                    | LuaTokenKind::TkLongComment
-                    | LuaTokenKind::TkEndOfLine
-                    | LuaTokenKind::TkWhitespace
-                    | LuaTokenKind::TkShebang
-            ) {
+            if token.kind.is_trivia() {
                 continue;
             }
+            return Ok(token);
+        }
+    }
 
-            return Ok(LuaTokenData::with_line(
-                kind,
+    /// Lex one token, whitespace and comments included, handing back its
+    /// error (line and message) instead of stopping: the compiler gives up
+    /// on the first bad token, editor tooling carries on past it.
+    pub fn next_raw_token(&mut self) -> (LuaTokenData, Option<(usize, String)>) {
+        if self.reader.is_eof() {
+            let token = LuaTokenData::with_line(
+                LuaTokenKind::TkEof,
                 self.reader.current_range(),
                 self.line,
-            ));
+            );
+            return (token, None);
         }
+
+        let kind = self.lex();
+        let token = LuaTokenData::with_line(kind, self.reader.current_range(), self.line);
+        (token, self.error.take())
     }
 
     fn name_to_kind(&self, name: &str) -> LuaTokenKind {
@@ -117,7 +115,8 @@ impl<'a> LuaTokenize<'a> {
                     return LuaTokenKind::TkLeftBracket;
                 }
                 if self.reader.current_char() != '[' {
-                    self.error(|| "invalid long string delimiter".to_string());
+                    let ctx = self.reader.current_text().to_string();
+                    self.error(|| format!("invalid long string delimiter near '{}'", ctx));
                     return LuaTokenKind::TkLongString;
                 }
 
@@ -408,15 +407,8 @@ impl<'a> LuaTokenize<'a> {
                         // Get context: extract chars before current position
                         // current_text() includes from token start (the quote) to after 'u'
                         let text_before = self.reader.current_text();
-                        // Skip opening quote and get last few chars
-                        let context_start = if text_before.len() > 6 {
-                            // Get last 5 chars (will include `abc\u`)
-                            &text_before[text_before.len() - 5..]
-                        } else if text_before.len() > 1 {
-                            &text_before[1..] // Skip opening quote
-                        } else {
-                            ""
-                        };
+                        // Last few chars (will include `abc\u`)
+                        let context_start = escape_context(text_before, 5);
                         let mut ctx = String::from(context_start);
                         let next_ch = self.reader.current_char();
                         if next_ch != '\0' && next_ch != '\n' && next_ch != '\r' {
@@ -434,13 +426,7 @@ impl<'a> LuaTokenize<'a> {
                         if ch == '\0' || ch == '\n' || ch == '\r' {
                             // Unfinished escape
                             let text_before = self.reader.current_text();
-                            let context_start = if text_before.len() > 11 {
-                                &text_before[text_before.len() - 10..]
-                            } else if text_before.len() > 1 {
-                                &text_before[1..]
-                            } else {
-                                ""
-                            };
+                            let context_start = escape_context(text_before, 10);
                             let ctx = String::from(context_start);
                             self.error(|| format!("unfinished unicode escape near '{}'", ctx));
                             return LuaTokenKind::TkString;
@@ -448,13 +434,7 @@ impl<'a> LuaTokenize<'a> {
                         if !ch.is_ascii_hexdigit() {
                             // Non-hex character
                             let text_before = self.reader.current_text();
-                            let context_start = if text_before.len() > 11 {
-                                &text_before[text_before.len() - 10..]
-                            } else if text_before.len() > 1 {
-                                &text_before[1..]
-                            } else {
-                                ""
-                            };
+                            let context_start = escape_context(text_before, 10);
                             let mut ctx = String::from(context_start);
                             ctx.push(ch);
                             self.error(|| {
@@ -471,13 +451,7 @@ impl<'a> LuaTokenize<'a> {
 
                     if hex_digits.is_empty() {
                         let text_before = self.reader.current_text();
-                        let context_start = if text_before.len() > 11 {
-                            &text_before[text_before.len() - 10..]
-                        } else if text_before.len() > 1 {
-                            &text_before[1..]
-                        } else {
-                            ""
-                        };
+                        let context_start = escape_context(text_before, 10);
                         let mut ctx = String::from(context_start);
                         let next_ch = self.reader.current_char();
                         if next_ch != '\0' && next_ch != '\n' && next_ch != '\r' {
@@ -497,13 +471,7 @@ impl<'a> LuaTokenize<'a> {
                         Ok(val) if val > 0x7FFFFFFF => {
                             // Value too large, include context in error
                             let text_before = self.reader.current_text();
-                            let context_start = if text_before.len() > 16 {
-                                &text_before[text_before.len() - 15..]
-                            } else if text_before.len() > 1 {
-                                &text_before[1..]
-                            } else {
-                                ""
-                            };
+                            let context_start = escape_context(text_before, 15);
                             // Don't include the closing } - the error is about the value
                             // being too large, which is detected before we accept the }
                             let ctx = String::from(context_start);
@@ -513,13 +481,7 @@ impl<'a> LuaTokenize<'a> {
                         Err(_) => {
                             // Parse error means value too large for u32
                             let text_before = self.reader.current_text();
-                            let context_start = if text_before.len() > 16 {
-                                &text_before[text_before.len() - 15..]
-                            } else if text_before.len() > 1 {
-                                &text_before[1..]
-                            } else {
-                                ""
-                            };
+                            let context_start = escape_context(text_before, 15);
                             let ctx = String::from(context_start);
                             self.error(|| format!("UTF-8 value too large near '{}'", ctx));
                             return LuaTokenKind::TkString;
@@ -565,6 +527,9 @@ impl<'a> LuaTokenize<'a> {
                     // Valid single-character escapes
                     self.reader.bump();
                 }
+                // A backslash at the very end of the source leaves the string
+                // unfinished, as in llex.c
+                _ if self.reader.is_eof() => break,
                 _ => {
                     // Invalid escape sequence
                     let ch = self.reader.current_char();
@@ -574,10 +539,16 @@ impl<'a> LuaTokenize<'a> {
             }
         }
 
-        if self.reader.current_char() != quote {
+        if self.reader.is_eof() {
             self.error(|| "unfinished string near <eof>".to_string());
             return LuaTokenKind::TkString;
         }
+        if self.reader.current_char() != quote {
+            // A line break ends the string; llex.c reports the text so far
+            let ctx = self.reader.current_text().to_string();
+            self.error(|| format!("unfinished string near '{}'", ctx));
+            return LuaTokenKind::TkString;
+        }
 
         self.reader.bump();
         LuaTokenKind::TkString
@@ -612,96 +583,35 @@ impl<'a> LuaTokenize<'a> {
         LuaTokenKind::TkLongString
     }
 
+    // Port of read_numeral from llex.c: take every character that may
+    // belong to a numeral, then let the shared str2number decide.
     fn lex_number(&mut self) -> LuaTokenKind {
-        enum NumberState {
-            Int,
-            Float,
-            Hex,
-            HexFloat,
-            WithExpo,
-            Bin,
-        }
-
-        let mut state = NumberState::Int;
         let first = self.reader.current_char();
         self.reader.bump();
-        match first {
-            '0' if matches!(self.reader.current_char(), 'X' | 'x') => {
-                self.reader.bump();
-                state = NumberState::Hex;
-            }
-            '0' if matches!(self.reader.current_char(), 'B' | 'b')
-                && self.lexer_config.support_binary_integer() =>
-            {
-                self.reader.bump();
-                state = NumberState::Bin;
-            }
-            '.' => {
-                state = NumberState::Float;
-            }
-            _ => {}
+        if first == '0'
+            && matches!(self.reader.current_char(), 'B' | 'b')
+            && self.lexer_config.support_binary_integer()
+        {
+            self.reader.bump();
+            self.reader.eat_while(|ch| matches!(ch, '0' | '1'));
+            self.reader
+                .eat_while(|ch| matches!(ch, 'u' | 'U' | 'l' | 'L'));
+            return LuaTokenKind::TkInt;
         }
 
+        let mut expo = ['E', 'e'];
+        if first == '0' && matches!(self.reader.current_char(), 'X' | 'x') {
+            self.reader.bump();
+            expo = ['P', 'p'];
+        }
         while !self.reader.is_eof() {
             let ch = self.reader.current_char();
-            let continue_ = match state {
-                NumberState::Int => match ch {
-                    '0'..='9' => true,
-                    '.' => {
-                        state = NumberState::Float;
-                        true
-                    }
-                    _ if matches!(self.reader.current_char(), 'e' | 'E') => {
-                        if matches!(self.reader.next_char(), '+' | '-') {
-                            self.reader.bump();
-                        }
-                        state = NumberState::WithExpo;
-                        true
-                    }
-                    _ => false,
-                },
-                NumberState::Float => match ch {
-                    '0'..='9' => true,
-                    _ if matches!(self.reader.current_char(), 'e' | 'E') => {
-                        if matches!(self.reader.next_char(), '+' | '-') {
-                            self.reader.bump();
-                        }
-                        state = NumberState::WithExpo;
-                        true
-                    }
-                    _ => false,
-                },
-                NumberState::Hex => match ch {
-                    '0'..='9' | 'a'..='f' | 'A'..='F' => true,
-                    '.' => {
-                        state = NumberState::HexFloat;
-                        true
-                    }
-                    _ if matches!(self.reader.current_char(), 'P' | 'p') => {
-                        if matches!(self.reader.next_char(), '+' | '-') {
-                            self.reader.bump();
-                        }
-                        state = NumberState::WithExpo;
-                        true
-                    }
-                    _ => false,
-                },
-                NumberState::HexFloat => match ch {
-                    '0'..='9' | 'a'..='f' | 'A'..='F' => true,
-                    _ if matches!(self.reader.current_char(), 'P' | 'p') => {
-                        if matches!(self.reader.next_char(), '+' | '-') {
-                            self.reader.bump();
-                        }
-                        state = NumberState::WithExpo;
-                        true
-                    }
-                    _ => false,
-                },
-                NumberState::WithExpo => ch.is_ascii_digit(),
-                NumberState::Bin => matches!(ch, '0' | '1'),
-            };
-
-            if continue_ {
+            if expo.contains(&ch) {
+                self.reader.bump();
+                if matches!(self.reader.current_char(), '+' | '-') {
+                    self.reader.bump();
+                }
+            } else if ch.is_ascii_hexdigit() || ch == '.' {
                 self.reader.bump();
             } else {
                 break;
@@ -714,24 +624,28 @@ impl<'a> LuaTokenize<'a> {
         }
 
         if self.lexer_config.support_ll_integer()
-            && matches!(
-                state,
-                NumberState::Int | NumberState::Hex | NumberState::Bin
-            )
+            && matches!(self.reader.current_char(), 'u' | 'U' | 'l' | 'L')
         {
             self.reader
                 .eat_while(|ch| matches!(ch, 'u' | 'U' | 'l' | 'L'));
             return LuaTokenKind::TkInt;
         }
 
-        if self.reader.current_char().is_ascii_alphabetic() {
-            let ch = self.reader.current_char();
-            self.error(|| format!("malformed number near '%{ch}'", ch = ch));
+        // A numeral touching a letter is malformed; take the letter so the
+        // message shows it.
+        let ch = self.reader.current_char();
+        if !self.reader.is_eof() && (ch.is_ascii_alphabetic() || ch == '_') {
+            self.reader.bump();
         }
 
-        match state {
-            NumberState::Int | NumberState::Hex => LuaTokenKind::TkInt,
-            _ => LuaTokenKind::TkFloat,
+        let text = self.reader.current_text();
+        match lua_str2number(text.as_bytes()) {
+            Some(n) if n.is_integer() => LuaTokenKind::TkInt,
+            Some(_) => LuaTokenKind::TkFloat,
+            None => {
+                self.error(|| format!("malformed number near '{text}'"));
+                LuaTokenKind::TkFloat
+            }
         }
     }
 
@@ -740,7 +654,7 @@ impl<'a> LuaTokenize<'a> {
         F: FnOnce() -> R,
         R: AsRef<str>,
     {
-        self.error = Some(format!("{}: {}", self.line, msg().as_ref()));
+        self.error = Some((self.line, msg().as_ref().to_string()));
     }
 }
 
@@ -751,3 +665,14 @@ fn is_name_start(ch: char) -> bool {
 fn is_name_continue(ch: char) -> bool {
     ch.is_ascii_alphanumeric() || ch == '_'
 }
+
+/// The tail of a string token for an escape error's "near '...'": at most
+/// `max` bytes, without the opening quote, cut on a char boundary.
+fn escape_context(text: &str, max: usize) -> &str {
+    let body = text.get(1..).unwrap_or("");
+    let mut start = body.len().saturating_sub(max);
+    while !body.is_char_boundary(start) {
+        start += 1;
+    }
+    &body[start..]
+}
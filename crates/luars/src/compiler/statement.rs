@@ -113,6 +113,10 @@ fn statement(fs: &mut FuncState) -> Result<(), String> {
         }
     }
 
+    // Register overflow inside any expression of this statement is recorded
+    // by checkstack; never let a statement with wrapped registers through.
+    fs.check_pending_checklimit()?;
+
     // Port of lparser.c:2136-2137: Free registers after statement
     // "ls->fs->freereg = luaY_nvarstack(ls->fs);"
     // luaY_nvarstack returns reglevel(fs, fs->nactvar)
@@ -1035,23 +1039,26 @@ fn funcstat(fs: &mut FuncState, line: usize) -> Result<(), String> {
 
     // funcname -> NAME {fieldsel} [':' NAME]
     // Parse first name (base variable)
+    let mut name = fs.lexer.current_token_text().to_string();
     let mut base = ExpDesc::new_void();
     expr_parser::singlevar(fs, &mut base)?;
 
     // Handle field selections: t.a.b.c
     while fs.lexer.current_token() == LuaTokenKind::TkDot {
         expr_parser::fieldsel(fs, &mut base)?;
+        push_funcname_part(fs, &mut name, '.');
     }
 
     // Handle method definition: function t:method()
     let is_method = fs.lexer.current_token() == LuaTokenKind::TkColon;
     if is_method {
         expr_parser::fieldsel(fs, &mut base)?;
+        push_funcname_part(fs, &mut name, ':');
     }
 
     // Parse function body
     let mut func_val = ExpDesc::new_void();
-    expr_parser::body(fs, &mut func_val, is_method)?;
+    expr_parser::named_body(fs, &mut func_val, is_method, Some(name))?;
 
     // Check if variable is readonly - use original line (where FUNCTION keyword was)
     check_readonly_at_line(fs, &mut base, line)?;
@@ -1065,6 +1072,13 @@ fn funcstat(fs: &mut FuncState, line: usize) -> Result<(), String> {
     Ok(())
 }
 
+/// Append the field name `fieldsel` just consumed to a function name.
+fn push_funcname_part(fs: &FuncState, name: &mut String, sep: char) {
+    let range = fs.lexer.previous_token_range();
+    name.push(sep);
+    name.push_str(&fs.lexer.origin_text()[range.start_offset..range.end_offset()]);
+}
+
 // Port of localfunc from lparser.c
 fn localfunc(fs: &mut FuncState) -> Result<(), String> {
     // localfunc -> NAME body
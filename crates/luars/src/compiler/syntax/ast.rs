@@ -0,0 +1,228 @@
+// Syntax tree produced by `syntax::parse`.
+//
+// Every node carries the byte span of the source it was parsed from.
+// Nodes the parser could not make sense of are kept as `Error` nodes, so
+// the rest of the tree is still there to walk.
+
+use crate::compiler::parser::{BinaryOperator, SourceRange, UnaryOperator};
+
+/// Index of a [`Declaration`] in [`SyntaxTree::declarations`](super::SyntaxTree::declarations).
+pub type DeclId = usize;
+
+/// An identifier as written in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name {
+    pub text: String,
+    pub span: SourceRange,
+}
+
+/// `<const>` / `<close>` on a declared variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attrib {
+    Const,
+    Close,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclKind {
+    /// `local x`
+    Local,
+    /// `local function f`
+    LocalFunction,
+    /// A named function parameter, including a named vararg `...t`
+    Parameter,
+    /// The implicit `self` of a `function t:m()` method
+    SelfParameter,
+    /// A control variable of a numeric or generic `for`
+    ForVariable,
+    /// `global x` or `global function f`
+    Global,
+    /// `global *`, which declares every name not declared otherwise
+    GlobalAll,
+}
+
+/// A variable declaration. Its span is the declared name, except for
+/// `self` (empty, right after the method name) and `global *` (the `*`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    pub name: String,
+    pub span: SourceRange,
+    pub kind: DeclKind,
+    pub attrib: Option<Attrib>,
+}
+
+/// What a variable name refers to, resolved with the compiler's rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// A local of the function the name is used in
+    Local(DeclId),
+    /// A local of an enclosing function
+    Upvalue(DeclId),
+    /// A field of `_ENV`, with the `global` declaration covering it, if any
+    Global(Option<DeclId>),
+}
+
+/// A use of a variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameRef {
+    pub name: Name,
+    pub resolution: Resolution,
+}
+
+/// A declared name, pointing at its entry in the declaration table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub name: Name,
+    pub decl: DeclId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub stats: Vec<Stat>,
+    pub span: SourceRange,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stat {
+    pub kind: StatKind,
+    pub span: SourceRange,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatKind {
+    /// `;`
+    Empty,
+    /// A function call used as a statement
+    Call(Expr),
+    Assign {
+        targets: Vec<Expr>,
+        values: Vec<Expr>,
+    },
+    Local {
+        names: Vec<Binding>,
+        values: Vec<Expr>,
+    },
+    LocalFunction {
+        name: Binding,
+        func: FuncBody,
+    },
+    Global {
+        names: Vec<Binding>,
+        values: Vec<Expr>,
+    },
+    /// `global *`
+    GlobalAll(Binding),
+    GlobalFunction {
+        name: Binding,
+        func: FuncBody,
+    },
+    /// `function a.b.c:m() ... end`
+    Function {
+        base: NameRef,
+        fields: Vec<Name>,
+        method: Option<Name>,
+        func: FuncBody,
+    },
+    Do(Block),
+    While {
+        cond: Expr,
+        body: Block,
+    },
+    Repeat {
+        body: Block,
+        cond: Expr,
+    },
+    If {
+        clauses: Vec<(Expr, Block)>,
+        else_block: Option<Block>,
+    },
+    NumericFor {
+        var: Binding,
+        start: Expr,
+        limit: Expr,
+        step: Option<Expr>,
+        body: Block,
+    },
+    GenericFor {
+        vars: Vec<Binding>,
+        exprs: Vec<Expr>,
+        body: Block,
+    },
+    Return(Vec<Expr>),
+    Break,
+    Goto(Name),
+    Label(Name),
+    /// Source the parser skipped over after a syntax error
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: SourceRange,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Nil,
+    True,
+    False,
+    /// `...`
+    Vararg,
+    /// A numeral; its text is the span
+    Number,
+    /// A short or long string literal; its text is the span
+    String,
+    Name(NameRef),
+    Index {
+        obj: Box<Expr>,
+        key: Box<Expr>,
+    },
+    Field {
+        obj: Box<Expr>,
+        name: Name,
+    },
+    Call {
+        func: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    MethodCall {
+        obj: Box<Expr>,
+        method: Name,
+        args: Vec<Expr>,
+    },
+    Function(FuncBody),
+    Table(Vec<TableField>),
+    Binary {
+        op: BinaryOperator,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Unary {
+        op: UnaryOperator,
+        operand: Box<Expr>,
+    },
+    Paren(Box<Expr>),
+    /// An expression the parser could not read
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableField {
+    /// `value`
+    Positional(Expr),
+    /// `name = value`
+    Named { name: Name, value: Expr },
+    /// `[key] = value`
+    Indexed { key: Expr, value: Expr },
+}
+
+/// Parameters and body of a function. A named vararg `...t` is the last
+/// parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncBody {
+    pub params: Vec<Binding>,
+    pub is_vararg: bool,
+    pub body: Block,
+    pub span: SourceRange,
+}
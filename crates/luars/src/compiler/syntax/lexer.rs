@@ -0,0 +1,202 @@
+// Tokenizer for editor tooling: the compiler's tokenizer, with positions
+// and without stopping at the first bad token.
+
+use crate::compiler::parser::{
+    LuaLanguageLevel, LuaTokenKind, LuaTokenize, Reader, SourceRange, TokensizeConfig,
+};
+
+/// A 1-based line and a 1-based column counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Self {
+        Position { line, column }
+    }
+}
+
+/// Byte offsets of line starts, for turning offsets into positions.
+///
+/// Line breaks are counted the way the lexer counts them: `\n`, `\r`,
+/// `\r\n` and `\n\r` each end one line.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let bytes = source.as_bytes();
+        let mut line_starts = vec![0];
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b @ (b'\n' | b'\r') => {
+                    i += 1;
+                    if i < bytes.len() && (bytes[i] == b'\n' || bytes[i] == b'\r') && bytes[i] != b
+                    {
+                        i += 1;
+                    }
+                    line_starts.push(i);
+                }
+                _ => i += 1,
+            }
+        }
+        LineIndex { line_starts }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Position of byte `offset` in `source` (the text this index was
+    /// built from). Offsets inside a character count as that character.
+    pub fn position(&self, source: &str, offset: usize) -> Position {
+        let offset = offset.min(source.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = source
+            .get(line_start..)
+            .unwrap_or("")
+            .char_indices()
+            .take_while(|(i, _)| line_start + i < offset)
+            .count();
+        Position::new(line, column + 1)
+    }
+
+    /// Byte offset of `position` in `source`, or `None` when the line has
+    /// no such column.
+    pub fn offset(&self, source: &str, position: Position) -> Option<usize> {
+        let line_start = *self.line_starts.get(position.line.checked_sub(1)?)?;
+        let line_end = self
+            .line_starts
+            .get(position.line)
+            .copied()
+            .unwrap_or(source.len());
+        let text = &source[line_start..line_end];
+        text.char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .nth(position.column.checked_sub(1)?)
+            .map(|i| line_start + i)
+    }
+}
+
+/// One token: its kind, byte span and where it starts and ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: LuaTokenKind,
+    pub span: SourceRange,
+    pub start: Position,
+    pub end: Position,
+}
+
+/// An error found while lexing or parsing, with the span it is about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: SourceRange,
+    pub start: Position,
+}
+
+/// Tokenizer over a source text.
+///
+/// `Lexer::new(source).tokens()` yields the tokens the parser sees;
+/// [`with_trivia`](Self::with_trivia) adds whitespace, line breaks and
+/// comments. A malformed token (an unfinished string, a bad escape) is
+/// still yielded and lexing goes on after it; the problem is recorded in
+/// [`errors`](Self::errors).
+pub struct Lexer<'a> {
+    source: &'a str,
+    tokenize: LuaTokenize<'a>,
+    lines: LineIndex,
+    trivia: bool,
+    done: bool,
+    errors: Vec<SyntaxError>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self::with_level(source, LuaLanguageLevel::default())
+    }
+
+    pub fn with_level(source: &'a str, level: LuaLanguageLevel) -> Self {
+        Lexer {
+            source,
+            tokenize: LuaTokenize::new(
+                Reader::new(source),
+                TokensizeConfig {
+                    language_level: level,
+                },
+            ),
+            lines: LineIndex::new(source),
+            trivia: false,
+            done: false,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Also yield whitespace, line breaks, comments and the shebang line.
+    pub fn with_trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        &self.lines
+    }
+
+    /// Errors of the tokens yielded so far.
+    pub fn errors(&self) -> &[SyntaxError] {
+        &self.errors
+    }
+
+    pub fn take_errors(&mut self) -> Vec<SyntaxError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// The remaining tokens, up to (not including) the end of the source.
+    pub fn tokens(&mut self) -> impl Iterator<Item = Token> + '_ {
+        std::iter::from_fn(move || self.next_token())
+    }
+
+    /// The next token, or `None` at the end of the source.
+    pub fn next_token(&mut self) -> Option<Token> {
+        while !self.done {
+            let (data, error) = self.tokenize.next_raw_token();
+            if data.kind == LuaTokenKind::TkEof {
+                self.done = true;
+                break;
+            }
+            let token = self.token(data.kind, data.range);
+            if let Some((_, message)) = error {
+                self.errors.push(SyntaxError {
+                    message,
+                    span: token.span,
+                    start: token.start,
+                });
+            }
+            if self.trivia || !data.kind.is_trivia() {
+                return Some(token);
+            }
+        }
+        None
+    }
+
+    /// A token of `kind` covering `span`, with its positions filled in.
+    pub fn token(&self, kind: LuaTokenKind, span: SourceRange) -> Token {
+        Token {
+            kind,
+            span,
+            start: self.lines.position(self.source, span.start_offset),
+            end: self.lines.position(self.source, span.end_offset()),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// Syntax front end for editor tooling.
+//
+// Shares the compiler's tokenizer, operator table and name lookup
+// (`search_declarations`), but builds a syntax tree instead of bytecode
+// and recovers from errors instead of stopping at the first one.
+
+mod ast;
+mod lexer;
+mod parser;
+
+pub use crate::compiler::parser::{
+    BinaryOperator, LuaLanguageLevel, LuaTokenKind, SourceRange, UnaryOperator,
+};
+pub use ast::*;
+pub use lexer::{Lexer, LineIndex, Position, SyntaxError, Token};
+
+/// Result of [`parse`]: the tree, its declarations and name uses, and
+/// every error found on the way.
+#[derive(Debug, Clone)]
+pub struct SyntaxTree {
+    pub block: Block,
+    /// Every declared variable, indexed by [`DeclId`]
+    pub declarations: Vec<Declaration>,
+    /// Every use of a variable, in source order
+    pub references: Vec<NameRef>,
+    pub errors: Vec<SyntaxError>,
+    line_index: LineIndex,
+}
+
+impl SyntaxTree {
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    pub fn declaration(&self, id: DeclId) -> &Declaration {
+        &self.declarations[id]
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        &self.line_index
+    }
+
+    /// The variable use whose name covers byte `offset`.
+    pub fn reference_at(&self, offset: usize) -> Option<&NameRef> {
+        self.references
+            .iter()
+            .find(|r| r.name.span.contains(offset))
+    }
+
+    /// The declaration of the variable at byte `offset`, whether `offset`
+    /// is on a use of it or on the declaration itself. `None` for a
+    /// global nothing declares.
+    pub fn definition_at(&self, offset: usize) -> Option<DeclId> {
+        if let Some(r) = self.reference_at(offset) {
+            return match r.resolution {
+                Resolution::Local(id) | Resolution::Upvalue(id) => Some(id),
+                Resolution::Global(id) => id,
+            };
+        }
+        self.declarations
+            .iter()
+            .position(|d| d.span.contains(offset))
+    }
+
+    /// Every use resolved to declaration `id`.
+    pub fn references_to(&self, id: DeclId) -> impl Iterator<Item = &NameRef> {
+        self.references.iter().filter(move |r| {
+            matches!(
+                r.resolution,
+                Resolution::Local(d) | Resolution::Upvalue(d) | Resolution::Global(Some(d))
+                    if d == id
+            )
+        })
+    }
+}
+
+/// Parse `source` as Lua 5.5 into a syntax tree. Never fails: syntax
+/// errors are listed in [`SyntaxTree::errors`] and the parts they cover
+/// appear as `Error` nodes.
+pub fn parse(source: &str) -> SyntaxTree {
+    parse_with_level(source, LuaLanguageLevel::default())
+}
+
+pub fn parse_with_level(source: &str, level: LuaLanguageLevel) -> SyntaxTree {
+    parser::Parser::parse(source, level)
+}
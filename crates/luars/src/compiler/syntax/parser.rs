@@ -0,0 +1,1152 @@
+// Recovering recursive-descent parser building the syntax tree.
+//
+// Follows the grammar of the compiler's parser (statement.rs /
+// expr_parser.rs, ports of lparser.c) and resolves names with the same
+// searchvar logic, but never stops: a syntax error is recorded, the
+// offending part becomes an `Error` node and parsing carries on.
+
+use super::SyntaxTree;
+use super::ast::*;
+use super::lexer::{Lexer, LineIndex, SyntaxError, Token};
+use crate::compiler::parser::{
+    BinaryOperator, LuaLanguageLevel, LuaTokenKind, SourceRange, UNARY_PRIORITY, UnaryOperator,
+    to_binary_operator, to_unary_operator,
+};
+use crate::compiler::{VarKind, search_declarations};
+use crate::lua_vm::lua_limits::MAXCCALLS;
+
+/// A declaration in scope, as searchvar sees it.
+struct ActiveVar {
+    /// Name matched by lookups; empty for `global *`
+    name: String,
+    kind: VarKind,
+    decl: DeclId,
+}
+
+/// Active declarations of one function being parsed.
+struct FuncScope {
+    /// Index of this function's first declaration in the conceptual
+    /// shared list of all enclosing functions' declarations (lparser.c's
+    /// `fs->firstlocal`)
+    first_local: usize,
+    active: Vec<ActiveVar>,
+    is_vararg: bool,
+}
+
+pub(super) struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    eof: Token,
+    pos: usize,
+    prev_end: usize,
+    depth: usize,
+    errors: Vec<SyntaxError>,
+    declarations: Vec<Declaration>,
+    decl_kinds: Vec<VarKind>,
+    references: Vec<NameRef>,
+    funcs: Vec<FuncScope>,
+    line_index: LineIndex,
+}
+
+impl<'a> Parser<'a> {
+    pub(super) fn parse(source: &'a str, level: LuaLanguageLevel) -> SyntaxTree {
+        let mut lexer = Lexer::with_level(source, level);
+        let tokens: Vec<Token> = lexer.tokens().collect();
+        let eof = lexer.token(LuaTokenKind::TkEof, SourceRange::new(source.len(), 0));
+        let errors = lexer.take_errors();
+
+        let mut parser = Parser {
+            source,
+            tokens,
+            eof,
+            pos: 0,
+            prev_end: 0,
+            depth: 0,
+            errors,
+            declarations: Vec::new(),
+            decl_kinds: Vec::new(),
+            references: Vec::new(),
+            // The main function is vararg
+            funcs: vec![FuncScope {
+                first_local: 0,
+                active: Vec::new(),
+                is_vararg: true,
+            }],
+            line_index: lexer.line_index().clone(),
+        };
+        let block = parser.chunk();
+        parser.errors.sort_by_key(|e| e.span.start_offset);
+
+        SyntaxTree {
+            block,
+            declarations: parser.declarations,
+            references: parser.references,
+            errors: parser.errors,
+            line_index: parser.line_index,
+        }
+    }
+
+    // ===== Tokens =====
+
+    fn current(&self) -> &Token {
+        self.tokens.get(self.pos).unwrap_or(&self.eof)
+    }
+
+    fn kind(&self) -> LuaTokenKind {
+        self.current().kind
+    }
+
+    fn peek_kind(&self) -> LuaTokenKind {
+        self.tokens
+            .get(self.pos + 1)
+            .map_or(LuaTokenKind::TkEof, |t| t.kind)
+    }
+
+    fn text(&self, span: SourceRange) -> &'a str {
+        &self.source[span.start_offset..span.end_offset()]
+    }
+
+    fn bump(&mut self) {
+        if self.pos < self.tokens.len() {
+            self.prev_end = self.tokens[self.pos].span.end_offset();
+            self.pos += 1;
+        }
+    }
+
+    fn test_next(&mut self, kind: LuaTokenKind) -> bool {
+        if self.kind() == kind {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn start(&self) -> usize {
+        self.current().span.start_offset
+    }
+
+    /// Span from `start` to the end of the last consumed token (empty at
+    /// `start` when nothing was consumed).
+    fn span_from(&self, start: usize) -> SourceRange {
+        SourceRange::from_start_end(start, self.prev_end.max(start))
+    }
+
+    // ===== Errors =====
+
+    /// Record `msg` about the current token, like the compiler's token_error.
+    fn error_here(&mut self, msg: &str) {
+        let span = self.current().span;
+        let near = if self.kind() == LuaTokenKind::TkEof {
+            "<eof>".to_string()
+        } else {
+            format!("'{}'", self.text(span))
+        };
+        self.error_at(span, format!("{} near {}", msg, near));
+    }
+
+    /// Record `message` about `span`. Only the first error at a given
+    /// offset is kept, so one mistake does not cascade.
+    fn error_at(&mut self, span: SourceRange, message: String) {
+        if self
+            .errors
+            .iter()
+            .any(|e| e.span.start_offset == span.start_offset)
+        {
+            return;
+        }
+        let start = self.line_index.position(self.source, span.start_offset);
+        self.errors.push(SyntaxError {
+            message,
+            span,
+            start,
+        });
+    }
+
+    fn expect(&mut self, kind: LuaTokenKind) -> bool {
+        if self.test_next(kind) {
+            return true;
+        }
+        self.error_here(&format!("'{}' expected", kind));
+        false
+    }
+
+    /// Port of check_match: `what` closing the `who` opened at `line`.
+    fn check_match(&mut self, what: LuaTokenKind, who: LuaTokenKind, line: usize) {
+        if self.test_next(what) {
+            return;
+        }
+        if self.current().start.line == line {
+            self.error_here(&format!("'{}' expected", what));
+        } else {
+            self.error_here(&format!(
+                "'{}' expected (to close '{}' at line {})",
+                what, who, line
+            ));
+        }
+    }
+
+    fn check_name(&mut self) -> Option<Name> {
+        if self.kind() != LuaTokenKind::TkName {
+            self.error_here("<name> expected");
+            return None;
+        }
+        let span = self.current().span;
+        self.bump();
+        Some(Name {
+            text: self.text(span).to_string(),
+            span,
+        })
+    }
+
+    /// Count one more level of nesting; false (with an error) past the
+    /// compiler's limit.
+    fn enter_level(&mut self) -> bool {
+        self.depth += 1;
+        if self.depth > MAXCCALLS {
+            self.error_here("chunk has too many syntax levels");
+            return false;
+        }
+        true
+    }
+
+    fn leave_level(&mut self) {
+        self.depth -= 1;
+    }
+
+    // ===== Scopes =====
+
+    fn scope(&mut self) -> &mut FuncScope {
+        self.funcs
+            .last_mut()
+            .expect("main function scope is never closed")
+    }
+
+    fn enter_block(&mut self) -> usize {
+        self.scope().active.len()
+    }
+
+    fn leave_block(&mut self, mark: usize) {
+        self.scope().active.truncate(mark);
+    }
+
+    fn open_func(&mut self) {
+        let first_local = self
+            .funcs
+            .last()
+            .map_or(0, |f| f.first_local + f.active.len());
+        self.funcs.push(FuncScope {
+            first_local,
+            active: Vec::new(),
+            is_vararg: false,
+        });
+    }
+
+    fn close_func(&mut self) {
+        self.funcs.pop();
+    }
+
+    /// Add a declaration; it is not visible until [`activate`](Self::activate).
+    fn declare(
+        &mut self,
+        name: Name,
+        kind: DeclKind,
+        attrib: Option<Attrib>,
+        var_kind: VarKind,
+    ) -> Binding {
+        let decl = self.declarations.len();
+        self.declarations.push(Declaration {
+            name: name.text.clone(),
+            span: name.span,
+            kind,
+            attrib,
+        });
+        self.decl_kinds.push(var_kind);
+        Binding { name, decl }
+    }
+
+    fn activate(&mut self, decl: DeclId) {
+        let name = match self.declarations[decl].kind {
+            DeclKind::GlobalAll => String::new(),
+            _ => self.declarations[decl].name.clone(),
+        };
+        let kind = self.decl_kinds[decl];
+        self.scope().active.push(ActiveVar { name, kind, decl });
+    }
+
+    /// Port of singlevar's lookup: search the enclosing functions from the
+    /// innermost out, with global declarations taking part as in searchvar.
+    fn resolve(&mut self, name: &Name) -> NameRef {
+        let mut mark = -1;
+        let innermost = self.funcs.len() - 1;
+        let mut found = None;
+        for (level, func) in self.funcs.iter().enumerate().rev() {
+            let hit = search_declarations(
+                func.active.len(),
+                func.first_local,
+                &name.text,
+                &mut mark,
+                |i| func.active.get(i).map(|v| (v.name.as_str(), v.kind)),
+            );
+            if let Some(i) = hit {
+                let var = &func.active[i];
+                found = Some(if var.kind.is_global() {
+                    Resolution::Global(Some(var.decl))
+                } else if level == innermost {
+                    Resolution::Local(var.decl)
+                } else {
+                    Resolution::Upvalue(var.decl)
+                });
+                break;
+            }
+        }
+
+        let resolution = match (found, mark) {
+            (Some(resolution), _) => resolution,
+            // The main chunk's `_ENV` upvalue, which no declaration hides
+            (None, _) if name.text == "_ENV" => Resolution::Global(None),
+            (None, -1) => Resolution::Global(None),
+            (None, -2) => {
+                self.error_at(name.span, format!("variable '{}' not declared", name.text));
+                Resolution::Global(None)
+            }
+            // The innermost collective declaration covers the name
+            (None, abs) => {
+                let abs = abs as usize;
+                let decl = self
+                    .funcs
+                    .iter()
+                    .find(|f| abs >= f.first_local && abs < f.first_local + f.active.len())
+                    .map(|f| f.active[abs - f.first_local].decl);
+                Resolution::Global(decl)
+            }
+        };
+        self.reference(name, resolution)
+    }
+
+    fn reference(&mut self, name: &Name, resolution: Resolution) -> NameRef {
+        let name_ref = NameRef {
+            name: name.clone(),
+            resolution,
+        };
+        self.references.push(name_ref.clone());
+        name_ref
+    }
+
+    // ===== Statements =====
+
+    fn chunk(&mut self) -> Block {
+        let start = self.start();
+        let mut stats = Vec::new();
+        loop {
+            // One scope for the whole chunk, across stray closing keywords
+            stats.extend(self.statlist().stats);
+            if self.kind() == LuaTokenKind::TkEof {
+                break;
+            }
+            // A stray 'end', 'else', ... at the top level
+            let stat_start = self.start();
+            self.error_here("'<eof>' expected");
+            self.bump();
+            stats.push(Stat {
+                kind: StatKind::Error,
+                span: self.span_from(stat_start),
+            });
+        }
+        Block {
+            stats,
+            span: SourceRange::from_start_end(start.min(self.source.len()), self.source.len()),
+        }
+    }
+
+    fn block_follow(&self, with_until: bool) -> bool {
+        match self.kind() {
+            LuaTokenKind::TkElse
+            | LuaTokenKind::TkElseIf
+            | LuaTokenKind::TkEnd
+            | LuaTokenKind::TkEof => true,
+            LuaTokenKind::TkUntil => with_until,
+            _ => false,
+        }
+    }
+
+    /// Port of statlist, in a scope of its own.
+    fn block(&mut self) -> Block {
+        let mark = self.enter_block();
+        let block = self.statlist();
+        self.leave_block(mark);
+        block
+    }
+
+    fn statlist(&mut self) -> Block {
+        let start = self.start();
+        let mut stats = Vec::new();
+        while !self.block_follow(true) {
+            if self.kind() == LuaTokenKind::TkReturn {
+                stats.push(self.retstat());
+                break;
+            }
+            let before = self.pos;
+            stats.push(self.statement());
+            if self.pos == before {
+                // No progress: skip the token so parsing moves on
+                let stat_start = self.start();
+                self.bump();
+                stats.push(Stat {
+                    kind: StatKind::Error,
+                    span: self.span_from(stat_start),
+                });
+            }
+        }
+        Block {
+            stats,
+            span: self.span_from(start),
+        }
+    }
+
+    fn statement(&mut self) -> Stat {
+        let start = self.start();
+        let line = self.current().start.line;
+        if !self.enter_level() {
+            self.leave_level();
+            self.bump();
+            return Stat {
+                kind: StatKind::Error,
+                span: self.span_from(start),
+            };
+        }
+        let kind = match self.kind() {
+            LuaTokenKind::TkSemicolon => {
+                self.bump();
+                StatKind::Empty
+            }
+            LuaTokenKind::TkIf => self.ifstat(line),
+            LuaTokenKind::TkWhile => {
+                self.bump();
+                let cond = self.expr();
+                self.expect(LuaTokenKind::TkDo);
+                let body = self.block();
+                self.check_match(LuaTokenKind::TkEnd, LuaTokenKind::TkWhile, line);
+                StatKind::While { cond, body }
+            }
+            LuaTokenKind::TkDo => {
+                self.bump();
+                let body = self.block();
+                self.check_match(LuaTokenKind::TkEnd, LuaTokenKind::TkDo, line);
+                StatKind::Do(body)
+            }
+            LuaTokenKind::TkFor => self.forstat(line),
+            LuaTokenKind::TkRepeat => {
+                self.bump();
+                let mark = self.enter_block();
+                let body = self.statlist();
+                self.check_match(LuaTokenKind::TkUntil, LuaTokenKind::TkRepeat, line);
+                // The condition sees the body's locals
+                let cond = self.expr();
+                self.leave_block(mark);
+                StatKind::Repeat { body, cond }
+            }
+            LuaTokenKind::TkFunction => self.funcstat(line),
+            LuaTokenKind::TkLocal => {
+                self.bump();
+                if self.test_next(LuaTokenKind::TkFunction) {
+                    self.localfunc(line)
+                } else {
+                    self.localstat()
+                }
+            }
+            LuaTokenKind::TkDbColon => {
+                self.bump();
+                let name = self.check_name();
+                self.expect(LuaTokenKind::TkDbColon);
+                name.map_or(StatKind::Error, StatKind::Label)
+            }
+            LuaTokenKind::TkBreak => {
+                self.bump();
+                StatKind::Break
+            }
+            LuaTokenKind::TkGoto => {
+                self.bump();
+                self.check_name().map_or(StatKind::Error, StatKind::Goto)
+            }
+            LuaTokenKind::TkName
+                if self.text(self.current().span) == "global"
+                    && matches!(
+                        self.peek_kind(),
+                        LuaTokenKind::TkMul
+                            | LuaTokenKind::TkLt
+                            | LuaTokenKind::TkName
+                            | LuaTokenKind::TkFunction
+                    ) =>
+            {
+                self.bump();
+                if self.test_next(LuaTokenKind::TkFunction) {
+                    self.globalfunc(line)
+                } else {
+                    self.globalstat()
+                }
+            }
+            _ => self.exprstat(),
+        };
+        self.leave_level();
+        Stat {
+            kind,
+            span: self.span_from(start),
+        }
+    }
+
+    fn ifstat(&mut self, line: usize) -> StatKind {
+        let mut clauses = Vec::new();
+        let mut else_block = None;
+        // IF / ELSEIF cond THEN block
+        loop {
+            self.bump(); // skip IF or ELSEIF
+            let cond = self.expr();
+            self.expect(LuaTokenKind::TkThen);
+            let block = self.block();
+            clauses.push((cond, block));
+            if self.kind() != LuaTokenKind::TkElseIf {
+                break;
+            }
+        }
+        if self.test_next(LuaTokenKind::TkElse) {
+            else_block = Some(self.block());
+        }
+        self.check_match(LuaTokenKind::TkEnd, LuaTokenKind::TkIf, line);
+        StatKind::If {
+            clauses,
+            else_block,
+        }
+    }
+
+    fn forstat(&mut self, line: usize) -> StatKind {
+        self.bump(); // skip FOR
+        let Some(first) = self.check_name() else {
+            return StatKind::Error;
+        };
+        let kind = match self.kind() {
+            LuaTokenKind::TkAssign => {
+                self.bump();
+                let start = self.expr();
+                self.expect(LuaTokenKind::TkComma);
+                let limit = self.expr();
+                let step = self.test_next(LuaTokenKind::TkComma).then(|| self.expr());
+                self.expect(LuaTokenKind::TkDo);
+                let mark = self.enter_block();
+                let var = self.declare(first, DeclKind::ForVariable, None, VarKind::VDKREG);
+                self.activate(var.decl);
+                let body = self.block();
+                self.leave_block(mark);
+                StatKind::NumericFor {
+                    var,
+                    start,
+                    limit,
+                    step,
+                    body,
+                }
+            }
+            LuaTokenKind::TkComma | LuaTokenKind::TkIn => {
+                let mut names = vec![first];
+                while self.test_next(LuaTokenKind::TkComma) {
+                    match self.check_name() {
+                        Some(name) => names.push(name),
+                        None => break,
+                    }
+                }
+                self.expect(LuaTokenKind::TkIn);
+                let exprs = self.explist();
+                self.expect(LuaTokenKind::TkDo);
+                let mark = self.enter_block();
+                let vars: Vec<Binding> = names
+                    .into_iter()
+                    .map(|name| self.declare(name, DeclKind::ForVariable, None, VarKind::VDKREG))
+                    .collect();
+                for var in &vars {
+                    self.activate(var.decl);
+                }
+                let body = self.block();
+                self.leave_block(mark);
+                StatKind::GenericFor { vars, exprs, body }
+            }
+            _ => {
+                self.error_here("'=' or 'in' expected");
+                return StatKind::Error;
+            }
+        };
+        self.check_match(LuaTokenKind::TkEnd, LuaTokenKind::TkFor, line);
+        kind
+    }
+
+    fn funcstat(&mut self, line: usize) -> StatKind {
+        let start = self.start();
+        self.bump(); // skip FUNCTION
+        let Some(base) = self.check_name() else {
+            return StatKind::Error;
+        };
+        let base = self.resolve(&base);
+        let mut fields = Vec::new();
+        while self.test_next(LuaTokenKind::TkDot) {
+            match self.check_name() {
+                Some(name) => fields.push(name),
+                None => break,
+            }
+        }
+        let mut method = None;
+        if self.test_next(LuaTokenKind::TkColon) {
+            method = self.check_name();
+        }
+        let self_span = method
+            .as_ref()
+            .map(|m| SourceRange::new(m.span.end_offset(), 0));
+        let func = self.body(start, self_span, line);
+        StatKind::Function {
+            base,
+            fields,
+            method,
+            func,
+        }
+    }
+
+    fn localfunc(&mut self, line: usize) -> StatKind {
+        let start = self.prev_start();
+        let Some(name) = self.check_name() else {
+            return StatKind::Error;
+        };
+        let name = self.declare(name, DeclKind::LocalFunction, None, VarKind::VDKREG);
+        // The function can refer to itself
+        self.activate(name.decl);
+        let func = self.body(start, None, line);
+        StatKind::LocalFunction { name, func }
+    }
+
+    /// Start of the token before the current one (the FUNCTION keyword of
+    /// `local function` / `global function`).
+    fn prev_start(&self) -> usize {
+        self.pos
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map_or(self.start(), |t| t.span.start_offset)
+    }
+
+    /// Port of getvarattribute: an optional `<const>` / `<close>`.
+    fn attrib(&mut self) -> Option<Attrib> {
+        if !self.test_next(LuaTokenKind::TkLt) {
+            return None;
+        }
+        let name = self.check_name()?;
+        self.expect(LuaTokenKind::TkGt);
+        match name.text.as_str() {
+            "const" => Some(Attrib::Const),
+            "close" => Some(Attrib::Close),
+            other => {
+                self.error_at(name.span, format!("unknown attribute '{}'", other));
+                None
+            }
+        }
+    }
+
+    fn localstat(&mut self) -> StatKind {
+        let default = self.attrib();
+        let mut pending = Vec::new();
+        while let Some(name) = self.check_name() {
+            let attrib = self.attrib().or(default);
+            pending.push((name, attrib));
+            if !self.test_next(LuaTokenKind::TkComma) {
+                break;
+            }
+        }
+        let values = if self.test_next(LuaTokenKind::TkAssign) {
+            self.explist()
+        } else {
+            Vec::new()
+        };
+        // The names come into scope after the values are read
+        let names: Vec<Binding> = pending
+            .into_iter()
+            .map(|(name, attrib)| {
+                let kind = match attrib {
+                    Some(Attrib::Const) => VarKind::RDKCONST,
+                    Some(Attrib::Close) => VarKind::RDKTOCLOSE,
+                    None => VarKind::VDKREG,
+                };
+                self.declare(name, DeclKind::Local, attrib, kind)
+            })
+            .collect();
+        for name in &names {
+            self.activate(name.decl);
+        }
+        StatKind::Local { names, values }
+    }
+
+    /// Port of getglobalattribute.
+    fn global_attrib(&mut self) -> Option<Attrib> {
+        let span = self.current().span;
+        let attrib = self.attrib();
+        if attrib == Some(Attrib::Close) {
+            self.error_at(span, "global variables cannot be to-be-closed".to_string());
+            return None;
+        }
+        attrib
+    }
+
+    fn global_kind(attrib: Option<Attrib>) -> VarKind {
+        match attrib {
+            Some(Attrib::Const) => VarKind::GDKCONST,
+            _ => VarKind::GDKREG,
+        }
+    }
+
+    fn globalstat(&mut self) -> StatKind {
+        let default = self.global_attrib();
+        if self.kind() == LuaTokenKind::TkMul {
+            let span = self.current().span;
+            self.bump();
+            let name = Name {
+                text: "*".to_string(),
+                span,
+            };
+            let all = self.declare(
+                name,
+                DeclKind::GlobalAll,
+                default,
+                Self::global_kind(default),
+            );
+            self.activate(all.decl);
+            return StatKind::GlobalAll(all);
+        }
+
+        let mut names = Vec::new();
+        while let Some(name) = self.check_name() {
+            let attrib = self.global_attrib().or(default);
+            names.push(self.declare(name, DeclKind::Global, attrib, Self::global_kind(attrib)));
+            if !self.test_next(LuaTokenKind::TkComma) {
+                break;
+            }
+        }
+        let values = if self.test_next(LuaTokenKind::TkAssign) {
+            self.explist()
+        } else {
+            Vec::new()
+        };
+        for name in &names {
+            self.activate(name.decl);
+        }
+        StatKind::Global { names, values }
+    }
+
+    fn globalfunc(&mut self, line: usize) -> StatKind {
+        let start = self.prev_start();
+        let Some(name) = self.check_name() else {
+            return StatKind::Error;
+        };
+        let name = self.declare(name, DeclKind::Global, None, VarKind::GDKREG);
+        self.activate(name.decl);
+        let func = self.body(start, None, line);
+        StatKind::GlobalFunction { name, func }
+    }
+
+    fn retstat(&mut self) -> Stat {
+        let start = self.start();
+        self.bump(); // skip RETURN
+        let values = if self.block_follow(true) || self.kind() == LuaTokenKind::TkSemicolon {
+            Vec::new()
+        } else {
+            self.explist()
+        };
+        self.test_next(LuaTokenKind::TkSemicolon);
+        Stat {
+            kind: StatKind::Return(values),
+            span: self.span_from(start),
+        }
+    }
+
+    fn exprstat(&mut self) -> StatKind {
+        let first = self.suffixedexp();
+        if matches!(self.kind(), LuaTokenKind::TkAssign | LuaTokenKind::TkComma) {
+            let mut targets = vec![first];
+            while self.test_next(LuaTokenKind::TkComma) {
+                targets.push(self.suffixedexp());
+            }
+            for target in &targets {
+                if !matches!(
+                    target.kind,
+                    ExprKind::Name(_)
+                        | ExprKind::Index { .. }
+                        | ExprKind::Field { .. }
+                        | ExprKind::Error
+                ) {
+                    self.error_at(
+                        target.span,
+                        "syntax error near assignment target".to_string(),
+                    );
+                }
+            }
+            self.expect(LuaTokenKind::TkAssign);
+            let values = self.explist();
+            return StatKind::Assign { targets, values };
+        }
+        match first.kind {
+            ExprKind::Call { .. } | ExprKind::MethodCall { .. } => StatKind::Call(first),
+            ExprKind::Error => StatKind::Error,
+            _ => {
+                self.error_here("syntax error");
+                StatKind::Error
+            }
+        }
+    }
+
+    // ===== Functions =====
+
+    /// Port of body: `(` parlist `)` block END. `self_span` is set for a
+    /// method, which gets an implicit `self` first parameter.
+    fn body(&mut self, start: usize, self_span: Option<SourceRange>, line: usize) -> FuncBody {
+        let mut names = Vec::new();
+        let mut is_vararg = false;
+        let mut vararg_name = None;
+        if self.expect(LuaTokenKind::TkLeftParen) {
+            if self.kind() != LuaTokenKind::TkRightParen {
+                loop {
+                    match self.kind() {
+                        LuaTokenKind::TkName => names.extend(self.check_name()),
+                        LuaTokenKind::TkDots => {
+                            self.bump();
+                            is_vararg = true;
+                            // Lua 5.5: named vararg parameter (...name)
+                            if self.kind() == LuaTokenKind::TkName {
+                                vararg_name = self.check_name();
+                            }
+                            break;
+                        }
+                        _ => {
+                            self.error_here("<name> expected");
+                            break;
+                        }
+                    }
+                    if !self.test_next(LuaTokenKind::TkComma) {
+                        break;
+                    }
+                }
+            }
+            self.expect(LuaTokenKind::TkRightParen);
+        }
+
+        self.open_func();
+        self.scope().is_vararg = is_vararg;
+        let mut params = Vec::new();
+        if let Some(span) = self_span {
+            let name = Name {
+                text: "self".to_string(),
+                span,
+            };
+            params.push(self.declare(name, DeclKind::SelfParameter, None, VarKind::VDKREG));
+        }
+        for name in names {
+            params.push(self.declare(name, DeclKind::Parameter, None, VarKind::VDKREG));
+        }
+        if let Some(name) = vararg_name {
+            params.push(self.declare(name, DeclKind::Parameter, None, VarKind::RDKVAVAR));
+        }
+        for param in &params {
+            self.activate(param.decl);
+        }
+        let body = self.statlist();
+        self.close_func();
+        self.check_match(LuaTokenKind::TkEnd, LuaTokenKind::TkFunction, line);
+
+        FuncBody {
+            params,
+            is_vararg,
+            body,
+            span: self.span_from(start),
+        }
+    }
+
+    // ===== Expressions =====
+
+    fn explist(&mut self) -> Vec<Expr> {
+        let mut exprs = vec![self.expr()];
+        while self.test_next(LuaTokenKind::TkComma) {
+            exprs.push(self.expr());
+        }
+        exprs
+    }
+
+    fn expr(&mut self) -> Expr {
+        self.subexpr(0)
+    }
+
+    /// Port of subexpr: operators with priority greater than `limit`.
+    fn subexpr(&mut self, limit: i32) -> Expr {
+        let start = self.start();
+        if !self.enter_level() {
+            self.leave_level();
+            return self.error_expr(start);
+        }
+        let uop = to_unary_operator(self.kind());
+        let mut lhs = if uop != UnaryOperator::OpNop {
+            self.bump();
+            let operand = self.subexpr(UNARY_PRIORITY);
+            Expr {
+                kind: ExprKind::Unary {
+                    op: uop,
+                    operand: Box::new(operand),
+                },
+                span: self.span_from(start),
+            }
+        } else {
+            self.simpleexp()
+        };
+
+        let mut op = to_binary_operator(self.kind());
+        while op != BinaryOperator::OpNop && op.get_priority().left > limit {
+            self.bump();
+            let rhs = self.subexpr(op.get_priority().right);
+            lhs = Expr {
+                kind: ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                span: self.span_from(start),
+            };
+            op = to_binary_operator(self.kind());
+        }
+        self.leave_level();
+        lhs
+    }
+
+    fn error_expr(&self, start: usize) -> Expr {
+        Expr {
+            kind: ExprKind::Error,
+            span: SourceRange::new(start, 0),
+        }
+    }
+
+    fn simpleexp(&mut self) -> Expr {
+        let start = self.start();
+        let kind = match self.kind() {
+            LuaTokenKind::TkInt | LuaTokenKind::TkFloat | LuaTokenKind::TkComplex => {
+                ExprKind::Number
+            }
+            LuaTokenKind::TkString | LuaTokenKind::TkLongString => ExprKind::String,
+            LuaTokenKind::TkNil => ExprKind::Nil,
+            LuaTokenKind::TkTrue => ExprKind::True,
+            LuaTokenKind::TkFalse => ExprKind::False,
+            LuaTokenKind::TkDots => {
+                if !self.scope().is_vararg {
+                    self.error_here("cannot use '...' outside a vararg function");
+                }
+                ExprKind::Vararg
+            }
+            LuaTokenKind::TkLeftBrace => {
+                let fields = self.constructor();
+                return Expr {
+                    kind: ExprKind::Table(fields),
+                    span: self.span_from(start),
+                };
+            }
+            LuaTokenKind::TkFunction => {
+                let line = self.current().start.line;
+                self.bump();
+                let func = self.body(start, None, line);
+                return Expr {
+                    kind: ExprKind::Function(func),
+                    span: self.span_from(start),
+                };
+            }
+            _ => return self.suffixedexp(),
+        };
+        self.bump();
+        Expr {
+            kind,
+            span: self.span_from(start),
+        }
+    }
+
+    fn primaryexp(&mut self) -> Expr {
+        let start = self.start();
+        match self.kind() {
+            LuaTokenKind::TkName => {
+                let name = self.check_name().expect("current token is a name");
+                let name_ref = self.resolve(&name);
+                Expr {
+                    kind: ExprKind::Name(name_ref),
+                    span: name.span,
+                }
+            }
+            LuaTokenKind::TkLeftParen => {
+                let line = self.current().start.line;
+                self.bump();
+                let inner = self.expr();
+                self.check_match(LuaTokenKind::TkRightParen, LuaTokenKind::TkLeftParen, line);
+                Expr {
+                    kind: ExprKind::Paren(Box::new(inner)),
+                    span: self.span_from(start),
+                }
+            }
+            _ => {
+                self.error_here("unexpected symbol");
+                // Take the token unless it may start the next statement
+                if !self.block_follow(true) && !self.starts_statement() {
+                    self.bump();
+                }
+                Expr {
+                    kind: ExprKind::Error,
+                    span: self.span_from(start),
+                }
+            }
+        }
+    }
+
+    fn starts_statement(&self) -> bool {
+        matches!(
+            self.kind(),
+            LuaTokenKind::TkIf
+                | LuaTokenKind::TkWhile
+                | LuaTokenKind::TkDo
+                | LuaTokenKind::TkFor
+                | LuaTokenKind::TkRepeat
+                | LuaTokenKind::TkFunction
+                | LuaTokenKind::TkLocal
+                | LuaTokenKind::TkReturn
+                | LuaTokenKind::TkBreak
+                | LuaTokenKind::TkGoto
+                | LuaTokenKind::TkDbColon
+        )
+    }
+
+    fn suffixedexp(&mut self) -> Expr {
+        let start = self.start();
+        let mut expr = self.primaryexp();
+        if expr.kind == ExprKind::Error {
+            return expr;
+        }
+        loop {
+            let kind = match self.kind() {
+                LuaTokenKind::TkDot => {
+                    self.bump();
+                    let Some(name) = self.check_name() else {
+                        break;
+                    };
+                    ExprKind::Field {
+                        obj: Box::new(expr),
+                        name,
+                    }
+                }
+                LuaTokenKind::TkLeftBracket => {
+                    self.bump();
+                    let key = self.expr();
+                    self.expect(LuaTokenKind::TkRightBracket);
+                    ExprKind::Index {
+                        obj: Box::new(expr),
+                        key: Box::new(key),
+                    }
+                }
+                LuaTokenKind::TkColon => {
+                    self.bump();
+                    let Some(method) = self.check_name() else {
+                        break;
+                    };
+                    let args = self.funcargs();
+                    ExprKind::MethodCall {
+                        obj: Box::new(expr),
+                        method,
+                        args,
+                    }
+                }
+                LuaTokenKind::TkLeftParen
+                | LuaTokenKind::TkString
+                | LuaTokenKind::TkLongString
+                | LuaTokenKind::TkLeftBrace => {
+                    let args = self.funcargs();
+                    ExprKind::Call {
+                        func: Box::new(expr),
+                        args,
+                    }
+                }
+                _ => break,
+            };
+            expr = Expr {
+                kind,
+                span: self.span_from(start),
+            };
+        }
+        expr
+    }
+
+    fn funcargs(&mut self) -> Vec<Expr> {
+        let start = self.start();
+        match self.kind() {
+            LuaTokenKind::TkLeftParen => {
+                let line = self.current().start.line;
+                self.bump();
+                let args = if self.kind() == LuaTokenKind::TkRightParen {
+                    Vec::new()
+                } else {
+                    self.explist()
+                };
+                self.check_match(LuaTokenKind::TkRightParen, LuaTokenKind::TkLeftParen, line);
+                args
+            }
+            LuaTokenKind::TkString | LuaTokenKind::TkLongString => {
+                self.bump();
+                vec![Expr {
+                    kind: ExprKind::String,
+                    span: self.span_from(start),
+                }]
+            }
+            LuaTokenKind::TkLeftBrace => {
+                let fields = self.constructor();
+                vec![Expr {
+                    kind: ExprKind::Table(fields),
+                    span: self.span_from(start),
+                }]
+            }
+            _ => {
+                self.error_here("function arguments expected");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Port of constructor: `{` [field {sep field} [sep]] `}`.
+    fn constructor(&mut self) -> Vec<TableField> {
+        let line = self.current().start.line;
+        self.bump(); // skip '{'
+        let mut fields = Vec::new();
+        while self.kind() != LuaTokenKind::TkRightBrace && self.kind() != LuaTokenKind::TkEof {
+            let before = self.pos;
+            let field = match self.kind() {
+                LuaTokenKind::TkName if self.peek_kind() == LuaTokenKind::TkAssign => {
+                    let name = self.check_name().expect("current token is a name");
+                    self.bump(); // skip '='
+                    TableField::Named {
+                        name,
+                        value: self.expr(),
+                    }
+                }
+                LuaTokenKind::TkLeftBracket => {
+                    self.bump();
+                    let key = self.expr();
+                    self.expect(LuaTokenKind::TkRightBracket);
+                    self.expect(LuaTokenKind::TkAssign);
+                    TableField::Indexed {
+                        key,
+                        value: self.expr(),
+                    }
+                }
+                _ => TableField::Positional(self.expr()),
+            };
+            fields.push(field);
+            if !self.test_next(LuaTokenKind::TkComma) && !self.test_next(LuaTokenKind::TkSemicolon)
+            {
+                break;
+            }
+            if self.pos == before {
+                break;
+            }
+        }
+        self.check_match(LuaTokenKind::TkRightBrace, LuaTokenKind::TkLeftBrace, line);
+        fields
+    }
+}
@@ -41,7 +41,8 @@ pub const MASKCOLORS: u8 = (1 << BLACKBIT) | WHITEBITS;
 ///       - Bit 5: BLACK
 ///       - Bit 6: FINALIZEDBIT
 ///       - Bit 7: SHAREDBIT
-///   `_padding: [u8; 3]` — keeps `index` naturally aligned
+///   `_padding: u8` — keeps `vm_id` naturally aligned
+///   `vm_id: u16` — identity of the owning VM, stamped by `trace_object`
 ///   `index: u32` — position in GcList (32-bit, max ~4.29B objects)
 ///
 ///   `size: u32` — allocation-time memory size estimate (for GC pacing).
@@ -52,7 +53,9 @@ pub const MASKCOLORS: u8 = (1 << BLACKBIT) | WHITEBITS;
 #[repr(C)]
 pub struct GcHeader {
     marked: Cell<u8>,
-    _padding: [u8; 3],
+    _padding: u8,
+    /// Identity of the VM whose GC registered the object (0 until then).
+    vm_id: Cell<u16>,
     index: Cell<u32>,
     size: Cell<u32>,
 }
@@ -61,6 +64,7 @@ impl std::fmt::Debug for GcHeader {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("GcHeader")
             .field("marked", &self.marked())
+            .field("vm_id", &self.vm_id())
             .field("index", &self.index())
             .field("size", &self.size())
             .finish()
@@ -97,6 +101,16 @@ impl GcHeader {
         self.index.set(idx as u32);
     }
 
+    #[inline(always)]
+    pub fn vm_id(&self) -> u16 {
+        self.vm_id.get()
+    }
+
+    #[inline(always)]
+    pub(crate) fn set_vm_id(&self, vm_id: u16) {
+        self.vm_id.set(vm_id);
+    }
+
     #[inline(always)]
     pub fn size(&self) -> u32 {
         self.size.get()
@@ -115,7 +129,8 @@ impl Default for GcHeader {
         // Use GcHeader::with_white(current_white) instead when creating GC objects
         GcHeader {
             marked: Cell::new(G_NEW),
-            _padding: [0; 3],
+            _padding: 0,
+            vm_id: Cell::new(0),
             index: Cell::new(0),
             size: Cell::new(0),
         }
@@ -134,7 +149,8 @@ impl GcHeader {
         );
         GcHeader {
             marked: Cell::new((1 << (WHITE0BIT + current_white)) | G_NEW),
-            _padding: [0; 3],
+            _padding: 0,
+            vm_id: Cell::new(0),
             index: Cell::new(0),
             size: Cell::new(0),
         }
@@ -737,7 +753,7 @@ pub enum GcObjectOwner {
     Table(Pooled<GcTable>),
     Function(Pooled<GcFunction>),
     Upvalue(Pooled<GcUpvalue>),
-    Thread(Box<GcThread>),
+    Thread(Pooled<GcThread>),
     Userdata(Pooled<GcUserdata>),
     CClosure(Pooled<GcCClosure>),
     RClosure(Pooled<GcRClosure>),
@@ -760,7 +776,7 @@ impl GcObjectOwner {
             GcObjectOwner::CClosure(c) => c.as_ptr() as *mut GcHeader,
             GcObjectOwner::RClosure(r) => r.as_ptr() as *mut GcHeader,
             GcObjectOwner::Upvalue(u) => u.as_ptr() as *mut GcHeader,
-            GcObjectOwner::Thread(t) => t.as_ref() as *const _ as *mut GcHeader,
+            GcObjectOwner::Thread(t) => t.as_ptr() as *mut GcHeader,
             GcObjectOwner::Userdata(u) => u.as_ptr() as *mut GcHeader,
             GcObjectOwner::Proto(p) => p.as_ptr() as *mut GcHeader,
         }
@@ -806,7 +822,7 @@ impl GcObjectOwner {
 
     pub fn as_thread_ptr(&self) -> Option<ThreadPtr> {
         match self {
-            GcObjectOwner::Thread(t) => Some(ThreadPtr::new(t.as_ref() as *const _)),
+            GcObjectOwner::Thread(t) => Some(ThreadPtr::new(t.as_ptr())),
             _ => None,
         }
     }
@@ -845,7 +861,7 @@ impl GcObjectOwner {
             GcObjectOwner::Table(t) => GcObjectPtr::from(TablePtr::new(t.as_ptr())),
             GcObjectOwner::Function(f) => GcObjectPtr::from(FunctionPtr::new(f.as_ptr())),
             GcObjectOwner::Upvalue(u) => GcObjectPtr::from(UpvaluePtr::new(u.as_ptr())),
-            GcObjectOwner::Thread(t) => GcObjectPtr::from(ThreadPtr::new(t.as_ref() as *const _)),
+            GcObjectOwner::Thread(t) => GcObjectPtr::from(ThreadPtr::new(t.as_ptr())),
             GcObjectOwner::Userdata(u) => GcObjectPtr::from(UserdataPtr::new(u.as_ptr())),
             GcObjectOwner::CClosure(c) => GcObjectPtr::from(CClosurePtr::new(c.as_ptr())),
             GcObjectOwner::RClosure(r) => GcObjectPtr::from(RClosurePtr::new(r.as_ptr())),
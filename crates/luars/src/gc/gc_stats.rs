@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+/// The kind of a collection, as reported in [`GcStats`] and [`GcEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GcCollectionKind {
+    /// An incremental cycle, spread over many steps between which the
+    /// program keeps running.
+    Incremental,
+    /// A major cycle of generational mode, also run incrementally.
+    Major,
+    /// A minor (young) collection of generational mode.
+    Minor,
+    /// A full collection, as run by `collectgarbage()`.
+    Full,
+    /// A full collection forced by an allocation over the memory limit.
+    Emergency,
+}
+
+/// A collection starting or ending, delivered to the hook installed with
+/// [`GlobalState::set_gc_event_hook`](crate::GlobalState::set_gc_event_hook).
+///
+/// Every `Start` is followed by exactly one `End` of the same kind. A
+/// collection that takes over an unfinished one (a full collection in the
+/// middle of an incremental cycle, say) ends it first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GcEvent {
+    Start {
+        kind: GcCollectionKind,
+        /// Bytes in use when the collection started.
+        bytes: usize,
+    },
+    End {
+        kind: GcCollectionKind,
+        bytes_before: usize,
+        bytes_after: usize,
+        /// Wall time from start to end. For incremental and major cycles
+        /// this includes the program running between steps.
+        duration: Duration,
+    },
+}
+
+/// A snapshot of the collector, returned by
+/// [`GlobalState::gc_stats_struct`](crate::GlobalState::gc_stats_struct).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GcStats {
+    /// Bytes currently in use by Lua objects.
+    pub bytes_allocated: usize,
+    /// Bytes ever allocated for new objects.
+    pub total_allocated: usize,
+    /// Bytes reclaimed by finished collections, net of what the program
+    /// allocated while an incremental cycle was running.
+    pub bytes_reclaimed: usize,
+    /// `bytes_allocated` at which the next collection step runs.
+    pub threshold: usize,
+    /// Bytes left to allocate before that step; zero or less means it is due.
+    pub debt: isize,
+    pub incremental_cycles: usize,
+    pub major_cycles: usize,
+    pub minor_collections: usize,
+    pub full_collections: usize,
+    pub emergency_collections: usize,
+    /// Duration of the last finished collection, as in [`GcEvent::End`].
+    pub last_pause_duration: Duration,
+    /// Objects in the young generation (all objects in incremental mode).
+    pub young_gen_size: usize,
+    /// Objects in the old generation (none in incremental mode).
+    pub old_gen_size: usize,
+}
+
+impl GcStats {
+    /// Collections of `kind` finished so far.
+    pub fn collections(&self, kind: GcCollectionKind) -> usize {
+        match kind {
+            GcCollectionKind::Incremental => self.incremental_cycles,
+            GcCollectionKind::Major => self.major_cycles,
+            GcCollectionKind::Minor => self.minor_collections,
+            GcCollectionKind::Full => self.full_collections,
+            GcCollectionKind::Emergency => self.emergency_collections,
+        }
+    }
+
+    pub(crate) fn count(&mut self, kind: GcCollectionKind) {
+        match kind {
+            GcCollectionKind::Incremental => self.incremental_cycles += 1,
+            GcCollectionKind::Major => self.major_cycles += 1,
+            GcCollectionKind::Minor => self.minor_collections += 1,
+            GcCollectionKind::Full => self.full_collections += 1,
+            GcCollectionKind::Emergency => self.emergency_collections += 1,
+        }
+    }
+}
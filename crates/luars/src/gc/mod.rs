@@ -36,13 +36,19 @@
 
 mod gc_kind;
 mod gc_object;
+mod gc_stats;
 mod object_allocator;
 mod paged_pool;
 mod string_interner;
 
+use std::any::Any;
 #[cfg(debug_assertions)]
 use std::collections::HashMap;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::Mutex;
+use std::time::Duration;
 
+use crate::platform_time::PlatformInstant;
 use crate::{
     LuaRawTable, LuaResult,
     lua_value::LuaValue,
@@ -50,6 +56,7 @@ use crate::{
 };
 pub use gc_kind::*;
 pub use gc_object::*;
+pub use gc_stats::*;
 pub use object_allocator::*;
 pub use paged_pool::*;
 pub use string_interner::*;
@@ -82,6 +89,37 @@ const GCSWEEPMAX: isize = GC_SWEEPMAX;
 
 /// Maximum l_mem value (like MAX_LMEM in Lua 5.5)
 const MAX_LMEM: isize = isize::MAX;
+
+/// Source of per-VM identities. 0 is never handed out, so an object whose
+/// header still reads 0 was not registered by any VM. Ids of dropped VMs are
+/// recycled rather than the counter wrapping, so no two live VMs ever share
+/// one; creating a VM while all 65535 ids are in use panics.
+static VM_IDS: Mutex<VmIds> = Mutex::new(VmIds {
+    next: 1,
+    free: Vec::new(),
+});
+
+struct VmIds {
+    next: u16,
+    free: Vec<u16>,
+}
+
+fn acquire_vm_id() -> u16 {
+    let mut ids = VM_IDS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(id) = ids.free.pop() {
+        return id;
+    }
+    let id = ids.next;
+    assert!(id != 0, "too many live Lua VMs (at most 65535)");
+    ids.next = id.wrapping_add(1);
+    id
+}
+
+fn release_vm_id(id: u16) {
+    let mut ids = VM_IDS.lock().unwrap_or_else(|e| e.into_inner());
+    ids.free.push(id);
+}
+
 /// Compute ceil(log2(x)) for GC parameter encoding
 /// Port of luaO_ceillog2 from Lua 5.5 lobject.c
 fn ceil_log2(x: u32) -> u8 {
@@ -268,6 +306,15 @@ pub struct GC {
     /// GC stopped by user (gcstp in Lua, GCSTPUSR bit)
     pub gc_stopped: bool,
 
+    /// The state is being closed (GCSTPCLS): no more steps, and objects
+    /// given a finalizer from here on are not registered.
+    pub gc_closing: bool,
+
+    /// The collector itself is running: a step, a minor collection or a
+    /// full cycle, including the finalizers it calls. Nested requests to
+    /// collect are turned into no-ops while it is set.
+    in_collection: bool,
+
     /// Objects pending __gc finalization (Lua 5.5: g->tobefnz)
     ///
     /// We keep a Vec instead of a linked list; objects stay in the main pool.
@@ -307,13 +354,24 @@ pub struct GC {
     /// Finalizers called during GC
     finobj: Vec<GcObjectPtr>,
 
+    /// Scratch buffer for the registered userdata metatables marked by
+    /// `mark_mt`, kept between cycles so marking them does not allocate.
+    userdata_mts: Vec<LuaValue>,
+
     // === Sweep state ===
     /// Current position in sweep (like Lua 5.5's sweepgc pointer)
     /// This ensures we don't re-scan the same objects
     sweepgc: SweepGc,
 
     // === Statistics ===
-    pub stats: GcStats,
+    /// Counters of `stats()`; the live fields are filled in on demand.
+    stats: GcStats,
+
+    /// The collection in progress: its kind, when it started and the bytes
+    /// in use then.
+    collection: Option<(GcCollectionKind, PlatformInstant, usize)>,
+
+    event_hook: Option<Box<GcEventHook>>,
 
     pub tm_gc: LuaValue,
 
@@ -326,23 +384,22 @@ pub struct GC {
     gc_error_msg: Option<String>,
 
     gc_memory_check: bool,
-}
 
-#[derive(Debug, Clone, Default)]
-pub struct GcStats {
-    pub collection_count: usize,
-    pub minor_collections: usize,
-    pub major_collections: usize,
-    pub objects_collected: usize,
-    pub bytes_allocated: usize,
-    #[allow(unused)]
-    pub bytes_freed: usize,
-    pub threshold: usize,
-    pub young_gen_size: usize,
-    pub old_gen_size: usize,
-    pub promoted_objects: usize,
+    /// An allocation crossed `max_memory_limit`; run an emergency full
+    /// collection at the next GC check instead of failing right away.
+    emergency_requested: bool,
+
+    /// The last emergency collection could not get back under the limit, so
+    /// further over-limit allocations fail until a cycle ends below it.
+    emergency_failed: bool,
+
+    /// Identity stamped into the header of every object this GC registers,
+    /// so values carried over from another VM can be rejected.
+    vm_id: u16,
 }
 
+pub(crate) type GcEventHook = dyn Fn(GcEvent);
+
 impl GC {
     pub fn new(option: SafeOption) -> Self {
         let mut gc = GC {
@@ -361,6 +418,8 @@ impl GC {
             gc_emergency: false,
             gc_stopem: false,
             gc_stopped: false,
+            gc_closing: false,
+            in_collection: false,
             tobefnz: Vec::new(),
             gc_params: [0; GCPARAM_COUNT], // Default to 100%
             gray: Vec::with_capacity(128),
@@ -371,14 +430,20 @@ impl GC {
             twups: Vec::new(),
             dead_threads_with_upvalues: Vec::new(),
             finobj: Vec::new(),
+            userdata_mts: Vec::new(),
             sweepgc: SweepGc::AllGc(0),
             stats: GcStats::default(),
+            collection: None,
+            event_hook: None,
             tm_gc: LuaValue::nil(),
             tm_mode: LuaValue::nil(),
             max_memory_limit: option.max_memory_limit,
             tmp_max_memory_limit: None,
             gc_error_msg: None,
             gc_memory_check: true,
+            emergency_requested: false,
+            emergency_failed: false,
+            vm_id: acquire_vm_id(),
         };
 
         gc.gc_params[PAUSE] = code_param(DEFAULT_PAUSE as u32);
@@ -518,6 +583,9 @@ impl GC {
     /// }
     /// ```
     pub fn change_mode(&mut self, l: &mut LuaState, new_mode: GcKind) {
+        // Switching modes finishes any cycle in progress
+        self.end_collection();
+
         // GenMajor is really incremental under the hood
         if self.gc_kind == GcKind::GenMajor {
             self.gc_kind = GcKind::Inc;
@@ -533,6 +601,24 @@ impl GC {
         }
     }
 
+    /// Whether `value` is not a GC object of some other VM. A single compare
+    /// against the header, cheap enough for API boundaries. Shared objects
+    /// (SHAREDBIT) are immutable and outlive every VM, so they belong to all.
+    ///
+    /// Only values of a VM that is still alive can be told apart: once a VM
+    /// is dropped its objects are freed, and reading the header of one is
+    /// itself a dangling access.
+    #[inline(always)]
+    pub fn owns_value(&self, value: &LuaValue) -> bool {
+        if !value.is_collectable() {
+            return true;
+        }
+        match value.as_gc_ptr_unchecked().header() {
+            Some(header) => header.vm_id() == self.vm_id || header.is_shared(),
+            None => true,
+        }
+    }
+
     /// Register a newly created GC object.
     ///
     /// Hot path is inlined: reads `header.size`, pushes to `allgc`, adjusts debt.
@@ -545,28 +631,86 @@ impl GC {
         if self.gc_memory_check {
             let total_bytes = self.get_total_bytes();
             let limit_bytes = self.get_limit_bytes();
-            if total_bytes + size as isize > limit_bytes {
+            if total_bytes + size as isize > limit_bytes && !self.request_emergency_gc(size) {
                 return Err(LuaError::OutOfMemory);
             }
         }
 
+        let header = gc_object_owner.header();
+        header.set_vm_id(self.vm_id);
+        // Created by the collector itself between marking and the white
+        // flip: a white object would read as dead once the flip happens.
+        if self.gc_state == GcState::Atomic {
+            header.make_black();
+        }
         // New objects always have age G_NEW, so skip the age match on the hot path
         self.allgc.add(gc_object_owner);
         self.gc_debt -= size as isize;
-        self.stats.bytes_allocated += size;
+        self.stats.total_allocated += size;
 
         Ok(())
     }
 
+    /// Over-limit allocation: decide between failing now and letting it
+    /// through with an emergency collection scheduled (the "try again" of
+    /// `luaM_malloc_`). The collection cannot run here, as callers may still
+    /// hold unanchored objects, so it runs at the next GC check, which the
+    /// zeroed debt forces. A sandbox budget (`tmp_max_memory_limit`) is a
+    /// hard cap and never retries, and an object larger than the whole
+    /// limit could not fit after any collection.
+    #[cold]
+    fn request_emergency_gc(&mut self, size: usize) -> bool {
+        if self.tmp_max_memory_limit.is_some()
+            || self.emergency_failed
+            || size as isize > self.max_memory_limit
+        {
+            return false;
+        }
+        if !self.emergency_requested {
+            self.emergency_requested = true;
+            if self.gc_debt > 0 {
+                self.set_debt(0);
+            }
+        }
+        true
+    }
+
+    /// Take a pending emergency request (see `request_emergency_gc`).
+    #[inline]
+    pub(crate) fn take_emergency_request(&mut self) -> bool {
+        if self.emergency_requested && !self.is_blocked() {
+            self.emergency_requested = false;
+            return true;
+        }
+        false
+    }
+
+    /// After an emergency collection: remember whether it was enough.
+    pub(crate) fn finish_emergency_gc(&mut self) {
+        self.emergency_failed = self.get_total_bytes() > self.get_limit_bytes();
+    }
+
     /// Track memory delta from a table resize.
     /// Mirrors Lua 5.5's `luaM_realloc_` which adjusts GCdebt by the size delta.
     /// Also updates `header.size` to reflect the new total object size.
     #[inline]
     pub fn track_resize(&mut self, table_ptr: TablePtr, delta: isize) {
+        self.track_header_resize(&table_ptr.as_ref().header, delta);
+    }
+
+    /// Track memory delta from a thread's stack growing (luaD_reallocstack).
+    #[inline]
+    pub fn track_thread_resize(&mut self, thread_ptr: ThreadPtr, delta: isize) {
+        if !thread_ptr.is_null() {
+            self.track_header_resize(&thread_ptr.as_ref().header, delta);
+        }
+    }
+
+    #[inline]
+    fn track_header_resize(&mut self, header: &GcHeader, delta: isize) {
         if delta != 0 {
             self.gc_debt -= delta; // allocation grows debt, deallocation shrinks it
-            // Update stored size in the GC header
-            let header = &mut table_ptr.as_mut_ref().header;
+            // Update stored size in the GC header so sweep frees the right amount
             let old_header_size = header.size() as isize;
             header.set_size((old_header_size + delta).max(0) as u32);
         }
@@ -649,6 +793,51 @@ impl GC {
         }
     }
 
+    /// Bytes that can still be allocated before the memory limit (or the
+    /// sandbox budget) is reached. Lets library functions reject a huge
+    /// result before building it instead of after.
+    #[inline]
+    pub(crate) fn memory_headroom(&self) -> isize {
+        if !self.gc_memory_check {
+            return isize::MAX;
+        }
+        self.get_limit_bytes()
+            .saturating_sub(self.get_total_bytes())
+            .max(0)
+    }
+
+    /// Whether an over-limit allocation may collect and try again: a
+    /// sandbox budget is a hard cap, and a collection already in progress
+    /// cannot be restarted.
+    pub(crate) fn can_retry_allocation(&self) -> bool {
+        self.tmp_max_memory_limit.is_none() && !self.is_blocked()
+    }
+
+    /// Whether a request to collect must be refused: the collector is
+    /// already running, a finalizer is executing, or the state is closing.
+    /// `collectgarbage` then returns fail, as `lua_gc` does for GCSTPGC.
+    #[inline(always)]
+    pub fn is_blocked(&self) -> bool {
+        self.in_collection || self.gc_stopem || self.gc_closing
+    }
+
+    /// Enter the collector; returns false (and does nothing) when it is
+    /// blocked. Pair with [`leave_collection`](Self::leave_collection).
+    #[inline]
+    pub(crate) fn enter_collection(&mut self) -> bool {
+        if self.is_blocked() {
+            return false;
+        }
+        self.in_collection = true;
+        true
+    }
+
+    #[inline]
+    pub(crate) fn leave_collection(&mut self) {
+        debug_assert!(self.in_collection, "leave_collection without enter");
+        self.in_collection = false;
+    }
+
     /// set new additional temporary memory limit
     pub fn set_temporary_memory_limit(&mut self, limit: isize) {
         let current_total_bytes = self.get_total_bytes();
@@ -716,9 +905,70 @@ impl GC {
         }
     }
 
+    /// Returns true if `ptr` names an object currently owned by this collector.
+    ///
+    /// The candidate is only compared by address and never dereferenced, so it
+    /// is safe to call with a pointer whose object has already been swept, but
+    /// a new object at the same address also matches. Linear in the number of
+    /// live objects: only a fallback for objects allocated outside the pools,
+    /// which `ObjectAllocator::slot_generation` cannot see.
+    pub fn owns_object(&self, ptr: GcObjectPtr) -> bool {
+        [
+            &self.allgc,
+            &self.survival,
+            &self.old1,
+            &self.old,
+            &self.fixed_list,
+        ]
+        .into_iter()
+        .any(|list| list.iter_ptrs().any(|owned| owned == ptr))
+    }
+
     /// Get current GC statistics
-    pub fn stats(&self) -> &GcStats {
-        &self.stats
+    pub fn stats(&self) -> GcStats {
+        GcStats {
+            bytes_allocated: self.get_total_bytes().max(0) as usize,
+            threshold: self.total_bytes.max(0) as usize,
+            debt: self.gc_debt,
+            young_gen_size: self.allgc.len() + self.survival.len(),
+            old_gen_size: self.old1.len() + self.old.len(),
+            ..self.stats.clone()
+        }
+    }
+
+    pub(crate) fn set_event_hook(&mut self, hook: Option<Box<GcEventHook>>) {
+        self.event_hook = hook;
+    }
+
+    /// Start timing a collection of `kind`, ending any unfinished one it
+    /// takes over so events stay paired.
+    pub(crate) fn begin_collection(&mut self, kind: GcCollectionKind) {
+        self.end_collection();
+        let bytes = self.get_total_bytes().max(0) as usize;
+        self.collection = Some((kind, PlatformInstant::now(), bytes));
+        if let Some(hook) = &self.event_hook {
+            hook(GcEvent::Start { kind, bytes });
+        }
+    }
+
+    /// Finish the collection started by `begin_collection`, if any.
+    pub(crate) fn end_collection(&mut self) {
+        let Some((kind, started, bytes_before)) = self.collection.take() else {
+            return;
+        };
+        let duration = Duration::from_secs_f64(started.elapsed_secs_f64());
+        let bytes_after = self.get_total_bytes().max(0) as usize;
+        self.stats.count(kind);
+        self.stats.bytes_reclaimed += bytes_before.saturating_sub(bytes_after);
+        self.stats.last_pause_duration = duration;
+        if let Some(hook) = &self.event_hook {
+            hook(GcEvent::End {
+                kind,
+                bytes_before,
+                bytes_after,
+                duration,
+            });
+        }
     }
 
     /// Check if a GcPtr represents a dead object (will be collected)
@@ -797,7 +1047,7 @@ impl GC {
             return;
         };
 
-        if header.to_finalize() {
+        if header.to_finalize() || self.gc_closing {
             return;
         };
 
@@ -1013,8 +1263,9 @@ impl GC {
             return;
         }
 
-        // BUG FIX: Prevent GC reentrancy during finalization or single_step.
-        if self.gc_stopem {
+        // No reentrancy: allocations made by finalizers or by the collector
+        // itself land here while a collection is already under way.
+        if !self.enter_collection() {
             self.set_debt(20000);
             return;
         }
@@ -1029,6 +1280,7 @@ impl GC {
                 }
             }
         }
+        self.leave_collection();
     }
 
     /// Incremental GC step (like incstep in Lua 5.5)
@@ -1065,6 +1317,14 @@ impl GC {
         // int fast = (work2do == 0);
         let fast = work2do == 0;
 
+        if self.collection.is_none() {
+            self.begin_collection(if self.gc_kind == GcKind::GenMajor {
+                GcCollectionKind::Major
+            } else {
+                GcCollectionKind::Incremental
+            });
+        }
+
         // Repeat until enough work is done (like Lua 5.5's do-while loop)
         loop {
             let stres = self.single_step(l, fast);
@@ -1072,6 +1332,7 @@ impl GC {
             match stres {
                 StepResult::Step2Minor => {
                     // Returned to minor collections
+                    self.end_collection();
                     return;
                 }
                 StepResult::Step2Pause => {
@@ -1100,6 +1361,7 @@ impl GC {
         // Set debt for next step (like Lua 5.5 incstep)
 
         if self.gc_state == GcState::Pause {
+            self.end_collection();
             self.set_pause();
         } else {
             // Lua 5.5: luaE_setdebt(g, stepsize);
@@ -1110,7 +1372,8 @@ impl GC {
 
     /// Single GC step (like singlestep in Lua 5.5)
     fn single_step(&mut self, l: &mut LuaState, fast: bool) -> StepResult {
-        self.gc_stopem = true;
+        debug_assert!(self.in_collection, "GC step outside enter_collection");
+        let old_stopem = std::mem::replace(&mut self.gc_stopem, true);
 
         let result = match self.gc_state {
             GcState::Pause => {
@@ -1158,6 +1421,9 @@ impl GC {
                 StepResult::Work(GCSWEEPMAX)
             }
             GcState::SwpEnd => {
+                // The sweep whitened every survivor, so objects linked by
+                // barriers during the sweep need no traversal any more.
+                self.clear_gray_lists();
                 self.gc_state = GcState::CallFin;
                 StepResult::Work(GCSWEEPMAX)
             }
@@ -1171,6 +1437,7 @@ impl GC {
                     StepResult::Work(GCSWEEPMAX)
                 } else {
                     // No more finalizers
+                    debug_assert!(self.gray.is_empty(), "gray list not empty at end of cycle");
                     self.gc_state = GcState::Pause;
                     StepResult::Step2Pause
                 }
@@ -1181,7 +1448,7 @@ impl GC {
             }
         };
 
-        self.gc_stopem = false;
+        self.gc_stopem = old_stopem;
         result
     }
 
@@ -1216,8 +1483,6 @@ impl GC {
     /// This is safe because restartcollection is only called from GCSpause state,
     /// meaning the previous cycle has completely finished (atomic phase cleared weak tables).
     fn restart_collection(&mut self, l: &mut LuaState) {
-        self.stats.collection_count += 1;
-
         //  Reset sweep_index when starting a new cycle
         // This ensures the next sweep will scan all objects from the beginning
         self.sweepgc = SweepGc::Done;
@@ -1274,6 +1539,22 @@ impl GC {
                 self.mark_object(l, mt_ptr);
             }
         }
+        // Registered userdata metatables are also reachable from the registry,
+        // but Lua code can overwrite that entry while instances still use them.
+        let mut userdata_mts = std::mem::take(&mut self.userdata_mts);
+        userdata_mts.clear();
+        userdata_mts.extend(
+            l.global_state()
+                .userdata_types
+                .values()
+                .map(|meta| meta.metatable),
+        );
+        for mt in &userdata_mts {
+            if let Some(mt_ptr) = mt.as_gc_ptr() {
+                self.mark_object(l, mt_ptr);
+            }
+        }
+        self.userdata_mts = userdata_mts;
     }
 
     /// Port of Lua 5.5's checkminormajor:
@@ -1680,6 +1961,9 @@ impl GC {
             }
         });
 
+        let delta = gc_table.data.shrink_hash_if_sparse();
+        self.track_resize(table_ptr, delta);
+
         self.gen_link(table_ptr.into());
     }
 
@@ -1747,7 +2031,7 @@ impl GC {
 
         // CRITICAL FIX: Use next() instead of iter_all() to avoid allocation
         let mut key = LuaValue::nil();
-        while let Some((k, v)) = table.next(&key).unwrap_or(None) {
+        while let Some((k, v)) = table.next_untracked(&key).unwrap_or(None) {
             // Mark key (strong reference)
             if let Some(key_ptr) = k.as_gc_ptr() {
                 self.mark_object(l, key_ptr);
@@ -1830,7 +2114,7 @@ impl GC {
         let mut marked = self.traverse_array(l, table_ptr);
 
         let mut key = LuaValue::nil();
-        while let Some((k, v)) = table.next(&key).unwrap_or(None) {
+        while let Some((k, v)) = table.next_untracked(&key).unwrap_or(None) {
             let key_ptr = k.as_gc_ptr();
             let val_ptr = v.as_gc_ptr();
 
@@ -1989,6 +2273,10 @@ impl GC {
                 if let ErrorMsg::Object(dead_err_obj) = state.dead_error() {
                     self.mark_value(l, dead_err_obj);
                 }
+                if let Some((err, co)) = state.coroutine_error_value() {
+                    self.mark_value(l, &err);
+                    self.mark_value(l, &co);
+                }
                 return 1;
             }
 
@@ -2045,6 +2333,13 @@ impl GC {
                 count += 1;
             }
 
+            // The coroutine (and its error) kept for tracebacks
+            if let Some((err, co)) = state.coroutine_error_value() {
+                self.mark_value(l, &err);
+                self.mark_value(l, &co);
+                count += 2;
+            }
+
             // Mark per-thread debug hook function (may be a GC-managed closure)
             let hook = &state.hook;
             if !hook.is_nil() {
@@ -2250,6 +2545,10 @@ impl GC {
         // first clearbyvalues pass.
         self.clear_by_values(l, orig_weak_len, orig_allweak_len);
 
+        debug_assert!(
+            self.gray.is_empty(),
+            "gray list not empty after atomic marking"
+        );
         self.current_white = GcHeader::otherwhite(self.current_white); // Flip current white
 
         // CRITICAL: Close open upvalues on dead threads BEFORE sweep starts.
@@ -2764,6 +3063,9 @@ impl GC {
         let threshold = self.apply_param(PAUSE, self.gc_marked);
         let real_bytes = self.total_bytes - self.gc_debt;
         let mut debt = threshold - real_bytes;
+        if real_bytes <= self.get_limit_bytes() {
+            self.emergency_failed = false;
+        }
 
         if debt < 0 {
             debt = 0;
@@ -3333,7 +3635,7 @@ impl GC {
     }
 
     fn young_collection(&mut self, l: &mut LuaState) {
-        self.stats.minor_collections += 1;
+        self.begin_collection(GcCollectionKind::Minor);
 
         //  Set gc_stopem to prevent recursive GC during collection
         // This matches Lua 5.5's behavior where GC steps check gc_stopem
@@ -3387,6 +3689,7 @@ impl GC {
 
         // Restore gc_stopem
         self.gc_stopem = old_stopem;
+        self.end_collection();
     }
 
     /// Sweep the young generation, promoting survivors
@@ -4012,13 +4315,19 @@ impl GC {
     /// }
     /// ```
     fn call_one_finalizer(&mut self, l: &mut LuaState) {
+        if let Some(msg) = self.run_one_finalizer(l) {
+            let _ = crate::stdlib::basic::warn_error(l, "__gc", &msg);
+        }
+    }
+
+    /// GCTM body: run the next pending finalizer and return the error it
+    /// raised, if any.
+    fn run_one_finalizer(&mut self, l: &mut LuaState) -> Option<String> {
         use crate::lua_vm::get_metamethod_event;
         debug_assert!(!self.gc_emergency, "GCTM called during emergency GC");
 
         // Get next object to finalize
-        let Some(gc_ptr) = self.udata2finalize() else {
-            return; // No more objects to finalize
-        };
+        let gc_ptr = self.udata2finalize()?;
 
         // Convert GcObjectPtr to LuaValue
         let obj_value = if gc_ptr.is_table() {
@@ -4029,13 +4338,11 @@ impl GC {
             LuaValue::thread(gc_ptr.as_thread_ptr())
         } else {
             // Other types don't support __gc
-            return;
+            return None;
         };
 
         // Get __gc metamethod
-        let Some(gc_method) = get_metamethod_event(l, &obj_value, TmKind::Gc) else {
-            return; // No __gc metamethod
-        };
+        let gc_method = get_metamethod_event(l, &obj_value, TmKind::Gc)?;
 
         // Stop GC during finalization (g->gcstp |= GCSTPGC)
         // GCSTPGC prevents GC reentrancy by making collectgarbage() return false
@@ -4051,16 +4358,22 @@ impl GC {
         l.allow_hook = false;
 
         // Call __gc(obj) using pcall to handle errors safely
-        let result = l.pcall(gc_method, vec![obj_value]);
+        let result = l.pcall_args(gc_method, &[obj_value]);
 
         // Restore hook state and GC state
         l.allow_hook = old_allow_hook;
         self.gc_stopem = old_stopem;
 
-        // If error occurred, warn but don't propagate
-        if result.is_err() {
-            let msg = l.get_error_msg(LuaError::RuntimeError);
-            eprintln!("[GC] WARNING: error in __gc: {}", msg);
+        // If error occurred, report it but don't propagate
+        match result {
+            Ok((true, _)) => None,
+            Ok((false, values)) => Some(
+                values
+                    .first()
+                    .and_then(|v| v.as_str().map(str::to_string))
+                    .unwrap_or_else(|| "error object is not a string".to_string()),
+            ),
+            Err(e) => Some(l.get_error_msg(e)),
         }
     }
 
@@ -4091,27 +4404,77 @@ impl GC {
     //         }
     //     }
     // }
+    /// Keeps both lists in registration order: `tobefnz` is popped from the
+    /// back, so finalizers run newest registration first, as in C Lua.
     fn separate_to_be_finalized(&mut self, all: bool) {
-        let mut i = 0;
-        while i < self.finobj.len() {
-            let gc_ptr = self.finobj[i];
-
+        let finobj = std::mem::take(&mut self.finobj);
+        for gc_ptr in finobj {
             let is_white = gc_ptr
                 .header()
                 .map(|header| header.is_white())
                 .unwrap_or(false);
 
             if is_white || all {
-                // Remove from finobj
-                self.finobj.swap_remove(i);
-                // Add to tobefnz
                 self.tobefnz.push(gc_ptr);
             } else {
-                i += 1; // Only increment if not removed
+                self.finobj.push(gc_ptr);
             }
         }
     }
 
+    /// First half of luaC_freeallobjects: stop the collector, queue every
+    /// object that still has a finalizer behind the already pending ones and
+    /// run them all. Returns the errors raised by the finalizers.
+    pub(crate) fn call_all_finalizers_at_close(&mut self, l: &mut LuaState) -> Vec<String> {
+        self.gc_closing = true;
+        self.gc_emergency = false;
+        let pending = std::mem::take(&mut self.tobefnz);
+        self.separate_to_be_finalized(true);
+        self.tobefnz.extend(pending);
+
+        let mut errors = Vec::new();
+        while !self.tobefnz.is_empty() {
+            errors.extend(self.run_one_finalizer(l));
+        }
+        errors
+    }
+
+    /// Second half of luaC_freeallobjects: free every object. Userdata go
+    /// first, each inside `catch_unwind` so one panicking `Drop` cannot stop
+    /// the rest from being dropped; tables, strings and functions follow.
+    /// Returns the messages of the panics caught.
+    pub(crate) fn free_all_objects(&mut self) -> Vec<String> {
+        self.gc_closing = true;
+        self.finobj.clear();
+        self.tobefnz.clear();
+
+        let mut objects = self.fixed_list.take_all();
+        for list in [
+            &mut self.old,
+            &mut self.old1,
+            &mut self.survival,
+            &mut self.allgc,
+        ] {
+            objects.extend(list.take_all());
+        }
+        let (userdata, rest): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .partition(|obj| matches!(obj, GcObjectOwner::Userdata(_)));
+
+        let mut panics = Vec::new();
+        for obj in userdata.into_iter().rev() {
+            if let Err(payload) =
+                catch_unwind(AssertUnwindSafe(|| Self::release_or_detach_object(obj)))
+            {
+                panics.push(panic_message(&*payload).to_string());
+            }
+        }
+        for obj in rest {
+            Self::release_or_detach_object(obj);
+        }
+        panics
+    }
+
     /// Port of Lua 5.5's markbeingfnz:
     /// ```c
     /// static void markbeingfnz (global_State *g) {
@@ -4198,23 +4561,22 @@ pub fn share_proto(proto_ptr: ProtoPtr) -> usize {
     mark_proto(proto_ptr)
 }
 
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown panic"
+    }
+}
+
 impl Drop for GC {
     fn drop(&mut self) {
-        for obj in self.allgc.take_all() {
-            Self::release_or_detach_object(obj);
-        }
-        for obj in self.survival.take_all() {
-            Self::release_or_detach_object(obj);
-        }
-        for obj in self.old1.take_all() {
-            Self::release_or_detach_object(obj);
-        }
-        for obj in self.old.take_all() {
-            Self::release_or_detach_object(obj);
-        }
-        for obj in self.fixed_list.take_all() {
-            Self::release_or_detach_object(obj);
-        }
+        // Only reached without `GlobalState::close`, whose report is the way
+        // to learn about panicking destructors
+        self.free_all_objects();
+        release_vm_id(self.vm_id);
     }
 }
 
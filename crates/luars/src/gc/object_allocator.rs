@@ -7,8 +7,9 @@ use crate::lua_vm::{CFunction, CallInfo, LuaState};
 use crate::{
     LuaRawFunction, LuaRawTable, LuaResult, LuaValue,
     gc::{
-        GC, GcCClosure, GcFunction, GcObjectOwner, GcProto, GcRClosure, GcString, GcTable,
-        GcThread, GcUpvalue, GcUserdata, PagedPool, Pooled, ProtoPtr, StringPtr, UpvaluePtr,
+        GC, GcCClosure, GcFunction, GcObjectKind, GcObjectOwner, GcObjectPtr, GcProto, GcRClosure,
+        GcString, GcTable, GcThread, GcUpvalue, GcUserdata, PagedPool, Pooled, ProtoPtr, StringPtr,
+        UpvaluePtr,
     },
 };
 
@@ -24,6 +25,7 @@ pub struct ObjectAllocator {
     upvalue_pool: PagedPool<GcUpvalue>,
     userdata_pool: PagedPool<GcUserdata>,
     proto_pool: PagedPool<GcProto>,
+    thread_pool: PagedPool<GcThread>,
     call_info_pool: PagedPool<CallInfo>,
 }
 
@@ -45,6 +47,7 @@ impl ObjectAllocator {
             upvalue_pool: PagedPool::new(64),
             userdata_pool: PagedPool::new(16),
             proto_pool: PagedPool::new(16),
+            thread_pool: PagedPool::new(16),
             call_info_pool: PagedPool::new(64),
         }
     }
@@ -75,6 +78,18 @@ impl ObjectAllocator {
         self.strings.intern_bytes(bytes, gc, &mut self.string_pool)
     }
 
+    /// Create bytes `range` of string `s`, sharing its buffer when both are long.
+    #[inline]
+    pub fn create_substring(
+        &mut self,
+        gc: &mut GC,
+        s: StringPtr,
+        range: std::ops::Range<usize>,
+    ) -> CreateResult {
+        self.strings
+            .intern_substring(s, range, gc, &mut self.string_pool)
+    }
+
     /// Create a raw byte string from Vec<u8> without requiring UTF-8.
     /// This compatibility path now uses the same byte-string interning rules as `create_bytes`.
     #[inline]
@@ -132,6 +147,11 @@ impl ObjectAllocator {
 
     #[inline(always)]
     pub fn create_proto(&mut self, gc: &mut GC, chunk: LuaProto) -> LuaResult<ProtoPtr> {
+        #[cfg(feature = "profiler")]
+        let chunk = LuaProto {
+            profile_id: crate::lua_vm::next_profile_id(),
+            ..chunk
+        };
         let current_white = gc.current_white;
         let size = std::mem::size_of::<GcProto>() as u32 + chunk.proto_data_size;
         let gc_proto = GcObjectOwner::Proto(self.proto_pool.alloc(GcProto::new(
@@ -239,7 +259,7 @@ impl ObjectAllocator {
     #[inline]
     pub fn create_userdata(&mut self, gc: &mut GC, userdata: LuaUserdata) -> CreateResult {
         let current_white = gc.current_white;
-        let size = std::mem::size_of::<LuaUserdata>();
+        let size = std::mem::size_of::<LuaUserdata>() + userdata.payload_size();
         let gc_userdata = GcObjectOwner::Userdata(self.userdata_pool.alloc(GcUserdata::new(
             userdata,
             current_white,
@@ -255,9 +275,13 @@ impl ObjectAllocator {
     #[inline]
     pub fn create_thread(&mut self, gc: &mut GC, thread: LuaState) -> CreateResult {
         let current_white = gc.current_white;
-        let size = std::mem::size_of::<LuaState>();
-        let mut gc_thread =
-            GcObjectOwner::Thread(Box::new(GcThread::new(thread, current_white, size as u32)));
+        let size = std::mem::size_of::<LuaState>()
+            + thread.stack.capacity() * std::mem::size_of::<LuaValue>();
+        let mut gc_thread = GcObjectOwner::Thread(self.thread_pool.alloc(GcThread::new(
+            thread,
+            current_white,
+            size as u32,
+        )));
         let ptr = gc_thread.as_thread_ptr().unwrap();
         gc_thread.as_thread_mut().unwrap().set_thread_ptr(ptr);
 
@@ -273,12 +297,43 @@ impl ObjectAllocator {
     pub fn trim_after_full_gc(&mut self) {
         self.table_pool.release_empty_pages();
         self.string_pool.release_empty_pages();
+        self.function_pool.release_empty_pages();
         self.cclosure_pool.release_empty_pages();
         self.rclosure_pool.release_empty_pages();
         self.upvalue_pool.release_empty_pages();
         self.userdata_pool.release_empty_pages();
         self.proto_pool.release_empty_pages();
+        self.thread_pool.release_empty_pages();
         self.call_info_pool.release_empty_pages();
-        self.userdata_pool.release_empty_pages();
+    }
+
+    /// Pool generation of the live object at `ptr`, or `None` if no object
+    /// of this allocator lives there any more. Never dereferences `ptr`.
+    pub(crate) fn slot_generation(&self, ptr: GcObjectPtr) -> Option<u32> {
+        match ptr.kind() {
+            GcObjectKind::String => self
+                .string_pool
+                .slot_generation(ptr.as_string_ptr().as_ptr()),
+            GcObjectKind::Table => self.table_pool.slot_generation(ptr.as_table_ptr().as_ptr()),
+            GcObjectKind::Function => self
+                .function_pool
+                .slot_generation(ptr.as_function_ptr().as_ptr()),
+            GcObjectKind::CClosure => self
+                .cclosure_pool
+                .slot_generation(ptr.as_cclosure_ptr().as_ptr()),
+            GcObjectKind::RClosure => self
+                .rclosure_pool
+                .slot_generation(ptr.as_rclosure_ptr().as_ptr()),
+            GcObjectKind::Upvalue => self
+                .upvalue_pool
+                .slot_generation(ptr.as_upvalue_ptr().as_ptr()),
+            GcObjectKind::Thread => self
+                .thread_pool
+                .slot_generation(ptr.as_thread_ptr().as_ptr()),
+            GcObjectKind::Userdata => self
+                .userdata_pool
+                .slot_generation(ptr.as_userdata_ptr().as_ptr()),
+            GcObjectKind::Proto => self.proto_pool.slot_generation(ptr.as_proto_ptr().as_ptr()),
+        }
     }
 }
@@ -6,6 +6,9 @@ use std::rc::Rc;
 
 struct PageSlot<T> {
     occupied: bool,
+    /// Allocation number of the current occupant, so a handle to a freed
+    /// object is not mistaken for whatever reuses the slot.
+    generation: u32,
     value: MaybeUninit<T>,
 }
 
@@ -13,6 +16,7 @@ impl<T> PageSlot<T> {
     fn vacant() -> Self {
         Self {
             occupied: false,
+            generation: 0,
             value: MaybeUninit::uninit(),
         }
     }
@@ -33,6 +37,9 @@ struct PagedPoolInner<T> {
     free_slots: Vec<NonNull<PageSlot<T>>>,
     min_page_len: usize,
     next_page_len: usize,
+    /// Counts allocations over the pool's lifetime, pages released and
+    /// regrown included.
+    next_generation: u32,
 }
 
 impl<T> PagedPoolInner<T> {
@@ -43,6 +50,7 @@ impl<T> PagedPoolInner<T> {
             free_slots: Vec::new(),
             min_page_len: page_len,
             next_page_len: page_len,
+            next_generation: 1,
         }
     }
 
@@ -60,6 +68,21 @@ impl<T> PagedPoolInner<T> {
         self.next_page_len = page_len.saturating_mul(2);
     }
 
+    fn slot_generation(&self, ptr: *const T) -> Option<u32> {
+        let addr = ptr as usize;
+        let stride = std::mem::size_of::<PageSlot<T>>();
+        for page in &self.pages {
+            let start = page.as_ptr() as usize;
+            if addr < start || addr >= start + page.len() * stride {
+                continue;
+            }
+            let slot = &page[(addr - start) / stride];
+            let is_value = unsafe { slot.value_ptr() } == ptr;
+            return (slot.occupied && is_value).then_some(slot.generation);
+        }
+        None
+    }
+
     fn release_empty_pages(&mut self) -> usize {
         let mut released_pages = 0;
         let mut kept_pages = Vec::with_capacity(self.pages.len());
@@ -120,10 +143,13 @@ impl<T> PagedPool<T> {
                     .pop()
                     .expect("paged pool must provide a free slot after grow");
 
+                let generation = inner.next_generation;
+                inner.next_generation = generation.wrapping_add(1);
                 unsafe {
                     let slot_ref = &mut *slot.as_ptr();
                     debug_assert!(!slot_ref.occupied, "paged pool slot already occupied");
                     slot_ref.occupied = true;
+                    slot_ref.generation = generation;
                     slot_ref.value_mut_ptr().write(value);
                 }
 
@@ -142,6 +168,14 @@ impl<T> PagedPool<T> {
     pub fn release_empty_pages(&mut self) -> usize {
         self.inner.borrow_mut().release_empty_pages()
     }
+
+    /// Generation of the live object at `ptr`, or `None` if `ptr` is not
+    /// an occupied slot of this pool. Only compares addresses, so `ptr` may
+    /// point at freed memory. Linear in the number of pages, which double
+    /// in size as the pool grows.
+    pub fn slot_generation(&self, ptr: *const T) -> Option<u32> {
+        self.inner.borrow().slot_generation(ptr)
+    }
 }
 
 impl<T> Default for PagedPool<T> {
@@ -270,6 +304,22 @@ mod tests {
         assert_eq!(9, *reused);
     }
 
+    #[test]
+    fn reused_slots_get_a_new_generation() {
+        let mut pool = PagedPool::new(1);
+        let first = pool.alloc(7u32);
+        let ptr = first.as_ptr();
+        let generation = pool.slot_generation(ptr).unwrap();
+
+        drop(first);
+        assert_eq!(None, pool.slot_generation(ptr));
+
+        let reused = pool.alloc(9u32);
+        assert_eq!(ptr, reused.as_ptr());
+        assert_ne!(Some(generation), pool.slot_generation(ptr));
+        assert_eq!(None, pool.slot_generation(std::ptr::dangling()));
+    }
+
     #[test]
     fn empty_pages_are_released_without_moving_live_slots() {
         let mut pool = PagedPool::new(2);
@@ -4,7 +4,7 @@ use crate::LuaValue;
 #[cfg(feature = "shared-proto")]
 use crate::gc::Pooled;
 use crate::gc::{CreateResult, GC, GcObjectOwner, GcString, PagedPool, StringPtr};
-use crate::lua_value::{InlineShortString, LuaStrRepr, LuaString};
+use crate::lua_value::{InlineShortString, LuaStrRepr, LuaString, Utf8State};
 use crate::lua_vm::lua_limits::LUAI_MAXSHORTLEN;
 
 const STRING_HASH_SEED_1: u64 = 0x243f_6a88_85a3_08d3;
@@ -206,7 +206,7 @@ impl StringInterner {
 
         if slen > Self::SHORT_STRING_LIMIT {
             let size = Self::long_string_size(slen);
-            let lua_string = LuaString::from_bytes(LuaStrRepr::Heap(Box::<[u8]>::from(bytes)), 0);
+            let lua_string = LuaString::from_bytes(LuaStrRepr::long(bytes), 0);
             let gc_string = Self::alloc_string_owner(current_white, lua_string, size, string_pool);
             let ptr = gc_string.as_str_ptr().unwrap();
             gc.trace_object(gc_string)?;
@@ -249,7 +249,7 @@ impl StringInterner {
 
         if slen > Self::SHORT_STRING_LIMIT {
             let size = Self::long_string_size(slen);
-            let lua_string = LuaString::from_bytes(LuaStrRepr::Heap(bytes.into_boxed_slice()), 0);
+            let lua_string = LuaString::from_bytes(LuaStrRepr::long(&bytes), 0);
             let gc_string = Self::alloc_string_owner(current_white, lua_string, size, string_pool);
             let ptr = gc_string.as_str_ptr().unwrap();
             gc.trace_object(gc_string)?;
@@ -280,6 +280,44 @@ impl StringInterner {
         )
     }
 
+    /// Bytes `range` of string `s`. A long result shares the buffer of a
+    /// long `s` instead of copying it; anything else is created as by
+    /// `intern_bytes`.
+    pub fn intern_substring(
+        &mut self,
+        s: StringPtr,
+        range: std::ops::Range<usize>,
+        gc: &mut GC,
+        string_pool: &mut PagedPool<GcString>,
+    ) -> CreateResult {
+        let parent = &s.as_ref().data;
+        if range.len() > Self::SHORT_STRING_LIMIT
+            && let Some(repr) = parent.str.share(range.clone())
+        {
+            let size = Self::long_string_size(range.len());
+            // A slice of valid UTF-8 is valid iff it starts and ends on a
+            // character boundary; otherwise the slice has to be scanned
+            let lua_string = match parent.as_str() {
+                Some(text) => LuaString::new(
+                    repr,
+                    0,
+                    if text.is_char_boundary(range.start) && text.is_char_boundary(range.end) {
+                        Utf8State::Valid
+                    } else {
+                        Utf8State::Invalid
+                    },
+                ),
+                None => LuaString::from_bytes(repr, 0),
+            };
+            let gc_string =
+                Self::alloc_string_owner(gc.current_white, lua_string, size, string_pool);
+            let ptr = gc_string.as_str_ptr().unwrap();
+            gc.trace_object(gc_string)?;
+            return Ok(LuaValue::longstring(ptr));
+        }
+        self.intern_bytes(&parent.as_bytes()[range], gc, string_pool)
+    }
+
     #[inline]
     fn create_short_string(
         &mut self,
@@ -429,7 +467,11 @@ mod tests {
         ));
         assert!(matches!(
             heap_41.as_string_ptr().unwrap().as_ref().data.str,
-            LuaStrRepr::Heap(_)
+            LuaStrRepr::Shared {
+                start: 0,
+                len: 41,
+                ..
+            }
         ));
     }
 
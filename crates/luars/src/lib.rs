@@ -16,18 +16,43 @@ extern crate self as luars;
 #[cfg(test)]
 mod test;
 
+#[cfg(feature = "bench-util")]
+#[doc(hidden)]
+pub mod bench_util;
+
 mod compiler;
 mod gc;
 mod lib_registry;
 mod lua_api;
 mod lua_value;
 mod lua_vm;
+mod platform_path;
 mod platform_time;
 mod stdlib;
 
 #[cfg(feature = "serde")]
 pub mod serde;
 
+/// Lexer and error-tolerant parser for editor tooling.
+///
+/// [`syntax::Lexer`] yields tokens with byte spans and line/column
+/// positions; [`syntax::parse`] builds a syntax tree with spans, error
+/// nodes where the source does not parse, and every variable use
+/// resolved to its declaration with the compiler's own scoping rules.
+pub mod syntax {
+    pub use crate::compiler::syntax::*;
+}
+
+/// Building blocks for tools that generate bytecode directly instead of
+/// Lua source.
+///
+/// Not covered by semver: these APIs follow the VM's instruction set and
+/// may change in any release.
+pub mod unstable {
+    pub use crate::compiler::BytecodeBuilder;
+    pub use crate::lua_vm::opcode::OpMode;
+}
+
 // Re-export the derive macros so users can `use luars::LuaUserData;`
 pub use luars_derive::LuaUserData;
 pub use luars_derive::lua_methods;
@@ -37,30 +62,42 @@ pub use lua_value::LuaUserdata;
 pub use lua_value::UserDataBuilder;
 pub use lua_value::alive_ref::RefAliveToken;
 pub use lua_value::userdata_trait::{
-    LuaEnum, LuaMethodProvider, LuaRegistrable, LuaStaticMethodProvider, OpaqueUserData, UdValue,
-    UserDataTrait,
+    __lua_name_in, LuaEnum, LuaFieldNameProvider, LuaMethodProvider, LuaRegistrable,
+    LuaStaticMethodProvider, OpaqueUserData, UdValue, UserDataTrait,
 };
+pub use lua_value::{BorrowConflict, SharedRef, SharedRefMut, SharedUserData};
 
+pub use gc::{GcCollectionKind, GcEvent, GcStats};
 pub use lib_registry::{LibraryModule, LibraryRegistry, LuaLibrary, PreloadModule};
 pub use lua_api::*;
 pub use lua_value::RustCallback;
 pub use lua_value::lua_convert::{FromLua, FromLuaMulti, IntoLua};
 pub use lua_value::{
-    LuaProto, LuaRawFunction, LuaRawTable, LuaValue, LuaValueKind, chunk_serializer::*,
+    LuaProto, LuaRawFunction, LuaRawTable, LuaValue, LuaValueKind, MultiValue, chunk_serializer::*,
+    lua_float_to_string,
 };
-pub use lua_vm::SafeOption;
+#[cfg(feature = "profiler")]
+pub use lua_vm::FunctionProfile;
 #[cfg(feature = "sandbox")]
 pub use lua_vm::SandboxConfig;
 pub use lua_vm::async_thread::{
     AsyncCallHandle, AsyncFuture, AsyncReturnValue, AsyncThread, IntoAsyncLua,
 };
-pub use lua_vm::lua_error::{LuaError, LuaFullError};
+pub use lua_vm::lua_error::{CloseReport, LuaError, LuaFullError};
+pub use lua_vm::{
+    CFunction, CallInfo, DebugInfo, FrameInfo, FunctionInfo, GlobalState, Instruction, LuaAnyRef,
+    LuaFunctionRef, LuaResult, LuaState, LuaStringRef, LuaTableRef, LuaWeakValue, OpCode,
+    UserDataRef,
+};
+pub use lua_vm::{CompileOptions, SafeOption, SafeOptionBuilder, SafeOptionError};
+pub use lua_vm::{GetObserver, SetAction, SetObserver, TypeOptions};
 pub use lua_vm::{
-    CFunction, CallInfo, DebugInfo, GlobalState, Instruction, LuaAnyRef, LuaFunctionRef, LuaResult,
-    LuaState, LuaStringRef, LuaTableRef, OpCode, UserDataRef,
+    LOAD_EVENT_MAX_SOURCE, LoadCaller, LoadDecision, LoadEvent, LoadMode, LoadOrigin, LoadSource,
 };
 pub use lua_vm::{LUA_MASKCALL, LUA_MASKCOUNT, LUA_MASKLINE, LUA_MASKRET};
-pub use stdlib::Stdlib;
+pub use lua_vm::{TypeClaim, TypeEntry, TypeRegistry, TypeRegistryError};
+pub use stdlib::package::{LUA_CPATH_DEFAULT, LUA_DIRSEP, LUA_PACKAGE_CONFIG, LUA_PATH_DEFAULT};
+pub use stdlib::{OpenMode, Stdlib, StdlibSet};
 
 #[cfg(feature = "unsafe-send")]
 mod send_impls {
@@ -5,7 +5,7 @@ use crate::lua_api;
 use crate::lua_value::LuaValue;
 use crate::lua_vm::LuaState;
 use crate::lua_vm::{CFunction, GlobalState, LuaResult};
-use crate::stdlib::{self, Stdlib};
+use crate::stdlib::{self, OpenMode, Stdlib, StdlibSet};
 // use crate::stdlib;
 
 /// Unified installation interface for libraries provided by luars or external crates.
@@ -92,7 +92,7 @@ impl LibraryModule {
 impl LuaLibrary for LibraryModule {
     fn install(&self, lua: &mut lua_api::Lua) -> LuaResult<()> {
         let vm = lua.global_state_mut();
-        load_library_module(vm, self)
+        load_library_module(vm, self, OpenMode::FillMissing)
     }
 }
 
@@ -167,17 +167,23 @@ impl LibraryRegistry {
         self.modules.push(module);
     }
 
-    /// Load all registered libraries into a VM
+    /// Load all registered libraries into a VM, filling in modules that
+    /// already exist (see [`OpenMode::FillMissing`]).
     pub fn load_all(&self, vm: &mut GlobalState) -> LuaResult<()> {
+        self.load_all_with(vm, OpenMode::FillMissing)
+    }
+
+    /// Load all registered libraries into a VM with the given mode.
+    pub fn load_all_with(&self, vm: &mut GlobalState, mode: OpenMode) -> LuaResult<()> {
         for module in &self.modules {
-            self.load_module(vm, module)?;
+            load_library_module(vm, module, mode)?;
         }
         Ok(())
     }
 
     /// Load a specific module into the VM
     pub fn load_module(&self, vm: &mut GlobalState, module: &LibraryModule) -> LuaResult<()> {
-        load_library_module(vm, module)
+        load_library_module(vm, module, OpenMode::FillMissing)
     }
 
     /// Get a module by name
@@ -186,74 +192,147 @@ impl LibraryRegistry {
     }
 }
 
-fn load_library_module(vm: &mut GlobalState, module: &LibraryModule) -> LuaResult<()> {
-    // Create a table for the library
-    let lib_table = vm.create_table(0, 0)?;
+/// Set `name` in `table` to the entry's standard value. In
+/// [`OpenMode::FillMissing`] a non-nil value already there is left alone.
+fn install_entry(
+    vm: &mut GlobalState,
+    table: &LuaValue,
+    name: &str,
+    entry: &LibraryEntry,
+    mode: OpenMode,
+) -> LuaResult<()> {
+    let name_key = vm.create_string(name)?;
+    if mode == OpenMode::FillMissing && vm.raw_get(table, &name_key).is_some_and(|v| !v.is_nil()) {
+        return Ok(());
+    }
+    let value = match entry {
+        LibraryEntry::Function(func) => LuaValue::cfunction(*func),
+        LibraryEntry::Value(value_init) => value_init(vm)?,
+    };
+    vm.raw_set(table, name_key, value);
+    Ok(())
+}
 
-    // Register all entries in the table
-    for (name, entry) in &module.entries {
-        let value = match entry {
-            LibraryEntry::Function(func) => LuaValue::cfunction(*func),
-            LibraryEntry::Value(value_init) => value_init(vm)?,
-        };
-        let name_key = vm.create_string(name)?;
-        vm.raw_set(&lib_table, name_key, value);
+/// `package.loaded`, if the package library has been opened.
+fn loaded_table(vm: &mut GlobalState) -> LuaResult<Option<LuaValue>> {
+    if let Some(package_table) = vm.get_global("package")?
+        && package_table.is_table()
+    {
+        let loaded_key = vm.create_string("loaded")?;
+        if let Some(loaded_table) = vm.raw_get(&package_table, &loaded_key)
+            && loaded_table.is_table()
+        {
+            return Ok(Some(loaded_table));
+        }
     }
+    Ok(None)
+}
+
+/// Static storage whose *address* keys the stdlib table registry entry, so
+/// scripts cannot reach it by name through `debug.getregistry()`.
+static STDLIB_TABLES_KEY: u8 = 0;
+
+/// Registry table mapping each module name to the table this VM's stdlib
+/// installed for it, created on first use.
+fn stdlib_tables(vm: &mut GlobalState) -> LuaResult<LuaValue> {
+    let key = LuaValue::lightuserdata(&STDLIB_TABLES_KEY as *const u8 as *mut std::ffi::c_void);
+    let registry = vm.registry;
+    if let Some(tables) = vm.raw_get(&registry, &key)
+        && tables.is_table()
+    {
+        return Ok(tables);
+    }
+    let tables = vm.create_table(0, 0)?;
+    vm.raw_set(&registry, key, tables);
+    Ok(tables)
+}
+
+/// The table an earlier open of this VM's stdlib installed for the module.
+/// Tables scripts or the host put in `package.loaded` or `_G` under the
+/// same name are never adopted.
+fn existing_module_table(vm: &mut GlobalState, name: &str) -> LuaResult<Option<LuaValue>> {
+    let tables = stdlib_tables(vm)?;
+    let mod_key = vm.create_string(name)?;
+    Ok(vm.raw_get(&tables, &mod_key).filter(|v| v.is_table()))
+}
+
+/// Record `table` as the one the stdlib installed for the module.
+fn record_module_table(vm: &mut GlobalState, name: &str, table: LuaValue) -> LuaResult<()> {
+    let tables = stdlib_tables(vm)?;
+    let mod_key = vm.create_string(name)?;
+    vm.raw_set(&tables, mod_key, table);
+    Ok(())
+}
 
-    // Set the library table as a global
+fn load_library_module(
+    vm: &mut GlobalState,
+    module: &LibraryModule,
+    mode: OpenMode,
+) -> LuaResult<()> {
     if module.name == "_G" {
         // For global functions, register them directly
+        let globals = vm.global;
+        let created = existing_module_table(vm, "_G")?.is_none();
+        if created {
+            record_module_table(vm, "_G", globals)?;
+        }
         for (name, entry) in &module.entries {
-            let value = match entry {
-                LibraryEntry::Function(func) => LuaValue::cfunction(*func),
-                LibraryEntry::Value(value_init) => value_init(vm)?,
-            };
-            vm.set_global(name, value)?;
+            install_entry(vm, &globals, name, entry, mode)?;
         }
         // Also register _G (the globals table) in package.loaded["_G"]
         // This matches C Lua's behavior where luaL_requiref stores the base library
         // result in package.loaded["_G"], enabling pushglobalfuncname to find
         // global C functions like pcall, print, etc.
-        let globals = vm.global;
-        if let Some(package_table) = vm.get_global("package")?
-            && package_table.is_table()
-        {
-            let loaded_key = vm.create_string("loaded")?;
-            if let Some(loaded_table) = vm.raw_get(&package_table, &loaded_key)
-                && loaded_table.is_table()
-            {
-                let mod_key = vm.create_string("_G")?;
-                vm.raw_set(&loaded_table, mod_key, globals);
-            }
+        if let Some(loaded_table) = loaded_table(vm)? {
+            let mod_key = vm.create_string("_G")?;
+            vm.raw_set(&loaded_table, mod_key, globals);
         }
-    } else {
-        // For module libraries, set the table as global
-        vm.set_global(&module.name, lib_table)?;
-
-        // Special handling for string library: set string metatable
-        if module.name == "string" {
-            // In Lua, all strings share a metatable where __index points to the string library
-            // This allows using string methods with : syntax (e.g., str:upper())
-            vm.set_string_metatable(lib_table)?;
+        if let Some(init_fn) = module.initializer
+            && created
+        {
+            init_fn(vm.main_state())?;
         }
+        return Ok(());
+    }
 
-        // Also register in package.loaded and package.preload (if package exists)
-        // This allows require() to find standard libraries
-        if let Some(package_table) = vm.get_global("package")?
-            && package_table.is_table()
-        {
-            let loaded_key = vm.create_string("loaded")?;
-            if let Some(loaded_table) = vm.raw_get(&package_table, &loaded_key)
-                && loaded_table.is_table()
-            {
-                let mod_key = vm.create_string(&module.name)?;
-                vm.raw_set(&loaded_table, mod_key, lib_table);
-            }
+    // Reuse the table from an earlier open so references scripts took to it
+    // stay valid; only a fresh table needs the module initializer.
+    let (lib_table, created) = match existing_module_table(vm, &module.name)? {
+        Some(table) => (table, false),
+        None => {
+            let table = vm.create_table(0, 0)?;
+            record_module_table(vm, &module.name, table)?;
+            (table, true)
         }
+    };
+
+    // Register all entries in the table
+    for (name, entry) in &module.entries {
+        install_entry(vm, &lib_table, name, entry, mode)?;
+    }
+
+    // For module libraries, set the table as global
+    vm.set_global(&module.name, lib_table)?;
+
+    // Special handling for string library: set string metatable
+    if module.name == "string" && (vm.string_mt.is_none() || mode == OpenMode::Overwrite) {
+        // In Lua, all strings share a metatable where __index points to the string library
+        // This allows using string methods with : syntax (e.g., str:upper())
+        vm.set_string_metatable(lib_table)?;
     }
 
-    // Call the module initializer if it exists
-    if let Some(init_fn) = module.initializer {
+    // Also register in package.loaded and package.preload (if package exists)
+    // This allows require() to find standard libraries
+    if let Some(loaded_table) = loaded_table(vm)? {
+        let mod_key = vm.create_string(&module.name)?;
+        vm.raw_set(&loaded_table, mod_key, lib_table);
+    }
+
+    // Call the module initializer if it exists. Reopening keeps the state
+    // it set up, e.g. `package.loaded` or `io.stdout`.
+    if let Some(init_fn) = module.initializer
+        && created
+    {
         init_fn(vm.main_state())?;
     }
 
@@ -267,40 +346,49 @@ impl Default for LibraryRegistry {
 }
 
 /// Create a standard Lua 5.5 library registry with all standard libraries
-pub fn create_standard_registry(open_lib: Stdlib) -> LibraryRegistry {
+pub fn create_standard_registry(open_libs: impl Into<StdlibSet>) -> LibraryRegistry {
+    let open_libs = open_libs.into();
     let mut registry = LibraryRegistry::new();
 
-    if matches!(open_lib, Stdlib::All | Stdlib::Package) {
+    if open_libs.contains(Stdlib::Package) {
         registry.register(stdlib::package::create_package_lib());
     }
     // Register all other standard libraries
-    if matches!(open_lib, Stdlib::All | Stdlib::Basic) {
+    if open_libs.contains(Stdlib::Basic) {
         registry.register(stdlib::basic::create_basic_lib());
     }
-    if matches!(open_lib, Stdlib::All | Stdlib::String) {
+    if open_libs.contains(Stdlib::String) {
         registry.register(stdlib::string::create_string_lib());
     }
-    if matches!(open_lib, Stdlib::All | Stdlib::Table) {
+    if open_libs.contains(Stdlib::Table) {
         registry.register(stdlib::table::create_table_lib());
     }
-    if matches!(open_lib, Stdlib::All | Stdlib::Math) {
+    if open_libs.contains(Stdlib::Math) {
         registry.register(stdlib::math::create_math_lib());
     }
-    if matches!(open_lib, Stdlib::All | Stdlib::Io) {
+    if open_libs.contains(Stdlib::Io) {
         registry.register(stdlib::io::create_io_lib());
     }
-    if matches!(open_lib, Stdlib::All | Stdlib::Os) {
+    if open_libs.contains(Stdlib::Os) {
         registry.register(stdlib::os::create_os_lib());
     }
-    if matches!(open_lib, Stdlib::All | Stdlib::Utf8) {
+    if open_libs.contains(Stdlib::Utf8) {
         registry.register(stdlib::utf8::create_utf8_lib());
     }
-    if matches!(open_lib, Stdlib::All | Stdlib::Coroutine) {
+    if open_libs.contains(Stdlib::Coroutine) {
         registry.register(stdlib::coroutine::create_coroutine_lib());
     }
-    if matches!(open_lib, Stdlib::All | Stdlib::Debug) {
+    if open_libs.contains(Stdlib::Debug) {
         registry.register(stdlib::debug::create_debug_lib());
     }
+    #[cfg(feature = "json")]
+    if open_libs.contains(Stdlib::Json) {
+        registry.register(stdlib::json::create_json_lib());
+    }
+    #[cfg(feature = "profiler")]
+    if open_libs.contains(Stdlib::Profiler) {
+        registry.register(stdlib::profiler::create_profiler_lib());
+    }
     // #[cfg(feature = "loadlib")]
     // registry.register(stdlib::ffi::create_ffi_lib());
     // #[cfg(feature = "async")]
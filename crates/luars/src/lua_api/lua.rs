@@ -6,14 +6,17 @@ use luars::SandboxConfig;
 use luars::lua_vm::SafeOption;
 use luars::lua_vm::{LuaTypedAsyncCallback, LuaTypedCallback};
 use luars::{
-    FromLua, FromLuaMulti, GlobalState, IntoLua, LuaEnum, LuaLibrary, LuaRegistrable, LuaResult,
-    LuaUserdata, LuaValueKind, Stdlib, UserDataRef, UserDataTrait,
+    FromLua, FromLuaMulti, GlobalState, IntoLua, LuaEnum, LuaLibrary, LuaProto, LuaRegistrable,
+    LuaResult, LuaUserdata, LuaValueKind, OpenMode, Stdlib, StdlibSet, TypeOptions, TypeRegistry,
+    UserDataRef, UserDataTrait,
 };
 
 #[cfg(feature = "sandbox")]
 use crate::LuaSandboxApi;
 use crate::lua_api::{Chunk, LuaFunction, LuaString, LuaTable, Scope, Value};
-use crate::{LuaApi, LuaAsyncApi, LuaError, LuaFullError, RefAliveToken, StackValueApi};
+use crate::{
+    CloseReport, LuaApi, LuaAsyncApi, LuaError, LuaFullError, RefAliveToken, StackValueApi,
+};
 
 /// Safe, embedding-oriented Lua runtime.
 ///
@@ -31,6 +34,22 @@ impl Lua {
         }
     }
 
+    /// Create a runtime with every type of `types` already registered.
+    /// See [`TypeRegistry`].
+    pub fn new_with_types(option: SafeOption, types: &TypeRegistry) -> Result<Self, LuaFullError> {
+        let mut lua = Lua::new(option);
+        if let Err(e) = lua.register_types(types) {
+            return Err(lua.get_error_message(e));
+        }
+        Ok(lua)
+    }
+
+    /// Close the runtime, reporting `__gc` errors and userdata `Drop` panics.
+    /// See [`GlobalState::close`].
+    pub fn close(self) -> Result<(), CloseReport> {
+        self.global_state_owner.close()
+    }
+
     /// Install a library provided by luars or an external crate.
     #[inline]
     pub fn install_library<L: LuaLibrary>(&mut self, library: L) -> LuaResult<()> {
@@ -92,7 +111,7 @@ impl Lua {
         &mut self,
         func: luars::LuaValue,
     ) -> LuaResult<Vec<luars::LuaValue>> {
-        self.global_state_owner.main_state().call(func, vec![])
+        self.global_state_owner.main_state().call_args(func, &[])
     }
 
     pub(crate) async fn call_function_value_async(
@@ -161,6 +180,24 @@ impl Lua {
             .from_value(value, api_name)
     }
 
+    /// Wrap a prototype, e.g. one made with
+    /// [`BytecodeBuilder`](crate::unstable::BytecodeBuilder), into a callable
+    /// function whose `_ENV` is the globals table.
+    pub fn load_proto(&mut self, proto: LuaProto) -> LuaResult<LuaFunction> {
+        let value = self.global_state_owner.main_state().load_proto(proto)?;
+        self.value_to_function(value)
+    }
+
+    /// Load the contents of a Lua file read some other way, such as from
+    /// stdin. See [`LuaState::load_file_bytes`](crate::LuaState::load_file_bytes).
+    pub fn load_file_bytes(&mut self, bytes: &[u8], chunk_name: &str) -> LuaResult<LuaFunction> {
+        let value = self
+            .global_state_owner
+            .main_state()
+            .load_file_bytes(bytes, chunk_name)?;
+        self.value_to_function(value)
+    }
+
     /// Run a lexical scope that can create non-`'static` Lua callbacks and borrowed userdata.
     pub fn scope<'lua, R>(
         &'lua mut self,
@@ -182,6 +219,12 @@ impl Lua {
     pub fn global_state_mut(&mut self) -> &mut GlobalState {
         &mut self.global_state_owner
     }
+
+    /// Get the main thread, for embedding layers that drive the stack
+    /// directly through [`LuaStackApi`](crate::LuaStackApi).
+    pub fn main_state(&mut self) -> &mut luars::LuaState {
+        self.global_state_owner.main_state()
+    }
 }
 
 impl Default for Lua {
@@ -192,8 +235,13 @@ impl Default for Lua {
 
 impl LuaApi for Lua {
     #[inline]
-    fn open_stdlib(&mut self, lib: Stdlib) -> LuaResult<()> {
-        self.global_state_owner.open_stdlib(lib)
+    fn open_stdlib(&mut self, libs: impl Into<StdlibSet>) -> LuaResult<()> {
+        self.global_state_owner.open_stdlib(libs)
+    }
+
+    #[inline]
+    fn open_stdlib_with(&mut self, libs: impl Into<StdlibSet>, mode: OpenMode) -> LuaResult<()> {
+        self.global_state_owner.open_stdlib_with(libs, mode)
     }
 
     #[inline]
@@ -321,6 +369,21 @@ impl LuaApi for Lua {
         self.global_state_owner.register_type_of::<T>(name)
     }
 
+    #[inline]
+    fn register_type_of_with<T: LuaRegistrable>(
+        &mut self,
+        name: &str,
+        options: TypeOptions,
+    ) -> LuaResult<()> {
+        self.global_state_owner
+            .register_type_of_with::<T>(name, options)
+    }
+
+    #[inline]
+    fn register_types(&mut self, types: &TypeRegistry) -> LuaResult<()> {
+        self.global_state_owner.register_types(types)
+    }
+
     #[inline]
     fn create_type_register_table<T: LuaRegistrable>(&mut self, name: &str) -> LuaResult<LuaTable> {
         self.global_state_owner.register_type_of::<T>(name)?;
@@ -1,15 +1,16 @@
 use std::ffi::c_void;
 
 use crate::lua_api::{
-    Chunk, LUA_GLOBALSINDEX, LUA_MULTRET, LUA_REGISTRYINDEX, LuaApi, LuaFunction, LuaString,
-    LuaTable, Value, lua_upvalueindex,
+    Chunk, LUA_GLOBALSINDEX, LUA_MULTRET, LUA_REFNIL, LUA_REGISTRYINDEX, LuaApi, LuaFunction,
+    LuaString, LuaTable, RefId, Value, lua_upvalueindex,
 };
-use crate::lua_vm::{LuaTypedAsyncCallback, LuaTypedCallback};
+use crate::lua_vm::store_in_registry;
+use crate::lua_vm::{LuaTypedAsyncCallback, LuaTypedCallback, get_metatable};
 use crate::stdlib::basic::parse_number::parse_lua_number;
 use crate::{
     FromLua, FromLuaMulti, IntoLua, LuaEnum, LuaError, LuaFullError, LuaRegistrable, LuaResult,
-    LuaStackApi, LuaState, LuaUserdata, LuaValue, LuaValueKind, RefAliveToken, StackValueApi,
-    Stdlib, UserDataRef, UserDataTrait,
+    LuaStackApi, LuaState, LuaUserdata, LuaValue, LuaValueKind, OpenMode, RefAliveToken,
+    StackValueApi, Stdlib, StdlibSet, TypeOptions, TypeRegistry, UserDataRef, UserDataTrait,
 };
 
 fn stack_api_base(state: &LuaState) -> usize {
@@ -362,8 +363,12 @@ fn stack_api_upvaluecount(value: LuaValue) -> usize {
 }
 
 impl LuaApi for LuaState {
-    fn open_stdlib(&mut self, lib: Stdlib) -> LuaResult<()> {
-        self.global_state_mut().open_stdlib(lib)
+    fn open_stdlib(&mut self, libs: impl Into<StdlibSet>) -> LuaResult<()> {
+        self.global_state_mut().open_stdlib(libs)
+    }
+
+    fn open_stdlib_with(&mut self, libs: impl Into<StdlibSet>, mode: OpenMode) -> LuaResult<()> {
+        self.global_state_mut().open_stdlib_with(libs, mode)
     }
 
     fn open_stdlibs(&mut self, libs: &[Stdlib]) -> LuaResult<()> {
@@ -460,6 +465,19 @@ impl LuaApi for LuaState {
         self.global_state_mut().register_type_of::<T>(name)
     }
 
+    fn register_type_of_with<T: LuaRegistrable>(
+        &mut self,
+        name: &str,
+        options: TypeOptions,
+    ) -> LuaResult<()> {
+        self.global_state_mut()
+            .register_type_of_with::<T>(name, options)
+    }
+
+    fn register_types(&mut self, types: &TypeRegistry) -> LuaResult<()> {
+        self.global_state_mut().register_types(types)
+    }
+
     fn create_type_register_table<T: LuaRegistrable>(&mut self, name: &str) -> LuaResult<LuaTable> {
         self.register_type_of::<T>(name)?;
         <Self as LuaApi>::get_global::<LuaTable>(self, name)?.ok_or_else(|| {
@@ -984,6 +1002,66 @@ impl LuaStackApi for LuaState {
         }
     }
 
+    fn lua_l_ref(&mut self, idx: isize) -> LuaResult<RefId> {
+        let top = stack_api_top(self);
+        if stack_api_len(self) == 0 {
+            return Err(self.error("luaL_ref: missing value on stack".to_string()));
+        }
+        let value = self.stack_get(top - 1).unwrap_or_default();
+        if value.is_nil() {
+            self.set_top_raw(top - 1);
+            return Ok(LUA_REFNIL);
+        }
+        // The registry's integer keys belong to the VM's ref manager
+        if idx == LUA_REGISTRYINDEX {
+            let reference = store_in_registry(self.global_state_mut(), value);
+            self.set_top_raw(top - 1);
+            return Ok(reference);
+        }
+
+        // Any other table keeps its free list in t[0], as lauxlib does
+        let table = stack_api_expect_value(self, idx, "luaL_ref")?;
+        let Some(t) = table.as_table() else {
+            return Err(self.error(format!(
+                "luaL_ref: table expected, got {}",
+                table.type_name()
+            )));
+        };
+        let free = t.raw_geti(0).and_then(|v| v.as_integer()).unwrap_or(0);
+        let reference = if free > 0 {
+            let next = t.raw_geti(free).unwrap_or_default();
+            self.raw_seti(&table, 0, next);
+            free
+        } else {
+            t.len() as i64 + 1
+        };
+        self.raw_seti(&table, reference, value);
+        self.set_top_raw(top - 1);
+        Ok(reference as RefId)
+    }
+
+    fn lua_l_unref(&mut self, idx: isize, reference: RefId) -> LuaResult<()> {
+        if reference < 0 {
+            return Ok(());
+        }
+        if idx == LUA_REGISTRYINDEX {
+            self.global_state_mut().release_ref_id(reference);
+            return Ok(());
+        }
+
+        let table = stack_api_expect_value(self, idx, "luaL_unref")?;
+        let Some(t) = table.as_table() else {
+            return Err(self.error(format!(
+                "luaL_unref: table expected, got {}",
+                table.type_name()
+            )));
+        };
+        let free = t.raw_geti(0).unwrap_or(LuaValue::integer(0));
+        self.raw_seti(&table, reference as i64, free);
+        self.raw_seti(&table, 0, LuaValue::integer(reference as i64));
+        Ok(())
+    }
+
     fn lua_createtable(&mut self, narr: usize, nrec: usize) -> LuaResult<()> {
         let value = self.create_table(narr, nrec)?;
         self.push_value(value)
@@ -1388,6 +1466,9 @@ impl LuaStackApi for LuaState {
         let value = self
             .stack_get(top - 1)
             .ok_or_else(|| self.error("lua_rawset: missing value on stack".to_string()))?;
+        if let Some(msg) = key.table_key_error() {
+            return Err(self.error(msg.to_string()));
+        }
         self.raw_set(&table, key, value);
         self.set_top_raw(top - 2);
         Ok(())
@@ -1419,6 +1500,64 @@ impl LuaStackApi for LuaState {
         stack_api_assign_value(self, to_idx, value, "lua_copy")
     }
 
+    fn lua_getmetatable(&mut self, idx: isize) -> LuaResult<bool> {
+        let Some(value) = stack_api_value(self, idx) else {
+            return Ok(false);
+        };
+        match get_metatable(self, &value) {
+            Some(metatable) if metatable.is_table() => {
+                self.push_value(metatable)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn lua_setmetatable(&mut self, idx: isize) -> LuaResult<()> {
+        let top = stack_api_top(self);
+        if stack_api_len(self) == 0 {
+            return Err(self.error("lua_setmetatable: missing metatable on stack".to_string()));
+        }
+
+        let target = stack_api_expect_value(self, idx, "lua_setmetatable")?;
+        let metatable = self.stack_get(top - 1).unwrap_or_default();
+        if !metatable.is_nil() && !metatable.is_table() {
+            return Err(self.error(format!(
+                "lua_setmetatable: table expected, got {}",
+                metatable.type_name()
+            )));
+        }
+        let new_mt = (!metatable.is_nil()).then_some(metatable);
+
+        if let Some(table) = target.as_table_mut() {
+            table.set_metatable(new_mt);
+        } else if let Some(ud) = target.as_userdata_mut() {
+            ud.set_metatable(metatable);
+        } else {
+            self.global_state_mut()
+                .set_basic_metatable(target.kind(), new_mt);
+        }
+        if (target.is_table() || target.is_userdata())
+            && let Some(gc_ptr) = target.as_gc_ptr()
+        {
+            self.gc_barrier_back(gc_ptr);
+        }
+        self.global_state_mut().gc.check_finalizer(&target);
+        self.set_top_raw(top - 1);
+        Ok(())
+    }
+
+    fn lua_concat(&mut self, n: usize) -> LuaResult<()> {
+        if n > stack_api_len(self) {
+            return Err(self.error(format!("lua_concat: not enough values on stack for {}", n)));
+        }
+        if n == 0 {
+            let empty = self.global_state().const_strings.str_empty;
+            return self.push_value(empty);
+        }
+        self.concat(n)
+    }
+
     fn lua_replace(&mut self, idx: isize) -> LuaResult<()> {
         let top = self.get_top();
         if top == 0 {
@@ -5,6 +5,10 @@ mod function;
 mod lua;
 mod lua_state;
 mod lua_string;
+mod module;
+#[cfg(not(target_arch = "wasm32"))]
+mod pool;
+mod reload;
 mod scope;
 mod table;
 mod test;
@@ -14,6 +18,10 @@ pub use chunk::Chunk;
 pub use function::LuaFunction;
 pub use lua::Lua;
 pub use lua_string::LuaString;
+pub use module::ModuleBuilder;
+#[cfg(not(target_arch = "wasm32"))]
+pub use pool::{VmPool, VmPoolBuilder};
+pub use reload::{ReloadOptions, ReloadReport, Tombstone};
 pub use scope::{Scope, ScopedFunction};
 pub use table::LuaTable;
 pub use value::Value;
@@ -22,7 +30,8 @@ pub use value::Value;
 use crate::SandboxConfig;
 use crate::{
     FromLua, FromLuaMulti, IntoLua, LuaEnum, LuaError, LuaFullError, LuaRegistrable, LuaResult,
-    LuaValue, LuaValueKind, RefAliveToken, Stdlib, UserDataRef, UserDataTrait,
+    LuaValue, LuaValueKind, OpenMode, RefAliveToken, Stdlib, StdlibSet, TypeOptions, TypeRegistry,
+    UserDataRef, UserDataTrait,
     lua_vm::{LuaTypedAsyncCallback, LuaTypedCallback},
 };
 
@@ -31,7 +40,8 @@ use crate::{
 /// This trait intentionally covers the typed, ergonomic surface. Low-level raw
 /// runtime escape hatches such as `global_state` stay on the concrete type.
 pub trait LuaApi {
-    fn open_stdlib(&mut self, lib: Stdlib) -> LuaResult<()>;
+    fn open_stdlib(&mut self, libs: impl Into<StdlibSet>) -> LuaResult<()>;
+    fn open_stdlib_with(&mut self, libs: impl Into<StdlibSet>, mode: OpenMode) -> LuaResult<()>;
     fn open_stdlibs(&mut self, libs: &[Stdlib]) -> LuaResult<()>;
     fn collect_garbage(&mut self) -> LuaResult<()>;
     fn execute(&mut self, source: &str) -> LuaResult<()>;
@@ -53,7 +63,13 @@ pub trait LuaApi {
     where
         F: LuaTypedAsyncCallback<Args, R>;
     fn register_type_of<T: LuaRegistrable>(&mut self, name: &str) -> LuaResult<()>;
+    fn register_type_of_with<T: LuaRegistrable>(
+        &mut self,
+        name: &str,
+        options: TypeOptions,
+    ) -> LuaResult<()>;
     fn register_enum_of<T: LuaEnum>(&mut self, name: &str) -> LuaResult<()>;
+    fn register_types(&mut self, types: &TypeRegistry) -> LuaResult<()>;
     fn create_type_register_table<T: LuaRegistrable>(&mut self, name: &str) -> LuaResult<LuaTable>;
     fn load<'lua>(&'lua mut self, source: &str) -> Chunk<'lua, Self>
     where
@@ -111,6 +127,8 @@ pub(crate) trait StackValueApi {
     fn from_value<T: FromLua>(&mut self, value: LuaValue, api_name: &str) -> LuaResult<T>;
 }
 
+pub use crate::lua_vm::{LUA_NOREF, LUA_REFNIL, RefId};
+
 pub const LUA_REGISTRYINDEX: isize = -1001000;
 pub const LUA_GLOBALSINDEX: isize = LUA_REGISTRYINDEX - 2;
 pub const LUA_MULTRET: isize = -1;
@@ -185,6 +203,11 @@ pub trait LuaStackApi {
     fn lua_l_optnumber(&mut self, idx: isize, default: f64) -> LuaResult<f64>;
     fn lua_l_optstring(&mut self, idx: isize, default: &str) -> LuaResult<String>;
     fn lua_l_optlstring(&mut self, idx: isize, default: &[u8]) -> LuaResult<Vec<u8>>;
+    /// Pop the value on top into table `idx` under a fresh integer key and
+    /// return the key (luaL_ref); nil gives `LUA_REFNIL` and stores nothing.
+    fn lua_l_ref(&mut self, idx: isize) -> LuaResult<RefId>;
+    /// Free a key handed out by [`lua_l_ref`](Self::lua_l_ref) (luaL_unref).
+    fn lua_l_unref(&mut self, idx: isize, reference: RefId) -> LuaResult<()>;
     fn lua_createtable(&mut self, narr: usize, nrec: usize) -> LuaResult<()>;
     fn lua_newtable(&mut self) -> LuaResult<()>;
     fn lua_upvaluecount(&self, idx: isize) -> usize;
@@ -222,6 +245,14 @@ pub trait LuaStackApi {
     fn lua_rawgeti(&mut self, idx: isize, index: i64) -> LuaResult<()>;
     fn lua_rawseti(&mut self, idx: isize, index: i64) -> LuaResult<()>;
     fn lua_copy(&mut self, from_idx: isize, to_idx: isize) -> LuaResult<()>;
+    /// Push the metatable of the value at `idx`, if it has one.
+    fn lua_getmetatable(&mut self, idx: isize) -> LuaResult<bool>;
+    /// Pop a table (or nil) and make it the metatable of the value at `idx`,
+    /// ignoring `__metatable` like the C API does.
+    fn lua_setmetatable(&mut self, idx: isize) -> LuaResult<()>;
+    /// Replace the `n` values on top with their concatenation; `n == 0`
+    /// pushes the empty string.
+    fn lua_concat(&mut self, n: usize) -> LuaResult<()>;
     fn lua_replace(&mut self, idx: isize) -> LuaResult<()>;
     fn lua_upvalueid(&mut self, func_idx: isize, n: usize) -> Option<*mut c_void>;
     fn lua_upvaluejoin(
@@ -0,0 +1,118 @@
+use luars::lua_vm::{CFunction, LuaTypedCallback};
+use luars::{IntoLua, LuaResult, LuaValue};
+
+use crate::LuaApi;
+use crate::lua_api::{Lua, LuaTable};
+
+type FieldInit = Box<dyn FnOnce(&mut Lua, &LuaTable) -> LuaResult<()>>;
+
+/// Builder for a namespaced table of Rust functions and values.
+///
+/// The public counterpart of the internal `lib_module!` tables: entries are
+/// collected first and materialized by [`ModuleBuilder::register`], which
+/// installs the table as a global and in `package.loaded`, so both
+/// `engine.log(...)` and `require "engine"` resolve to it.
+///
+/// ```ignore
+/// ModuleBuilder::new("engine")
+///     .function("log", |msg: String| println!("{msg}"))
+///     .value("VERSION", "1.2")
+///     .submodule("gfx", |gfx| gfx.function("clear", || {}))
+///     .register(&mut lua)?;
+/// ```
+///
+/// The module is an ordinary global, so a sandbox does not see it unless it
+/// is captured explicitly (e.g. with `sandbox_capture_global`).
+pub struct ModuleBuilder {
+    name: String,
+    fields: Vec<FieldInit>,
+}
+
+impl ModuleBuilder {
+    /// Start a module that will be registered under `name`.
+    pub fn new(name: impl Into<String>) -> Self {
+        ModuleBuilder {
+            name: name.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// The name this module is registered under.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Add a typed Rust callback, converted like `create_function`.
+    pub fn function<F, Args, R>(self, name: impl Into<String>, f: F) -> Self
+    where
+        F: LuaTypedCallback<Args, R>,
+    {
+        let name = name.into();
+        self.field(move |lua, table| {
+            let func = lua.create_function(f)?;
+            table.set(name.as_str(), func)
+        })
+    }
+
+    /// Add a raw C function that works on the stack directly.
+    pub fn cfunction(self, name: impl Into<String>, f: CFunction) -> Self {
+        let name = name.into();
+        self.field(move |_, table| table.set(name.as_str(), LuaValue::cfunction(f)))
+    }
+
+    /// Add a constant or any other value convertible to Lua.
+    pub fn value<T: IntoLua + 'static>(self, name: impl Into<String>, value: T) -> Self {
+        let name = name.into();
+        self.field(move |_, table| table.set(name.as_str(), value))
+    }
+
+    /// Add a nested table built by `build`. On [`register`](Self::register)
+    /// it is also stored in `package.loaded` under the dotted name
+    /// (`"engine.gfx"`), so it can be required on its own.
+    pub fn submodule(
+        self,
+        name: impl Into<String>,
+        build: impl FnOnce(ModuleBuilder) -> ModuleBuilder,
+    ) -> Self {
+        let name = name.into();
+        let sub = build(ModuleBuilder::new(format!("{}.{}", self.name, name)));
+        self.field(move |lua, table| {
+            let sub_table = sub.build_into_loaded(lua)?;
+            table.set(name.as_str(), sub_table)
+        })
+    }
+
+    /// Create the module table without installing it anywhere.
+    pub fn build(self, lua: &mut Lua) -> LuaResult<LuaTable> {
+        let table = lua.create_table_with_capacity(0, self.fields.len())?;
+        for init in self.fields {
+            init(lua, &table)?;
+        }
+        Ok(table)
+    }
+
+    /// Build the module, set it as a global and record it in
+    /// `package.loaded` (when the package library is open).
+    pub fn register(self, lua: &mut Lua) -> LuaResult<LuaTable> {
+        let name = self.name.clone();
+        let table = self.build_into_loaded(lua)?;
+        lua.set_global(&name, &table)?;
+        Ok(table)
+    }
+
+    fn build_into_loaded(self, lua: &mut Lua) -> LuaResult<LuaTable> {
+        let name = self.name.clone();
+        let table = self.build(lua)?;
+        if let Some(package) = lua.get_global::<Option<LuaTable>>("package")?.flatten()
+            && let Some(loaded) = package.get::<Option<LuaTable>>("loaded")?
+        {
+            loaded.set(name.as_str(), &table)?;
+        }
+        Ok(table)
+    }
+
+    fn field(mut self, init: impl FnOnce(&mut Lua, &LuaTable) -> LuaResult<()> + 'static) -> Self {
+        self.fields.push(Box::new(init));
+        self
+    }
+}
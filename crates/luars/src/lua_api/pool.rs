@@ -0,0 +1,494 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use luars::lua_vm::SafeOption;
+use luars::{LuaError, LuaFullError, LuaResult};
+
+use crate::LuaApi;
+use crate::lua_api::Lua;
+
+type InitFn = dyn Fn(&mut Lua) -> LuaResult<()> + Send + Sync;
+type Reply = Box<dyn FnOnce() + Send>;
+/// The work itself: returns whether the callback panicked, and the reply
+/// to deliver once the worker is usable again.
+type JobFn = Box<dyn FnOnce(&mut Lua) -> (bool, Reply) + Send>;
+
+/// A unit of work shipped to a worker.
+struct Job {
+    run: JobFn,
+    /// When the job's Lua code must stop, if the pool has a timeout.
+    deadline: Option<Instant>,
+}
+
+/// A fixed set of worker threads, each owning its own [`Lua`].
+///
+/// A `Lua` never leaves the thread that created it; work is shipped to it as
+/// a closure and the result comes back through a future, so the pool can be
+/// shared (`Arc<VmPool>`) across async tasks on any executor.
+///
+/// ```ignore
+/// let pool = VmPool::builder()
+///     .workers(4)
+///     .init(|lua| {
+///         lua.open_stdlib(Stdlib::All)?;
+///         lua.execute("function handle(x) return x * 2 end")
+///     })
+///     .build()?;
+///
+/// let n: i64 = pool.run(|lua| lua.call_global1("handle", 21)).await?;
+/// pool.broadcast(|lua| lua.execute("function handle(x) return x * 3 end")).await?;
+/// ```
+///
+/// A callback that panics takes down only the VM it ran on: the worker drops
+/// that VM, builds a fresh one with the init callback and keeps serving.
+/// [`restarts`](VmPool::restarts) counts these recreations. A recreated VM
+/// only has what `init` sets up, not earlier broadcasts.
+///
+/// With a [`timeout`](VmPoolBuilder::timeout), Lua code that runs past its
+/// job's deadline is aborted, so a runaway script costs one failed job
+/// instead of a worker.
+pub struct VmPool {
+    senders: Vec<mpsc::Sender<Job>>,
+    handles: Vec<JoinHandle<()>>,
+    next: AtomicUsize,
+    restarts: Arc<AtomicUsize>,
+    timeout: Option<Duration>,
+}
+
+/// Configuration for a [`VmPool`], created by [`VmPool::builder`].
+pub struct VmPoolBuilder {
+    workers: usize,
+    option: SafeOption,
+    name: String,
+    init: Option<Arc<InitFn>>,
+    timeout: Option<(Duration, String)>,
+}
+
+impl VmPoolBuilder {
+    /// Number of worker threads (default: available parallelism).
+    pub fn workers(mut self, n: usize) -> Self {
+        self.workers = n;
+        self
+    }
+
+    /// Limits for every worker VM.
+    pub fn safe_option(mut self, option: SafeOption) -> Self {
+        self.option = option;
+        self
+    }
+
+    /// Prefix for worker thread names (default: `"luars-worker"`).
+    pub fn thread_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Set up each fresh VM: open libraries, register functions, load
+    /// scripts. Runs on the worker thread, once at start and again whenever
+    /// the worker recreates its VM after a panic.
+    pub fn init(
+        mut self,
+        init: impl Fn(&mut Lua) -> LuaResult<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.init = Some(Arc::new(init));
+        self
+    }
+
+    /// Abort jobs whose Lua code is still running `limit` after they were
+    /// handed to the pool: the VM's interrupt hook raises `message` and the
+    /// job fails with it. Afterwards the worker unwinds the aborted call
+    /// and keeps its VM if that leaves it idle, recreating it otherwise.
+    /// Time spent in Rust, e.g. awaiting an async function, is only cut
+    /// short when Lua code runs again.
+    pub fn timeout(mut self, limit: Duration, message: impl Into<String>) -> Self {
+        self.timeout = Some((limit, message.into()));
+        self
+    }
+
+    /// Spawn the workers and wait until every VM is initialized. Fails when
+    /// no worker was requested, or with the first init error after shutting
+    /// the other workers down.
+    pub fn build(self) -> Result<VmPool, LuaFullError> {
+        if self.workers == 0 {
+            return Err(pool_error("VmPool requires at least 1 worker".to_string()));
+        }
+        let restarts = Arc::new(AtomicUsize::new(0));
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let mut senders = Vec::with_capacity(self.workers);
+        let mut handles = Vec::with_capacity(self.workers);
+
+        for id in 0..self.workers {
+            let (tx, rx) = mpsc::channel::<Job>();
+            let worker = Worker {
+                option: self.option.clone(),
+                init: self.init.clone(),
+                restarts: restarts.clone(),
+                timeout_message: self.timeout.as_ref().map(|(_, message)| message.clone()),
+            };
+            let ready = ready_tx.clone();
+            let handle = std::thread::Builder::new()
+                .name(format!("{}-{}", self.name, id))
+                .spawn(move || worker.run(rx, ready))
+                .map_err(|e| pool_error(format!("cannot spawn worker thread: {e}")))?;
+            senders.push(tx);
+            handles.push(handle);
+        }
+        drop(ready_tx);
+
+        let pool = VmPool {
+            senders,
+            handles,
+            next: AtomicUsize::new(0),
+            restarts,
+            timeout: self.timeout.map(|(limit, _)| limit),
+        };
+        for _ in 0..pool.handles.len() {
+            match ready_rx.recv() {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    pool.shutdown();
+                    return Err(e);
+                }
+                Err(_) => {
+                    pool.shutdown();
+                    return Err(pool_error("worker exited during init".to_string()));
+                }
+            }
+        }
+        Ok(pool)
+    }
+}
+
+impl VmPool {
+    pub fn builder() -> VmPoolBuilder {
+        VmPoolBuilder {
+            workers: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(2),
+            option: SafeOption::default(),
+            name: "luars-worker".to_string(),
+            init: None,
+            timeout: None,
+        }
+    }
+
+    /// Run `f` on the next worker (round robin) and await its result. Lua
+    /// errors come back with their message; a panic in `f` is reported as
+    /// an error and the worker's VM is recreated. The timeout, if any,
+    /// counts from this call.
+    pub fn run<R, F>(&self, f: F) -> impl Future<Output = Result<R, LuaFullError>> + use<R, F>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Lua) -> LuaResult<R> + Send + 'static,
+    {
+        let (tx, rx) = oneshot();
+        let job = Job {
+            run: Box::new(move |lua| {
+                let (panicked, result) = call_guarded(lua, f);
+                (panicked, Box::new(move || tx.send(result)))
+            }),
+            deadline: self.deadline(),
+        };
+        let sent = self.dispatch(job);
+        async move {
+            sent?;
+            rx.await?
+        }
+    }
+
+    /// Run `f` on every worker and wait for all of them, e.g. to hot-reload
+    /// a script. Returns the first error; the other workers still ran `f`.
+    pub fn broadcast<F>(&self, f: F) -> impl Future<Output = Result<(), LuaFullError>> + use<F>
+    where
+        F: Fn(&mut Lua) -> LuaResult<()> + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let mut pending = Vec::with_capacity(self.senders.len());
+        for sender in &self.senders {
+            let f = f.clone();
+            let (tx, rx) = oneshot();
+            let job = Job {
+                run: Box::new(move |lua| {
+                    let (panicked, result) = call_guarded(lua, |lua| f(lua));
+                    (panicked, Box::new(move || tx.send(result)))
+                }),
+                deadline: self.deadline(),
+            };
+            // A worker that is gone can't be updated; report it like a
+            // failed job instead of skipping it silently.
+            let sent = sender
+                .send(job)
+                .map_err(|_| pool_error("worker is not running".to_string()));
+            pending.push((sent, rx));
+        }
+        async move {
+            let mut first_error = None;
+            for (sent, rx) in pending {
+                let result = match sent {
+                    Ok(()) => rx.await.and_then(|result| result),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    first_error.get_or_insert(e);
+                }
+            }
+            first_error.map_or(Ok(()), Err)
+        }
+    }
+
+    /// Number of worker threads.
+    pub fn worker_count(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// How many times a worker replaced its VM after a panic or after a
+    /// timeout left it mid-call.
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    /// Stop accepting work and wait for the workers to finish what they
+    /// have queued. Dropping the pool stops the workers without waiting.
+    pub fn shutdown(self) {
+        let VmPool {
+            senders, handles, ..
+        } = self;
+        drop(senders);
+        for handle in handles {
+            let _ = handle.join();
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|limit| Instant::now() + limit)
+    }
+
+    /// Hand `job` to the next live worker.
+    fn dispatch(&self, mut job: Job) -> Result<(), LuaFullError> {
+        let n = self.senders.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        for i in 0..n {
+            match self.senders[(start + i) % n].send(job) {
+                Ok(()) => return Ok(()),
+                Err(mpsc::SendError(returned)) => job = returned,
+            }
+        }
+        Err(pool_error("no worker is running".to_string()))
+    }
+}
+
+struct Worker {
+    option: SafeOption,
+    init: Option<Arc<InitFn>>,
+    restarts: Arc<AtomicUsize>,
+    /// Set when the pool has a timeout: what a job past its deadline raises.
+    timeout_message: Option<String>,
+}
+
+/// Deadline of the job a worker is running, shared with its VM's interrupt
+/// hook.
+#[derive(Default)]
+struct Watchdog {
+    deadline: Cell<Option<Instant>>,
+    fired: Cell<bool>,
+}
+
+impl Worker {
+    fn run(self, jobs: mpsc::Receiver<Job>, ready: mpsc::Sender<Result<(), LuaFullError>>) {
+        let watchdog = Rc::new(Watchdog::default());
+        let mut lua = match self.create(&watchdog) {
+            Ok(lua) => {
+                let _ = ready.send(Ok(()));
+                lua
+            }
+            Err(e) => {
+                let _ = ready.send(Err(e));
+                return;
+            }
+        };
+        drop(ready);
+
+        while let Ok(job) = jobs.recv() {
+            watchdog.deadline.set(job.deadline);
+            watchdog.fired.set(false);
+            let (mut panicked, reply) = (job.run)(&mut lua);
+
+            // A job cut off by the watchdog leaves the frames it was running
+            // for the error report. Unwind them like a pcall would, with the
+            // deadline still past so `__close` handlers cannot run on, and
+            // keep the VM if that leaves it idle.
+            let mut stuck = false;
+            if watchdog.fired.get() && !panicked {
+                match catch_unwind(AssertUnwindSafe(|| recover(&mut lua))) {
+                    Ok(idle) => stuck = !idle,
+                    Err(_) => panicked = true,
+                }
+            }
+            watchdog.deadline.set(None);
+            if panicked || stuck {
+                // The VM may have been left mid-operation; don't reuse it. If
+                // dropping it panics as well, whatever is left is leaked.
+                let _ = catch_unwind(AssertUnwindSafe(move || drop(lua)));
+                self.restarts.fetch_add(1, Ordering::Relaxed);
+                match self.create(&watchdog) {
+                    Ok(fresh) => lua = fresh,
+                    Err(_) => {
+                        // Without a working VM this worker is useless; exit
+                        // so dispatch moves on to the others.
+                        reply();
+                        return;
+                    }
+                }
+            }
+            reply();
+        }
+    }
+
+    fn create(&self, watchdog: &Rc<Watchdog>) -> Result<Lua, LuaFullError> {
+        let option = self.option.clone();
+        let init = self.init.clone();
+        let timeout_message = self.timeout_message.clone();
+        let watchdog = watchdog.clone();
+        let result = catch_unwind(AssertUnwindSafe(move || {
+            let mut lua = Lua::new(option);
+            if let Some(message) = timeout_message {
+                lua.global_state_mut()
+                    .set_interrupt_hook(move || match watchdog.deadline.get() {
+                        Some(deadline) if Instant::now() >= deadline => {
+                            watchdog.fired.set(true);
+                            Err(message.clone())
+                        }
+                        _ => Ok(()),
+                    });
+            }
+            if let Some(init) = init
+                && let Err(e) = init(&mut lua)
+            {
+                return Err(lua.get_error_message(e));
+            }
+            Ok(lua)
+        }));
+        result.unwrap_or_else(|payload| {
+            Err(pool_error(format!(
+                "worker init panicked: {}",
+                panic_message(&*payload)
+            )))
+        })
+    }
+}
+
+/// Unwind what a failed job left on the main thread; returns whether no
+/// call is in progress any more and the stack is empty.
+fn recover(lua: &mut Lua) -> bool {
+    let state = lua.main_state();
+    if state.call_depth() > 0 {
+        state.unwind_after_error();
+    }
+    state.call_depth() == 0 && state.get_top() == 0
+}
+
+/// Call `f`, turning Lua errors into full errors while the VM is still at
+/// hand and catching panics. The flag is true when `f` panicked.
+fn call_guarded<R>(
+    lua: &mut Lua,
+    f: impl FnOnce(&mut Lua) -> LuaResult<R>,
+) -> (bool, Result<R, LuaFullError>) {
+    match catch_unwind(AssertUnwindSafe(|| f(lua))) {
+        Ok(Ok(value)) => (false, Ok(value)),
+        Ok(Err(e)) => (false, Err(lua.get_error_message(e))),
+        Err(payload) => (
+            true,
+            Err(pool_error(format!(
+                "worker panicked: {}",
+                panic_message(&*payload)
+            ))),
+        ),
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown panic"
+    }
+}
+
+fn pool_error(message: String) -> LuaFullError {
+    LuaFullError {
+        kind: LuaError::RuntimeError,
+        message,
+    }
+}
+
+// ===== Single-use channel for results =====
+//
+// Executor-agnostic: the receiver is a plain `Future` woken by the sender.
+
+struct Slot<T> {
+    value: Option<T>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+struct Sender<T>(Arc<Mutex<Slot<T>>>);
+
+struct Receiver<T>(Arc<Mutex<Slot<T>>>);
+
+fn oneshot<T>() -> (Sender<T>, Receiver<T>) {
+    let slot = Arc::new(Mutex::new(Slot {
+        value: None,
+        closed: false,
+        waker: None,
+    }));
+    (Sender(slot.clone()), Receiver(slot))
+}
+
+impl<T> Sender<T> {
+    fn send(self, value: T) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).value = Some(value);
+        // Waking happens in Drop.
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut slot = self.0.lock().unwrap_or_else(|e| e.into_inner());
+            slot.closed = true;
+            slot.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, LuaFullError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(value) = slot.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+        if slot.closed {
+            return Poll::Ready(Err(pool_error(
+                "worker stopped before replying".to_string(),
+            )));
+        }
+        slot.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
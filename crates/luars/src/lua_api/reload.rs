@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use luars::{LuaResult, LuaValue, LuaValueKind};
+
+use crate::LuaApi;
+use crate::lua_api::{Lua, LuaFunction, LuaTable, Value};
+
+/// What [`Lua::reload_module_with`] leaves behind for keys that the old
+/// module table has but the reloaded one does not.
+#[derive(Clone, Debug, Default)]
+pub enum Tombstone {
+    /// Leave the old value in place.
+    #[default]
+    Keep,
+    /// Remove the key from the table.
+    Remove,
+    /// Store this value instead, e.g. a function that raises a descriptive error.
+    Value(Value),
+}
+
+/// Options for [`Lua::reload_module_with`].
+#[derive(Clone, Debug)]
+pub struct ReloadOptions {
+    /// Treatment of keys that disappeared from the module.
+    pub removed: Tombstone,
+    /// Re-point upvalues of the new functions at the old functions' upvalues
+    /// with the same name, so module-level locals (counters, caches, the
+    /// module table itself) keep their state across the reload.
+    pub join_upvalues: bool,
+}
+
+impl Default for ReloadOptions {
+    fn default() -> Self {
+        ReloadOptions {
+            removed: Tombstone::Keep,
+            join_upvalues: true,
+        }
+    }
+}
+
+/// What a reload changed in the old module table. Keys are rendered with
+/// `tostring` semantics.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    /// Keys whose function value was replaced by the new one.
+    pub replaced: Vec<String>,
+    /// Keys that only exist in the new module.
+    pub added: Vec<String>,
+    /// Keys that only exist in the old module (handled per [`Tombstone`]).
+    pub removed: Vec<String>,
+    /// Non-function values present in both; the old value is kept.
+    pub kept: Vec<String>,
+    /// `(key, upvalue)` pairs re-pointed at the old function's upvalue.
+    pub joined_upvalues: Vec<(String, String)>,
+    /// `(key, upvalue)` pairs left alone because they hold a function, whose
+    /// new body should win.
+    pub skipped_upvalues: Vec<(String, String)>,
+}
+
+impl Lua {
+    /// Reload a module with the default [`ReloadOptions`].
+    pub fn reload_module(&mut self, name: &str) -> LuaResult<ReloadReport> {
+        self.reload_module_with(name, ReloadOptions::default())
+    }
+
+    /// Run the loader of an already required module again and patch the
+    /// table in `package.loaded[name]` in place, so code holding that table
+    /// sees the new functions.
+    ///
+    /// For every key of the new module table: a function replaces the old
+    /// value, a key the old table lacks is added, and any other value leaves
+    /// the old one alone (it is usually state). Keys missing from the new
+    /// table are handled per [`ReloadOptions::removed`].
+    ///
+    /// Only lookups through the table change. A function value copied out of
+    /// the module before the reload (`local f = mod.f`) still runs the old
+    /// body.
+    pub fn reload_module_with(
+        &mut self,
+        name: &str,
+        options: ReloadOptions,
+    ) -> LuaResult<ReloadReport> {
+        let Some(loaded) = self.loaded_table()? else {
+            return Err(self
+                .global_state_mut()
+                .error("reload_module requires the package library".to_string()));
+        };
+        let Some(old) = loaded.raw_get::<Option<LuaTable>>(name)? else {
+            return Err(self
+                .global_state_mut()
+                .error(format!("module '{name}' is not loaded as a table")));
+        };
+
+        // Let require run the searchers and loader again, then put the old
+        // table back whatever happened.
+        loaded.raw_set(name, LuaValue::nil())?;
+        let result = self.call_global1::<_, Value>("require", name);
+        loaded.raw_set(name, &old)?;
+        let Some(new) = result?.as_table() else {
+            return Err(self
+                .global_state_mut()
+                .error(format!("module '{name}' did not return a table on reload")));
+        };
+
+        let old_pairs = old.pairs::<Value, Value>()?;
+        let new_pairs = new.pairs::<Value, Value>()?;
+
+        // Upvalues of the old functions by name, for new functions that have
+        // no old counterpart under the same key.
+        let mut old_upvalues: HashMap<String, (LuaFunction, usize)> = HashMap::new();
+        for (_, value) in &old_pairs {
+            if let Some(func) = value.as_function() {
+                for n in 1..=func.upvalue_count() {
+                    if let Some((up_name, _)) = func.get_upvalue::<Value>(n)?
+                        && !up_name.is_empty()
+                    {
+                        old_upvalues
+                            .entry(up_name)
+                            .or_insert_with(|| (func.clone(), n));
+                    }
+                }
+            }
+        }
+
+        let mut report = ReloadReport::default();
+        for (key, value) in &new_pairs {
+            let key_name = key.to_string_lossy();
+            let old_value = old.raw_get::<Value>(key)?;
+
+            if let Some(func) = value.as_function() {
+                if options.join_upvalues {
+                    let same_key = old_value.as_function();
+                    join_upvalues(
+                        &func,
+                        same_key.as_ref(),
+                        &old_upvalues,
+                        &key_name,
+                        &mut report,
+                    )?;
+                }
+                old.raw_set(key, value)?;
+                if old_value.is_nil() {
+                    report.added.push(key_name);
+                } else {
+                    report.replaced.push(key_name);
+                }
+            } else if old_value.is_nil() {
+                old.raw_set(key, value)?;
+                report.added.push(key_name);
+            } else {
+                report.kept.push(key_name);
+            }
+        }
+
+        for (key, _) in &old_pairs {
+            if !new.raw_get::<Value>(key)?.is_nil() {
+                continue;
+            }
+            match &options.removed {
+                Tombstone::Keep => {}
+                Tombstone::Remove => old.raw_set(key, LuaValue::nil())?,
+                Tombstone::Value(tombstone) => old.raw_set(key, tombstone)?,
+            }
+            report.removed.push(key.to_string_lossy());
+        }
+
+        Ok(report)
+    }
+
+    fn loaded_table(&mut self) -> LuaResult<Option<LuaTable>> {
+        let Some(package) = self.get_global::<Option<LuaTable>>("package")?.flatten() else {
+            return Ok(None);
+        };
+        package.get::<Option<LuaTable>>("loaded")
+    }
+}
+
+/// Share each named upvalue of `new` with the matching upvalue of the old
+/// function under the same key, or failing that of any old module function.
+fn join_upvalues(
+    new: &LuaFunction,
+    same_key: Option<&LuaFunction>,
+    old_upvalues: &HashMap<String, (LuaFunction, usize)>,
+    key_name: &str,
+    report: &mut ReloadReport,
+) -> LuaResult<()> {
+    for n in 1..=new.upvalue_count() {
+        let Some((up_name, new_value)) = new.get_upvalue::<Value>(n)? else {
+            continue;
+        };
+        if up_name.is_empty() {
+            continue;
+        }
+        let same_key_source = match same_key {
+            Some(old) => find_upvalue(old, &up_name)?.map(|m| (old.clone(), m)),
+            None => None,
+        };
+        let Some((old, m)) = same_key_source.or_else(|| old_upvalues.get(&up_name).cloned()) else {
+            continue;
+        };
+        let Some((_, old_value)) = old.get_upvalue::<Value>(m)? else {
+            continue;
+        };
+        if new_value.kind() == LuaValueKind::Function || old_value.kind() == LuaValueKind::Function
+        {
+            report
+                .skipped_upvalues
+                .push((key_name.to_string(), up_name));
+            continue;
+        }
+        if new.join_upvalue(n, &old, m)? {
+            report.joined_upvalues.push((key_name.to_string(), up_name));
+        }
+    }
+    Ok(())
+}
+
+fn find_upvalue(func: &LuaFunction, name: &str) -> LuaResult<Option<usize>> {
+    for n in 1..=func.upvalue_count() {
+        if let Some((up_name, _)) = func.get_upvalue::<Value>(n)?
+            && up_name == name
+        {
+            return Ok(Some(n));
+        }
+    }
+    Ok(None)
+}
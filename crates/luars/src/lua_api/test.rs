@@ -1,18 +1,19 @@
 #[cfg(test)]
 mod tests {
-    use std::cell::Cell;
+    use std::cell::{Cell, RefCell};
     use std::ffi::c_void;
     use std::io::Write;
     use std::path::PathBuf;
+    use std::rc::Rc;
 
     #[cfg(feature = "serde")]
     use crate::lua_api::Value;
     use crate::{
         GlobalState, LuaApi, LuaAsyncApi, LuaStackApi, LuaUserData, LuaValue, LuaValueKind,
-        RefAliveToken, SafeOption, Stdlib,
+        MultiValue, RefAliveToken, SafeOption, Stdlib,
         lua_api::{
-            LUA_GLOBALSINDEX, LUA_MULTRET, LUA_REGISTRYINDEX, Lua, LuaFunction, LuaTable,
-            lua_upvalueindex,
+            LUA_GLOBALSINDEX, LUA_MULTRET, LUA_REFNIL, LUA_REGISTRYINDEX, Lua, LuaFunction,
+            LuaTable, ModuleBuilder, ReloadOptions, Tombstone, VmPool, lua_upvalueindex,
         },
         lua_methods,
     };
@@ -95,6 +96,92 @@ mod tests {
         assert_eq!(result, 42);
     }
 
+    #[test]
+    fn multi_value_keeps_nils_through_calls_and_tables() {
+        let mut lua = Lua::new(SafeOption::default());
+        lua.open_stdlib(Stdlib::All).unwrap();
+        lua.load("function identity(...) return ... end")
+            .exec()
+            .unwrap();
+
+        let args = MultiValue::pack(lua.main_state(), (1, None::<i64>, "x", true)).unwrap();
+        assert_eq!(args.len(), 4);
+        assert!(args[1].is_nil());
+
+        let results: MultiValue = lua.call_global("identity", args.clone()).unwrap();
+        assert_eq!(results, args);
+        let (a, b, c, d): (i64, Option<i64>, String, bool) =
+            results.clone().unpack(lua.main_state()).unwrap();
+        assert_eq!((a, b, c.as_str(), d), (1, None, "x", true));
+
+        let trailing: MultiValue = lua.eval_multi("return identity(1, nil, nil)").unwrap();
+        assert_eq!(trailing.len(), 3);
+
+        let state = lua.main_state();
+        let identity = state.get_global("identity").unwrap().unwrap();
+        let direct = state.call_function(identity, args.clone()).unwrap();
+        assert_eq!(direct, args);
+
+        // table.pack / table.unpack from Rust
+        let packed = results.into_table(state).unwrap();
+        let unpacked = MultiValue::from_table(state, &packed, 1, 4).unwrap();
+        assert_eq!(unpacked, args);
+        state.set_global_value("packed", packed).unwrap();
+        let ok: bool = lua
+            .eval("return packed.n == 4 and packed[2] == nil and select('#', table.unpack(packed, 1, packed.n)) == 4")
+            .unwrap();
+        assert!(ok);
+    }
+
+    #[test]
+    fn global_newindex_hook_records_definitions() {
+        let mut lua = Lua::new(SafeOption::default());
+        lua.open_stdlib(Stdlib::All).unwrap();
+
+        let defined = Rc::new(RefCell::new(Vec::<String>::new()));
+        let record = {
+            let defined = defined.clone();
+            lua.create_function(move |globals: LuaTable, name: String, value: LuaValue| {
+                defined.borrow_mut().push(name.clone());
+                globals.raw_set(name, value)
+            })
+            .unwrap()
+        };
+        let meta = lua.create_table().unwrap();
+        meta.set("__newindex", record).unwrap();
+        lua.globals().set_metatable(Some(&meta)).unwrap();
+
+        lua.load("alpha = 1\nfunction beta() return 2 end\ngamma = alpha + beta()")
+            .exec()
+            .unwrap();
+        assert_eq!(*defined.borrow(), ["alpha", "beta", "gamma"]);
+        let gamma: i64 = lua.eval("return gamma").unwrap();
+        assert_eq!(gamma, 3);
+
+        // Raw helpers bypass the hook; the *_with_meta ones go through it.
+        let state = lua.main_state();
+        state
+            .set_global_value("raw_one", LuaValue::integer(4))
+            .unwrap();
+        state
+            .set_global_with_meta("hooked", LuaValue::integer(5))
+            .unwrap();
+        assert_eq!(*defined.borrow(), ["alpha", "beta", "gamma", "hooked"]);
+        let hooked = state.get_global_with_meta("hooked").unwrap().unwrap();
+        assert_eq!(hooked.as_integer(), Some(5));
+
+        // __index is only seen by the metamethod-aware reader.
+        let fallback = lua
+            .eval::<LuaTable>("return { __index = function(_, k) return 'default ' .. k end }")
+            .unwrap();
+        meta.set("__index", fallback.get::<LuaFunction>("__index").unwrap())
+            .unwrap();
+        let state = lua.main_state();
+        assert!(state.get_global_value("missing").unwrap().is_none());
+        let missing = state.get_global_with_meta("missing").unwrap().unwrap();
+        assert_eq!(missing.as_str(), Some("default missing"));
+    }
+
     #[test]
     fn high_level_collect_garbage_works() {
         let mut lua = Lua::new(SafeOption::default());
@@ -334,6 +421,25 @@ mod tests {
         assert_eq!(answer, 42);
     }
 
+    #[test]
+    fn table_raw_set_normalizes_and_rejects_invalid_keys() {
+        let mut lua = Lua::new(SafeOption::default());
+        let table = lua.create_table().unwrap();
+
+        table.raw_set(2.0, "two").unwrap();
+        assert_eq!(table.raw_get::<String>(2_i64).unwrap(), "two");
+
+        let err = table.raw_set(f64::NAN, 1_i64).unwrap_err();
+        let message = lua.get_error_message(err).message;
+        assert!(message.contains("table index is NaN"), "{message}");
+        let err = table.raw_set(LuaValue::nil(), 1_i64).unwrap_err();
+        let message = lua.get_error_message(err).message;
+        assert!(message.contains("table index is nil"), "{message}");
+        let err = table.set(f64::NAN, 1_i64).unwrap_err();
+        let message = lua.get_error_message(err).message;
+        assert!(message.contains("table index is NaN"), "{message}");
+    }
+
     #[test]
     fn safe_value_handle_supports_string_and_downcasts() {
         let mut lua = Lua::new(SafeOption::default());
@@ -566,6 +672,63 @@ mod tests {
         assert_eq!(add.call1::<_, i64>((20, 22)).unwrap(), 42);
     }
 
+    #[test]
+    fn compiled_function_called_repeatedly_with_typed_results() {
+        let mut lua = Lua::new(SafeOption::default());
+        lua.open_stdlib(Stdlib::All).unwrap();
+
+        let update = lua
+            .load(
+                r#"
+                local x = 0.0
+                return function(dt)
+                    x = x + dt
+                    return x, x >= 1.0
+                end
+                "#,
+            )
+            .set_name("update.lua")
+            .eval::<LuaFunction>()
+            .unwrap();
+        lua.collect_garbage().unwrap();
+
+        let mut done = false;
+        for _ in 0..120 {
+            let (_, finished): (f64, bool) = update.call(1.0 / 60.0).unwrap();
+            done = finished;
+        }
+        assert!(done);
+
+        let bad = lua.load("return 1, 'not a bool'").into_function().unwrap();
+        let err = bad.call::<_, (bool, f64)>(()).unwrap_err();
+        let message = lua.get_error_message(err).message;
+        assert!(message.contains("result #2"), "{message}");
+
+        let state = lua.main_state();
+        let args = MultiValue::pack(state, (true, "not a number")).unwrap();
+        let err = args.unpack_args::<(bool, f64)>(state).unwrap_err();
+        let message = state.get_error_msg(err);
+        assert!(message.contains("argument #2"), "{message}");
+        assert!(!message.contains("result"), "{message}");
+
+        // Conversions written against the original trait only define
+        // from_lua_multi, and serve both results and arguments
+        struct Sum(i64);
+        impl crate::FromLuaMulti for Sum {
+            fn from_lua_multi(
+                values: Vec<LuaValue>,
+                _state: &mut crate::LuaState,
+            ) -> Result<Self, String> {
+                Ok(Sum(values.iter().filter_map(|v| v.as_integer()).sum()))
+            }
+        }
+        let sum = lua.load("return 1, 2, 3").into_function().unwrap();
+        assert_eq!(sum.call::<_, Sum>(()).unwrap().0, 6);
+        let state = lua.main_state();
+        let args = MultiValue::pack(state, (4, 5)).unwrap();
+        assert_eq!(args.unpack_args::<Sum>(state).unwrap().0, 9);
+    }
+
     #[tokio::test]
     async fn high_level_async_api_exec_and_call_work() {
         let mut lua = Lua::new(SafeOption::default());
@@ -628,6 +791,322 @@ mod tests {
         assert!(lua.get_global::<i64>("sandbox_value").unwrap().is_none());
     }
 
+    #[test]
+    fn module_builder_registers_nested_module() {
+        fn arg_count(state: &mut crate::LuaState) -> crate::LuaResult<usize> {
+            let n = state.arg_count() as i64;
+            state.push_value(LuaValue::integer(n))?;
+            Ok(1)
+        }
+
+        let mut lua = Lua::new(SafeOption::default());
+        lua.open_stdlib(Stdlib::All).unwrap();
+
+        let log = std::rc::Rc::new(std::cell::RefCell::new(Vec::<String>::new()));
+        let sink = log.clone();
+        ModuleBuilder::new("engine")
+            .function("log", move |msg: String| sink.borrow_mut().push(msg))
+            .function("spawn", |kind: String, x: f64| format!("{kind}@{x}"))
+            .cfunction("argc", arg_count)
+            .value("VERSION", "1.2")
+            .value("MAX_ENTITIES", 4096_i64)
+            .submodule("gfx", |gfx| {
+                gfx.function("scale", |w: i64, h: i64| (w * 2, h * 2))
+                    .value("BACKEND", "null")
+            })
+            .register(&mut lua)
+            .unwrap();
+
+        let result: String = lua
+            .load(
+                r#"
+                engine.log("started " .. engine.VERSION)
+                assert(engine.MAX_ENTITIES == 4096)
+                assert(engine.argc(1, nil, 3) == 3)
+                local w, h = engine.gfx.scale(320, 200)
+                assert(w == 640 and h == 400 and engine.gfx.BACKEND == "null")
+
+                assert(require("engine") == engine)
+                assert(require("engine.gfx") == engine.gfx)
+                assert(package.loaded["engine.gfx"] == engine.gfx)
+                return engine.spawn("orc", 1.5)
+                "#,
+            )
+            .eval()
+            .unwrap();
+
+        assert_eq!(result, "orc@1.5");
+        assert_eq!(log.borrow().as_slice(), ["started 1.2"]);
+    }
+
+    #[cfg(feature = "sandbox")]
+    #[test]
+    fn module_builder_module_stays_out_of_sandbox_until_captured() {
+        let mut lua = Lua::new(SafeOption::default());
+        lua.open_stdlib(Stdlib::All).unwrap();
+        ModuleBuilder::new("engine")
+            .function("answer", || 42_i64)
+            .register(&mut lua)
+            .unwrap();
+
+        let hidden: bool = lua
+            .load_sandboxed("return engine == nil", &SandboxConfig::default())
+            .eval()
+            .unwrap();
+        assert!(hidden);
+
+        let mut config = SandboxConfig::default();
+        lua.sandbox_capture_global(&mut config, "engine").unwrap();
+        let value: i64 = lua
+            .load_sandboxed("return engine.answer()", &config)
+            .eval()
+            .unwrap();
+        assert_eq!(value, 42);
+    }
+
+    #[tokio::test]
+    async fn call_async_error_keeps_message() {
+        let mut lua = Lua::new(SafeOption::default());
+        lua.open_stdlib(Stdlib::All).unwrap();
+        let f: LuaFunction = lua
+            .load("return function() error('boom') end")
+            .eval()
+            .unwrap();
+        let err = lua.call_async::<_, ()>(&f, ()).await.unwrap_err();
+        let full = lua.get_error_message(err);
+        assert!(
+            full.message.starts_with("chunk:1: boom"),
+            "{}",
+            full.message
+        );
+    }
+
+    #[tokio::test]
+    async fn vm_pool_runs_jobs_and_broadcasts() {
+        let pool = VmPool::builder()
+            .workers(3)
+            .init(|lua| {
+                lua.open_stdlib(Stdlib::All)?;
+                lua.execute("factor = 2; function handle(x) return x * factor end")
+            })
+            .build()
+            .unwrap();
+        assert_eq!(pool.worker_count(), 3);
+
+        let value: i64 = pool
+            .run(|lua| lua.call_global1("handle", 21_i64))
+            .await
+            .unwrap();
+        assert_eq!(value, 42);
+
+        let err = pool
+            .run(|lua| lua.execute("error('boom')"))
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("boom"), "{err}");
+
+        pool.broadcast(|lua| lua.execute("factor = 3"))
+            .await
+            .unwrap();
+        for _ in 0..6 {
+            let value: i64 = pool
+                .run(|lua| lua.call_global1("handle", 2_i64))
+                .await
+                .unwrap();
+            assert_eq!(value, 6);
+        }
+        pool.shutdown();
+    }
+
+    #[tokio::test]
+    async fn vm_pool_recreates_worker_after_panic() {
+        let pool = VmPool::builder()
+            .workers(1)
+            .init(|lua| lua.execute("state = 'fresh'"))
+            .build()
+            .unwrap();
+
+        pool.run(|lua| lua.execute("state = 'dirty'"))
+            .await
+            .unwrap();
+        let err = pool
+            .run(|_| -> crate::LuaResult<()> { panic!("handler bug") })
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("handler bug"), "{err}");
+        assert_eq!(pool.restarts(), 1);
+
+        let state: String = pool
+            .run(|lua| Ok(lua.get_global::<String>("state")?.unwrap_or_default()))
+            .await
+            .unwrap();
+        assert_eq!(state, "fresh");
+    }
+
+    #[tokio::test]
+    async fn vm_pool_timeout_aborts_runaway_job() {
+        let pool = VmPool::builder()
+            .workers(1)
+            .timeout(std::time::Duration::from_millis(200), "job timeout")
+            .init(|lua| {
+                lua.open_stdlib(Stdlib::All)?;
+                lua.execute("state = 'fresh'")
+            })
+            .build()
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        let err = pool
+            .run(|lua| {
+                lua.execute(
+                    "state = 'dirty'; while true do pcall(function() while true do end end) end",
+                )
+            })
+            .await
+            .unwrap_err();
+        assert!(err.message.contains("job timeout"), "{err}");
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        // The aborted call unwinds cleanly, so the worker keeps its VM
+        assert_eq!(pool.restarts(), 0);
+        let state: String = pool
+            .run(|lua| Ok(lua.get_global::<String>("state")?.unwrap_or_default()))
+            .await
+            .unwrap();
+        assert_eq!(state, "dirty");
+
+        // Jobs that finish in time are unaffected
+        let sum: i64 = pool
+            .run(|lua| {
+                lua.load("local n = 0 for i = 1, 1000 do n = n + i end return n")
+                    .eval()
+            })
+            .await
+            .unwrap();
+        assert_eq!(sum, 500500);
+        assert_eq!(pool.restarts(), 0);
+        pool.shutdown();
+    }
+
+    #[test]
+    fn vm_pool_build_reports_init_error() {
+        let err = VmPool::builder()
+            .workers(2)
+            .init(|lua| {
+                lua.open_stdlib(Stdlib::Basic)?;
+                lua.execute("error('bad init')")
+            })
+            .build()
+            .err()
+            .unwrap();
+        assert!(err.message.contains("bad init"), "{err}");
+    }
+
+    #[test]
+    fn vm_pool_build_rejects_zero_workers() {
+        let err = VmPool::builder().workers(0).build().err().unwrap();
+        assert!(err.message.contains("at least 1 worker"), "{err}");
+    }
+
+    #[test]
+    fn reload_module_patches_table_in_place() {
+        let dir = test_temp_dir().join("reload");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hot_mod.lua");
+        std::fs::write(
+            &path,
+            r#"
+            local M = {}
+            local calls = 0
+            function M.greet() calls = calls + 1; return "v1" end
+            function M.count() return calls end
+            M.version = 1
+            M.old_only = function() end
+            return M
+            "#,
+        )
+        .unwrap();
+
+        let mut lua = Lua::new(SafeOption::default());
+        lua.open_stdlib(Stdlib::All).unwrap();
+        lua.set_global("MOD_DIR", dir.to_string_lossy().as_ref())
+            .unwrap();
+        lua.execute(
+            r#"
+            package.path = MOD_DIR .. "/?.lua"
+            local mod = require "hot_mod"
+            holder = { m = mod, f = mod.greet }
+            assert(mod.greet() == "v1" and mod.greet() == "v1")
+            "#,
+        )
+        .unwrap();
+
+        std::fs::write(
+            &path,
+            r#"
+            local M = {}
+            local calls = 0
+            function M.greet() calls = calls + 1; return "v2" end
+            function M.count() return calls end
+            function M.extra() return "new" end
+            M.version = 2
+            return M
+            "#,
+        )
+        .unwrap();
+        let report = lua.reload_module("hot_mod").unwrap();
+        assert_eq!(report.replaced.len(), 2, "{report:?}");
+        assert!(report.replaced.contains(&"greet".to_string()));
+        assert_eq!(report.added, ["extra"]);
+        assert_eq!(report.removed, ["old_only"]);
+        assert_eq!(report.kept, ["version"]);
+        assert!(
+            report
+                .joined_upvalues
+                .contains(&("count".to_string(), "calls".to_string())),
+            "{report:?}"
+        );
+
+        lua.execute(
+            r#"
+            local mod = holder.m
+            assert(require "hot_mod" == mod)
+            -- Lookups through the table see the new body...
+            assert(mod.greet() == "v2")
+            -- ...a reference taken before the reload keeps the old one.
+            assert(holder.f() == "v1")
+            -- Both bodies share the module-level 'calls' local.
+            assert(mod.count() == 4, mod.count())
+            assert(mod.version == 1)
+            assert(mod.extra() == "new")
+            assert(type(mod.old_only) == "function")
+            "#,
+        )
+        .unwrap();
+
+        std::fs::write(&path, "return { greet = function() return 'v3' end }").unwrap();
+        let report = lua
+            .reload_module_with(
+                "hot_mod",
+                ReloadOptions {
+                    removed: Tombstone::Remove,
+                    ..ReloadOptions::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(report.replaced, ["greet"]);
+        lua.execute(
+            r#"
+            local mod = holder.m
+            assert(mod.greet() == "v3")
+            assert(mod.extra == nil and mod.old_only == nil and mod.count == nil)
+            "#,
+        )
+        .unwrap();
+
+        assert!(lua.reload_module("no_such_module").is_err());
+    }
+
     #[test]
     fn table_and_function_convert_from_lua() {
         let mut lua = Lua::new(SafeOption::default());
@@ -1266,6 +1745,71 @@ mod tests {
         assert_eq!(results[0].as_integer(), Some(789));
     }
 
+    #[test]
+    fn stack_api_supports_refs_metatables_and_concat() {
+        let mut vm = GlobalState::new(SafeOption::default());
+
+        let state = vm.main_state();
+        state.lua_settop(0).unwrap();
+
+        state.lua_pushstring("kept").unwrap();
+        let reference = state.lua_l_ref(LUA_REGISTRYINDEX).unwrap();
+        assert!(reference > 0);
+        assert_eq!(state.lua_gettop(), 0);
+        state.lua_registry_geti(reference as i64).unwrap();
+        assert_eq!(state.lua_l_checkstring(-1).unwrap(), "kept");
+        state.lua_settop(0).unwrap();
+        state.lua_l_unref(LUA_REGISTRYINDEX, reference).unwrap();
+
+        state.lua_pushnil().unwrap();
+        assert_eq!(state.lua_l_ref(LUA_REGISTRYINDEX).unwrap(), LUA_REFNIL);
+        assert_eq!(state.lua_gettop(), 0);
+
+        // Plain tables reuse freed slots through the list kept in t[0]
+        state.lua_newtable().unwrap();
+        state.lua_pushinteger(10).unwrap();
+        let first = state.lua_l_ref(1).unwrap();
+        state.lua_pushinteger(20).unwrap();
+        let second = state.lua_l_ref(1).unwrap();
+        assert_eq!((first, second), (1, 2));
+        state.lua_l_unref(1, first).unwrap();
+        state.lua_pushinteger(30).unwrap();
+        assert_eq!(state.lua_l_ref(1).unwrap(), first);
+        state.lua_rawgeti(1, first as i64).unwrap();
+        assert_eq!(state.lua_tointegerx(-1), Some(30));
+        state.lua_settop(0).unwrap();
+
+        state.lua_newtable().unwrap();
+        assert!(!state.lua_getmetatable(1).unwrap());
+        state.lua_newtable().unwrap();
+        state.lua_pushstring("meta").unwrap();
+        state.lua_setfield(2, "__name").unwrap();
+        state.lua_setmetatable(1).unwrap();
+        assert_eq!(state.lua_gettop(), 1);
+        assert!(state.lua_getmetatable(1).unwrap());
+        state.lua_getfield(-1, "__name").unwrap();
+        assert_eq!(state.lua_l_checkstring(-1).unwrap(), "meta");
+        state.lua_settop(1).unwrap();
+        state.lua_pushnil().unwrap();
+        state.lua_setmetatable(1).unwrap();
+        assert!(!state.lua_getmetatable(1).unwrap());
+        state.lua_pushinteger(1).unwrap();
+        assert!(state.lua_setmetatable(1).is_err());
+        state.lua_settop(0).unwrap();
+
+        state.lua_pushstring("x = ").unwrap();
+        state.lua_pushinteger(42).unwrap();
+        state.lua_pushnumber(0.5).unwrap();
+        state.lua_concat(3).unwrap();
+        assert_eq!(state.lua_gettop(), 1);
+        assert_eq!(state.lua_l_checkstring(-1).unwrap(), "x = 420.5");
+        state.lua_concat(0).unwrap();
+        assert_eq!(state.lua_l_checkstring(-1).unwrap(), "");
+        state.lua_newtable().unwrap();
+        assert!(state.lua_concat(2).is_err());
+        state.lua_settop(0).unwrap();
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn table_serde_json_round_trip_works() {
@@ -1,3 +1,4 @@
+use crate::lua_value::lua_float_to_string;
 use std::ffi::c_void;
 
 #[cfg(feature = "serde")]
@@ -98,25 +99,9 @@ impl Value {
 
     #[inline]
     pub fn to_pointer(&self) -> Option<*const c_void> {
-        let value = self.to_value();
-        if let Some(pointer) = value.as_lightuserdata() {
-            return Some(pointer.cast_const());
-        }
-
-        match value.kind() {
-            LuaValueKind::String
-            | LuaValueKind::Table
-            | LuaValueKind::Function
-            | LuaValueKind::CFunction
-            | LuaValueKind::CClosure
-            | LuaValueKind::RClosure
-            | LuaValueKind::Userdata
-            | LuaValueKind::Thread => Some(value.raw_ptr_repr().cast()),
-            LuaValueKind::Nil
-            | LuaValueKind::Boolean
-            | LuaValueKind::Integer
-            | LuaValueKind::Float => None,
-        }
+        self.to_value()
+            .identity()
+            .map(|address| address as *const c_void)
     }
 
     /// Convert the wrapped value to a best-effort owned string.
@@ -125,8 +110,8 @@ impl Value {
         self.to_value()
             .as_str()
             .map(str::to_owned)
-            .or_else(|| self.to_value().as_integer().map(|i| i.to_string()))
-            .or_else(|| self.to_value().as_float().map(|n| n.to_string()))
+            .or_else(|| self.to_value().as_integer_strict().map(|i| i.to_string()))
+            .or_else(|| self.to_value().as_float().map(lua_float_to_string))
             .unwrap_or_else(|| self.type_name().to_owned())
     }
 
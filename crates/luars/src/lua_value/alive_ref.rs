@@ -31,6 +31,9 @@ use std::cell::Cell;
 use std::fmt;
 use std::rc::Rc;
 
+use crate::lua_value::shared_userdata::SharedBorrow;
+use crate::lua_vm::LuaError;
+
 // ============================================================================
 // RefAliveToken — liveness tracking
 // ============================================================================
@@ -39,19 +42,51 @@ use std::rc::Rc;
 ///
 /// Each sub-reference holds one token. When the parent userdata is GC-collected
 /// (only for owned storage), the token becomes expired and all sub-references
-/// will return errors on access.
+/// will return errors on access. Sub-references into a shared userdata also
+/// carry its borrow flag, see [`check_borrow`](Self::check_borrow).
 ///
 /// `!Send + !Sync` (contains `Rc`).
 #[derive(Clone)]
 pub struct RefAliveToken {
     inner: Rc<Cell<bool>>,
+    shared: Option<SharedBorrow>,
 }
 
 impl RefAliveToken {
     /// Create from an existing `Rc<Cell<bool>>`. Used by `LuaUserdata`.
     #[inline]
     pub fn from_inner(inner: Rc<Cell<bool>>) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            shared: None,
+        }
+    }
+
+    /// Tie the token to the borrow flag of the shared userdata whose value
+    /// the sub-references point into.
+    pub(crate) fn with_shared(mut self, shared: SharedBorrow) -> Self {
+        self.shared = Some(shared);
+        self
+    }
+
+    /// Fail with [`LuaError::BorrowConflict`] if the shared value this token
+    /// points into is exclusively borrowed elsewhere.
+    #[inline]
+    pub(crate) fn check_borrow(&self) -> Result<(), LuaError> {
+        match &self.shared {
+            Some(shared) => shared.check().map_err(|_| LuaError::BorrowConflict),
+            None => Ok(()),
+        }
+    }
+
+    /// Like [`check_borrow`](Self::check_borrow), for access that mutates:
+    /// any other borrow conflicts.
+    #[inline]
+    pub(crate) fn check_borrow_mut(&self) -> Result<(), LuaError> {
+        match &self.shared {
+            Some(shared) => shared.check_mut().map_err(|_| LuaError::BorrowConflict),
+            None => Ok(()),
+        }
     }
 
     /// Create a permanently dead token. Sub-references created with this
@@ -60,6 +95,7 @@ impl RefAliveToken {
     pub fn dead() -> Self {
         Self {
             inner: Rc::new(Cell::new(false)),
+            shared: None,
         }
     }
 
@@ -89,6 +125,7 @@ impl Default for RefAliveToken {
     fn default() -> Self {
         Self {
             inner: Rc::new(Cell::new(true)),
+            shared: None,
         }
     }
 }
@@ -1,11 +1,12 @@
 // Chunk serializer/deserializer for string.dump/load
 // Custom binary format for lua-rs bytecode
 
+use super::chunk_verifier::verify_proto;
 use super::{LocVar, LuaProto, LuaValue, UpvalueDesc};
 use crate::Instruction;
 use crate::gc::{GcProto, ProtoPtr};
 use crate::lua_vm::GlobalState;
-use crate::lua_vm::lua_limits::LUAI_MAXSHORTLEN;
+use crate::lua_vm::lua_limits::{LUAI_MAXSHORTLEN, MAXCCALLS};
 use std::collections::HashMap;
 use std::io::{Cursor, Read};
 use std::sync::Arc;
@@ -18,16 +19,98 @@ fn detached_proto(chunk: LuaProto) -> ProtoPtr {
 
 // Magic number for lua-rs bytecode (different from official Lua)
 const LUARS_MAGIC: &[u8] = b"\x1bLuaRS";
-const LUARS_VERSION: u8 = 1;
+const LUARS_VERSION: u8 = 2;
+// Header layout follows lundump.c: format byte, LUAC_DATA corruption check,
+// the sizes of the serialized types, then LUAC_INT / LUAC_NUM test values.
+// All multi-byte values are written little-endian regardless of the host.
+const LUARS_FORMAT: u8 = 0;
+const LUAC_DATA: &[u8] = b"\x19\x93\r\n\x1a\n";
+const LUAC_INT: i64 = 0x5678;
+const LUAC_NUM: f64 = 370.5;
+const SIZE_INT: u8 = 4; // counts, line numbers, pcs
+const SIZE_SIZE_T: u8 = 8; // string lengths
+const SIZE_INSTRUCTION: u8 = std::mem::size_of::<u32>() as u8;
+const SIZE_INTEGER: u8 = std::mem::size_of::<i64>() as u8;
+const SIZE_NUMBER: u8 = std::mem::size_of::<f64>() as u8;
+
+/// Write the binary chunk header (port of dumpHeader from ldump.c)
+fn write_header(buf: &mut Vec<u8>, strip: bool) {
+    buf.extend_from_slice(LUARS_MAGIC);
+    buf.push(LUARS_VERSION);
+    buf.push(LUARS_FORMAT);
+    buf.extend_from_slice(LUAC_DATA);
+    buf.push(SIZE_INT);
+    buf.push(SIZE_SIZE_T);
+    buf.push(SIZE_INSTRUCTION);
+    buf.push(SIZE_INTEGER);
+    buf.push(SIZE_NUMBER);
+    write_i64(buf, LUAC_INT);
+    write_f64(buf, LUAC_NUM);
+    buf.push(if strip { 1 } else { 0 });
+}
+
+fn truncated<E>(_: E) -> String {
+    "bad binary format (truncated chunk)".to_string()
+}
+
+/// Validate the binary chunk header (port of checkHeader from lundump.c).
+/// Returns the strip flag.
+fn check_header(cursor: &mut Cursor<&[u8]>) -> Result<bool, String> {
+    let mut magic = [0u8; 6];
+    cursor.read_exact(&mut magic).map_err(truncated)?;
+    if magic != LUARS_MAGIC {
+        return Err("not a lua-rs bytecode file".to_string());
+    }
+
+    let version = read_u8(cursor).map_err(truncated)?;
+    if version != LUARS_VERSION {
+        return Err(format!(
+            "bad binary format (version mismatch: expected {}, got {})",
+            LUARS_VERSION, version
+        ));
+    }
+    if read_u8(cursor).map_err(truncated)? != LUARS_FORMAT {
+        return Err("bad binary format (format mismatch)".to_string());
+    }
+
+    let mut data = [0u8; 6];
+    cursor.read_exact(&mut data).map_err(truncated)?;
+    if data != LUAC_DATA {
+        return Err("bad binary format (corrupted chunk)".to_string());
+    }
+
+    for (expected, what) in [
+        (SIZE_INT, "int"),
+        (SIZE_SIZE_T, "size_t"),
+        (SIZE_INSTRUCTION, "Instruction"),
+        (SIZE_INTEGER, "lua_Integer"),
+        (SIZE_NUMBER, "lua_Number"),
+    ] {
+        if read_u8(cursor).map_err(truncated)? != expected {
+            return Err(format!("bad binary format ({} size mismatch)", what));
+        }
+    }
+
+    let int_check = read_i64(cursor).map_err(truncated)?;
+    if int_check != LUAC_INT {
+        return Err(if int_check.swap_bytes() == LUAC_INT {
+            "bad binary format (endianness mismatch)".to_string()
+        } else {
+            "bad binary format (integer format mismatch)".to_string()
+        });
+    }
+    if read_f64(cursor).map_err(truncated)? != LUAC_NUM {
+        return Err("bad binary format (float format mismatch)".to_string());
+    }
+
+    Ok(read_u8(cursor).map_err(truncated)? != 0)
+}
 
-/// Serialize a Chunk to binary format (requires ObjectPool for string access)
+/// Serialize a Chunk to binary format, deduplicating string constants
 pub fn serialize_chunk_with_pool(chunk: &LuaProto, strip: bool) -> Result<Vec<u8>, String> {
     let mut buf = Vec::new();
 
-    // Write header
-    buf.extend_from_slice(LUARS_MAGIC);
-    buf.push(LUARS_VERSION);
-    buf.push(if strip { 1 } else { 0 });
+    write_header(&mut buf, strip);
 
     // Create string table for deduplication
     let mut string_table = HashMap::new();
@@ -42,10 +125,7 @@ pub fn serialize_chunk_with_pool(chunk: &LuaProto, strip: bool) -> Result<Vec<u8
 pub fn serialize_chunk(chunk: &LuaProto, strip: bool) -> Result<Vec<u8>, String> {
     let mut buf = Vec::new();
 
-    // Write header
-    buf.extend_from_slice(LUARS_MAGIC);
-    buf.push(LUARS_VERSION);
-    buf.push(if strip { 1 } else { 0 });
+    write_header(&mut buf, strip);
 
     // Create string table for deduplication
     let mut string_table = HashMap::new();
@@ -60,29 +140,7 @@ pub fn serialize_chunk(chunk: &LuaProto, strip: bool) -> Result<Vec<u8>, String>
 pub fn deserialize_chunk(data: &[u8]) -> Result<LuaProto, String> {
     let mut cursor = Cursor::new(data);
 
-    // Verify magic number
-    let mut magic = [0u8; 6];
-    cursor
-        .read_exact(&mut magic)
-        .map_err(|e| format!("failed to read magic: {}", e))?;
-    if magic != LUARS_MAGIC {
-        return Err("not a lua-rs bytecode file".to_string());
-    }
-
-    // Read version
-    let mut version = [0u8; 1];
-    cursor
-        .read_exact(&mut version)
-        .map_err(|e| format!("failed to read version: {}", e))?;
-    if version[0] != LUARS_VERSION {
-        return Err(format!("unsupported bytecode version: {}", version[0]));
-    }
-
-    // Read strip flag (for future use)
-    let mut stripped = [0u8; 1];
-    cursor
-        .read_exact(&mut stripped)
-        .map_err(|_| "failed to read strip flag")?;
+    check_header(&mut cursor)?;
 
     // Create string table for deduplication during deserialization
     let mut string_table = Vec::new();
@@ -98,35 +156,13 @@ pub fn deserialize_chunk_with_strings_vm(
 ) -> Result<LuaProto, String> {
     let mut cursor = Cursor::new(data);
 
-    // Verify magic number
-    let mut magic = [0u8; 6];
-    cursor
-        .read_exact(&mut magic)
-        .map_err(|e| format!("failed to read magic: {}", e))?;
-    if magic != LUARS_MAGIC {
-        return Err("not a lua-rs bytecode file".to_string());
-    }
-
-    // Read version
-    let mut version = [0u8; 1];
-    cursor
-        .read_exact(&mut version)
-        .map_err(|e| format!("failed to read version: {}", e))?;
-    if version[0] != LUARS_VERSION {
-        return Err(format!("unsupported bytecode version: {}", version[0]));
-    }
-
-    // Read strip flag
-    let mut stripped = [0u8; 1];
-    cursor
-        .read_exact(&mut stripped)
-        .map_err(|_| "failed to read strip flag")?;
+    check_header(&mut cursor)?;
 
     // Create string table for deduplication
     let mut string_table = Vec::new();
 
     // Read chunk data with VM, supporting string deduplication
-    let chunk = read_chunk_with_vm_dedup(&mut cursor, vm, &mut string_table)?;
+    let chunk = read_chunk_with_vm_dedup(&mut cursor, vm, &mut string_table, 0)?;
     Ok(chunk)
 }
 
@@ -136,29 +172,7 @@ pub fn deserialize_chunk_with_strings(
 ) -> Result<(LuaProto, Vec<(usize, String)>), String> {
     let mut cursor = Cursor::new(data);
 
-    // Verify magic number
-    let mut magic = [0u8; 6];
-    cursor
-        .read_exact(&mut magic)
-        .map_err(|e| format!("failed to read magic: {}", e))?;
-    if magic != LUARS_MAGIC {
-        return Err("not a lua-rs bytecode file".to_string());
-    }
-
-    // Read version
-    let mut version = [0u8; 1];
-    cursor
-        .read_exact(&mut version)
-        .map_err(|e| format!("failed to read version: {}", e))?;
-    if version[0] != LUARS_VERSION {
-        return Err(format!("unsupported bytecode version: {}", version[0]));
-    }
-
-    // Read strip flag
-    let mut stripped = [0u8; 1];
-    cursor
-        .read_exact(&mut stripped)
-        .map_err(|_| "failed to read strip flag")?;
+    check_header(&mut cursor)?;
 
     // Read chunk data with string collection
     let mut strings = Vec::new();
@@ -213,7 +227,7 @@ fn write_chunk_with_dedup(
 
     // Write debug info (if not stripped)
     if strip {
-        write_u32(buf, 0); // no source name (len=0)
+        write_size(buf, 0); // no source name (len=0)
         write_u32(buf, 0); // index=0 means None
         write_u32(buf, 0); // no locals
         write_u32(buf, 0); // no line info
@@ -221,7 +235,7 @@ fn write_chunk_with_dedup(
         if let Some(ref name) = chunk.source_name {
             write_string_with_dedup(buf, name.as_ref(), string_table)?; // Use dedup for source name
         } else {
-            write_u32(buf, 0); // len = 0
+            write_size(buf, 0); // len = 0
             write_u32(buf, 0); // index = 0 means None
         }
 
@@ -288,7 +302,7 @@ fn write_chunk_no_pool_with_dedup(
 
     // Write debug info with deduplication
     if strip {
-        write_u32(buf, 0);
+        write_size(buf, 0);
         write_u32(buf, 0);
         write_u32(buf, 0);
         write_u32(buf, 0);
@@ -296,7 +310,7 @@ fn write_chunk_no_pool_with_dedup(
         if let Some(ref name) = chunk.source_name {
             write_string_with_dedup(buf, name.as_ref(), string_table)?;
         } else {
-            write_u32(buf, 0);
+            write_size(buf, 0);
             write_u32(buf, 0);
         }
 
@@ -359,14 +373,14 @@ fn write_chunk_no_pool(buf: &mut Vec<u8>, chunk: &LuaProto, strip: bool) -> Resu
 
     // Write debug info
     if strip {
-        write_u32(buf, 0);
+        write_size(buf, 0);
         write_u32(buf, 0);
         write_u32(buf, 0);
     } else {
         if let Some(ref name) = chunk.source_name {
             write_string(buf, name.as_ref());
         } else {
-            write_u32(buf, 0);
+            write_size(buf, 0);
         }
 
         write_u32(buf, chunk.locals.len() as u32);
@@ -388,14 +402,14 @@ fn write_chunk_no_pool(buf: &mut Vec<u8>, chunk: &LuaProto, strip: bool) -> Resu
 #[allow(dead_code)]
 fn read_chunk(cursor: &mut Cursor<&[u8]>) -> Result<LuaProto, String> {
     // Read code
-    let code_len = read_u32(cursor)? as usize;
+    let code_len = read_count(cursor)?;
     let mut code = Vec::with_capacity(code_len);
     for _ in 0..code_len {
         code.push(Instruction::from_u32(read_u32(cursor)?));
     }
 
     // Read constants
-    let const_len = read_u32(cursor)? as usize;
+    let const_len = read_count(cursor)?;
     let mut constants = Vec::with_capacity(const_len);
     for _ in 0..const_len {
         constants.push(read_constant(cursor)?);
@@ -411,7 +425,7 @@ fn read_chunk(cursor: &mut Cursor<&[u8]>) -> Result<LuaProto, String> {
     let lastlinedefined = read_u32(cursor)? as usize;
 
     // Read upvalue descriptors
-    let desc_len = read_u32(cursor)? as usize;
+    let desc_len = read_count(cursor)?;
     let mut upvalue_descs = Vec::with_capacity(desc_len);
     for _ in 0..desc_len {
         let name = read_string(cursor)?;
@@ -425,7 +439,7 @@ fn read_chunk(cursor: &mut Cursor<&[u8]>) -> Result<LuaProto, String> {
     }
 
     // Read child prototypes
-    let child_len = read_u32(cursor)? as usize;
+    let child_len = read_count(cursor)?;
     let mut child_protos = Vec::with_capacity(child_len);
     for _ in 0..child_len {
         child_protos.push(detached_proto(read_chunk(cursor)?));
@@ -434,7 +448,7 @@ fn read_chunk(cursor: &mut Cursor<&[u8]>) -> Result<LuaProto, String> {
     // Read debug info
     let source_name = read_optional_string(cursor)?.map(Arc::<str>::from);
 
-    let locals_len = read_u32(cursor)? as usize;
+    let locals_len = read_count(cursor)?;
     let mut locals = Vec::with_capacity(locals_len);
     for _ in 0..locals_len {
         let name = read_string(cursor)?;
@@ -447,7 +461,7 @@ fn read_chunk(cursor: &mut Cursor<&[u8]>) -> Result<LuaProto, String> {
         });
     }
 
-    let line_len = read_u32(cursor)? as usize;
+    let line_len = read_count(cursor)?;
     let mut line_info = Vec::with_capacity(line_len);
     for _ in 0..line_len {
         line_info.push(read_u32(cursor)?);
@@ -466,11 +480,16 @@ fn read_chunk(cursor: &mut Cursor<&[u8]>) -> Result<LuaProto, String> {
         child_protos,
         upvalue_descs,
         source_name,
+        name: None,
         line_info,
         linedefined,
         lastlinedefined,
         proto_data_size: 0,
+        key_hints: Vec::new(),
+        #[cfg(feature = "profiler")]
+        profile_id: 0,
     };
+    chunk.init_key_hints();
     chunk.compute_proto_data_size();
     Ok(chunk)
 }
@@ -480,14 +499,14 @@ fn read_chunk_with_dedup(
     string_table: &mut Vec<String>,
 ) -> Result<LuaProto, String> {
     // Read code
-    let code_len = read_u32(cursor)? as usize;
+    let code_len = read_count(cursor)?;
     let mut code = Vec::with_capacity(code_len);
     for _ in 0..code_len {
         code.push(Instruction::from_u32(read_u32(cursor)?));
     }
 
     // Read constants with string deduplication
-    let const_len = read_u32(cursor)? as usize;
+    let const_len = read_count(cursor)?;
     let mut constants = Vec::with_capacity(const_len);
     for _ in 0..const_len {
         constants.push(read_constant_with_dedup(cursor, string_table)?);
@@ -503,7 +522,7 @@ fn read_chunk_with_dedup(
     let lastlinedefined = read_u32(cursor)? as usize;
 
     // Read upvalue descriptors with string deduplication
-    let desc_len = read_u32(cursor)? as usize;
+    let desc_len = read_count(cursor)?;
     let mut upvalue_descs = Vec::with_capacity(desc_len);
     for _ in 0..desc_len {
         let name = read_string_with_dedup(cursor, string_table)?;
@@ -517,7 +536,7 @@ fn read_chunk_with_dedup(
     }
 
     // Read child prototypes
-    let child_len = read_u32(cursor)? as usize;
+    let child_len = read_count(cursor)?;
     let mut child_protos = Vec::with_capacity(child_len);
     for _ in 0..child_len {
         child_protos.push(detached_proto(read_chunk_with_dedup(cursor, string_table)?));
@@ -526,7 +545,7 @@ fn read_chunk_with_dedup(
     // Read debug info with string deduplication
     let source_name = read_optional_string_with_dedup(cursor, string_table)?.map(Arc::<str>::from);
 
-    let locals_len = read_u32(cursor)? as usize;
+    let locals_len = read_count(cursor)?;
     let mut locals = Vec::with_capacity(locals_len);
     for _ in 0..locals_len {
         let name = read_string_with_dedup(cursor, string_table)?;
@@ -539,7 +558,7 @@ fn read_chunk_with_dedup(
         });
     }
 
-    let line_len = read_u32(cursor)? as usize;
+    let line_len = read_count(cursor)?;
     let mut line_info = Vec::with_capacity(line_len);
     for _ in 0..line_len {
         line_info.push(read_u32(cursor)?);
@@ -558,11 +577,16 @@ fn read_chunk_with_dedup(
         child_protos,
         upvalue_descs,
         source_name,
+        name: None,
         line_info,
         linedefined,
         lastlinedefined,
         proto_data_size: 0,
+        key_hints: Vec::new(),
+        #[cfg(feature = "profiler")]
+        profile_id: 0,
     };
+    chunk.init_key_hints();
     chunk.compute_proto_data_size();
     Ok(chunk)
 }
@@ -622,7 +646,7 @@ fn write_constant_with_dedup(
     } else if let Some(bytes) = value.as_bytes() {
         if value.as_str().is_none() {
             buf.push(TAG_BINARY);
-            write_u32(buf, bytes.len() as u32);
+            write_size(buf, bytes.len());
             buf.extend_from_slice(bytes);
         } else if let Some(lua_string) = value.as_str() {
             // Use short string tag for strings <= LUAI_MAXSHORTLEN bytes, long string otherwise
@@ -651,7 +675,7 @@ fn write_string_with_dedup(
     // Check if string was already written
     if let Some(&index) = string_table.get(s) {
         // Write index reference (0 length + index)
-        write_u32(buf, 0); // size = 0 means "reuse or empty"
+        write_size(buf, 0); // size = 0 means "reuse or empty"
         write_u32(buf, index); // index of existing string (1-based, >0)
     } else {
         // New string: assign it an index and write it
@@ -661,7 +685,7 @@ fn write_string_with_dedup(
         // Write the actual string
         if s.is_empty() {
             // Empty string: write len=0, index=0 (special case)
-            write_u32(buf, 0); // len = 0
+            write_size(buf, 0); // len = 0
             write_u32(buf, 0); // index = 0 means new empty string
         } else {
             // Non-empty string: write normally
@@ -723,7 +747,7 @@ fn read_constant(cursor: &mut Cursor<&[u8]>) -> Result<LuaValue, String> {
         TAG_FLOAT => Ok(LuaValue::number(read_f64(cursor)?)),
         TAG_SHORT_STRING | TAG_LONG_STRING => {
             // Skip the string data, return nil as placeholder
-            let len = read_u32(cursor)? as usize;
+            let len = read_size(cursor)?;
             let mut buf = vec![0u8; len];
             cursor
                 .read_exact(&mut buf)
@@ -740,14 +764,14 @@ fn read_chunk_with_vm(
     vm: &mut GlobalState,
 ) -> Result<LuaProto, String> {
     // Read code
-    let code_len = read_u32(cursor)? as usize;
+    let code_len = read_count(cursor)?;
     let mut code = Vec::with_capacity(code_len);
     for _ in 0..code_len {
         code.push(Instruction::from_u32(read_u32(cursor)?));
     }
 
     // Read constants with direct VM string creation
-    let const_len = read_u32(cursor)? as usize;
+    let const_len = read_count(cursor)?;
     let mut constants = Vec::with_capacity(const_len);
     for _ in 0..const_len {
         constants.push(read_constant_with_vm(cursor, vm)?);
@@ -763,7 +787,7 @@ fn read_chunk_with_vm(
     let lastlinedefined = read_u32(cursor)? as usize;
 
     // Read upvalue descriptors
-    let desc_len = read_u32(cursor)? as usize;
+    let desc_len = read_count(cursor)?;
     let mut upvalue_descs = Vec::with_capacity(desc_len);
     for _ in 0..desc_len {
         let name = read_string(cursor)?;
@@ -777,7 +801,7 @@ fn read_chunk_with_vm(
     }
 
     // Read child prototypes recursively with VM
-    let child_len = read_u32(cursor)? as usize;
+    let child_len = read_count(cursor)?;
     let mut child_protos = Vec::with_capacity(child_len);
     for _ in 0..child_len {
         let child_chunk = read_chunk_with_vm(cursor, vm)?;
@@ -787,7 +811,7 @@ fn read_chunk_with_vm(
     // Read debug info
     let source_name = read_optional_string(cursor)?.map(Arc::<str>::from);
 
-    let locals_len = read_u32(cursor)? as usize;
+    let locals_len = read_count(cursor)?;
     let mut locals = Vec::with_capacity(locals_len);
     for _ in 0..locals_len {
         let name = read_string(cursor)?;
@@ -800,7 +824,7 @@ fn read_chunk_with_vm(
         });
     }
 
-    let line_len = read_u32(cursor)? as usize;
+    let line_len = read_count(cursor)?;
     let mut line_info = Vec::with_capacity(line_len);
     for _ in 0..line_len {
         line_info.push(read_u32(cursor)?);
@@ -816,6 +840,7 @@ fn read_chunk_with_vm(
         max_stack_size,
         upvalue_descs,
         source_name,
+        name: None,
         locals,
         line_info,
         needs_vararg_table,
@@ -823,7 +848,11 @@ fn read_chunk_with_vm(
         linedefined,
         lastlinedefined,
         proto_data_size: 0,
+        key_hints: Vec::new(),
+        #[cfg(feature = "profiler")]
+        profile_id: 0,
     };
+    chunk.init_key_hints();
     chunk.compute_proto_data_size();
     Ok(chunk)
 }
@@ -853,16 +882,22 @@ fn read_chunk_with_vm_dedup(
     cursor: &mut Cursor<&[u8]>,
     vm: &mut GlobalState,
     string_table: &mut Vec<String>,
+    depth: usize,
 ) -> Result<LuaProto, String> {
+    // The compiler never nests functions deeper than its syntax-level limit
+    if depth > MAXCCALLS {
+        return Err("bad binary format (functions nested too deeply)".to_string());
+    }
+
     // Read code
-    let code_len = read_u32(cursor)? as usize;
+    let code_len = read_count(cursor)?;
     let mut code = Vec::with_capacity(code_len);
     for _ in 0..code_len {
         code.push(Instruction::from_u32(read_u32(cursor)?));
     }
 
     // Read constants with VM string creation and deduplication
-    let const_len = read_u32(cursor)? as usize;
+    let const_len = read_count(cursor)?;
     let mut constants = Vec::with_capacity(const_len);
     for _ in 0..const_len {
         constants.push(read_constant_with_vm_dedup(cursor, vm, string_table)?);
@@ -878,7 +913,7 @@ fn read_chunk_with_vm_dedup(
     let lastlinedefined = read_u32(cursor)? as usize;
 
     // Read upvalue descriptors with deduplication
-    let desc_len = read_u32(cursor)? as usize;
+    let desc_len = read_count(cursor)?;
     let mut upvalue_descs = Vec::with_capacity(desc_len);
     for _ in 0..desc_len {
         let name = read_string_with_dedup(cursor, string_table)?;
@@ -892,17 +927,17 @@ fn read_chunk_with_vm_dedup(
     }
 
     // Read child prototypes recursively with VM and deduplication
-    let child_len = read_u32(cursor)? as usize;
+    let child_len = read_count(cursor)?;
     let mut child_protos = Vec::with_capacity(child_len);
     for _ in 0..child_len {
-        let child_chunk = read_chunk_with_vm_dedup(cursor, vm, string_table)?;
+        let child_chunk = read_chunk_with_vm_dedup(cursor, vm, string_table, depth + 1)?;
         child_protos.push(vm.create_proto(child_chunk).map_err(|e| e.to_string())?);
     }
 
     // Read debug info with deduplication
     let source_name = read_optional_string_with_dedup(cursor, string_table)?.map(Arc::<str>::from);
 
-    let locals_len = read_u32(cursor)? as usize;
+    let locals_len = read_count(cursor)?;
     let mut locals = Vec::with_capacity(locals_len);
     for _ in 0..locals_len {
         let name = read_string_with_dedup(cursor, string_table)?;
@@ -915,7 +950,7 @@ fn read_chunk_with_vm_dedup(
         });
     }
 
-    let line_len = read_u32(cursor)? as usize;
+    let line_len = read_count(cursor)?;
     let mut line_info = Vec::with_capacity(line_len);
     for _ in 0..line_len {
         line_info.push(read_u32(cursor)?);
@@ -931,6 +966,7 @@ fn read_chunk_with_vm_dedup(
         max_stack_size,
         upvalue_descs,
         source_name,
+        name: None,
         locals,
         line_info,
         needs_vararg_table,
@@ -938,8 +974,13 @@ fn read_chunk_with_vm_dedup(
         linedefined,
         lastlinedefined,
         proto_data_size: 0,
+        key_hints: Vec::new(),
+        #[cfg(feature = "profiler")]
+        profile_id: 0,
     };
+    chunk.init_key_hints();
     chunk.compute_proto_data_size();
+    verify_proto(&chunk).map_err(|e| format!("bad binary format ({})", e))?;
     Ok(chunk)
 }
 
@@ -962,7 +1003,7 @@ fn read_constant_with_vm_dedup(
         }
         TAG_BINARY => {
             // Read a non-UTF-8 byte-string constant from the legacy dump format.
-            let len = read_u32(cursor)? as usize;
+            let len = read_size(cursor)?;
             let mut bytes = vec![0u8; len];
             cursor
                 .read_exact(&mut bytes)
@@ -979,14 +1020,14 @@ fn read_chunk_with_strings(
     strings: &mut Vec<(usize, String)>,
 ) -> Result<LuaProto, String> {
     // Read code
-    let code_len = read_u32(cursor)? as usize;
+    let code_len = read_count(cursor)?;
     let mut code = Vec::with_capacity(code_len);
     for _ in 0..code_len {
         code.push(Instruction::from_u32(read_u32(cursor)?));
     }
 
     // Read constants with string collection
-    let const_len = read_u32(cursor)? as usize;
+    let const_len = read_count(cursor)?;
     let mut constants = Vec::with_capacity(const_len);
     for i in 0..const_len {
         constants.push(read_constant_with_strings(cursor, i, strings)?);
@@ -1002,7 +1043,7 @@ fn read_chunk_with_strings(
     let lastlinedefined = read_u32(cursor)? as usize;
 
     // Read upvalue descriptors
-    let desc_len = read_u32(cursor)? as usize;
+    let desc_len = read_count(cursor)?;
     let mut upvalue_descs = Vec::with_capacity(desc_len);
     for _ in 0..desc_len {
         let name = read_string(cursor)?;
@@ -1016,7 +1057,7 @@ fn read_chunk_with_strings(
     }
 
     // Read child prototypes
-    let child_len = read_u32(cursor)? as usize;
+    let child_len = read_count(cursor)?;
     let mut child_protos = Vec::with_capacity(child_len);
     for _ in 0..child_len {
         child_protos.push(detached_proto(read_chunk_with_strings(cursor, strings)?));
@@ -1025,7 +1066,7 @@ fn read_chunk_with_strings(
     // Read debug info
     let source_name = read_optional_string(cursor)?.map(Arc::<str>::from);
 
-    let locals_len = read_u32(cursor)? as usize;
+    let locals_len = read_count(cursor)?;
     let mut locals = Vec::with_capacity(locals_len);
     for _ in 0..locals_len {
         let name = read_string(cursor)?;
@@ -1038,7 +1079,7 @@ fn read_chunk_with_strings(
         });
     }
 
-    let line_len = read_u32(cursor)? as usize;
+    let line_len = read_count(cursor)?;
     let mut line_info = Vec::with_capacity(line_len);
     for _ in 0..line_len {
         line_info.push(read_u32(cursor)?);
@@ -1057,11 +1098,16 @@ fn read_chunk_with_strings(
         child_protos,
         upvalue_descs,
         source_name,
+        name: None,
         line_info,
         linedefined,
         lastlinedefined,
         proto_data_size: 0,
+        key_hints: Vec::new(),
+        #[cfg(feature = "profiler")]
+        profile_id: 0,
     };
+    chunk.init_key_hints();
     chunk.compute_proto_data_size();
     Ok(chunk)
 }
@@ -1071,6 +1117,11 @@ fn write_u32(buf: &mut Vec<u8>, value: u32) {
     buf.extend_from_slice(&value.to_le_bytes());
 }
 
+/// String lengths are always 64-bit so dumps do not depend on the host usize
+fn write_size(buf: &mut Vec<u8>, value: usize) {
+    buf.extend_from_slice(&(value as u64).to_le_bytes());
+}
+
 fn write_i64(buf: &mut Vec<u8>, value: i64) {
     buf.extend_from_slice(&value.to_le_bytes());
 }
@@ -1081,7 +1132,7 @@ fn write_f64(buf: &mut Vec<u8>, value: f64) {
 
 fn write_string(buf: &mut Vec<u8>, s: &str) {
     let bytes = s.as_bytes();
-    write_u32(buf, bytes.len() as u32);
+    write_size(buf, bytes.len());
     buf.extend_from_slice(bytes);
 }
 
@@ -1101,6 +1152,31 @@ fn read_u32(cursor: &mut Cursor<&[u8]>) -> Result<u32, String> {
     Ok(u32::from_le_bytes(buf))
 }
 
+fn read_size(cursor: &mut Cursor<&[u8]>) -> Result<usize, String> {
+    let mut buf = [0u8; 8];
+    cursor
+        .read_exact(&mut buf)
+        .map_err(|e| format!("read error: {}", e))?;
+    let len = u64::from_le_bytes(buf);
+    // Never trust a length beyond the bytes actually left in the chunk
+    let remaining = (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+    if len > remaining {
+        return Err(truncated(len));
+    }
+    Ok(len as usize)
+}
+
+/// Read an element count, rejecting counts the rest of the chunk cannot hold
+/// (every element takes at least one byte) before anything is allocated.
+fn read_count(cursor: &mut Cursor<&[u8]>) -> Result<usize, String> {
+    let count = read_u32(cursor)? as u64;
+    let remaining = (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+    if count > remaining {
+        return Err(truncated(count));
+    }
+    Ok(count as usize)
+}
+
 fn read_i64(cursor: &mut Cursor<&[u8]>) -> Result<i64, String> {
     let mut buf = [0u8; 8];
     cursor
@@ -1118,7 +1194,7 @@ fn read_f64(cursor: &mut Cursor<&[u8]>) -> Result<f64, String> {
 }
 
 fn read_string(cursor: &mut Cursor<&[u8]>) -> Result<String, String> {
-    let len = read_u32(cursor)? as usize;
+    let len = read_size(cursor)?;
     let mut buf = vec![0u8; len];
     cursor
         .read_exact(&mut buf)
@@ -1127,7 +1203,7 @@ fn read_string(cursor: &mut Cursor<&[u8]>) -> Result<String, String> {
 }
 
 fn read_optional_string(cursor: &mut Cursor<&[u8]>) -> Result<Option<String>, String> {
-    let len = read_u32(cursor)? as usize;
+    let len = read_size(cursor)?;
     if len == 0 {
         return Ok(None);
     }
@@ -1144,7 +1220,7 @@ fn read_string_with_dedup(
     cursor: &mut Cursor<&[u8]>,
     string_table: &mut Vec<String>,
 ) -> Result<String, String> {
-    let len = read_u32(cursor)? as usize;
+    let len = read_size(cursor)?;
 
     if len == 0 {
         // Could be a reference or an empty string
@@ -1159,19 +1235,6 @@ fn read_string_with_dedup(
 
         // This is a reference to an existing string
         if index > string_table.len() {
-            eprintln!(
-                "ERROR: String reference index {} out of range (table size: {})",
-                index,
-                string_table.len()
-            );
-            eprintln!("String table contents (first 50):");
-            for (i, s) in string_table.iter().take(50).enumerate() {
-                eprintln!(
-                    "  [{}]: {:?}",
-                    i + 1,
-                    if s.len() > 50 { &s[..50] } else { s }
-                );
-            }
             return Err(format!("invalid string reference index: {}", index));
         }
         Ok(string_table[index - 1].clone())
@@ -1193,7 +1256,7 @@ fn read_optional_string_with_dedup(
     cursor: &mut Cursor<&[u8]>,
     string_table: &mut Vec<String>,
 ) -> Result<Option<String>, String> {
-    let len = read_u32(cursor)? as usize;
+    let len = read_size(cursor)?;
 
     if len == 0 {
         // Read next u32 to determine if it's None or a reference
@@ -1205,11 +1268,6 @@ fn read_optional_string_with_dedup(
             // It's a reference
             let index = index_u32 as usize;
             if index > string_table.len() {
-                eprintln!(
-                    "ERROR in read_optional: String reference index {} out of range (table size: {})",
-                    index,
-                    string_table.len()
-                );
                 return Err(format!("invalid string reference index: {}", index));
             }
             return Ok(Some(string_table[index - 1].clone()));
@@ -1262,4 +1320,97 @@ mod tests {
         assert_eq!(restored.code.len(), 0);
         assert_eq!(restored.constants.len(), 0);
     }
+
+    // Offset of the LUAC_INT check value: magic, version, format, LUAC_DATA, 5 sizes
+    const LUAC_INT_OFFSET: usize = 6 + 1 + 1 + 6 + 5;
+
+    fn dump(vm: &mut GlobalState, source: &str) -> Vec<u8> {
+        let chunk = vm.compile(source).unwrap();
+        serialize_chunk_with_pool(&chunk, false).unwrap()
+    }
+
+    #[test]
+    fn test_header_is_little_endian() {
+        let bytes = serialize_chunk(&LuaProto::new(), false).unwrap();
+        let int_bytes = &bytes[LUAC_INT_OFFSET..LUAC_INT_OFFSET + 8];
+        assert_eq!(int_bytes, &LUAC_INT.to_le_bytes());
+        let num_bytes = &bytes[LUAC_INT_OFFSET + 8..LUAC_INT_OFFSET + 16];
+        assert_eq!(num_bytes, &LUAC_NUM.to_le_bytes());
+    }
+
+    #[test]
+    fn test_header_mismatches_are_reported() {
+        let bytes = serialize_chunk(&LuaProto::new(), false).unwrap();
+
+        let mut swapped = bytes.clone();
+        swapped[LUAC_INT_OFFSET..LUAC_INT_OFFSET + 8].reverse();
+        let err = deserialize_chunk(&swapped).unwrap_err();
+        assert_eq!(err, "bad binary format (endianness mismatch)");
+
+        let mut float_bad = bytes.clone();
+        float_bad[LUAC_INT_OFFSET + 8] ^= 0xff;
+        let err = deserialize_chunk(&float_bad).unwrap_err();
+        assert_eq!(err, "bad binary format (float format mismatch)");
+
+        let mut size_bad = bytes.clone();
+        size_bad[LUAC_INT_OFFSET - 2] = 4; // lua_Integer size
+        let err = deserialize_chunk(&size_bad).unwrap_err();
+        assert_eq!(err, "bad binary format (lua_Integer size mismatch)");
+
+        let err = deserialize_chunk(&bytes[..LUAC_INT_OFFSET + 3]).unwrap_err();
+        assert_eq!(err, "bad binary format (truncated chunk)");
+
+        let mut version_bad = bytes;
+        version_bad[6] = 1;
+        let err = deserialize_chunk(&version_bad).unwrap_err();
+        assert!(
+            err.starts_with("bad binary format (version mismatch"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_numeric_and_long_string_constants() {
+        let mut vm = GlobalState::new(crate::SafeOption::default());
+        let long = "x".repeat(70_000);
+        let source =
+            format!("local a, b, c = 370.5, -1.25e300, -9007199254740993\nreturn '{long}'");
+        let bytes = dump(&mut vm, &source);
+        let chunk = deserialize_chunk_with_strings_vm(&bytes, &mut vm).unwrap();
+
+        let constants = &chunk.constants;
+        assert!(constants.iter().any(|k| k.as_float() == Some(370.5)));
+        assert!(constants.iter().any(|k| k.as_float() == Some(-1.25e300)));
+        assert!(
+            constants
+                .iter()
+                .any(|k| k.as_integer_strict() == Some(-9007199254740993))
+        );
+        assert!(
+            constants
+                .iter()
+                .any(|k| k.as_bytes().is_some_and(|b| b.len() == 70_000))
+        );
+    }
+
+    #[test]
+    fn test_round_trip_deeply_nested_protos() {
+        let mut vm = GlobalState::new(crate::SafeOption::default());
+        let depth = 60;
+        let mut source = String::from("return 0.5");
+        for level in 0..depth {
+            source = format!("return function() local k = {level} {source} end");
+        }
+        let bytes = dump(&mut vm, &source);
+        let chunk = deserialize_chunk_with_strings_vm(&bytes, &mut vm).unwrap();
+
+        let mut current = &chunk;
+        let mut nested = 0;
+        while let Some(child) = current.child_protos.first() {
+            current = &child.as_ref().data;
+            nested += 1;
+        }
+        assert_eq!(nested, depth);
+        assert!(current.constants.iter().any(|k| k.as_float() == Some(0.5)));
+    }
 }
@@ -0,0 +1,451 @@
+// Structural verifier for prototypes that did not come from the compiler.
+//
+// The executor trusts its bytecode: registers, constants and upvalues are
+// read through unchecked pointers and jump targets are never bounds-checked.
+// The compiler upholds those invariants by construction, but a binary chunk
+// or a hand-built prototype can encode anything, so every prototype coming
+// out of the deserializer or the bytecode builder, or handed to `load_proto`,
+// is checked here before it can reach `lua_execute`.
+
+use super::LuaProto;
+use crate::lua_vm::TmKind;
+use crate::lua_vm::lua_limits::MAXUPVAL;
+use crate::{Instruction, OpCode};
+
+/// Largest frame a prototype may request; register operands are 8 bits wide
+/// (MAX_FSTACK in lcode.c).
+const MAX_FSTACK: usize = Instruction::MAX_A as usize;
+
+fn bad(pc: usize, what: &str) -> String {
+    format!("invalid bytecode at pc {}: {}", pc, what)
+}
+
+struct Verifier<'a> {
+    proto: &'a LuaProto,
+    pc: usize,
+}
+
+impl Verifier<'_> {
+    fn reg(&self, r: u32) -> Result<(), String> {
+        self.regs(r, 1)
+    }
+
+    /// Check that the `n` registers starting at `r` lie inside the frame.
+    fn regs(&self, r: u32, n: u32) -> Result<(), String> {
+        if r as usize + n as usize > self.proto.max_stack_size {
+            return Err(bad(self.pc, "register out of frame"));
+        }
+        Ok(())
+    }
+
+    fn konst(&self, k: u32) -> Result<(), String> {
+        if k as usize >= self.proto.constants.len() {
+            return Err(bad(self.pc, "constant index out of range"));
+        }
+        Ok(())
+    }
+
+    /// Field-name operands take the short-string fast path in the executor.
+    fn string_konst(&self, k: u32) -> Result<(), String> {
+        self.konst(k)?;
+        if !self.proto.constants[k as usize].is_short_string() {
+            return Err(bad(self.pc, "field name is not a short string constant"));
+        }
+        Ok(())
+    }
+
+    fn rk(&self, instr: Instruction) -> Result<(), String> {
+        if instr.get_k() {
+            self.konst(instr.get_c())
+        } else {
+            self.reg(instr.get_c())
+        }
+    }
+
+    fn upval(&self, u: u32) -> Result<(), String> {
+        if u as usize >= self.proto.upvalue_count {
+            return Err(bad(self.pc, "upvalue index out of range"));
+        }
+        Ok(())
+    }
+
+    /// Check a jump to `target`; the instruction there must be executable.
+    fn target(&self, target: i64) -> Result<(), String> {
+        let code = &self.proto.code;
+        if target < 0 || target as usize >= code.len() {
+            return Err(bad(self.pc, "jump out of range"));
+        }
+        let instr = code[target as usize];
+        if instr.get_opcode() == OpCode::ExtraArg {
+            return Err(bad(self.pc, "jump into an extra argument"));
+        }
+        // Skipping the open call would leave a stale stack top
+        if is_open_consumer(instr) {
+            return Err(bad(self.pc, "jump into an open operand"));
+        }
+        Ok(())
+    }
+
+    fn next_is(&self, op: OpCode) -> Result<Instruction, String> {
+        match self.proto.code.get(self.pc + 1) {
+            Some(next) if next.get_opcode() == op => Ok(*next),
+            _ => Err(bad(
+                self.pc,
+                &format!("missing {:?} after {:?}", op, self.op()),
+            )),
+        }
+    }
+
+    /// An operand count of 0 means "up to the stack top", which only the
+    /// open call or vararg right before this instruction sets. Its results
+    /// must start at or after register `first` for the count to be sound.
+    fn open_results(&self, first: u32) -> Result<(), String> {
+        let prev = self.pc.checked_sub(1).map(|p| self.proto.code[p]);
+        // TAILCALL's C flags a vararg caller, not a result count
+        let open = prev.is_some_and(|prev| {
+            let sets_top = match prev.get_opcode() {
+                OpCode::TailCall => true,
+                OpCode::Call | OpCode::Vararg => prev.get_c() == 0,
+                _ => false,
+            };
+            sets_top && prev.get_a() >= first
+        });
+        if !open {
+            return Err(bad(self.pc, "open operand without a preceding open call"));
+        }
+        Ok(())
+    }
+
+    fn op(&self) -> OpCode {
+        self.proto.code[self.pc].get_opcode()
+    }
+
+    fn check_instruction(&self, instr: Instruction) -> Result<(), String> {
+        use OpCode::*;
+
+        let pc = self.pc as i64;
+        let a = instr.get_a();
+        let b = instr.get_b();
+        let c = instr.get_c();
+        match instr.get_opcode() {
+            Move | Unm | BNot | Not | Len => {
+                self.reg(a)?;
+                self.reg(b)?;
+            }
+            LoadI | LoadF | LoadFalse | LoadTrue | Return1 | Tbc | ErrNNil => self.reg(a)?,
+            LFalseSkip => {
+                self.reg(a)?;
+                self.target(pc + 2)?;
+            }
+            LoadK => {
+                self.reg(a)?;
+                self.konst(instr.get_bx())?;
+            }
+            LoadKX => {
+                self.reg(a)?;
+                self.konst(self.next_is(ExtraArg)?.get_ax())?;
+            }
+            LoadNil => self.regs(a, b + 1)?,
+            GetUpval | SetUpval => {
+                self.reg(a)?;
+                self.upval(b)?;
+            }
+            GetTabUp => {
+                self.reg(a)?;
+                self.upval(b)?;
+                self.string_konst(c)?;
+            }
+            GetTable => {
+                self.reg(a)?;
+                self.reg(b)?;
+                self.reg(c)?;
+            }
+            GetI => {
+                self.reg(a)?;
+                self.reg(b)?;
+            }
+            GetField => {
+                self.reg(a)?;
+                self.reg(b)?;
+                self.string_konst(c)?;
+            }
+            SetTabUp => {
+                self.upval(a)?;
+                self.string_konst(b)?;
+                self.rk(instr)?;
+            }
+            SetTable => {
+                self.reg(a)?;
+                self.reg(b)?;
+                self.rk(instr)?;
+            }
+            SetI => {
+                self.reg(a)?;
+                self.rk(instr)?;
+            }
+            SetField => {
+                self.reg(a)?;
+                self.string_konst(b)?;
+                self.rk(instr)?;
+            }
+            NewTable => {
+                // The executor always steps over the trailing EXTRAARG
+                self.reg(a)?;
+                self.next_is(ExtraArg)?;
+            }
+            Self_ => {
+                self.regs(a, 2)?;
+                self.reg(b)?;
+                self.string_konst(c)?;
+            }
+            AddI | ShlI | ShrI => {
+                self.reg(a)?;
+                self.reg(b)?;
+                self.next_is(MmBinI)?;
+            }
+            AddK | SubK | MulK | ModK | PowK | DivK | IDivK | BAndK | BOrK | BXorK => {
+                self.reg(a)?;
+                self.reg(b)?;
+                self.konst(c)?;
+                self.next_is(MmBinK)?;
+            }
+            Add | Sub | Mul | Mod | Pow | Div | IDiv | BAnd | BOr | BXor | Shl | Shr => {
+                self.reg(a)?;
+                self.reg(b)?;
+                self.reg(c)?;
+                self.next_is(MmBin)?;
+            }
+            MmBin | MmBinI | MmBinK => {
+                // The fallback stores into the A register of the preceding
+                // arithmetic instruction, which must therefore exist
+                if self.pc == 0 || !is_arith(self.proto.code[self.pc - 1].get_opcode()) {
+                    return Err(bad(self.pc, "metamethod fallback without arithmetic"));
+                }
+                let tm = TmKind::from_u8(c as u8);
+                if !(TmKind::Add as u8..=TmKind::Shr as u8).contains(&(tm as u8)) {
+                    return Err(bad(self.pc, "invalid arithmetic metamethod"));
+                }
+                self.reg(a)?;
+                match instr.get_opcode() {
+                    MmBin => self.reg(b)?,
+                    MmBinK => self.konst(b)?,
+                    _ => {}
+                }
+            }
+            Concat => {
+                if b == 0 {
+                    return Err(bad(self.pc, "empty concatenation"));
+                }
+                self.regs(a, b)?;
+            }
+            Close | Return0 | VarargPrep => self.regs(0, a)?,
+            Jmp => self.target(pc + 1 + instr.get_sj() as i64)?,
+            Eq | Lt | Le | TestSet => {
+                self.reg(a)?;
+                self.reg(b)?;
+                self.next_is(Jmp)?;
+            }
+            EqK => {
+                self.reg(a)?;
+                self.konst(b)?;
+                self.next_is(Jmp)?;
+            }
+            EqI | LtI | LeI | GtI | GeI | Test => {
+                self.reg(a)?;
+                self.next_is(Jmp)?;
+            }
+            Call => {
+                self.reg(a)?;
+                if b == 0 {
+                    self.open_results(a + 1)?;
+                } else {
+                    self.regs(a, b)?;
+                }
+                if c > 1 {
+                    self.regs(a, c - 1)?;
+                }
+            }
+            TailCall => {
+                self.reg(a)?;
+                if b == 0 {
+                    self.open_results(a + 1)?;
+                } else {
+                    self.regs(a, b)?;
+                }
+            }
+            Return => {
+                self.regs(0, a)?;
+                if b == 0 {
+                    self.open_results(a)?;
+                } else if b > 1 {
+                    self.regs(a, b - 1)?;
+                }
+            }
+            ForLoop => {
+                self.regs(a, 3)?;
+                self.target(pc + 1 - instr.get_bx() as i64)?;
+            }
+            ForPrep => {
+                self.regs(a, 3)?;
+                self.target(pc + 2 + instr.get_bx() as i64)?;
+            }
+            TForPrep => {
+                self.regs(a, 4)?;
+                self.target(pc + 1 + instr.get_bx() as i64)?;
+            }
+            TForCall => {
+                // Iterator, state and control are copied to R[A+3..A+5] and
+                // the call leaves its C results from R[A+3] on
+                self.regs(a, 6)?;
+                self.regs(a + 3, c)?;
+            }
+            TForLoop => {
+                self.regs(a, 4)?;
+                self.target(pc + 1 - instr.get_bx() as i64)?;
+            }
+            SetList => {
+                self.regs(a, instr.get_vb() + 1)?;
+                if instr.get_vb() == 0 {
+                    self.open_results(a + 1)?;
+                }
+                if instr.get_k() {
+                    self.next_is(ExtraArg)?;
+                }
+            }
+            Closure => {
+                self.reg(a)?;
+                self.closure(instr.get_bx())?;
+            }
+            Vararg => {
+                self.reg(a)?;
+                if instr.get_k() {
+                    self.reg(b)?;
+                }
+                if c > 1 {
+                    self.regs(a, c - 1)?;
+                }
+            }
+            GetVarg => {
+                self.reg(a)?;
+                self.reg(b)?;
+                self.reg(c)?;
+            }
+            ExtraArg => {
+                // Only reachable as the argument of the previous instruction
+                let owner = self.pc.checked_sub(1).map(|p| self.proto.code[p]);
+                let owned = owner.is_some_and(|prev| match prev.get_opcode() {
+                    LoadKX | NewTable => true,
+                    SetList => prev.get_k(),
+                    _ => false,
+                });
+                if !owned {
+                    return Err(bad(self.pc, "stray extra argument"));
+                }
+            }
+            None => return Err(bad(self.pc, "unknown opcode")),
+        }
+        Ok(())
+    }
+
+    /// Check the upvalue descriptors of the child prototype `bx` against the
+    /// frame and upvalues of this one, which the new closure captures from.
+    fn closure(&self, bx: u32) -> Result<(), String> {
+        let Some(child) = self.proto.child_protos.get(bx as usize) else {
+            return Err(bad(self.pc, "prototype index out of range"));
+        };
+        for desc in &child.as_ref().data.upvalue_descs {
+            if desc.is_local {
+                self.reg(desc.index)?;
+            } else {
+                self.upval(desc.index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Instructions that take their operand count from the stack top.
+fn is_open_consumer(instr: Instruction) -> bool {
+    match instr.get_opcode() {
+        OpCode::Call | OpCode::TailCall | OpCode::Return => instr.get_b() == 0,
+        OpCode::SetList => instr.get_vb() == 0,
+        _ => false,
+    }
+}
+
+/// Arithmetic opcodes, each followed by the MMBIN* that handles its
+/// metamethod fallback.
+fn is_arith(op: OpCode) -> bool {
+    use OpCode::*;
+    matches!(
+        op,
+        AddI | ShlI
+            | ShrI
+            | AddK
+            | SubK
+            | MulK
+            | ModK
+            | PowK
+            | DivK
+            | IDivK
+            | BAndK
+            | BOrK
+            | BXorK
+            | Add
+            | Sub
+            | Mul
+            | Mod
+            | Pow
+            | Div
+            | IDiv
+            | BAnd
+            | BOr
+            | BXor
+            | Shl
+            | Shr
+    )
+}
+
+/// Check that `proto` only uses registers, constants, upvalues, prototypes
+/// and jump targets it actually has. Its child prototypes are not visited;
+/// see [`verify_proto_tree`].
+pub(crate) fn verify_proto(proto: &LuaProto) -> Result<(), String> {
+    if proto.max_stack_size > MAX_FSTACK {
+        return Err("frame too large".to_string());
+    }
+    if proto.param_count > proto.max_stack_size {
+        return Err("more parameters than registers".to_string());
+    }
+    if proto.upvalue_count > MAXUPVAL || proto.upvalue_count != proto.upvalue_descs.len() {
+        return Err("inconsistent upvalues".to_string());
+    }
+    if !proto.line_info.is_empty() && proto.line_info.len() != proto.code.len() {
+        return Err("inconsistent line info".to_string());
+    }
+
+    // Execution must never run off the end of the code
+    match proto.code.last().map(|i| i.get_opcode()) {
+        Some(OpCode::Return | OpCode::Return0 | OpCode::Return1) => {}
+        _ => return Err("function does not end with a return".to_string()),
+    }
+    // The frame of a vararg function is only laid out by VARARGPREP
+    if proto.is_vararg && proto.code[0].get_opcode() != OpCode::VarargPrep {
+        return Err(bad(0, "vararg function does not start with VARARGPREP"));
+    }
+
+    let mut verifier = Verifier { proto, pc: 0 };
+    for (pc, &instr) in proto.code.iter().enumerate() {
+        verifier.pc = pc;
+        verifier.check_instruction(instr)?;
+    }
+    Ok(())
+}
+
+/// [`verify_proto`] for `proto` and, depth first, every prototype nested in
+/// it.
+pub(crate) fn verify_proto_tree(proto: &LuaProto) -> Result<(), String> {
+    for child in &proto.child_protos {
+        verify_proto_tree(&child.as_ref().data)?;
+    }
+    verify_proto(proto)
+}
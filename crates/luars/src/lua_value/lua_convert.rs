@@ -8,6 +8,7 @@
 //! - `String`, `&str` (via intermediate `String`)
 //! - `Option<T>` where `T: FromLua` / `T: IntoLua`
 //! - `LuaValue` (identity — zero-cost passthrough)
+//! - [`MultiValue`](crate::MultiValue) (every value of a list, nils included)
 //! - `T` where `T: UserDataTrait + Clone + 'static` (owned clone from Lua userdata)
 //!
 //! # Usage in derive macros
@@ -31,6 +32,7 @@
 
 use crate::UserDataTrait;
 use crate::lua_value::LuaValue;
+use crate::lua_value::lua_float_to_string;
 use crate::lua_vm::LuaState;
 
 pub(crate) fn collect_into_lua_values<T: IntoLua>(
@@ -74,10 +76,24 @@ pub trait FromLua: Sized {
 ///
 /// This is used by typed call helpers that need to map `Vec<LuaValue>` into a
 /// Rust return type. Single-value returns are supported automatically for every
-/// `T: FromLua`, and tuples map positionally.
+/// `T: FromLua`, and tuples map positionally; a tuple conversion error is
+/// prefixed with the 1-based position that failed, labelled by the caller
+/// (`result #2: ...` for call results, `argument #2: ...` for arguments).
 pub trait FromLuaMulti: Sized {
-    /// Convert a list of Lua values to `Self`.
+    /// Convert a list of Lua values to `Self`, naming failed positions as
+    /// results.
     fn from_lua_multi(values: Vec<LuaValue>, state: &mut LuaState) -> Result<Self, String>;
+
+    /// Convert a list of Lua values to `Self`, naming a failed position with
+    /// `label` (e.g. `"argument"` or `"result"`). Only conversions that
+    /// report positions need to override it.
+    fn from_lua_multi_labeled(
+        values: Vec<LuaValue>,
+        state: &mut LuaState,
+        _label: &str,
+    ) -> Result<Self, String> {
+        Self::from_lua_multi(values, state)
+    }
 }
 
 /// Convert a Rust type into a `LuaValue` and push it.
@@ -261,11 +277,11 @@ impl FromLua for String {
     fn from_lua(value: LuaValue, _state: &mut LuaState) -> Result<Self, String> {
         if let Some(s) = value.as_str() {
             Ok(s.to_owned())
-        } else if let Some(i) = value.as_integer() {
+        } else if let Some(i) = value.as_integer_strict() {
             // Lua coerces numbers to strings
             Ok(format!("{}", i))
         } else if let Some(f) = value.as_float() {
-            Ok(format!("{}", f))
+            Ok(lua_float_to_string(f))
         } else {
             Err(format!("expected string, got {}", value.type_name()))
         }
@@ -360,10 +376,27 @@ macro_rules! impl_lua_tuple_conversions {
 
             impl<$($ty: FromLua),+> FromLuaMulti for ($($ty,)+) {
                 #[inline]
-                fn from_lua_multi(values: Vec<LuaValue>, state: &mut LuaState) -> Result<Self, String> {
+                fn from_lua_multi(
+                    values: Vec<LuaValue>,
+                    state: &mut LuaState,
+                ) -> Result<Self, String> {
+                    Self::from_lua_multi_labeled(values, state, "result")
+                }
+
+                #[inline]
+                fn from_lua_multi_labeled(
+                    values: Vec<LuaValue>,
+                    state: &mut LuaState,
+                    label: &str,
+                ) -> Result<Self, String> {
                     let mut iter = values.into_iter();
+                    let mut position = 0;
                     Ok(($(
-                        $ty::from_lua(iter.next().unwrap_or(LuaValue::nil()), state)?,
+                        {
+                            position += 1;
+                            $ty::from_lua(iter.next().unwrap_or(LuaValue::nil()), state)
+                                .map_err(|msg| format!("{} #{}: {}", label, position, msg))?
+                        },
                     )+))
                 }
             }
@@ -1,5 +1,6 @@
-#[cfg(feature = "shared-proto")]
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::alloc::{self, Layout};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering, fence};
 
 use crate::gc::{StringInterner, StringPtr};
 
@@ -88,12 +89,113 @@ impl InlineShortString {
     }
 }
 
+/// Reference-counted byte buffer behind long strings.
+///
+/// A thin pointer to one allocation holding the count, the length and the
+/// bytes, so a [`LuaStrRepr::Shared`] view (buffer + start + length) still
+/// fits in the 24 bytes of the other variants. `string.sub` hands out views
+/// of its subject instead of copying, and a view keeps the buffer alive
+/// after the string it was cut from is collected.
+///
+/// The count is atomic like `Arc`'s: with `shared-proto`, constant strings
+/// of one prototype are used by VMs on different threads at once.
+pub struct SharedBytes {
+    ptr: NonNull<SharedHeader>,
+}
+
+#[repr(C)]
+struct SharedHeader {
+    refs: AtomicUsize,
+    len: usize,
+}
+
+impl SharedBytes {
+    #[inline]
+    fn layout(len: usize) -> Layout {
+        Layout::new::<SharedHeader>()
+            .extend(Layout::array::<u8>(len).expect("string too large"))
+            .expect("string too large")
+            .0
+            .pad_to_align()
+    }
+
+    pub fn new(bytes: &[u8]) -> Self {
+        let layout = Self::layout(bytes.len());
+        unsafe {
+            let raw = alloc::alloc(layout) as *mut SharedHeader;
+            let Some(ptr) = NonNull::new(raw) else {
+                alloc::handle_alloc_error(layout)
+            };
+            ptr.as_ptr().write(SharedHeader {
+                refs: AtomicUsize::new(1),
+                len: bytes.len(),
+            });
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), raw.add(1) as *mut u8, bytes.len());
+            Self { ptr }
+        }
+    }
+
+    #[inline(always)]
+    fn header(&self) -> &SharedHeader {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.header().len
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline(always)]
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr().add(1) as *const u8, self.len()) }
+    }
+
+    /// Number of strings sharing this buffer.
+    #[inline]
+    pub fn ref_count(&self) -> usize {
+        self.header().refs.load(Ordering::Acquire)
+    }
+}
+
+impl Clone for SharedBytes {
+    #[inline]
+    fn clone(&self) -> Self {
+        self.header().refs.fetch_add(1, Ordering::Relaxed);
+        Self { ptr: self.ptr }
+    }
+}
+
+impl Drop for SharedBytes {
+    fn drop(&mut self) {
+        if self.header().refs.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        // Pairs with the other owners' releases, as in `Arc::drop`
+        fence(Ordering::Acquire);
+        let layout = Self::layout(self.len());
+        unsafe { alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout) }
+    }
+}
+
 /// Immutable byte storage for Lua strings.
 pub enum LuaStrRepr {
     /// Compact inline storage for the hottest very short strings.
     Smol(InlineShortString),
-    /// Heap-backed storage for any non-inline string payload.
+    /// Heap-backed storage for the other short strings, and for long
+    /// strings too large for `Shared`.
     Heap(Box<[u8]>),
+    /// `len` bytes from `start` of a buffer that other long strings may
+    /// share.
+    Shared {
+        buf: SharedBytes,
+        start: u32,
+        len: u32,
+    },
 }
 
 impl Clone for LuaStrRepr {
@@ -101,16 +203,53 @@ impl Clone for LuaStrRepr {
         match self {
             Self::Smol(value) => Self::Smol(value.clone()),
             Self::Heap(value) => Self::Heap(value.clone()),
+            Self::Shared { buf, start, len } => Self::Shared {
+                buf: buf.clone(),
+                start: *start,
+                len: *len,
+            },
         }
     }
 }
 
 impl LuaStrRepr {
+    /// Storage for a long string: a whole shareable buffer, unless it is
+    /// too large to address with `Shared`'s 32-bit offsets.
+    pub fn long(bytes: &[u8]) -> Self {
+        if u32::try_from(bytes.len()).is_err() {
+            return Self::Heap(Box::from(bytes));
+        }
+        Self::Shared {
+            buf: SharedBytes::new(bytes),
+            start: 0,
+            len: bytes.len() as u32,
+        }
+    }
+
+    /// `range` of this string sharing its buffer, if it has one and the
+    /// range covers at least a quarter of it. A smaller view is better off
+    /// copied than pinning a buffer mostly made of bytes nothing uses.
+    pub fn share(&self, range: std::ops::Range<usize>) -> Option<Self> {
+        let Self::Shared { buf, start, len } = self else {
+            return None;
+        };
+        debug_assert!(range.start <= range.end && range.end <= *len as usize);
+        if range.len() < buf.len() / 4 {
+            return None;
+        }
+        Some(Self::Shared {
+            buf: buf.clone(),
+            start: *start + range.start as u32,
+            len: range.len() as u32,
+        })
+    }
+
     #[inline(always)]
     pub fn len(&self) -> usize {
         match self {
             Self::Smol(s) => s.len(),
             Self::Heap(s) => s.len(),
+            Self::Shared { len, .. } => *len as usize,
         }
     }
 
@@ -119,6 +258,10 @@ impl LuaStrRepr {
         match self {
             Self::Smol(s) => s.as_bytes(),
             Self::Heap(s) => s.as_ref(),
+            Self::Shared { buf, start, len } => unsafe {
+                buf.as_bytes()
+                    .get_unchecked(*start as usize..(*start + *len) as usize)
+            },
         }
     }
 }
@@ -373,6 +516,30 @@ mod tests {
         assert_eq!(binary.as_str(), None);
     }
 
+    #[test]
+    fn test_shared_repr_views_share_one_buffer() {
+        let text = "0123456789".repeat(10);
+        let whole = LuaStrRepr::long(text.as_bytes());
+        let middle = whole.share(10..60).unwrap();
+        let inner = middle.share(5..40).unwrap();
+
+        assert_eq!(middle.as_bytes(), &text.as_bytes()[10..60]);
+        assert_eq!(inner.as_bytes(), &text.as_bytes()[15..50]);
+        assert!(LuaStrRepr::Heap(Box::from(&b"x"[..])).share(0..1).is_none());
+        // Under a quarter of the buffer is copied instead
+        assert!(whole.share(0..24).is_none());
+        assert!(middle.share(0..24).is_none());
+
+        drop(whole);
+        let LuaStrRepr::Shared { buf, .. } = &inner else {
+            unreachable!()
+        };
+        assert_eq!(buf.ref_count(), 2);
+        assert_eq!(buf.as_bytes(), text.as_bytes());
+        drop(middle);
+        assert_eq!(buf.ref_count(), 1);
+    }
+
     #[test]
     fn test_lua_str_repr_equality_is_byte_based() {
         let smol = LuaStrRepr::Smol(InlineShortString::new_str("same-bytes"));
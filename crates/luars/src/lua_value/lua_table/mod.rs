@@ -28,6 +28,12 @@ impl LuaRawTable {
         }
     }
 
+    /// Bytes a table created with [`new`](Self::new)`(asize, hsize)` allocates
+    /// for its array and hash parts.
+    pub(crate) fn alloc_bytes(asize: u32, hsize: u32) -> usize {
+        NativeTable::alloc_bytes(asize, hsize)
+    }
+
     /// Invalidate cached TM flags (called when new keys are inserted).
     /// Matches Lua 5.5's `invalidateTMcache(t)`: `t->flags &= ~maskflags`
     #[inline(always)]
@@ -113,6 +119,13 @@ impl LuaRawTable {
         self.impl_table.set_int(key, value)
     }
 
+    /// Shift `t[first..=last]` one slot up or down in place; false when the
+    /// range is not entirely in the array part (see `NativeTable`).
+    #[inline]
+    pub(crate) fn shift_array_slots(&mut self, first: i64, last: i64, up: bool) -> bool {
+        self.impl_table.shift_array_slots(first, last, up)
+    }
+
     #[inline(always)]
     pub fn raw_get(&self, key: &LuaValue) -> Option<LuaValue> {
         self.impl_table.raw_get(key)
@@ -138,6 +151,20 @@ impl LuaRawTable {
         self.impl_table.next(input_key)
     }
 
+    /// See [`NativeTable::next_untracked`].
+    #[inline]
+    pub(crate) fn next_untracked(
+        &self,
+        input_key: &LuaValue,
+    ) -> Result<Option<(LuaValue, LuaValue)>, ()> {
+        self.impl_table.next_untracked(input_key)
+    }
+
+    /// See [`NativeTable::shrink_hash_if_sparse`].
+    pub(crate) fn shrink_hash_if_sparse(&mut self) -> isize {
+        self.impl_table.shrink_hash_if_sparse()
+    }
+
     pub fn remove_array_at(&mut self, i: i64) -> LuaResult<LuaValue> {
         self.impl_table
             .remove_at(i)
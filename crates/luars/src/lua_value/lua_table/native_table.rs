@@ -4,11 +4,15 @@
 use crate::gc::{GcString, StringPtr};
 use crate::lua_value::{
     LuaValue,
-    lua_value::{LUA_TNIL, LUA_VEMPTY, LUA_VNIL, LUA_VNUMINT, LUA_VSHRSTR, Value, novariant},
+    lua_value::{
+        LUA_TNIL, LUA_VEMPTY, LUA_VNIL, LUA_VNUMINT, LUA_VSHRSTR, Value, lua_float_to_int,
+        novariant,
+    },
     short_string_ptr_eq,
 };
 
 use std::alloc::{self, Layout};
+use std::cell::Cell;
 use std::ptr;
 
 /// Node for hash table - packed to 24 bytes matching Lua 5.5's Node layout.
@@ -118,6 +122,15 @@ pub struct NativeTable {
     /// Last free position in hash table (optimization like Lua 5.5)
     /// Points to next candidate for free slot search
     lastfree: *mut Node,
+    /// Set when live entries moved (a rehash, a colliding node pushed to a
+    /// free slot, hash keys migrating to the array) during a traversal. A
+    /// traversal resumed after that could skip keys, so `next` refuses its
+    /// key instead, as "invalid key to 'next'".
+    reordered: Cell<bool>,
+    /// Between a `next(nil)` and the `next` that reports the end. Moves
+    /// outside a traversal don't set `reordered`, so `next(t, k)` works
+    /// after plain insertions.
+    traversing: Cell<bool>,
 }
 
 impl NativeTable {
@@ -167,6 +180,8 @@ impl NativeTable {
             node: ptr::null_mut(),
             lsizenode: 0,
             lastfree: ptr::null_mut(),
+            reordered: Cell::new(false),
+            traversing: Cell::new(false),
         };
 
         if array_cap > 0 {
@@ -244,6 +259,17 @@ impl NativeTable {
         (sizenode * std::mem::size_of::<Node>()) as isize
     }
 
+    /// Bytes [`new`](Self::new) allocates for these capacities: the array
+    /// part as given, the hash part rounded up to a power of two.
+    pub(crate) fn alloc_bytes(array_cap: u32, hash_cap: u32) -> usize {
+        let nodes = if hash_cap == 0 {
+            0
+        } else {
+            1usize << Self::compute_lsizenode(hash_cap)
+        };
+        (Self::array_mem_bytes(array_cap) + Self::hash_mem_bytes(nodes)) as usize
+    }
+
     /// Get hash size (number of nodes)
     #[inline(always)]
     fn sizenode(&self) -> usize {
@@ -344,6 +370,55 @@ impl NativeTable {
         }
     }
 
+    /// Short string lookup guided by a cached node index (inline cache for
+    /// GETTABUP). The hint is only trusted when it is in bounds and the node
+    /// there still holds `key`, so a rehash or a node moved by a collision
+    /// falls back to the hashed lookup, which refreshes the hint.
+    /// Assumes hash is non-empty and key is short string.
+    #[inline(always)]
+    pub(crate) fn get_shortstr_hinted(
+        &self,
+        key: &LuaValue,
+        hint: &Cell<u32>,
+        dest: *mut LuaValue,
+    ) -> bool {
+        let key_ptr = key.string_ptr_raw();
+        let idx = hint.get() as usize;
+
+        unsafe {
+            let mut node = if idx < self.sizenode() {
+                self.node.add(idx)
+            } else {
+                self.mainposition_string(key)
+            };
+            if !((*node).key_tt == LUA_VSHRSTR
+                && short_string_ptr_eq((*node).key_string_ptr(), key_ptr))
+            {
+                node = self.mainposition_string(key);
+                loop {
+                    if (*node).key_tt == LUA_VSHRSTR
+                        && short_string_ptr_eq((*node).key_string_ptr(), key_ptr)
+                    {
+                        hint.set(node.offset_from(self.node) as u32);
+                        break;
+                    }
+                    let next = (*node).next;
+                    if next == 0 {
+                        return false;
+                    }
+                    node = node.offset(next as isize);
+                }
+            }
+
+            if (*node).val_tt != LUA_VNIL {
+                (*dest).tt = (*node).val_tt;
+                (*dest).value = (*node).val_data;
+                return true;
+            }
+            false
+        }
+    }
+
     /// Fast path for repeated writes to an existing integer key.
     /// Mirrors C Lua's fastset semantics: when the key already has a live slot,
     /// the update can ignore `__newindex` and complete in place.
@@ -616,6 +691,7 @@ impl NativeTable {
             let othern = self.mainposition_from_node(mp);
             if othern != mp {
                 if let Some(free_node) = self.getfreepos() {
+                    self.mark_reordered();
                     let mut prev = othern;
                     while prev.offset((*prev).next as isize) != mp {
                         prev = prev.offset((*prev).next as isize);
@@ -900,6 +976,47 @@ impl NativeTable {
         Self::hash_mem_bytes(self.sizenode()) - old_bytes + extra_delta
     }
 
+    /// Rebuild the hash part at its live size when fewer than a quarter of
+    /// its nodes hold live entries and some entries were removed. Deleting
+    /// keys never shrinks a table and a rehash only happens when an insertion
+    /// finds no free node, so without this a table that was filled and then
+    /// emptied keeps its full node array for as long as it lives. Nodes that
+    /// were never used (a presized table still being filled) do not count as
+    /// waste. Skipped while a `next()` traversal is under way, as it may
+    /// resume from one of the dead keys; a traversal left unfinished keeps
+    /// the table at its size until another one runs to the end. Called by
+    /// the GC while traversing.
+    /// Returns memory delta (new_bytes - old_bytes).
+    pub(crate) fn shrink_hash_if_sparse(&mut self) -> isize {
+        const MIN_SHRINK_SIZENODE: usize = 16;
+
+        let size = self.sizenode();
+        if size < MIN_SHRINK_SIZENODE || self.traversing.get() {
+            return 0;
+        }
+        let mut live = 0usize;
+        let mut removed = false;
+        for i in 0..size {
+            unsafe {
+                let node = self.node.add(i);
+                if novariant((*node).key_tt) != LUA_TNIL {
+                    if (*node).val_tt == LUA_VNIL {
+                        removed = true;
+                        continue;
+                    }
+                    live += 1;
+                    if live * 4 >= size {
+                        return 0;
+                    }
+                }
+            }
+        }
+        if !removed {
+            return 0;
+        }
+        self.resize_hash(Self::compute_lsizenode(live as u32))
+    }
+
     /// Port of Lua 5.5's rehash from ltable.c
     /// Computes optimal sizes for array and hash parts, then resizes.
     /// Called when hash part is full and a new key needs to be inserted.
@@ -1078,11 +1195,14 @@ impl NativeTable {
     /// Moves integer keys to array and non-integer keys to hash.
     /// Returns memory delta (new - old).
     fn resize(&mut self, new_asize: u32, new_hash_count: u32) -> isize {
+        self.mark_reordered();
         let old_asize = self.asize;
         let mut delta: isize = 0;
 
+        // A single entry still needs one node: lsize 0 would mean no hash
+        // part at all to resize_hash.
         let new_lsize = if new_hash_count > 0 {
-            Self::compute_lsizenode(new_hash_count)
+            Self::compute_lsizenode(new_hash_count).max(1)
         } else {
             0
         };
@@ -1389,15 +1509,11 @@ impl NativeTable {
         // This ensures t[3.0] and t[3] refer to the same slot
         // Only check actual float type (ttisfloat), not integers
         let mut key = *key;
-        if key.ttisfloat() {
-            let f = key.fltvalue();
-            if f.fract() == 0.0 && f.is_finite() {
-                let i = f as i64;
-                if i as f64 == f {
-                    key = LuaValue::integer(i);
-                }
-            }
-        };
+        if key.ttisfloat()
+            && let Some(i) = lua_float_to_int(key.fltvalue())
+        {
+            key = LuaValue::integer(i);
+        }
 
         // Try array part for integers
         if key.ttisinteger() {
@@ -1440,6 +1556,9 @@ impl NativeTable {
             }
         }
         // Move each key: write to array, remove from hash
+        if !to_migrate.is_empty() {
+            self.mark_reordered();
+        }
         for (k, v) in to_migrate {
             self.write_array(k, v);
             let key_val = LuaValue::integer(k);
@@ -1561,6 +1680,7 @@ impl NativeTable {
                 // Move the displaced node to a free slot and give mp to the new key.
                 // First, get a free position.
                 if let Some(free_node) = self.getfreepos() {
+                    self.mark_reordered();
                     // Find the previous node in the displaced node's own chain
                     let mut prev = othern;
                     while prev.offset((*prev).next as isize) != mp {
@@ -1670,15 +1790,11 @@ impl NativeTable {
         // This ensures t[3.0] and t[3] refer to the same slot
         // Only check actual float type (ttisfloat), not integers
         let mut key = *key;
-        if key.ttisfloat() {
-            let f = key.fltvalue();
-            if f.fract() == 0.0 && f.is_finite() {
-                let i = f as i64;
-                if i as f64 == f {
-                    key = LuaValue::integer(i);
-                }
-            }
-        };
+        if key.ttisfloat()
+            && let Some(i) = lua_float_to_int(key.fltvalue())
+        {
+            key = LuaValue::integer(i);
+        }
 
         // Check if key is an integer in array range (lua5.5's keyinarray check)
         if key.ttisinteger() {
@@ -1889,6 +2005,40 @@ impl NativeTable {
         sz
     }
 
+    /// Move `t[first..=last]` one slot up (to `first+1..=last+1`) or down
+    /// (to `first-1..=last-1`) with one memmove of the values and one of the
+    /// tags: the shifting of table.insert and table.remove. Returns false,
+    /// changing nothing, unless source and destination both lie in the
+    /// array part.
+    pub fn shift_array_slots(&mut self, first: i64, last: i64, up: bool) -> bool {
+        if first > last {
+            return true;
+        }
+        let (lo, hi) = if up {
+            (first, last + 1)
+        } else {
+            (first - 1, last)
+        };
+        if lo < 1 || hi > self.asize as i64 {
+            return false;
+        }
+        let a = (first - 1) as usize;
+        let n = (last - first + 1) as usize;
+        let b = a + n - 1;
+        // Values are laid out backwards from `array`, so t[a..=b] starts at
+        // the value slot of `b`; tags run forwards from slot `a`.
+        unsafe {
+            if up {
+                ptr::copy(self.get_arr_val(b), self.get_arr_val(b + 1), n);
+                ptr::copy(self.get_arr_tag(a), self.get_arr_tag(a + 1), n);
+            } else {
+                ptr::copy(self.get_arr_val(b), self.get_arr_val(b - 1), n);
+                ptr::copy(self.get_arr_tag(a), self.get_arr_tag(a - 1), n);
+            }
+        }
+        true
+    }
+
     /// Remove value at lua_index (1-based), shifting elements backward
     /// This is the efficient implementation for table.remove(t, pos)
     pub fn remove_at(&mut self, lua_index: i64) -> Option<LuaValue> {
@@ -1999,20 +2149,49 @@ impl NativeTable {
         }
     }
 
-    /// Port of lua5.5's luaH_next
-    /// Table iteration following the unified indexing scheme
     /// Port of lua5.5's luaH_next.
     /// Returns Ok(Some((key, value))) for next entry, Ok(None) for end of table,
-    /// or Err(()) for invalid key (key not found in table).
+    /// or Err(()) for invalid key: one not in the table, or any key once
+    /// entries were reordered since the traversal started.
     #[inline]
     pub fn next(&self, key: &LuaValue) -> Result<Option<(LuaValue, LuaValue)>, ()> {
-        let asize = self.asize;
+        if key.is_nil() {
+            self.reordered.set(false);
+            self.traversing.set(true);
+        } else if self.reordered.get() {
+            return Err(());
+        }
+        let entry = self.next_untracked(key)?;
+        if entry.is_none() {
+            self.traversing.set(false);
+        }
+        Ok(entry)
+    }
+
+    /// Record that live entries moved, if a traversal is under way.
+    #[inline(always)]
+    fn mark_reordered(&self) {
+        if self.traversing.get() {
+            self.reordered.set(true);
+        }
+    }
 
+    /// `next` for the runtime's own walks (the GC), which must not end or
+    /// restart the traversal state of Lua code iterating the same table.
+    #[inline]
+    pub(crate) fn next_untracked(
+        &self,
+        key: &LuaValue,
+    ) -> Result<Option<(LuaValue, LuaValue)>, ()> {
         // Get starting index from the input key
-        let mut i = match self.findindex(key) {
-            Some(idx) => idx,
-            None => return Err(()), // Invalid key (not found in table)
-        };
+        let i = self.findindex(key).ok_or(())?;
+        Ok(self.next_from(i))
+    }
+
+    /// First entry at or after unified index `i`.
+    #[inline]
+    fn next_from(&self, mut i: u32) -> Option<(LuaValue, LuaValue)> {
+        let asize = self.asize;
 
         // First, scan the array part [i..asize)
         while i < asize {
@@ -2027,7 +2206,7 @@ impl NativeTable {
                         value: *val_ptr,
                         tt: tag,
                     };
-                    return Ok(Some((LuaValue::integer(lua_index), value)));
+                    return Some((LuaValue::integer(lua_index), value));
                 }
             }
             i += 1;
@@ -2042,13 +2221,13 @@ impl NativeTable {
                 let node = self.node.add(i as usize);
                 if novariant((*node).key_tt) != LUA_TNIL && (*node).val_tt != LUA_VNIL {
                     // Found a non-empty hash entry (key present and value not nil)
-                    return Ok(Some(((*node).key(), (*node).value())));
+                    return Some(((*node).key(), (*node).value()));
                 }
             }
             i += 1;
         }
 
-        Ok(None) // No more elements
+        None // No more elements
     }
 
     /// GC-safe iteration: call f for each entry
@@ -2184,6 +2363,56 @@ mod tests {
         assert_eq!(t.len(), 10);
     }
 
+    #[test]
+    fn test_shift_array_slots() {
+        let mut t = NativeTable::new(8, 0);
+        for i in 1..=5 {
+            t.set_int(i, LuaValue::integer(i));
+        }
+
+        // [1, 2, 3, 4, 5] -> [1, 2, 2, 3, 4, 5]
+        assert!(t.shift_array_slots(2, 5, true));
+        assert_eq!(t.len(), 6);
+        let up: Vec<_> = (1..=6).map(|i| t.get_int(i).unwrap()).collect();
+        assert_eq!(up, [1, 2, 2, 3, 4, 5].map(LuaValue::integer));
+
+        // and back down: [1, 2, 3, 4, 5, 5]
+        assert!(t.shift_array_slots(3, 6, false));
+        let down: Vec<_> = (1..=6).map(|i| t.get_int(i).unwrap()).collect();
+        assert_eq!(down, [1, 2, 3, 4, 5, 5].map(LuaValue::integer));
+
+        // Ranges reaching outside the array part are left to the caller
+        assert!(!t.shift_array_slots(1, 8, true));
+        assert!(!t.shift_array_slots(1, 3, false));
+        assert_eq!(t.get_int(1), Some(LuaValue::integer(1)));
+    }
+
+    #[test]
+    fn test_dense_integer_keys_live_in_array_part() {
+        let mut t = NativeTable::new(0, 0);
+        for i in 1..=100_000 {
+            t.set_int(i, LuaValue::integer(i));
+        }
+        assert!(t.asize >= 100_000);
+        assert_eq!(t.hash_size(), 0);
+        assert_eq!(t.len(), 100_000);
+
+        // Keys first inserted into the hash part move to the array part on rehash
+        let mut t = NativeTable::new(0, 0);
+        for i in (1..=64).rev() {
+            t.set_int(i, LuaValue::integer(i));
+        }
+        t.set_int(65, LuaValue::integer(65));
+        for i in 66..=1024 {
+            t.set_int(i, LuaValue::integer(i));
+        }
+        assert!(t.asize >= 1024);
+        assert_eq!(t.hash_size(), 0);
+        for i in 1..=1024 {
+            assert_eq!(t.get_int(i), Some(LuaValue::integer(i)));
+        }
+    }
+
     #[test]
     fn test_hash_collisions() {
         let mut t = NativeTable::new(0, 4);
@@ -2203,6 +2432,93 @@ mod tests {
         }
     }
 
+    /// Inserts, deletes and next() calls interleaved: a traversal either
+    /// stops with an invalid key or visits every key that stayed in the
+    /// table from its start to its end.
+    #[test]
+    fn test_next_with_interleaved_inserts_and_deletes() {
+        use std::collections::HashSet;
+
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut rand = move |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        };
+        // Small keys land in the array part, the rest in the hash part
+        let key = |id: u64| {
+            if id < 64 {
+                id as i64 + 1
+            } else {
+                id as i64 * 7919
+            }
+        };
+
+        let mut t = NativeTable::new(0, 0);
+        let mut present = HashSet::new();
+        for id in 0..512 {
+            t.raw_set(&LuaValue::integer(key(id)), LuaValue::integer(1));
+            present.insert(key(id));
+        }
+
+        let (mut ops, mut completed, mut invalidated) = (0, 0, 0);
+        while ops < 100_000 {
+            let start = present.clone();
+            let mut removed = HashSet::new();
+            let mut seen = HashSet::new();
+            let mut k = LuaValue::nil();
+            let finished = loop {
+                let (next_key, _) = match t.next(&k) {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => break true,
+                    Err(()) => break false,
+                };
+                seen.insert(next_key.as_integer().unwrap());
+                k = next_key;
+                ops += 1;
+                let target = key(rand(1024));
+                match rand(4) {
+                    0 | 1 => {
+                        t.raw_set(&LuaValue::integer(target), LuaValue::integer(ops));
+                        present.insert(target);
+                    }
+                    2 => {
+                        t.raw_set(&LuaValue::integer(target), LuaValue::nil());
+                        if present.remove(&target) {
+                            removed.insert(target);
+                        }
+                    }
+                    _ => {
+                        t.raw_set(&k, LuaValue::integer(ops));
+                    }
+                }
+            };
+            if finished {
+                completed += 1;
+                for k in start.intersection(&present) {
+                    assert!(removed.contains(k) || seen.contains(k), "key {k} skipped");
+                }
+            } else {
+                invalidated += 1;
+            }
+
+            // The table itself is intact: a fresh traversal sees every key once
+            let mut all = HashSet::new();
+            let mut k = LuaValue::nil();
+            while let Some((next_key, value)) = t.next(&k).unwrap() {
+                assert!(all.insert(next_key.as_integer().unwrap()));
+                assert_eq!(t.raw_get(&next_key), Some(value));
+                k = next_key;
+            }
+            assert_eq!(all, present);
+        }
+        assert!(
+            completed > 0 && invalidated > 0,
+            "{completed} {invalidated}"
+        );
+    }
+
     #[test]
     fn test_performance_integer_keys() {
         use std::time::Instant;
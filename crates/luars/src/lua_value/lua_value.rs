@@ -634,6 +634,19 @@ impl LuaValue {
         self.raw_cfunction()
     }
 
+    /// The error raised when `self` is used as a new table key, if it is not
+    /// a valid one (the checks of luaH_newkey).
+    #[inline]
+    pub(crate) fn table_key_error(&self) -> Option<&'static str> {
+        if self.ttisnil() {
+            Some("table index is nil")
+        } else if self.ttisfloat() && self.fltvalue().is_nan() {
+            Some("table index is NaN")
+        } else {
+            None
+        }
+    }
+
     // ============ pub API ============
 
     #[inline(always)]
@@ -831,6 +844,25 @@ impl LuaValue {
         self.as_bytes()
     }
 
+    /// Order two strings by their raw bytes, like `l_strcmp` in the C
+    /// locale. Returns `None` unless both values are strings; the same
+    /// string object compares equal without reading its bytes.
+    #[inline]
+    pub fn str_cmp(&self, other: &LuaValue) -> Option<std::cmp::Ordering> {
+        if !self.ttisstring() || !other.ttisstring() {
+            return None;
+        }
+        if self.raw_ptr() == other.raw_ptr() {
+            return Some(std::cmp::Ordering::Equal);
+        }
+        Some(
+            self.gc_string()
+                .data
+                .as_bytes()
+                .cmp(other.gc_string().data.as_bytes()),
+        )
+    }
+
     #[inline(always)]
     pub fn as_table(&self) -> Option<&LuaRawTable> {
         if self.ttistable() {
@@ -1114,6 +1146,20 @@ impl LuaValue {
         self.raw_ptr()
     }
 
+    /// Address shown for this value by `tostring`, `%p` and `Debug`. GC
+    /// objects never move, so it is fixed for the object's lifetime and
+    /// distinct among live objects. `None` for nil, booleans and numbers.
+    pub fn identity(&self) -> Option<usize> {
+        match self.kind() {
+            LuaValueKind::Nil
+            | LuaValueKind::Boolean
+            | LuaValueKind::Integer
+            | LuaValueKind::Float => None,
+            LuaValueKind::CFunction => Some(self.raw_cfunction() as *const () as usize),
+            _ => Some(self.raw_ptr() as usize),
+        }
+    }
+
     /// Get hash value for this LuaValue (for native table implementation)
     #[inline(always)]
     pub fn hash_value(&self) -> u64 {
@@ -1166,22 +1212,25 @@ impl LuaValue {
     }
 }
 
+/// Port of luaV_flttointns with F2Ieq: the integer a float is exactly
+/// equal to, if any. NaN, infinities, fractions and values outside
+/// [-2^63, 2^63) have none; -0.0 converts to 0.
+#[inline(always)]
+pub(crate) fn lua_float_to_int(f: f64) -> Option<i64> {
+    // i64::MIN is exactly representable as f64, i64::MAX is not (it rounds
+    // up to 2^63), so the upper bound must be a strict comparison.
+    if f >= i64::MIN as f64 && f < -(i64::MIN as f64) && f == f.floor() {
+        Some(f as i64)
+    } else {
+        None
+    }
+}
+
 /// Check if a float value exactly equals an integer value.
 /// Returns false if the float can't precisely represent the integer.
 #[inline(always)]
 fn lua_float_eq_int(f: f64, i: i64) -> bool {
-    if !f.is_finite() {
-        return false;
-    }
-    // Check if float is integral and round-trips through i64
-    if f != f.floor() {
-        return false;
-    }
-    // Check range (i64::MIN is exactly representable as f64, i64::MAX is not)
-    if f < i64::MIN as f64 || f >= (i64::MAX as f64) + 1.0 {
-        return false;
-    }
-    (f as i64) == i
+    lua_float_to_int(f) == Some(i)
 }
 
 impl PartialEq for LuaValue {
@@ -1291,33 +1340,27 @@ fn fmt_binary_bytes(
     write!(f, "])")
 }
 
+/// Shows the kind and what can be read without a VM: the value of nils,
+/// booleans, numbers and strings, and an identity (`Table#7f3a1c2b40`) for
+/// everything else. Tables, functions, userdata and threads are identified
+/// by address only and never dereferenced, so a stale handle still formats.
 impl std::fmt::Debug for LuaValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.kind() {
-            LuaValueKind::Nil => write!(f, "nil"),
-            LuaValueKind::Boolean => write!(f, "{}", self.bvalue()),
-            LuaValueKind::Integer => write!(f, "{}", self.ivalue()),
-            LuaValueKind::Float => write!(f, "{}", self.fltvalue()),
+            LuaValueKind::Nil => write!(f, "Nil"),
+            LuaValueKind::Boolean => write!(f, "Boolean({})", self.bvalue()),
+            LuaValueKind::Integer => write!(f, "Integer({})", self.ivalue()),
+            LuaValueKind::Float => {
+                write!(f, "Float({})", super::lua_float_to_string(self.fltvalue()))
+            }
             LuaValueKind::String => {
-                if let Some(s) = self.as_str() {
-                    write!(f, "{}", s)
-                } else {
-                    fmt_binary_bytes(f, self.as_bytes().unwrap_or(&[]), "string")
+                let bytes = self.as_bytes().unwrap_or(&[]);
+                match std::str::from_utf8(bytes) {
+                    Ok(s) => write!(f, "String({:?})", s),
+                    Err(_) => write!(f, "String(b\"{}\")", bytes.escape_ascii()),
                 }
             }
-            LuaValueKind::Table => write!(f, "table(0x{:#x})", self.raw_ptr_repr() as usize),
-            LuaValueKind::Function => write!(f, "function(0x{:#x})", self.raw_ptr_repr() as usize),
-            LuaValueKind::CFunction => {
-                write!(f, "cfunction(0x{:#x})", self.raw_ptr_repr() as usize)
-            }
-            LuaValueKind::CClosure => {
-                write!(f, "cclosure(0x{:#x})", self.raw_ptr_repr() as usize)
-            }
-            LuaValueKind::RClosure => {
-                write!(f, "rclosure(0x{:#x})", self.raw_ptr_repr() as usize)
-            }
-            LuaValueKind::Userdata => write!(f, "userdata(0x{:#x})", self.raw_ptr_repr() as usize),
-            LuaValueKind::Thread => write!(f, "thread(0x{:#x})", self.raw_ptr_repr() as usize),
+            kind => write!(f, "{:?}#{:x}", kind, self.identity().unwrap_or_default()),
         }
     }
 }
@@ -1329,12 +1372,7 @@ impl std::fmt::Display for LuaValue {
             LuaValueKind::Boolean => write!(f, "{}", self.bvalue()),
             LuaValueKind::Integer => write!(f, "{}", self.ivalue()),
             LuaValueKind::Float => {
-                let n = self.fltvalue();
-                if n.floor() == n && n.abs() < 1e14 {
-                    write!(f, "{:.0}", n)
-                } else {
-                    write!(f, "{}", n)
-                }
+                write!(f, "{}", super::lua_float_to_string(self.fltvalue()))
             }
             LuaValueKind::String => {
                 if let Some(s) = self.as_str() {
@@ -1343,29 +1381,12 @@ impl std::fmt::Display for LuaValue {
                     fmt_binary_bytes(f, self.as_bytes().unwrap_or(&[]), "string")
                 }
             }
-            LuaValueKind::Table => write!(f, "table: 0x{:x}", self.raw_ptr() as usize),
-            LuaValueKind::Function => {
-                write!(f, "function: 0x{:x}", self.raw_ptr() as usize)
-            }
-            LuaValueKind::CFunction => {
-                write!(
-                    f,
-                    "function: 0x{:x}",
-                    self.raw_cfunction() as *const () as usize
-                )
-            }
-            LuaValueKind::CClosure => {
-                write!(f, "function: 0x{:x}", self.raw_ptr() as usize)
-            }
-            LuaValueKind::RClosure => {
-                write!(f, "function: 0x{:x}", self.raw_ptr() as usize)
-            }
-            LuaValueKind::Userdata => {
-                write!(f, "userdata: 0x{:x}", self.raw_ptr() as usize)
-            }
-            LuaValueKind::Thread => {
-                write!(f, "thread: 0x{:x}", self.raw_ptr() as usize)
-            }
+            _ => write!(
+                f,
+                "{}: 0x{:x}",
+                self.type_name(),
+                self.identity().unwrap_or_default()
+            ),
         }
     }
 }
@@ -1392,18 +1413,19 @@ impl std::hash::Hash for LuaValue {
         }
 
         // Special handling for numbers to maintain equality invariant
-        // (integer 1 == float 1.0, so they must hash the same)
+        // (integer 1 == float 1.0 and 0 == -0.0, so they must hash the same):
+        // floats with an exact integer value hash as that integer.
         if tt == LUA_VNUMINT || tt == LUA_VNUMFLT {
-            // Always hash numbers as floats to maintain hash consistency
-            // when integer equals float
-            let n = if tt == LUA_VNUMINT {
-                self.raw_i64() as f64
-            } else {
-                self.raw_f64()
-            };
-            // Use a stable representation for hashing
             LUA_TNUMBER.hash(state);
-            n.to_bits().hash(state);
+            if tt == LUA_VNUMINT {
+                self.raw_i64().hash(state);
+            } else {
+                let f = self.raw_f64();
+                match lua_float_to_int(f) {
+                    Some(i) => i.hash(state),
+                    None => f.to_bits().hash(state),
+                }
+            }
         } else if tt <= LUA_VFALSE {
             // nil or boolean - hash type tag only
             tt.hash(state);
@@ -1531,4 +1553,27 @@ mod tests {
         assert!(!nil.iscollectable());
         assert!(!int.iscollectable());
     }
+
+    #[test]
+    fn test_equal_numbers_hash_equal() {
+        use std::hash::{BuildHasher, RandomState};
+        let state = RandomState::new();
+        let pairs = [
+            (LuaValue::integer(2), LuaValue::float(2.0)),
+            (LuaValue::integer(0), LuaValue::float(-0.0)),
+            (
+                LuaValue::integer(i64::MIN),
+                LuaValue::float(i64::MIN as f64),
+            ),
+        ];
+        for (i, f) in pairs {
+            assert_eq!(i, f);
+            assert_eq!(state.hash_one(i), state.hash_one(f));
+        }
+        // 2^63 is one past i64::MAX: not equal, and no saturating conversion
+        assert_ne!(LuaValue::integer(i64::MAX), LuaValue::float(2f64.powi(63)));
+        assert_eq!(lua_float_to_int(2f64.powi(63)), None);
+        assert_eq!(lua_float_to_int(f64::NAN), None);
+        assert_eq!(lua_float_to_int(-0.0), Some(0));
+    }
 }
@@ -2,19 +2,29 @@
 // 16 bytes, no pointer caching, all GC objects accessed via ID
 pub mod alive_ref;
 pub mod chunk_serializer;
+mod chunk_verifier;
 pub mod lua_convert;
 mod lua_string;
 mod lua_table;
 #[allow(clippy::module_inception)]
 mod lua_value;
+mod multi_value;
+mod number_format;
+mod shared_userdata;
 mod userdata;
 pub mod userdata_builder;
 pub mod userdata_trait;
 
 use self::lua_value::Value;
+use std::cell::Cell;
 use std::sync::Arc;
 
+pub(crate) use chunk_verifier::{verify_proto, verify_proto_tree};
 pub use lua_string::*;
+pub use multi_value::MultiValue;
+pub(crate) use number_format::format_g;
+pub use number_format::lua_float_to_string;
+pub use shared_userdata::{BorrowConflict, SharedRef, SharedRefMut, SharedUserData};
 pub use userdata::LuaUserdata;
 pub use userdata_builder::UserDataBuilder;
 pub use userdata_trait::{
@@ -23,6 +33,7 @@ pub use userdata_trait::{
 
 // Re-export the optimized LuaValue and type enum for pattern matching
 pub use lua_table::LuaRawTable;
+pub(crate) use lua_value::lua_float_to_int;
 pub use lua_value::{BIT_ISCOLLECTABLE, LUA_VFALSE, LUA_VNIL, LUA_VNUMFLT, LUA_VNUMINT, LUA_VTRUE};
 pub use lua_value::{LuaValue, LuaValueKind};
 
@@ -181,10 +192,18 @@ pub struct LuaProto {
     pub child_protos: Vec<ProtoPtr>,     // Nested function prototypes
     pub upvalue_descs: Vec<UpvalueDesc>, // Upvalue descriptors
     pub source_name: Option<Arc<str>>,   // Source file/chunk name for debugging
+    pub name: Option<Arc<str>>,          // Name from `function a.b:c()`, for tracebacks
     pub line_info: Vec<u32>,             // Line number for each instruction (for debug)
     pub linedefined: usize,              // Line where function starts (0 for main)
     pub lastlinedefined: usize,          // Line where function ends (0 for main)
     pub proto_data_size: u32,            // Cached size for GC (code+constants+children+lines)
+    /// Inline cache for GETTABUP: last hash node index where each constant
+    /// key was found (indexed like `constants`). Only a hint, always revalidated.
+    pub key_hints: Vec<Cell<u32>>,
+    /// Set when the proto is allocated and never reused, unlike its
+    /// address; the profiler keys its statistics by it.
+    #[cfg(feature = "profiler")]
+    pub(crate) profile_id: u64,
 }
 
 impl Default for LuaProto {
@@ -208,13 +227,23 @@ impl LuaProto {
             child_protos: Vec::new(),
             upvalue_descs: Vec::new(),
             source_name: None,
+            name: None,
             line_info: Vec::new(),
             linedefined: 0,
             lastlinedefined: 0,
             proto_data_size: 0,
+            key_hints: Vec::new(),
+            #[cfg(feature = "profiler")]
+            profile_id: 0,
         }
     }
 
+    /// Allocate one GETTABUP key hint per constant. Call once the constants
+    /// are final, before `compute_proto_data_size`.
+    pub fn init_key_hints(&mut self) {
+        self.key_hints = vec![Cell::new(0); self.constants.len()];
+    }
+
     /// Compute and cache proto_data_size. Call once after compilation is complete.
     pub fn compute_proto_data_size(&mut self) {
         use std::mem::size_of;
@@ -222,7 +251,9 @@ impl LuaProto {
         let const_size = self.constants.len() * size_of::<LuaValue>();
         let child_size = self.child_protos.len() * size_of::<ProtoPtr>();
         let line_size = self.line_info.len() * size_of::<u32>();
-        self.proto_data_size = (instr_size + const_size + child_size + line_size) as u32;
+        let hint_size = self.key_hints.len() * size_of::<Cell<u32>>();
+        self.proto_data_size =
+            (instr_size + const_size + child_size + line_size + hint_size) as u32;
     }
 
     #[cfg(feature = "shared-proto")]
@@ -0,0 +1,191 @@
+//! `MultiValue` — an ordered list of Lua values, as passed to and returned
+//! from a call.
+//!
+//! Unlike a Lua table, a `MultiValue` keeps its length explicitly: nils are
+//! values like any other, so `(1, nil, "x")` stays three values and trailing
+//! nils are never dropped. [`into_table`](MultiValue::into_table) and
+//! [`from_table`](MultiValue::from_table) mirror `table.pack` and
+//! `table.unpack`, including the `n` field that records the count.
+
+use std::ops::{Index, IndexMut};
+
+use crate::lua_value::LuaValue;
+use crate::lua_value::lua_convert::{FromLuaMulti, IntoLua, collect_into_lua_values};
+use crate::lua_vm::{LuaResult, LuaState};
+
+/// Values of a multi-value call argument or result list.
+///
+/// Implements [`IntoLua`] and [`FromLuaMulti`], so it can be passed as the
+/// arguments of any typed call helper and requested as its result type to
+/// receive every returned value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MultiValue(Vec<LuaValue>);
+
+impl MultiValue {
+    /// An empty list.
+    pub fn new() -> Self {
+        MultiValue(Vec::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        MultiValue(Vec::with_capacity(capacity))
+    }
+
+    /// Convert Rust values (a tuple, a `Vec`, a single value, ...) into Lua
+    /// values. `None` becomes nil and keeps its position.
+    pub fn pack<T: IntoLua>(state: &mut LuaState, values: T) -> LuaResult<Self> {
+        collect_into_lua_values(state, values)
+            .map(MultiValue)
+            .map_err(|msg| state.error(msg))
+    }
+
+    /// Convert the values into a Rust type, typically a tuple. Missing
+    /// positions read as nil; a failed position is reported as a result
+    /// (`result #2: ...`).
+    pub fn unpack<R: FromLuaMulti>(self, state: &mut LuaState) -> LuaResult<R> {
+        R::from_lua_multi(self.0, state).map_err(|msg| state.error(msg))
+    }
+
+    /// Like [`unpack`](MultiValue::unpack), for values received as the
+    /// arguments of a call: a failed position is reported as an argument
+    /// (`argument #2: ...`).
+    pub fn unpack_args<R: FromLuaMulti>(self, state: &mut LuaState) -> LuaResult<R> {
+        R::from_lua_multi_labeled(self.0, state, "argument").map_err(|msg| state.error(msg))
+    }
+
+    /// Like `table.pack`: a new table holding the values at `1..=n`, with the
+    /// count in field `n` so trailing nils are not lost.
+    pub fn into_table(self, state: &mut LuaState) -> LuaResult<LuaValue> {
+        let n = self.0.len();
+        let table = state.create_table(n, 1)?;
+        for (i, value) in self.0.into_iter().enumerate() {
+            state.raw_seti(&table, i as i64 + 1, value);
+        }
+        let n_key = state.create_string("n")?;
+        state.raw_set(&table, n_key, LuaValue::integer(n as i64));
+        Ok(table)
+    }
+
+    /// Like `table.unpack(table, i, j)`: the values at `i..=j`, nil for
+    /// absent entries. Reads go through `__index` when the table has a
+    /// metatable.
+    pub fn from_table(state: &mut LuaState, table: &LuaValue, i: i64, j: i64) -> LuaResult<Self> {
+        let Some(t) = table.as_table() else {
+            return Err(state.error(format!("table expected, got {}", table.type_name())));
+        };
+        if i > j {
+            return Ok(MultiValue::new());
+        }
+        let n = (j as u64).wrapping_sub(i as u64);
+        if n >= i32::MAX as u64 {
+            return Err(state.error("too many results to unpack".to_string()));
+        }
+        let mut values = Vec::with_capacity(n as usize + 1);
+        if t.has_metatable() {
+            for idx in i..=j {
+                values.push(state.table_geti(table, idx)?);
+            }
+        } else {
+            values.extend((i..=j).map(|idx| t.raw_geti(idx).unwrap_or_default()));
+        }
+        Ok(MultiValue(values))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The value at 0-based `index`, or `None` past the end. A nil inside the
+    /// list is `Some(nil)`.
+    pub fn get(&self, index: usize) -> Option<LuaValue> {
+        self.0.get(index).copied()
+    }
+
+    pub fn push(&mut self, value: LuaValue) {
+        self.0.push(value);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, LuaValue> {
+        self.0.iter()
+    }
+
+    pub fn as_slice(&self) -> &[LuaValue] {
+        &self.0
+    }
+
+    pub fn into_vec(self) -> Vec<LuaValue> {
+        self.0
+    }
+}
+
+impl From<Vec<LuaValue>> for MultiValue {
+    fn from(values: Vec<LuaValue>) -> Self {
+        MultiValue(values)
+    }
+}
+
+impl From<MultiValue> for Vec<LuaValue> {
+    fn from(values: MultiValue) -> Self {
+        values.0
+    }
+}
+
+impl FromIterator<LuaValue> for MultiValue {
+    fn from_iter<I: IntoIterator<Item = LuaValue>>(iter: I) -> Self {
+        MultiValue(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for MultiValue {
+    type Item = LuaValue;
+    type IntoIter = std::vec::IntoIter<LuaValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MultiValue {
+    type Item = &'a LuaValue;
+    type IntoIter = std::slice::Iter<'a, LuaValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Index<usize> for MultiValue {
+    type Output = LuaValue;
+
+    fn index(&self, index: usize) -> &LuaValue {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for MultiValue {
+    fn index_mut(&mut self, index: usize) -> &mut LuaValue {
+        &mut self.0[index]
+    }
+}
+
+impl IntoLua for MultiValue {
+    #[inline]
+    fn into_lua(self, state: &mut LuaState) -> Result<usize, String> {
+        let count = self.0.len();
+        for value in self.0 {
+            state.push_value(value).map_err(|e| format!("{:?}", e))?;
+        }
+        Ok(count)
+    }
+}
+
+impl FromLuaMulti for MultiValue {
+    #[inline]
+    fn from_lua_multi(values: Vec<LuaValue>, _state: &mut LuaState) -> Result<Self, String> {
+        Ok(MultiValue(values))
+    }
+}
@@ -0,0 +1,69 @@
+// Float to string conversion shared by tostring, print, concatenation,
+// string.format and every host-facing rendering of Lua numbers.
+
+/// Format a float value matching Lua 5.5's tostringbuffFloat behavior:
+/// First try %.15g (max digits preserving tostring(tonumber(x)) == x),
+/// then %.17g if roundtrip fails, and append ".0" if result looks integer-like.
+pub fn lua_float_to_string(n: f64) -> String {
+    if n.is_nan() {
+        // printf spells NaN with its sign bit ("-nan" for 0/0 on x86)
+        return if n.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+
+    // First try: format with roughly 15 significant digits (%.15g equivalent)
+    let s = format_g(n, 15);
+
+    // Check if it roundtrips
+    let mut result = if s.parse::<f64>().ok() == Some(n) {
+        s
+    } else {
+        // Second try: format with 17 significant digits (%.17g equivalent)
+        format_g(n, 17)
+    };
+
+    // If result looks like an integer (no '.', 'e', 'E', 'n', 'i'), add ".0"
+    if !result.contains('.')
+        && !result.contains('e')
+        && !result.contains('E')
+        && !result.contains('n')
+        && !result.contains('i')
+    {
+        result.push_str(".0");
+    }
+
+    result
+}
+
+/// Format a finite float with %.<prec>g semantics (C-style %g formatting)
+pub(crate) fn format_g(n: f64, prec: usize) -> String {
+    if n == 0.0 {
+        return if n.is_sign_negative() { "-0" } else { "0" }.to_string();
+    }
+
+    // %g picks the style from the exponent *after* rounding to `prec` digits
+    let sci = format!("{:.prec$e}", n, prec = prec - 1);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+
+    if exp >= -4 && exp < prec as i32 {
+        // Fixed-point notation: precision = prec - (exp + 1) decimal places
+        let decimal_places = (prec as i32 - exp - 1).max(0) as usize;
+        strip_decimal_zeros(&format!("{:.prec$}", n, prec = decimal_places))
+    } else {
+        // Scientific notation with a signed, at least two-digit exponent
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", strip_decimal_zeros(mantissa), sign, exp.abs())
+    }
+}
+
+/// Strip trailing zeros after decimal point, remove point if no digits follow
+fn strip_decimal_zeros(s: &str) -> String {
+    if !s.contains('.') {
+        return s.to_string();
+    }
+    let trimmed = s.trim_end_matches('0');
+    trimmed.strip_suffix('.').unwrap_or(trimmed).to_string()
+}
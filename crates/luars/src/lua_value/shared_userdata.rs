@@ -0,0 +1,436 @@
+//! Shared userdata — Rust state that several Lua values alias.
+//!
+//! [`LuaUserdata::new`](crate::LuaUserdata::new) moves its value into Lua,
+//! so pushing "the same" object twice produces two independent copies.
+//! [`SharedUserData`] instead wraps a handle the host keeps a clone of:
+//!
+//! | Handle | Constructor | Conflict check |
+//! |---|---|---|
+//! | `Rc<RefCell<T>>` | [`LuaUserdata::new_shared`](crate::LuaUserdata::new_shared) | `RefCell` borrow flag |
+//! | `Arc<Mutex<T>>` | [`LuaUserdata::new_shared_mutex`](crate::LuaUserdata::new_shared_mutex) (`unsafe-send`) | `Mutex::try_lock` |
+//!
+//! Every field access, metamethod and `#[lua_methods]` call borrows through
+//! the handle for its duration. A borrow that conflicts with one held
+//! elsewhere — typically a `RefMut` the host keeps across a call into Lua —
+//! raises "value is currently borrowed" instead of aliasing the value.
+//!
+//! Two shared userdata over the same handle compare equal with `==`.
+//!
+//! Sub-references returned by field or method access point into the shared
+//! value and keep the handle's borrow flag: each access through them checks
+//! it the same way, so they too raise "value is currently borrowed" while
+//! the host holds a conflicting borrow.
+
+use std::any::Any;
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+#[cfg(feature = "unsafe-send")]
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+
+use crate::lua_value::userdata_trait::{UdValue, UserDataTrait};
+use crate::lua_vm::CFunction;
+
+/// The error a conflicting borrow of a [`SharedUserData`] reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowConflict;
+
+impl fmt::Display for BorrowConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("value is currently borrowed")
+    }
+}
+
+impl std::error::Error for BorrowConflict {}
+
+/// Borrow flag of a shared handle, seen without knowing `T`.
+pub(crate) trait BorrowState {
+    fn can_borrow(&self) -> bool;
+    fn can_borrow_mut(&self) -> bool;
+}
+
+impl<T> BorrowState for RefCell<T> {
+    fn can_borrow(&self) -> bool {
+        self.try_borrow().is_ok()
+    }
+
+    fn can_borrow_mut(&self) -> bool {
+        self.try_borrow_mut().is_ok()
+    }
+}
+
+#[cfg(feature = "unsafe-send")]
+impl<T> BorrowState for Mutex<T> {
+    fn can_borrow(&self) -> bool {
+        try_lock(self).is_ok()
+    }
+
+    fn can_borrow_mut(&self) -> bool {
+        try_lock(self).is_ok()
+    }
+}
+
+/// The borrow flag a sub-reference into a shared value checks on every
+/// access. Holding it also keeps the value itself alive.
+#[derive(Clone)]
+pub(crate) enum SharedBorrow {
+    Local(Rc<dyn BorrowState>),
+    #[cfg(feature = "unsafe-send")]
+    Mutex(Arc<dyn BorrowState>),
+}
+
+impl SharedBorrow {
+    fn state(&self) -> &dyn BorrowState {
+        match self {
+            SharedBorrow::Local(state) => state.as_ref(),
+            #[cfg(feature = "unsafe-send")]
+            SharedBorrow::Mutex(state) => state.as_ref(),
+        }
+    }
+
+    /// Fail if the value is exclusively borrowed elsewhere.
+    pub(crate) fn check(&self) -> Result<(), BorrowConflict> {
+        if self.state().can_borrow() {
+            Ok(())
+        } else {
+            Err(BorrowConflict)
+        }
+    }
+
+    /// Fail if the value is borrowed elsewhere at all.
+    pub(crate) fn check_mut(&self) -> Result<(), BorrowConflict> {
+        if self.state().can_borrow_mut() {
+            Ok(())
+        } else {
+            Err(BorrowConflict)
+        }
+    }
+}
+
+enum SharedHandle<T> {
+    Local(Rc<RefCell<T>>),
+    #[cfg(feature = "unsafe-send")]
+    Mutex(Arc<Mutex<T>>),
+}
+
+/// Userdata payload that aliases a host-held `Rc<RefCell<T>>` (or
+/// `Arc<Mutex<T>>` with the `unsafe-send` feature).
+///
+/// Created by [`LuaUserdata::new_shared`](crate::LuaUserdata::new_shared);
+/// `#[lua_methods]` wrappers find their `self` through it.
+pub struct SharedUserData<T: UserDataTrait> {
+    handle: SharedHandle<T>,
+}
+
+/// A shared borrow of the value behind a [`SharedUserData`].
+pub enum SharedRef<'a, T> {
+    Local(Ref<'a, T>),
+    #[cfg(feature = "unsafe-send")]
+    Mutex(MutexGuard<'a, T>),
+}
+
+/// An exclusive borrow of the value behind a [`SharedUserData`].
+pub enum SharedRefMut<'a, T> {
+    Local(RefMut<'a, T>),
+    #[cfg(feature = "unsafe-send")]
+    Mutex(MutexGuard<'a, T>),
+}
+
+impl<T> Deref for SharedRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            SharedRef::Local(r) => r,
+            #[cfg(feature = "unsafe-send")]
+            SharedRef::Mutex(g) => g,
+        }
+    }
+}
+
+impl<T> Deref for SharedRefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            SharedRefMut::Local(r) => r,
+            #[cfg(feature = "unsafe-send")]
+            SharedRefMut::Mutex(g) => g,
+        }
+    }
+}
+
+impl<T> DerefMut for SharedRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        match self {
+            SharedRefMut::Local(r) => r,
+            #[cfg(feature = "unsafe-send")]
+            SharedRefMut::Mutex(g) => g,
+        }
+    }
+}
+
+/// A poisoned mutex still holds a usable value; Lua keeps working with it.
+#[cfg(feature = "unsafe-send")]
+fn try_lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, BorrowConflict> {
+    match mutex.try_lock() {
+        Ok(guard) => Ok(guard),
+        Err(TryLockError::Poisoned(poisoned)) => Ok(poisoned.into_inner()),
+        Err(TryLockError::WouldBlock) => Err(BorrowConflict),
+    }
+}
+
+impl<T: UserDataTrait> SharedUserData<T> {
+    /// Wrap a single-threaded shared handle.
+    pub fn new(handle: Rc<RefCell<T>>) -> Self {
+        SharedUserData {
+            handle: SharedHandle::Local(handle),
+        }
+    }
+
+    /// Wrap a thread-safe shared handle.
+    #[cfg(feature = "unsafe-send")]
+    pub fn new_mutex(handle: Arc<Mutex<T>>) -> Self
+    where
+        T: Send,
+    {
+        SharedUserData {
+            handle: SharedHandle::Mutex(handle),
+        }
+    }
+
+    /// Borrow the value, failing if it is exclusively borrowed elsewhere.
+    pub fn try_borrow(&self) -> Result<SharedRef<'_, T>, BorrowConflict> {
+        match &self.handle {
+            SharedHandle::Local(cell) => cell
+                .try_borrow()
+                .map(SharedRef::Local)
+                .map_err(|_| BorrowConflict),
+            #[cfg(feature = "unsafe-send")]
+            SharedHandle::Mutex(mutex) => try_lock(mutex).map(SharedRef::Mutex),
+        }
+    }
+
+    /// Borrow the value exclusively, failing if it is borrowed elsewhere.
+    pub fn try_borrow_mut(&self) -> Result<SharedRefMut<'_, T>, BorrowConflict> {
+        match &self.handle {
+            SharedHandle::Local(cell) => cell
+                .try_borrow_mut()
+                .map(SharedRefMut::Local)
+                .map_err(|_| BorrowConflict),
+            #[cfg(feature = "unsafe-send")]
+            SharedHandle::Mutex(mutex) => try_lock(mutex).map(SharedRefMut::Mutex),
+        }
+    }
+
+    /// Whether both wrap the same handle.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.handle, &other.handle) {
+            (SharedHandle::Local(a), SharedHandle::Local(b)) => Rc::ptr_eq(a, b),
+            #[cfg(feature = "unsafe-send")]
+            (SharedHandle::Mutex(a), SharedHandle::Mutex(b)) => Arc::ptr_eq(a, b),
+            #[cfg(feature = "unsafe-send")]
+            _ => false,
+        }
+    }
+
+    /// Run `f` on the other operand of a comparison, unwrapping it first if
+    /// it shares a `T` too.
+    fn with_operand<R>(
+        other: &dyn UserDataTrait,
+        f: impl FnOnce(&dyn UserDataTrait) -> Option<R>,
+    ) -> Option<R> {
+        match other.as_any().downcast_ref::<Self>() {
+            Some(shared) => f(&*shared.try_borrow().ok()?),
+            None => f(other),
+        }
+    }
+}
+
+/// Type-erased view of a [`SharedUserData`], kept by
+/// [`UserdataStorage::Shared`](crate::lua_value::userdata::UserdataStorage).
+pub(crate) trait SharedAccess: UserDataTrait {
+    /// Whether Lua may read the value now, i.e. no exclusive borrow is held.
+    fn is_accessible(&self) -> bool;
+
+    /// `TypeId` of the shared `T`, so the value picks up what was
+    /// registered for `T`.
+    fn value_type_id(&self) -> std::any::TypeId;
+
+    /// The handle's borrow flag, for sub-references into the value.
+    fn borrow_state(&self) -> SharedBorrow;
+}
+
+impl<T: UserDataTrait> SharedAccess for SharedUserData<T> {
+    fn is_accessible(&self) -> bool {
+        self.try_borrow().is_ok()
+    }
+
+    fn value_type_id(&self) -> std::any::TypeId {
+        std::any::TypeId::of::<T>()
+    }
+
+    fn borrow_state(&self) -> SharedBorrow {
+        match &self.handle {
+            SharedHandle::Local(cell) => SharedBorrow::Local(cell.clone()),
+            #[cfg(feature = "unsafe-send")]
+            SharedHandle::Mutex(mutex) => SharedBorrow::Mutex(mutex.clone()),
+        }
+    }
+}
+
+impl<T: UserDataTrait> UserDataTrait for SharedUserData<T> {
+    fn type_name(&self) -> &'static str {
+        match self.try_borrow() {
+            Ok(value) => value.type_name(),
+            Err(_) => std::any::type_name::<T>(),
+        }
+    }
+
+    /// The handle itself; the shared value is counted by whoever owns it.
+    fn size_hint(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
+
+    fn get_field(&self, key: &str) -> Option<UdValue> {
+        self.try_borrow().ok()?.get_field(key)
+    }
+
+    fn set_field(&mut self, key: &str, value: UdValue) -> Option<Result<(), String>> {
+        match self.try_borrow_mut() {
+            Ok(mut target) => target.set_field(key, value),
+            Err(e) => Some(Err(e.to_string())),
+        }
+    }
+
+    fn get_index(&self, idx: i64) -> Option<UdValue> {
+        self.try_borrow().ok()?.get_index(idx)
+    }
+
+    fn set_index(&mut self, idx: i64, value: UdValue) -> Option<Result<(), String>> {
+        match self.try_borrow_mut() {
+            Ok(mut target) => target.set_index(idx, value),
+            Err(e) => Some(Err(e.to_string())),
+        }
+    }
+
+    fn lua_tostring(&self) -> Option<String> {
+        self.try_borrow().ok()?.lua_tostring()
+    }
+
+    /// Identity of the handle, not of the values behind it.
+    fn lua_eq(&self, other: &dyn UserDataTrait) -> Option<bool> {
+        other
+            .as_any()
+            .downcast_ref::<Self>()
+            .map(|other| self.ptr_eq(other))
+    }
+
+    fn lua_lt(&self, other: &dyn UserDataTrait) -> Option<bool> {
+        let value = self.try_borrow().ok()?;
+        Self::with_operand(other, |other| value.lua_lt(other))
+    }
+
+    fn lua_le(&self, other: &dyn UserDataTrait) -> Option<bool> {
+        let value = self.try_borrow().ok()?;
+        Self::with_operand(other, |other| value.lua_le(other))
+    }
+
+    fn lua_len(&self) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_len()
+    }
+
+    fn lua_unm(&self) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_unm()
+    }
+
+    fn lua_bnot(&self) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_bnot()
+    }
+
+    fn lua_add(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_add(other)
+    }
+
+    fn lua_sub(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_sub(other)
+    }
+
+    fn lua_mul(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_mul(other)
+    }
+
+    fn lua_div(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_div(other)
+    }
+
+    fn lua_mod(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_mod(other)
+    }
+
+    fn lua_pow(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_pow(other)
+    }
+
+    fn lua_idiv(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_idiv(other)
+    }
+
+    fn lua_band(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_band(other)
+    }
+
+    fn lua_bor(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_bor(other)
+    }
+
+    fn lua_bxor(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_bxor(other)
+    }
+
+    fn lua_shl(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_shl(other)
+    }
+
+    fn lua_shr(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_shr(other)
+    }
+
+    fn lua_concat(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_concat(other)
+    }
+
+    fn lua_concat_rhs(&self, other: &UdValue) -> Option<UdValue> {
+        self.try_borrow().ok()?.lua_concat_rhs(other)
+    }
+
+    fn lua_close(&mut self) {
+        if let Ok(mut value) = self.try_borrow_mut() {
+            value.lua_close();
+        }
+    }
+
+    fn lua_call(&self) -> Option<CFunction> {
+        self.try_borrow().ok()?.lua_call()
+    }
+
+    fn lua_next(&self, control: &UdValue) -> Option<(UdValue, UdValue)> {
+        self.try_borrow().ok()?.lua_next(control)
+    }
+
+    fn field_names(&self) -> &'static [&'static str] {
+        match self.try_borrow() {
+            Ok(value) => value.field_names(),
+            Err(_) => &[],
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -8,6 +8,7 @@
 //! |---|---|---|
 //! | [`Owned`](UserdataStorage::Owned) | GC owns the data | Token flips on drop |
 //! | [`Borrowed`](UserdataStorage::Borrowed) | External / sub-reference | Token shared with parent |
+//! | [`Shared`](UserdataStorage::Shared) | GC owns a handle, host keeps a clone | Token flips on drop |
 //!
 //! # Sub-references
 //!
@@ -17,7 +18,9 @@
 //!
 //! All accessor methods ([`get_trait`], [`get_trait_mut`], etc.) check
 //! `is_alive()` before dereferencing. If the backing data has expired,
-//! they return [`LuaError::ExpiredReference`].
+//! they return [`LuaError::ExpiredReference`]. Shared userdata whose value
+//! is exclusively borrowed elsewhere, and sub-references into it, return
+//! [`LuaError::BorrowConflict`].
 //!
 //! # Example
 //!
@@ -32,8 +35,16 @@
 //! }
 //! ```
 
-use std::{any::Any, fmt};
+use std::cell::RefCell;
+use std::rc::Rc;
+#[cfg(feature = "unsafe-send")]
+use std::sync::{Arc, Mutex};
+use std::{
+    any::{Any, TypeId},
+    fmt,
+};
 
+use crate::lua_value::shared_userdata::{SharedAccess, SharedUserData};
 use crate::lua_vm::lua_error::LuaError;
 use crate::{LuaValue, RefAliveToken, UserDataTrait, gc::TablePtr};
 
@@ -44,6 +55,9 @@ pub enum UserdataStorage {
     /// Borrowed: a raw pointer to data with an external lifetime.
     /// Validity is tracked via the [`RefAliveToken`] in [`LuaUserdata`].
     Borrowed(*mut dyn UserDataTrait),
+    /// Shared: the data lives behind a handle the host also holds, see
+    /// [`SharedUserData`]. Dropping the userdata releases only the handle.
+    Shared(Box<dyn SharedAccess>),
 }
 
 /// GC-managed userdata — the bridge between Rust types and Lua values.
@@ -70,6 +84,26 @@ impl LuaUserdata {
         }
     }
 
+    /// Create a **shared** userdata over `handle`. Each call makes a distinct
+    /// Lua value; all of them, and the host, see the same `T`.
+    pub fn new_shared<T: UserDataTrait>(handle: Rc<RefCell<T>>) -> Self {
+        LuaUserdata {
+            data: UserdataStorage::Shared(Box::new(SharedUserData::new(handle))),
+            metatable: TablePtr::null(),
+            alive_token: RefAliveToken::default(),
+        }
+    }
+
+    /// [`new_shared`](Self::new_shared) for a thread-safe handle.
+    #[cfg(feature = "unsafe-send")]
+    pub fn new_shared_mutex<T: UserDataTrait + Send>(handle: Arc<Mutex<T>>) -> Self {
+        LuaUserdata {
+            data: UserdataStorage::Shared(Box::new(SharedUserData::new_mutex(handle))),
+            metatable: TablePtr::null(),
+            alive_token: RefAliveToken::default(),
+        }
+    }
+
     /// Create an owned userdata from an already-boxed trait object.
     pub fn from_boxed(data: Box<dyn UserDataTrait>) -> Self {
         LuaUserdata {
@@ -121,21 +155,28 @@ impl LuaUserdata {
     #[inline]
     pub fn is_alive(&self) -> bool {
         match &self.data {
-            UserdataStorage::Owned(_) => true,
+            UserdataStorage::Owned(_) | UserdataStorage::Shared(_) => true,
             UserdataStorage::Borrowed(_) => self.alive_token.is_alive(),
         }
     }
 
-    /// Clone the liveness token (for creating sub-references).
+    /// Clone the liveness token (for creating sub-references). Tokens of a
+    /// shared userdata carry its borrow flag.
     #[inline]
     pub fn sub_guard_token(&self) -> RefAliveToken {
-        self.alive_token.clone()
+        match &self.data {
+            UserdataStorage::Shared(shared) => {
+                self.alive_token.clone().with_shared(shared.borrow_state())
+            }
+            _ => self.alive_token.clone(),
+        }
     }
 
     // ==================== Trait-based access ====================
 
     /// Get the trait object. Returns `Err(ExpiredReference)` if this is a
-    /// borrowed userdata whose token has expired.
+    /// borrowed userdata whose token has expired, and `Err(BorrowConflict)`
+    /// if this is a shared userdata whose value is exclusively borrowed.
     #[inline]
     pub fn get_trait(&self) -> Result<&dyn UserDataTrait, LuaError> {
         if !self.is_alive() {
@@ -143,12 +184,21 @@ impl LuaUserdata {
         }
         Ok(match &self.data {
             UserdataStorage::Owned(boxed) => boxed.as_ref(),
-            UserdataStorage::Borrowed(ptr) => unsafe { &**ptr },
+            UserdataStorage::Borrowed(ptr) => {
+                self.alive_token.check_borrow()?;
+                unsafe { &**ptr }
+            }
+            UserdataStorage::Shared(shared) => {
+                if !shared.is_accessible() {
+                    return Err(LuaError::BorrowConflict);
+                }
+                shared.as_ref()
+            }
         })
     }
 
-    /// Get the mutable trait object. Returns `Err(ExpiredReference)` if this
-    /// is a borrowed userdata whose token has expired.
+    /// Get the mutable trait object. Same errors as
+    /// [`get_trait`](Self::get_trait).
     #[inline]
     pub fn get_trait_mut(&mut self) -> Result<&mut dyn UserDataTrait, LuaError> {
         if !self.is_alive() {
@@ -156,10 +206,28 @@ impl LuaUserdata {
         }
         Ok(match &mut self.data {
             UserdataStorage::Owned(boxed) => boxed.as_mut(),
-            UserdataStorage::Borrowed(ptr) => unsafe { &mut **ptr },
+            UserdataStorage::Borrowed(ptr) => {
+                self.alive_token.check_borrow_mut()?;
+                unsafe { &mut **ptr }
+            }
+            UserdataStorage::Shared(shared) => {
+                if !shared.is_accessible() {
+                    return Err(LuaError::BorrowConflict);
+                }
+                shared.as_mut()
+            }
         })
     }
 
+    /// The type registrations apply to: the shared `T` for shared
+    /// userdata, otherwise the concrete type behind [`as_any`](UserDataTrait::as_any).
+    pub(crate) fn registered_type_id(&self) -> Option<TypeId> {
+        match &self.data {
+            UserdataStorage::Shared(shared) => Some(shared.value_type_id()),
+            _ => Some(self.get_trait().ok()?.as_any().type_id()),
+        }
+    }
+
     /// Get the type name. Returns `"expired_userdata"` if expired.
     #[inline]
     pub fn type_name(&self) -> &'static str {
@@ -193,6 +261,16 @@ impl LuaUserdata {
         Ok(self.get_trait_mut()?.as_any_mut())
     }
 
+    /// Bytes owned by this userdata beyond the `LuaUserdata` header, as
+    /// reported by [`UserDataTrait::size_hint`]. Borrowed data is not counted.
+    pub(crate) fn payload_size(&self) -> usize {
+        match &self.data {
+            UserdataStorage::Owned(boxed) => boxed.size_hint(),
+            UserdataStorage::Shared(shared) => shared.size_hint(),
+            UserdataStorage::Borrowed(_) => 0,
+        }
+    }
+
     // ==================== Metatable ====================
 
     pub fn get_metatable(&self) -> Option<LuaValue> {
@@ -214,7 +292,7 @@ impl LuaUserdata {
 
 impl Drop for LuaUserdata {
     fn drop(&mut self) {
-        if let UserdataStorage::Owned(_) = &self.data {
+        if let UserdataStorage::Owned(_) | UserdataStorage::Shared(_) = &self.data {
             self.alive_token.set(false);
         }
     }
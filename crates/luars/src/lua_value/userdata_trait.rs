@@ -120,6 +120,7 @@ impl UdValue {
 ///
 /// # Dispatch priority (when Lua accesses `obj.key`):
 /// 1. `get_field(key)` — field or method access (fields return value, methods return CFunction)
+///    for string keys, `get_index(key)` for integer keys
 /// 2. Metatable `__index` — traditional Lua fallback
 ///
 /// # Example (manual implementation)
@@ -158,6 +159,13 @@ pub trait UserDataTrait: 'static {
     /// For derive macro: uses the struct name.
     fn type_name(&self) -> &'static str;
 
+    /// Bytes this value accounts for in the GC heap (`collectgarbage("count")`).
+    /// Defaults to the size of the value itself; types owning heap buffers
+    /// should add their capacity so the collector paces itself correctly.
+    fn size_hint(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
     // ==================== Field Access ====================
 
     /// Get a field value by name.
@@ -175,6 +183,18 @@ pub trait UserDataTrait: 'static {
         None
     }
 
+    /// Get an element by integer key (`obj[i]`), for buffer- and array-like
+    /// types. Returns `None` to fall through to metatable `__index`.
+    fn get_index(&self, _idx: i64) -> Option<UdValue> {
+        None
+    }
+
+    /// Set an element by integer key (`obj[i] = v`). Same return convention
+    /// as [`set_field`](Self::set_field); `None` falls through to `__newindex`.
+    fn set_index(&mut self, _idx: i64, _value: UdValue) -> Option<Result<(), String>> {
+        None
+    }
+
     // ==================== Metamethods ====================
     // These are auto-generated by the derive macro when the struct
     // implements the corresponding Rust trait. Return None = not supported.
@@ -287,10 +307,20 @@ pub trait UserDataTrait: 'static {
     }
 
     /// `__concat`: Concatenation (`obj .. other`).
+    ///
+    /// `other` is a string or number as is; another userdata comes as its
+    /// [`lua_tostring`](Self::lua_tostring) text when it has one.
+    /// Auto-generated by `#[lua_impl(Concat)]` from `Display`.
     fn lua_concat(&self, _other: &UdValue) -> Option<UdValue> {
         None
     }
 
+    /// `__concat` with the userdata on the right (`other .. obj`). Tried when
+    /// the left operand has no concatenation of its own.
+    fn lua_concat_rhs(&self, _other: &UdValue) -> Option<UdValue> {
+        None
+    }
+
     /// `__close`: Called when a to-be-closed variable goes out of scope.
     fn lua_close(&mut self) {}
 
@@ -460,6 +490,17 @@ impl UdValue {
             _ => None,
         }
     }
+
+    /// The text this value contributes to a concatenation: strings as they
+    /// are and numbers formatted like `tostring`. `None` for anything else.
+    pub fn to_concat_string(&self) -> Option<String> {
+        match self {
+            UdValue::Str(s) => Some(s.clone()),
+            UdValue::Integer(i) => Some(i.to_string()),
+            UdValue::Number(n) => Some(crate::lua_value::lua_float_to_string(*n)),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for UdValue {
@@ -636,9 +677,51 @@ impl<T> LuaStaticMethodProvider for T {}
 ///
 /// Unlike `LuaStaticMethodProvider` (which has a blanket impl that always
 /// returns `&[]`), this trait guarantees that the type has real method info.
-pub trait LuaRegistrable {
+pub trait LuaRegistrable: 'static {
     /// Return all static (associated) methods for type registration.
     fn lua_static_methods() -> &'static [(&'static str, CFunction)];
+
+    /// `__call` handler for the type table, so `Type(...)` constructs a value.
+    /// Generated for the function marked `#[lua(constructor)]`; it receives
+    /// the type table as argument 1.
+    fn lua_constructor() -> Option<CFunction> {
+        None
+    }
+}
+
+// ==================== Field / Method Name Collisions ====================
+
+/// Blanket trait providing an empty list of Lua-visible field names.
+///
+/// `#[derive(LuaUserData)]` generates an inherent `__LUA_FIELD_NAMES` constant
+/// that shadows this default. `#[lua_methods]` checks every instance method
+/// name against it at compile time, since a field with the same name would
+/// hide the method from Lua.
+pub trait LuaFieldNameProvider {
+    const __LUA_FIELD_NAMES: &'static [&'static str] = &[];
+}
+
+impl<T> LuaFieldNameProvider for T {}
+
+/// `names.contains(&name)` usable in constant evaluation.
+#[doc(hidden)]
+pub const fn __lua_name_in(names: &[&str], name: &str) -> bool {
+    let name = name.as_bytes();
+    let mut i = 0;
+    while i < names.len() {
+        let candidate = names[i].as_bytes();
+        if candidate.len() == name.len() {
+            let mut j = 0;
+            while j < name.len() && candidate[j] == name[j] {
+                j += 1;
+            }
+            if j == name.len() {
+                return true;
+            }
+        }
+        i += 1;
+    }
+    false
 }
 
 // ==================== Enum Export ====================
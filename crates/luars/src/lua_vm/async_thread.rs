@@ -23,14 +23,18 @@
 //!               └── normal yield → wake & return Pending
 //! ```
 
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
 
 use crate::UserDataTrait;
 use crate::lua_value::{LuaUserdata, LuaValue};
 use crate::lua_vm::lua_ref::RefId;
-use crate::lua_vm::{GlobalState, GlobalStateHandle, LuaResult};
+use crate::lua_vm::{ErrorMsg, GlobalState, GlobalStateHandle, LuaResult};
 
 // ============ AsyncReturnValue ============
 
@@ -297,6 +301,256 @@ impl_into_async_lua_tuple!(
 /// Not `Send` — must run on a single-threaded / LocalSet executor.
 pub type AsyncFuture = Pin<Box<dyn Future<Output = LuaResult<Vec<AsyncReturnValue>>>>>;
 
+// ============ Async task tracking ============
+
+/// Bookkeeping for every async future a coroutine has started and not yet
+/// finished, so the host can see what is in flight and cancel it.
+///
+/// Each future stored by [`LuaState::set_pending_future`](crate::LuaState::set_pending_future)
+/// is wrapped in a [`TrackedTask`] that registers itself here, tagged with
+/// the coroutine that started it. The entry goes away when the future
+/// completes, times out or is dropped, whichever comes first.
+#[derive(Default)]
+pub(crate) struct AsyncTasks {
+    next_id: Cell<u64>,
+    tasks: RefCell<HashMap<u64, AsyncTask>>,
+    /// Timeout given to tasks started without an explicit one
+    default_timeout: Cell<Option<Duration>>,
+}
+
+/// The coroutine that started an async task: its thread object plus the
+/// pool generation of that object, so a coroutine allocated later in the
+/// same slot is a different owner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct TaskOwner {
+    thread: usize,
+    generation: Option<u32>,
+}
+
+impl TaskOwner {
+    pub(crate) fn of(state: &crate::lua_vm::LuaState) -> Self {
+        let thread = state.thread_ptr();
+        Self {
+            thread: thread.as_ptr() as usize,
+            generation: state
+                .global_state()
+                .object_allocator
+                .slot_generation(thread.into()),
+        }
+    }
+}
+
+struct AsyncTask {
+    /// Coroutine that started the future
+    owner: TaskOwner,
+    /// The future itself; taken out while it is being polled
+    future: Option<AsyncFuture>,
+    /// Set by `cancel`; the next poll fails instead of running the future
+    cancelled: bool,
+    /// Past this point the next poll fails with a timeout
+    deadline: Option<Instant>,
+    /// Waker of the last poll, so cancellation wakes whoever awaits the task
+    waker: Option<Waker>,
+}
+
+impl AsyncTasks {
+    /// Register `future`, started by coroutine `owner`. A `None` timeout
+    /// falls back to the default set with `set_default_timeout`.
+    pub(crate) fn track(
+        self: &Rc<Self>,
+        owner: TaskOwner,
+        global_state: GlobalStateHandle,
+        future: AsyncFuture,
+        timeout: Option<Duration>,
+    ) -> AsyncFuture {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let deadline = timeout
+            .or(self.default_timeout.get())
+            .map(|timeout| Instant::now() + timeout);
+        self.tasks.borrow_mut().insert(
+            id,
+            AsyncTask {
+                owner,
+                future: Some(future),
+                cancelled: false,
+                deadline,
+                waker: None,
+            },
+        );
+        Box::pin(TrackedTask {
+            id,
+            tasks: self.clone(),
+            global_state,
+            timer_armed: false,
+        })
+    }
+
+    pub(crate) fn set_default_timeout(&self, timeout: Option<Duration>) {
+        self.default_timeout.set(timeout);
+    }
+
+    /// Number of tracked futures that are still running.
+    pub(crate) fn len(&self) -> usize {
+        self.tasks
+            .borrow()
+            .values()
+            .filter(|t| !t.cancelled)
+            .count()
+    }
+
+    /// Cancel the futures started by `owner`, or all of them for `None`.
+    /// Cancelled futures are dropped right away, and whoever awaits one
+    /// gets an error. Returns how many futures were cancelled.
+    pub(crate) fn cancel(&self, owner: Option<TaskOwner>) -> usize {
+        let mut dropped = Vec::new();
+        let mut wakers = Vec::new();
+        let mut cancelled = 0;
+        for task in self.tasks.borrow_mut().values_mut() {
+            if task.cancelled || owner.is_some_and(|owner| owner != task.owner) {
+                continue;
+            }
+            task.cancelled = true;
+            cancelled += 1;
+            dropped.extend(task.future.take());
+            wakers.extend(task.waker.take());
+        }
+        // Drop and wake outside the borrow: both may reach back in here
+        drop(dropped);
+        for waker in wakers {
+            waker.wake();
+        }
+        cancelled
+    }
+
+    fn remove(&self, id: u64) {
+        let task = self.tasks.borrow_mut().remove(&id);
+        drop(task);
+    }
+}
+
+/// Handle to a future registered in [`AsyncTasks`].
+struct TrackedTask {
+    id: u64,
+    tasks: Rc<AsyncTasks>,
+    global_state: GlobalStateHandle,
+    /// Whether the deadline timer already holds a waker for this task
+    timer_armed: bool,
+}
+
+impl TrackedTask {
+    fn fail(&mut self, message: &str) -> Poll<LuaResult<Vec<AsyncReturnValue>>> {
+        self.tasks.remove(self.id);
+        Poll::Ready(Err(self.global_state.as_mut().error(message.to_string())))
+    }
+}
+
+impl Future for TrackedTask {
+    type Output = LuaResult<Vec<AsyncReturnValue>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let (future, deadline) = match this.tasks.tasks.borrow_mut().get_mut(&this.id) {
+            Some(task) if !task.cancelled => {
+                task.waker = Some(cx.waker().clone());
+                (task.future.take(), task.deadline)
+            }
+            _ => (None, None),
+        };
+        let Some(mut future) = future else {
+            return this.fail("async task cancelled");
+        };
+        if let Some(deadline) = deadline {
+            if Instant::now() >= deadline {
+                drop(future);
+                return this.fail("async task timed out");
+            }
+            if !this.timer_armed {
+                this.timer_armed = true;
+                timer::wake_at(deadline, cx.waker().clone());
+            }
+        }
+
+        let result = future.as_mut().poll(cx);
+        if result.is_ready() {
+            drop(future);
+            this.tasks.remove(this.id);
+            return result;
+        }
+        // Put the future back, unless it was cancelled while being polled
+        let mut tasks = this.tasks.tasks.borrow_mut();
+        match tasks.get_mut(&this.id) {
+            Some(task) if !task.cancelled => {
+                task.future = Some(future);
+                Poll::Pending
+            }
+            _ => {
+                drop(tasks);
+                drop(future);
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for TrackedTask {
+    fn drop(&mut self) {
+        self.tasks.remove(self.id);
+    }
+}
+
+/// Deadline wakeups for timed tasks, so a future that never wakes its task
+/// still gets polled once its timeout passes. One background thread serves
+/// every VM; it only starts when the first timed task is polled.
+mod timer {
+    use std::sync::OnceLock;
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+    use std::task::Waker;
+    use std::time::Instant;
+
+    static TIMER: OnceLock<Sender<(Instant, Waker)>> = OnceLock::new();
+
+    pub(super) fn wake_at(deadline: Instant, waker: Waker) {
+        let sender = TIMER.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel();
+            std::thread::Builder::new()
+                .name("luars-async-timer".to_string())
+                .spawn(move || run(receiver))
+                .expect("failed to spawn the async timer thread");
+            sender
+        });
+        let _ = sender.send((deadline, waker));
+    }
+
+    fn run(receiver: Receiver<(Instant, Waker)>) {
+        let mut pending: Vec<(Instant, Waker)> = Vec::new();
+        loop {
+            let next = pending.iter().map(|(deadline, _)| *deadline).min();
+            let received = match next {
+                Some(deadline) => {
+                    receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(entry) => pending.push(entry),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            let now = Instant::now();
+            pending.retain(|(deadline, waker)| {
+                if *deadline <= now {
+                    waker.wake_by_ref();
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+}
+
 // ============ Async sentinel ============
 
 /// Static storage whose *address* is used as the async sentinel value.
@@ -353,7 +607,10 @@ fn materialize_values(
 /// Recursively convert a single `AsyncReturnValue` to a `LuaValue`.
 fn materialize_single(vm: &mut GlobalState, value: AsyncReturnValue) -> LuaResult<LuaValue> {
     match value {
-        AsyncReturnValue::Value(lv) => Ok(lv),
+        AsyncReturnValue::Value(lv) => {
+            vm.check_values_owned(&[lv])?;
+            Ok(lv)
+        }
         AsyncReturnValue::String(s) => vm.create_string(&s),
         AsyncReturnValue::UserData(ud) => vm.create_userdata(ud),
         AsyncReturnValue::Table(entries) => {
@@ -475,7 +732,20 @@ impl AsyncThread {
                     ResumeResult::NormalYield(values)
                 }
             }
-            Err(e) => ResumeResult::Finished(Err(e)),
+            Err(e) => {
+                // A coroutine that dies archives its error on the thread;
+                // re-raise it so the caller's `get_error_message` sees it.
+                if thread_state.dead {
+                    match thread_state.take_dead_error() {
+                        ErrorMsg::Msg(msg) => {
+                            let _ = thread_state.error(msg);
+                        }
+                        ErrorMsg::Object(obj) => thread_state.set_error_object(obj),
+                        ErrorMsg::None => {}
+                    }
+                }
+                ResumeResult::Finished(Err(e))
+            }
         }
     }
 
@@ -47,6 +47,11 @@ pub mod call_status {
     /// without prepending true/false.
     pub const CIST_YCALL: u32 = 1 << 29;
 
+    /// Suspended `pcall_stack_based_k`: once the callee finishes after a
+    /// yield, the C function is called again to continue. `aux_i32` holds
+    /// the callee's position relative to `base`.
+    pub const CIST_YCONT: u32 = 1 << 31;
+
     /// Frame was interrupted by a hook (set during hook callback).
     /// Used by debug.getinfo to report namewhat="hook".
     pub const CIST_HOOKED: u32 = 1 << 30;
@@ -138,6 +143,7 @@ pub struct CallInfo {
     /// Reused i32 payload.
     /// - When CIST_CLSRET is set: saved nres for return continuation.
     /// - When CIST_PENDING_FINISH is set: pending finish destination/state.
+    /// - When CIST_YCONT is set: callee position for the continuation.
     pub aux_i32: i32,
 }
 
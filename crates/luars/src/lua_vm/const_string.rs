@@ -1,5 +1,5 @@
 use crate::{
-    LuaValue,
+    LuaValue, LuaValueKind,
     gc::{GC, ObjectAllocator},
     lua_vm::TmKind,
 };
@@ -48,6 +48,10 @@ pub struct ConstString {
     pub str_hook_tail_call: LuaValue, // "tail call"
 
     pub str_n: LuaValue, // "n" (for vararg table)
+
+    // Pre-cached strings returned by library and error paths
+    pub str_empty: LuaValue,                   // ""
+    pub str_error_in_error_handling: LuaValue, // "error in error handling"
 }
 
 impl ConstString {
@@ -81,6 +85,8 @@ impl ConstString {
             str_hook_count: nil,
             str_hook_tail_call: nil,
             str_n: nil,
+            str_empty: nil,
+            str_error_in_error_handling: nil,
         };
 
         // Pre-create all metamethod name strings indexed by TmKind discriminant
@@ -182,6 +188,14 @@ impl ConstString {
         gc.fixed(cs.str_hook_tail_call.as_gc_ptr().unwrap());
         gc.fixed(cs.str_n.as_gc_ptr().unwrap());
 
+        // Pre-create strings of library results and error messages
+        cs.str_empty = allocator.create_string(gc, "").unwrap();
+        cs.str_error_in_error_handling = allocator
+            .create_string(gc, "error in error handling")
+            .unwrap();
+        gc.fixed(cs.str_empty.as_gc_ptr().unwrap());
+        gc.fixed(cs.str_error_in_error_handling.as_gc_ptr().unwrap());
+
         cs.attach_to_gc(gc);
         cs
     }
@@ -225,6 +239,26 @@ impl ConstString {
         share_lua_value(&mut self.str_hook_count);
         share_lua_value(&mut self.str_hook_tail_call);
         share_lua_value(&mut self.str_n);
+        share_lua_value(&mut self.str_empty);
+        share_lua_value(&mut self.str_error_in_error_handling);
+    }
+
+    /// Pre-cached result of `type(value)`.
+    #[inline(always)]
+    pub fn type_name(&self, value: &LuaValue) -> LuaValue {
+        match value.kind() {
+            LuaValueKind::Nil => self.str_nil,
+            LuaValueKind::Boolean => self.str_boolean,
+            LuaValueKind::Integer | LuaValueKind::Float => self.str_number,
+            LuaValueKind::String => self.str_string,
+            LuaValueKind::Table => self.str_table,
+            LuaValueKind::Function
+            | LuaValueKind::CFunction
+            | LuaValueKind::CClosure
+            | LuaValueKind::RClosure => self.str_function,
+            LuaValueKind::Userdata => self.str_userdata,
+            LuaValueKind::Thread => self.str_thread,
+        }
     }
 
     /// Get pre-cached metamethod name string by TmKind enum value — O(1) array index.
@@ -167,3 +167,40 @@ impl DebugInfo {
         self.func = Some(func);
     }
 }
+
+/// Static description of a function value, for inspectors that enumerate the
+/// VM from Rust. Built by [`LuaState::function_info`](crate::LuaState::function_info)
+/// without running any Lua.
+#[derive(Debug, Clone)]
+pub struct FunctionInfo {
+    /// Number of fixed parameters (0 for C functions)
+    pub params: u8,
+    /// Whether the function takes `...` (always true for C functions)
+    pub is_vararg: bool,
+    /// Upvalue names from the debug info; "" for C upvalues and "(no name)"
+    /// for stripped chunks, as debug.getupvalue reports them
+    pub upvalue_names: Vec<String>,
+    /// Current upvalue values, parallel to `upvalue_names`
+    pub upvalues: Vec<LuaValue>,
+    /// Short source for display (e.g. "test.lua", "[C]")
+    pub source: String,
+    /// First and last line of the definition (0 for a main chunk, -1 for C)
+    pub line_range: (i32, i32),
+}
+
+/// One call frame of a thread, as reported by
+/// [`LuaState::thread_stack`](crate::LuaState::thread_stack).
+#[derive(Debug, Clone)]
+pub struct FrameInfo {
+    /// "Lua", "C" or "main"
+    pub what: &'static str,
+    /// Function name as resolved from the calling instruction, if any
+    pub name: Option<String>,
+    /// Short source for display (e.g. "test.lua", "[C]")
+    pub source: String,
+    /// Line being executed (-1 for C frames)
+    pub current_line: i32,
+    /// Active local variables in declaration order, named from the chunk's
+    /// debug info; empty for C frames
+    pub locals: Vec<(String, LuaValue)>,
+}
@@ -6,8 +6,10 @@ use crate::{
     gc::UpvaluePtr,
     lua_vm::{
         CFunction, LUA_HOOKCALL, LUA_HOOKRET, LuaResult, LuaState, StkId, TmKind,
-        call_info::call_status, execute::hook::hook_on_return, get_metamethod_event,
-        lua_limits::EXTRA_STACK,
+        call_info::call_status,
+        execute::{hook::hook_on_return, lua_execute},
+        get_metamethod_event,
+        lua_limits::{EXTRA_STACK, MAXCCMT},
     },
 };
 
@@ -77,9 +79,9 @@ pub fn resolve_call_chain(
 
         // Try to get __call metamethod
         if let Some(mm) = get_metamethod_event(lua_state, &func, TmKind::Call) {
-            // Check chain depth (Lua 5.5 allows up to 15 __call layers)
-            // We check BEFORE incrementing, so if we're already at 15, error
-            if ccmt_depth == 15 {
+            // Check chain depth (Lua 5.5 allows up to MAXCCMT __call layers)
+            // We check BEFORE incrementing, so if we're already at the limit, error
+            if ccmt_depth == MAXCCMT {
                 return Err(lua_state.error("'__call' chain too long".to_string()));
             }
             ccmt_depth += 1;
@@ -254,6 +256,14 @@ pub fn pretailcall_lua(
         .copy_within(func_idx..func_idx + narg1, func_pos);
 
     let new_base = func_pos + 1;
+    let actual_nargs = nargs.max(numparams);
+    let frame_top = func_pos + 1 + max_stack_size;
+
+    // Ensure physical stack is large enough, including for the padding below
+    let needed_physical = frame_top + EXTRA_STACK;
+    if needed_physical > lua_state.stack_len() {
+        lua_state.grow_stack(needed_physical)?;
+    }
 
     // Pad missing parameters with nil
     if nargs < numparams {
@@ -263,15 +273,6 @@ pub fn pretailcall_lua(
         }
     }
 
-    let actual_nargs = nargs.max(numparams);
-    let frame_top = func_pos + 1 + max_stack_size;
-
-    // Ensure physical stack is large enough
-    let needed_physical = frame_top + EXTRA_STACK;
-    if needed_physical > lua_state.stack_len() {
-        lua_state.grow_stack(needed_physical)?;
-    }
-
     // Batch update CI fields (reuse current frame, no push/pop)
     {
         let sp = lua_state.stack_mut().as_mut_ptr();
@@ -482,6 +483,30 @@ fn precall_meta(
     Err(crate::stdlib::debug::typeerror(lua_state, &func, "call"))
 }
 
+/// Like C Lua's `luaD_call`: call the value at `func_idx` with the `nargs`
+/// values above it and run it to completion, leaving `nresults` results
+/// (all of them for -1) from `func_idx` on.
+///
+/// Metamethods and host-side calls go through here so that their frames
+/// are built by `precall`, exactly like the CALL opcode's: `__call` inserts
+/// the called object once, and the callee sees the same argument count
+/// however it was reached.
+pub fn call_value(
+    lua_state: &mut LuaState,
+    func_idx: usize,
+    nargs: usize,
+    nresults: i32,
+) -> LuaResult<()> {
+    let caller_depth = lua_state.call_depth();
+    if precall(lua_state, func_idx, nargs, nresults)? {
+        lua_state.inc_n_ccalls()?;
+        let r = lua_execute(lua_state, caller_depth);
+        lua_state.dec_n_ccalls();
+        r?;
+    }
+    Ok(())
+}
+
 /// Like C Lua's `luaD_pretailcall`.
 /// Caller MUST set stack top and close upvalues before calling.
 ///
@@ -580,6 +605,10 @@ pub fn poscall(
 ) -> LuaResult<()> {
     let nresults = ci.nresults();
 
+    #[cfg(feature = "profiler")]
+    if lua_state.hook_mask & crate::lua_vm::LUA_MASKPROFILE != 0 {
+        lua_state.profile_return();
+    }
     // Return hook (cold path — almost never fires)
     if lua_state.hook_mask & LUA_MASKRET != 0 && lua_state.allow_hook {
         hook_on_return(lua_state, pc, nres as i32)?;
@@ -11,6 +11,8 @@
 use crate::{
     gc::StringInterner,
     lua_value::LuaValue,
+    lua_value::userdata_trait::UdValue,
+    lua_value::{lua_float_to_string, lua_value_to_udvalue, udvalue_to_lua_value},
     lua_vm::{
         LuaResult, LuaState,
         execute::{
@@ -18,7 +20,7 @@ use crate::{
             metamethod::{TmKind, call_tm_res},
         },
     },
-    stdlib::{basic::lua_float_to_string, debug::typeerror},
+    stdlib::debug::concaterror,
 };
 
 #[inline]
@@ -265,14 +267,45 @@ fn tryconcattm(lua_state: &mut LuaState, top: usize) -> LuaResult<()> {
         let result = call_tm_res(lua_state, mm, p1, p2)?;
         // Store result at top - 2 (replaces first operand)
         lua_state.stack_mut()[top - 2] = result;
-        Ok(())
-    } else {
-        // No metamethod found — generate error
-        let bad = if p1.ttisstring() || cvt2str(&p1) {
-            &p2
-        } else {
-            &p1
-        };
-        Err(typeerror(lua_state, bad, "concatenate"))
+        return Ok(());
+    }
+
+    // No __concat in either metatable: fall back to the concatenation a
+    // userdata type implements in Rust, so a Lua-defined __concat wins
+    if (p1.ttisfulluserdata() || p2.ttisfulluserdata())
+        && let Some(udv) = try_trait_concat(&p1, &p2)
+    {
+        let result = udvalue_to_lua_value(lua_state, udv)?;
+        lua_state.stack_mut()[top - 2] = result;
+        return Ok(());
+    }
+
+    // Neither side can be concatenated — generate error
+    Err(concaterror(lua_state, &p1, &p2, top - 2))
+}
+
+/// `lua_concat` of a userdata on the left, else `lua_concat_rhs` of one on
+/// the right.
+fn try_trait_concat(p1: &LuaValue, p2: &LuaValue) -> Option<UdValue> {
+    if let Some(ud) = p1.as_userdata_mut()
+        && let Ok(trait_obj) = ud.get_trait()
+        && let Some(udv) = trait_obj.lua_concat(&concat_operand(p2))
+    {
+        return Some(udv);
+    }
+    let ud = p2.as_userdata_mut()?;
+    let trait_obj = ud.get_trait().ok()?;
+    trait_obj.lua_concat_rhs(&concat_operand(p1))
+}
+
+/// The other operand as a concat trait method sees it: userdata with a
+/// `lua_tostring` as that text, so two userdata types can be joined.
+fn concat_operand(v: &LuaValue) -> UdValue {
+    if let Some(ud) = v.as_userdata_mut()
+        && let Ok(trait_obj) = ud.get_trait()
+        && let Some(s) = trait_obj.lua_tostring()
+    {
+        return UdValue::Str(s);
     }
+    lua_value_to_udvalue(v)
 }
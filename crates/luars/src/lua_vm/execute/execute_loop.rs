@@ -10,7 +10,10 @@
   5. **Unsafe but Sound**: Use raw pointers with invariant guarantees for stack
 
   Key Invariants (maintained by caller):
-  - Stack pointer valid throughout execution (no reallocation)
+  - The stack Vec may reallocate whenever control leaves the loop (calls,
+    metamethods, hooks, GC, __close). `resize` re-points every
+    `CallInfo::base_stk`, so after any such operation the cached `base_stk`
+    must be reloaded from `ci` (syncbase!) before touching registers again
   - CallInfo valid and matches current frame
   - Chunk lifetime extends through execution
   - base + register < stack.len() (validated at call time)
@@ -23,7 +26,7 @@ use crate::{
     LuaValue, OpCode,
     lua_value::{BIT_ISCOLLECTABLE, LuaProto},
     lua_vm::{
-        LuaError, StkId, TmKind,
+        LUA_MASKINTERNAL, LUA_MASKPROFILE, LuaError, StkId, TmKind,
         call_info::call_status::{CIST_C, CIST_CLSRET, CIST_PENDING_FINISH},
         execute::{
             arith::{self, lua_fmod, lua_idiv, lua_imod, lua_shiftl, lua_shiftr, luai_numpow},
@@ -34,7 +37,7 @@ use crate::{
                 bin_tm_fallback, eq_fallback, error_div_by_zero, error_global, error_mod_by_zero,
                 float_for_loop, forprep, handle_pending_ops, instr_at, ivalue, k_val, objlen,
                 order_tm_fallback, pk_val, return0_with_hook, return1_with_hook, tointegerns,
-                tonumberns, ttisfloat, ttisinteger, ttisstring, unary_tm_fallback,
+                tonumberns, ttisfloat, ttisinteger, unary_tm_fallback,
             },
             hook::{hook_check_instruction, hook_on_call},
             number::{le_num, lt_num},
@@ -58,16 +61,37 @@ fn init_oldpc(lua_state: &mut LuaState, pc: usize, chunk: &LuaProto) {
     }
 }
 
+/// Checks that operand A of a register-addressing instruction lies inside
+/// the frame the compiler reserved (`max_stack_size`). A mismatch means the
+/// compiler under-counted registers and the executor would touch slots that
+/// belong to the next frame.
+fn register_in_frame(instr: Instruction, chunk: &LuaProto) -> bool {
+    let a = instr.get_a() as usize;
+    match instr.get_opcode() {
+        // A is not a register (jump offset, extra argument, upvalue index)
+        OpCode::Jmp | OpCode::ExtraArg | OpCode::SetTabUp => true,
+        // Returns may name the first free register when returning nothing;
+        // VARARGPREP's A is the number of fixed parameters
+        OpCode::Return | OpCode::Return0 | OpCode::VarargPrep => a <= chunk.max_stack_size,
+        _ => a < chunk.max_stack_size,
+    }
+}
+
 #[inline]
 fn current_trap(lua_state: &LuaState) -> bool {
-    #[cfg(not(feature = "sandbox"))]
-    {
-        lua_state.hook_mask != 0
-    }
+    lua_state.has_active_instruction_watch()
+}
 
-    #[cfg(feature = "sandbox")]
+/// Whether entering a Lua function must go through `hook_on_call`: the
+/// thread has an instruction watch, or the profiler records it.
+#[inline(always)]
+fn call_hooked(lua_state: &LuaState, trap: bool) -> bool {
+    #[cfg(feature = "profiler")]
+    return trap || lua_state.hook_mask & LUA_MASKPROFILE != 0;
+    #[cfg(not(feature = "profiler"))]
     {
-        lua_state.has_active_instruction_watch()
+        let _ = lua_state;
+        trap
     }
 }
 
@@ -106,11 +130,13 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
 
         // CALL HOOK: fire when entering a new Lua function (pc == 0)
         let mut trap = current_trap(lua_state);
-        if pc == 0 && trap {
+        if pc == 0 && call_hooked(lua_state, trap) {
             let hook_mask = lua_state.hook_mask;
-            if hook_mask & LUA_MASKCALL != 0 && lua_state.allow_hook {
+            if hook_mask & (LUA_MASKCALL | LUA_MASKPROFILE) != 0 {
                 ci.save_pc(pc);
                 hook_on_call(lua_state, hook_mask, ci.call_status, chunk)?;
+                // The hook runs arbitrary code and may have grown the stack
+                base_stk = ci.base_stk;
             }
             if hook_mask & LUA_MASKCOUNT != 0 {
                 lua_state.hook_count = lua_state.base_hook_count;
@@ -156,11 +182,12 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
                 code = &chunk.code;
                 constants = &chunk.constants;
                 trap = current_trap(lua_state);
-                if trap {
+                if call_hooked(lua_state, trap) {
                     let hook_mask = lua_state.hook_mask;
-                    if hook_mask & LUA_MASKCALL != 0 && lua_state.allow_hook {
+                    if hook_mask & (LUA_MASKCALL | LUA_MASKPROFILE) != 0 {
                         ci.save_pc(0);
                         hook_on_call(lua_state, hook_mask, ci.call_status, chunk)?;
+                        base_stk = ci.base_stk;
                     }
                     if hook_mask & LUA_MASKCOUNT != 0 {
                         lua_state.hook_count = lua_state.base_hook_count;
@@ -172,15 +199,7 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
 
         macro_rules! updatetrap {
             () => {
-                #[cfg(not(feature = "sandbox"))]
-                {
-                    trap = lua_state.hook_mask != 0;
-                }
-
-                #[cfg(feature = "sandbox")]
-                {
-                    trap = lua_state.has_active_instruction_watch();
-                }
+                trap = lua_state.has_active_instruction_watch();
             };
         }
 
@@ -204,6 +223,14 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
         loop {
             let instr = instr_at(code, pc); // vmfetch
             pc += 1;
+            debug_assert!(
+                register_in_frame(instr, chunk),
+                "{:?} at pc {} uses register R[{}] outside frame (max_stack_size {})",
+                instr.get_opcode(),
+                pc - 1,
+                instr.get_a(),
+                chunk.max_stack_size
+            );
 
             if trap {
                 ci.save_pc(pc);
@@ -316,6 +343,7 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
                         instr,
                         code,
                         constants,
+                        &chunk.key_hints,
                     )?;
                     continue;
                 }
@@ -809,15 +837,9 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
                             ivalue(ra) < ivalue(rb)
                         } else if ra.is_number() && rb.is_number() {
                             lt_num(ra, rb)
-                        } else if ttisstring(ra) && ttisstring(rb) {
-                            let sa = ra.as_bytes();
-                            let sb = rb.as_bytes();
-
-                            if let (Some(sa), Some(sb)) = (sa, sb) {
-                                sa < sb
-                            } else {
-                                false
-                            }
+                        } else if let Some(ord) = ra.str_cmp(rb) {
+                            // Two strings never consult __lt/__le
+                            ord.is_lt()
                         } else {
                             let va = *ra;
                             let vb = *rb;
@@ -850,15 +872,9 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
                             ivalue(ra) <= ivalue(rb)
                         } else if ra.is_number() && rb.is_number() {
                             le_num(ra, rb)
-                        } else if ttisstring(ra) && ttisstring(rb) {
-                            let sa = ra.as_bytes();
-                            let sb = rb.as_bytes();
-
-                            if let (Some(sa), Some(sb)) = (sa, sb) {
-                                sa <= sb
-                            } else {
-                                false
-                            }
+                        } else if let Some(ord) = ra.str_cmp(rb) {
+                            // Two strings never consult __lt/__le
+                            ord.is_le()
                         } else {
                             let va = *ra;
                             let vb = *rb;
@@ -1150,11 +1166,12 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
                             let ci_ptr = lua_state.get_call_info_ptr(frame_idx);
                             ci = unsafe { &mut *ci_ptr };
                             trap = current_trap(lua_state);
-                            if trap {
+                            if call_hooked(lua_state, trap) {
                                 let hook_mask = lua_state.hook_mask;
-                                if hook_mask & LUA_MASKCALL != 0 && lua_state.allow_hook {
+                                if hook_mask & (LUA_MASKCALL | LUA_MASKPROFILE) != 0 {
                                     ci.save_pc(0);
                                     hook_on_call(lua_state, hook_mask, ci.call_status, chunk)?;
+                                    base_stk = ci.base_stk;
                                 }
                                 if hook_mask & LUA_MASKCOUNT != 0 {
                                     lua_state.hook_count = lua_state.base_hook_count;
@@ -1288,6 +1305,10 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
                         return0_with_hook(lua_state, ci, ci.base + instr.get_a() as usize, pc)?;
                         break;
                     }
+                    #[cfg(feature = "profiler")]
+                    if lua_state.hook_mask & LUA_MASKPROFILE != 0 {
+                        lua_state.profile_return();
+                    }
 
                     // Inlined fast path: no hook, no moveresults overhead
                     // Follows C Lua OP_RETURN0: L->ci = ci->previous; then goto returning
@@ -1316,6 +1337,10 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
                         return1_with_hook(lua_state, ci, ci.base + instr.get_a() as usize, pc)?;
                         break;
                     }
+                    #[cfg(feature = "profiler")]
+                    if lua_state.hook_mask & LUA_MASKPROFILE != 0 {
+                        lua_state.profile_return();
+                    }
 
                     // Inlined fast path — raw pointer for single copy
                     // Follows C Lua OP_RETURN1: L->ci = ci->previous; setobjs2s; then goto returning
@@ -1422,9 +1447,11 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
                         continue;
                     }
 
+                    // C iterator completed; it may have reallocated the stack
                     if lua_state.hook_mask & LUA_MASKLINE != 0 {
                         lua_state.oldpc = (pc - 1) as u32;
                     }
+                    syncbase!();
                     updatetrap!();
                 }
                 OpCode::TForLoop => {
@@ -1514,11 +1541,13 @@ pub fn lua_execute(lua_state: &mut LuaState, target_depth: usize) -> LuaResult<(
                     // Re-sync base_stk after potential base change
                     base_stk = StkId::from_stack(lua_state.stack_mut().as_mut_ptr(), ci.base);
 
-                    // After varargprep, hook call if hooks are active
-                    let hook_mask = lua_state.hook_mask;
+                    // After varargprep, hook call if hooks are active. The
+                    // profiler already saw this call on entry.
+                    let hook_mask = lua_state.hook_mask & !LUA_MASKINTERNAL;
                     if hook_mask != 0 {
                         ci.save_pc(pc);
                         hook_on_call(lua_state, hook_mask, ci.call_status, chunk)?;
+                        syncbase!();
 
                         if hook_mask & LUA_MASKLINE != 0 {
                             lua_state.oldpc = u32::MAX; // force line event on next instruction
@@ -2,15 +2,16 @@ use crate::lua_value::{LUA_VNIL, LuaInnerValue};
 use crate::stdlib::basic::parse_number::parse_lua_number;
 use crate::stdlib::debug::{objtypename, ordererror, typeerror};
 use crate::{
-    Instruction, LuaProto, LuaResult, LuaValue, OpCode,
+    Instruction, LuaProto, LuaResult, LuaValue, OpCode, UdValue, UserDataTrait,
     gc::TablePtr,
     lua_value::{lua_value_to_udvalue, udvalue_to_lua_value, udvalue_to_lua_value_with_token},
     lua_vm::{
-        LuaError, LuaState, StkId, TmKind,
+        GetObserver, LuaError, LuaState, SetAction, SetObserver, StkId, TmKind,
         call_info::{
             CallInfo,
             call_status::{
-                CIST_C, CIST_PENDING_FINISH, CIST_RECST, CIST_XPCALL, CIST_YCALL, CIST_YPCALL,
+                CIST_C, CIST_PENDING_FINISH, CIST_RECST, CIST_XPCALL, CIST_YCALL, CIST_YCONT,
+                CIST_YPCALL,
             },
         },
         execute::{
@@ -96,12 +97,6 @@ pub fn ttisfloat(v: &LuaValue) -> bool {
     v.ttisfloat()
 }
 
-/// ttisstring_ref - 引用版本（保留兼容性）
-#[inline]
-pub fn ttisstring(v: &LuaValue) -> bool {
-    v.is_string()
-}
-
 // ============ 值访问宏 (对应 Lua 的 ivalue/fltvalue) ============
 
 /// ivalue_ref - 引用版本（保留兼容性）
@@ -246,11 +241,15 @@ fn finishget_inner(
             {
                 let token = ud.sub_guard_token();
                 let trait_obj = ud.get_trait()?;
-                // Try trait-based get_field (key must be a string)
-                if let Some(key_str) = key.as_str()
-                    && let Some(udv) = trait_obj.get_field(key_str)
-                {
+                if let Some(udv) = userdata_trait_get(trait_obj, key) {
                     let result = udvalue_to_lua_value_with_token(lua_state, udv, token)?;
+                    if let Some(on_get) = lua_state
+                        .global_state()
+                        .userdata_observers(ud)
+                        .and_then(|o| o.on_get)
+                    {
+                        return observe_get(lua_state, &*on_get, t, key, result).map(Some);
+                    }
                     return Ok(Some(result));
                 }
             }
@@ -395,14 +394,8 @@ pub(crate) fn finishset(
     skip_existing_check: bool,
 ) -> LuaResult<bool> {
     // Check for invalid keys (nil or NaN)
-    if key.is_nil() {
-        return Err(lua_state.error("table index is nil".to_string()));
-    }
-    if key.ttisfloat()
-        && let Some(f) = key.as_float()
-        && f.is_nan()
-    {
-        return Err(lua_state.error("table index is NaN".to_string()));
+    if let Some(msg) = key.table_key_error() {
+        return Err(lua_state.error(msg.to_string()));
     }
 
     let mut t = *obj;
@@ -445,14 +438,32 @@ pub(crate) fn finishset(
                 continue;
             }
         } else {
-            // Not a table — try trait-based set_field for userdata first
+            // Not a table — try trait-based set_field/set_index for userdata first
             if t.ttisfulluserdata()
                 && let Some(ud) = t.as_userdata_mut()
-                && let Some(key_str) = key.as_str()
+                && (key.ttisinteger() || key.as_str().is_some())
             {
+                if let Some(on_set) = lua_state
+                    .global_state()
+                    .userdata_observers(ud)
+                    .and_then(|o| o.on_set)
+                {
+                    let token = ud.sub_guard_token();
+                    let old = match userdata_trait_get(ud.get_trait()?, key) {
+                        Some(udv) => udvalue_to_lua_value_with_token(lua_state, udv, token)?,
+                        None => LuaValue::nil(),
+                    };
+                    if observe_set(lua_state, &*on_set, t, key, old, value)? == SetAction::Skip {
+                        return Ok(true);
+                    }
+                }
+                // The observer ran arbitrary code; look the userdata up again.
+                let Some(ud) = t.as_userdata_mut() else {
+                    return Err(typeerror(lua_state, &t, "index"));
+                };
                 let udv = lua_value_to_udvalue(&value);
                 match ud.get_trait_mut() {
-                    Ok(trait_obj) => match trait_obj.set_field(key_str, udv) {
+                    Ok(trait_obj) => match userdata_trait_set(trait_obj, key, udv) {
                         Some(Ok(())) => return Ok(true),
                         Some(Err(msg)) => {
                             return Err(lua_state.error(msg));
@@ -484,6 +495,90 @@ pub(crate) fn finishset(
     Err(lua_state.error("'__newindex' chain too long; possible loop".to_string()))
 }
 
+/// Trait-level lookup of `key` on a userdata: integer keys go to
+/// `get_index`, string keys to `get_field`.
+#[inline]
+fn userdata_trait_get(trait_obj: &dyn UserDataTrait, key: &LuaValue) -> Option<UdValue> {
+    if key.ttisinteger() {
+        trait_obj.get_index(key.ivalue())
+    } else {
+        key.as_str().and_then(|k| trait_obj.get_field(k))
+    }
+}
+
+/// Trait-level store counterpart of [`userdata_trait_get`].
+#[inline]
+fn userdata_trait_set(
+    trait_obj: &mut dyn UserDataTrait,
+    key: &LuaValue,
+    value: UdValue,
+) -> Option<Result<(), String>> {
+    if key.ttisinteger() {
+        trait_obj.set_index(key.ivalue(), value)
+    } else {
+        key.as_str().and_then(|k| trait_obj.set_field(k, value))
+    }
+}
+
+/// Run a userdata field observer with `parked` values kept on the stack
+/// above the frame, so a collection triggered by the callback cannot free
+/// them.
+fn with_parked<R>(
+    lua_state: &mut LuaState,
+    parked: &[LuaValue],
+    f: impl FnOnce(&mut LuaState) -> R,
+) -> LuaResult<R> {
+    let saved_top = lua_state.get_top();
+    let ci_top = lua_state
+        .current_frame()
+        .map_or(saved_top, |ci| ci.top as usize);
+    lua_state.set_top_raw(saved_top.max(ci_top));
+    for value in parked {
+        if let Err(e) = lua_state.push_value(*value) {
+            lua_state.set_top_raw(saved_top);
+            return Err(e);
+        }
+    }
+    let result = f(lua_state);
+    lua_state.set_top_raw(saved_top);
+    Ok(result)
+}
+
+/// Pass a trait field read through the type's `on_get` observer.
+fn observe_get(
+    lua_state: &mut LuaState,
+    on_get: &GetObserver,
+    obj: LuaValue,
+    key: &LuaValue,
+    value: LuaValue,
+) -> LuaResult<LuaValue> {
+    let name = key.as_str().unwrap_or_default();
+    match with_parked(lua_state, &[obj, *key, value], |l| {
+        on_get(l, &obj, name, value)
+    })? {
+        Ok(value) => Ok(value),
+        Err(msg) => Err(lua_state.error(msg)),
+    }
+}
+
+/// Ask the type's `on_set` observer whether a trait field write may proceed.
+fn observe_set(
+    lua_state: &mut LuaState,
+    on_set: &SetObserver,
+    obj: LuaValue,
+    key: &LuaValue,
+    old: LuaValue,
+    new: LuaValue,
+) -> LuaResult<SetAction> {
+    let name = key.as_str().unwrap_or_default();
+    match with_parked(lua_state, &[obj, *key, old, new], |l| {
+        on_set(l, &obj, name, old, new)
+    })? {
+        Ok(action) => Ok(action),
+        Err(msg) => Err(lua_state.error(msg)),
+    }
+}
+
 pub fn get_metamethod_event(
     lua_state: &mut LuaState,
     value: &LuaValue,
@@ -542,7 +637,39 @@ fn finish_c_frame(lua_state: &mut LuaState, ci: &CallInfo) -> LuaResult<()> {
     let has_recst = call_status & CIST_RECST != 0;
     let is_xpcall = call_status & CIST_XPCALL != 0;
 
-    if call_status & CIST_YPCALL != 0 {
+    if call_status & CIST_YCONT != 0 {
+        // The callee of pcall_stack_based_k finished: continue the C function.
+        let func_idx = ci.base + ci.aux_i32 as usize;
+        let status = if has_recst {
+            // The callee failed and a __close it was unwinding yielded.
+            let error_val = lua_state.take_error_object();
+            lua_state.clear_error();
+            match lua_state.close_tbc_with_error(func_idx + 1, error_val) {
+                Ok(()) => {
+                    let final_err = lua_state.take_error_object();
+                    lua_state.clear_error();
+                    let err = if !final_err.is_nil() {
+                        final_err
+                    } else {
+                        error_val
+                    };
+                    lua_state.stack_set(func_idx, err)?;
+                    lua_state.set_top_raw(func_idx + 1);
+                    (false, 1)
+                }
+                Err(LuaError::Yield) => {
+                    let had_cascaded = lua_state.has_error_object();
+                    let cascaded = lua_state.take_error_object();
+                    lua_state.set_error_object(if had_cascaded { cascaded } else { error_val });
+                    return Err(LuaError::Yield);
+                }
+                Err(e) => return Err(e),
+            }
+        } else {
+            (true, lua_state.get_top() - func_idx)
+        };
+        lua_state.resume_continuation(status)
+    } else if call_status & CIST_YPCALL != 0 {
         if has_recst {
             // Save handler before it gets overwritten (only for xpcall)
             let handler = if is_xpcall {
@@ -574,13 +701,18 @@ fn finish_c_frame(lua_state: &mut LuaState, ci: &CallInfo) -> LuaResult<()> {
                     // If xpcall, call error handler to transform the error
                     let result_err = if is_xpcall {
                         lua_state.nny += 1;
-                        let handler_result = lua_state.pcall(handler, vec![result_err]);
+                        let handler_result = lua_state.pcall_args(handler, &[result_err]);
                         lua_state.nny -= 1;
                         match handler_result {
                             Ok((true, results)) => {
                                 results.into_iter().next().unwrap_or(LuaValue::nil())
                             }
-                            _ => lua_state.create_string("error in error handling")?,
+                            _ => {
+                                lua_state
+                                    .global_state()
+                                    .const_strings
+                                    .str_error_in_error_handling
+                            }
                         }
                     } else {
                         result_err
@@ -687,50 +819,63 @@ fn finish_c_frame(lua_state: &mut LuaState, ci: &CallInfo) -> LuaResult<()> {
         let body_results_start = pcall_func_pos + 1;
         let body_nres = stack_top.saturating_sub(body_results_start);
 
-        // Move results down to func_pos
-        for i in 0..body_nres {
-            let val = lua_state
-                .stack_get(body_results_start + i)
-                .unwrap_or_default();
-            lua_state.stack_set(pcall_func_pos + i, val)?;
-        }
-
-        let n = body_nres;
-
         lua_state.pop_frame();
+        finish_c_results(
+            lua_state,
+            pcall_func_pos,
+            body_results_start,
+            body_nres,
+            nresults,
+        )
+    } else {
+        // Generic C frame after yield — just pop it.
+        // This shouldn't normally happen, but be safe.
+        lua_state.pop_frame();
+        Ok(())
+    }
+}
+
+/// Move the `n` results of a C frame that was just popped from
+/// `first_result` down to its function slot, adjust them to `nresults` and
+/// restore the caller's top (the tail of `call_c_function`).
+pub(crate) fn finish_c_results(
+    lua_state: &mut LuaState,
+    func_pos: usize,
+    first_result: usize,
+    n: usize,
+    nresults: i32,
+) -> LuaResult<()> {
+    for i in 0..n {
+        let val = lua_state.stack_get(first_result + i).unwrap_or_default();
+        lua_state.stack_set(func_pos + i, val)?;
+    }
 
-        let final_n = if nresults == -1 { n } else { nresults as usize };
-        let new_top = pcall_func_pos + final_n;
+    let final_n = if nresults == -1 { n } else { nresults as usize };
+    let new_top = func_pos + final_n;
 
-        if nresults >= 0 {
-            let wanted = nresults as usize;
-            for i in n..wanted {
-                lua_state.stack_set(pcall_func_pos + i, LuaValue::nil())?;
-            }
+    if nresults >= 0 {
+        let wanted = nresults as usize;
+        for i in n..wanted {
+            lua_state.stack_set(func_pos + i, LuaValue::nil())?;
         }
+    }
 
-        lua_state.set_top_raw(new_top);
+    lua_state.set_top_raw(new_top);
 
-        if lua_state.call_depth() > 0 {
-            let ci_idx = lua_state.call_depth() - 1;
-            if nresults == -1 {
-                let ci_top = lua_state.get_call_info(ci_idx).top as usize;
-                if ci_top < new_top {
-                    lua_state.get_call_info_mut(ci_idx).top = new_top as u32;
-                }
-            } else {
-                let frame_top = lua_state.get_call_info(ci_idx).top as usize;
-                lua_state.set_top_raw(frame_top);
+    if lua_state.call_depth() > 0 {
+        let ci_idx = lua_state.call_depth() - 1;
+        if nresults == -1 {
+            let ci_top = lua_state.get_call_info(ci_idx).top as usize;
+            if ci_top < new_top {
+                lua_state.get_call_info_mut(ci_idx).top = new_top as u32;
             }
+        } else {
+            let frame_top = lua_state.get_call_info(ci_idx).top as usize;
+            lua_state.set_top_raw(frame_top);
         }
-
-        Ok(())
-    } else {
-        // Generic C frame after yield — just pop it.
-        // This shouldn't normally happen, but be safe.
-        lua_state.pop_frame();
-        Ok(())
     }
+
+    Ok(())
 }
 
 #[inline]
@@ -1066,7 +1211,10 @@ pub fn forprep(lua_state: &mut LuaState, ra: StkId) -> LuaResult<bool> {
             return Err(lua_state.error("'for' step is zero".to_string()));
         }
 
-        let should_skip = if step > 0.0 {
+        // Written as lvm.c does so a NaN step takes the descending branch
+        // and a NaN limit or init never skips: the body then runs once and
+        // float_for_loop's comparison fails
+        let should_skip = if 0.0 < step {
             limit < init
         } else {
             init < limit
@@ -1151,19 +1299,25 @@ fn for_limit(
     }
 }
 
+/// Port of lvm.c's floatforloop. Unlike the integer loop there is no
+/// precomputed count: the control variable accumulates the step and is
+/// compared against the limit each time, exactly as PUC does, so a loop
+/// like `for x = 1, 2, 0.1` runs the same number of times here as there.
 #[cold]
 #[inline(never)]
 pub fn float_for_loop(ra: StkId) -> bool {
     let step = ra.offset(1).fltvalue();
     let limit = ra.fltvalue();
-    let mut idx = ra.offset(2).fltvalue();
-    idx += step;
-    if (step > 0.0 && idx <= limit) || (step <= 0.0 && idx >= limit) {
+    let idx = ra.offset(2).fltvalue() + step;
+    let go_on = if 0.0 < step {
+        idx <= limit
+    } else {
+        limit <= idx
+    };
+    if go_on {
         ra.offset(2).change_f(idx);
-        return true;
     }
-
-    false
+    go_on
 }
 
 #[cold]
@@ -1173,11 +1327,11 @@ fn error_for_bad_limit(lua_state: &mut LuaState, limit_val: &LuaValue) -> LuaErr
     lua_state.error(format!("bad 'for' limit (number expected, got {})", t))
 }
 
-/// Cold error: attempt to divide by zero (IDIV)
+/// Cold error: attempt to perform 'n//0' (IDIV)
 #[cold]
 #[inline(never)]
 pub fn error_div_by_zero(lua_state: &mut LuaState) -> LuaError {
-    lua_state.error("attempt to divide by zero".to_string())
+    lua_state.error("attempt to perform 'n//0'".to_string())
 }
 
 /// Cold error: attempt to perform 'n%0' (MOD)
@@ -1388,10 +1542,19 @@ fn finishget_to_reg_inner(
             {
                 let token = ud.sub_guard_token();
                 let trait_obj = ud.get_trait()?;
-                if let Some(key_str) = key.as_str()
-                    && let Some(udv) = trait_obj.get_field(key_str)
-                {
+                if let Some(udv) = userdata_trait_get(trait_obj, key) {
                     let result = udvalue_to_lua_value_with_token(lua_state, udv, token)?;
+                    if let Some(on_get) = lua_state
+                        .global_state()
+                        .userdata_observers(ud)
+                        .and_then(|o| o.on_get)
+                    {
+                        // The observer may grow the stack; address the slot by index.
+                        let dest = lua_state.offset_of_stk_id(dest_stk_id) as usize;
+                        let result = observe_get(lua_state, &*on_get, t, key, result)?;
+                        lua_state.stack_mut()[dest] = result;
+                        return Ok(());
+                    }
                     dest_stk_id.write(&result);
                     return Ok(());
                 }
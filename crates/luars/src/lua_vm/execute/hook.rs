@@ -6,10 +6,13 @@ use crate::lua_vm::LUA_HOOKRET;
 use crate::lua_vm::LUA_MASKLINE;
 use crate::lua_vm::LuaState;
 use crate::lua_vm::call_info::call_status::CIST_TAIL;
-use crate::lua_vm::{LUA_HOOKCALL, LUA_HOOKTAILCALL, LUA_MASKCALL, LUA_MASKCOUNT};
+use crate::lua_vm::{
+    LUA_HOOKCALL, LUA_HOOKTAILCALL, LUA_MASKCALL, LUA_MASKCOUNT, LUA_MASKINTERNAL,
+    LUA_MASKINTERRUPT,
+};
 
 /// Fire call hook at function entry (normal call or tail call).
-/// Called when pc == 0 and LUA_MASKCALL is set.
+/// Called when pc == 0 and LUA_MASKCALL or LUA_MASKPROFILE is set.
 /// Also initialises hook_count when LUA_MASKCOUNT is set.
 #[cold]
 #[inline(never)]
@@ -19,7 +22,7 @@ pub fn hook_on_call(
     call_status: u32,
     chunk: &LuaProto,
 ) -> LuaResult<()> {
-    if hook_mask & LUA_MASKCALL != 0 {
+    if hook_mask & LUA_MASKCALL != 0 && lua_state.allow_hook {
         let event = if call_status & CIST_TAIL != 0 {
             LUA_HOOKTAILCALL
         } else {
@@ -28,6 +31,10 @@ pub fn hook_on_call(
         // ftransfer=1 (first param), ntransfer=numparams (like C Lua's luaD_hookcall)
         lua_state.run_hook(event, -1, 1, chunk.param_count as i32)?;
     }
+    #[cfg(feature = "profiler")]
+    if hook_mask & crate::lua_vm::LUA_MASKPROFILE != 0 {
+        lua_state.profile_call(chunk);
+    }
     // Initialise per-thread hook_count from per-thread base_hook_count
     if hook_mask & LUA_MASKCOUNT != 0 {
         lua_state.hook_count = lua_state.base_hook_count;
@@ -75,12 +82,6 @@ pub fn hook_check_instruction(
 ) -> LuaResult<bool> {
     let hook_mask = lua_state.hook_mask;
 
-    #[cfg(not(feature = "sandbox"))]
-    if hook_mask == 0 {
-        return Ok(false);
-    }
-
-    #[cfg(feature = "sandbox")]
     if hook_mask == 0 && !lua_state.has_active_instruction_watch() {
         return Ok(false);
     }
@@ -88,8 +89,11 @@ pub fn hook_check_instruction(
     #[cfg(feature = "sandbox")]
     lua_state.check_sandbox_runtime_limits()?;
 
-    #[cfg(feature = "sandbox")]
-    if hook_mask == 0 {
+    if hook_mask & LUA_MASKINTERRUPT != 0 {
+        lua_state.count_interrupt()?;
+    }
+
+    if hook_mask & !LUA_MASKINTERNAL == 0 {
         return Ok(lua_state.has_active_instruction_watch());
     }
 
@@ -168,13 +172,5 @@ pub fn hook_check_instruction(
             lua_state.oldpc = npci as u32;
         }
     }
-    #[cfg(not(feature = "sandbox"))]
-    {
-        Ok(lua_state.hook_mask != 0)
-    }
-
-    #[cfg(feature = "sandbox")]
-    {
-        Ok(lua_state.has_active_instruction_watch())
-    }
+    Ok(lua_state.has_active_instruction_watch())
 }
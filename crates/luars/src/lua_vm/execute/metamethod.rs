@@ -2,13 +2,13 @@ use crate::gc::TablePtr;
 use crate::lua_value::{LuaValue, lua_value_to_udvalue, udvalue_to_lua_value};
 use crate::lua_vm::call_info::CallInfo;
 use crate::lua_vm::call_info::call_status::CIST_PENDING_FINISH;
-use crate::lua_vm::execute::call::{self, call_c_function};
-use crate::lua_vm::execute::execute_loop::lua_execute;
+use crate::lua_vm::execute::call;
 use crate::lua_vm::execute::helper::{get_binop_metamethod, get_metamethod_from_meta_ptr};
 /// Metamethod operations
 ///
 /// Implements MMBIN, MMBINI, MMBINK opcodes
 /// Based on Lua 5.5 ltm.c
+use crate::lua_vm::lua_limits::EXTRA_STACK;
 use crate::lua_vm::{LuaError, LuaResult, LuaState, StkId, get_metamethod_event};
 use crate::stdlib::debug;
 
@@ -211,8 +211,56 @@ pub fn try_bin_tm(
     }
 }
 
-/// Call a metamethod with two arguments
-/// Based on Lua 5.5's luaT_callTMres - returns the result value directly
+/// Call the metamethod already pushed at `func_pos` with its `nargs`
+/// operands. Any callable works: Lua and Rust functions, userdata with a
+/// call trait and objects with `__call`, which get the same frame the CALL
+/// opcode would build for them. A value that cannot be called is reported
+/// against the metamethod, as luaG_callerror does.
+#[inline]
+fn call_metamethod(
+    lua_state: &mut LuaState,
+    metamethod: LuaValue,
+    func_pos: usize,
+    nargs: usize,
+    nresults: i32,
+) -> LuaResult<()> {
+    if !metamethod.is_function()
+        && !metamethod.ttisfulluserdata()
+        && get_metamethod_event(lua_state, &metamethod, TmKind::Call).is_none()
+    {
+        return Err(debug::callerror(lua_state, &metamethod));
+    }
+    call::call_value(lua_state, func_pos, nargs, nresults)
+}
+
+/// Stack slot where a metamethod call is assembled (`L->top` in ltm.c).
+///
+/// Inside a Lua frame this is the frame top, which EXTRA_STACK keeps room
+/// above; callers in the inline hot path already did set_top_raw(ci_top), so
+/// the sync is almost always a no-op. With no frame active (the host calling
+/// `LuaState::table_get`/`table_set` directly) it is the current stack top.
+#[inline(always)]
+fn tm_func_pos(lua_state: &mut LuaState) -> LuaResult<usize> {
+    let Some(ci) = lua_state.current_frame() else {
+        return host_tm_func_pos(lua_state);
+    };
+    let ci_top = ci.top as usize;
+    if lua_state.get_top() != ci_top {
+        lua_state.set_top_raw(ci_top);
+    }
+    Ok(ci_top)
+}
+
+#[cold]
+fn host_tm_func_pos(lua_state: &mut LuaState) -> LuaResult<usize> {
+    let top = lua_state.get_top();
+    let needed = top + EXTRA_STACK;
+    if needed > lua_state.stack_len() {
+        lua_state.grow_stack(needed)?;
+    }
+    Ok(top)
+}
+
 /// Port of Lua 5.5's luaT_callTMres from ltm.c:119
 /// ```c
 /// lu_byte luaT_callTMres (lua_State *L, const TValue *f, const TValue *p1,
@@ -239,19 +287,7 @@ pub fn call_tm_res(
     arg1: LuaValue,
     arg2: LuaValue,
 ) -> LuaResult<LuaValue> {
-    // Sync top to ci_top — callers in the inline hot path already did set_top_raw(ci_top),
-    // so the comparison is almost always true. We still check for safety in other callers.
-    let func_pos = {
-        let ci_top = lua_state
-            .current_frame()
-            .expect("metamethod call requires an active call frame")
-            .top as usize;
-        let top = lua_state.get_top();
-        if top != ci_top {
-            lua_state.set_top_raw(ci_top);
-        }
-        ci_top
-    };
+    let func_pos = tm_func_pos(lua_state)?;
 
     // Direct stack write using raw pointers — like Lua 5.5's setobj2s.
     // EXTRA_STACK (5 slots) guarantees space above ci->top.
@@ -264,44 +300,7 @@ pub fn call_tm_res(
     lua_state.set_top_raw(func_pos + 3);
 
     // Call the metamethod with nresults=1
-    if metamethod.is_lua_function() {
-        let lua_func = metamethod
-            .as_lua_function()
-            .expect("call_tmres checked lua function type before access");
-        let chunk = lua_func.chunk();
-        let upvalue_ptrs = lua_func.upvalues().as_ptr();
-
-        let new_base = func_pos + 1;
-        let caller_depth = lua_state.call_depth();
-
-        if !(chunk.param_count == 2
-            && lua_state.try_push_lua_frame_exact(
-                new_base,
-                1,
-                chunk.max_stack_size,
-                chunk as *const _,
-                upvalue_ptrs,
-            )?)
-        {
-            lua_state.push_lua_frame(
-                new_base,
-                2,
-                1,
-                chunk.param_count,
-                chunk.max_stack_size,
-                chunk as *const _,
-                upvalue_ptrs,
-            )?;
-        }
-        lua_state.inc_n_ccalls()?;
-        let r = lua_execute(lua_state, caller_depth);
-        lua_state.dec_n_ccalls();
-        r?;
-    } else if metamethod.is_cfunction() {
-        call_c_function(lua_state, func_pos, 2, 1)?;
-    } else {
-        return Err(crate::stdlib::debug::callerror(lua_state, &metamethod));
-    }
+    call_metamethod(lua_state, metamethod, func_pos, 2, 1)?;
 
     let result_val = lua_state.stack()[func_pos];
     lua_state.set_top_raw(func_pos);
@@ -314,17 +313,7 @@ pub fn call_tm_res1(
     metamethod: LuaValue,
     arg1: LuaValue,
 ) -> LuaResult<LuaValue> {
-    let func_pos = {
-        let ci_top = lua_state
-            .current_frame()
-            .expect("metamethod call requires an active call frame")
-            .top as usize;
-        let top = lua_state.get_top();
-        if top != ci_top {
-            lua_state.set_top_raw(ci_top);
-        }
-        ci_top
-    };
+    let func_pos = tm_func_pos(lua_state)?;
 
     {
         let stack = lua_state.stack_mut();
@@ -333,44 +322,7 @@ pub fn call_tm_res1(
     }
     lua_state.set_top_raw(func_pos + 2);
 
-    if metamethod.is_lua_function() {
-        let lua_func = metamethod
-            .as_lua_function()
-            .expect("call_tm_res1 checked lua function type before access");
-        let chunk = lua_func.chunk();
-        let upvalue_ptrs = lua_func.upvalues().as_ptr();
-
-        let new_base = func_pos + 1;
-        let caller_depth = lua_state.call_depth();
-
-        if !(chunk.param_count == 1
-            && lua_state.try_push_lua_frame_exact(
-                new_base,
-                1,
-                chunk.max_stack_size,
-                chunk as *const _,
-                upvalue_ptrs,
-            )?)
-        {
-            lua_state.push_lua_frame(
-                new_base,
-                1,
-                1,
-                chunk.param_count,
-                chunk.max_stack_size,
-                chunk as *const _,
-                upvalue_ptrs,
-            )?;
-        }
-        lua_state.inc_n_ccalls()?;
-        let r = lua_execute(lua_state, caller_depth);
-        lua_state.dec_n_ccalls();
-        r?;
-    } else if metamethod.is_cfunction() {
-        call_c_function(lua_state, func_pos, 1, 1)?;
-    } else {
-        return Err(crate::stdlib::debug::callerror(lua_state, &metamethod));
-    }
+    call_metamethod(lua_state, metamethod, func_pos, 1, 1)?;
 
     let result_val = lua_state.stack()[func_pos];
     lua_state.set_top_raw(func_pos);
@@ -385,17 +337,7 @@ pub fn call_tm_res_into(
     arg2: LuaValue,
     dest_stk_id: StkId,
 ) -> LuaResult<()> {
-    let func_pos = {
-        let ci_top = lua_state
-            .current_frame()
-            .expect("metamethod call requires an active call frame")
-            .top as usize;
-        let top = lua_state.get_top();
-        if top != ci_top {
-            lua_state.set_top_raw(ci_top);
-        }
-        ci_top
-    };
+    let func_pos = tm_func_pos(lua_state)?;
 
     {
         let stack = lua_state.stack_mut();
@@ -405,44 +347,7 @@ pub fn call_tm_res_into(
     }
     lua_state.set_top_raw(func_pos + 3);
 
-    if metamethod.is_lua_function() {
-        let lua_func = metamethod
-            .as_lua_function()
-            .expect("call_tm_res checked lua function type before access");
-        let chunk = lua_func.chunk();
-        let upvalue_ptrs = lua_func.upvalues().as_ptr();
-
-        let new_base = func_pos + 1;
-        let caller_depth = lua_state.call_depth();
-
-        if !(chunk.param_count == 2
-            && lua_state.try_push_lua_frame_exact(
-                new_base,
-                1,
-                chunk.max_stack_size,
-                chunk as *const _,
-                upvalue_ptrs,
-            )?)
-        {
-            lua_state.push_lua_frame(
-                new_base,
-                2,
-                1,
-                chunk.param_count,
-                chunk.max_stack_size,
-                chunk as *const _,
-                upvalue_ptrs,
-            )?;
-        }
-        lua_state.inc_n_ccalls()?;
-        let r = lua_execute(lua_state, caller_depth);
-        lua_state.dec_n_ccalls();
-        r?;
-    } else if metamethod.is_cfunction() {
-        call_c_function(lua_state, func_pos, 2, 1)?;
-    } else {
-        return Err(debug::callerror(lua_state, &metamethod));
-    }
+    call_metamethod(lua_state, metamethod, func_pos, 2, 1)?;
 
     let result = lua_state.stack()[func_pos];
     dest_stk_id.write(&result);
@@ -475,18 +380,7 @@ pub fn call_tm(
     arg2: LuaValue,
     arg3: LuaValue,
 ) -> LuaResult<()> {
-    // Sync top to ci_top
-    let func_pos = {
-        let ci_top = lua_state
-            .current_frame()
-            .expect("metamethod call requires an active call frame")
-            .top as usize;
-        let top = lua_state.get_top();
-        if top != ci_top {
-            lua_state.set_top_raw(ci_top);
-        }
-        ci_top
-    };
+    let func_pos = tm_func_pos(lua_state)?;
 
     // Direct stack write using raw pointers
     {
@@ -499,44 +393,7 @@ pub fn call_tm(
     lua_state.set_top_raw(func_pos + 4);
 
     // Call with 0 results (nresults=0)
-    if metamethod.is_lua_function() {
-        let lua_func = metamethod
-            .as_lua_function()
-            .expect("call_tm checked lua function type before access");
-        let chunk = lua_func.chunk();
-        let upvalue_ptrs = lua_func.upvalues().as_ptr();
-
-        let new_base = func_pos + 1;
-        let caller_depth = lua_state.call_depth();
-
-        if !(chunk.param_count == 3
-            && lua_state.try_push_lua_frame_exact(
-                new_base,
-                0,
-                chunk.max_stack_size,
-                chunk as *const _,
-                upvalue_ptrs,
-            )?)
-        {
-            lua_state.push_lua_frame(
-                new_base,
-                3,
-                0,
-                chunk.param_count,
-                chunk.max_stack_size,
-                chunk as *const _,
-                upvalue_ptrs,
-            )?;
-        }
-        lua_state.inc_n_ccalls()?;
-        let r = lua_execute(lua_state, caller_depth);
-        lua_state.dec_n_ccalls();
-        r?;
-    } else if metamethod.is_cfunction() {
-        call::call_c_function(lua_state, func_pos, 3, 0)?;
-    } else {
-        return Err(crate::stdlib::debug::callerror(lua_state, &metamethod));
-    }
+    call_metamethod(lua_state, metamethod, func_pos, 3, 0)?;
 
     Ok(())
 }
@@ -10,6 +10,7 @@ mod number;
 mod table_ops;
 mod vararg;
 
+pub(crate) use concat::concat;
 pub use execute_loop::lua_execute;
 pub use helper::{get_metamethod_event, get_metatable};
 pub use metamethod::TmKind;
@@ -30,17 +30,11 @@ use crate::{
         },
     },
 };
+use std::cell::Cell;
 
 macro_rules! updatetrap {
     ($trap:expr, $lua_state:expr) => {
-        #[cfg(not(feature = "sandbox"))]
-        {
-            *$trap = $lua_state.hook_mask != 0;
-        }
-        #[cfg(feature = "sandbox")]
-        {
-            *$trap = $lua_state.has_active_instruction_watch();
-        }
+        *$trap = $lua_state.has_active_instruction_watch();
     };
 }
 
@@ -56,6 +50,7 @@ pub(crate) fn op_get_tabup(
     instr: Instruction,
     code: &[Instruction],
     constants: &[LuaValue],
+    key_hints: &[Cell<u32>],
 ) -> LuaResult<()> {
     let a = instr.get_a();
     let upvalue_ptr = unsafe { *ci.upvalue_ptrs.add(instr.get_b() as usize) };
@@ -67,18 +62,24 @@ pub(crate) fn op_get_tabup(
         "GetTabUp key must be short string for fast path"
     );
 
-    if upval_value.is_table() {
-        let table = upval_value.hvalue();
-        if !*trap {
-            let next_instr = instr_at(code, *pc);
-            if next_instr.get_opcode() == OpCode::GetField && next_instr.get_b() == a {
-                let next_key = k_val(constants, next_instr.get_c());
-                debug_assert!(
-                    next_key.is_short_string(),
-                    "GetField key must be short string for fast path"
-                );
-
-                if let Some(outer) = table.impl_table.get_shortstr_fast(key) {
+    // Raw lookup first: __index only matters for absent keys, so a hit in
+    // _ENV is final even when it has a metatable.
+    if upval_value.is_table() && upval_value.hvalue().impl_table.has_hash() {
+        let table = &upval_value.hvalue().impl_table;
+        let found = match key_hints.get(instr.get_c() as usize) {
+            Some(hint) => table.get_shortstr_hinted(key, hint, dest.as_ptr()),
+            None => table.get_shortstr_into(key, dest.as_ptr()),
+        };
+        if found {
+            if !*trap {
+                let next_instr = instr_at(code, *pc);
+                if next_instr.get_opcode() == OpCode::GetField && next_instr.get_b() == a {
+                    let next_key = k_val(constants, next_instr.get_c());
+                    debug_assert!(
+                        next_key.is_short_string(),
+                        "GetField key must be short string for fast path"
+                    );
+                    let outer = dest.get_ref();
                     if outer.is_table() {
                         let inner_table = outer.hvalue();
                         if inner_table.impl_table.has_hash()
@@ -87,17 +88,10 @@ pub(crate) fn op_get_tabup(
                                 .get_shortstr_into(next_key, dest.as_ptr())
                         {
                             *pc += 1;
-                            return Ok(());
                         }
                     }
-
-                    (*base_stk).offset(a as usize).write(&outer);
-                    return Ok(());
                 }
             }
-        }
-
-        if table.impl_table.has_hash() && table.impl_table.get_shortstr_into(key, dest.as_ptr()) {
             return Ok(());
         }
     }
@@ -492,7 +486,8 @@ pub(crate) fn op_set_table(
         let table = ra.hvalue_mut();
         let meta = table.meta_ptr();
         if meta.is_null() || meta.as_mut_ref().data.no_tm(TmKind::NewIndex.into()) {
-            if !rb.is_nil() && !rb.is_integer() {
+            // nil and NaN keys go through finishset, which raises the error
+            if !rb.is_integer() && rb.get_ref().table_key_error().is_none() {
                 let (_new_key, delta) = table.impl_table.raw_set(rb.get_ref(), *rc_value);
                 if delta != 0 {
                     lua_state.gc_track_table_resize(ra.as_table_ptr(), delta);
@@ -826,6 +821,11 @@ pub(crate) fn op_set_list(
         last += extra * (1 << Instruction::SIZE_V_C);
     }
     let ra = (*base_stk).offset(a as usize).get();
+    // Compiled code always sets R[A] with NEWTABLE, but a binary chunk can
+    // reach here with anything in it
+    if !ra.ttistable() {
+        return Err(lua_state.error("SETLIST target is not a table".to_string()));
+    }
     let h = ra.hvalue_mut();
     if last > h.impl_table.asize as usize {
         h.impl_table.resize_array(last as u32);
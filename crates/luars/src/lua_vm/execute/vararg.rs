@@ -93,8 +93,12 @@ pub fn get_varargs(
 
         // Varargs are stored below base, ra is at base+a, so ranges never overlap.
         // Use copy_within to avoid heap allocation.
-        let stack = lua_state.stack_mut();
-        stack.copy_within(vararg_start..vararg_start + touse, ra_pos);
+        // With no extra arguments the frame may not have been moved and
+        // vararg_start can lie past the end of the stack
+        if touse > 0 {
+            let stack = lua_state.stack_mut();
+            stack.copy_within(vararg_start..vararg_start + touse, ra_pos);
+        }
     } else {
         // Get from vararg table at R[B]
         let table_val = { lua_state.stack()[base + b] };
@@ -0,0 +1,106 @@
+// Host hook consulted before `load`, `loadfile`, `dofile` and `require`
+// compile a chunk, so every dynamically loaded chunk can be audited or
+// refused at a single point.
+
+/// Sources above this size are passed to the hook as a digest only.
+pub const LOAD_EVENT_MAX_SOURCE: usize = 1 << 20;
+
+/// Which library function is loading the chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadOrigin {
+    Load,
+    LoadFile,
+    DoFile,
+    Require,
+}
+
+/// Whether the chunk is source text or precompiled bytecode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadMode {
+    Text,
+    Binary,
+}
+
+/// The chunk's contents as shown to the hook.
+#[derive(Debug, Clone, Copy)]
+pub enum LoadSource<'a> {
+    Bytes(&'a [u8]),
+    /// Sources longer than [`LOAD_EVENT_MAX_SOURCE`]: length and FNV-1a hash.
+    Digest {
+        len: usize,
+        hash: u64,
+    },
+}
+
+/// Position of the Lua code on whose behalf the chunk is loaded: the
+/// innermost Lua frame, e.g. the line calling `require`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadCaller {
+    /// Short source name, as in error messages.
+    pub source: String,
+    /// Current line, 0 when unknown.
+    pub line: usize,
+}
+
+/// A chunk about to be compiled or undumped.
+#[derive(Debug, Clone)]
+pub struct LoadEvent<'a> {
+    pub origin: LoadOrigin,
+    pub mode: LoadMode,
+    /// Chunk name as given to the compiler (`@path` for files).
+    pub chunkname: &'a str,
+    pub source: LoadSource<'a>,
+    /// `None` when no Lua code is running, e.g. `require` called from Rust.
+    pub caller: Option<LoadCaller>,
+}
+
+impl<'a> LoadEvent<'a> {
+    pub(crate) fn new(
+        origin: LoadOrigin,
+        mode: LoadMode,
+        chunkname: &'a str,
+        bytes: &'a [u8],
+        caller: Option<LoadCaller>,
+    ) -> Self {
+        let source = if bytes.len() > LOAD_EVENT_MAX_SOURCE {
+            LoadSource::Digest {
+                len: bytes.len(),
+                hash: fnv1a(bytes),
+            }
+        } else {
+            LoadSource::Bytes(bytes)
+        };
+        LoadEvent {
+            origin,
+            mode,
+            chunkname,
+            source,
+            caller,
+        }
+    }
+
+    /// FNV-1a hash of the whole source, stable across runs and builds.
+    pub fn content_hash(&self) -> u64 {
+        match self.source {
+            LoadSource::Bytes(bytes) => fnv1a(bytes),
+            LoadSource::Digest { hash, .. } => hash,
+        }
+    }
+}
+
+/// Verdict of a load hook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadDecision {
+    Allow,
+    /// Refuse the chunk: `load` and `loadfile` return nil and the message,
+    /// `dofile` and `require` raise it.
+    Deny(String),
+}
+
+pub(crate) type LoadHook = dyn Fn(&LoadEvent) -> LoadDecision;
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
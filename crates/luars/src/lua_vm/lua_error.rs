@@ -29,6 +29,10 @@ pub enum LuaError {
     /// Attempt to access a borrowed userdata whose parent has been
     /// garbage collected or scope has ended. Static message.
     ExpiredReference,
+
+    /// Attempt to use a shared userdata while its value is exclusively
+    /// borrowed elsewhere. Static message.
+    BorrowConflict,
 }
 
 impl LuaError {
@@ -36,6 +40,7 @@ impl LuaError {
     pub fn static_message(&self) -> Option<&'static str> {
         match self {
             LuaError::ExpiredReference => Some("attempt to use an expired reference"),
+            LuaError::BorrowConflict => Some("value is currently borrowed"),
             _ => None,
         }
     }
@@ -54,6 +59,7 @@ impl std::fmt::Display for LuaError {
             LuaError::CloseThread => write!(f, "Close Thread"),
             LuaError::ErrorInErrorHandling => write!(f, "Error In Error Handling"),
             LuaError::ExpiredReference => write!(f, "Expired Reference"),
+            LuaError::BorrowConflict => write!(f, "Borrow Conflict"),
         }
     }
 }
@@ -105,3 +111,44 @@ impl LuaFullError {
         &self.message
     }
 }
+
+/// What went wrong while closing a state with
+/// [`GlobalState::close`](super::GlobalState::close).
+///
+/// Teardown always runs to the end; errors raised by `__gc` finalizers and
+/// panics from userdata `Drop` impls are collected here instead of aborting
+/// it. An implicit drop prints the same report as a warning.
+#[derive(Debug, Clone, Default)]
+pub struct CloseReport {
+    /// Error messages of `__gc` finalizers that failed.
+    pub finalizer_errors: Vec<String>,
+    /// Panic messages of userdata `Drop` impls that panicked.
+    pub drop_panics: Vec<String>,
+}
+
+impl CloseReport {
+    /// True when the state closed cleanly.
+    pub fn is_empty(&self) -> bool {
+        self.finalizer_errors.is_empty() && self.drop_panics.is_empty()
+    }
+}
+
+impl std::fmt::Display for CloseReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "closing the state: {} __gc error(s), {} userdata drop panic(s)",
+            self.finalizer_errors.len(),
+            self.drop_panics.len()
+        )?;
+        for msg in &self.finalizer_errors {
+            write!(f, "\n  error in __gc: {}", msg)?;
+        }
+        for msg in &self.drop_panics {
+            write!(f, "\n  panic in drop: {}", msg)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CloseReport {}
@@ -81,6 +81,10 @@ pub const MAX_SRC_LEN: usize = 59;
 /// Matches Lua 5.5's MAXTAGLOOP.
 pub const MAXTAGLOOP: usize = 2000;
 
+/// Maximum number of `__call` metamethods chained in front of a call.
+/// The count lives in 4 bits of `call_status`, as in Lua 5.5's MAX_CCMT.
+pub const MAXCCMT: u8 = 15;
+
 // ===== Pattern Matching =====
 
 /// Maximum number of captures in `string.find` / `string.gmatch` patterns.
@@ -90,6 +94,11 @@ pub const LUA_MAXCAPTURES: usize = 32;
 /// Maximum match recursion depth for pattern matching.
 pub const MAXCCALLS_PATTERN: usize = 200;
 
+/// Default and maximum nesting depth for json.encode / json.decode, which
+/// recurse on the Rust stack like the pattern matcher.
+#[cfg(feature = "json")]
+pub const JSON_MAX_DEPTH: usize = 200;
+
 // ===== String Library =====
 
 /// Maximum string size (1 GB).
@@ -118,6 +118,41 @@ impl std::fmt::Debug for LuaRefValue {
     }
 }
 
+/// A Rust-held value that is not a GC root but can tell whether its object
+/// was collected.
+///
+/// Created by [`GlobalState::weak_value`]. Slots of collected objects are
+/// reused, so a raw `LuaValue` kept past a collection may point at an
+/// unrelated object; this handle records which allocation it was taken from
+/// and [`GlobalState::is_value_alive`] compares that against the slot.
+#[derive(Clone, Copy, Debug)]
+pub struct LuaWeakValue {
+    value: LuaValue,
+    /// Pool generation of the object; `None` for non-collectable values and
+    /// for objects that live outside the pools.
+    generation: Option<u32>,
+}
+
+impl LuaWeakValue {
+    pub(crate) fn new(value: LuaValue, generation: Option<u32>) -> Self {
+        Self { value, generation }
+    }
+
+    pub(crate) fn generation(&self) -> Option<u32> {
+        self.generation
+    }
+
+    /// The value, if its object is still the one this handle was taken from.
+    pub fn get(&self, global_state: &GlobalState) -> Option<LuaValue> {
+        global_state.is_value_alive(self).then_some(self.value)
+    }
+
+    /// The value without checking that its object is still alive.
+    pub fn value_unchecked(&self) -> LuaValue {
+        self.value
+    }
+}
+
 // ============================================================================
 // User-facing Ref types (mlua-inspired)
 // ============================================================================
@@ -370,6 +405,9 @@ impl LuaTableRef {
                 .main_state()
                 .error("LuaTableRef does not reference a table".to_string()));
         };
+        if let Some(metatable) = metatable {
+            vm.check_values_owned(&[metatable.to_value()])?;
+        }
 
         table.set_metatable(metatable.map(LuaTableRef::to_value));
         if let Some(gc_ptr) = value.as_gc_ptr() {
@@ -401,6 +439,10 @@ impl LuaTableRef {
     /// Set an arbitrary key-value pair.
     pub fn set_value(&self, key: LuaValue, value: LuaValue) -> LuaResult<()> {
         let vm = self.inner.global_state_mut();
+        if let Some(msg) = key.table_key_error() {
+            return Err(vm.error(msg.to_string()));
+        }
+        vm.check_values_owned(&[key, value])?;
         let table = self.inner.to_value();
         vm.raw_set(&table, key, value);
         Ok(())
@@ -411,6 +453,10 @@ impl LuaTableRef {
         let vm = self.inner.global_state_mut();
         let key = collect_single_value(vm, key, "LuaTableRef::rawset_typed(key)")?;
         let value = collect_single_value(vm, value, "LuaTableRef::rawset_typed(value)")?;
+        if let Some(msg) = key.table_key_error() {
+            return Err(vm.error(msg.to_string()));
+        }
+        vm.check_values_owned(&[key, value])?;
         let table = self.inner.to_value();
         vm.raw_set(&table, key, value);
         Ok(())
@@ -419,6 +465,7 @@ impl LuaTableRef {
     pub fn rawget_typed<K: IntoLua, V: FromLua>(&self, key: K) -> LuaResult<V> {
         let vm = self.inner.global_state_mut();
         let key = collect_single_value(vm, key, "LuaTableRef::rawget_typed(key)")?;
+        vm.check_values_owned(&[key])?;
         let table = self.inner.to_value();
         let value = vm.raw_get(&table, &key).unwrap_or_default();
         V::from_lua(value, vm.main_state()).map_err(|msg| vm.error(msg))
@@ -427,6 +474,7 @@ impl LuaTableRef {
     pub fn rawseti_typed<V: IntoLua>(&self, key: i64, value: V) -> LuaResult<()> {
         let vm = self.inner.global_state_mut();
         let value = collect_single_value(vm, value, "LuaTableRef::rawseti_typed(value)")?;
+        vm.check_values_owned(&[value])?;
         let table = self.inner.to_value();
         vm.raw_seti(&table, key, value);
         Ok(())
@@ -443,14 +491,14 @@ impl LuaTableRef {
 
     /// Get all key-value pairs (snapshot, no metamethods).
     pub fn pairs(&self) -> LuaResult<Vec<(LuaValue, LuaValue)>> {
-        let vm = self.inner.global_state();
+        let vm = self.inner.global_state_mut();
         let table = self.inner.to_value();
         vm.table_pairs(&table)
     }
 
     /// Get the array length (equivalent to Lua's `#t`).
     pub fn len(&self) -> LuaResult<usize> {
-        let vm = self.inner.global_state();
+        let vm = self.inner.global_state_mut();
         let table = self.inner.to_value();
         vm.table_length(&table)
     }
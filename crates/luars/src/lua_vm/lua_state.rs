@@ -2,38 +2,77 @@
 // Represents a single thread/coroutine execution context
 // Multiple LuaStates can share the same LuaVM (global_State)
 
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
 use crate::compiler::format_source;
 use crate::gc::{
     CreateResult, GcKind, GcObjectPtr, Pooled, ProtoPtr, StringPtr, TablePtr, ThreadPtr, UpvaluePtr,
 };
+use crate::lua_value::lua_float_to_string;
 use crate::lua_value::userdata_trait::UserDataTrait;
-use crate::lua_value::{LuaUserdata, LuaValue, LuaValueKind, UpvalueStore};
-use crate::lua_vm::async_thread::AsyncFuture;
+use crate::lua_value::verify_proto_tree;
+use crate::lua_value::{LuaUserdata, LuaValue, LuaValueKind, MultiValue, UpvalueStore};
+use crate::lua_vm::async_thread::{AsyncFuture, TaskOwner};
 use crate::lua_vm::call_info::call_status::{
-    self, CIST_C, CIST_HOOKED, CIST_RECST, CIST_XPCALL, CIST_YPCALL,
+    self, CIST_C, CIST_HOOKED, CIST_RECST, CIST_XPCALL, CIST_YCONT, CIST_YPCALL,
 };
 use crate::lua_vm::error_msg::ErrorMsg;
-use crate::lua_vm::execute::call::{call_c_function, resolve_call_chain};
+use crate::lua_vm::execute::call::{call_c_function, call_value, resolve_call_chain};
 use crate::lua_vm::execute::{self, lua_execute};
-use crate::lua_vm::lua_limits::{BASIC_STACK_SIZE, CSTACKERR, EXTRA_STACK, LUAI_MAXCSTACK};
+use crate::lua_vm::lua_limits::{
+    BASIC_STACK_SIZE, CSTACKERR, EXTRA_STACK, LUAI_MAXCSTACK, MAXCCMT,
+};
 use crate::lua_vm::safe_option::{LuaSafeState, SafeOption};
 #[cfg(feature = "sandbox")]
 use crate::lua_vm::sandbox::{SANDBOX_TIMEOUT_CHECK_INTERVAL, SandboxConfig, SandboxRuntimeLimits};
 use crate::lua_vm::{
-    CallInfo, CallInfoPtr, GlobalState, GlobalStateHandle, LuaError, LuaResult,
-    LuaTypedAsyncCallback, LuaTypedCallback, StkId, TmKind, get_metamethod_event,
+    CallInfo, CallInfoPtr, GlobalState, GlobalStateHandle, INTERRUPT_CHECK_INTERVAL, LoadCaller,
+    LoadOrigin, LuaError, LuaResult, LuaTypedAsyncCallback, LuaTypedCallback, LuaWeakValue, StkId,
+    TmKind, get_metamethod_event,
 };
 use crate::lua_vm::{
-    LUA_HOOKCALL, LUA_HOOKCOUNT, LUA_HOOKLINE, LUA_HOOKRET, LUA_HOOKTAILCALL, async_thread,
+    LUA_HOOKCALL, LUA_HOOKCOUNT, LUA_HOOKLINE, LUA_HOOKRET, LUA_HOOKTAILCALL, LUA_MASKINTERNAL,
+    LUA_MASKINTERRUPT, LUA_MASKPROFILE, async_thread,
 };
+#[cfg(feature = "profiler")]
+use crate::lua_vm::{ProfileFrame, Profiler};
 #[cfg(feature = "sandbox")]
 use crate::platform_time::unix_nanos;
 use crate::stdlib::debug::{objtypename, ordererror, pub_getfuncname};
 use crate::{
-    AsyncReturnValue, DebugInfo, FromLua, IntoLua, LuaAnyRef, LuaFullError, LuaFunctionRef,
-    LuaProto, LuaRegistrable, LuaStringRef, LuaTableRef, RefAliveToken, UserDataRef,
+    AsyncReturnValue, DebugInfo, FrameInfo, FromLua, FunctionInfo, IntoLua, LuaAnyRef,
+    LuaFullError, LuaFunctionRef, LuaProto, LuaRegistrable, LuaStringRef, LuaTableRef,
+    RefAliveToken, UserDataRef,
 };
 
+/// Per-thread state that only a thread that hit an error, a C function
+/// suspended by `pcall_stack_based_k`, or a thread the profiler recorded
+/// needs.
+#[derive(Default)]
+struct ThreadExtra {
+    /// Archived error for a dead coroutine.
+    /// Active runtime errors live in GlobalState, but dead coroutines must
+    /// preserve their own terminal error for coroutine.resume/close without
+    /// leaking that error into later unrelated calls.
+    dead_error: ErrorMsg,
+
+    /// The last error that came out of a coroutine this thread resumed,
+    /// paired with that coroutine. A dead coroutine keeps its frames, so a
+    /// traceback of that error can show where it was actually raised.
+    /// Replaced by the next such error.
+    coroutine_error: Option<(LuaValue, LuaValue)>,
+
+    /// Outcome of a `pcall_stack_based_k` call that yielded, handed to the
+    /// C function while it is called again to continue.
+    continuation: Option<(bool, usize)>,
+
+    /// Lua frames entered while the profiler records, innermost last.
+    #[cfg(feature = "profiler")]
+    profile_frames: Vec<ProfileFrame>,
+}
+
 /// Execution state for a Lua thread/coroutine
 /// This is separate from LuaVM (global_State) to support multiple execution contexts
 pub struct LuaState {
@@ -89,6 +128,10 @@ pub struct LuaState {
     /// Current countdown for count hook (per-thread, counts instructions independently).
     pub(crate) hook_count: i32,
 
+    /// Instructions left before the interrupt hook is polled again, counted
+    /// while `LUA_MASKINTERRUPT` is set.
+    pub(crate) interrupt_count: u32,
+
     /// Last instruction PC (0-based index) seen by the line hook (like C Lua's L->oldpc).
     /// Stored per-thread so the main execution loop doesn't waste a register on it.
     /// Used by hook_check_instruction to detect line changes via changedline logic.
@@ -123,11 +166,9 @@ pub struct LuaState {
     /// matching C Lua's behavior.
     pub(crate) dead: bool,
 
-    /// Archived error for a dead coroutine.
-    /// Active runtime errors live in GlobalState, but dead coroutines must
-    /// preserve their own terminal error for coroutine.resume/close without
-    /// leaking that error into later unrelated calls.
-    dead_error: ErrorMsg,
+    /// Error and continuation state most threads never use, boxed on first
+    /// use so an idle coroutine doesn't carry it. See [`ThreadExtra`].
+    extra: Option<Box<ThreadExtra>>,
 
     /// Whether close_tbc_with_error is currently running on this thread.
     /// Used to detect re-entrant coroutine.close() calls from __close handlers.
@@ -160,9 +201,22 @@ impl LuaState {
         is_main: bool,
         safe_option: SafeOption,
     ) -> Self {
+        // Coroutines get their own stack cap, and start with no stack at all:
+        // it grows on demand through resize(), so an idle coroutine costs
+        // little more than the state itself.
+        let (stack, stack_limit) = if is_main {
+            (
+                Vec::with_capacity(BASIC_STACK_SIZE),
+                safe_option.max_stack_size,
+            )
+        } else {
+            (Vec::new(), safe_option.max_coroutine_stack)
+        };
+        let mut safe_state = LuaSafeState::from(safe_option);
+        safe_state.max_stack_size = stack_limit;
         Self {
             global_state,
-            stack: Vec::with_capacity(BASIC_STACK_SIZE),
+            stack,
             thread: ThreadPtr::null(),
             stack_top: 0, // Start with empty stack (Lua's L->top.p = L->stack)
             call_stack: Vec::with_capacity(call_stack_size),
@@ -175,15 +229,16 @@ impl LuaState {
             hook_mask: 0,
             base_hook_count: 0,
             hook_count: 0,
+            interrupt_count: 0,
             oldpc: 0,
             ftransfer: 0,
             ntransfer: 0,
-            safe_state: safe_option.into(),
+            safe_state,
             is_main,
             tbc_list: Vec::new(),
             yielded: false,
             dead: false,
-            dead_error: ErrorMsg::None,
+            extra: None,
             is_closing: false,
             nny: if is_main { 1 } else { 0 },
             pending_future: None,
@@ -689,15 +744,33 @@ impl LuaState {
 
     /// Ensure the physical stack has room for `additional` more values beyond stack_top.
     /// Checks both the logical limit (max_stack_size) and physical capacity (Vec length).
-    /// After this call, `push_value_unchecked` can be safely used `additional` times.
+    /// See [`push_values`](Self::push_values) for pushing a known number of
+    /// results in one go.
+    /// Raises "stack overflow (too many results)" when the room cannot be made.
     #[inline]
     pub fn ensure_stack_capacity(&mut self, additional: usize) -> LuaResult<()> {
-        let needed = self.stack_top + additional;
-        if needed > self.safe_state.max_stack_size {
-            return Err(LuaError::StackOverflow);
+        self.check_stack_msg(additional, "too many results")
+    }
+
+    /// luaL_checkstack: like `ensure_stack_capacity`, but the error reads
+    /// "stack overflow (msg)". Use it wherever a user-controlled count turns
+    /// into a number of pushed values.
+    #[inline]
+    pub fn check_stack_msg(&mut self, additional: usize, msg: &str) -> LuaResult<()> {
+        let needed = self.stack_top.saturating_add(additional);
+        if needed <= self.stack.len() && needed <= self.safe_state.max_stack_size {
+            return Ok(());
         }
-        if needed > self.stack.len() {
-            self.resize(needed)?;
+        self.grow_checked(needed, msg)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn grow_checked(&mut self, needed: usize, msg: &str) -> LuaResult<()> {
+        // resize() checks the allocation with try_reserve
+        if needed > self.safe_state.max_stack_size || self.resize(needed).is_err() {
+            let label = self.stack_overflow_label();
+            return Err(self.error(format!("{label} ({msg})")));
         }
         Ok(())
     }
@@ -746,10 +819,13 @@ impl LuaState {
     /// Set stack value at absolute index
     #[inline(always)]
     pub fn stack_set(&mut self, index: usize, value: LuaValue) -> LuaResult<()> {
+        self.check_value_owned(&value)?;
         if index >= self.safe_state.max_stack_size {
             self.error(format!(
-                "stack overflow: attempted to set index {} exceeding maximum {}",
-                index, self.safe_state.max_stack_size
+                "{}: attempted to set index {} exceeding maximum {}",
+                self.stack_overflow_label(),
+                index,
+                self.safe_state.max_stack_size
             ));
             return Err(LuaError::StackOverflow);
         }
@@ -765,22 +841,80 @@ impl LuaState {
     fn resize(&mut self, new_size: usize) -> LuaResult<()> {
         if new_size > self.safe_state.max_stack_size {
             self.error(format!(
-                "stack overflow: attempted to resize to {} exceeding maximum {}",
-                new_size, self.safe_state.max_stack_size
+                "{}: attempted to resize to {} exceeding maximum {}",
+                self.stack_overflow_label(),
+                new_size,
+                self.safe_state.max_stack_size
             ));
             return Err(LuaError::StackOverflow);
         }
         let capacity = self.stack.capacity();
+        if new_size > self.stack.len()
+            && self.stack.try_reserve(new_size - self.stack.len()).is_err()
+        {
+            self.error(format!(
+                "{}: not enough memory to grow the stack to {new_size}",
+                self.stack_overflow_label()
+            ));
+            return Err(LuaError::StackOverflow);
+        }
         self.stack.resize(new_size, LuaValue::nil());
         if self.stack.capacity() > capacity {
             // If the vector had to reallocate, we need to update all cached stack pointers
             self.fix_open_upvalue_pointers();
             self.fix_call_info_base_stk();
+            self.track_stack_growth(capacity);
         }
 
         Ok(())
     }
 
+    /// Coroutine stacks have their own cap (`SafeOption::max_coroutine_stack`),
+    /// so their overflow says which limit was hit.
+    fn stack_overflow_label(&self) -> &'static str {
+        if self.is_main {
+            "stack overflow"
+        } else {
+            "coroutine stack overflow"
+        }
+    }
+
+    /// Charge the GC for stack slots gained (or credit it for slots given
+    /// back) since the stack had `old_capacity`.
+    fn track_stack_growth(&mut self, old_capacity: usize) {
+        let delta = (self.stack.capacity() as isize - old_capacity as isize)
+            * std::mem::size_of::<LuaValue>() as isize;
+        let thread = self.thread_ptr();
+        self.global_state_mut()
+            .gc
+            .track_thread_resize(thread, delta);
+    }
+
+    /// Give back stack a suspended coroutine no longer uses, like
+    /// luaD_shrinkstack: once the allocation is more than four times the
+    /// live part (the top and every active frame's registers), cut it to
+    /// twice the live part, never below `BASIC_STACK_SIZE`.
+    fn shrink_stack(&mut self) {
+        let frames_top = self.call_stack[..self.call_depth]
+            .iter()
+            .map(|ci| ci.top as usize)
+            .max()
+            .unwrap_or(0);
+        let in_use = self.stack_top.max(frames_top) + EXTRA_STACK;
+        let goal = (in_use * 2).max(BASIC_STACK_SIZE);
+        if self.stack.capacity() <= goal * 2 {
+            return;
+        }
+        let old_capacity = self.stack.capacity();
+        self.stack.truncate(goal);
+        self.stack.shrink_to(goal);
+        if self.stack.capacity() != old_capacity {
+            self.fix_open_upvalue_pointers();
+            self.fix_call_info_base_stk();
+            self.track_stack_growth(old_capacity);
+        }
+    }
+
     /// Fix all open upvalue cached pointers after a Vec reallocation.
     /// Must be called whenever the stack Vec's internal buffer moves
     /// (e.g., after Vec::push triggers a reallocation).
@@ -851,9 +985,11 @@ impl LuaState {
 
     /// Set a runtime error message.
     ///
-    /// Mirrors Lua 5.5's luaG_runerror boundary: VM/runtime errors raised from
-    /// an active Lua frame carry source information immediately, while errors
-    /// raised from C/host frames keep their raw message.
+    /// Errors raised from an active Lua frame carry its position, as
+    /// luaG_runerror does. Errors raised from a C/host function carry the
+    /// position of the Lua code that called it, as luaL_error's
+    /// `luaL_where(L, 1)` does; when the caller is itself a C function (e.g.
+    /// `pcall(string.rep)`) the message stays raw.
     #[cold]
     #[inline(never)]
     pub fn error(&mut self, msg: String) -> LuaError {
@@ -866,6 +1002,19 @@ impl LuaState {
         let Some(ci) = self.current_frame() else {
             return msg;
         };
+        // Level 1 for C functions: the frame that called them
+        let ci = if ci.is_lua() {
+            ci
+        } else {
+            match self
+                .call_depth
+                .checked_sub(2)
+                .and_then(|i| self.get_frame(i))
+            {
+                Some(caller) => caller,
+                None => return msg,
+            }
+        };
 
         let Some(location) = Self::frame_error_location(ci) else {
             return msg;
@@ -880,6 +1029,20 @@ impl LuaState {
 
     #[inline(always)]
     fn frame_error_location(ci: &CallInfo) -> Option<String> {
+        let (source, line) = Self::frame_source_line(ci)?;
+        let chunk = unsafe { &*ci.chunk_ptr };
+
+        Some(if line > 0 {
+            format!("{}:{}: ", source, line)
+        } else if chunk.line_info.is_empty() {
+            format!("{}:?: ", source)
+        } else {
+            format!("{}: ", source)
+        })
+    }
+
+    /// Short source name and current line (0 when unknown) of a Lua frame.
+    fn frame_source_line(ci: &CallInfo) -> Option<(String, usize)> {
         if !ci.is_lua() || ci.chunk_ptr.is_null() {
             return None;
         }
@@ -894,16 +1057,29 @@ impl LuaState {
         } else {
             0
         };
+        Some((source, line))
+    }
 
-        Some(if line > 0 {
-            format!("{}:{}: ", source, line)
-        } else if chunk.line_info.is_empty() {
-            format!("{}:?: ", source)
-        } else {
-            format!("{}: ", source)
+    /// The innermost running Lua frame, as reported to the load hook.
+    pub(crate) fn load_caller(&self) -> Option<LoadCaller> {
+        (0..self.call_depth).rev().find_map(|i| {
+            let (source, line) = Self::frame_source_line(self.get_frame(i)?)?;
+            Some(LoadCaller { source, line })
         })
     }
 
+    /// Load a file on behalf of `loadfile`, `dofile` or `require`,
+    /// consulting the load hook first.
+    pub(crate) fn load_audited_file(
+        &mut self,
+        path: &str,
+        origin: LoadOrigin,
+    ) -> Result<ProtoPtr, String> {
+        let caller = self.load_caller();
+        self.global_state_mut()
+            .load_audited_file(path, Some((origin, caller)))
+    }
+
     /// Set the current error object for protected calls to retrieve.
     #[cold]
     #[inline(never)]
@@ -958,34 +1134,72 @@ impl LuaState {
         let _ = self.global_state_mut().take_error();
     }
 
+    #[inline]
+    fn extra_mut(&mut self) -> &mut ThreadExtra {
+        self.extra.get_or_insert_with(Box::default)
+    }
+
     #[inline(always)]
     pub fn archive_dead_error(&mut self, err: ErrorMsg) {
-        self.dead_error = err;
+        self.extra_mut().dead_error = err;
     }
 
     #[inline(always)]
     pub fn dead_error(&self) -> &ErrorMsg {
-        &self.dead_error
+        const NO_ERROR: &ErrorMsg = &ErrorMsg::None;
+        self.extra
+            .as_ref()
+            .map_or(NO_ERROR, |extra| &extra.dead_error)
     }
 
     #[inline(always)]
     pub fn take_dead_error(&mut self) -> ErrorMsg {
-        std::mem::take(&mut self.dead_error)
+        self.extra
+            .as_mut()
+            .map(|extra| std::mem::take(&mut extra.dead_error))
+            .unwrap_or_default()
     }
 
     #[inline(always)]
     pub fn clear_dead_error(&mut self) {
-        self.dead_error = ErrorMsg::None;
+        if let Some(extra) = self.extra.as_mut() {
+            extra.dead_error = ErrorMsg::None;
+        }
+    }
+
+    /// Record that `error` came out of resuming `thread`
+    /// (`coroutine.resume` or a `coroutine.wrap` function).
+    pub(crate) fn set_coroutine_error(&mut self, error: LuaValue, thread: LuaValue) {
+        self.extra_mut().coroutine_error = Some((error, thread));
+    }
+
+    /// The coroutine `error` came out of, if it is the last coroutine error
+    /// this thread saw and that coroutine still has its frames.
+    pub(crate) fn coroutine_error_source(&self, error: &LuaValue) -> Option<&LuaState> {
+        let (recorded, thread) = self.extra.as_ref()?.coroutine_error.as_ref()?;
+        if error.is_nil() || recorded != error {
+            return None;
+        }
+        let co = thread.as_thread_mut()?;
+        (co.call_depth > 0).then_some(&*co)
+    }
+
+    pub(crate) fn coroutine_error_value(&self) -> Option<(LuaValue, LuaValue)> {
+        self.extra.as_ref()?.coroutine_error
     }
 
     /// Generate a Lua-style stack traceback
     /// Similar to luaL_traceback in lauxlib.c
     pub fn generate_traceback(&self) -> String {
-        let mut result = String::new();
-
         // Only iterate through valid frames (up to call_depth)
         // call_stack Vec may contain residual data beyond call_depth
-        let valid_frames = &self.call_stack[..self.call_depth];
+        Self::traceback_of_frames(&self.call_stack[..self.call_depth])
+    }
+
+    /// Traceback lines for `frames`, oldest first in the slice and newest
+    /// first in the output.
+    pub(crate) fn traceback_of_frames(valid_frames: &[CallInfoPtr]) -> String {
+        let mut result = String::new();
 
         // Iterate through call stack from newest to oldest
         // Start from level 0 (most recent frame, not counting the error frame itself)
@@ -1017,7 +1231,9 @@ impl LuaState {
 
                     // Determine function description
                     // Main chunk has linedefined == 0
-                    let what = if chunk.linedefined == 0 {
+                    let what = if let Some(name) = chunk.name.as_deref() {
+                        format!("function '{}'", name)
+                    } else if chunk.linedefined == 0 {
                         "main chunk".to_string()
                     } else {
                         format!("function <{}:{}>", source_display, chunk.linedefined)
@@ -1053,9 +1269,28 @@ impl LuaState {
         std::mem::take(&mut self.yield_values)
     }
 
-    /// Store a pending async future (called by async function wrappers before yielding)
+    /// Store a pending async future (called by async function wrappers before yielding).
+    /// The future is tracked as a task of this coroutine until it finishes,
+    /// under the VM's default task timeout, if any.
     #[inline(always)]
     pub fn set_pending_future(&mut self, future: AsyncFuture) {
+        self.set_pending_future_with_timeout(future, None);
+    }
+
+    /// Like [`set_pending_future`](Self::set_pending_future), but the task
+    /// fails with "async task timed out" if it has not finished `timeout`
+    /// after it was started. `None` uses the VM's default timeout (see
+    /// [`GlobalState::set_async_task_timeout`]).
+    pub fn set_pending_future_with_timeout(
+        &mut self,
+        future: AsyncFuture,
+        timeout: Option<Duration>,
+    ) {
+        let owner = TaskOwner::of(self);
+        let future =
+            self.global_state()
+                .async_tasks
+                .track(owner, self.global_state, future, timeout);
         self.pending_future = Some(future);
     }
 
@@ -1619,8 +1854,10 @@ impl LuaState {
     pub fn grow_stack(&mut self, needed: usize) -> LuaResult<()> {
         if needed > self.safe_state.max_stack_size {
             self.error(format!(
-                "stack overflow: attempted to grow stack to {} exceeding maximum {}",
-                needed, self.safe_state.max_stack_size
+                "{}: attempted to grow stack to {} exceeding maximum {}",
+                self.stack_overflow_label(),
+                needed,
+                self.safe_state.max_stack_size
             ));
             return Err(LuaError::StackOverflow);
         }
@@ -2007,6 +2244,19 @@ impl LuaState {
         }
     }
 
+    /// Replace an existing argument of the current call in place, like the
+    /// C API converting a stack slot (keeps a converted value anchored).
+    pub(crate) fn set_arg(&mut self, index: usize, value: LuaValue) {
+        if index == 0 || self.call_depth == 0 {
+            return;
+        }
+        let frame = &self.call_stack[self.call_depth - 1];
+        let stack_index = frame.base + index - 1;
+        if stack_index < frame.top as usize && stack_index < self.stack.len() {
+            self.stack[stack_index] = value;
+        }
+    }
+
     /// Get and convert a specific argument using the regular `FromLua` bridge.
     #[inline]
     pub fn get_arg_as<T: FromLua>(&mut self, index: usize) -> LuaResult<Option<T>> {
@@ -2058,10 +2308,12 @@ impl LuaState {
 
     #[inline(always)]
     pub fn push_value(&mut self, value: LuaValue) -> LuaResult<()> {
+        self.check_value_owned(&value)?;
         // Check stack limit (Lua's luaD_checkstack equivalent)
         if self.stack_top >= self.safe_state.max_stack_size {
             self.error(format!(
-                "stack overflow: attempted to push value exceeding maximum {}",
+                "{}: attempted to push value exceeding maximum {}",
+                self.stack_overflow_label(),
                 self.safe_state.max_stack_size
             ));
             return Err(LuaError::StackOverflow);
@@ -2093,6 +2345,24 @@ impl LuaState {
         Ok(())
     }
 
+    /// Push a known number of results with a single stack reservation
+    /// (luaL_checkstack with `msg`), storing them directly instead of going
+    /// through [`push_value`](Self::push_value) one by one. Meant for values
+    /// a library function builds itself, such as `string.byte` results.
+    pub(crate) fn push_values<I>(&mut self, values: I, msg: &str) -> LuaResult<usize>
+    where
+        I: ExactSizeIterator<Item = LuaValue>,
+    {
+        let count = values.len();
+        self.check_stack_msg(count, msg)?;
+        let top = self.stack_top;
+        for (slot, value) in self.stack[top..top + count].iter_mut().zip(values) {
+            *slot = value;
+        }
+        self.stack_top = top + count;
+        Ok(count)
+    }
+
     // ===== Object Creation =====
 
     /// Create a raw table value.
@@ -2181,6 +2451,34 @@ impl LuaState {
         self.global_state_mut().create_bytes(bytes)
     }
 
+    /// Create bytes `range` of string `s`, sharing the bytes of a long
+    /// string instead of copying them when the result is long too.
+    ///
+    /// The returned LuaValue is not rooted by the API itself. Callers must root
+    /// it before any subsequent allocation or GC step.
+    #[inline]
+    pub fn create_substring(
+        &mut self,
+        s: &LuaValue,
+        range: std::ops::Range<usize>,
+    ) -> CreateResult {
+        self.global_state_mut().create_substring(s, range)
+    }
+
+    /// Bytes of string `value`, without copying. `None` if it is not a
+    /// string.
+    ///
+    /// The slice borrows the state, so nothing can run the collector while
+    /// it is held; `value` itself must still be reachable (on the stack or
+    /// rooted) when this is called.
+    #[inline]
+    pub fn get_string_bytes(&self, value: &LuaValue) -> Option<&[u8]> {
+        let bytes = value.as_bytes()?;
+        // The bytes belong to the GC object, not to `value`: tie them to the
+        // state, which has to be borrowed mutably to collect anything
+        Some(unsafe { std::slice::from_raw_parts(bytes.as_ptr(), bytes.len()) })
+    }
+
     /// Create a registry-rooted string handle from bytes.
     #[inline]
     pub fn create_bytes_ref(&mut self, bytes: &[u8]) -> LuaResult<LuaStringRef> {
@@ -2239,6 +2537,46 @@ impl LuaState {
         self.global_state_mut().create_userdata(ud)
     }
 
+    /// Create a GC-managed userdata that **shares** `handle` with the host.
+    ///
+    /// Unlike [`create_userdata`](Self::create_userdata), calling this twice
+    /// with clones of one handle gives two Lua values over the same object:
+    /// mutations through either are visible through the other and from
+    /// Rust, and the two compare equal. Accesses borrow through the
+    /// `RefCell`, so Lua raises "value is currently borrowed" instead of
+    /// touching a value the host holds a `RefMut` to. Collecting the value
+    /// drops its clone of the handle.
+    ///
+    /// The returned LuaValue is not rooted by the API itself.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let player = Rc::new(RefCell::new(Player::new("Alice", 100)));
+    /// let a = state.create_userdata_shared(player.clone())?;
+    /// let b = state.create_userdata_shared(player.clone())?;
+    /// ```
+    #[inline]
+    pub fn create_userdata_shared<T: UserDataTrait>(
+        &mut self,
+        handle: Rc<RefCell<T>>,
+    ) -> CreateResult {
+        let ud = LuaUserdata::new_shared(handle);
+        self.global_state_mut().create_userdata(ud)
+    }
+
+    /// [`create_userdata_shared`](Self::create_userdata_shared) for a
+    /// thread-safe handle. Accesses use `Mutex::try_lock`, so any lock held
+    /// elsewhere is a conflict.
+    #[cfg(feature = "unsafe-send")]
+    #[inline]
+    pub fn create_userdata_shared_mutex<T: UserDataTrait + Send>(
+        &mut self,
+        handle: std::sync::Arc<std::sync::Mutex<T>>,
+    ) -> CreateResult {
+        let ud = LuaUserdata::new_shared_mutex(handle);
+        self.global_state_mut().create_userdata(ud)
+    }
+
     /// Create a raw RClosure value.
     ///
     /// The returned LuaValue is not rooted by the API itself. Callers must root
@@ -2292,18 +2630,38 @@ impl LuaState {
 
     // ===== Global Access =====
 
-    /// Get global variable
+    /// Get global variable (raw: `__index` on `_G` is not consulted).
+    /// Use [`get_global_with_meta`](Self::get_global_with_meta) to read it
+    /// the way Lua code does.
     #[inline]
     pub fn get_global_value(&mut self, name: &str) -> LuaResult<Option<LuaValue>> {
         self.global_state_mut().get_global(name)
     }
 
-    /// Set global variable
+    /// Set global variable (raw: `__newindex` on `_G` is not consulted).
+    /// Use [`set_global_with_meta`](Self::set_global_with_meta) to assign it
+    /// the way Lua code does.
     #[inline]
     pub fn set_global_value(&mut self, name: &str, value: LuaValue) -> LuaResult<()> {
         self.global_state_mut().set_global(name, value)
     }
 
+    /// Get global variable like `_G[name]` in Lua, going through `__index`
+    /// on the globals table. Returns `None` for nil.
+    pub fn get_global_with_meta(&mut self, name: &str) -> LuaResult<Option<LuaValue>> {
+        let key = self.create_string(name)?;
+        let globals = self.global_state().global;
+        Ok(self.table_get(&globals, &key)?.filter(|v| !v.is_nil()))
+    }
+
+    /// Set global variable like `_G[name] = value` in Lua, going through
+    /// `__newindex` on the globals table.
+    pub fn set_global_with_meta(&mut self, name: &str, value: LuaValue) -> LuaResult<()> {
+        let key = self.create_string(name)?;
+        let globals = self.global_state().global;
+        self.table_set(&globals, key, value)
+    }
+
     // ===== Convenience API =====
 
     /// Compile source code and return a callable function value with _ENV wired.
@@ -2317,6 +2675,29 @@ impl LuaState {
             .create_loaded_function(chunk, UpvalueStore::from_single(env_upval))
     }
 
+    /// Turn an already compiled or hand-built prototype into a callable
+    /// function, with the globals table as its `_ENV`.
+    ///
+    /// The prototype and all nested ones are checked by the same verifier
+    /// as binary chunks, and like them it is refused when
+    /// [`SafeOption::allow_load_bytecode`] is off.
+    pub fn load_proto(&mut self, chunk: LuaProto) -> LuaResult<LuaValue> {
+        if !self.allow_load_bytecode() {
+            return Err(self
+                .error("attempt to load a prototype (bytecode loading is disabled)".to_string()));
+        }
+        if chunk.upvalue_count > 1 {
+            return Err(self.error("main chunk must have at most one upvalue (_ENV)".to_string()));
+        }
+        if let Err(msg) = verify_proto_tree(&chunk) {
+            return Err(self.error(format!("bad prototype ({})", msg)));
+        }
+        let global = self.global_state().global;
+        let env_upval = self.global_state_mut().create_upvalue_closed(global)?;
+        self.global_state_mut()
+            .create_loaded_function(chunk, UpvalueStore::from_single(env_upval))
+    }
+
     #[cfg(feature = "sandbox")]
     pub fn load_sandboxed(&mut self, source: &str, config: &SandboxConfig) -> LuaResult<LuaValue> {
         let chunk = self.compile_chunk(source)?;
@@ -2362,14 +2743,16 @@ impl LuaState {
     /// Call a function value with arguments.
     ///
     /// Unlike the LuaVM version, this operates on the *current* coroutine state
-    /// and is safe to call from within a CFunction.
+    /// and is safe to call from within a CFunction. Arguments and results are
+    /// a [`MultiValue`], so nils anywhere in either list are kept.
     #[inline]
     pub fn call_function(
         &mut self,
         func: LuaValue,
-        args: Vec<LuaValue>,
-    ) -> LuaResult<Vec<LuaValue>> {
-        self.call(func, args)
+        args: impl Into<MultiValue>,
+    ) -> LuaResult<MultiValue> {
+        let args = args.into();
+        self.call_args(func, args.as_slice()).map(MultiValue::from)
     }
 
     /// Look up a global function by name and call it.
@@ -2467,9 +2850,8 @@ impl LuaState {
 
     // ===== Execute =====
 
-    /// Compile and execute a Lua source string, returning results.
-    ///
-    /// This is a convenience proxy for `LuaVM::execute_string`.
+    /// Compile and execute a Lua source string, returning every value the
+    /// chunk returns (nils included, so `return 1, nil, 3` gives three).
     ///
     /// # Example
     /// ```ignore
@@ -2478,7 +2860,20 @@ impl LuaState {
     /// ```
     pub fn execute(&mut self, source: &str) -> LuaResult<Vec<LuaValue>> {
         let func = self.load(source)?;
-        self.call(func, vec![])
+        self.call_args(func, &[])
+    }
+
+    /// Like [`execute`](Self::execute), for chunks that return one value:
+    /// returns the first result, or nil when the chunk returns nothing.
+    ///
+    /// # Example
+    /// ```ignore
+    /// let sum = state.execute_single("return 1 + 2")?;
+    /// assert_eq!(sum.as_integer(), Some(3));
+    /// ```
+    pub fn execute_single(&mut self, source: &str) -> LuaResult<LuaValue> {
+        let results = self.execute(source)?;
+        Ok(results.into_iter().next().unwrap_or_default())
     }
 
     #[cfg(feature = "sandbox")]
@@ -2499,20 +2894,18 @@ impl LuaState {
             let func = state
                 .global_state_mut()
                 .create_function(chunk, UpvalueStore::from_single(env_upval))?;
-            state.call(func, vec![])
+            state.call_args(func, &[])
         })
     }
 
-    /// Execute a pre-compiled chunk, returning results.
-    ///
-    /// This is a convenience proxy for `LuaVM::execute`.
+    /// Execute a pre-compiled chunk, returning all of its results.
     pub fn execute_chunk(&mut self, chunk: ProtoPtr) -> LuaResult<Vec<LuaValue>> {
         let global = self.global_state().global;
         let env_upval = self.global_state_mut().create_upvalue_closed(global)?;
         let func = self
             .global_state_mut()
             .create_function(chunk, UpvalueStore::from_single(env_upval))?;
-        self.call(func, vec![])
+        self.call_args(func, &[])
     }
 
     pub fn get_global_as<T: FromLua>(&mut self, name: &str) -> LuaResult<Option<T>> {
@@ -2525,9 +2918,10 @@ impl LuaState {
         }
     }
 
+    /// Syntax errors carry their own chunk position; never add the caller's.
     #[inline]
     pub fn compile_error(&mut self, message: impl Into<String>) -> LuaError {
-        self.error(message.into());
+        let _ = self.global_state_mut().error(message.into());
         LuaError::CompileError
     }
 
@@ -2553,19 +2947,75 @@ impl LuaState {
             .map_err(|msg| self.error(msg))
     }
 
+    /// Load the contents of a Lua file read some other way, such as from
+    /// stdin: like [`dofile`](Self::dofile) it skips a BOM and `#!` line and
+    /// accepts binary chunks. Returns the chunk as a function with the
+    /// globals table as its `_ENV`.
+    pub fn load_file_bytes(&mut self, bytes: &[u8], chunk_name: &str) -> LuaResult<LuaValue> {
+        let proto = self
+            .global_state_mut()
+            .load_proto_from_bytes(bytes, chunk_name)
+            .map_err(|msg| self.error(msg))?;
+        let global = self.global_state().global;
+        let env_upval = self.global_state_mut().create_upvalue_closed(global)?;
+        self.global_state_mut()
+            .create_function(proto, UpvalueStore::from_single(env_upval))
+    }
+
     pub fn get_error_message(&mut self, e: LuaError) -> String {
         self.get_error_msg(e)
     }
 
+    /// Intern the message a protected call returns for a caught error. Exempt
+    /// from the memory limit, like PUC's preallocated `memerrmsg`, so that a
+    /// pcall can still report an out-of-memory error.
+    fn create_error_string(&mut self, msg: &str) -> CreateResult {
+        let gc = &mut self.global_state_mut().gc;
+        gc.disable_memory_check();
+        let result = self.create_string(msg);
+        self.global_state_mut().gc.enable_memory_check();
+        result
+    }
+
     pub fn get_full_error(&mut self, e: LuaError) -> LuaFullError {
-        let message = self.get_error_msg(e);
         let message = match e {
-            LuaError::CompileError => message,
-            _ => self.render_error_message(&message),
+            LuaError::CompileError => self.get_error_msg(e),
+            _ => {
+                let message = match self.global_state().get_error_object_ref().copied() {
+                    Some(obj) if !obj.is_nil() && !obj.is_string() && !obj.is_number() => {
+                        self.global_state_mut().take_error();
+                        self.error_object_message(obj)
+                    }
+                    _ => self.get_error_msg(e),
+                };
+                self.render_error_message(&message)
+            }
         };
         LuaFullError { kind: e, message }
     }
 
+    /// What lua.c's message handler reports for an error object that is not
+    /// a string: its `__tostring` text, or just its type. The metamethod runs
+    /// protected, so one that raises or returns a non-string cannot replace
+    /// the error being reported.
+    fn error_object_message(&mut self, obj: LuaValue) -> String {
+        if obj.ttisfulluserdata()
+            && let Some(ud) = obj.as_userdata_mut()
+            && let Ok(trait_obj) = ud.get_trait()
+            && let Some(s) = trait_obj.lua_tostring()
+        {
+            return s;
+        }
+        if let Some(mm) = get_metamethod_event(self, &obj, TmKind::ToString)
+            && let Ok((true, results)) = self.pcall_args(mm, &[obj])
+            && let Some(s) = results.first().and_then(|v| v.as_str())
+        {
+            return s.to_string();
+        }
+        self.clear_error();
+        format!("(error object is a {} value)", obj.type_name())
+    }
+
     fn render_error_message(&mut self, error_msg: &str) -> String {
         let result = (|| -> LuaResult<String> {
             let debug_table = match self.get_global_value("debug")? {
@@ -2583,7 +3033,7 @@ impl LuaState {
 
             let msg_val = self.create_string(error_msg)?;
             let level_val = LuaValue::integer(1);
-            let (success, results) = self.pcall(traceback_func, vec![msg_val, level_val])?;
+            let (success, results) = self.pcall_args(traceback_func, &[msg_val, level_val])?;
 
             if success
                 && let Some(result) = results.first()
@@ -2602,7 +3052,23 @@ impl LuaState {
     }
 
     fn fallback_error_message(&self, error_msg: &str) -> String {
-        let traceback = self.generate_traceback();
+        let mut traceback = String::new();
+        let mut thread = self;
+        // An error raised inside a coroutine lists the coroutine's frames
+        // (innermost coroutine first) before those of the thread resuming it.
+        let mut sources = Vec::new();
+        while let Some((err, _)) = thread.coroutine_error_value()
+            && err.as_str() == Some(error_msg)
+            && let Some(co) = thread.coroutine_error_source(&err)
+        {
+            sources.push(co);
+            thread = co;
+        }
+        for co in sources.iter().rev() {
+            traceback.push_str(&co.generate_traceback());
+            traceback.push_str("\t(coroutine boundary)\n");
+        }
+        traceback.push_str(&self.generate_traceback());
         if !traceback.is_empty() {
             format!("{}\nstack traceback:\n{}", error_msg, traceback)
         } else {
@@ -2721,8 +3187,39 @@ impl LuaState {
     /// // Write:
     /// state.register_type_of::<Point>("Point")?;
     /// ```
+    ///
+    /// Unlike `register_type`, this also gives the type its shared metatable;
+    /// see [`GlobalState::register_type_of`](crate::GlobalState::register_type_of).
     pub fn register_type_of<T: LuaRegistrable>(&mut self, name: &str) -> LuaResult<()> {
-        self.register_type(name, T::lua_static_methods())
+        self.global_state_mut()
+            .register_userdata_metatable(std::any::TypeId::of::<T>(), name)?;
+        self.register_type(name, T::lua_static_methods())?;
+        if let Some(constructor) = T::lua_constructor() {
+            let class_table = self.get_global_value(name)?.unwrap_or_default();
+            self.global_state_mut()
+                .set_type_constructor(&class_table, constructor)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`register_type_of`](Self::register_type_of), additionally
+    /// installing the field observers from `options`; see
+    /// [`TypeOptions`](crate::TypeOptions).
+    pub fn register_type_of_with<T: LuaRegistrable>(
+        &mut self,
+        name: &str,
+        options: crate::TypeOptions,
+    ) -> LuaResult<()> {
+        self.register_type_of::<T>(name)?;
+        self.global_state_mut()
+            .set_userdata_observers(std::any::TypeId::of::<T>(), options.into());
+        Ok(())
+    }
+
+    /// See [`GlobalState::set_userdata_metatable_protected`](crate::GlobalState::set_userdata_metatable_protected).
+    pub fn set_userdata_metatable_protected<T: 'static>(&mut self, protected: bool) -> bool {
+        self.global_state_mut()
+            .set_userdata_metatable_protected::<T>(protected)
     }
 
     // ===== Table Operations =====
@@ -2734,6 +3231,8 @@ impl LuaState {
 
     /// Get value from table with __index metamethod support
     pub fn table_get(&mut self, table: &LuaValue, key: &LuaValue) -> LuaResult<Option<LuaValue>> {
+        self.check_value_owned(table)?;
+        self.check_value_owned(key)?;
         // First try raw access
         if let Some(val) = self.global_state_mut().raw_get(table, key) {
             return Ok(Some(val));
@@ -2744,12 +3243,17 @@ impl LuaState {
 
     /// Set value in table with metamethod support (__newindex)
     pub fn table_set(&mut self, table: &LuaValue, key: LuaValue, value: LuaValue) -> LuaResult<()> {
+        self.check_value_owned(table)?;
+        self.check_value_owned(&key)?;
+        self.check_value_owned(&value)?;
         execute::helper::finishset(self, table, &key, value, false)?;
         Ok(())
     }
 
     /// Compare two values using < operator with metamethod support (__lt)
     pub fn obj_lt(&mut self, a: &LuaValue, b: &LuaValue) -> LuaResult<bool> {
+        self.check_value_owned(a)?;
+        self.check_value_owned(b)?;
         // Integer-integer
         if let (Some(i1), Some(i2)) = (a.as_integer(), b.as_integer()) {
             return Ok(i1 < i2);
@@ -2763,12 +3267,8 @@ impl LuaState {
             return Ok(n1 < n2);
         }
         // String (including binary): compare raw bytes
-        if a.is_string() && b.is_string() {
-            let ba = a.as_bytes();
-            let bb = b.as_bytes();
-            if let (Some(ba), Some(bb)) = (ba, bb) {
-                return Ok(ba < bb);
-            }
+        if let Some(ord) = a.str_cmp(b) {
+            return Ok(ord.is_lt());
         }
         // Try __lt metamethod
         match execute::metamethod::try_comp_tm(self, *a, *b, execute::TmKind::Lt) {
@@ -2781,6 +3281,7 @@ impl LuaState {
     /// Get object length with metamethod support (__len)
     /// Returns the length as i64, going through __len if available.
     pub fn obj_len(&mut self, obj: &LuaValue) -> LuaResult<i64> {
+        self.check_value_owned(obj)?;
         if let Some(bytes) = obj.as_bytes() {
             return Ok(bytes.len() as i64);
         }
@@ -2806,15 +3307,18 @@ impl LuaState {
         )))
     }
 
-    /// Set value in table
+    /// Set value in table (raw, no metamethods). Returns false if `table`
+    /// is not a table or any of the values belongs to another VM.
     pub fn raw_set(&mut self, table: &LuaValue, key: LuaValue, value: LuaValue) -> bool {
         self.global_state_mut().raw_set(table, key, value)
     }
 
+    /// Get element from table by integer key (raw, no metamethods).
     pub fn raw_geti(&mut self, table: &LuaValue, index: i64) -> Option<LuaValue> {
         self.global_state_mut().raw_geti(table, index)
     }
 
+    /// Set element in table by integer key (raw, no metamethods).
     pub fn raw_seti(&mut self, table: &LuaValue, index: i64, value: LuaValue) -> bool {
         self.global_state_mut().raw_seti(table, index, value)
     }
@@ -2862,16 +3366,59 @@ impl LuaState {
 
     // ===== Unprotected Call =====
 
+    /// Whether `value` can be called: a function, a userdata with a
+    /// `lua_call` trait method, or a value whose `__call` chain (at most
+    /// `MAXCCMT` links, like the CALL opcode) ends in one of those.
+    /// Stdlib "is this callable" checks go through here so they accept
+    /// exactly what [`call`](Self::call) accepts.
+    pub fn is_callable(&mut self, value: &LuaValue) -> bool {
+        let mut current = *value;
+        for _ in 0..=MAXCCMT {
+            if current.is_function() {
+                return true;
+            }
+            if current.ttisfulluserdata()
+                && let Some(ud) = current.as_userdata_mut()
+                && let Ok(trait_obj) = ud.get_trait()
+                && trait_obj.lua_call().is_some()
+            {
+                return true;
+            }
+            match get_metamethod_event(self, &current, TmKind::Call) {
+                Some(mm) => current = mm,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    /// Call any callable value, resolving its `__call` chain the same way the
+    /// CALL opcode does. Alias of [`call_args`](Self::call_args) under the
+    /// name stdlib call sites use alongside [`is_callable`](Self::is_callable).
+    #[inline]
+    pub fn call_value(&mut self, func: LuaValue, args: &[LuaValue]) -> LuaResult<Vec<LuaValue>> {
+        self.call_args(func, args)
+    }
+
     /// Unprotected call - like C Lua's lua_call / lua_callk.
     /// Errors propagate as Err(LuaError) to the enclosing pcall boundary.
     /// Does NOT create an error recovery boundary, so __close handlers
     /// see the correct error chain without an extra pcall frame.
     pub fn call(&mut self, func: LuaValue, args: Vec<LuaValue>) -> LuaResult<Vec<LuaValue>> {
-        let initial_depth = self.call_depth();
+        self.call_args(func, &args)
+    }
+
+    /// Same as [`call`](Self::call), with arguments borrowed from a slice so
+    /// internal callers (hooks, finalizers, comparators) need no argument Vec.
+    pub fn call_args(&mut self, func: LuaValue, args: &[LuaValue]) -> LuaResult<Vec<LuaValue>> {
         // Use stack_top (logical top) instead of stack.len() (physical end).
         // The physical stack can be much larger than needed (e.g., after deep
         // recursion tests), so placing the function at stack.len() would waste
         // address space and risk hitting max_stack_size limits unnecessarily.
+        self.check_value_owned(&func)?;
+        for arg in args {
+            self.check_value_owned(arg)?;
+        }
         let func_idx = self.stack_top;
         let arg_count = args.len();
         let needed = func_idx + 1 + arg_count;
@@ -2883,38 +3430,11 @@ impl LuaState {
 
         // Write function and args at stack_top position
         self.stack[func_idx] = func;
-        for (i, arg) in args.into_iter().enumerate() {
-            self.stack[func_idx + 1 + i] = arg;
-        }
+        self.stack[func_idx + 1..needed].copy_from_slice(args);
         self.stack_top = needed;
 
-        // Resolve __call metamethod chain if needed
-        let (actual_arg_count, ccmt_depth) = resolve_call_chain(self, func_idx, arg_count)?;
-
-        let func_val = self
-            .stack_get(func_idx)
-            .ok_or_else(|| self.error("call: function not found".to_string()))?;
-
-        if func_val.is_c_callable() {
-            // C function - call directly via call_c_function (unprotected)
-            call_c_function(self, func_idx, actual_arg_count, -1)?;
-        } else {
-            // Lua function - push frame and execute
-            let base = func_idx + 1;
-            self.push_frame(&func_val, base, actual_arg_count, -1)?;
-
-            if ccmt_depth > 0 {
-                let frame_idx = self.call_depth - 1;
-                if let Some(frame) = self.call_stack.get_mut(frame_idx) {
-                    frame.call_status = call_status::set_ccmt_count(frame.call_status, ccmt_depth);
-                }
-            }
-
-            self.inc_n_ccalls()?;
-            let r = lua_execute(self, initial_depth);
-            self.dec_n_ccalls();
-            r?; // Propagate errors without catching
-        }
+        // Same frame setup as the CALL opcode; errors propagate uncaught
+        call_value(self, func_idx, arg_count, -1)?;
 
         // Collect results from func_idx to stack_top
         let mut results = Vec::new();
@@ -2948,6 +3468,35 @@ impl LuaState {
         Ok(results)
     }
 
+    /// Unwind what an error left behind on a thread no pcall protected:
+    /// drop every frame, close upvalues and to-be-closed variables like a
+    /// pcall would, and empty the stack. Frames are kept until then so the
+    /// error can still be reported with a traceback. The error stays set
+    /// unless a `__close` handler replaced it.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn unwind_after_error(&mut self) {
+        while self.call_depth() > 0 {
+            self.pop_frame();
+        }
+        self.close_upvalues(0);
+        if !self.tbc_list.is_empty() {
+            let error = self.global_state_mut().take_error();
+            let err_obj = match &error {
+                ErrorMsg::Object(obj) => *obj,
+                ErrorMsg::Msg(msg) => self.create_string(msg).unwrap_or_default(),
+                ErrorMsg::None => LuaValue::nil(),
+            };
+            let _ = self.close_tbc_with_error(0, err_obj);
+            if !self.has_error_object() {
+                self.global_state_mut().error_msg = error;
+            }
+        }
+        let clear_end = self.stack_top.min(self.stack.len());
+        self.stack[..clear_end].fill(LuaValue::nil());
+        self.stack_top = 0;
+    }
+
     /// Fast comparison call for sort — avoids Vec allocations entirely.
     /// Calls `func(a, b)` and returns whether the result is truthy.
     ///
@@ -3005,7 +3554,7 @@ impl LuaState {
             call_c_function(self, func_idx, 2, 1)?;
         } else {
             // Fallback for __call metamethods — rare in sort comparators
-            let results = self.call(func, vec![a, b])?;
+            let results = self.call_value(func, &[a, b])?;
             return Ok(results.first().map(|v| v.is_truthy()).unwrap_or(false));
         }
 
@@ -3039,6 +3588,15 @@ impl LuaState {
         &mut self,
         func: LuaValue,
         args: Vec<LuaValue>,
+    ) -> LuaResult<(bool, Vec<LuaValue>)> {
+        self.pcall_args(func, &args)
+    }
+
+    /// Same as [`pcall`](Self::pcall), with arguments borrowed from a slice.
+    pub fn pcall_args(
+        &mut self,
+        func: LuaValue,
+        args: &[LuaValue],
     ) -> LuaResult<(bool, Vec<LuaValue>)> {
         // This is equivalent to C Lua's lua_call → luaD_callnoyield:
         // the callback runs in a non-yieldable context.
@@ -3052,13 +3610,17 @@ impl LuaState {
     fn pcall_inner(
         &mut self,
         func: LuaValue,
-        args: Vec<LuaValue>,
+        args: &[LuaValue],
     ) -> LuaResult<(bool, Vec<LuaValue>)> {
         // Save state for cleanup
         let initial_depth = self.call_depth();
         let saved_stack_top = self.stack_top;
         // Use stack_top (logical top) instead of stack.len() (physical end).
         // See call() for rationale.
+        self.check_value_owned(&func)?;
+        for arg in args {
+            self.check_value_owned(arg)?;
+        }
         let func_idx = self.stack_top;
         let arg_count = args.len();
         let needed = func_idx + 1 + arg_count;
@@ -3070,9 +3632,7 @@ impl LuaState {
 
         // Write function and args at stack_top position
         self.stack[func_idx] = func;
-        for (i, arg) in args.into_iter().enumerate() {
-            self.stack[func_idx + 1 + i] = arg;
-        }
+        self.stack[func_idx + 1..needed].copy_from_slice(args);
 
         // Sync logical stack top
         self.stack_top = needed;
@@ -3083,7 +3643,7 @@ impl LuaState {
             Ok((count, depth)) => (count, depth),
             Err(e) => {
                 let error_msg = self.get_error_message(e);
-                let err_str = self.create_string(&error_msg)?;
+                let err_str = self.create_error_string(&error_msg)?;
                 self.stack_top = saved_stack_top;
                 return Ok((false, vec![err_str]));
             }
@@ -3102,7 +3662,7 @@ impl LuaState {
             if let Err(e) = self.push_frame(&func, base, actual_arg_count, -1) {
                 self.stack_top = saved_stack_top;
                 let error_msg = self.get_error_message(e);
-                let err_str = self.create_string(&error_msg)?;
+                let err_str = self.create_error_string(&error_msg)?;
                 return Ok((false, vec![err_str]));
             }
 
@@ -3114,18 +3674,17 @@ impl LuaState {
                 }
             }
 
-            // Get the C function pointer
-            let cfunc = if let Some(c_func) = func.as_cfunction() {
-                c_func
+            // Call the C function or Rust closure
+            let result = if let Some(c_func) = func.as_cfunction() {
+                c_func(self)
             } else if let Some(closure) = func.as_cclosure() {
-                closure.func()
+                (closure.func())(self)
             } else {
-                unreachable!()
+                func.as_rclosure()
+                    .expect("pcall checked c-callable type before access")
+                    .call(self)
             };
 
-            // Call C function
-            let result = cfunc(self);
-
             // CloseThread bypasses all pcalls — don't pop this frame,
             // handle_resume_result will pop everything.
             if matches!(result, Err(LuaError::CloseThread)) {
@@ -3161,7 +3720,7 @@ impl LuaState {
                         err_obj
                     } else {
                         let error_msg = self.get_error_message(e);
-                        self.create_string(&error_msg)?
+                        self.create_error_string(&error_msg)?
                     };
                     self.stack_top = saved_stack_top;
                     Ok((false, vec![result_err]))
@@ -3174,7 +3733,7 @@ impl LuaState {
             if let Err(e) = self.push_frame(&func, base, actual_arg_count, -1) {
                 self.stack_top = saved_stack_top;
                 let error_msg = self.get_error_message(e);
-                let err_str = self.create_string(&error_msg)?;
+                let err_str = self.create_error_string(&error_msg)?;
                 return Ok((false, vec![err_str]));
             }
 
@@ -3246,7 +3805,7 @@ impl LuaState {
                     } else if had_err_object {
                         err_obj
                     } else {
-                        self.create_string(&error_msg_str)?
+                        self.create_error_string(&error_msg_str)?
                     };
 
                     // Clean up stack
@@ -3265,22 +3824,7 @@ impl LuaState {
     pub fn call_stack_based(&mut self, func_idx: usize, arg_count: usize) -> LuaResult<usize> {
         let initial_depth = self.call_depth();
 
-        let (actual_arg_count, _ccmt_depth) = resolve_call_chain(self, func_idx, arg_count)?;
-
-        let func = self
-            .stack_get(func_idx)
-            .ok_or_else(|| self.error("call: function not found".to_string()))?;
-
-        let result = if func.is_c_callable() {
-            call_c_function(self, func_idx, actual_arg_count, -1).map(|_| ())
-        } else {
-            let base = func_idx + 1;
-            self.push_frame(&func, base, actual_arg_count, -1)?;
-            self.inc_n_ccalls()?;
-            let r = lua_execute(self, initial_depth);
-            self.dec_n_ccalls();
-            r
-        };
+        let result = call_value(self, func_idx, arg_count, -1);
 
         match result {
             Ok(()) => {
@@ -3323,7 +3867,7 @@ impl LuaState {
             Err(e) => {
                 // __call resolution failed - return error
                 let error_msg = self.get_error_message(e);
-                let err_str = self.create_string(&error_msg)?;
+                let err_str = self.create_error_string(&error_msg)?;
                 self.stack_set(func_idx, err_str)?;
                 self.set_top(func_idx + 1)?;
                 return Ok((false, 1));
@@ -3404,7 +3948,10 @@ impl LuaState {
                     self.close_upvalues(base);
                     let _ = self.close_tbc_with_error(base, LuaValue::nil());
                 }
-                let err_msg = self.create_string("error in error handling")?;
+                let err_msg = self
+                    .global_state()
+                    .const_strings
+                    .str_error_in_error_handling;
                 self.stack_set(func_idx, err_msg)?;
                 self.set_top(func_idx + 1)?;
                 Ok((false, 1))
@@ -3466,7 +4013,7 @@ impl LuaState {
                 } else if had_err_object {
                     err_obj
                 } else {
-                    self.create_string(&error_msg_str)?
+                    self.create_error_string(&error_msg_str)?
                 };
 
                 // Set error at func_idx and update stack top
@@ -3478,6 +4025,63 @@ impl LuaState {
         }
     }
 
+    /// Protected call from a C function that lets the callee yield, like
+    /// `lua_pcallk`. Without a yield it is `pcall_stack_based`. After a yield,
+    /// the C function's frame stays suspended until the callee finishes in
+    /// the resumed coroutine; then the C function is called again with the
+    /// same arguments, and `take_continuation` returns what this call would
+    /// have: results (or the error value) from `func_idx` to the top.
+    /// Anything the C function needs afterwards must be on its stack below
+    /// `func_idx` before the call.
+    pub fn pcall_stack_based_k(
+        &mut self,
+        func_idx: usize,
+        arg_count: usize,
+    ) -> LuaResult<(bool, usize)> {
+        let initial_depth = self.call_depth();
+        let result = self.pcall_stack_based(func_idx, arg_count);
+        if matches!(result, Err(LuaError::Yield)) && initial_depth > 0 {
+            let ci = self.get_call_info_mut(initial_depth - 1);
+            ci.call_status |= CIST_YPCALL | CIST_YCONT;
+            ci.aux_i32 = (func_idx - ci.base) as i32;
+        }
+        result
+    }
+
+    /// In a C function that `pcall_stack_based_k` suspended: the outcome of
+    /// that call when the function is being called again to continue it.
+    /// `None` on an ordinary call.
+    pub fn take_continuation(&mut self) -> Option<(bool, usize)> {
+        self.extra.as_mut()?.continuation.take()
+    }
+
+    /// Call a C function suspended by `pcall_stack_based_k` again, with its
+    /// callee's outcome, and return its results to its caller.
+    pub(crate) fn resume_continuation(&mut self, status: (bool, usize)) -> LuaResult<()> {
+        let ci = self.current_frame_mut().unwrap();
+        ci.call_status &= !(CIST_YPCALL | CIST_YCONT | CIST_RECST);
+        let func_pos = ci.base - ci.func_offset as usize;
+        let nresults = ci.nresults();
+
+        let func = self.stack_get(func_pos).unwrap_or_default();
+        self.extra_mut().continuation = Some(status);
+        let result = if let Some(f) = func.as_cfunction() {
+            f(self)
+        } else if let Some(cc) = func.as_cclosure() {
+            (cc.func())(self)
+        } else {
+            func.as_rclosure().unwrap().call(self)
+        };
+        if let Some(extra) = self.extra.as_mut() {
+            extra.continuation = None;
+        }
+        let n = result?;
+
+        let first_result = self.get_top() - n;
+        self.pop_c_frame();
+        execute::helper::finish_c_results(self, func_pos, first_result, n, nresults)
+    }
+
     /// Protected call with error handler, stack-based (xpcall semantics).
     /// Like pcall_stack_based but calls the error handler at `handler_idx`
     /// BEFORE unwinding call frames, so debug.traceback can see the full stack.
@@ -3494,7 +4098,7 @@ impl LuaState {
             Ok((count, depth)) => (count, depth),
             Err(e) => {
                 let error_msg = self.get_error_message(e);
-                let err_str = self.create_string(&error_msg)?;
+                let err_str = self.create_error_string(&error_msg)?;
                 self.stack_set(func_idx, err_str)?;
                 self.set_top(func_idx + 1)?;
                 return Ok((false, 1));
@@ -3553,7 +4157,10 @@ impl LuaState {
                     self.close_upvalues(base);
                     let _ = self.close_tbc_with_error(base, LuaValue::nil());
                 }
-                let err_msg = self.create_string("error in error handling")?;
+                let err_msg = self
+                    .global_state()
+                    .const_strings
+                    .str_error_in_error_handling;
                 self.stack_set(func_idx, err_msg)?;
                 self.set_top(func_idx + 1)?;
                 Ok((false, 1))
@@ -3567,7 +4174,7 @@ impl LuaState {
                 let mut err_value = if had_err_object {
                     err_obj
                 } else {
-                    self.create_string(&error_msg_str)?
+                    self.create_error_string(&error_msg_str)?
                 };
 
                 let frame_base = if self.call_depth() > initial_depth {
@@ -3634,20 +4241,8 @@ impl LuaState {
 
                     let handler_depth = self.call_depth();
 
-                    let handler_result =
-                        if handler.is_cfunction() || handler.as_cclosure().is_some() {
-                            call_c_function(self, handler_func_idx, 1, -1)
-                        } else {
-                            match self.push_frame(&handler, handler_func_idx + 1, 1, -1) {
-                                Ok(()) => {
-                                    self.inc_n_ccalls()?;
-                                    let r = lua_execute(self, handler_depth);
-                                    self.dec_n_ccalls();
-                                    r
-                                }
-                                Err(handler_err) => Err(handler_err),
-                            }
-                        };
+                    // The handler may be any callable, including a `__call` chain
+                    let handler_result = call_value(self, handler_func_idx, 1, -1);
 
                     match handler_result {
                         Ok(_) => {
@@ -3716,7 +4311,9 @@ impl LuaState {
 
                 // Determine final error value
                 let final_error = if handler_failed {
-                    self.create_string("error in error handling")?
+                    self.global_state()
+                        .const_strings
+                        .str_error_in_error_handling
                 } else {
                     transformed_error
                 };
@@ -3758,7 +4355,7 @@ impl LuaState {
             Err(e) => {
                 self.set_top(handler_idx)?;
                 let error_msg = self.get_error_message(e);
-                let err_str = self.create_string(&error_msg)?;
+                let err_str = self.create_error_string(&error_msg)?;
                 return Ok((false, vec![err_str]));
             }
         };
@@ -3777,7 +4374,7 @@ impl LuaState {
             if let Err(e) = self.push_frame(&func, base, actual_arg_count, -1) {
                 self.set_top(handler_idx)?;
                 let error_msg = self.get_error_message(e);
-                let err_str = self.create_string(&error_msg)?;
+                let err_str = self.create_error_string(&error_msg)?;
                 return Ok((false, vec![err_str]));
             }
 
@@ -3789,16 +4386,16 @@ impl LuaState {
                 }
             }
 
-            let cfunc = if let Some(c_func) = func.as_cfunction() {
-                c_func
+            let c_result = if let Some(c_func) = func.as_cfunction() {
+                c_func(self)
             } else if let Some(closure) = func.as_cclosure() {
-                closure.func()
+                (closure.func())(self)
             } else {
-                unreachable!()
+                func.as_rclosure()
+                    .expect("xpcall checked c-callable type before access")
+                    .call(self)
             };
 
-            let c_result = cfunc(self);
-
             // CloseThread bypasses everything
             if matches!(c_result, Err(LuaError::CloseThread)) {
                 return Err(LuaError::CloseThread);
@@ -3827,7 +4424,7 @@ impl LuaState {
             if let Err(e) = self.push_frame(&func, base, actual_arg_count, -1) {
                 self.set_top(handler_idx)?;
                 let error_msg = self.get_error_message(e);
-                let err_str = self.create_string(&error_msg)?;
+                let err_str = self.create_error_string(&error_msg)?;
                 return Ok((false, vec![err_str]));
             }
 
@@ -3886,7 +4483,11 @@ impl LuaState {
                     self.close_upvalues(base);
                     let _ = self.close_tbc_with_error(base, LuaValue::nil());
                 }
-                let results = vec![self.create_string("error in error handling")?];
+                let results = vec![
+                    self.global_state()
+                        .const_strings
+                        .str_error_in_error_handling,
+                ];
                 self.set_top(handler_idx)?;
                 Ok((false, results))
             }
@@ -3903,7 +4504,7 @@ impl LuaState {
                 let mut err_value = if had_err_object {
                     err_obj
                 } else {
-                    self.create_string(&error_msg_str)?
+                    self.create_error_string(&error_msg_str)?
                 };
 
                 // Get frame_base for later cleanup (upvalues/TBC)
@@ -3936,20 +4537,8 @@ impl LuaState {
 
                     let handler_depth = self.call_depth();
 
-                    let handler_result =
-                        if handler.is_cfunction() || handler.as_cclosure().is_some() {
-                            execute::call::call_c_function(self, handler_func_idx, 1, -1)
-                        } else {
-                            match self.push_frame(&handler, handler_func_idx + 1, 1, -1) {
-                                Ok(()) => {
-                                    self.inc_n_ccalls()?;
-                                    let r = execute::lua_execute(self, handler_depth);
-                                    self.dec_n_ccalls();
-                                    r
-                                }
-                                Err(handler_err) => Err(handler_err),
-                            }
-                        };
+                    // The handler may be any callable, including a `__call` chain
+                    let handler_result = call_value(self, handler_func_idx, 1, -1);
 
                     match handler_result {
                         Ok(()) => {
@@ -4027,9 +4616,13 @@ impl LuaState {
 
                 if results.is_empty() {
                     if handler_failed {
-                        results.push(self.create_string("error in error handling")?);
+                        results.push(
+                            self.global_state()
+                                .const_strings
+                                .str_error_in_error_handling,
+                        );
                     } else {
-                        results.push(self.create_string(&error_msg_str)?);
+                        results.push(self.create_error_string(&error_msg_str)?);
                     }
                 }
 
@@ -4052,20 +4645,32 @@ impl LuaState {
         // - call_depth > 0 && yielded → suspended after yield (can resume)
         // - call_depth == 0 && stack not empty → initial state (can resume)
         // - call_depth == 0 && stack empty → dead (cannot resume)
+        // These messages carry no position, as in lua_resume: the thread's
+        // own current line says nothing about the bad resume.
         if self.dead {
             // Clear stale error_object so that this error uses the fresh
-            // error_msg set by self.error(), rather than a leftover
-            // error_object from a previous failed resume.
+            // error_msg, rather than a leftover error_object from a
+            // previous failed resume.
             self.clear_error();
-            return Err(self.error("cannot resume dead coroutine".to_string()));
+            return Err(self
+                .global_state_mut()
+                .error("cannot resume dead coroutine".to_string()));
         }
         if self.call_depth > 0 && !self.yielded {
             self.clear_error();
-            return Err(self.error("cannot resume non-suspended coroutine".to_string()));
+            return Err(self
+                .global_state_mut()
+                .error("cannot resume non-suspended coroutine".to_string()));
+        }
+        for arg in &args {
+            self.check_value_owned(arg)?;
         }
 
         // Mark as running (not yielded)
         self.yielded = false;
+        #[cfg(feature = "profiler")]
+        self.sync_profile_mask();
+        self.sync_interrupt_mask();
 
         // Check if this is the first resume (no active frames)
         if self.call_depth == 0 {
@@ -4086,6 +4691,7 @@ impl LuaState {
             // Fix open upvalue pointers if Vec was reallocated
             if self.stack.capacity() != old_capacity {
                 self.fix_open_upvalue_pointers();
+                self.track_stack_growth(old_capacity);
             }
 
             // Create initial frame, expecting all return values
@@ -4195,6 +4801,7 @@ impl LuaState {
                 Err(LuaError::Yield) => {
                     // Coroutine yielded — mark as yielded for resume detection
                     self.yielded = true;
+                    self.shrink_stack();
                     let yield_vals = self.take_yield();
                     return Ok((false, yield_vals));
                 }
@@ -4269,6 +4876,8 @@ impl LuaState {
                     let pcall_nresults = pcall_ci.nresults();
                     let close_level = pcall_ci.base; // close from body position
                     let is_xpcall = pcall_ci.call_status & CIST_XPCALL != 0;
+                    let continuation_func_idx = (pcall_ci.call_status & CIST_YCONT != 0)
+                        .then(|| pcall_ci.base + pcall_ci.aux_i32 as usize);
 
                     // Save the xpcall handler before anything overwrites it
                     let xpcall_handler = if is_xpcall {
@@ -4313,54 +4922,47 @@ impl LuaState {
                             // If xpcall, call error handler to transform the error
                             let result_err = if is_xpcall {
                                 self.nny += 1;
-                                let handler_result = self.pcall(xpcall_handler, vec![result_err]);
+                                let handler_result = self.pcall_args(xpcall_handler, &[result_err]);
                                 self.nny -= 1;
                                 match handler_result {
                                     Ok((true, results)) => {
                                         results.into_iter().next().unwrap_or(LuaValue::nil())
                                     }
-                                    _ => self.create_string("error in error handling")?,
+                                    _ => {
+                                        self.global_state()
+                                            .const_strings
+                                            .str_error_in_error_handling
+                                    }
                                 }
                             } else {
                                 result_err
                             };
 
-                            // Set up pcall error result: (false, error)
-                            self.stack_set(pcall_func_pos, LuaValue::boolean(false))
-                                .ok();
-                            self.stack_set(pcall_func_pos + 1, result_err).ok();
-                            let n = 2;
-
-                            // Pop pcall frame
-                            self.pop_frame();
-
-                            // Handle nresults like call_c_function post-processing
-                            let final_n = if pcall_nresults == -1 {
-                                n
-                            } else {
-                                pcall_nresults as usize
-                            };
-                            let new_top = pcall_func_pos + final_n;
-                            if pcall_nresults >= 0 {
-                                let wanted = pcall_nresults as usize;
-                                for i in n..wanted {
-                                    self.stack_set(pcall_func_pos + i, LuaValue::nil()).ok();
-                                }
-                            }
-                            self.set_top_raw(new_top);
-
-                            // Restore caller frame top
-                            if self.call_depth() > 0 {
-                                let ci_idx = self.call_depth() - 1;
-                                if pcall_nresults == -1 {
-                                    let ci_top = self.get_call_info(ci_idx).top as usize;
-                                    if ci_top < new_top {
-                                        self.get_call_info_mut(ci_idx).top = new_top as u32;
-                                    }
-                                } else {
-                                    let frame_top = self.get_call_info(ci_idx).top as usize;
-                                    self.set_top_raw(frame_top);
+                            if let Some(func_idx) = continuation_func_idx {
+                                // A pcall_stack_based_k callee failed: the C
+                                // function continues with the error value.
+                                self.stack_set(func_idx, result_err).ok();
+                                self.set_top_raw(func_idx + 1);
+                                if let Err(e) = self.resume_continuation((false, 1)) {
+                                    result = Err(e);
+                                    continue;
                                 }
+                            } else {
+                                // Set up pcall error result: (false, error)
+                                self.stack_set(pcall_func_pos, LuaValue::boolean(false))
+                                    .ok();
+                                self.stack_set(pcall_func_pos + 1, result_err).ok();
+
+                                // Pop pcall frame
+                                self.pop_frame();
+                                execute::helper::finish_c_results(
+                                    self,
+                                    pcall_func_pos,
+                                    pcall_func_pos,
+                                    2,
+                                    pcall_nresults,
+                                )
+                                .ok();
                             }
 
                             // Continue execution
@@ -4388,6 +4990,7 @@ impl LuaState {
 
                             // Mark coroutine as yielded so resume works
                             self.yielded = true;
+                            self.shrink_stack();
 
                             // Return yield values normally
                             let yield_vals = self.take_yield();
@@ -4429,7 +5032,12 @@ impl LuaState {
     /// Called when modifying an object to point to another object
     #[inline(always)]
     pub(crate) fn gc_barrier(&mut self, upvalue_ptr: UpvaluePtr, value_gc_ptr: GcObjectPtr) {
-        let owner_ptr = GcObjectPtr::from(upvalue_ptr);
+        self.gc_object_barrier(GcObjectPtr::from(upvalue_ptr), value_gc_ptr);
+    }
+
+    /// Forward GC barrier for an arbitrary owner (luaC_objbarrier in Lua 5.5)
+    #[inline(always)]
+    pub(crate) fn gc_object_barrier(&mut self, owner_ptr: GcObjectPtr, value_gc_ptr: GcObjectPtr) {
         self.global_state
             .gc_barrier(self as *mut LuaState, owner_ptr, value_gc_ptr);
     }
@@ -4465,7 +5073,7 @@ impl LuaState {
         ci.save_pc(pc);
         self.set_top_raw(c);
         self.global_state.check_gc(self as *mut LuaState);
-        *trap = self.hook_mask != 0;
+        *trap = self.has_active_instruction_watch();
     }
 
     #[inline(always)]
@@ -4476,20 +5084,127 @@ impl LuaState {
         self.global_state.check_gc(self as *mut LuaState);
     }
 
+    /// Run a full collection. Does nothing when called while the collector
+    /// is already running, e.g. from a finalizer.
     pub fn collect_garbage(&mut self) -> LuaResult<()> {
         self.global_state.full_gc(self as *mut LuaState, false);
         Ok(())
     }
 
+    /// Check that `bytes` more can be allocated under the memory limit
+    /// before building a large result, failing with `OutOfMemory` like the
+    /// allocation itself would. As in luaM_malloc_, an emergency full
+    /// collection runs first when it could make room, so every value the
+    /// caller still needs must already be on the Lua stack.
+    pub(crate) fn reserve_memory(&mut self, bytes: usize) -> LuaResult<()> {
+        let fits = |state: &Self| bytes as isize <= state.global_state().gc.memory_headroom();
+        if fits(self) {
+            return Ok(());
+        }
+        if self.global_state().gc.can_retry_allocation() {
+            self.global_state.full_gc(self as *mut LuaState, true);
+            if fits(self) {
+                return Ok(());
+            }
+        }
+        Err(LuaError::OutOfMemory)
+    }
+
+    /// Reject a value created by another VM with "value belongs to a
+    /// different VM". See [`GlobalState::is_own_value`].
+    #[inline(always)]
+    pub fn check_value_owned(&mut self, value: &LuaValue) -> LuaResult<()> {
+        if self.global_state().is_own_value(value) {
+            Ok(())
+        } else {
+            Err(self.foreign_value_error())
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn foreign_value_error(&mut self) -> LuaError {
+        self.error("value belongs to a different VM".to_string())
+    }
+
+    /// Validate a value held on the Rust side before handing it back to the VM.
+    ///
+    /// Returns the value, or an error instead of touching freed memory when
+    /// the object was collected, or when it belongs to another VM. See
+    /// [`GlobalState::is_value_alive`].
+    pub fn check_value_alive(&mut self, value: &LuaWeakValue) -> LuaResult<LuaValue> {
+        let raw = value.value_unchecked();
+        if !self.global_state().is_value_alive(value) {
+            return Err(self.error(format!(
+                "attempt to use a collected {} value (anchor it with create_ref)",
+                raw.type_name()
+            )));
+        }
+        self.check_value_owned(&raw)?;
+        Ok(raw)
+    }
+
     pub(crate) fn change_gc_mode(&mut self, kind: GcKind) {
         self.global_state
             .change_gc_mode(self as *mut LuaState, kind);
     }
 
-    #[cfg(feature = "sandbox")]
+    /// Whether the execute loop must call `hook_check_instruction` before
+    /// every instruction: a debug hook, sandbox limits or an interrupt hook.
+    /// Only reads this thread's own fields; the interrupt hook is mirrored
+    /// into `hook_mask` by [`sync_interrupt_mask`](Self::sync_interrupt_mask).
     #[inline(always)]
     pub(crate) fn has_active_instruction_watch(&self) -> bool {
-        self.hook_mask != 0 || self.sandbox_limits.is_some()
+        #[cfg(feature = "sandbox")]
+        if self.sandbox_limits.is_some() {
+            return true;
+        }
+        self.hook_mask & !LUA_MASKPROFILE != 0
+    }
+
+    /// Watch instructions for the interrupt hook exactly while the VM has
+    /// one; called when the hook changes and whenever the thread is resumed.
+    pub(crate) fn sync_interrupt_mask(&mut self) {
+        let installed = self.global_state().interrupt_hook.is_some();
+        if installed && self.hook_mask & LUA_MASKINTERRUPT == 0 {
+            self.hook_mask |= LUA_MASKINTERRUPT;
+            self.interrupt_count = INTERRUPT_CHECK_INTERVAL;
+        } else if !installed {
+            self.hook_mask &= !LUA_MASKINTERRUPT;
+        }
+    }
+
+    /// Count hook for the VM's interrupt hook: poll it every
+    /// `INTERRUPT_CHECK_INTERVAL` instructions, raising its message when it
+    /// asks to stop. After a failure the count stays at one, so the next
+    /// instruction, e.g. right after a `pcall` that caught the error, polls
+    /// again.
+    #[inline(always)]
+    pub(crate) fn count_interrupt(&mut self) -> LuaResult<()> {
+        if self.interrupt_count > 1 {
+            self.interrupt_count -= 1;
+            return Ok(());
+        }
+        self.poll_interrupt()
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn poll_interrupt(&mut self) -> LuaResult<()> {
+        let verdict = match &self.global_state().interrupt_hook {
+            Some(hook) => hook(),
+            None => Ok(()),
+        };
+        match verdict {
+            Ok(()) => {
+                self.interrupt_count = INTERRUPT_CHECK_INTERVAL;
+                Ok(())
+            }
+            Err(msg) => {
+                self.interrupt_count = 1;
+                Err(self.error(msg))
+            }
+        }
     }
 
     #[cfg(feature = "sandbox")]
@@ -4625,7 +5340,7 @@ impl LuaState {
             LuaValue::nil()
         };
 
-        let result = self.call(hook, vec![event_name, line_val]);
+        let result = self.call_args(hook, &[event_name, line_val]);
 
         // Clear hooked flag
         if ci_idx < self.call_depth() {
@@ -4641,7 +5356,48 @@ impl LuaState {
         result.map(|_| ())
     }
 
+    /// Concatenate the `n` values on top of the stack into one, with the
+    /// coercions and `__concat` of the CONCAT opcode (lua_concat).
+    pub(crate) fn concat(&mut self, n: usize) -> LuaResult<()> {
+        execute::concat(self, n)
+    }
+
+    /// Like [`to_string`](Self::to_string), but yields a Lua string: the
+    /// pre-interned constants for nil and booleans, and the `__tostring`
+    /// result itself, so neither is copied out and interned again.
+    pub fn tostring_value(&mut self, value: &LuaValue) -> LuaResult<LuaValue> {
+        self.check_value_owned(value)?;
+        let cs = &self.global_state().const_strings;
+        match value.kind() {
+            LuaValueKind::String => return Ok(*value),
+            LuaValueKind::Nil => return Ok(cs.str_nil),
+            LuaValueKind::Boolean => {
+                return Ok(if value.as_boolean() == Some(true) {
+                    cs.str_true
+                } else {
+                    cs.str_false
+                });
+            }
+            LuaValueKind::Table
+            | LuaValueKind::Thread
+            | LuaValueKind::CClosure
+            | LuaValueKind::RClosure => {
+                if let Some(mm) = get_metamethod_event(self, value, TmKind::ToString) {
+                    let result = execute::call_tm_res1(self, mm, *value)?;
+                    if result.is_string() {
+                        return Ok(result);
+                    }
+                    return Err(self.error("'__tostring' must return a string".to_string()));
+                }
+            }
+            _ => {}
+        }
+        let s = self.to_string(value)?;
+        self.create_string(&s)
+    }
+
     pub fn to_string(&mut self, value: &LuaValue) -> LuaResult<String> {
+        self.check_value_owned(value)?;
         // Fast path: simple types without metamethods
         match value.kind() {
             LuaValueKind::Nil => return Ok("nil".to_string()),
@@ -4661,7 +5417,7 @@ impl LuaState {
             }
             LuaValueKind::Float => {
                 if let Some(n) = value.as_number() {
-                    return Ok(n.to_string());
+                    return Ok(lua_float_to_string(n));
                 }
             }
             LuaValueKind::String => {
@@ -4704,7 +5460,7 @@ impl LuaState {
             Ok(format!(
                 "{}: 0x{:x}",
                 type_prefix,
-                value.raw_ptr_repr() as usize
+                value.identity().unwrap_or_default()
             ))
         } else {
             Ok(format!("{}", value))
@@ -4725,17 +5481,21 @@ impl LuaState {
     // ========================================================================
 
     /// Set a Lua hook function with the given mask and count.
-    /// This is the programmatic equivalent of `debug.sethook`.
+    /// This is the programmatic equivalent of `debug.sethook`. A hook
+    /// function from another VM is ignored.
     pub fn set_hook(&mut self, hook: LuaValue, mask: u8, count: i32) {
+        if !self.global_state().is_own_value(&hook) {
+            return;
+        }
         self.hook = hook;
-        self.hook_mask = mask;
+        self.hook_mask = mask & !LUA_MASKINTERNAL | self.hook_mask & LUA_MASKINTERNAL;
         self.base_hook_count = count;
         self.hook_count = count;
     }
 
     /// Get the current hook mask (bitmask of LUA_MASKCALL/RET/LINE/COUNT).
     pub fn hook_mask(&self) -> u8 {
-        self.hook_mask
+        self.hook_mask & !LUA_MASKINTERNAL
     }
 
     /// Get the current hook function value.
@@ -4743,6 +5503,92 @@ impl LuaState {
         self.hook
     }
 
+    /// Start the profiler, recording this thread from its next call on.
+    #[cfg(feature = "profiler")]
+    pub(crate) fn start_profiler(&mut self) {
+        self.global_state_mut().start_profiler();
+        self.hook_mask |= LUA_MASKPROFILE;
+    }
+
+    /// Stop the profiler, charging the functions still running on this
+    /// thread and on the main thread up to now.
+    #[cfg(feature = "profiler")]
+    pub(crate) fn stop_profiler(&mut self) {
+        if !self.is_main {
+            self.close_profile_frames();
+            self.hook_mask &= !LUA_MASKPROFILE;
+        }
+        self.global_state_mut().stop_profiler();
+    }
+
+    /// Charge the frames still open on this thread up to now.
+    #[cfg(feature = "profiler")]
+    pub(crate) fn close_profile_frames(&mut self) {
+        let profiler = self.global_state.as_mut().profiler.as_deref_mut();
+        if let (Some(profiler), Some(extra)) = (profiler, self.extra.as_mut()) {
+            profiler.close_frames(&mut extra.profile_frames);
+        }
+    }
+
+    /// Record profiling on this thread exactly while the profiler runs;
+    /// called whenever the thread is resumed.
+    #[cfg(feature = "profiler")]
+    pub(crate) fn sync_profile_mask(&mut self) {
+        if self.global_state().is_profiling() {
+            self.hook_mask |= LUA_MASKPROFILE;
+        } else {
+            self.hook_mask &= !LUA_MASKPROFILE;
+        }
+    }
+
+    /// Profiler side of a call hook: `chunk` was entered in the current frame.
+    #[cfg(feature = "profiler")]
+    #[inline(never)]
+    pub(crate) fn profile_call(&mut self, chunk: &LuaProto) {
+        let depth = self.call_depth;
+        match self.running_profiler() {
+            Some(profiler) => profiler.enter(&mut self.extra_mut().profile_frames, chunk, depth),
+            None => self.drop_profile_frames(),
+        }
+    }
+
+    /// Profiler side of a return hook: the current Lua frame is returning.
+    #[cfg(feature = "profiler")]
+    #[inline(never)]
+    pub(crate) fn profile_return(&mut self) {
+        let depth = self.call_depth;
+        match self.running_profiler() {
+            Some(profiler) => {
+                if let Some(extra) = self.extra.as_mut() {
+                    profiler.leave(&mut extra.profile_frames, depth);
+                }
+            }
+            None => self.drop_profile_frames(),
+        }
+    }
+
+    /// The profiler, if it is recording. Borrowed through the global
+    /// handle so the caller can still hand it this thread's frames.
+    #[cfg(feature = "profiler")]
+    #[inline(always)]
+    fn running_profiler<'a>(&self) -> Option<&'a mut Profiler> {
+        self.global_state
+            .as_mut()
+            .profiler
+            .as_deref_mut()
+            .filter(|profiler| profiler.is_running())
+    }
+
+    /// The profiler was stopped while this thread was recording.
+    #[cfg(feature = "profiler")]
+    #[cold]
+    fn drop_profile_frames(&mut self) {
+        if let Some(extra) = self.extra.as_mut() {
+            extra.profile_frames.clear();
+        }
+        self.hook_mask &= !LUA_MASKPROFILE;
+    }
+
     /// Get the source name of the function at the given stack level (0 = current).
     /// This is a fast path that only reads the chunk's source_name without
     /// building a full DebugInfo. Returns `None` for C functions or invalid levels.
@@ -4868,6 +5714,112 @@ impl LuaState {
         lua_func.upvalues().len()
     }
 
+    // ========================================================================
+    // Inspection API — read-only views of VM state for embedders' tooling.
+    // Nothing here runs Lua code or metamethods.
+    // ========================================================================
+
+    /// Walk the entries of a table like `next`, array part first, without
+    /// invoking `__pairs` or `__index`. Yields nothing for non-table values.
+    ///
+    /// The walk is live: the iterator borrows the state, so neither Lua code
+    /// nor the collector can run while it is in use, and the table cannot
+    /// change under it. The yielded values are not rooted: anchor any that
+    /// must outlive the iterator (e.g. with `create_ref`) before running Lua
+    /// or allocating again.
+    pub fn iter_table(
+        &self,
+        table: &LuaValue,
+    ) -> impl Iterator<Item = (LuaValue, LuaValue)> + use<'_> {
+        let table = *table;
+        let mut key = table.is_table().then(LuaValue::nil);
+        std::iter::from_fn(move || {
+            let t = table.as_table()?;
+            let (k, v) = t.next_untracked(&key.take()?).ok().flatten()?;
+            key = Some(k);
+            Some((k, v))
+        })
+    }
+
+    /// Walk the global table; see [`iter_table`](Self::iter_table).
+    pub fn iter_globals(&self) -> impl Iterator<Item = (LuaValue, LuaValue)> + use<'_> {
+        let globals = self.global_state().global;
+        self.iter_table(&globals)
+    }
+
+    /// Describe a function value: parameters, upvalues and where it was
+    /// defined. Returns `None` for non-function values.
+    pub fn function_info(&self, func: &LuaValue) -> Option<FunctionInfo> {
+        if let Some(lua_func) = func.as_lua_function() {
+            let chunk = lua_func.chunk();
+            let upvalues = lua_func.upvalues();
+            let upvalue_names = (0..upvalues.len())
+                .map(|i| match chunk.upvalue_descs.get(i) {
+                    Some(desc) if !desc.name.is_empty() => desc.name.to_string(),
+                    _ => "(no name)".to_string(),
+                })
+                .collect();
+            return Some(FunctionInfo {
+                params: chunk.param_count as u8,
+                is_vararg: chunk.is_vararg,
+                upvalue_names,
+                upvalues: upvalues
+                    .iter()
+                    .map(|up| up.as_ref().data.get_value())
+                    .collect(),
+                source: format_source(chunk.source_name.as_deref().unwrap_or("=?")),
+                line_range: (chunk.linedefined as i32, chunk.lastlinedefined as i32),
+            });
+        }
+        let upvalues: Vec<LuaValue> = if let Some(cclosure) = func.as_cclosure() {
+            cclosure.upvalues().to_vec()
+        } else if let Some(rclosure) = func.as_rclosure() {
+            rclosure.upvalues().to_vec()
+        } else if func.is_function() {
+            Vec::new()
+        } else {
+            return None;
+        };
+        Some(FunctionInfo {
+            params: 0,
+            is_vararg: true,
+            upvalue_names: vec![String::new(); upvalues.len()],
+            upvalues,
+            source: "[C]".to_string(),
+            line_range: (-1, -1),
+        })
+    }
+
+    /// The call frames of a thread, innermost first, with the locals active
+    /// in each. For a suspended coroutine the first frame is the one that
+    /// yielded. Returns an empty list for non-thread values and for
+    /// coroutines that have not started or are dead.
+    pub fn thread_stack(&self, thread: &LuaValue) -> Vec<FrameInfo> {
+        let Some(state) = thread.as_thread_mut() else {
+            return Vec::new();
+        };
+        state.stack_frames()
+    }
+
+    /// The call frames of this thread; see [`thread_stack`](Self::thread_stack).
+    pub fn stack_frames(&self) -> Vec<FrameInfo> {
+        (0..self.call_depth())
+            .filter_map(|level| {
+                let info = self.get_info_by_level(level, "Snl")?;
+                let locals = (1..=self.local_count(level))
+                    .filter_map(|i| self.get_local(level, i))
+                    .collect();
+                Some(FrameInfo {
+                    what: info.what.unwrap_or("C"),
+                    name: info.name,
+                    source: info.short_src.unwrap_or_default(),
+                    current_line: info.currentline.unwrap_or(-1),
+                    locals,
+                })
+            })
+            .collect()
+    }
+
     pub fn to_any_ref(&mut self, value: LuaValue) -> LuaAnyRef {
         self.global_state_mut().to_ref(value)
     }
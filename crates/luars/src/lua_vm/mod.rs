@@ -1,5 +1,7 @@
 // Lua Virtual Machine
 // Executes compiled bytecode with register-based architecture
+use std::any::TypeId;
+use std::collections::HashMap;
 use std::ffi::c_void;
 use std::ptr::null_mut;
 
@@ -10,12 +12,15 @@ pub mod debug_info;
 mod error_msg;
 mod execute;
 mod file_layout;
+mod load_hook;
 pub mod lua_error;
 pub mod lua_limits;
 mod lua_ref;
 mod lua_rng;
 mod lua_state;
 pub mod opcode;
+#[cfg(feature = "profiler")]
+mod profiler;
 mod safe_option;
 #[cfg(feature = "sandbox")]
 mod sandbox;
@@ -23,45 +28,65 @@ mod sandbox;
 mod shared_proto;
 pub(crate) mod stk_id;
 mod string_arth;
+mod type_options;
+mod type_registry;
 
 use crate::compiler::{LuaLanguageLevel, compile_code, compile_code_with_name};
 use crate::gc::{
-    CreateResult, GcKind, GcObjectPtr, GcState, ObjectAllocator, ThreadPtr, UpvaluePtr,
+    CreateResult, GcCollectionKind, GcEvent, GcKind, GcObjectPtr, GcState, GcStats,
+    ObjectAllocator, ThreadPtr, UpvaluePtr,
 };
 use crate::gc::{GC, ProtoPtr};
 use crate::lua_value::lua_convert::{FromLua, IntoLua};
 use crate::lua_value::{LuaProto, LuaUpvalue, LuaUserdata, LuaValue, LuaValueKind, UpvalueStore};
+use crate::lua_vm::async_thread::{AsyncTasks, TaskOwner};
 pub use crate::lua_vm::call_info::{CallInfo, CallInfoPtr};
 use crate::lua_vm::const_string::ConstString;
-pub use crate::lua_vm::debug_info::DebugInfo;
+pub use crate::lua_vm::debug_info::{DebugInfo, FrameInfo, FunctionInfo};
 pub(crate) use crate::lua_vm::error_msg::ErrorMsg;
 use crate::lua_vm::file_layout::inspect_file_chunk_layout;
+use crate::lua_vm::lua_error::CloseReport;
 pub use crate::lua_vm::lua_error::LuaError;
 use crate::lua_vm::lua_ref::RefManager;
-use crate::lua_vm::lua_ref::store_in_registry;
+pub(crate) use crate::lua_vm::lua_ref::store_in_registry;
 pub use crate::lua_vm::lua_ref::{
-    LUA_REFNIL, LuaAnyRef, LuaFunctionRef, LuaRefValue, LuaStringRef, LuaTableRef, RefId,
-    UserDataRef,
+    LUA_NOREF, LUA_REFNIL, LuaAnyRef, LuaFunctionRef, LuaRefValue, LuaStringRef, LuaTableRef,
+    LuaWeakValue, RefId, UserDataRef,
 };
 pub(crate) use crate::lua_vm::stk_id::StkId;
 
 type ArithMetaFn = fn(&mut LuaState) -> LuaResult<usize>;
+pub(crate) use crate::lua_vm::load_hook::LoadHook;
+pub use crate::lua_vm::load_hook::{
+    LOAD_EVENT_MAX_SOURCE, LoadCaller, LoadDecision, LoadEvent, LoadMode, LoadOrigin, LoadSource,
+};
 pub use crate::lua_vm::lua_state::LuaState;
-pub use crate::lua_vm::safe_option::SafeOption;
+#[cfg(feature = "profiler")]
+pub use crate::lua_vm::profiler::FunctionProfile;
+#[cfg(feature = "profiler")]
+pub(crate) use crate::lua_vm::profiler::{ProfileFrame, Profiler, next_profile_id};
+pub use crate::lua_vm::safe_option::{
+    CompileOptions, SafeOption, SafeOptionBuilder, SafeOptionError,
+};
 #[cfg(feature = "sandbox")]
 pub use crate::lua_vm::sandbox::SandboxConfig;
 use crate::platform_time::{PlatformInstant, unix_nanos};
-use crate::stdlib::Stdlib;
+use crate::stdlib::{OpenMode, Stdlib, StdlibSet};
 use crate::{LuaEnum, LuaRegistrable, OpaqueUserData, RustCallback, lib_registry};
 pub use execute::TmKind;
-pub(crate) use execute::arith::{lua_shiftl, luai_numpow};
+pub(crate) use execute::arith::{lua_fmod, lua_idiv, lua_imod, lua_shiftl, luai_numpow};
 pub use execute::{get_metamethod_event, get_metatable};
 pub use lua_rng::LuaRng;
 pub use opcode::{Instruction, OpCode};
 use std::future::Future;
 use std::pin::Pin;
 use std::ptr::NonNull;
+use std::rc::Rc;
+use std::time::Duration;
 pub use string_arth::*;
+pub(crate) use type_options::TypeObservers;
+pub use type_options::{GetObserver, SetAction, SetObserver, TypeOptions};
+pub use type_registry::{TypeClaim, TypeEntry, TypeRegistry, TypeRegistryError};
 
 pub type LuaResult<T> = Result<T, LuaError>;
 /// C Function type - Rust function callable from Lua
@@ -198,6 +223,35 @@ pub const LUA_MASKCALL: u8 = 1 << LUA_HOOKCALL as u8;
 pub const LUA_MASKRET: u8 = 1 << LUA_HOOKRET as u8;
 pub const LUA_MASKLINE: u8 = 1 << LUA_HOOKLINE as u8;
 pub const LUA_MASKCOUNT: u8 = 1 << LUA_HOOKCOUNT as u8;
+/// Internal mask bit routing Lua calls and returns to the profiler. It is 0
+/// without the `profiler` feature, so hook checks that include it compile
+/// to the same code as before.
+pub(crate) const LUA_MASKPROFILE: u8 = if cfg!(feature = "profiler") {
+    1 << 7
+} else {
+    0
+};
+/// Internal mask bit counting instructions down to the next poll of the
+/// interrupt hook, like a count hook.
+pub(crate) const LUA_MASKINTERRUPT: u8 = 1 << 6;
+/// Mask bits the VM sets for itself; debug hooks neither see nor clear them.
+pub(crate) const LUA_MASKINTERNAL: u8 = LUA_MASKPROFILE | LUA_MASKINTERRUPT;
+
+/// Shared metatable of a userdata type registered with `register_type_of`.
+pub(crate) struct UserdataTypeMeta {
+    pub(crate) metatable: LuaValue,
+    /// When set, `setmetatable` from Lua raises "cannot change a protected metatable".
+    pub(crate) protected: bool,
+    /// Field access hooks from `register_type_of_with`.
+    pub(crate) observers: TypeObservers,
+}
+
+/// Host policy deciding which environment variables `os.getenv` may read.
+pub(crate) type EnvFilter = dyn Fn(&str) -> bool;
+pub(crate) type InterruptHook = dyn Fn() -> Result<(), String>;
+
+/// Lua instructions run between two polls of the interrupt hook.
+pub(crate) const INTERRUPT_CHECK_INTERVAL: u32 = 1024;
 
 /// Global VM state (equivalent to global_State in Lua C API)
 /// Manages global resources shared by all execution threads/coroutines
@@ -232,6 +286,31 @@ pub struct GlobalState {
     /// Nil metatable
     pub(crate) nil_mt: Option<LuaValue>,
 
+    /// Per-type metatables of registered userdata types, keyed by the Rust type.
+    /// New userdata of a registered type start out with this metatable.
+    pub(crate) userdata_types: HashMap<TypeId, UserdataTypeMeta>,
+
+    /// Set once any registered type has field observers, so userdata field
+    /// access without observers never pays for the type lookup.
+    pub(crate) has_userdata_observers: bool,
+
+    /// Host policy for `os.getenv`: names it rejects read as unset.
+    pub(crate) env_filter: Option<Box<EnvFilter>>,
+
+    /// Host audit hook for chunks loaded by `load`, `loadfile`, `dofile`
+    /// and `require`.
+    pub(crate) load_hook: Option<Box<LoadHook>>,
+
+    /// Host hook polled while Lua code runs, e.g. to enforce a deadline.
+    pub(crate) interrupt_hook: Option<Box<InterruptHook>>,
+
+    /// Standard libraries opened so far, so reopening them is a no-op.
+    pub(crate) opened_stdlibs: StdlibSet,
+
+    /// Call counts and timings, once the profiler has been started.
+    #[cfg(feature = "profiler")]
+    pub(crate) profiler: Option<Box<Profiler>>,
+
     pub(crate) safe_option: SafeOption,
 
     /// Shared C call depth counter — tracks real Rust stack depth across all
@@ -258,6 +337,16 @@ pub struct GlobalState {
     /// Cached default I/O file handles for fast access (avoids registry lookup per io.write/read)
     pub(crate) io_default_output: Option<LuaValue>,
     pub(crate) io_default_input: Option<LuaValue>,
+
+    /// Async futures started by coroutines and not finished yet
+    pub(crate) async_tasks: Rc<AsyncTasks>,
+}
+
+impl Drop for GlobalState {
+    fn drop(&mut self) {
+        // Hosts that want the errors call `close` instead
+        let _ = self.close_state();
+    }
 }
 
 impl GlobalState {
@@ -282,6 +371,14 @@ impl GlobalState {
             number_mt: None,
             bool_mt: None,
             nil_mt: None,
+            userdata_types: HashMap::new(),
+            has_userdata_observers: false,
+            env_filter: None,
+            load_hook: None,
+            interrupt_hook: None,
+            opened_stdlibs: StdlibSet::empty(),
+            #[cfg(feature = "profiler")]
+            profiler: None,
             safe_option: option.clone(),
             n_ccalls: 0,
             version: LuaLanguageLevel::Lua55,
@@ -294,6 +391,7 @@ impl GlobalState {
             extra_space: null_mut(),
             io_default_output: None,
             io_default_input: None,
+            async_tasks: Rc::default(),
         });
 
         // Set GlobalState pointer in main_state
@@ -326,6 +424,37 @@ impl GlobalState {
         inner
     }
 
+    /// Close the state like `lua_close`, reporting what went wrong.
+    ///
+    /// Pending `__gc` finalizers run first, then every object that still has
+    /// one, newest registration first. Userdata are dropped next, one at a
+    /// time, with a panicking `Drop` caught and recorded, and everything else
+    /// is freed last. Dropping the state does the same and discards the report.
+    pub fn close(mut self: Pin<Box<Self>>) -> Result<(), CloseReport> {
+        let state = unsafe { self.as_mut().get_unchecked_mut() };
+        state.close_state()
+    }
+
+    fn close_state(&mut self) -> Result<(), CloseReport> {
+        if self.gc.gc_closing {
+            return Ok(());
+        }
+        let mut report = CloseReport::default();
+        // Running Lua code while unwinding risks a second panic, which aborts.
+        if !self.main_state.is_null() && !std::thread::panicking() {
+            let main_state = self.main_state;
+            report.finalizer_errors = self
+                .gc
+                .call_all_finalizers_at_close(&mut main_state.as_mut_ref().data);
+        }
+        report.drop_panics = self.gc.free_all_objects();
+        if report.is_empty() {
+            Ok(())
+        } else {
+            Err(report)
+        }
+    }
+
     pub(crate) fn main_state(&mut self) -> &mut LuaState {
         &mut self.main_state.as_mut_ref().data
     }
@@ -340,6 +469,9 @@ impl GlobalState {
     #[cold]
     #[inline(never)]
     pub fn error_with_object(&mut self, obj: LuaValue) -> LuaError {
+        if !self.is_own_value(&obj) {
+            return self.foreign_value_error();
+        }
         self.error_msg = ErrorMsg::Object(obj);
         LuaError::RuntimeError
     }
@@ -394,6 +526,9 @@ impl GlobalState {
 
     /// Set a value in the registry by string key
     pub fn registry_set(&mut self, key: &str, value: LuaValue) -> LuaResult<()> {
+        if !self.is_own_value(&value) {
+            return Err(self.foreign_value_error());
+        }
         let key_value = self.create_string(key)?;
 
         // Use VM table_set so we always run the GC barrier
@@ -460,11 +595,47 @@ impl GlobalState {
         self.registry_geti(ref_id as i64).unwrap_or_default()
     }
 
-    pub fn open_stdlib(&mut self, lib: Stdlib) -> LuaResult<()> {
-        lib_registry::create_standard_registry(lib).load_all(self)?;
+    /// Open one standard library, or several combined with `|`
+    /// (`Stdlib::Basic | Stdlib::String`).
+    ///
+    /// Libraries this VM already opened are skipped, and a library table
+    /// the stdlib installed earlier is reused with only its missing entries
+    /// filled in, so calling this again never undoes what scripts patched.
+    /// A table of the same name that the stdlib did not install is replaced.
+    /// See [`open_stdlib_with`](Self::open_stdlib_with) to reset entries
+    /// instead.
+    pub fn open_stdlib(&mut self, libs: impl Into<StdlibSet>) -> LuaResult<()> {
+        self.open_stdlib_with(libs, OpenMode::FillMissing)
+    }
+
+    /// Open standard libraries, choosing how existing library tables are
+    /// treated. [`OpenMode::Overwrite`] resets every function and value of
+    /// the libraries to the standard one, in the tables scripts already
+    /// hold. State created on first open, like `package.loaded` or
+    /// `io.stdout`, is kept either way.
+    pub fn open_stdlib_with(
+        &mut self,
+        libs: impl Into<StdlibSet>,
+        mode: OpenMode,
+    ) -> LuaResult<()> {
+        let libs = libs.into();
+        let todo = match mode {
+            OpenMode::FillMissing => libs - self.opened_stdlibs,
+            OpenMode::Overwrite => libs,
+        };
+        if todo.is_empty() {
+            return Ok(());
+        }
+        lib_registry::create_standard_registry(todo).load_all_with(self, mode)?;
+        self.opened_stdlibs |= todo;
         Ok(())
     }
 
+    /// The standard libraries opened on this VM so far.
+    pub fn opened_stdlibs(&self) -> StdlibSet {
+        self.opened_stdlibs
+    }
+
     /// Open multiple standard libraries at once.
     ///
     /// # Example
@@ -603,8 +774,46 @@ impl GlobalState {
         self.set_global(name, closure_val)
     }
 
+    /// Number of async futures started from Lua that are still running,
+    /// whether or not anything is currently polling them.
+    pub fn async_task_count(&self) -> usize {
+        self.async_tasks.len()
+    }
+
+    /// Cancel the async futures started by coroutine `thread`. They are
+    /// dropped at once, and an `AsyncThread` awaiting one fails with
+    /// "async task cancelled". `coroutine.close` does this for the thread
+    /// it closes. Returns how many futures were cancelled.
+    pub fn cancel_async_tasks_for(&mut self, thread: &LuaValue) -> usize {
+        match thread.as_thread_mut() {
+            Some(state) => self.async_tasks.cancel(Some(TaskOwner::of(state))),
+            None => 0,
+        }
+    }
+
+    /// Give every async future started from now on without an explicit
+    /// timeout a deadline of `timeout`, after which it is dropped and its
+    /// awaiter fails with "async task timed out". `None` (the default) lets
+    /// futures run for as long as they take. A hung host future then cannot
+    /// keep a coroutine, or the worker driving it, busy forever.
+    pub fn set_async_task_timeout(&mut self, timeout: Option<Duration>) {
+        self.async_tasks.set_default_timeout(timeout);
+    }
+
+    /// Cancel every async future started from Lua, e.g. before shutting
+    /// down. Returns how many futures were cancelled.
+    pub fn abort_async_tasks(&mut self) -> usize {
+        self.async_tasks.cancel(None)
+    }
+
     /// Register a UserData type as a Lua global with its static methods.
+    ///
+    /// Also creates the type's shared metatable (stored in the registry under
+    /// `name`, like `luaL_newmetatable`). Every userdata of type `T` created
+    /// afterwards uses it, so `getmetatable(obj).__index` is the place for
+    /// Lua-side extensions; Rust fields and methods still take precedence.
     pub fn register_type_of<T: LuaRegistrable>(&mut self, name: &str) -> LuaResult<()> {
+        self.register_userdata_metatable(TypeId::of::<T>(), name)?;
         let static_methods = T::lua_static_methods();
         let class_table = self.create_table(0, static_methods.len())?;
 
@@ -613,9 +822,127 @@ impl GlobalState {
             self.raw_set(&class_table, key, LuaValue::cfunction(func));
         }
 
+        if let Some(constructor) = T::lua_constructor() {
+            self.set_type_constructor(&class_table, constructor)?;
+        }
+
         self.set_global(name, class_table)
     }
 
+    /// Make `Type(...)` call `constructor` (the `#[lua(constructor)]`
+    /// function) through a `__call` metamethod on the type table.
+    pub(crate) fn set_type_constructor(
+        &mut self,
+        class_table: &LuaValue,
+        constructor: CFunction,
+    ) -> LuaResult<()> {
+        let mt = self.create_table(0, 1)?;
+        let call_key = self.const_strings.get_tm_value(TmKind::Call);
+        self.raw_set(&mt, call_key, LuaValue::cfunction(constructor));
+        if let Some(t) = class_table.as_table_mut() {
+            t.set_metatable(Some(mt));
+        }
+        Ok(())
+    }
+
+    /// Like [`register_type_of`](Self::register_type_of), additionally
+    /// installing the field observers from `options` for `T`.
+    pub fn register_type_of_with<T: LuaRegistrable>(
+        &mut self,
+        name: &str,
+        options: TypeOptions,
+    ) -> LuaResult<()> {
+        self.register_type_of::<T>(name)?;
+        self.set_userdata_observers(TypeId::of::<T>(), options.into());
+        Ok(())
+    }
+
+    /// Register every type of `types`, as `register_type_of` /
+    /// `register_type_of_with` would one by one.
+    pub fn register_types(&mut self, types: &TypeRegistry) -> LuaResult<()> {
+        types.install(self)
+    }
+
+    pub(crate) fn set_userdata_observers(&mut self, type_id: TypeId, observers: TypeObservers) {
+        if observers.on_set.is_some() || observers.on_get.is_some() {
+            self.has_userdata_observers = true;
+        }
+        if let Some(meta) = self.userdata_types.get_mut(&type_id) {
+            meta.observers = observers;
+        }
+    }
+
+    /// Observers registered for the type of `ud`, if any.
+    #[inline]
+    pub(crate) fn userdata_observers(&self, ud: &LuaUserdata) -> Option<TypeObservers> {
+        if !self.has_userdata_observers {
+            return None;
+        }
+        let type_id = ud.registered_type_id()?;
+        self.userdata_types
+            .get(&type_id)
+            .map(|meta| meta.observers.clone())
+    }
+
+    pub(crate) fn register_userdata_metatable(
+        &mut self,
+        type_id: TypeId,
+        name: &str,
+    ) -> LuaResult<()> {
+        let metatable = match self.userdata_types.get(&type_id) {
+            Some(meta) => meta.metatable,
+            None => {
+                let metatable = self.create_table(0, 2)?;
+                let index = self.create_table(0, 0)?;
+                let index_key = self.const_strings.get_tm_value(TmKind::Index);
+                self.raw_set(&metatable, index_key, index);
+                let name_key = self.const_strings.tm_name;
+                let name_value = self.create_string(name)?;
+                self.raw_set(&metatable, name_key, name_value);
+                self.userdata_types.insert(
+                    type_id,
+                    UserdataTypeMeta {
+                        metatable,
+                        protected: true,
+                        observers: TypeObservers::default(),
+                    },
+                );
+                metatable
+            }
+        };
+        let registry = self.registry;
+        let key = self.create_string(name)?;
+        self.raw_set(&registry, key, metatable);
+        Ok(())
+    }
+
+    /// Shared metatable of a type registered with
+    /// [`register_type_of`](Self::register_type_of).
+    pub fn userdata_metatable<T: 'static>(&self) -> Option<LuaValue> {
+        self.userdata_types
+            .get(&TypeId::of::<T>())
+            .map(|meta| meta.metatable)
+    }
+
+    /// Choose whether Lua code may replace the metatable of a registered
+    /// userdata type with `setmetatable`. Registered types start protected.
+    /// Returns `false` if `T` has not been registered.
+    pub fn set_userdata_metatable_protected<T: 'static>(&mut self, protected: bool) -> bool {
+        match self.userdata_types.get_mut(&TypeId::of::<T>()) {
+            Some(meta) => {
+                meta.protected = protected;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `Some(protected)` if `ud` is of a registered type, `None` otherwise.
+    pub(crate) fn userdata_metatable_protection(&self, ud: &LuaUserdata) -> Option<bool> {
+        let type_id = ud.registered_type_id()?;
+        self.userdata_types.get(&type_id).map(|meta| meta.protected)
+    }
+
     /// Create a table and immediately wrap it in a managed `LuaTableRef`.
     pub fn create_table_ref(
         &mut self,
@@ -673,7 +1000,7 @@ impl GlobalState {
     }
 
     pub fn to_table_ref(&mut self, value: LuaValue) -> Option<LuaTableRef> {
-        if !value.is_table() {
+        if !value.is_table() || !self.is_own_value(&value) {
             return None;
         }
         let ref_id = store_in_registry(self, value);
@@ -684,7 +1011,7 @@ impl GlobalState {
     }
 
     pub fn to_function_ref(&mut self, value: LuaValue) -> Option<LuaFunctionRef> {
-        if !value.is_function() {
+        if !value.is_function() || !self.is_own_value(&value) {
             return None;
         }
         let ref_id = store_in_registry(self, value);
@@ -695,7 +1022,7 @@ impl GlobalState {
     }
 
     pub fn to_string_ref(&mut self, value: LuaValue) -> Option<LuaStringRef> {
-        if !value.is_string() {
+        if !value.is_string() || !self.is_own_value(&value) {
             return None;
         }
         let ref_id = store_in_registry(self, value);
@@ -706,6 +1033,9 @@ impl GlobalState {
     }
 
     pub fn to_userdata_ref<T: 'static>(&mut self, value: LuaValue) -> Option<UserDataRef<T>> {
+        if !self.is_own_value(&value) {
+            return None;
+        }
         let userdata = value.as_userdata_mut()?;
         userdata.downcast_ref::<T>()?;
         let ref_id = store_in_registry(self, value);
@@ -715,6 +1045,127 @@ impl GlobalState {
         ))
     }
 
+    /// Compiler optimizations used for every chunk compiled from now on,
+    /// including those loaded from Lua with `load`, `dofile` or `require`.
+    pub fn set_compile_options(&mut self, options: CompileOptions) {
+        self.safe_option.compile_options = options;
+    }
+
+    pub fn compile_options(&self) -> CompileOptions {
+        self.safe_option.compile_options
+    }
+
+    /// Decide which environment variables `os.getenv` may read. Names for
+    /// which `filter` returns `false` read as unset, so scripts cannot tell
+    /// a hidden variable from a missing one. Everything is visible until a
+    /// filter is installed.
+    pub fn set_env_filter(&mut self, filter: impl Fn(&str) -> bool + 'static) {
+        self.env_filter = Some(Box::new(filter));
+    }
+
+    /// Remove the filter installed by [`set_env_filter`](Self::set_env_filter).
+    pub fn clear_env_filter(&mut self) {
+        self.env_filter = None;
+    }
+
+    /// Whether `os.getenv` may read the variable `name`. A name that is not
+    /// UTF-8 cannot be shown to the filter and is refused while one is set.
+    pub(crate) fn env_visible(&self, name: &[u8]) -> bool {
+        match &self.env_filter {
+            None => true,
+            Some(filter) => std::str::from_utf8(name).is_ok_and(filter),
+        }
+    }
+
+    /// Inspect every chunk Lua code loads through `load`, `loadfile`,
+    /// `dofile` or `require` before it is compiled or undumped. The hook
+    /// sees whether the chunk is text or binary, its name, its contents
+    /// and the Lua line that asked for it; returning
+    /// [`LoadDecision::Deny`] refuses the chunk. Chunks the host loads
+    /// itself (`LuaState::load`, `LuaState::dofile`, ...) are not shown.
+    pub fn set_load_hook(&mut self, hook: impl Fn(&LoadEvent) -> LoadDecision + 'static) {
+        self.load_hook = Some(Box::new(hook));
+    }
+
+    /// Remove the hook installed by [`set_load_hook`](Self::set_load_hook).
+    pub fn clear_load_hook(&mut self) {
+        self.load_hook = None;
+    }
+
+    /// Start recording how often each Lua function is called and how long
+    /// it runs, on every thread of this VM. Recording costs a clock read
+    /// per call and per return; results accumulate until
+    /// [`reset_profile`](Self::reset_profile).
+    #[cfg(feature = "profiler")]
+    pub fn start_profiler(&mut self) {
+        self.profiler.get_or_insert_default().start();
+        self.main_state().hook_mask |= LUA_MASKPROFILE;
+    }
+
+    /// Stop recording. Functions still running on the main thread are
+    /// charged up to now.
+    #[cfg(feature = "profiler")]
+    pub fn stop_profiler(&mut self) {
+        self.main_state().close_profile_frames();
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.stop();
+        }
+        self.main_state().hook_mask &= !LUA_MASKPROFILE;
+    }
+
+    /// Whether the profiler is recording.
+    #[cfg(feature = "profiler")]
+    pub fn is_profiling(&self) -> bool {
+        self.profiler.as_ref().is_some_and(|p| p.is_running())
+    }
+
+    /// Every Lua function that completed a call while recording, most
+    /// exclusive time first.
+    #[cfg(feature = "profiler")]
+    pub fn profile_report(&self) -> Vec<FunctionProfile> {
+        self.profiler
+            .as_ref()
+            .map(|p| p.report())
+            .unwrap_or_default()
+    }
+
+    /// Discard the recorded counts and timings.
+    #[cfg(feature = "profiler")]
+    pub fn reset_profile(&mut self) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.reset();
+        }
+    }
+
+    /// Poll `hook` about every thousand Lua instructions run by a thread of
+    /// this VM. Returning `Err(message)` raises `message` at the running
+    /// Lua line. Until the hook succeeds again it is polled before every
+    /// instruction, so code that catches the error with `pcall` is stopped
+    /// again right away.
+    ///
+    /// Install the hook before starting the code to watch: functions
+    /// already running only notice it once they call or return, and other
+    /// coroutines once they are resumed. The hook
+    /// must not try to reach the VM.
+    pub fn set_interrupt_hook(&mut self, hook: impl Fn() -> Result<(), String> + 'static) {
+        self.interrupt_hook = Some(Box::new(hook));
+        self.main_state().sync_interrupt_mask();
+    }
+
+    /// Remove the hook installed by [`set_interrupt_hook`](Self::set_interrupt_hook).
+    pub fn clear_interrupt_hook(&mut self) {
+        self.interrupt_hook = None;
+        self.main_state().sync_interrupt_mask();
+    }
+
+    /// Run the load hook, if any, returning the denial message.
+    pub(crate) fn check_load(&self, event: &LoadEvent) -> Result<(), String> {
+        match self.load_hook.as_ref().map(|hook| hook(event)) {
+            None | Some(LoadDecision::Allow) => Ok(()),
+            Some(LoadDecision::Deny(msg)) => Err(msg),
+        }
+    }
+
     /// Compile source code using VM's string pool.
     ///
     /// This owner-level helper does not choose an error target state.
@@ -757,8 +1208,16 @@ impl GlobalState {
     }
 
     pub(crate) fn load_proto_from_file(&mut self, path: &str) -> Result<ProtoPtr, String> {
-        use crate::lua_value::chunk_serializer;
+        self.load_audited_file(path, None)
+    }
 
+    /// Load a file, first showing it to the load hook when `audit` names
+    /// the library function and Lua caller loading it.
+    pub(crate) fn load_audited_file(
+        &mut self,
+        path: &str,
+        audit: Option<(LoadOrigin, Option<LoadCaller>)>,
+    ) -> Result<ProtoPtr, String> {
         #[cfg(miri)]
         let resolved_path = std::path::PathBuf::from(path);
 
@@ -768,12 +1227,18 @@ impl GlobalState {
 
         let file_bytes =
             std::fs::read(&resolved_path).map_err(|e| format!("cannot open {}: {}", path, e))?;
-        let layout = inspect_file_chunk_layout(&file_bytes);
+        let chunk_name = format!("@{}", resolved_path.display());
 
-        if layout.is_binary && !self.safe_option.allow_load_bytecode {
-            return Err(
-                "attempt to load a binary chunk (bytecode loading is disabled)".to_string(),
-            );
+        if let Some((origin, caller)) = audit
+            && self.load_hook.is_some()
+        {
+            let mode = if inspect_file_chunk_layout(&file_bytes).is_binary {
+                LoadMode::Binary
+            } else {
+                LoadMode::Text
+            };
+            let event = LoadEvent::new(origin, mode, &chunk_name, &file_bytes, caller);
+            self.check_load(&event)?;
         }
 
         #[cfg(feature = "shared-proto")]
@@ -796,23 +1261,7 @@ impl GlobalState {
             }
         }
 
-        let chunk_name = format!("@{}", resolved_path.display());
-
-        let chunk = if layout.is_binary {
-            chunk_serializer::deserialize_chunk_with_strings_vm(
-                &file_bytes[layout.skip_offset..],
-                self,
-            )
-            .map_err(|e| format!("binary load error: {}", e))?
-        } else {
-            let code_str = String::from_utf8(file_bytes[layout.text_start..].to_vec())
-                .map_err(|_| "source file is not valid UTF-8".to_string())?;
-            self.compile_with_name(&code_str, &chunk_name)?
-        };
-
-        let proto = self
-            .prepare_loaded_chunk(chunk)
-            .map_err(|_| self.gc.get_error_message())?;
+        let proto = self.load_proto_from_bytes(&file_bytes, &chunk_name)?;
 
         #[cfg(feature = "shared-proto")]
         {
@@ -838,12 +1287,54 @@ impl GlobalState {
         Ok(proto)
     }
 
+    /// Compile or undump the contents of a Lua file, however they were read:
+    /// a leading BOM and `#` line are skipped and a binary chunk is recognized
+    /// by its signature, as luaL_loadfilex does.
+    pub(crate) fn load_proto_from_bytes(
+        &mut self,
+        bytes: &[u8],
+        chunk_name: &str,
+    ) -> Result<ProtoPtr, String> {
+        use crate::lua_value::chunk_serializer;
+
+        let layout = inspect_file_chunk_layout(bytes);
+
+        if layout.is_binary && !self.safe_option.allow_load_bytecode {
+            return Err(
+                "attempt to load a binary chunk (bytecode loading is disabled)".to_string(),
+            );
+        }
+
+        let chunk = if layout.is_binary {
+            let chunk = chunk_serializer::deserialize_chunk_with_strings_vm(
+                &bytes[layout.skip_offset..],
+                self,
+            )
+            .map_err(|e| format!("binary load error: {}", e))?;
+            // File chunks get exactly one upvalue (_ENV) from their callers
+            if chunk.upvalue_count > 1 {
+                return Err("main chunk must have at most one upvalue (_ENV)".to_string());
+            }
+            chunk
+        } else {
+            let code_str = String::from_utf8(bytes[layout.text_start..].to_vec())
+                .map_err(|_| "source file is not valid UTF-8".to_string())?;
+            self.compile_with_name(&code_str, chunk_name)?
+        };
+
+        self.prepare_loaded_chunk(chunk)
+            .map_err(|_| self.gc.get_error_message())
+    }
+
     pub fn get_global(&mut self, name: &str) -> LuaResult<Option<LuaValue>> {
         let key = self.create_string(name)?;
         Ok(self.raw_get(&self.global, &key))
     }
 
     pub fn set_global(&mut self, name: &str, value: LuaValue) -> LuaResult<()> {
+        if !self.is_own_value(&value) {
+            return Err(self.foreign_value_error());
+        }
         let key = self.create_string(name)?;
 
         // Use VM table_set so we always run the GC barrier
@@ -900,6 +1391,10 @@ impl GlobalState {
                 Stdlib::Io => config.io,
                 Stdlib::Package => config.package,
                 Stdlib::Debug => config.debug,
+                #[cfg(feature = "json")]
+                Stdlib::Json => config.json,
+                #[cfg(feature = "profiler")]
+                Stdlib::Profiler => config.profiler,
                 Stdlib::Basic | Stdlib::All => false,
             };
 
@@ -909,6 +1404,7 @@ impl GlobalState {
         }
 
         for (name, value) in &config.injected_globals {
+            self.check_values_owned(std::slice::from_ref(value))?;
             let key = self.create_string(name)?;
             self.raw_set(&env, key, *value);
         }
@@ -928,6 +1424,9 @@ impl GlobalState {
     /// Set the metatable for all strings
     /// This allows string methods to be called with : syntax (e.g., str:upper())
     pub fn set_string_metatable(&mut self, string_lib_table: LuaValue) -> LuaResult<()> {
+        if !self.is_own_value(&string_lib_table) {
+            return Err(self.foreign_value_error());
+        }
         // Create a metatable with __index + arithmetic metamethods
         // This matches Lua 5.5's createmetatable() in lstrlib.c
         let mt_value = self.create_table(0, 10)?;
@@ -966,6 +1465,9 @@ impl GlobalState {
 
     /// Create a new thread (coroutine) - returns ThreadId-based LuaValue
     pub fn create_thread(&mut self, func: LuaValue) -> CreateResult {
+        if !self.is_own_value(&func) {
+            return Err(self.foreign_value_error());
+        }
         // Create a new LuaState for the coroutine
         let mut thread = LuaState::new(
             1,
@@ -975,12 +1477,14 @@ impl GlobalState {
         );
 
         // Push the function onto the thread's stack (updates stack_top)
-        // It will be used when resume() is first called
+        // It will be used when resume() is first called. An idle coroutine
+        // holds just that one slot; resume() grows the stack.
+        thread.stack.reserve_exact(1);
         thread
             .push_value(func)
             .expect("Failed to push function onto coroutine stack");
 
-        // Create thread in ObjectPool and return LuaValue
+        // Hand the thread to the GC and return it as a LuaValue
         self.object_allocator.create_thread(&mut self.gc, thread)
     }
 
@@ -1008,8 +1512,9 @@ impl GlobalState {
             return Err(self.error("invalid thread".to_string()));
         };
 
+        // The main thread is always running or normal
         if l.is_main_thread() {
-            return Err(self.error("cannot resume main thread".to_string()));
+            return Err(self.error("cannot resume non-suspended coroutine".to_string()));
         }
 
         // Borrow mutably and delegate to LuaState::resume
@@ -1019,9 +1524,12 @@ impl GlobalState {
     /// Fast table get - NO metatable support!
     /// Use this for normal field access (GETFIELD, GETTABLE, GETI)
     /// This is the correct behavior for Lua bytecode instructions
-    /// Only use table_get_with_meta when you explicitly need __index metamethod
+    /// Use `LuaState::table_get` when you explicitly need the __index metamethod
     #[inline(always)]
     pub fn raw_get(&self, table_value: &LuaValue, key: &LuaValue) -> Option<LuaValue> {
+        if !self.is_own_value(table_value) || !self.is_own_value(key) {
+            return None;
+        }
         let table = table_value.as_table()?;
         table.raw_get(key)
     }
@@ -1038,13 +1546,15 @@ impl GlobalState {
     ///     println!("{} = {}", k, v);
     /// }
     /// ```
-    pub fn table_pairs(&self, table_value: &LuaValue) -> LuaResult<Vec<(LuaValue, LuaValue)>> {
+    pub fn table_pairs(&mut self, table_value: &LuaValue) -> LuaResult<Vec<(LuaValue, LuaValue)>> {
+        self.check_values_owned(std::slice::from_ref(table_value))?;
         let table = table_value.as_table().ok_or(LuaError::RuntimeError)?;
         Ok(table.iter_all())
     }
 
     /// Get the length of the array part of a table (like `#t` in Lua).
-    pub fn table_length(&self, table_value: &LuaValue) -> LuaResult<usize> {
+    pub fn table_length(&mut self, table_value: &LuaValue) -> LuaResult<usize> {
+        self.check_values_owned(std::slice::from_ref(table_value))?;
         let table = table_value.as_table().ok_or(LuaError::RuntimeError)?;
         Ok(table.len())
     }
@@ -1100,6 +1610,10 @@ impl GlobalState {
 
     #[inline(always)]
     pub fn raw_set(&mut self, table_value: &LuaValue, key: LuaValue, value: LuaValue) -> bool {
+        if !self.is_own_value(table_value) || !self.is_own_value(&key) || !self.is_own_value(&value)
+        {
+            return false;
+        }
         let Some(table) = table_value.as_table_mut() else {
             return false;
         };
@@ -1122,11 +1636,17 @@ impl GlobalState {
 
     #[inline(always)]
     pub fn raw_geti(&self, table_value: &LuaValue, key: i64) -> Option<LuaValue> {
+        if !self.is_own_value(table_value) {
+            return None;
+        }
         let table = table_value.as_table()?;
         table.raw_geti(key)
     }
 
     pub fn raw_seti(&mut self, table_value: &LuaValue, key: i64, value: LuaValue) -> bool {
+        if !self.is_own_value(table_value) || !self.is_own_value(&value) {
+            return false;
+        }
         let Some(table) = table_value.as_table_mut() else {
             return false;
         };
@@ -1172,6 +1692,22 @@ impl GlobalState {
         self.object_allocator.create_bytes(&mut self.gc, bytes)
     }
 
+    /// Create bytes `range` of string `s`. A long substring of a long
+    /// string shares its bytes instead of copying them.
+    ///
+    /// Panics if `s` is not a string or `range` is out of its bounds.
+    #[inline]
+    pub fn create_substring(
+        &mut self,
+        s: &LuaValue,
+        range: std::ops::Range<usize>,
+    ) -> CreateResult {
+        let ptr = s.as_string_ptr().expect("create_substring: not a string");
+        assert!(range.start <= range.end && range.end <= ptr.as_ref().data.str.len());
+        self.object_allocator
+            .create_substring(&mut self.gc, ptr, range)
+    }
+
     /// Create string from owned String (avoids clone for non-interned strings)
     #[inline]
     pub fn create_string_owned(&mut self, s: String) -> CreateResult {
@@ -1186,7 +1722,14 @@ impl GlobalState {
     }
 
     /// Create new userdata
-    pub fn create_userdata(&mut self, data: LuaUserdata) -> CreateResult {
+    pub fn create_userdata(&mut self, mut data: LuaUserdata) -> CreateResult {
+        if !self.userdata_types.is_empty()
+            && data.get_metatable().is_none()
+            && let Some(type_id) = data.registered_type_id()
+            && let Some(meta) = self.userdata_types.get(&type_id)
+        {
+            data.set_metatable(meta.metatable);
+        }
         self.object_allocator.create_userdata(&mut self.gc, data)
     }
 
@@ -1206,6 +1749,7 @@ impl GlobalState {
     /// The upvalues are automatically created as closed upvalues with the given values
     #[inline]
     pub fn create_c_closure(&mut self, func: CFunction, upvalues: Vec<LuaValue>) -> CreateResult {
+        self.check_values_owned(&upvalues)?;
         self.object_allocator
             .create_c_closure(&mut self.gc, func, upvalues)
     }
@@ -1214,6 +1758,7 @@ impl GlobalState {
     /// Unlike CFunction (bare fn pointer), this can capture arbitrary Rust state.
     #[inline]
     pub fn create_rclosure(&mut self, func: RustCallback, upvalues: Vec<LuaValue>) -> CreateResult {
+        self.check_values_owned(&upvalues)?;
         self.object_allocator
             .create_rclosure(&mut self.gc, func, upvalues)
     }
@@ -1256,6 +1801,9 @@ impl GlobalState {
     /// Create a closed upvalue with a value
     #[inline(always)]
     pub fn create_upvalue_closed(&mut self, value: LuaValue) -> LuaResult<UpvaluePtr> {
+        if !self.is_own_value(&value) {
+            return Err(self.foreign_value_error());
+        }
         let upval = LuaUpvalue::new_closed(value);
         self.object_allocator.create_upvalue(&mut self.gc, upval)
     }
@@ -1266,13 +1814,18 @@ impl GlobalState {
     //
     /// Check GC and run a step if needed (like luaC_checkGC in Lua 5.5)
     ///
-    ///  Must check gc_stopped and gc_stopem before running GC!
+    /// `GC::step` and `full_gc` refuse to run when the GC is blocked:
     /// - gc_stopped: User explicitly stopped GC (collectgarbage("stop"))
-    /// - gc_stopem: GC is already running (prevents recursive GC during allocation)
+    /// - in_collection / gc_stopem: GC or a finalizer is already running
     #[inline(always)]
     fn check_gc(&mut self, l: &mut LuaState) -> bool {
         if self.gc.gc_debt <= 0 {
-            self.gc.step(l);
+            if self.gc.take_emergency_request() {
+                self.full_gc(l, true);
+                self.gc.finish_emergency_gc();
+            } else {
+                self.gc.step(l);
+            }
             return true;
         }
 
@@ -1282,8 +1835,19 @@ impl GlobalState {
     // ============ GC Management ============
     /// Perform a full GC cycle (like luaC_fullgc in Lua 5.5)
     /// This is the internal version that can be called in emergency situations
+    ///
+    /// Does nothing when the collector is already running, e.g. when a
+    /// finalizer asks for a collection.
     fn full_gc(&mut self, l: &mut LuaState, is_emergency: bool) {
+        if !self.gc.enter_collection() {
+            return;
+        }
         self.gc.gc_emergency = is_emergency;
+        self.gc.begin_collection(if is_emergency {
+            GcCollectionKind::Emergency
+        } else {
+            GcCollectionKind::Full
+        });
 
         // Dispatch based on GC mode (from luaC_fullgc)
         match self.gc.gc_kind {
@@ -1303,6 +1867,8 @@ impl GlobalState {
 
         self.object_allocator.trim_after_full_gc();
         self.gc.gc_emergency = false;
+        self.gc.end_collection();
+        self.gc.leave_collection();
     }
 
     /// Full GC cycle for incremental mode (like fullinc in Lua 5.5)
@@ -1337,32 +1903,122 @@ impl GlobalState {
         self.gc.enter_gen(l);
     }
 
-    /// Get GC statistics
+    /// Take a handle to `value` that can later tell whether its object was
+    /// collected. See [`is_value_alive`](Self::is_value_alive).
+    pub fn weak_value(&self, value: LuaValue) -> LuaWeakValue {
+        let generation = value
+            .as_gc_ptr()
+            .and_then(|ptr| self.object_allocator.slot_generation(ptr));
+        LuaWeakValue::new(value, generation)
+    }
+
+    /// Check whether the object behind a weak handle is still alive.
+    ///
+    /// Raw `LuaValue`s are not GC roots: once Lua drops every reference and a
+    /// collection runs, a copy kept in Rust dangles, and the memory may
+    /// already hold a new object. Values that must outlive Lua-side
+    /// references should be anchored with [`create_ref`](Self::create_ref)
+    /// or one of the `to_*_ref` handles; this check lets embedders detect the
+    /// mistake instead of dereferencing freed memory. It compares the
+    /// handle's allocation generation with the object's slot, without
+    /// touching the object. Non-collectable values are always alive.
+    pub fn is_value_alive(&self, value: &LuaWeakValue) -> bool {
+        let Some(ptr) = value.value_unchecked().as_gc_ptr() else {
+            return true;
+        };
+        match value.generation() {
+            Some(generation) => self.object_allocator.slot_generation(ptr) == Some(generation),
+            // Objects allocated outside the pools (boxed under miri, shared
+            // constant strings) can only be compared by address.
+            None => self.gc.owns_object(ptr),
+        }
+    }
+
+    /// Check that a value was not created by another VM.
+    ///
+    /// Objects record the VM whose collector registered them, and values from
+    /// one VM must never be stored in or passed to another: the other VM would
+    /// neither mark nor free them. Every host entry point that takes a value
+    /// checks this: the `Result` APIs fail with "value belongs to a different
+    /// VM", raw accessors report a foreign table or key as absent and refuse
+    /// to store a foreign value. It is a single compare against the object
+    /// header. Non-collectable values and shared objects belong to every VM.
+    /// Values of a VM that was already dropped cannot be detected, as their
+    /// objects, header included, are freed with it.
+    #[inline(always)]
+    pub fn is_own_value(&self, value: &LuaValue) -> bool {
+        self.gc.owns_value(value)
+    }
+
+    /// Fail with "value belongs to a different VM" unless every value is
+    /// this VM's; for the `Result` APIs built on the raw accessors.
+    pub(crate) fn check_values_owned(&mut self, values: &[LuaValue]) -> LuaResult<()> {
+        if values.iter().all(|v| self.is_own_value(v)) {
+            Ok(())
+        } else {
+            Err(self.foreign_value_error())
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn foreign_value_error(&mut self) -> LuaError {
+        self.error("value belongs to a different VM".to_string())
+    }
+
+    /// Get GC statistics, formatted for reading. See
+    /// [`gc_stats_struct`](Self::gc_stats_struct) for the numbers themselves.
     pub fn gc_stats(&self) -> String {
-        let stats = self.gc.stats();
+        let stats = self.gc_stats_struct();
         format!(
             "GC Stats:\n\
             - Bytes allocated: {}\n\
             - Threshold: {}\n\
-            - Total collections: {}\n\
+            - Debt: {}\n\
+            - Bytes reclaimed: {}\n\
+            - Incremental cycles: {}\n\
+            - Major cycles: {}\n\
             - Minor collections: {}\n\
-            - Major collections: {}\n\
-            - Objects collected: {}\n\
+            - Full collections: {}\n\
+            - Emergency collections: {}\n\
+            - Last pause: {:?}\n\
             - Young generation size: {}\n\
-            - Old generation size: {}\n\
-            - Promoted objects: {}",
+            - Old generation size: {}",
             stats.bytes_allocated,
             stats.threshold,
-            stats.collection_count,
+            stats.debt,
+            stats.bytes_reclaimed,
+            stats.incremental_cycles,
+            stats.major_cycles,
             stats.minor_collections,
-            stats.major_collections,
-            stats.objects_collected,
+            stats.full_collections,
+            stats.emergency_collections,
+            stats.last_pause_duration,
             stats.young_gen_size,
-            stats.old_gen_size,
-            stats.promoted_objects
+            stats.old_gen_size
         )
     }
 
+    /// Snapshot of the collector: heap size, collection counts per kind
+    /// and the duration of the last collection.
+    pub fn gc_stats_struct(&self) -> GcStats {
+        self.gc.stats()
+    }
+
+    /// Call `hook` when a collection starts and when it ends.
+    ///
+    /// The hook runs in the middle of the collection, so it must not try to
+    /// reach the VM; record the event and act on it later. Timing costs two
+    /// clock reads per collection whether or not a hook is installed.
+    pub fn set_gc_event_hook(&mut self, hook: impl Fn(GcEvent) + 'static) {
+        self.gc.set_event_hook(Some(Box::new(hook)));
+    }
+
+    /// Remove the hook installed by [`set_gc_event_hook`](Self::set_gc_event_hook).
+    pub fn clear_gc_event_hook(&mut self) {
+        self.gc.set_event_hook(None);
+    }
+
     pub(crate) fn get_main_thread_ptr(&self) -> ThreadPtr {
         self.main_state
     }
@@ -1377,7 +2033,11 @@ impl GlobalState {
         }
     }
 
+    /// A metatable from another VM is ignored, like any other foreign value.
     pub fn set_basic_metatable(&mut self, kind: LuaValueKind, mt: Option<LuaValue>) {
+        if mt.is_some_and(|mt| !self.is_own_value(&mt)) {
+            return;
+        }
         match kind {
             LuaValueKind::String => self.string_mt = mt,
             LuaValueKind::Integer | LuaValueKind::Float => self.number_mt = mt,
@@ -1445,7 +2105,11 @@ impl GlobalStateHandle {
     }
 
     pub(crate) fn change_gc_mode(self, state: *mut LuaState, kind: GcKind) {
-        self.as_mut().gc.change_mode(unsafe { &mut *state }, kind);
+        let gc = &mut self.as_mut().gc;
+        if gc.enter_collection() {
+            gc.change_mode(unsafe { &mut *state }, kind);
+            gc.leave_collection();
+        }
     }
 }
 
@@ -0,0 +1,270 @@
+// Per-function call counts and wall-clock timings (`profiler` feature).
+//
+// Lua calls and returns reach the profiler through the hook-mask bit
+// LUA_MASKPROFILE. Each thread keeps a shadow stack of the Lua frames it
+// entered while recording; a frame's time is charged when it returns.
+// Frames left behind by an error or replaced by a tail call are charged
+// when the next call or return at their depth comes by.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ahash::RandomState;
+
+use crate::compiler::format_source;
+use crate::lua_value::LuaProto;
+use crate::platform_time::PlatformInstant;
+
+static NEXT_PROFILE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A fresh [`LuaProto::profile_id`], unique across every VM of the process.
+/// Protos are keyed by it rather than by address, which a collected proto
+/// hands on to the next one allocated in its slot.
+pub(crate) fn next_profile_id() -> u64 {
+    NEXT_PROFILE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Call count and timings of one Lua function, from
+/// [`GlobalState::profile_report`](crate::GlobalState::profile_report).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// Short source name, as in error messages.
+    pub source: String,
+    /// Line where the function is defined; 0 for a main chunk.
+    pub line_defined: usize,
+    /// Completed calls.
+    pub calls: u64,
+    /// Time from entry to return, callees included. Recursive calls are
+    /// only counted at the outermost level.
+    pub inclusive_ns: u64,
+    /// Time spent in the function itself and the C functions it calls.
+    pub exclusive_ns: u64,
+}
+
+struct FunctionStats {
+    profile: FunctionProfile,
+    /// Frames of this function on the shadow stacks, to detect recursion.
+    active: u32,
+}
+
+/// One Lua frame on a thread's shadow stack.
+pub(crate) struct ProfileFrame {
+    slot: usize,
+    depth: usize,
+    session: u32,
+    start: u64,
+    /// Time spent in Lua callees, in clock units.
+    child: u64,
+}
+
+/// Timestamps for the shadow stacks. On x86_64 these are TSC ticks, read
+/// in a fraction of the time `Instant::now` takes, and converted to
+/// nanoseconds against `Instant` when a report is made; elsewhere they are
+/// nanoseconds already.
+struct ProfileClock {
+    start: PlatformInstant,
+    start_ticks: u64,
+}
+
+impl ProfileClock {
+    fn new() -> Self {
+        Self {
+            start: PlatformInstant::now(),
+            start_ticks: Self::read_ticks(),
+        }
+    }
+
+    #[inline(always)]
+    fn read_ticks() -> u64 {
+        #[cfg(all(target_arch = "x86_64", not(miri)))]
+        {
+            // SAFETY: RDTSC is available on every x86_64 CPU.
+            unsafe { std::arch::x86_64::_rdtsc() }
+        }
+        #[cfg(not(all(target_arch = "x86_64", not(miri))))]
+        {
+            0
+        }
+    }
+
+    #[inline(always)]
+    fn now(&self) -> u64 {
+        if cfg!(all(target_arch = "x86_64", not(miri))) {
+            Self::read_ticks()
+        } else {
+            self.start.elapsed_nanos()
+        }
+    }
+
+    /// Nanoseconds per tick, measured over the clock's lifetime so far.
+    fn ns_per_tick(&self) -> f64 {
+        if !cfg!(all(target_arch = "x86_64", not(miri))) {
+            return 1.0;
+        }
+        let ticks = Self::read_ticks().wrapping_sub(self.start_ticks);
+        let nanos = self.start.elapsed_nanos();
+        if ticks == 0 || nanos == 0 {
+            1.0
+        } else {
+            nanos as f64 / ticks as f64
+        }
+    }
+}
+
+pub(crate) struct Profiler {
+    clock: ProfileClock,
+    running: bool,
+    /// Bumped by `start` and `reset`; frames of an earlier session are
+    /// dropped without being charged.
+    session: u32,
+    /// Stats slot of each proto, by [`LuaProto::profile_id`].
+    slots: HashMap<u64, usize, RandomState>,
+    /// The last function entered and its slot; recursion and loops keep
+    /// hitting the same one.
+    last: (u64, usize),
+    stats: Vec<FunctionStats>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            clock: ProfileClock::new(),
+            running: false,
+            session: 0,
+            slots: HashMap::default(),
+            last: (0, 0),
+            stats: Vec::new(),
+        }
+    }
+}
+
+impl Profiler {
+    pub(crate) fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub(crate) fn start(&mut self) {
+        if self.running {
+            return;
+        }
+        self.running = true;
+        self.session = self.session.wrapping_add(1);
+        for stats in &mut self.stats {
+            stats.active = 0;
+        }
+    }
+
+    /// Stop recording. Threads charge their open frames with
+    /// [`close_frames`](Self::close_frames) first.
+    pub(crate) fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Charge every frame still open on `frames` up to now.
+    pub(crate) fn close_frames(&mut self, frames: &mut Vec<ProfileFrame>) {
+        let now = self.clock.now();
+        self.unwind(frames, 0, now);
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.session = self.session.wrapping_add(1);
+        self.slots.clear();
+        self.last = (0, 0);
+        self.stats.clear();
+    }
+
+    /// A Lua function was entered; its frame is at `depth` (1-based).
+    #[inline]
+    pub(crate) fn enter(&mut self, frames: &mut Vec<ProfileFrame>, chunk: &LuaProto, depth: usize) {
+        let now = self.clock.now();
+        self.unwind(frames, depth, now);
+        let key = chunk.profile_id;
+        let slot = if self.last.0 == key {
+            self.last.1
+        } else {
+            self.slot_for(chunk)
+        };
+        self.last = (key, slot);
+        self.stats[slot].active += 1;
+        frames.push(ProfileFrame {
+            slot,
+            depth,
+            session: self.session,
+            start: now,
+            child: 0,
+        });
+    }
+
+    /// Stats slot of `chunk`, allocated on its first call.
+    #[inline(never)]
+    fn slot_for(&mut self, chunk: &LuaProto) -> usize {
+        let key = chunk.profile_id;
+        match self.slots.get(&key) {
+            Some(&slot) => slot,
+            None => {
+                let slot = self.stats.len();
+                self.stats.push(FunctionStats {
+                    profile: FunctionProfile {
+                        source: format_source(chunk.source_name.as_deref().unwrap_or("=?")),
+                        line_defined: chunk.linedefined,
+                        calls: 0,
+                        inclusive_ns: 0,
+                        exclusive_ns: 0,
+                    },
+                    active: 0,
+                });
+                self.slots.insert(key, slot);
+                slot
+            }
+        }
+    }
+
+    /// The Lua frame at `depth` is returning.
+    #[inline]
+    pub(crate) fn leave(&mut self, frames: &mut Vec<ProfileFrame>, depth: usize) {
+        let now = self.clock.now();
+        self.unwind(frames, depth, now);
+    }
+
+    /// Pop and charge the frames at `depth` or deeper.
+    #[inline]
+    fn unwind(&mut self, frames: &mut Vec<ProfileFrame>, depth: usize, now: u64) {
+        while let Some(frame) = frames.pop_if(|frame| frame.depth >= depth) {
+            if frame.session != self.session {
+                continue;
+            }
+            let elapsed = now.saturating_sub(frame.start);
+            let stats = &mut self.stats[frame.slot];
+            stats.active -= 1;
+            stats.profile.calls += 1;
+            stats.profile.exclusive_ns += elapsed.saturating_sub(frame.child);
+            if stats.active == 0 {
+                stats.profile.inclusive_ns += elapsed;
+            }
+            if let Some(parent) = frames.last_mut() {
+                parent.child += elapsed;
+            }
+        }
+    }
+
+    /// Every function called so far, most exclusive time first.
+    pub(crate) fn report(&self) -> Vec<FunctionProfile> {
+        let scale = self.clock.ns_per_tick();
+        let mut report: Vec<FunctionProfile> = self
+            .stats
+            .iter()
+            .filter(|stats| stats.profile.calls > 0)
+            .map(|stats| FunctionProfile {
+                inclusive_ns: (stats.profile.inclusive_ns as f64 * scale) as u64,
+                exclusive_ns: (stats.profile.exclusive_ns as f64 * scale) as u64,
+                ..stats.profile.clone()
+            })
+            .collect();
+        report.sort_by(|a, b| {
+            b.exclusive_ns
+                .cmp(&a.exclusive_ns)
+                .then(b.calls.cmp(&a.calls))
+        });
+        report
+    }
+}
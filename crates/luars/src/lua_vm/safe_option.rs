@@ -1,29 +1,260 @@
-use super::lua_limits::{LUAI_MAXCSTACK, LUAI_MAXSTACK, MAX_CALL_DEPTH};
+use super::lua_limits::{BASIC_STACK_SIZE, LUAI_MAXCSTACK, LUAI_MAXSTACK, MAX_CALL_DEPTH};
 
+/// Per-state limits and loading policy.
+///
+/// Build one with [`SafeOption::builder`], which checks each limit against
+/// the range the VM supports, or start from `SafeOption::default()` and
+/// override fields directly. New options may be added in any release, so
+/// the struct cannot be built with a literal outside this crate.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct SafeOption {
+    /// Maximum size of a thread's value stack, in slots.
+    /// Matches C Lua 5.5's `LUAI_MAXSTACK`.  Default: 1,000,000.
     pub max_stack_size: usize,
+    /// Maximum size of each coroutine's value stack, in slots, separate from
+    /// the main thread's so one runaway coroutine cannot use up the whole
+    /// budget. Overflowing it raises "coroutine stack overflow" inside the
+    /// coroutine.  Default: 1,000,000.
+    pub max_coroutine_stack: usize,
     /// Maximum Lua call-stack depth (number of CallInfo frames).
     /// A pure-Lua recursion guard.  Default: `MAX_CALL_DEPTH` (1024).
     pub max_call_depth: usize,
     /// Maximum C-stack depth (Rust recursion depth, tracked by `n_ccalls`).
     /// Mirrors C Lua 5.5's `LUAI_MAXCSTACK`.  Default: 200.
     pub max_c_stack_depth: usize,
-    /// Maximum memory limit in bytes
+    /// Maximum memory the GC may have allocated, in bytes.
+    /// Default: `isize::MAX` (unlimited).
     pub max_memory_limit: isize,
     /// Whether to allow loading bytecode (default: true).  If false, attempts to load
     /// bytecode will be rejected.
     pub allow_load_bytecode: bool,
+    /// Compile every chunk as if it began with a `global` declaration, so
+    /// names not declared with `global` (or `global *`) are compile errors
+    /// (default: false, the usual global-by-default behavior).
+    pub strict_globals: bool,
+    /// Compiler optimizations applied to every chunk loaded by this state.
+    pub compile_options: CompileOptions,
+    /// Initial `package.path` (default: `None`, meaning
+    /// [`LUA_PATH_DEFAULT`](crate::LUA_PATH_DEFAULT)). Hosts that honor
+    /// `LUA_PATH` put the resolved value here.
+    pub package_path: Option<String>,
+    /// Initial `package.cpath` (default: `None`, meaning
+    /// [`LUA_CPATH_DEFAULT`](crate::LUA_CPATH_DEFAULT)).
+    pub package_cpath: Option<String>,
 }
 
 impl Default for SafeOption {
     fn default() -> Self {
         Self {
             max_stack_size: LUAI_MAXSTACK,
+            max_coroutine_stack: LUAI_MAXSTACK,
             max_call_depth: MAX_CALL_DEPTH,
             max_c_stack_depth: LUAI_MAXCSTACK,
             max_memory_limit: isize::MAX,
             allow_load_bytecode: true,
+            strict_globals: false,
+            compile_options: CompileOptions::default(),
+            package_path: None,
+            package_cpath: None,
+        }
+    }
+}
+
+impl SafeOption {
+    /// Start from the defaults and set only the limits that matter:
+    ///
+    /// ```ignore
+    /// let option = SafeOption::builder()
+    ///     .max_memory_mb(256)
+    ///     .max_call_depth(200)
+    ///     .build()?;
+    /// ```
+    pub fn builder() -> SafeOptionBuilder {
+        SafeOptionBuilder {
+            option: SafeOption::default(),
+        }
+    }
+}
+
+/// Builder for [`SafeOption`]; see [`SafeOption::builder`].
+///
+/// Setters take values in the units named by the method. Ranges are checked
+/// once, in [`build`](SafeOptionBuilder::build).
+#[derive(Debug, Clone)]
+pub struct SafeOptionBuilder {
+    option: SafeOption,
+}
+
+impl SafeOptionBuilder {
+    /// Smallest accepted `max_stack_size`: a fresh thread's initial stack.
+    pub const MIN_STACK_SIZE: usize = BASIC_STACK_SIZE;
+    /// Largest accepted `max_stack_size`; stack indices are `i32` in the API.
+    pub const MAX_STACK_SIZE: usize = i32::MAX as usize;
+    /// Largest accepted `max_c_stack_depth`. Each level is a Rust stack
+    /// frame, so deeper limits would overflow the native stack first.
+    pub const MAX_C_STACK_DEPTH: usize = 10_000;
+
+    /// Value stack size in slots, `MIN_STACK_SIZE..=MAX_STACK_SIZE`.
+    pub fn max_stack_size(mut self, slots: usize) -> Self {
+        self.option.max_stack_size = slots;
+        self
+    }
+
+    /// Value stack size of each coroutine in slots,
+    /// `MIN_STACK_SIZE..=MAX_STACK_SIZE`.
+    pub fn max_coroutine_stack(mut self, slots: usize) -> Self {
+        self.option.max_coroutine_stack = slots;
+        self
+    }
+
+    /// Lua call depth in frames, at least 1.
+    pub fn max_call_depth(mut self, frames: usize) -> Self {
+        self.option.max_call_depth = frames;
+        self
+    }
+
+    /// Rust recursion depth (metamethods, C functions, parser nesting),
+    /// `1..=MAX_C_STACK_DEPTH`.
+    pub fn max_c_stack_depth(mut self, depth: usize) -> Self {
+        self.option.max_c_stack_depth = depth;
+        self
+    }
+
+    /// Memory limit in bytes, at least 1.
+    pub fn max_memory_bytes(mut self, bytes: usize) -> Self {
+        self.option.max_memory_limit = bytes.min(isize::MAX as usize) as isize;
+        self
+    }
+
+    /// Memory limit in mebibytes, at least 1.
+    pub fn max_memory_mb(self, mb: usize) -> Self {
+        self.max_memory_bytes(mb.saturating_mul(1024 * 1024))
+    }
+
+    pub fn allow_load_bytecode(mut self, allow: bool) -> Self {
+        self.option.allow_load_bytecode = allow;
+        self
+    }
+
+    pub fn strict_globals(mut self, strict: bool) -> Self {
+        self.option.strict_globals = strict;
+        self
+    }
+
+    pub fn compile_options(mut self, options: CompileOptions) -> Self {
+        self.option.compile_options = options;
+        self
+    }
+
+    /// Initial `package.path` used when the package library opens.
+    pub fn package_path(mut self, path: impl Into<String>) -> Self {
+        self.option.package_path = Some(path.into());
+        self
+    }
+
+    /// Initial `package.cpath` used when the package library opens.
+    pub fn package_cpath(mut self, cpath: impl Into<String>) -> Self {
+        self.option.package_cpath = Some(cpath.into());
+        self
+    }
+
+    /// Check every limit and return the finished option, or the first
+    /// limit that is out of range.
+    pub fn build(self) -> Result<SafeOption, SafeOptionError> {
+        let o = &self.option;
+        check(
+            "max_stack_size",
+            o.max_stack_size,
+            Self::MIN_STACK_SIZE,
+            Self::MAX_STACK_SIZE,
+        )?;
+        check(
+            "max_coroutine_stack",
+            o.max_coroutine_stack,
+            Self::MIN_STACK_SIZE,
+            Self::MAX_STACK_SIZE,
+        )?;
+        check("max_call_depth", o.max_call_depth, 1, usize::MAX)?;
+        check(
+            "max_c_stack_depth",
+            o.max_c_stack_depth,
+            1,
+            Self::MAX_C_STACK_DEPTH,
+        )?;
+        check(
+            "max_memory_limit",
+            o.max_memory_limit as usize,
+            1,
+            isize::MAX as usize,
+        )?;
+        Ok(self.option)
+    }
+}
+
+fn check(field: &'static str, value: usize, min: usize, max: usize) -> Result<(), SafeOptionError> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(SafeOptionError {
+            field,
+            value,
+            min,
+            max,
+        })
+    }
+}
+
+/// A [`SafeOptionBuilder`] limit outside the range the VM supports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafeOptionError {
+    /// The `SafeOption` field that was rejected.
+    pub field: &'static str,
+    pub value: usize,
+    pub min: usize,
+    pub max: usize,
+}
+
+impl std::fmt::Display for SafeOptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} must be between {} and {} (got {})",
+            self.field, self.min, self.max, self.value
+        )
+    }
+}
+
+impl std::error::Error for SafeOptionError {}
+
+/// Individually toggleable compiler optimizations. All are on by default and
+/// none changes what a program computes, so turning one off is a way to
+/// check whether a misbehaving chunk is miscompiled by it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompileOptions {
+    /// Evaluate arithmetic and bitwise operations on numeric constants at
+    /// compile time (`2^10`, `-1`, `~0`), as lcode.c's constfolding does.
+    pub constant_folding: bool,
+    /// Merge concatenations of string literals (`"a" .. "b"`) into a single
+    /// constant.
+    pub concat_folding: bool,
+}
+
+impl CompileOptions {
+    /// Every optimization turned off.
+    pub fn unoptimized() -> Self {
+        Self {
+            constant_folding: false,
+            concat_folding: false,
+        }
+    }
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            constant_folding: true,
+            concat_folding: true,
         }
     }
 }
@@ -16,6 +16,10 @@ pub struct SandboxConfig {
     pub io: bool,
     pub package: bool,
     pub debug: bool,
+    #[cfg(feature = "json")]
+    pub json: bool,
+    #[cfg(feature = "profiler")]
+    pub profiler: bool,
     pub allow_require: bool,
     pub allow_load: bool,
     pub allow_loadfile: bool,
@@ -40,6 +44,10 @@ impl Default for SandboxConfig {
             io: false,
             package: false,
             debug: false,
+            #[cfg(feature = "json")]
+            json: true,
+            #[cfg(feature = "profiler")]
+            profiler: false,
             allow_require: false,
             allow_load: false,
             allow_loadfile: false,
@@ -71,6 +79,10 @@ impl SandboxConfig {
             Stdlib::Io => self.io = true,
             Stdlib::Package => self.package = true,
             Stdlib::Debug => self.debug = true,
+            #[cfg(feature = "json")]
+            Stdlib::Json => self.json = true,
+            #[cfg(feature = "profiler")]
+            Stdlib::Profiler => self.profiler = true,
             Stdlib::All => {
                 self.basic = true;
                 self.math = true;
@@ -82,6 +94,14 @@ impl SandboxConfig {
                 self.io = true;
                 self.package = true;
                 self.debug = true;
+                #[cfg(feature = "json")]
+                {
+                    self.json = true;
+                }
+                #[cfg(feature = "profiler")]
+                {
+                    self.profiler = true;
+                }
             }
         }
         self
@@ -196,4 +216,8 @@ pub const SANDBOX_LIB_GLOBALS: &[(Stdlib, &str)] = &[
     (Stdlib::Io, "io"),
     (Stdlib::Package, "package"),
     (Stdlib::Debug, "debug"),
+    #[cfg(feature = "json")]
+    (Stdlib::Json, "json"),
+    #[cfg(feature = "profiler")]
+    (Stdlib::Profiler, "profiler"),
 ];
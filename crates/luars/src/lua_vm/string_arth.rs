@@ -6,7 +6,11 @@
 
 use crate::{
     LuaResult, LuaState, LuaValue,
-    lua_vm::{TmKind, execute, execute::arith::luai_numpow},
+    lua_vm::{
+        TmKind, execute,
+        execute::arith::{lua_fmod, lua_idiv, lua_imod, luai_numpow},
+        execute::helper::{error_div_by_zero, error_mod_by_zero},
+    },
     stdlib::basic::parse_number::parse_lua_number,
 };
 
@@ -49,11 +53,19 @@ pub fn string_arith_bin(
     let n1 = string_arith_tonum(&v1);
     let n2 = string_arith_tonum(&v2);
 
-    if let (Some(a), Some(b)) = (n1, n2)
-        && let Some(result) = op(a, b)
-    {
-        l.push_value(result)?;
-        return Ok(1);
+    if let (Some(a), Some(b)) = (n1, n2) {
+        // Integer division by zero raises, as luaV_idiv / luaV_mod do
+        if a.is_integer() && b.as_integer_strict() == Some(0) {
+            match tm_kind {
+                TmKind::IDiv => return Err(error_div_by_zero(l)),
+                TmKind::Mod => return Err(error_mod_by_zero(l)),
+                _ => {}
+            }
+        }
+        if let Some(result) = op(a, b) {
+            l.push_value(result)?;
+            return Ok(1);
+        }
     }
 
     // Conversion failed — implement trymt() from C Lua:
@@ -81,7 +93,7 @@ pub fn string_arith_bin(
 }
 
 pub fn arith_add(a: LuaValue, b: LuaValue) -> Option<LuaValue> {
-    match (a.as_integer(), b.as_integer()) {
+    match (a.as_integer_strict(), b.as_integer_strict()) {
         (Some(x), Some(y)) => Some(LuaValue::integer(x.wrapping_add(y))),
         _ => {
             let fa = a.as_number().or_else(|| a.as_integer().map(|i| i as f64))?;
@@ -92,7 +104,7 @@ pub fn arith_add(a: LuaValue, b: LuaValue) -> Option<LuaValue> {
 }
 
 pub fn arith_sub(a: LuaValue, b: LuaValue) -> Option<LuaValue> {
-    match (a.as_integer(), b.as_integer()) {
+    match (a.as_integer_strict(), b.as_integer_strict()) {
         (Some(x), Some(y)) => Some(LuaValue::integer(x.wrapping_sub(y))),
         _ => {
             let fa = a.as_number().or_else(|| a.as_integer().map(|i| i as f64))?;
@@ -103,7 +115,7 @@ pub fn arith_sub(a: LuaValue, b: LuaValue) -> Option<LuaValue> {
 }
 
 pub fn arith_mul(a: LuaValue, b: LuaValue) -> Option<LuaValue> {
-    match (a.as_integer(), b.as_integer()) {
+    match (a.as_integer_strict(), b.as_integer_strict()) {
         (Some(x), Some(y)) => Some(LuaValue::integer(x.wrapping_mul(y))),
         _ => {
             let fa = a.as_number().or_else(|| a.as_integer().map(|i| i as f64))?;
@@ -114,29 +126,14 @@ pub fn arith_mul(a: LuaValue, b: LuaValue) -> Option<LuaValue> {
 }
 
 pub fn arith_mod(a: LuaValue, b: LuaValue) -> Option<LuaValue> {
-    match (a.as_integer(), b.as_integer()) {
-        (Some(x), Some(y)) => {
-            if y == 0 {
-                return Some(LuaValue::float(f64::NAN));
-            }
-            // Lua mod: a - floor(a/b)*b
-            let r = x.wrapping_rem(y);
-            if r != 0 && (r ^ y) < 0 {
-                Some(LuaValue::integer(r.wrapping_add(y)))
-            } else {
-                Some(LuaValue::integer(r))
-            }
-        }
+    match (a.as_integer_strict(), b.as_integer_strict()) {
+        // string_arith_bin raises the zero-divisor error first
+        (Some(x), Some(y)) if y != 0 => Some(LuaValue::integer(lua_imod(x, y))),
+        (Some(_), Some(_)) => None,
         _ => {
             let fa = a.as_number().or_else(|| a.as_integer().map(|i| i as f64))?;
             let fb = b.as_number().or_else(|| b.as_integer().map(|i| i as f64))?;
-            let r = fa % fb;
-            // Lua float mod semantics
-            if r != 0.0 && r.is_sign_negative() != fb.is_sign_negative() {
-                Some(LuaValue::float(r + fb))
-            } else {
-                Some(LuaValue::float(r))
-            }
+            Some(LuaValue::float(lua_fmod(fa, fb)))
         }
     }
 }
@@ -155,19 +152,9 @@ pub fn arith_div(a: LuaValue, b: LuaValue) -> Option<LuaValue> {
 }
 
 pub fn arith_idiv(a: LuaValue, b: LuaValue) -> Option<LuaValue> {
-    match (a.as_integer(), b.as_integer()) {
-        (Some(x), Some(y)) => {
-            if y == 0 {
-                return Some(LuaValue::float(f64::NAN));
-            }
-            // Lua floor division
-            let d = x.wrapping_div(y);
-            if (x ^ y) < 0 && d * y != x {
-                Some(LuaValue::integer(d - 1))
-            } else {
-                Some(LuaValue::integer(d))
-            }
-        }
+    match (a.as_integer_strict(), b.as_integer_strict()) {
+        (Some(x), Some(y)) if y != 0 => Some(LuaValue::integer(lua_idiv(x, y))),
+        (Some(_), Some(_)) => None,
         _ => {
             let fa = a.as_number().or_else(|| a.as_integer().map(|i| i as f64))?;
             let fb = b.as_number().or_else(|| b.as_integer().map(|i| i as f64))?;
@@ -210,7 +197,7 @@ pub fn string_arith_unm(l: &mut LuaState) -> LuaResult<usize> {
         .ok_or_else(|| l.error("attempt to perform arithmetic on a nil value".to_string()))?;
 
     if let Some(n) = string_arith_tonum(&v1) {
-        if let Some(i) = n.as_integer() {
+        if let Some(i) = n.as_integer_strict() {
             l.push_value(LuaValue::integer(i.wrapping_neg()))?;
         } else if let Some(f) = n.as_number() {
             l.push_value(LuaValue::float(-f))?;
@@ -0,0 +1,80 @@
+// Per-type hooks for registered userdata, attached at register time and
+// consulted by the VM around trait-based field access.
+
+use std::rc::Rc;
+
+use crate::lua_value::LuaValue;
+use crate::lua_vm::LuaState;
+
+/// Outcome of an [`TypeOptions::on_set`] observer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetAction {
+    /// Go ahead with the trait's `set_field`.
+    Allow,
+    /// Drop the write silently; the field keeps its old value.
+    Skip,
+}
+
+/// Called before a Lua write to a field handled by `set_field`, with the
+/// userdata, the field name, the current value and the incoming one.
+/// `Err(msg)` is raised as a Lua error at the assignment.
+pub type SetObserver =
+    dyn Fn(&mut LuaState, &LuaValue, &str, LuaValue, LuaValue) -> Result<SetAction, String>;
+
+/// Called after `get_field` produced a value for Lua; returns the value Lua
+/// actually sees. `Err(msg)` is raised as a Lua error at the read.
+pub type GetObserver = dyn Fn(&mut LuaState, &LuaValue, &str, LuaValue) -> Result<LuaValue, String>;
+
+/// Options for `register_type_of_with`.
+#[derive(Default)]
+pub struct TypeOptions {
+    pub on_set: Option<Box<SetObserver>>,
+    pub on_get: Option<Box<GetObserver>>,
+}
+
+impl TypeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_set(
+        mut self,
+        observer: impl Fn(
+            &mut LuaState,
+            &LuaValue,
+            &str,
+            LuaValue,
+            LuaValue,
+        ) -> Result<SetAction, String>
+        + 'static,
+    ) -> Self {
+        self.on_set = Some(Box::new(observer));
+        self
+    }
+
+    pub fn on_get(
+        mut self,
+        observer: impl Fn(&mut LuaState, &LuaValue, &str, LuaValue) -> Result<LuaValue, String>
+        + 'static,
+    ) -> Self {
+        self.on_get = Some(Box::new(observer));
+        self
+    }
+}
+
+/// Observers as stored on the type's metadata. Reference counted so the VM
+/// can hold one across the callback while the callback mutates the VM.
+#[derive(Default, Clone)]
+pub(crate) struct TypeObservers {
+    pub(crate) on_set: Option<Rc<SetObserver>>,
+    pub(crate) on_get: Option<Rc<GetObserver>>,
+}
+
+impl From<TypeOptions> for TypeObservers {
+    fn from(options: TypeOptions) -> Self {
+        TypeObservers {
+            on_set: options.on_set.map(Rc::from),
+            on_get: options.on_get.map(Rc::from),
+        }
+    }
+}
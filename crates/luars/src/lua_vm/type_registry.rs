@@ -0,0 +1,215 @@
+use std::any::TypeId;
+
+use crate::LuaRegistrable;
+use crate::lua_vm::{GlobalState, LuaResult, TypeObservers, TypeOptions};
+
+/// Userdata types to install into a VM in one go, for hosts whose types
+/// come from several crates.
+///
+/// Each plugin adds its types under its own source name; the host merges
+/// the registries and hands the result to
+/// [`GlobalState::register_types`] or [`Lua::new_with_types`](crate::Lua::new_with_types)
+/// without naming a single type itself.
+///
+/// ```ignore
+/// // in the geometry plugin
+/// pub fn types() -> Result<TypeRegistry, TypeRegistryError> {
+///     let mut types = TypeRegistry::new();
+///     types.add::<Point>("geometry", "Point")?.add::<Rect>("geometry", "Rect")?;
+///     Ok(types)
+/// }
+///
+/// // in the host
+/// let mut types = TypeRegistry::new();
+/// types.merge(&geometry::types()?)?;
+/// types.merge(&physics::types()?)?;
+/// let lua = Lua::new_with_types(SafeOption::default(), &types)?;
+/// ```
+///
+/// Holds the field observers of [`add_with`](Self::add_with), so it is not
+/// `Send`: build it on the thread that creates the VMs.
+#[derive(Clone, Default)]
+pub struct TypeRegistry {
+    entries: Vec<TypeEntry>,
+}
+
+/// One type of a [`TypeRegistry`]: what a `register_type_of` call would do.
+#[derive(Clone)]
+pub struct TypeEntry {
+    name: String,
+    source: String,
+    rust_type: &'static str,
+    type_id: TypeId,
+    install: fn(&mut GlobalState, &str) -> LuaResult<()>,
+    observers: TypeObservers,
+}
+
+impl TypeEntry {
+    /// Name of the global the type table is installed as.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The plugin or crate that added the type.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Rust name of the type, as given by `std::any::type_name`.
+    pub fn rust_type(&self) -> &'static str {
+        self.rust_type
+    }
+
+    fn claim(&self) -> TypeClaim {
+        TypeClaim {
+            name: self.name.clone(),
+            source: self.source.clone(),
+            rust_type: self.rust_type,
+        }
+    }
+
+    /// Whether the type has field observers from `add_with`.
+    pub fn has_observers(&self) -> bool {
+        self.observers.on_set.is_some() || self.observers.on_get.is_some()
+    }
+}
+
+impl std::fmt::Debug for TypeEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypeEntry")
+            .field("name", &self.name)
+            .field("source", &self.source)
+            .field("rust_type", &self.rust_type)
+            .field("has_observers", &self.has_observers())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for TypeRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(&self.entries).finish()
+    }
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `T` as global `name`, on behalf of `source`. Fails if the name
+    /// or the Rust type is already taken.
+    pub fn add<T: LuaRegistrable>(
+        &mut self,
+        source: &str,
+        name: &str,
+    ) -> Result<&mut Self, TypeRegistryError> {
+        self.add_with::<T>(source, name, TypeOptions::default())
+    }
+
+    /// Like [`add`](Self::add), with the field observers of
+    /// [`register_type_of_with`](GlobalState::register_type_of_with).
+    pub fn add_with<T: LuaRegistrable>(
+        &mut self,
+        source: &str,
+        name: &str,
+        options: TypeOptions,
+    ) -> Result<&mut Self, TypeRegistryError> {
+        self.push(TypeEntry {
+            name: name.to_string(),
+            source: source.to_string(),
+            rust_type: std::any::type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            install: GlobalState::register_type_of::<T>,
+            observers: options.into(),
+        })?;
+        Ok(self)
+    }
+
+    /// Add every type of `other`. Stops at the first conflict; the types
+    /// before it stay added.
+    pub fn merge(&mut self, other: &TypeRegistry) -> Result<(), TypeRegistryError> {
+        for entry in &other.entries {
+            self.push(entry.clone())?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, entry: TypeEntry) -> Result<(), TypeRegistryError> {
+        if let Some(existing) = self
+            .entries
+            .iter()
+            .find(|e| e.name == entry.name || e.type_id == entry.type_id)
+        {
+            return Err(TypeRegistryError {
+                existing: Box::new(existing.claim()),
+                added: Box::new(entry.claim()),
+            });
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// The types, in the order they were added.
+    pub fn entries(&self) -> impl Iterator<Item = &TypeEntry> {
+        self.entries.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TypeEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn install(&self, vm: &mut GlobalState) -> LuaResult<()> {
+        for entry in &self.entries {
+            (entry.install)(vm, &entry.name)?;
+            vm.set_userdata_observers(entry.type_id, entry.observers.clone());
+        }
+        Ok(())
+    }
+}
+
+/// One side of a [`TypeRegistryError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeClaim {
+    pub name: String,
+    pub source: String,
+    pub rust_type: &'static str,
+}
+
+/// Two sources of a [`TypeRegistry`] claiming the same global name or the
+/// same Rust type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeRegistryError {
+    /// The type already in the registry
+    pub existing: Box<TypeClaim>,
+    /// The type that was being added
+    pub added: Box<TypeClaim>,
+}
+
+impl std::fmt::Display for TypeRegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (old, new) = (&self.existing, &self.added);
+        if old.name == new.name {
+            write!(
+                f,
+                "userdata type name '{}' registered twice: as {} by '{}' and as {} by '{}'",
+                old.name, old.rust_type, old.source, new.rust_type, new.source
+            )
+        } else {
+            write!(
+                f,
+                "userdata type {} registered twice: as '{}' by '{}' and as '{}' by '{}'",
+                old.rust_type, old.name, old.source, new.name, new.source
+            )
+        }
+    }
+}
+
+impl std::error::Error for TypeRegistryError {}
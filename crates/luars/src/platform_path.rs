@@ -0,0 +1,96 @@
+//! Filenames coming from Lua strings.
+//!
+//! Lua strings are byte strings and scripts may pass any bytes as a filename.
+//! On Unix those bytes are the path, exactly as `fopen` would see them.
+//! Windows paths are UTF-16, so there a filename must be valid UTF-8 (which
+//! any literal in a UTF-8 source file is); other bytes are rejected with a
+//! regular I/O error rather than being mangled into a different name.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Convert the bytes of a Lua string into a path for `std::fs`.
+#[cfg(unix)]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> io::Result<PathBuf> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    Ok(PathBuf::from(OsStr::from_bytes(bytes)))
+}
+
+/// Convert the bytes of a Lua string into a path for `std::fs`.
+#[cfg(not(unix))]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> io::Result<PathBuf> {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Ok(PathBuf::from(s)),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "filename is not valid UTF-8",
+        )),
+    }
+}
+
+/// The bytes of a path, for handing a filename back to Lua.
+#[cfg(unix)]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// The bytes of a path, for handing a filename back to Lua.
+#[cfg(not(unix))]
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// The C `errno` value for an I/O error, as the third result of
+/// `luaL_fileresult`.
+///
+/// Windows reports system error codes, which the C runtime translates
+/// (`_dosmaperr`) before a Lua script ever sees them; the common ones are
+/// mapped the same way here so scripts can compare against the usual values.
+pub(crate) fn errno(e: &io::Error) -> i64 {
+    let Some(code) = e.raw_os_error() else {
+        return match e.kind() {
+            io::ErrorKind::InvalidInput => 22, // EINVAL
+            _ => 0,
+        };
+    };
+    if cfg!(windows) {
+        match code {
+            2 | 3 | 15 | 18 | 123 | 206 => 2, // ENOENT
+            4 => 24,                          // EMFILE
+            5 | 16 | 19 | 32 | 33 => 13,      // EACCES
+            6 => 9,                           // EBADF
+            8 => 12,                          // ENOMEM
+            17 => 18,                         // EXDEV
+            80 | 183 => 17,                   // EEXIST
+            112 => 28,                        // ENOSPC
+            145 => 41,                        // ENOTEMPTY
+            _ => 22,                          // EINVAL
+        }
+    } else {
+        code as i64
+    }
+}
+
+/// Create a fresh empty file in the system temporary directory and return
+/// its name, like `lua_tmpnam` does with `mkstemp`: creating the file
+/// claims the name, so two calls never hand out the same one.
+pub(crate) fn create_temp_file() -> io::Result<PathBuf> {
+    let dir = std::env::temp_dir();
+    let seed = crate::platform_time::unix_nanos() ^ ((std::process::id() as u64) << 32);
+    for attempt in 0..100u64 {
+        let id = seed.wrapping_add(attempt.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let path = dir.join(format!("lua_{:012x}", id & 0xffff_ffff_ffff));
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::from(io::ErrorKind::AlreadyExists))
+}
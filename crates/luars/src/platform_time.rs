@@ -38,6 +38,20 @@ impl PlatformInstant {
             (js_sys::Date::now() - self.start_ms) / 1000.0
         }
     }
+
+    #[cfg(feature = "profiler")]
+    #[inline]
+    pub(crate) fn elapsed_nanos(&self) -> u64 {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.inner.elapsed().as_nanos() as u64
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            ((js_sys::Date::now() - self.start_ms) * 1_000_000.0) as u64
+        }
+    }
 }
 
 #[inline]
@@ -0,0 +1,124 @@
+// Argument checks shared by the standard libraries.
+// Port of the luaL_check*/luaL_opt* helpers from lauxlib.c: strings and
+// numbers are interchangeable wherever Lua would coerce one to the other.
+
+use crate::lua_value::{lua_float_to_int, lua_float_to_string};
+use crate::stdlib::basic::parse_number::parse_lua_number;
+use crate::stdlib::debug::{arg_typeerror, arg_typeerror_novalue, argerror};
+use crate::{LuaResult, LuaState, LuaValue};
+
+/// Port of luaO_tostring: the string form of a number, as `tostring` and
+/// the `..` operator produce it. Returns `None` for non-numbers.
+pub fn number_to_string(v: &LuaValue) -> Option<String> {
+    if let Some(i) = v.as_integer_strict() {
+        Some(itoa::Buffer::new().format(i).to_string())
+    } else {
+        v.as_float().map(lua_float_to_string)
+    }
+}
+
+/// Port of luaL_checklstring: argument `arg` as a string value. A number is
+/// converted and written back into its argument slot, as lua_tolstring does,
+/// so the new string stays anchored for the rest of the call.
+pub fn check_string(l: &mut LuaState, arg: usize) -> LuaResult<LuaValue> {
+    let Some(v) = l.get_arg(arg) else {
+        return Err(arg_typeerror_novalue(l, arg, "string"));
+    };
+    if v.is_string() {
+        return Ok(v);
+    }
+    let Some(s) = number_to_string(&v) else {
+        return Err(arg_typeerror(l, arg, "string", &v));
+    };
+    let sv = l.create_string(&s)?;
+    l.set_arg(arg, sv);
+    Ok(sv)
+}
+
+/// Port of luaL_optlstring: `None` when the argument is absent or nil.
+pub fn opt_string(l: &mut LuaState, arg: usize) -> LuaResult<Option<LuaValue>> {
+    match l.get_arg(arg) {
+        Some(v) if !v.is_nil() => check_string(l, arg).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Port of luaL_checknumber: numbers and numeric strings.
+pub fn check_number(l: &mut LuaState, arg: usize) -> LuaResult<f64> {
+    let Some(v) = l.get_arg(arg) else {
+        return Err(arg_typeerror_novalue(l, arg, "number"));
+    };
+    let n = if v.is_string() {
+        parse_lua_number(v.as_str().unwrap_or(""))
+    } else {
+        v
+    };
+    match n.as_number() {
+        Some(f) => Ok(f),
+        None => Err(arg_typeerror(l, arg, "number", &v)),
+    }
+}
+
+/// Port of lua_tointegerx under the default F2Ieq mode: integers, floats
+/// with an exact integer value and strings converting to either.
+/// `Err(true)` means a number without an integer representation.
+fn to_integer(v: &LuaValue) -> Result<i64, bool> {
+    let n = if v.is_string() {
+        parse_lua_number(v.as_str().unwrap_or(""))
+    } else {
+        *v
+    };
+    if let Some(i) = n.as_integer_strict() {
+        return Ok(i);
+    }
+    match n.as_float() {
+        Some(f) => lua_float_to_int(f).ok_or(true),
+        None => Err(false),
+    }
+}
+
+/// Port of luaL_checkinteger.
+pub fn check_integer(l: &mut LuaState, arg: usize) -> LuaResult<i64> {
+    let Some(v) = l.get_arg(arg) else {
+        return Err(arg_typeerror_novalue(l, arg, "number"));
+    };
+    match to_integer(&v) {
+        Ok(i) => Ok(i),
+        Err(true) => Err(argerror(l, arg, "number has no integer representation")),
+        Err(false) => Err(arg_typeerror(l, arg, "number", &v)),
+    }
+}
+
+/// Port of luaL_optinteger: `default` when the argument is absent or nil.
+pub fn opt_integer(l: &mut LuaState, arg: usize, default: i64) -> LuaResult<i64> {
+    match l.get_arg(arg) {
+        Some(v) if !v.is_nil() => check_integer(l, arg),
+        _ => Ok(default),
+    }
+}
+
+/// Port of luaL_checktype(L, arg, LUA_TTABLE).
+pub fn check_table(l: &mut LuaState, arg: usize) -> LuaResult<LuaValue> {
+    match l.get_arg(arg) {
+        Some(v) if v.is_table() => Ok(v),
+        Some(v) => Err(arg_typeerror(l, arg, "table", &v)),
+        None => Err(arg_typeerror_novalue(l, arg, "table")),
+    }
+}
+
+/// Port of luaL_checktype(L, arg, LUA_TFUNCTION).
+pub fn check_function(l: &mut LuaState, arg: usize) -> LuaResult<LuaValue> {
+    match l.get_arg(arg) {
+        Some(v) if v.is_function() => Ok(v),
+        Some(v) => Err(arg_typeerror(l, arg, "function", &v)),
+        None => Err(arg_typeerror_novalue(l, arg, "function")),
+    }
+}
+
+/// Port of luaL_checkany: any value, including nil, but not an absent one.
+pub fn check_any(l: &mut LuaState, arg: usize) -> LuaResult<LuaValue> {
+    match l.get_arg(arg) {
+        Some(v) => Ok(v),
+        None => Err(argerror(l, arg, "value expected")),
+    }
+}
@@ -6,14 +6,19 @@ pub mod parse_number;
 mod require;
 
 use crate::GlobalState;
+use crate::compiler::format_source;
 use crate::gc::{GcKind, GcState, MAJORMINOR, MINORMAJOR, MINORMUL, PAUSE, STEPMUL, STEPSIZE};
 use crate::gc::{code_param, decode_param};
 use crate::lib_registry::LibraryModule;
+use crate::lua_value::lua_float_to_string;
 use crate::lua_value::{
     LuaValue, LuaValueKind, UpvalueStore, lua_value_to_udvalue, udvalue_to_lua_value,
 };
-use crate::lua_vm::{LuaError, LuaResult, LuaState, get_metatable};
+use crate::lua_vm::{
+    LoadEvent, LoadMode, LoadOrigin, LuaError, LuaResult, LuaState, TmKind, get_metatable,
+};
 use crate::stdlib::basic::parse_number::parse_lua_number;
+use crate::stdlib::debug::argerror;
 use require::lua_require;
 
 pub fn create_basic_lib() -> LibraryModule {
@@ -69,24 +74,11 @@ fn lua_type(l: &mut LuaState) -> LuaResult<usize> {
     let value = match l.get_arg(1) {
         Some(v) => v,
         None => {
-            return Err(l.error("bad argument #1 to 'type' (value expected)".to_string()));
+            return Err(argerror(l, 1, "value expected"));
         }
     };
 
-    let cs = &l.global_state_mut().const_strings;
-    let result = match value.kind() {
-        LuaValueKind::Nil => cs.str_nil,
-        LuaValueKind::Boolean => cs.str_boolean,
-        LuaValueKind::Integer | LuaValueKind::Float => cs.str_number,
-        LuaValueKind::String => cs.str_string,
-        LuaValueKind::Table => cs.str_table,
-        LuaValueKind::Function
-        | LuaValueKind::CFunction
-        | LuaValueKind::CClosure
-        | LuaValueKind::RClosure => cs.str_function,
-        LuaValueKind::Userdata => cs.str_userdata,
-        LuaValueKind::Thread => cs.str_thread,
-    };
+    let result = l.global_state_mut().const_strings.type_name(&value);
 
     l.push_value(result)?;
     Ok(1)
@@ -98,7 +90,7 @@ fn lua_assert(l: &mut LuaState) -> LuaResult<usize> {
 
     // assert() without arguments: error "value expected"
     if arg_count == 0 {
-        return Err(l.error("bad argument #1 to 'assert' (value expected)".to_string()));
+        return Err(argerror(l, 1, "value expected"));
     }
 
     // Get first argument without consuming it
@@ -155,8 +147,8 @@ fn lua_where(l: &LuaState, level: usize) -> String {
                     let source = chunk
                         .source_name
                         .as_deref()
-                        .map(|name| name.strip_prefix('@').unwrap_or(name))
-                        .unwrap_or("[string]");
+                        .map(format_source)
+                        .unwrap_or_else(|| "[string]".to_string());
                     let line = if ci.pc > 0 && (ci.pc as usize - 1) < chunk.line_info.len() {
                         chunk.line_info[ci.pc as usize - 1] as usize
                     } else if !chunk.line_info.is_empty() {
@@ -220,7 +212,7 @@ fn lua_tonumber(l: &mut LuaState) -> LuaResult<usize> {
     let base = l.get_arg(2).and_then(|v| v.as_integer()).unwrap_or(10);
 
     if has_base && !(2..=36).contains(&base) {
-        return Err(l.error("bad argument #2 to 'tonumber' (base out of range)".to_string()));
+        return Err(argerror(l, 2, "base out of range"));
     }
 
     let result = match value.kind() {
@@ -265,7 +257,7 @@ fn lua_tonumber(l: &mut LuaState) -> LuaResult<usize> {
                         }
                     }
                 } else {
-                    parse_lua_number(s_str)
+                    parse_lua_number(s)
                 }
             } else {
                 LuaValue::nil()
@@ -273,9 +265,7 @@ fn lua_tonumber(l: &mut LuaState) -> LuaResult<usize> {
         }
         _ => {
             if has_base {
-                return Err(l.error(
-                    "bad argument #1 to 'tonumber' (string expected, got number)".to_string(),
-                ));
+                return Err(argerror(l, 1, "string expected, got number"));
             }
             LuaValue::nil()
         }
@@ -285,84 +275,6 @@ fn lua_tonumber(l: &mut LuaState) -> LuaResult<usize> {
     Ok(1)
 }
 
-/// Format a float value matching Lua 5.5's tostringbuffFloat behavior:
-/// First try %.15g (max digits preserving tostring(tonumber(x)) == x),
-/// then %.17g if roundtrip fails, and append ".0" if result looks integer-like.
-pub(crate) fn lua_float_to_string(n: f64) -> String {
-    if n.is_nan() {
-        return "-nan".to_string();
-    }
-    if n.is_infinite() {
-        return if n > 0.0 { "inf" } else { "-inf" }.to_string();
-    }
-
-    // First try: format with roughly 15 significant digits (%.15g equivalent)
-    let s = format_g(n, 15);
-
-    // Check if it roundtrips
-    let mut result = if s.parse::<f64>().ok() == Some(n) {
-        s
-    } else {
-        // Second try: format with 17 significant digits (%.17g equivalent)
-        format_g(n, 17)
-    };
-
-    // If result looks like an integer (no '.', 'e', 'E', 'n', 'i'), add ".0"
-    if !result.contains('.')
-        && !result.contains('e')
-        && !result.contains('E')
-        && !result.contains('n')
-        && !result.contains('i')
-    {
-        result.push_str(".0");
-    }
-
-    result
-}
-
-/// Format a float with %.<prec>g semantics (C-style %g formatting)
-fn format_g(n: f64, prec: usize) -> String {
-    if n == 0.0 {
-        return if n.is_sign_negative() { "-0" } else { "0" }.to_string();
-    }
-
-    let abs_n = n.abs();
-    // Determine the base-10 exponent
-    let exp = abs_n.log10().floor() as i32;
-
-    let formatted = if exp >= -4 && exp < prec as i32 {
-        // Fixed-point notation: precision = prec - (exp + 1) decimal places
-        let decimal_places = (prec as i32 - exp - 1).max(0) as usize;
-        format!("{:.prec$}", n, prec = decimal_places)
-    } else {
-        // Scientific notation: precision = prec - 1 decimal places
-        format!("{:.prec$e}", n, prec = prec - 1)
-    };
-
-    // Strip trailing zeros after decimal point (matching %g behavior)
-    strip_trailing_zeros(&formatted)
-}
-
-/// Strip trailing zeros from a formatted number string (matching C's %g behavior)
-fn strip_trailing_zeros(s: &str) -> String {
-    if let Some(e_pos) = s.find('e').or_else(|| s.find('E')) {
-        // Scientific notation: strip zeros between decimal and 'e'
-        let (mantissa, exponent) = s.split_at(e_pos);
-        let stripped = strip_decimal_zeros(mantissa);
-        format!("{}{}", stripped, exponent)
-    } else if s.contains('.') {
-        strip_decimal_zeros(s)
-    } else {
-        s.to_string()
-    }
-}
-
-/// Strip trailing zeros after decimal point, remove point if no digits follow
-fn strip_decimal_zeros(s: &str) -> String {
-    let trimmed = s.trim_end_matches('0');
-    trimmed.strip_suffix('.').unwrap_or(trimmed).to_string()
-}
-
 /// tostring(v) - Convert to string
 fn lua_tostring(l: &mut LuaState) -> LuaResult<usize> {
     let value = l
@@ -408,8 +320,7 @@ fn lua_tostring(l: &mut LuaState) -> LuaResult<usize> {
     }
 
     // General path: metamethods, functions, tables, etc.
-    let result = l.to_string(&value)?;
-    let result_value = l.create_string(&result)?;
+    let result_value = l.tostring_value(&value)?;
     l.push_value(result_value)?;
     Ok(1)
 }
@@ -420,7 +331,7 @@ fn lua_tostring(l: &mut LuaState) -> LuaResult<usize> {
 fn lua_select(l: &mut LuaState) -> LuaResult<usize> {
     let index_arg = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'select' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
 
     // Total args after index
     let total_args = l.arg_count();
@@ -433,15 +344,15 @@ fn lua_select(l: &mut LuaState) -> LuaResult<usize> {
             l.push_value(result)?;
             return Ok(1);
         }
-        return Err(l.error("bad argument #1 to 'select' (number expected)".to_string()));
+        return Err(argerror(l, 1, "number expected"));
     }
 
     let index = index_arg
         .as_integer()
-        .ok_or_else(|| l.error("bad argument #1 to 'select' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "number expected"))?;
 
     if index == 0 {
-        return Err(l.error("bad argument #1 to 'select' (index out of range)".to_string()));
+        return Err(argerror(l, 1, "index out of range"));
     }
 
     // Calculate start position (1-based to 0-based)
@@ -450,7 +361,7 @@ fn lua_select(l: &mut LuaState) -> LuaResult<usize> {
     } else {
         let abs_idx = (-index) as usize;
         if abs_idx > vararg_count {
-            return Err(l.error("bad argument #1 to 'select' (index out of range)".to_string()));
+            return Err(argerror(l, 1, "index out of range"));
         }
         vararg_count - abs_idx
     };
@@ -490,7 +401,7 @@ fn lua_select(l: &mut LuaState) -> LuaResult<usize> {
 fn lua_ipairs(l: &mut LuaState) -> LuaResult<usize> {
     let table_val = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'ipairs' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
 
     // Return iterator function, table, and 0 (3 values)
     // Note: C Lua 5.5 does NOT require arg1 to be a table (luaL_checkany)
@@ -513,14 +424,19 @@ fn ipairs_next(l: &mut LuaState) -> LuaResult<usize> {
     };
     let next_index = index.wrapping_add(1);
 
-    // Single table dereference — cache it for both has_metatable + raw_geti.
+    // Like luaV_fastgeti: a raw hit never consults __index, and a miss only
+    // falls back to the full lua_geti path when the metatable may have one.
     let value = if let Some(table) = table_val.as_table_mut() {
-        if !table.has_metatable() {
-            // Hot path: no metatable → direct array access
-            table.raw_geti(next_index).unwrap_or(LuaValue::nil())
-        } else {
-            // Cold: metatable present → full __index dispatch
-            l.table_geti(&table_val, next_index)?
+        match table.raw_geti(next_index) {
+            Some(v) if !v.is_nil() => v,
+            _ => {
+                let meta = table.meta_ptr();
+                if meta.is_null() || meta.as_mut_ref().data.no_tm(TmKind::Index.into()) {
+                    LuaValue::nil()
+                } else {
+                    l.table_geti(&table_val, next_index)?
+                }
+            }
         }
     } else {
         l.table_geti(&table_val, next_index)?
@@ -541,9 +457,9 @@ fn ipairs_next(l: &mut LuaState) -> LuaResult<usize> {
 /// Supports __pairs metamethod (C Lua 5.5), tables, and userdata.
 /// Returns up to 4 values: iterator, state, initial, to-be-closed variable.
 fn lua_pairs(l: &mut LuaState) -> LuaResult<usize> {
-    let val = l.get_arg(1).ok_or_else(|| {
-        l.error("bad argument #1 to 'pairs' (table or userdata expected)".to_string())
-    })?;
+    let val = l
+        .get_arg(1)
+        .ok_or_else(|| argerror(l, 1, "table or userdata expected"))?;
 
     // Check for __pairs metamethod first (like C Lua 5.5)
     if val.is_table() || val.is_userdata() {
@@ -593,7 +509,7 @@ fn lua_pairs(l: &mut LuaState) -> LuaResult<usize> {
         l.push_value(LuaValue::nil())?;
         Ok(4)
     } else {
-        Err(l.error("bad argument #1 to 'pairs' (table or userdata expected)".to_string()))
+        Err(argerror(l, 1, "table or userdata expected"))
     }
 }
 
@@ -639,7 +555,7 @@ fn lua_next(l: &mut LuaState) -> LuaResult<usize> {
     let result = {
         let table = table_val
             .as_table()
-            .ok_or_else(|| l.error("bad argument #1 to 'next' (table expected)".to_string()))?;
+            .ok_or_else(|| argerror(l, 1, "table expected"))?;
         table
             .next(&index_val)
             .map_err(|_| l.error("invalid key to 'next'".to_string()))?
@@ -663,7 +579,7 @@ fn lua_pcall(l: &mut LuaState) -> LuaResult<usize> {
 
     let arg_count = l.arg_count();
     if arg_count < 1 {
-        return Err(l.error("bad argument #1 to 'pcall' (value expected)".to_string()));
+        return Err(argerror(l, 1, "value expected"));
     }
 
     // Get current frame info
@@ -705,7 +621,7 @@ fn lua_xpcall(l: &mut LuaState) -> LuaResult<usize> {
     //   xpcall's C frame has: base+0=f, base+1=msgh, base+2..=args
     let arg_count = l.arg_count();
     if arg_count < 2 {
-        return Err(l.error("bad argument #2 to 'xpcall' (value expected)".to_string()));
+        return Err(argerror(l, 2, "value expected"));
     }
 
     let base = l
@@ -780,14 +696,14 @@ fn lua_xpcall(l: &mut LuaState) -> LuaResult<usize> {
 fn lua_getmetatable(l: &mut LuaState) -> LuaResult<usize> {
     let value = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'getmetatable' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
 
     let mt = get_metatable(l, &value);
     match mt {
         Some(mt_val) => {
             // Check for __metatable field - if present, return that instead
             if let Some(table) = mt_val.as_table() {
-                let key = l.create_string("__metatable")?;
+                let key = l.global_state_mut().const_strings.tm_metatable;
                 if let Some(mm) = table.raw_get(&key)
                     && !mm.is_nil()
                 {
@@ -808,17 +724,17 @@ fn lua_getmetatable(l: &mut LuaState) -> LuaResult<usize> {
 fn lua_setmetatable(l: &mut LuaState) -> LuaResult<usize> {
     let table = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'setmetatable' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
     let metatable = l
         .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'setmetatable' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 2, "value expected"))?;
 
     if let Some(table_ref) = table.as_table_mut() {
         // Check for __metatable protection on existing metatable
         if let Some(existing_mt) = table_ref.get_metatable()
             && let Some(mt_table) = existing_mt.as_table()
         {
-            let key = l.create_string("__metatable")?;
+            let key = l.global_state_mut().const_strings.tm_metatable;
             let has_protection = mt_table.raw_get(&key).is_some_and(|v| !v.is_nil());
             if has_protection {
                 return Err(l.error("cannot change a protected metatable".to_string()));
@@ -847,6 +763,30 @@ fn lua_setmetatable(l: &mut LuaState) -> LuaResult<usize> {
         if let Some(gc_ptr) = table.as_gc_ptr() {
             l.gc_barrier_back(gc_ptr);
         }
+    } else if let Some(ud) = table.as_userdata_mut()
+        && let Some(protected) = l.global_state().userdata_metatable_protection(ud)
+    {
+        // Userdata of a registered Rust type: its shared metatable may only be
+        // replaced once the host has lifted the protection.
+        let key = l.global_state_mut().const_strings.tm_metatable;
+        let has_field = ud
+            .get_metatable()
+            .and_then(|mt| mt.as_table().and_then(|mt| mt.raw_get(&key)))
+            .is_some_and(|v| !v.is_nil());
+        if protected || has_field {
+            return Err(l.error("cannot change a protected metatable".to_string()));
+        }
+        match metatable.kind() {
+            LuaValueKind::Nil | LuaValueKind::Table => ud.set_metatable(metatable),
+            _ => {
+                return Err(
+                    l.error("setmetatable() second argument must be a table or nil".to_string())
+                );
+            }
+        }
+        if let Some(gc_ptr) = table.as_gc_ptr() {
+            l.gc_barrier_back(gc_ptr);
+        }
     }
 
     // Lua 5.5: luaC_checkfinalizer - register object if __gc is present
@@ -860,10 +800,10 @@ fn lua_setmetatable(l: &mut LuaState) -> LuaResult<usize> {
 fn lua_rawget(l: &mut LuaState) -> LuaResult<usize> {
     let table = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'rawget' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
     let key = l
         .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'rawget' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 2, "value expected"))?;
 
     let value = table
         .as_table()
@@ -873,65 +813,55 @@ fn lua_rawget(l: &mut LuaState) -> LuaResult<usize> {
         l.push_value(v)?;
         return Ok(1);
     }
-    Err(l.error("bad argument #1 to 'rawget' (table expected)".to_string()))
+    Err(argerror(l, 1, "table expected"))
 }
 
 /// rawset(table, index, value) - Set without metamethods
 fn lua_rawset(l: &mut LuaState) -> LuaResult<usize> {
     let table = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'rawset' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
     let key = l
         .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'rawset' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 2, "value expected"))?;
     let value = l
         .get_arg(3)
-        .ok_or_else(|| l.error("bad argument #3 to 'rawset' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 3, "value expected"))?;
 
     if table.is_table() {
-        // Check for NaN key
-        if key.is_float()
-            && let Some(f) = key.as_number()
-            && f.is_nan()
-        {
-            return Err(l.error("table index is NaN".to_string()));
+        if let Some(msg) = key.table_key_error() {
+            return Err(l.error(msg.to_string()));
         }
         l.raw_set(&table, key, value);
         l.push_value(table)?;
         return Ok(1);
     }
-    Err(l.error("bad argument #1 to 'rawset' (table expected)".to_string()))
+    Err(argerror(l, 1, "table expected"))
 }
 
 /// rawlen(v) - Length without metamethods
 fn lua_rawlen(l: &mut LuaState) -> LuaResult<usize> {
     let value = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'rawlen' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
 
     let len = match value.kind() {
         LuaValueKind::Table => {
             if let Some(table) = value.as_table() {
                 table.len() as i64
             } else {
-                return Err(
-                    l.error("bad argument #1 to 'rawlen' (table or string expected)".to_string())
-                );
+                return Err(argerror(l, 1, "table or string expected"));
             }
         }
         LuaValueKind::String => {
             if let Some(s) = value.as_str() {
                 s.len() as i64
             } else {
-                return Err(
-                    l.error("bad argument #1 to 'rawlen' (table or string expected)".to_string())
-                );
+                return Err(argerror(l, 1, "table or string expected"));
             }
         }
         _ => {
-            return Err(
-                l.error("bad argument #1 to 'rawlen' (table or string expected)".to_string())
-            );
+            return Err(argerror(l, 1, "table or string expected"));
         }
     };
 
@@ -953,12 +883,10 @@ fn lua_rawequal(l: &mut LuaState) -> LuaResult<usize> {
 /// Lua 5.5 version with full parameter support including the new 'param' option
 fn lua_collectgarbage(l: &mut LuaState) -> LuaResult<usize> {
     // Check if GC is internally stopped (like Lua 5.5's gcstp & (GCSTPGC | GCSTPCLS))
-    // GCSTPGC means GC is currently running (prevents reentrancy)
+    // i.e. a collection or a finalizer is running, or the state is closing.
     // From lapi.c line 1174: if (g->gcstp & (GCSTPGC | GCSTPCLS)) return -1;
-    if l.global_state_mut().gc.gc_stopem {
-        // Return nil (false) to indicate GC is currently running
-        // In Lua 5.5, lua_gc returns -1, which is not returned to Lua code
-        // The Lua manual says collectgarbage returns false if it cannot run
+    if l.global_state().gc.is_blocked() {
+        // lua_gc returns -1 and collectgarbage turns it into fail (nil)
         l.push_value(LuaValue::nil())?;
         return Ok(1);
     }
@@ -1167,114 +1095,160 @@ fn lua_collectgarbage(l: &mut LuaState) -> LuaResult<usize> {
 
 /// load(chunk [, chunkname [, mode [, env]]]) - Load a chunk
 fn lua_load(l: &mut LuaState) -> LuaResult<usize> {
-    use crate::lua_value::chunk_serializer;
-
-    let chunk_val = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'load' (value expected)".to_string()))?;
+    // Called again after a reader yielded and then returned: the source read
+    // so far sits in the slot after load's arguments, the reader's results
+    // (or its error) above it.
+    if let Some((ok, n)) = l.take_continuation() {
+        let slot = l.get_top() - n - 1;
+        let source = l
+            .stack_get(slot)
+            .and_then(|v| v.as_bytes().map(<[u8]>::to_vec))
+            .unwrap_or_default();
+        let args = load_args(l, slot);
+        let reader = args.chunk.unwrap_or_default();
+        return match read_source(l, reader, slot, source, Some((ok, n)))? {
+            Ok(source) => load_source(l, args, Some(source)),
+            Err(msg) => load_failed(l, &msg),
+        };
+    }
 
-    // Save all arguments before potentially calling reader function
-    // because calling the reader will modify the stack
-    let chunkname_arg = l.get_arg(2).and_then(|v| v.as_str().map(|s| s.to_string()));
-    let mode_arg = l.get_arg(3).and_then(|v| v.as_str().map(|s| s.to_string()));
-    let env_arg = l.get_arg(4);
+    let args = load_args(l, l.get_top());
+    let Some(chunk_val) = args.chunk else {
+        return Err(argerror(l, 1, "value expected"));
+    };
 
     // Check if chunk is callable (function, cfunction, or table with __call metamethod)
-    let is_reader = chunk_val.is_function() || chunk_val.is_table();
-
-    let text_source = if !is_reader { chunk_val.as_str() } else { None };
-
-    // Get the chunk string or binary data
-    let (code_bytes, is_binary) = if text_source.is_some() {
-        (Vec::new(), false)
-    } else if is_reader {
+    if chunk_val.is_function() || chunk_val.is_table() {
         // chunk is a reader function - call it repeatedly to get source
-        let mut accumulated = Vec::new();
-        let mut is_binary = false;
-        let mut first_chunk = true;
-
-        loop {
-            // Call the reader function
-            l.push_value(chunk_val)?;
+        let slot = l.get_top();
+        l.push_value(LuaValue::nil())?;
+        return match read_source(l, chunk_val, slot, Vec::new(), None)? {
+            Ok(source) => load_source(l, args, Some(source)),
+            Err(msg) => load_failed(l, &msg),
+        };
+    }
+    load_source(l, args, None)
+}
 
-            let func_idx = l.get_top() - 1;
-            let call_result = l.pcall_stack_based(func_idx, 0);
+/// The arguments of `load`, found on the stack below `top`.
+struct LoadArgs {
+    chunk: Option<LuaValue>,
+    chunkname: Option<String>,
+    mode: Option<String>,
+    env: Option<LuaValue>,
+}
 
-            let result = match call_result {
-                Ok((true, result_count)) => {
-                    if result_count > 0 {
-                        l.stack_get(func_idx).unwrap_or_default()
-                    } else {
-                        LuaValue::nil()
-                    }
-                }
-                Ok((false, _)) => {
-                    // Error occurred in reader function
-                    let error_val = l.stack_get(func_idx).unwrap_or_default();
-                    l.set_top(func_idx)?;
+fn load_args(l: &LuaState, top: usize) -> LoadArgs {
+    let base = l.current_frame().unwrap().base;
+    let arg = |i: usize| (base + i < top).then(|| l.stack_get(base + i).unwrap_or_default());
+    let string_arg = |i: usize| arg(i).and_then(|v| v.as_str().map(str::to_string));
+    LoadArgs {
+        chunk: arg(0),
+        chunkname: string_arg(1),
+        mode: string_arg(2),
+        env: arg(3),
+    }
+}
 
-                    // Return nil + error message
-                    l.push_value(LuaValue::nil())?;
-                    let err_msg =
-                        l.create_string(&format!("error in reader function: {}", error_val))?;
-                    l.push_value(err_msg)?;
-                    return Ok(2);
-                }
-                Err(e) => {
-                    // Fatal error, propagate it
-                    return Err(e);
-                }
-            };
+/// Return nil plus an error message from `load`.
+fn load_failed(l: &mut LuaState, msg: &str) -> LuaResult<usize> {
+    l.push_value(LuaValue::nil())?;
+    let err_msg = l.create_string(msg)?;
+    l.push_value(err_msg)?;
+    Ok(2)
+}
 
-            // nil or empty string means end of input
-            if result.is_nil() {
+/// Call a `load` reader until it returns nil or an empty string, appending
+/// each piece to `source`. The reader runs at `slot + 1`. If it yields,
+/// `source` is kept in `slot` and reading goes on when `lua_load` is called
+/// again to continue; `pending` is then the outcome of that reader call.
+/// Returns the message `load` fails with if the reader errors or returns
+/// anything but a string.
+fn read_source(
+    l: &mut LuaState,
+    reader: LuaValue,
+    slot: usize,
+    mut source: Vec<u8>,
+    mut pending: Option<(bool, usize)>,
+) -> LuaResult<Result<Vec<u8>, String>> {
+    let func_idx = slot + 1;
+    loop {
+        let (ok, result_count) = match pending.take() {
+            Some(status) => status,
+            None => {
                 l.set_top(func_idx)?;
-                break;
-            }
-
-            // Get raw bytes from either textual or binary Lua strings.
-            let bytes_opt = result.as_bytes();
-
-            if let Some(bytes) = bytes_opt {
-                if bytes.is_empty() {
-                    l.set_top(func_idx)?;
-                    break;
+                l.push_value(reader)?;
+                match l.pcall_stack_based_k(func_idx, 0) {
+                    Err(LuaError::Yield) => {
+                        let saved = l.create_binary(source)?;
+                        l.stack_set(slot, saved)?;
+                        return Err(LuaError::Yield);
+                    }
+                    status => status?,
                 }
+            }
+        };
+        let result = if result_count > 0 {
+            l.stack_get(func_idx).unwrap_or_default()
+        } else {
+            LuaValue::nil()
+        };
 
-                // Check if first byte is binary marker (0x1B for Lua bytecode)
-                if first_chunk && !bytes.is_empty() && bytes[0] == 0x1B {
-                    is_binary = true;
-                }
+        if !ok {
+            l.set_top(slot)?;
+            return Ok(Err(format!("error in reader function: {}", result)));
+        }
+        // nil or empty string means end of input
+        if result.is_nil() {
+            break;
+        }
+        // Get raw bytes from either textual or binary Lua strings.
+        let Some(bytes) = result.as_bytes() else {
+            l.set_top(slot)?;
+            return Ok(Err("reader function must return a string".to_string()));
+        };
+        if bytes.is_empty() {
+            break;
+        }
+        // Copy the bytes before set_top lets the GC collect the piece
+        source.extend_from_slice(bytes);
+    }
+    l.set_top(slot)?;
+    Ok(Ok(source))
+}
 
-                // IMPORTANT: Copy the bytes BEFORE calling set_top
-                // because set_top may allow GC to run
-                accumulated.extend_from_slice(bytes);
-                first_chunk = false;
+/// Compile what `load` was given: the string argument, or the source a
+/// reader returned.
+fn load_source(
+    l: &mut LuaState,
+    args: LoadArgs,
+    reader_source: Option<Vec<u8>>,
+) -> LuaResult<usize> {
+    use crate::lua_value::chunk_serializer;
 
-                // Clean up stack
-                l.set_top(func_idx)?;
-            } else {
-                // Reader function returned non-string value (not nil)
-                // Return nil + error message like Lua does
-                l.set_top(func_idx)?;
-                l.push_value(LuaValue::nil())?;
-                let err_msg = l.create_string("reader function must return a string")?;
-                l.push_value(err_msg)?;
-                return Ok(2);
-            }
-        }
+    let chunk_val = args.chunk.unwrap_or_default();
+    let text_source = if reader_source.is_none() {
+        chunk_val.as_str()
+    } else {
+        None
+    };
 
-        (accumulated, is_binary)
+    // Get the chunk string or binary data
+    let (code_bytes, is_binary) = if text_source.is_some() {
+        (Vec::new(), false)
+    } else if let Some(source) = reader_source {
+        let is_binary = source.first() == Some(&0x1B);
+        (source, is_binary)
     } else if let Some(bytes) = chunk_val.as_bytes() {
         let is_binary = bytes.first() == Some(&0x1B);
         (bytes.to_vec(), is_binary)
     } else {
-        return Err(l.error("bad argument #1 to 'load' (function or string expected)".to_string()));
+        return Err(argerror(l, 1, "function or string expected"));
     };
 
     // Optional chunk name for error messages
     // If not provided and chunk is text, use the source code itself (or a prefix)
-    let chunkname = if let Some(name) = chunkname_arg {
+    let chunkname = if let Some(name) = args.chunkname {
         name
     } else if let Some(source) = text_source {
         source.to_string()
@@ -1290,11 +1264,11 @@ fn lua_load(l: &mut LuaState) -> LuaResult<usize> {
     };
 
     // Optional mode ("b", "t", or "bt")
-    let mode = mode_arg.unwrap_or_else(|| "bt".to_string());
+    let mode = args.mode.unwrap_or_else(|| "bt".to_string());
 
     // Validate mode string - must contain only 'b' and/or 't'
     if mode.is_empty() || mode.chars().any(|c| c != 'b' && c != 't') {
-        return Err(crate::stdlib::debug::argerror(l, 3, "invalid mode"));
+        return Err(argerror(l, 3, "invalid mode"));
     }
 
     // Check if mode allows this chunk type
@@ -1323,8 +1297,21 @@ fn lua_load(l: &mut LuaState) -> LuaResult<usize> {
         }
     }
 
+    if l.global_state().load_hook.is_some() {
+        let mode = if is_binary {
+            LoadMode::Binary
+        } else {
+            LoadMode::Text
+        };
+        let bytes = text_source.map_or(code_bytes.as_slice(), str::as_bytes);
+        let event = LoadEvent::new(LoadOrigin::Load, mode, &chunkname, bytes, l.load_caller());
+        if let Err(msg) = l.global_state().check_load(&event) {
+            return load_failed(l, &msg);
+        }
+    }
+
     // Optional environment table
-    let env = env_arg;
+    let env = args.env;
 
     let chunk_result = if is_binary {
         // Deserialize binary bytecode with VM to directly create strings
@@ -1337,14 +1324,14 @@ fn lua_load(l: &mut LuaState) -> LuaResult<usize> {
         l.compile_chunk_with_name(source, &chunkname)
             .map_err(|e| l.get_error_msg(e))
     } else {
-        // Compile text code using VM's string pool with chunk name
-        // Source code should be valid UTF-8
-        let code_str = match String::from_utf8(code_bytes) {
-            Ok(s) => s,
-            Err(_) => return Err(l.error("source is not valid UTF-8".to_string())),
-        };
-        l.compile_chunk_with_name(&code_str, &chunkname)
-            .map_err(|e| l.get_error_msg(e))
+        // Compile text code using VM's string pool with chunk name.
+        // Source the lexer cannot take is a load failure, not a raised error
+        match String::from_utf8(code_bytes) {
+            Ok(code_str) => l
+                .compile_chunk_with_name(&code_str, &chunkname)
+                .map_err(|e| l.get_error_msg(e)),
+            Err(_) => Err("source is not valid UTF-8".to_string()),
+        }
     };
 
     match chunk_result {
@@ -1393,12 +1380,12 @@ fn lua_load(l: &mut LuaState) -> LuaResult<usize> {
 fn lua_loadfile(l: &mut LuaState) -> LuaResult<usize> {
     let filename = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'loadfile' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
 
     let filename_str = if let Some(s) = filename.as_str() {
         s.to_string()
     } else {
-        return Err(l.error("bad argument #1 to 'loadfile' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     };
 
     // Optional mode ("b", "t", or "bt")
@@ -1466,7 +1453,8 @@ fn lua_loadfile(l: &mut LuaState) -> LuaResult<usize> {
         return Ok(2);
     }
 
-    match l.load_proto_from_file(&filename_str) {
+    // The message is returned as-is, without the caller's position
+    match l.load_audited_file(&filename_str, LoadOrigin::LoadFile) {
         Ok(proto) => {
             let upvalue_count = proto.as_ref().data.upvalue_count;
             let mut upvalues = Vec::with_capacity(upvalue_count);
@@ -1492,8 +1480,8 @@ fn lua_loadfile(l: &mut LuaState) -> LuaResult<usize> {
             l.push_value(func)?;
             Ok(1)
         }
-        Err(e) => {
-            let err_msg = l.create_string(&e.to_string())?;
+        Err(msg) => {
+            let err_msg = l.create_string(&msg)?;
             l.push_value(LuaValue::nil())?;
             l.push_value(err_msg)?;
             Ok(2)
@@ -1513,13 +1501,15 @@ fn lua_dofile(l: &mut LuaState) -> LuaResult<usize> {
         if let Some(s) = v.as_str() {
             s.to_string()
         } else {
-            return Err(l.error("bad argument #1 to 'dofile' (string expected)".to_string()));
+            return Err(argerror(l, 1, "string expected"));
         }
     } else {
         return Err(l.error("dofile: reading from stdin not yet implemented".to_string()));
     };
 
-    let proto = l.load_proto_from_file(&filename_str)?;
+    let proto = l
+        .load_audited_file(&filename_str, LoadOrigin::DoFile)
+        .map_err(|msg| l.error(msg))?;
     let global = l.global_state_mut().global;
     // Create function with _ENV upvalue (global table)
     let env_upvalue = l.create_upvalue_closed(global)?;
@@ -1561,20 +1551,14 @@ fn lua_warn(l: &mut LuaState) -> LuaResult<usize> {
 
     // At least one argument required, all must be strings
     if args.is_empty() {
-        return Err(
-            l.error("bad argument #1 to 'warn' (string expected, got no value)".to_string())
-        );
+        return Err(argerror(l, 1, "string expected, got no value"));
     }
     let mut parts: Vec<String> = Vec::with_capacity(args.len());
     for (i, arg) in args.iter().enumerate() {
         if let Some(s) = arg.as_str() {
             parts.push(s.to_string());
         } else {
-            return Err(l.error(format!(
-                "bad argument #{} to 'warn' (string expected, got {})",
-                i + 1,
-                arg.type_name()
-            )));
+            return Err(crate::stdlib::debug::arg_typeerror(l, i + 1, "string", arg));
         }
     }
 
@@ -1615,20 +1599,36 @@ fn lua_warn(l: &mut LuaState) -> LuaResult<usize> {
 
     // Regular message: concatenate all parts (no separator)
     let message: String = parts.concat();
+    emit_warning(l, &current_mode, &message)?;
+
+    Ok(0)
+}
 
-    match current_mode.as_str() {
+fn emit_warning(l: &mut LuaState, mode: &str, message: &str) -> LuaResult<()> {
+    match mode {
         "on" => {
             eprintln!("Lua warning: {}", message);
         }
         "store" => {
             // Store in _WARN global
-            let warn_val = l.create_string(&message)?;
+            let warn_val = l.create_string(message)?;
             l.global_state_mut().set_global("_WARN", warn_val)?;
         }
         _ => {
             // "off" - do nothing
         }
     }
+    Ok(())
+}
 
-    Ok(0)
+/// Report an error raised in `where_` (e.g. "__gc") as a warning, following
+/// the current `warn` mode (luaE_warnerror).
+pub(crate) fn warn_error(l: &mut LuaState, where_: &str, msg: &str) -> LuaResult<()> {
+    let registry = l.global_state_mut().registry;
+    let mode_key = l.create_string("_WARN_MODE")?;
+    let mode = l
+        .raw_get(&registry, &mode_key)
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| "off".to_string());
+    emit_warning(l, &mode, &format!("error in {} ({})", where_, msg))
 }
@@ -1,193 +1,223 @@
+// String to number conversion shared by the lexer, tonumber, string->number
+// coercion, io.read("n") and the integer argument checks of the string
+// library. Port of luaO_str2num / l_str2int / l_str2d from lobject.c.
+//
+// The grammar is C Lua's, independent of any locale: '.' is always the
+// decimal point. Leading and trailing whitespace is allowed, anything else
+// (including an embedded '\0', '_' separators, "inf" or "nan") is rejected.
+
 use crate::LuaValue;
 
+/// Convert a string to a Lua number, or nil if it is not a numeral.
 #[inline(never)]
 pub fn parse_lua_number(s: &str) -> LuaValue {
-    let s = s.trim();
-    if s.is_empty() || s.contains('\0') {
-        return LuaValue::nil();
+    lua_str2number(s.as_bytes()).unwrap_or_default()
+}
+
+/// Port of luaO_str2num: try an integer first, then a float. The whole
+/// input must be consumed, modulo surrounding whitespace.
+pub fn lua_str2number(s: &[u8]) -> Option<LuaValue> {
+    if let Some(i) = str2int(s) {
+        return Some(LuaValue::integer(i));
     }
+    str2d(s).map(LuaValue::float)
+}
 
-    // Handle sign
-    let (sign, rest) = if let Some(rest) = s.strip_prefix('-') {
-        (-1i64, rest)
-    } else if let Some(rest) = s.strip_prefix('+') {
-        (1i64, rest)
-    } else {
-        (1i64, s)
-    };
+/// C's isspace in the "C" locale.
+#[inline]
+fn is_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\x0b' | b'\x0c' | b'\r')
+}
 
-    // No whitespace allowed between sign and digits
-    // Check for hex prefix (0x or 0X)
-    if rest.starts_with("0x") || rest.starts_with("0X") {
-        let hex_part = &rest[2..];
+#[inline]
+fn skip_spaces(s: &[u8], mut i: usize) -> usize {
+    while i < s.len() && is_space(s[i]) {
+        i += 1;
+    }
+    i
+}
 
-        // Hex float contains '.' or 'p'/'P' - always treat as float
-        if hex_part.contains('.') || hex_part.to_lowercase().contains('p') {
-            if let Some(f) = parse_hex_float(hex_part) {
-                return LuaValue::float(sign as f64 * f);
-            }
-            return LuaValue::nil();
+/// Consume an optional sign; returns whether it was '-'.
+#[inline]
+fn skip_sign(s: &[u8], i: &mut usize) -> bool {
+    match s.get(*i) {
+        Some(b'-') => {
+            *i += 1;
+            true
+        }
+        Some(b'+') => {
+            *i += 1;
+            false
         }
+        _ => false,
+    }
+}
 
-        // Plain hex integer — parse with wrapping (C Lua wraps on overflow)
-        let mut result: u64 = 0;
-        let mut has_digits = false;
-        for c in hex_part.chars() {
-            if c == '_' {
-                continue; // allow underscores (Lua 5.5 doesn't, but skip for safety)
-            }
-            if let Some(d) = c.to_digit(16) {
-                result = result.wrapping_mul(16).wrapping_add(d as u64);
-                has_digits = true;
-            } else {
-                // Invalid character
-                return LuaValue::nil();
-            }
+#[inline]
+fn is_hex_prefix(s: &[u8], i: usize) -> bool {
+    s.get(i) == Some(&b'0') && matches!(s.get(i + 1), Some(b'x' | b'X'))
+}
+
+/// Port of l_str2int. Hex integers wrap around; decimal integers that do
+/// not fit are rejected here so they can be read as floats.
+fn str2int(s: &[u8]) -> Option<i64> {
+    const MAXBY10: u64 = (i64::MAX / 10) as u64;
+    const MAXLASTD: u64 = (i64::MAX % 10) as u64;
+
+    let mut i = skip_spaces(s, 0);
+    let neg = skip_sign(s, &mut i);
+    let mut a: u64 = 0;
+    let mut empty = true;
+    if is_hex_prefix(s, i) {
+        i += 2;
+        while let Some(d) = s.get(i).and_then(|&b| (b as char).to_digit(16)) {
+            a = a.wrapping_mul(16).wrapping_add(d as u64);
+            empty = false;
+            i += 1;
         }
-        if has_digits {
-            let i = result as i64;
-            return LuaValue::integer(sign * i);
+    } else {
+        while let Some(&b) = s.get(i).filter(|b| b.is_ascii_digit()) {
+            let d = (b - b'0') as u64;
+            if a >= MAXBY10 && (a > MAXBY10 || d > MAXLASTD + neg as u64) {
+                return None; // overflow
+            }
+            a = a * 10 + d;
+            empty = false;
+            i += 1;
         }
-        return LuaValue::nil();
     }
+    i = skip_spaces(s, i);
+    if empty || i != s.len() {
+        return None;
+    }
+    let a = a as i64;
+    Some(if neg { a.wrapping_neg() } else { a })
+}
 
-    // Decimal number - determine if integer or float
-    let has_dot = rest.contains('.');
-    let has_exponent = rest.to_lowercase().contains('e');
+/// Port of l_str2d: a decimal or hexadecimal float, as C's strtod would
+/// read it in the "C" locale, minus the "inf"/"nan" spellings.
+fn str2d(s: &[u8]) -> Option<f64> {
+    let mut i = skip_spaces(s, 0);
+    let neg = skip_sign(s, &mut i);
+    let (value, end) = if is_hex_prefix(s, i) {
+        strx2number(s, i + 2)?
+    } else {
+        str2decimal(s, i)?
+    };
+    if skip_spaces(s, end) != s.len() {
+        return None;
+    }
+    Some(if neg { -value } else { value })
+}
 
-    if !has_dot && !has_exponent {
-        // Try as integer
-        if let Ok(i) = s.parse::<i64>() {
-            return LuaValue::integer(i);
+/// Scan `digits [. digits] [(e|E) [sign] digits]` at `start` (unsigned)
+/// and convert it. Returns the value and the end of the numeral.
+fn str2decimal(s: &[u8], start: usize) -> Option<(f64, usize)> {
+    let count_digits = |mut i: usize| {
+        while s.get(i).is_some_and(u8::is_ascii_digit) {
+            i += 1;
         }
+        i
+    };
+    let mut i = count_digits(start);
+    let mut has_digits = i > start;
+    if s.get(i) == Some(&b'.') {
+        let frac = i + 1;
+        i = count_digits(frac);
+        has_digits |= i > frac;
     }
-
-    // Try as float (either has '.'/e' or integer parse failed due to overflow)
-    // Reject inf/nan which Rust's parse accepts but Lua doesn't
-    if let Ok(f) = s.parse::<f64>()
-        && (f.is_finite() || s.contains('.') || has_exponent)
-    {
-        // Only accept inf/nan if they came from a valid numeric expression
-        // (e.g., overflow), not from the literal strings "inf"/"nan"
-        let lower = s.to_lowercase();
-        let stripped = lower.trim_start_matches(['+', '-']);
-        if stripped.starts_with("inf") || stripped.starts_with("nan") {
-            return LuaValue::nil();
+    if !has_digits {
+        return None;
+    }
+    if matches!(s.get(i), Some(b'e' | b'E')) {
+        let mut j = i + 1;
+        skip_sign(s, &mut j);
+        let exp_end = count_digits(j);
+        // Like strtod, a dangling exponent marker is not part of the
+        // numeral, which then fails as trailing garbage.
+        if exp_end > j {
+            i = exp_end;
         }
-        return LuaValue::float(f);
     }
-
-    LuaValue::nil()
+    // The scanned range is plain ASCII in Rust's float syntax, which rounds
+    // correctly and never depends on a locale.
+    let text = std::str::from_utf8(&s[start..i]).ok()?;
+    text.parse::<f64>().ok().map(|f| (f, i))
 }
 
-/// Parse hexadecimal float format (e.g., "0x1.8p+1" = 3.0)
-/// Format: [integer_part][.fractional_part][p|P[+|-]exponent]
-/// Returns Some(f64) on success, None on parse error
-///
-/// Matches C Lua's `lua_strx2number`: accumulates mantissa as an integer
-/// (up to significant digits), tracks the binary exponent separately, so
-/// very long digit strings don't cause overflow.
-fn parse_hex_float(s: &str) -> Option<f64> {
-    // Split by 'p' or 'P' to separate mantissa and exponent
-    let (mantissa_str, exp_str) = if let Some(pos) = s.to_lowercase().find('p') {
-        (&s[..pos], &s[pos + 1..])
-    } else {
-        // No exponent, treat whole string as mantissa with exponent 0
-        (s, "0")
-    };
+/// Port of lua_strx2number: the part after "0x", as
+/// `hexdigits [. hexdigits] [(p|P) [sign] digits]`.
+fn strx2number(s: &[u8], start: usize) -> Option<(f64, usize)> {
+    // 15 hex digits = 60 bits, enough for a correctly rounded 53-bit
+    // mantissa once later digits are folded into a sticky bit.
+    const MAXSIGDIG: u32 = 15;
 
-    // Parse mantissa (hex digits with optional decimal point).
-    // Track significand as u64 and binary exponent separately.
-    // Leading zeros are not counted as significant digits.
     let mut mantissa: u64 = 0;
-    let mut found_dot = false;
-    let mut has_digit = false;
-    let mut sig_digits = 0; // number of significant hex digits consumed (non-leading-zero)
-    let mut exp_adjust: i64 = 0; // binary exponent adjustment
-    const MAX_SIG: usize = 15; // 15 hex digits = 60 bits, fits in u64
-
-    for ch in mantissa_str.chars() {
-        if ch == '.' {
-            if found_dot {
-                return None; // Multiple decimal points
-            }
-            found_dot = true;
-        } else if let Some(digit) = ch.to_digit(16) {
-            has_digit = true;
-            if digit != 0 && sig_digits == 0 {
-                // First non-zero digit: start tracking significant digits
-                mantissa = digit as u64;
-                sig_digits = 1;
-                if found_dot {
-                    exp_adjust -= 4;
+    let mut sigdig: u32 = 0;
+    let mut nosigdig: u32 = 0;
+    let mut sticky = false;
+    let mut exp: i64 = 0;
+    let mut has_dot = false;
+    let mut i = start;
+    loop {
+        match s.get(i) {
+            Some(b'.') if !has_dot => has_dot = true,
+            Some(&b) if b.is_ascii_hexdigit() => {
+                let d = (b as char).to_digit(16).unwrap_or(0) as u64;
+                if sigdig == 0 && d == 0 {
+                    nosigdig += 1;
+                } else {
+                    sigdig += 1;
+                    if sigdig <= MAXSIGDIG {
+                        mantissa = mantissa * 16 + d;
+                    } else {
+                        sticky |= d != 0;
+                        exp += 4;
+                    }
                 }
-            } else if sig_digits > 0 && sig_digits < MAX_SIG {
-                // Accumulating significant digits
-                mantissa = mantissa * 16 + digit as u64;
-                sig_digits += 1;
-                if found_dot {
-                    exp_adjust -= 4;
-                }
-            } else if sig_digits >= MAX_SIG {
-                // Beyond precision: skip digit, adjust exponent for integer-part digits
-                if !found_dot {
-                    exp_adjust += 4;
-                }
-                // Fractional digits beyond precision are dropped.
-            } else {
-                // sig_digits == 0 && digit == 0 → leading zero
-                // Still need to track exponent for fractional leading zeros
-                if found_dot {
-                    exp_adjust -= 4;
+                if has_dot {
+                    exp -= 4;
                 }
             }
-        } else if !ch.is_whitespace() {
-            return None; // Invalid character
+            _ => break,
         }
+        i += 1;
     }
-
-    if !has_digit {
+    if nosigdig + sigdig == 0 {
         return None;
     }
-
-    // Parse binary exponent (decimal number after 'p')
-    let exp_str = exp_str.trim();
-    if exp_str.is_empty() && s.to_lowercase().contains('p') {
-        return None; // 'p' present but no exponent
+    if matches!(s.get(i), Some(b'p' | b'P')) {
+        let mut j = i + 1;
+        let neg = skip_sign(s, &mut j);
+        if !s.get(j).is_some_and(u8::is_ascii_digit) {
+            return None; // exponent needs at least one digit
+        }
+        let mut e: i64 = 0;
+        while let Some(&b) = s.get(j).filter(|b| b.is_ascii_digit()) {
+            // Saturate: past this the result is 0 or inf anyway.
+            e = (e * 10 + (b - b'0') as i64).min(1 << 20);
+            j += 1;
+        }
+        exp += if neg { -e } else { e };
+        i = j;
     }
-    let exp: i64 = if exp_str == "0" || exp_str.is_empty() {
-        0
-    } else {
-        exp_str.parse::<i64>().ok()?
-    };
-
-    let total_exp = exp + exp_adjust;
-    // Use ldexp-style multiplication; for very large/small exponents, f64 handles
-    // overflow to inf and underflow to 0 naturally.
-    let result = ldexp(mantissa as f64, total_exp);
-    Some(result)
+    let mantissa = mantissa | sticky as u64;
+    Some((ldexp(mantissa as f64, exp), i))
 }
 
-/// Multiply a float by 2^exp (ldexp). Handles large exponents that exceed
-/// the range of a single multiplication by splitting into steps.
+/// x * 2^exp, stepping through the exponent so intermediate powers of two
+/// stay representable.
 fn ldexp(mut x: f64, mut exp: i64) -> f64 {
-    if x == 0.0 || exp == 0 {
-        return x;
+    let up = f64::from_bits(0x5ff0_0000_0000_0000); // 2^512
+    let down = f64::from_bits(0x1ff0_0000_0000_0000); // 2^-512
+    while exp > 512 && x.is_finite() && x != 0.0 {
+        x *= up;
+        exp -= 512;
     }
-    // Apply exponent in steps of at most 1023 (max finite 2^n for f64)
-    while exp > 1023 {
-        x *= 2.0f64.powi(1023);
-        exp -= 1023;
-        if x.is_infinite() {
-            return x;
-        }
-    }
-    while exp < -1074 {
-        x *= 2.0f64.powi(-1074);
-        exp += 1074;
-        if x == 0.0 {
-            return x;
-        }
+    while exp < -512 && x != 0.0 {
+        x *= down;
+        exp += 512;
     }
-    x * (2.0f64).powi(exp as i32)
+    let exp = exp.clamp(-512, 512);
+    x * f64::from_bits(((exp + 1023) as u64) << 52)
 }
@@ -1,3 +1,4 @@
+use crate::stdlib::debug::argerror;
 use crate::{LuaResult, LuaValue, lua_vm::LuaState};
 
 /// require(modname) - Load a module
@@ -5,10 +6,10 @@ use crate::{LuaResult, LuaValue, lua_vm::LuaState};
 pub fn lua_require(l: &mut LuaState) -> LuaResult<usize> {
     let modname_val = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'require' (string expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "string expected"))?;
 
     let Some(modname_str) = modname_val.as_str() else {
-        return Err(l.error("bad argument #1 to 'require' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     };
 
     // Get package.loaded from registry (like standard Lua's LUA_LOADED_TABLE)
@@ -3,7 +3,10 @@
 
 use crate::lib_registry::LibraryModule;
 use crate::lua_value::LuaValue;
+use crate::lua_vm::async_thread::TaskOwner;
 use crate::lua_vm::{ErrorMsg, LuaError, LuaResult, LuaState};
+use crate::stdlib::auxlib::check_function;
+use crate::stdlib::debug::{arg_typeerror, arg_typeerror_novalue};
 
 pub fn create_coroutine_lib() -> LibraryModule {
     crate::lib_module!("coroutine", {
@@ -18,18 +21,60 @@ pub fn create_coroutine_lib() -> LibraryModule {
     })
 }
 
-/// coroutine.create(f) - Create a new coroutine
-fn coroutine_create(l: &mut LuaState) -> LuaResult<usize> {
-    let func = match l.get_arg(1) {
-        Some(f) => f,
-        None => {
-            return Err(l.error("coroutine.create requires a function argument".to_string()));
-        }
-    };
+/// Port of getco: argument 1 as a thread value.
+fn getco(l: &mut LuaState) -> LuaResult<LuaValue> {
+    match l.get_arg(1) {
+        Some(v) if v.is_thread() => Ok(v),
+        Some(v) => Err(arg_typeerror(l, 1, "thread", &v)),
+        None => Err(arg_typeerror_novalue(l, 1, "thread")),
+    }
+}
+
+/// Port of getoptco: argument 1 as a thread, or the calling thread when the
+/// argument is absent. An explicit nil is still an error.
+fn getoptco(l: &mut LuaState) -> LuaResult<LuaValue> {
+    if l.get_arg(1).is_none() {
+        Ok(LuaValue::thread(l.thread_ptr()))
+    } else {
+        getco(l)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CoStatus {
+    Running,
+    Dead,
+    Suspended,
+    Normal,
+}
 
-    if !func.is_function() && !func.is_cfunction() {
-        return Err(l.error("coroutine.create requires a function argument".to_string()));
+/// Port of auxstatus: the status of `co` as seen from `l`.
+fn auxstatus(l: &LuaState, co: &LuaState) -> CoStatus {
+    if std::ptr::eq(l, co) {
+        CoStatus::Running
+    } else if co.dead {
+        // Dead by error — still has stack/frames for debug.traceback
+        CoStatus::Dead
+    } else if co.call_depth() > 0 {
+        // Frames and not yielded: it resumed another coroutine and waits
+        if co.is_yielded() {
+            CoStatus::Suspended
+        } else {
+            CoStatus::Normal
+        }
+    } else if co.is_main_thread() {
+        CoStatus::Normal
+    } else if !co.stack().is_empty() {
+        // Has its function but no frames - not started yet
+        CoStatus::Suspended
+    } else {
+        CoStatus::Dead
     }
+}
+
+/// coroutine.create(f) - Create a new coroutine
+fn coroutine_create(l: &mut LuaState) -> LuaResult<usize> {
+    let func = check_function(l, 1)?;
 
     // Use VM's create_thread which properly sets up the thread with the function
     let vm = l.global_state_mut();
@@ -41,16 +86,7 @@ fn coroutine_create(l: &mut LuaState) -> LuaResult<usize> {
 
 /// coroutine.resume(co, ...) - Resume a coroutine
 fn coroutine_resume(l: &mut LuaState) -> LuaResult<usize> {
-    let thread_val = match l.get_arg(1) {
-        Some(t) => t,
-        None => {
-            return Err(l.error("coroutine.resume requires a thread argument".to_string()));
-        }
-    };
-
-    if !thread_val.is_thread() {
-        return Err(l.error("coroutine.resume requires a thread argument".to_string()));
-    }
+    let thread_val = getco(l)?;
 
     // Get remaining arguments
     let all_args = l.get_args();
@@ -96,6 +132,7 @@ fn coroutine_resume(l: &mut LuaState) -> LuaResult<usize> {
                 LuaValue::nil()
             };
             l.clear_error();
+            l.set_coroutine_error(error_val, thread_val);
             l.push_value(LuaValue::boolean(false))?; // success=false
             l.push_value(error_val)?;
             Ok(2)
@@ -125,50 +162,16 @@ fn coroutine_yield(l: &mut LuaState) -> LuaResult<usize> {
 
 /// coroutine.status(co) - Get coroutine status
 fn coroutine_status(l: &mut LuaState) -> LuaResult<usize> {
-    let thread_val = match l.get_arg(1) {
-        Some(t) => t,
-        None => {
-            return Err(l.error("coroutine.status requires a thread argument".to_string()));
-        }
-    };
-
-    if !thread_val.is_thread() {
-        return Err(l.error("coroutine.status requires a thread argument".to_string()));
-    }
-
-    // Check if thread exists and get status
-    // Pre-read const strings before mutable borrow of thread
-    let cs = &l.global_state_mut().const_strings;
-    let str_running = cs.str_running;
-    let str_suspended = cs.str_suspended;
-    let str_normal = cs.str_normal;
-    let str_dead = cs.str_dead;
-
-    let status_val = if let Some(thread) = thread_val.as_thread_mut() {
-        if thread.is_main_thread() {
-            // Main thread is always running
-            str_running
-        } else if thread.dead {
-            // Dead by error — still has stack/frames for debug.traceback
-            str_dead
-        } else if thread.call_depth() > 0 {
-            if thread.is_yielded() {
-                str_suspended
-            } else {
-                // Thread has frames and is not yielded — it's either running
-                // or normal (resumed another coroutine and waiting).
-                // Compare identity: if calling thread IS this thread, it's "running".
-                let is_self = std::ptr::eq(l as *const LuaState, thread as *const LuaState);
-                if is_self { str_running } else { str_normal }
-            }
-        } else if !thread.stack().is_empty() {
-            // Has stack but no frames - initial state
-            str_suspended
-        } else {
-            str_dead
-        }
-    } else {
-        str_dead
+    let thread_val = getco(l)?;
+    let status = thread_val
+        .as_thread_mut()
+        .map_or(CoStatus::Dead, |co| auxstatus(l, co));
+    let cs = &l.global_state().const_strings;
+    let status_val = match status {
+        CoStatus::Running => cs.str_running,
+        CoStatus::Dead => cs.str_dead,
+        CoStatus::Suspended => cs.str_suspended,
+        CoStatus::Normal => cs.str_normal,
     };
 
     l.push_value(status_val)?;
@@ -193,16 +196,7 @@ fn coroutine_running(l: &mut LuaState) -> LuaResult<usize> {
 
 /// coroutine.wrap(f) - Create a wrapped coroutine
 fn coroutine_wrap(l: &mut LuaState) -> LuaResult<usize> {
-    let func = match l.get_arg(1) {
-        Some(f) => f,
-        None => {
-            return Err(l.error("coroutine.wrap requires a function argument".to_string()));
-        }
-    };
-
-    if !func.is_function() && !func.is_cfunction() {
-        return Err(l.error("coroutine.wrap requires a function argument".to_string()));
-    }
+    let func = check_function(l, 1)?;
 
     // Create the coroutine
     let vm = l.global_state_mut();
@@ -252,31 +246,27 @@ fn coroutine_wrap_call(l: &mut LuaState) -> LuaResult<usize> {
             // Match Lua's coroutine.wrap semantics: propagate the wrapped
             // coroutine's actual error value, including dead-coroutine errors
             // archived on the thread after resume_thread unwinds.
-            if let Some(thread) = thread_val.as_thread_mut() {
-                let has_active_err_obj = thread.has_error_object();
+            let Some(thread) = thread_val.as_thread_mut() else {
+                return Err(LuaError::RuntimeError);
+            };
+            let error_val = if thread.has_error_object() {
                 let active_err_obj = thread.error_object();
-                if has_active_err_obj {
-                    let _ = thread.get_error_msg(e);
-                    return Err(l.error_with_object(active_err_obj));
-                }
-
+                let _ = thread.get_error_msg(e);
+                active_err_obj
+            } else {
                 let active_msg = thread.get_error_msg(e);
                 if !active_msg.is_empty() {
-                    let err_str = l.create_string(&active_msg)?;
-                    return Err(l.error_with_object(err_str));
-                }
-
-                match thread.dead_error() {
-                    ErrorMsg::Object(obj) => return Err(l.error_with_object(*obj)),
-                    ErrorMsg::Msg(msg) if !msg.is_empty() => {
-                        let err_str = l.create_string(msg)?;
-                        return Err(l.error_with_object(err_str));
+                    l.create_string(&active_msg)?
+                } else {
+                    match thread.dead_error() {
+                        ErrorMsg::Object(obj) => *obj,
+                        ErrorMsg::Msg(msg) if !msg.is_empty() => l.create_string(msg)?,
+                        _ => return Err(LuaError::RuntimeError),
                     }
-                    _ => {}
                 }
-            }
-
-            Err(LuaError::RuntimeError)
+            };
+            l.set_coroutine_error(error_val, thread_val);
+            Err(l.error_with_object(error_val))
         }
     }
 }
@@ -285,71 +275,33 @@ fn coroutine_wrap_call(l: &mut LuaState) -> LuaResult<usize> {
 /// Returns true iff nny == 0 (not inside a non-yieldable C call boundary).
 fn coroutine_isyieldable(l: &mut LuaState) -> LuaResult<usize> {
     // If a thread argument is given, check that thread; otherwise check current
-    let is_yieldable = if let Some(arg) = l.get_arg(1) {
-        if let Some(thread) = arg.as_thread_mut() {
-            thread.nny == 0
-        } else {
-            return Err(l.error("value is not a thread".to_string()));
-        }
-    } else {
-        l.nny == 0
-    };
+    let thread_val = getoptco(l)?;
+    let is_yieldable = thread_val.as_thread_mut().is_some_and(|co| co.nny == 0);
     l.push_value(LuaValue::boolean(is_yieldable))?;
     Ok(1)
 }
 
-enum CloseStatus {
-    Dead = 0,
-    Suspended = 1,
-    Normal = 2,
-    Running = 3,
-}
-
 /// coroutine.close([co]) - Close a coroutine, marking it as dead
 /// If no argument, closes the calling thread (self).
 /// Calls __close on any pending to-be-closed variables, then kills the thread.
+/// This is the only way they run: like PUC Lua, an unreachable suspended
+/// coroutine is collected without closing its pending variables.
 fn coroutine_close(l: &mut LuaState) -> LuaResult<usize> {
-    // getoptco: if no argument, use the calling thread itself
-    let thread_val = match l.get_arg(1) {
-        Some(t) if t.is_thread() => t,
-        Some(t) if !t.is_nil() => {
-            return Err(l.error("bad argument #1 to 'close' (coroutine expected)".to_string()));
-        }
-        _ => {
-            // No argument or nil — close self
-            let thread_ptr = l.thread_ptr();
-            LuaValue::thread(thread_ptr)
-        }
-    };
+    let thread_val = getoptco(l)?;
 
     // Clear the thread's stack and frames to mark it as closed
     if let Some(thread) = thread_val.as_thread_mut() {
-        // Determine status (matches C Lua's auxstatus)
-        let is_self = std::ptr::eq(l as *const LuaState, thread as *const LuaState);
-        // 0 = dead, 1 = suspended, 2 = normal, 3 = running
-        let status: CloseStatus = if is_self {
-            CloseStatus::Running
-        } else if thread.dead {
-            CloseStatus::Dead
-        } else if thread.is_yielded() {
-            CloseStatus::Suspended
-        } else if thread.call_depth() > 0 {
-            CloseStatus::Normal
-        } else if !thread.stack().is_empty() {
-            CloseStatus::Suspended
-        } else {
-            CloseStatus::Dead
-        };
+        let status = auxstatus(l, thread);
 
         match status {
-            CloseStatus::Dead | CloseStatus::Suspended => {
+            CoStatus::Dead | CoStatus::Suspended => {
                 // OK to close dead or suspended coroutines.
                 // For dead-by-error coroutines, preserve the error.
             }
-            CloseStatus::Normal => {
+            CoStatus::Normal => {
                 return Err(l.error("cannot close a normal coroutine".to_string()));
             }
-            CloseStatus::Running => {
+            CoStatus::Running => {
                 if thread.is_main_thread() {
                     return Err(l.error("cannot close main thread".to_string()));
                 }
@@ -390,6 +342,12 @@ fn coroutine_close(l: &mut LuaState) -> LuaResult<usize> {
         }
         thread.stack_truncate();
 
+        // Cancel the async futures the coroutine was waiting on
+        thread.pending_future = None;
+        l.global_state()
+            .async_tasks
+            .cancel(Some(TaskOwner::of(thread)));
+
         match close_result {
             Ok(()) => {
                 // Check if coroutine had a pending error (dead-by-error)
@@ -2,11 +2,12 @@
 // Implements: traceback, getinfo, getlocal, getmetatable, getupvalue, etc.
 
 use crate::compiler::format_source;
+use crate::gc::GcObjectPtr;
 use crate::lib_registry::LibraryModule;
 use crate::lua_value::{LuaProto, LuaValue};
 use crate::lua_vm::call_info::call_status;
 use crate::lua_vm::opcode::OpCode;
-use crate::lua_vm::{LuaError, LuaResult, LuaState, TmKind, get_metatable};
+use crate::lua_vm::{LUA_MASKINTERNAL, LuaError, LuaResult, LuaState, TmKind, get_metatable};
 use crate::{Instruction, LUA_MASKCALL, LUA_MASKCOUNT, LUA_MASKLINE, LUA_MASKRET, lib_module};
 
 /// Get the type name of an object, checking __name in metatable first.
@@ -15,9 +16,8 @@ pub fn objtypename(l: &mut LuaState, v: &LuaValue) -> String {
     if let Some(mt) = get_metatable(l, v)
         && let Some(mt_table) = mt.as_table()
     {
-        // Create a string key for __name lookup
-        if let Ok(key) = l.create_string("__name")
-            && let Some(name_val) = mt_table.raw_get(&key)
+        let key = l.global_state_mut().const_strings.tm_name;
+        if let Some(name_val) = mt_table.raw_get(&key)
             && let Some(s) = name_val.as_str()
         {
             return s.to_string();
@@ -671,6 +671,24 @@ pub fn opinterror(
     l.error(format!("attempt to {} a {} value{}", op, blame_type, info))
 }
 
+/// Generate a concatenation error (mirrors luaG_concaterror): blames `p2`
+/// when `p1` is a string or number. `p1_idx` is the stack slot of `p1`, `p2`
+/// being right after it, so the variable name comes from the right register.
+pub fn concaterror(l: &mut LuaState, p1: &LuaValue, p2: &LuaValue, p1_idx: usize) -> LuaError {
+    let (bad, idx) = if p1.ttisstring() || p1.is_number() {
+        (p2, p1_idx + 1)
+    } else {
+        (p1, p1_idx)
+    };
+    let tname = objtypename(l, bad);
+    let base = l.current_frame().map_or(0, |ci| ci.base);
+    let info = match idx.checked_sub(base) {
+        Some(reg) => varinfo_for_reg(l, reg as u32),
+        None => String::new(),
+    };
+    l.error(format!("attempt to concatenate a {} value{}", tname, info))
+}
+
 /// Generate a comparison error (mirrors luaG_ordererror).
 pub fn ordererror(l: &mut LuaState, p1: &LuaValue, p2: &LuaValue) -> LuaError {
     let t1 = objtypename(l, p1);
@@ -742,7 +760,10 @@ fn debug_lib_init(l: &mut LuaState) -> LuaResult<()> {
     let hook_table = l.create_table(0, 0)?;
     // Create its metatable with __mode = "k" (weak keys)
     let meta = l.create_table(0, 1)?;
-    let mode_key = l.create_string("__mode")?;
+    let mode_key = l
+        .global_state_mut()
+        .const_strings
+        .get_tm_value(TmKind::Mode);
     let mode_val = l.create_string("k")?;
     l.raw_set(&meta, mode_key, mode_val);
     if let Some(hook_tbl) = hook_table.as_table_mut() {
@@ -802,10 +823,30 @@ fn debug_traceback(l: &mut LuaState) -> LuaResult<usize> {
 
     trace.push_str("stack traceback:");
 
-    // Get call stack info from target state
-    let call_depth = target.call_depth();
+    // An error raised inside a coroutine (re-raised by a coroutine.wrap
+    // function, or returned by coroutine.resume) lists the frames where it
+    // was raised first, innermost coroutine first.
+    let mut sources = Vec::new();
+    let mut thread = target;
+    while let Some(co) = thread.coroutine_error_source(&message_val) {
+        sources.push(co);
+        thread = co;
+    }
+    for co in sources.iter().rev() {
+        push_traceback_frames(&mut trace, co, 0);
+        trace.push_str("\n\t(coroutine boundary)");
+    }
+    push_traceback_frames(&mut trace, target, level);
 
-    let start_level = level;
+    let result = l.create_string(&trace)?;
+    l.push_value(result)?;
+    Ok(1)
+}
+
+/// Append `target`'s frames from `level` down to a traceback, eliding the
+/// middle of very deep stacks like luaL_traceback.
+fn push_traceback_frames(trace: &mut String, target: &LuaState, level: usize) {
+    let call_depth = target.call_depth();
 
     // Port of luaL_traceback from lauxlib.c
     const LEVELS1: usize = 10;
@@ -814,7 +855,7 @@ fn debug_traceback(l: &mut LuaState) -> LuaResult<usize> {
     // call_depth counts all active frames, including the current C frame for
     // debug.traceback itself. So the number of visible frames is the total
     // depth minus the requested skip level.
-    let top_frame = call_depth.saturating_sub(start_level);
+    let top_frame = call_depth.saturating_sub(level);
     if top_frame > 0 {
         let frames: Vec<usize> = (0..top_frame).rev().collect();
         let total = frames.len();
@@ -855,7 +896,9 @@ fn debug_traceback(l: &mut LuaState) -> LuaResult<usize> {
                         0
                     };
 
-                    let func_desc = if let Some((kind, name)) = getfuncname(target, i) {
+                    let func_desc = if let Some(name) = chunk.name.as_deref() {
+                        format!("function '{}'", name)
+                    } else if let Some((kind, name)) = getfuncname(target, i) {
                         format!("{} '{}'", kind, name)
                     } else if chunk.linedefined == 0 {
                         "main chunk".to_string()
@@ -890,10 +933,6 @@ fn debug_traceback(l: &mut LuaState) -> LuaResult<usize> {
             }
         }
     }
-
-    let result = l.create_string(&trace)?;
-    l.push_value(result)?;
-    Ok(1)
 }
 
 /// debug.getinfo([thread,] f [, what]) - Get function info
@@ -951,9 +990,7 @@ fn debug_getinfo(l: &mut LuaState) -> LuaResult<usize> {
             None => return Ok(0), // level out of range → return nothing (falsy)
         }
     } else {
-        return Err(
-            l.error("bad argument #1 to 'getinfo' (function or number expected)".to_string())
-        );
+        return Err(argerror(l, 1, "function or number expected"));
     };
 
     // Convert DebugInfo to Lua table
@@ -1102,6 +1139,12 @@ fn debug_setmetatable(l: &mut LuaState) -> LuaResult<usize> {
         if let Some(gc_ptr) = value.as_gc_ptr() {
             l.gc_barrier_back(gc_ptr);
         }
+    } else if let Some(ud) = value.as_userdata_mut() {
+        // Full userdata carry their own metatable, like tables
+        ud.set_metatable(mt_val.unwrap_or_default());
+        if let Some(gc_ptr) = value.as_gc_ptr() {
+            l.gc_barrier_back(gc_ptr);
+        }
     } else {
         // For basic types (number, string, boolean), set the global type metatable
         let kind = value.kind();
@@ -1226,7 +1269,7 @@ fn debug_sethook(l: &mut LuaState) -> LuaResult<usize> {
     // SAFETY: target_ptr points to a valid LuaState
     let target = unsafe { &mut *target_ptr };
     target.hook = hook;
-    target.hook_mask = mask;
+    target.hook_mask = mask | target.hook_mask & LUA_MASKINTERNAL;
     target.base_hook_count = count;
     target.hook_count = count;
 
@@ -1246,7 +1289,7 @@ fn debug_getlocal(l: &mut LuaState) -> LuaResult<usize> {
     // Detect optional thread argument
     let arg1 = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'getlocal'".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
 
     // Detect optional thread argument and set target state
     let (func_or_level, local_idx_val, target_ptr): (LuaValue, LuaValue, *const LuaState) =
@@ -1254,17 +1297,17 @@ fn debug_getlocal(l: &mut LuaState) -> LuaResult<usize> {
             // debug.getlocal(thread, f, local)
             let a2 = l
                 .get_arg(2)
-                .ok_or_else(|| l.error("bad argument #2 to 'getlocal'".to_string()))?;
+                .ok_or_else(|| argerror(l, 2, "value expected"))?;
             let a3 = l
                 .get_arg(3)
-                .ok_or_else(|| l.error("bad argument #3 to 'getlocal'".to_string()))?;
+                .ok_or_else(|| argerror(l, 3, "value expected"))?;
             let thread = arg1.as_thread_mut().unwrap() as *const LuaState;
             (a2, a3, thread)
         } else {
             // debug.getlocal(f, local)
             let a2 = l
                 .get_arg(2)
-                .ok_or_else(|| l.error("bad argument #2 to 'getlocal'".to_string()))?;
+                .ok_or_else(|| argerror(l, 2, "value expected"))?;
             (arg1, a2, l as *const LuaState)
         };
 
@@ -1309,7 +1352,7 @@ fn debug_getlocal(l: &mut LuaState) -> LuaResult<usize> {
     })?;
 
     if level < 0 {
-        return Err(l.error("bad argument #1 to 'getlocal' (level out of range)".to_string()));
+        return Err(argerror(l, 1, "level out of range"));
     }
     let level = level as usize;
 
@@ -1336,7 +1379,7 @@ fn debug_getlocal(l: &mut LuaState) -> LuaResult<usize> {
 
     let call_depth = target.call_depth();
     if level >= call_depth {
-        return Err(l.error("bad argument #1 to 'getlocal' (level out of range)".to_string()));
+        return Err(argerror(l, 1, "level out of range"));
     }
 
     let frame_idx = call_depth - 1 - level;
@@ -1473,29 +1516,29 @@ fn debug_setlocal(l: &mut LuaState) -> LuaResult<usize> {
     // Parse arguments: [thread,] level, local_index, value
     let arg1 = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'setlocal'".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
 
     let (level_val, local_val, value, target_ptr): (LuaValue, LuaValue, LuaValue, *mut LuaState) =
         if arg1.is_thread() {
             // debug.setlocal(thread, level, local, value)
             let a2 = l
                 .get_arg(2)
-                .ok_or_else(|| l.error("bad argument #2 to 'setlocal'".to_string()))?;
+                .ok_or_else(|| argerror(l, 2, "value expected"))?;
             let a3 = l
                 .get_arg(3)
-                .ok_or_else(|| l.error("bad argument #3 to 'setlocal'".to_string()))?;
+                .ok_or_else(|| argerror(l, 3, "value expected"))?;
             let a4 = l
                 .get_arg(4)
-                .ok_or_else(|| l.error("bad argument #4 to 'setlocal'".to_string()))?;
+                .ok_or_else(|| argerror(l, 4, "value expected"))?;
             let thread = arg1.as_thread_mut().unwrap() as *mut LuaState;
             (a2, a3, a4, thread)
         } else {
             let a2 = l
                 .get_arg(2)
-                .ok_or_else(|| l.error("bad argument #2 to 'setlocal'".to_string()))?;
+                .ok_or_else(|| argerror(l, 2, "value expected"))?;
             let a3 = l
                 .get_arg(3)
-                .ok_or_else(|| l.error("bad argument #3 to 'setlocal'".to_string()))?;
+                .ok_or_else(|| argerror(l, 3, "value expected"))?;
             (arg1, a2, a3, l as *mut LuaState)
         };
 
@@ -1504,19 +1547,19 @@ fn debug_setlocal(l: &mut LuaState) -> LuaResult<usize> {
 
     let level = level_val
         .as_integer()
-        .ok_or_else(|| l.error("bad argument #1 to 'setlocal' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "number expected"))?;
     let local_index = local_val
         .as_integer()
-        .ok_or_else(|| l.error("bad argument #2 to 'setlocal' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 2, "number expected"))?;
 
     if level < 0 {
-        return Err(l.error("bad argument #1 to 'setlocal' (level out of range)".to_string()));
+        return Err(argerror(l, 1, "level out of range"));
     }
     let level = level as usize;
 
     let call_depth = target.call_depth();
     if level >= call_depth {
-        return Err(l.error("bad argument #1 to 'setlocal' (level out of range)".to_string()));
+        return Err(argerror(l, 1, "level out of range"));
     }
 
     let frame_idx = call_depth - 1 - level;
@@ -1636,13 +1679,12 @@ fn debug_getupvalue(l: &mut LuaState) -> LuaResult<usize> {
 
     // Check that first argument is a function
     if !func.is_function() {
-        return Err(l.error("bad argument #1 to 'getupvalue' (function expected)".to_string()));
+        return Err(argerror(l, 1, "function expected"));
     }
 
     let up_index = up_index_val
         .as_integer()
-        .ok_or_else(|| l.error("bad argument #2 to 'getupvalue' (number expected)".to_string()))?
-        as usize;
+        .ok_or_else(|| argerror(l, 2, "number expected"))? as usize;
 
     if let Some(lua_func) = func.as_lua_function() {
         // Get upvalue from Lua function
@@ -1674,7 +1716,7 @@ fn debug_getupvalue(l: &mut LuaState) -> LuaResult<usize> {
         let upvalues = cclosure.upvalues();
         if up_index > 0 && up_index <= upvalues.len() {
             let value = upvalues[up_index - 1];
-            let name_str = l.create_string("")?;
+            let name_str = l.global_state_mut().const_strings.str_empty;
             l.push_value(name_str)?;
             l.push_value(value)?;
             return Ok(2);
@@ -1684,7 +1726,7 @@ fn debug_getupvalue(l: &mut LuaState) -> LuaResult<usize> {
         let upvalues = rclosure.upvalues();
         if up_index > 0 && up_index <= upvalues.len() {
             let value = upvalues[up_index - 1];
-            let name_str = l.create_string("")?;
+            let name_str = l.global_state_mut().const_strings.str_empty;
             l.push_value(name_str)?;
             l.push_value(value)?;
             return Ok(2);
@@ -1709,13 +1751,12 @@ fn debug_setupvalue(l: &mut LuaState) -> LuaResult<usize> {
 
     // Check that first argument is a function
     if !func.is_function() {
-        return Err(l.error("bad argument #1 to 'setupvalue' (function expected)".to_string()));
+        return Err(argerror(l, 1, "function expected"));
     }
 
     let up_index = up_index_val
         .as_integer()
-        .ok_or_else(|| l.error("bad argument #2 to 'setupvalue' (number expected)".to_string()))?
-        as usize;
+        .ok_or_else(|| argerror(l, 2, "number expected"))? as usize;
 
     if let Some(lua_func) = func.as_lua_function() {
         // Set upvalue in Lua function
@@ -1769,13 +1810,12 @@ fn debug_upvalueid(l: &mut LuaState) -> LuaResult<usize> {
 
     // Check that first argument is a function
     if !func.is_function() {
-        return Err(l.error("bad argument #1 to 'upvalueid' (function expected)".to_string()));
+        return Err(argerror(l, 1, "function expected"));
     }
 
     let up_index = up_index_val
         .as_integer()
-        .ok_or_else(|| l.error("bad argument #2 to 'upvalueid' (number expected)".to_string()))?
-        as usize;
+        .ok_or_else(|| argerror(l, 2, "number expected"))? as usize;
 
     if let Some(lua_func) = func.as_lua_function() {
         let upvalues = lua_func.upvalues();
@@ -1826,12 +1866,10 @@ fn debug_upvaluejoin(l: &mut LuaState) -> LuaResult<usize> {
 
     let n1 = n1_val
         .as_integer()
-        .ok_or_else(|| l.error("bad argument #2 to 'upvaluejoin' (number expected)".to_string()))?
-        as usize;
+        .ok_or_else(|| argerror(l, 2, "number expected"))? as usize;
     let n2 = n2_val
         .as_integer()
-        .ok_or_else(|| l.error("bad argument #4 to 'upvaluejoin' (number expected)".to_string()))?
-        as usize;
+        .ok_or_else(|| argerror(l, 4, "number expected"))? as usize;
 
     // Get both Lua functions
     let lua_func1 = func1
@@ -1861,30 +1899,26 @@ fn debug_upvaluejoin(l: &mut LuaState) -> LuaResult<usize> {
 
     let upvalues1_mut = lua_func1_mut.upvalues_mut();
     upvalues1_mut[n1 - 1] = upvalue_to_share;
+    if let Some(owner) = func1.as_gc_ptr() {
+        l.gc_object_barrier(owner, GcObjectPtr::from(upvalue_to_share));
+    }
 
     Ok(0)
 }
 
 /// debug.setuservalue(udata, value [, n]) - Set user value of a userdata
 fn debug_setuservalue(l: &mut LuaState) -> LuaResult<usize> {
-    let udata = l.get_arg(1).ok_or_else(|| {
-        l.error("bad argument #1 to 'setuservalue' (userdata expected)".to_string())
-    })?;
+    let udata = l
+        .get_arg(1)
+        .ok_or_else(|| argerror(l, 1, "userdata expected"))?;
 
     // Must be full userdata (not light userdata)
     if udata.ttislightuserdata() {
-        return Err(l.error(
-            "bad argument #1 to 'setuservalue' (full userdata expected, got light userdata)"
-                .to_string(),
-        ));
+        return Err(argerror(l, 1, "full userdata expected, got light userdata"));
     }
 
     if !udata.is_userdata() {
-        let t = udata.type_name();
-        return Err(l.error(format!(
-            "bad argument #1 to 'setuservalue' (userdata expected, got {})",
-            t
-        )));
+        return Err(arg_typeerror(l, 1, "userdata", &udata));
     }
 
     // For now, setuservalue is a no-op (user values not yet stored in LuaUserdata)
@@ -1894,9 +1928,9 @@ fn debug_setuservalue(l: &mut LuaState) -> LuaResult<usize> {
 
 /// debug.getuservalue(udata [, n]) - Get user value of a userdata
 fn debug_getuservalue(l: &mut LuaState) -> LuaResult<usize> {
-    let udata = l.get_arg(1).ok_or_else(|| {
-        l.error("bad argument #1 to 'getuservalue' (userdata expected)".to_string())
-    })?;
+    let udata = l
+        .get_arg(1)
+        .ok_or_else(|| argerror(l, 1, "userdata expected"))?;
 
     if !udata.is_userdata() || udata.ttislightuserdata() {
         l.push_value(LuaValue::nil())?;
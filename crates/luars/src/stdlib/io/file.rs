@@ -1,12 +1,16 @@
 // File userdata implementation
 // Provides file handles for IO operations
 
-use std::fs::File;
+use super::strerror;
+use crate::lua_value::lua_float_to_string;
+use std::fs::{File, OpenOptions};
 use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
 
+use crate::lua_value::LuaValue;
 use crate::lua_value::userdata_trait::UserDataTrait;
-use crate::lua_value::{LuaValue, LuaValueKind};
-use crate::lua_vm::{LuaResult, LuaState};
+use crate::lua_vm::{LuaResult, LuaState, TmKind};
+use crate::stdlib::basic::parse_number::lua_str2number;
 
 #[cfg(not(target_arch = "wasm32"))]
 use super::popen::{PopenFile, PopenReader, PopenWriter};
@@ -17,76 +21,6 @@ pub enum ReadNumberResult {
     Float(f64),
 }
 
-/// Parse a Lua integer literal (decimal or hex, with wrapping for hex)
-fn parse_lua_integer(s: &str) -> Option<i64> {
-    let s = s.trim();
-    if s.starts_with("0x") || s.starts_with("0X") {
-        let hex = &s[2..];
-        // Parse as u64 to allow wrapping (e.g., 0x8000000000000001 -> negative i64)
-        u64::from_str_radix(hex, 16).ok().map(|n| n as i64)
-    } else if s.starts_with("-0x") || s.starts_with("-0X") {
-        let hex = &s[3..];
-        u64::from_str_radix(hex, 16)
-            .ok()
-            .map(|n| (n as i64).wrapping_neg())
-    } else {
-        s.parse::<i64>().ok()
-    }
-}
-
-/// Parse a Lua float literal (including hex floats like 0xABCp-3)
-fn parse_lua_float(s: &str) -> Option<f64> {
-    let s = s.trim();
-    // Standard float
-    if let Ok(n) = s.parse::<f64>() {
-        return Some(n);
-    }
-    // Hex float: 0xHHH[.HHH][pEXP]
-    let (neg, rest) = if let Some(rest) = s.strip_prefix('-') {
-        (true, rest)
-    } else if let Some(rest) = s.strip_prefix('+') {
-        (false, rest)
-    } else {
-        (false, s)
-    };
-    if rest.starts_with("0x") || rest.starts_with("0X") {
-        let hex = &rest[2..];
-        // Split at 'p' or 'P'
-        let (mantissa_str, exp_str) = if let Some(p) = hex.find(['p', 'P']) {
-            (&hex[..p], Some(&hex[p + 1..]))
-        } else {
-            (hex, None)
-        };
-        // Parse mantissa (integer part . fractional part)
-        let (int_str, frac_str) = if let Some(dot) = mantissa_str.find('.') {
-            (&mantissa_str[..dot], Some(&mantissa_str[dot + 1..]))
-        } else {
-            (mantissa_str, None)
-        };
-        let int_val = u64::from_str_radix(int_str, 16).ok()? as f64;
-        let frac_val = if let Some(frac) = frac_str {
-            if frac.is_empty() {
-                0.0
-            } else {
-                let frac_num = u64::from_str_radix(frac, 16).ok()? as f64;
-                frac_num / 16f64.powi(frac.len() as i32)
-            }
-        } else {
-            0.0
-        };
-        let mut result = int_val + frac_val;
-        if let Some(exp) = exp_str {
-            let exp_val: i32 = exp.parse().ok()?;
-            result *= 2f64.powi(exp_val);
-        }
-        if neg {
-            result = -result;
-        }
-        return Some(result);
-    }
-    None
-}
-
 /// File handle wrapper - supports files and standard streams
 pub struct LuaFile {
     inner: FileInner,
@@ -111,6 +45,34 @@ enum FileInner {
     Closed,
 }
 
+/// What C stdio reports for a write to a stream not opened for writing.
+fn bad_file_descriptor() -> io::Error {
+    #[cfg(unix)]
+    {
+        io::Error::from_raw_os_error(9) // EBADF
+    }
+    #[cfg(not(unix))]
+    {
+        io::Error::other("Bad file descriptor")
+    }
+}
+
+/// `OpenOptions` sharing files the way the C runtime's `fopen` does. On
+/// Windows std also lets other handles delete or rename an open file, which
+/// would make `os.remove` of an open file succeed there.
+fn open_options() -> OpenOptions {
+    #[allow(unused_mut)]
+    let mut options = OpenOptions::new();
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_SHARE_READ: u32 = 0x1;
+        const FILE_SHARE_WRITE: u32 = 0x2;
+        options.share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE);
+    }
+    options
+}
+
 impl LuaFile {
     /// Create stdin handle
     pub fn stdin() -> Self {
@@ -133,45 +95,42 @@ impl LuaFile {
         }
     }
 
-    pub fn open_read(path: &str) -> io::Result<Self> {
-        let file = File::open(path)?;
+    pub fn open_read(path: &Path) -> io::Result<Self> {
+        let file = open_options().read(true).open(path)?;
         Ok(LuaFile {
             inner: FileInner::Read(BufReader::new(file)),
         })
     }
 
-    pub fn open_write(path: &str) -> io::Result<Self> {
-        let file = File::create(path)?;
+    pub fn open_write(path: &Path) -> io::Result<Self> {
+        let file = open_options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
         Ok(LuaFile {
             inner: FileInner::Write(BufWriter::new(file)),
         })
     }
 
-    pub fn open_append(path: &str) -> io::Result<Self> {
-        let file = std::fs::OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(path)?;
+    pub fn open_append(path: &Path) -> io::Result<Self> {
+        let file = open_options().append(true).create(true).open(path)?;
         Ok(LuaFile {
             inner: FileInner::Write(BufWriter::new(file)),
         })
     }
 
-    pub fn open_readwrite(path: &str) -> io::Result<Self> {
-        let file = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(path)?;
+    /// Open an existing file for reading and writing (r+ mode)
+    pub fn open_readwrite(path: &Path) -> io::Result<Self> {
+        let file = open_options().read(true).write(true).open(path)?;
         Ok(LuaFile {
             inner: FileInner::ReadWrite(BufReader::new(file)),
         })
     }
 
     /// Open for reading and appending (a+ mode)
-    pub fn open_append_read(path: &str) -> io::Result<Self> {
-        let file = std::fs::OpenOptions::new()
+    pub fn open_append_read(path: &Path) -> io::Result<Self> {
+        let file = open_options()
             .read(true)
             .append(true)
             .create(true)
@@ -182,8 +141,8 @@ impl LuaFile {
     }
 
     /// Open for reading and writing, truncating (w+ mode)
-    pub fn open_write_read(path: &str) -> io::Result<Self> {
-        let file = std::fs::OpenOptions::new()
+    pub fn open_write_read(path: &Path) -> io::Result<Self> {
+        let file = open_options()
             .read(true)
             .write(true)
             .create(true)
@@ -194,13 +153,6 @@ impl LuaFile {
         })
     }
 
-    /// Create from existing File for write-only use (io.output)
-    pub fn from_file(file: File) -> Self {
-        LuaFile {
-            inner: FileInner::Write(BufWriter::new(file)),
-        }
-    }
-
     /// Create from existing File for read-write use (io.tmpfile)
     pub fn from_file_rw(file: File) -> Self {
         LuaFile {
@@ -548,19 +500,11 @@ impl LuaFile {
             return Ok(None);
         }
 
-        let s = String::from_utf8(buf).unwrap_or_default();
-        let trimmed = s.trim();
-
-        // Try integer first
-        if let Some(n) = parse_lua_integer(trimmed) {
-            return Ok(Some(ReadNumberResult::Integer(n)));
+        match lua_str2number(&buf) {
+            Some(n) if n.is_integer() => Ok(Some(ReadNumberResult::Integer(n.ivalue()))),
+            Some(n) => Ok(Some(ReadNumberResult::Float(n.fltvalue()))),
+            None => Ok(None),
         }
-        // Then float
-        if let Some(n) = parse_lua_float(trimmed) {
-            return Ok(Some(ReadNumberResult::Float(n)));
-        }
-
-        Ok(None)
     }
 
     pub fn read_all(&mut self) -> io::Result<Vec<u8>> {
@@ -667,6 +611,13 @@ impl LuaFile {
                 Ok(())
             }
             FileInner::ReadWrite(reader) => {
+                // Drop read-ahead so the write lands at the logical position
+                // (append mode writes at the end regardless). A real seek is
+                // needed: stream_position() would keep the buffer.
+                if !reader.buffer().is_empty() {
+                    #[allow(clippy::seek_from_current)]
+                    reader.seek(io::SeekFrom::Current(0))?;
+                }
                 reader.get_mut().write_all(data)?;
                 Ok(())
             }
@@ -680,7 +631,7 @@ impl LuaFile {
                 io::stderr().write_all(data)?;
                 Ok(())
             }
-            _ => Err(io::Error::other("File not opened for writing")),
+            _ => Err(bad_file_descriptor()),
         }
     }
 
@@ -772,23 +723,32 @@ pub fn create_file_metatable(l: &mut LuaState) -> LuaResult<LuaValue> {
     let setvbuf_key = l.create_string("setvbuf")?;
     l.raw_set(&index_table, setvbuf_key, LuaValue::cfunction(file_setvbuf));
 
-    let index_key = l.create_string("__index")?;
+    let index_key = l
+        .global_state_mut()
+        .const_strings
+        .get_tm_value(TmKind::Index);
     l.raw_set(&mt, index_key, index_table);
 
     // Set __close = file_gc_close (for <close> variables) - silently handles already-closed files
-    let close_mm_key = l.create_string("__close")?;
+    let close_mm_key = l
+        .global_state_mut()
+        .const_strings
+        .get_tm_value(TmKind::Close);
     l.raw_set(&mt, close_mm_key, LuaValue::cfunction(file_gc_close));
 
     // Set __gc = file_gc_close (for garbage collection) - silently handles already-closed files
-    let gc_key = l.create_string("__gc")?;
+    let gc_key = l.global_state_mut().const_strings.get_tm_value(TmKind::Gc);
     l.raw_set(&mt, gc_key, LuaValue::cfunction(file_gc_close));
 
     // Set __tostring for file objects
-    let tostring_key = l.create_string("__tostring")?;
+    let tostring_key = l
+        .global_state_mut()
+        .const_strings
+        .get_tm_value(TmKind::ToString);
     l.raw_set(&mt, tostring_key, LuaValue::cfunction(file_tostring));
 
     // Set __name = "FILE*" for type identification (luaT_objtypename)
-    let name_key = l.create_string("__name")?;
+    let name_key = l.global_state_mut().const_strings.tm_name;
     let name_val = l.create_string("FILE*")?;
     l.raw_set(&mt, name_key, name_val);
 
@@ -839,36 +799,20 @@ fn file_write(l: &mut LuaState) -> LuaResult<usize> {
             // Write all arguments (starting from arg 2)
             let mut i = 2;
             while let Some(val) = l.get_arg(i) {
-                let write_result = match val.kind() {
-                    LuaValueKind::String => match val.as_bytes() {
-                        Some(bytes) => lua_file.write_bytes(bytes),
-                        None => {
-                            return Err(l.error("write expects strings or numbers".to_string()));
-                        }
-                    },
-                    LuaValueKind::Integer => {
-                        if let Some(n) = val.as_integer() {
-                            lua_file.write(&n.to_string())
-                        } else {
-                            return Err(l.error("write expects strings or numbers".to_string()));
-                        }
-                    }
-                    LuaValueKind::Float => {
-                        if let Some(n) = val.as_float() {
-                            lua_file.write(&n.to_string())
-                        } else {
-                            return Err(l.error("write expects strings or numbers".to_string()));
-                        }
-                    }
-                    _ => {
-                        return Err(l.error("write expects strings or numbers".to_string()));
-                    }
+                let write_result = if let Some(bytes) = val.as_bytes() {
+                    lua_file.write_bytes(bytes)
+                } else if let Some(n) = val.as_integer_strict() {
+                    lua_file.write(&n.to_string())
+                } else if let Some(n) = val.as_float() {
+                    lua_file.write(&lua_float_to_string(n))
+                } else {
+                    return Err(crate::stdlib::debug::arg_typeerror(l, i, "string", &val));
                 };
 
                 if let Err(e) = write_result {
                     // Return (nil, errmsg, errno) like C Lua
                     l.push_value(LuaValue::nil())?;
-                    let msg = l.create_string(&format!("{}", e))?;
+                    let msg = l.create_string(&strerror(&e))?;
                     l.push_value(msg)?;
                     let errno = e.raw_os_error().unwrap_or(0);
                     l.push_value(LuaValue::integer(errno as i64))?;
@@ -1072,7 +1016,10 @@ fn file_lines(l: &mut LuaState) -> LuaResult<usize> {
 
     // Create metatable with __call using the shared io_lines_call
     let mt = l.create_table(0, 1)?;
-    let call_key = l.create_string("__call")?;
+    let call_key = l
+        .global_state_mut()
+        .const_strings
+        .get_tm_value(TmKind::Call);
     l.raw_set(&mt, call_key, LuaValue::cfunction(super::io_lines_call));
     if let Some(t) = state_table.as_table_mut() {
         t.set_metatable(Some(mt));
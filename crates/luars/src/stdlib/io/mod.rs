@@ -4,12 +4,16 @@ mod file;
 mod popen;
 
 use crate::lib_registry::LibraryModule;
+use crate::lua_value::lua_float_to_string;
 use crate::lua_value::{LuaUserdata, LuaValue};
-use crate::lua_vm::{LuaResult, LuaState};
+use crate::lua_vm::{LuaResult, LuaState, TmKind};
 pub use file::{LuaFile, create_file_metatable};
 use std::fs::OpenOptions;
 use std::io::{self, Write};
 
+use crate::platform_path::{errno, path_from_bytes};
+use crate::stdlib::debug::argerror;
+
 pub(crate) enum ReadOneResult {
     Value(LuaValue),
     Fail,
@@ -251,21 +255,22 @@ fn io_write(l: &mut LuaState) -> LuaResult<usize> {
             while let Some(arg) = l.get_arg(i) {
                 let write_result = if let Some(bytes) = arg.as_bytes() {
                     lua_file.write_bytes(bytes)
-                } else if let Some(n) = arg.as_integer() {
+                } else if let Some(n) = arg.as_integer_strict() {
                     lua_file.write(&n.to_string())
                 } else if let Some(n) = arg.as_float() {
-                    lua_file.write(&n.to_string())
+                    lua_file.write(&lua_float_to_string(n))
                 } else {
-                    return Err(crate::stdlib::debug::arg_typeerror(
-                        l,
-                        i,
-                        "string or number",
-                        &arg,
-                    ));
+                    return Err(crate::stdlib::debug::arg_typeerror(l, i, "string", &arg));
                 };
 
                 if let Err(e) = write_result {
-                    return Err(l.error(format!("write error: {}", e)));
+                    // Return (nil, errmsg, errno) like C Lua
+                    l.push_value(LuaValue::nil())?;
+                    let msg = l.create_string(&strerror(&e))?;
+                    l.push_value(msg)?;
+                    let errno = e.raw_os_error().unwrap_or(0);
+                    l.push_value(LuaValue::integer(errno as i64))?;
+                    return Ok(3);
                 }
 
                 i += 1;
@@ -323,7 +328,11 @@ fn read_one_format_file(
             // read(0) returns "" if not EOF, nil if EOF
             match lua_file.is_eof() {
                 Ok(true) => return Ok(ReadOneResult::Fail),
-                Ok(false) => return Ok(ReadOneResult::Value(l.create_string("")?)),
+                Ok(false) => {
+                    return Ok(ReadOneResult::Value(
+                        l.global_state_mut().const_strings.str_empty,
+                    ));
+                }
                 Err(e) => return Ok(ReadOneResult::IoError(e)),
             }
         }
@@ -458,30 +467,30 @@ fn io_flush(l: &mut LuaState) -> LuaResult<usize> {
 fn io_open(l: &mut LuaState) -> LuaResult<usize> {
     let filename_val = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'io.open' (string expected)".to_string()))?;
-    let filename_str = match filename_val.as_str() {
-        Some(s) => s.to_string(),
-        None => return Err(l.error("bad argument #1 to 'io.open' (string expected)".to_string())),
+        .ok_or_else(|| argerror(l, 1, "string expected"))?;
+    let Some(filename) = filename_val.as_bytes() else {
+        return Err(argerror(l, 1, "string expected"));
     };
+    let filename = filename.to_vec();
 
     let mode_str = l
         .get_arg(2)
         .and_then(|v| v.as_str().map(|s| s.to_string()))
         .unwrap_or_else(|| "r".to_string());
 
-    // Strip 'b' suffix (binary mode, no-op on most platforms)
-    let mode = mode_str.trim_end_matches('b');
-
-    let file_result = match mode {
-        "r" => LuaFile::open_read(&filename_str),
-        "w" => LuaFile::open_write(&filename_str),
-        "a" => LuaFile::open_append(&filename_str),
-        "r+" => LuaFile::open_readwrite(&filename_str),
-        "w+" => LuaFile::open_write_read(&filename_str),
-        "a+" => LuaFile::open_append_read(&filename_str),
-        _ => return Err(l.error(format!("invalid mode: {}", mode_str))),
+    let Some((kind, update)) = parse_open_mode(&mode_str) else {
+        return Err(argerror(l, 2, "invalid mode"));
     };
 
+    let file_result = path_from_bytes(&filename).and_then(|path| match (kind, update) {
+        ('r', false) => LuaFile::open_read(&path),
+        ('w', false) => LuaFile::open_write(&path),
+        ('a', false) => LuaFile::open_append(&path),
+        ('r', true) => LuaFile::open_readwrite(&path),
+        ('w', true) => LuaFile::open_write_read(&path),
+        _ => LuaFile::open_append_read(&path),
+    });
+
     match file_result {
         Ok(file) => {
             // Create file metatable
@@ -501,19 +510,49 @@ fn io_open(l: &mut LuaState) -> LuaResult<usize> {
             l.push_value(userdata)?;
             Ok(1)
         }
-        Err(e) => {
-            // Return nil, error message, and errno (matching C Lua)
-            l.push_value(LuaValue::nil())?;
-            let err_msg = format!("{}: {}", filename_str, e);
-            let err_str = l.create_string(&err_msg)?;
-            l.push_value(err_str)?;
-            let errno = e.raw_os_error().unwrap_or(0) as i64;
-            l.push_value(LuaValue::integer(errno))?;
-            Ok(3)
-        }
+        Err(e) => file_result_error(l, &filename, &e),
+    }
+}
+
+/// Parse an fopen mode: one of `r`/`w`/`a`, then `b`s and at most one `+`
+/// in any order, so both "r+b" and "rb+" are accepted as C's fopen does.
+/// Returns the base mode and whether `+` (update) was given.
+fn parse_open_mode(mode: &str) -> Option<(char, bool)> {
+    let mut chars = mode.chars();
+    let kind = chars.next().filter(|c| matches!(c, 'r' | 'w' | 'a'))?;
+    let rest = chars.as_str();
+    let updates = rest.bytes().filter(|&b| b == b'+').count();
+    let valid = updates <= 1 && rest.bytes().all(|b| b == b'+' || b == b'b');
+    valid.then_some((kind, updates == 1))
+}
+
+/// Error text as C's `strerror` spells it: Rust's `io::Error` display
+/// appends " (os error N)", which Lua scripts do not expect.
+pub(crate) fn strerror(e: &io::Error) -> String {
+    let msg = e.to_string();
+    match msg.rfind(" (os error ") {
+        Some(pos) => msg[..pos].to_string(),
+        None => msg,
     }
 }
 
+/// The failure results of C's `luaL_fileresult`: nil, "<filename>: <strerror>"
+/// and errno. The filename keeps its original bytes.
+pub(crate) fn file_result_error(
+    l: &mut LuaState,
+    filename: &[u8],
+    e: &io::Error,
+) -> LuaResult<usize> {
+    let mut msg = filename.to_vec();
+    msg.extend_from_slice(b": ");
+    msg.extend_from_slice(strerror(e).as_bytes());
+    let msg = l.create_bytes(&msg)?;
+    l.push_value(LuaValue::nil())?;
+    l.push_value(msg)?;
+    l.push_value(LuaValue::integer(errno(e)))?;
+    Ok(3)
+}
+
 const MAXARGLINE: usize = 250;
 
 /// io.lines([filename]) - Return iterator for lines
@@ -538,12 +577,12 @@ fn io_lines(l: &mut LuaState) -> LuaResult<usize> {
     if has_filename {
         let filename_val = filename.unwrap();
         // io.lines(filename, ...) - open file and return iterator
-        let filename_str = match filename_val.as_str() {
-            Some(s) => s.to_string(),
-            None => return Err(l.error("bad argument #1 to 'lines' (string expected)".to_string())),
+        let Some(filename) = filename_val.as_bytes() else {
+            return Err(argerror(l, 1, "string expected"));
         };
+        let filename = filename.to_vec();
 
-        match LuaFile::open_read(&filename_str) {
+        match path_from_bytes(&filename).and_then(|path| LuaFile::open_read(&path)) {
             Ok(file) => {
                 let file_mt = create_file_metatable(l)?;
                 let userdata = l.create_userdata(LuaUserdata::new(file))?;
@@ -574,7 +613,10 @@ fn io_lines(l: &mut LuaState) -> LuaResult<usize> {
 
                 // Also set __call so the table is directly callable (for load() etc.)
                 let mt = l.create_table(0, 1)?;
-                let call_key = l.create_string("__call")?;
+                let call_key = l
+                    .global_state_mut()
+                    .const_strings
+                    .get_tm_value(TmKind::Call);
                 l.raw_set(&mt, call_key, LuaValue::cfunction(io_lines_call));
                 if let Some(t) = state_table.as_table_mut() {
                     t.set_metatable(Some(mt));
@@ -592,7 +634,11 @@ fn io_lines(l: &mut LuaState) -> LuaResult<usize> {
                 l.push_value(userdata)?; // to-be-closed file handle
                 Ok(4)
             }
-            Err(e) => Err(l.error(format!("cannot open file '{}': {}", filename_str, e))),
+            Err(e) => Err(l.error(format!(
+                "cannot open file '{}' ({})",
+                String::from_utf8_lossy(&filename),
+                strerror(&e)
+            ))),
         }
     } else {
         // io.lines() or io.lines(nil, ...) - read from default input
@@ -629,7 +675,10 @@ fn io_lines(l: &mut LuaState) -> LuaResult<usize> {
         );
 
         let mt = l.create_table(0, 1)?;
-        let call_key = l.create_string("__call")?;
+        let call_key = l
+            .global_state_mut()
+            .const_strings
+            .get_tm_value(TmKind::Call);
         l.raw_set(&mt, call_key, LuaValue::cfunction(io_lines_call));
         if let Some(t) = state_table.as_table_mut() {
             t.set_metatable(Some(mt));
@@ -793,8 +842,8 @@ fn io_lines_close_on_eof(
     if !no_close {
         let _ = lua_file.close();
     }
-    if let Some(t) = state_val.as_table_mut() {
-        t.raw_set(closed_key, LuaValue::boolean(true));
+    if state_val.is_table() {
+        l.raw_set(state_val, *closed_key, LuaValue::boolean(true));
     }
     l.push_value(LuaValue::nil())?;
     Ok(1)
@@ -807,12 +856,16 @@ fn io_input(l: &mut LuaState) -> LuaResult<usize> {
 
     if let Some(arg_val) = arg {
         // Set new input file
-        if let Some(filename) = arg_val.as_str() {
+        if let Some(filename) = arg_val.as_bytes() {
             // Open file for reading
-            let lua_file = match LuaFile::open_read(filename) {
+            let lua_file = match path_from_bytes(filename).and_then(|p| LuaFile::open_read(&p)) {
                 Ok(f) => f,
                 Err(e) => {
-                    return Err(l.error(format!("cannot open file '{}': {}", filename, e)));
+                    return Err(l.error(format!(
+                        "cannot open file '{}' ({})",
+                        String::from_utf8_lossy(filename),
+                        strerror(&e)
+                    )));
                 }
             };
             let file_mt = create_file_metatable(l)?;
@@ -862,23 +915,28 @@ fn io_output(l: &mut LuaState) -> LuaResult<usize> {
 
     if let Some(arg_val) = arg {
         // Set new output file
-        if let Some(filename) = arg_val.as_str() {
+        if let Some(filename) = arg_val.as_bytes() {
+            let path = path_from_bytes(filename);
             // Create parent directories if they don't exist
-            if let Some(parent) = std::path::Path::new(filename).parent()
+            if let Ok(path) = &path
+                && let Some(parent) = path.parent()
                 && !parent.as_os_str().is_empty()
             {
                 let _ = std::fs::create_dir_all(parent);
             }
 
             // Open file for writing
-            let file = match std::fs::File::create(filename) {
+            let lua_file = match path.and_then(|path| LuaFile::open_write(&path)) {
                 Ok(f) => f,
                 Err(e) => {
-                    return Err(l.error(format!("cannot open file '{}': {}", filename, e)));
+                    return Err(l.error(format!(
+                        "cannot open file '{}' ({})",
+                        String::from_utf8_lossy(filename),
+                        strerror(&e)
+                    )));
                 }
             };
 
-            let lua_file = LuaFile::from_file(file);
             let file_mt = create_file_metatable(l)?;
             let userdata = l.create_userdata(LuaUserdata::new(lua_file))?;
 
@@ -900,7 +958,7 @@ fn io_output(l: &mut LuaState) -> LuaResult<usize> {
                     return Err(l.error("attempt to use an expired file handle".to_string()));
                 };
                 if data.downcast_ref::<LuaFile>().is_none() {
-                    return Err(l.error("bad argument #1 to 'output' (file expected)".to_string()));
+                    return Err(argerror(l, 1, "file expected"));
                 }
             }
 
@@ -910,9 +968,7 @@ fn io_output(l: &mut LuaState) -> LuaResult<usize> {
             l.raw_set(&registry, key, arg_val);
             l.global_state_mut().io_default_output = Some(arg_val);
         } else {
-            return Err(
-                l.error("bad argument #1 to 'output' (string or file expected)".to_string())
-            );
+            return Err(argerror(l, 1, "string or file expected"));
         }
     }
 
@@ -1078,14 +1134,14 @@ fn io_popen(l: &mut LuaState) -> LuaResult<usize> {
     let command = l
         .get_arg(1)
         .and_then(|v| v.as_str().map(|s| s.to_string()))
-        .ok_or_else(|| l.error("bad argument #1 to 'popen' (string expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "string expected"))?;
     let mode = l
         .get_arg(2)
         .and_then(|v| v.as_str().map(|s| s.to_string()))
         .unwrap_or_else(|| "r".to_string());
 
     if !popen::validate_popen_mode(&mode) {
-        return Err(l.error("bad argument #2 to 'popen' (invalid mode)".to_string()));
+        return Err(argerror(l, 2, "invalid mode"));
     }
 
     match LuaFile::popen(&command, &mode) {
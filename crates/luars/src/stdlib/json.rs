@@ -0,0 +1,583 @@
+// JSON library (optional, `json` feature)
+// Implements: encode, decode, null
+//
+// A cjson-compatible subset. `json.null` is a NULL light userdata, like
+// cjson's, standing for JSON null where a Lua nil can't (inside arrays).
+
+use crate::lib_registry::LibraryModule;
+use crate::lua_value::{LuaValue, lua_float_to_string};
+use crate::lua_vm::lua_limits::JSON_MAX_DEPTH;
+use crate::lua_vm::{LuaResult, LuaState};
+use crate::stdlib::debug::argerror;
+
+/// Nesting limit for both directions. `opts.max_depth` can only lower it.
+const MAX_DEPTH: usize = JSON_MAX_DEPTH;
+
+pub fn create_json_lib() -> LibraryModule {
+    let mut module = crate::lib_module!("json", {
+        "encode" => json_encode,
+        "decode" => json_decode,
+    });
+    module = module.with_value("null", |_| Ok(json_null()));
+    module
+}
+
+#[inline]
+fn json_null() -> LuaValue {
+    LuaValue::lightuserdata(std::ptr::null_mut())
+}
+
+#[inline]
+fn is_json_null(value: &LuaValue) -> bool {
+    value.as_lightuserdata().is_some_and(|p| p.is_null())
+}
+
+/// Read `opts[name]` from an optional options table argument.
+fn opt_field(l: &mut LuaState, opts: Option<LuaValue>, name: &str) -> LuaResult<LuaValue> {
+    let Some(opts) = opts.filter(|o| !o.is_nil()) else {
+        return Ok(LuaValue::nil());
+    };
+    let key = l.create_string(name)?;
+    Ok(opts
+        .as_table()
+        .and_then(|t| t.raw_get(&key))
+        .unwrap_or_default())
+}
+
+fn check_opts(l: &mut LuaState, narg: usize) -> LuaResult<Option<LuaValue>> {
+    let opts = l.get_arg(narg);
+    if let Some(o) = opts
+        && !o.is_nil()
+        && !o.is_table()
+    {
+        return Err(argerror(l, narg, "table expected"));
+    }
+    Ok(opts)
+}
+
+fn max_depth_opt(l: &mut LuaState, opts: Option<LuaValue>) -> LuaResult<usize> {
+    let value = opt_field(l, opts, "max_depth")?;
+    if value.is_nil() {
+        return Ok(MAX_DEPTH);
+    }
+    match value.as_integer() {
+        Some(n) if n >= 1 => Ok((n as usize).min(MAX_DEPTH)),
+        _ => Err(l.error("json: 'max_depth' must be a positive integer".to_string())),
+    }
+}
+
+// ===== encode =====
+
+struct Encoder {
+    out: Vec<u8>,
+    empty_as_object: bool,
+    max_depth: usize,
+}
+
+/// json.encode(value [, opts]) -> string
+fn json_encode(l: &mut LuaState) -> LuaResult<usize> {
+    if l.arg_count() == 0 {
+        return Err(argerror(l, 1, "value expected"));
+    }
+    let value = l.get_arg(1).unwrap_or_default();
+    let opts = check_opts(l, 2)?;
+    let empty_as_object = opt_field(l, opts, "empty_as_object")?.is_truthy();
+    let max_depth = max_depth_opt(l, opts)?;
+
+    let mut encoder = Encoder {
+        out: Vec::new(),
+        empty_as_object,
+        max_depth,
+    };
+    if let Err(msg) = encoder.value(&value, 0) {
+        return Err(l.error(format!("json.encode: {msg}")));
+    }
+    let result = l.create_bytes(&encoder.out)?;
+    l.push_value(result)?;
+    Ok(1)
+}
+
+impl Encoder {
+    fn value(&mut self, value: &LuaValue, depth: usize) -> Result<(), String> {
+        if value.is_table() {
+            self.table(value, depth + 1)
+        } else {
+            self.scalar(value)
+        }
+    }
+
+    // Kept out of line so the recursive path through `value`/`table` has
+    // small frames.
+    #[inline(never)]
+    fn scalar(&mut self, value: &LuaValue) -> Result<(), String> {
+        if value.is_nil() || is_json_null(value) {
+            self.out.extend_from_slice(b"null");
+        } else if let Some(b) = value.as_boolean() {
+            self.out
+                .extend_from_slice(if b { b"true" } else { b"false" });
+        } else if value.is_integer() {
+            let mut buf = itoa::Buffer::new();
+            self.out.extend_from_slice(
+                buf.format(value.as_integer().unwrap_or_default())
+                    .as_bytes(),
+            );
+        } else if value.is_float() {
+            let n = value.as_float().unwrap_or_default();
+            if !n.is_finite() {
+                return Err(format!(
+                    "cannot encode non-finite number {}",
+                    lua_float_to_string(n)
+                ));
+            }
+            // Shortest round-trip form; keeps a ".0" so it decodes as a float.
+            self.out.extend_from_slice(format!("{n:?}").as_bytes());
+        } else if let Some(bytes) = value.as_bytes() {
+            self.string(bytes);
+        } else {
+            return Err(format!(
+                "cannot encode value of type '{}'",
+                value.type_name()
+            ));
+        }
+        Ok(())
+    }
+
+    #[inline(never)]
+    fn key(&mut self, key: &LuaValue) -> Result<(), String> {
+        if let Some(bytes) = key.as_bytes() {
+            self.string(bytes);
+        } else if key.is_integer() {
+            let mut buf = itoa::Buffer::new();
+            let text = buf.format(key.as_integer().unwrap_or_default()).to_string();
+            self.string(text.as_bytes());
+        } else if key.is_float() {
+            let text = lua_float_to_string(key.as_float().unwrap_or_default());
+            self.string(text.as_bytes());
+        } else {
+            return Err(format!(
+                "table key must be a string or number, got '{}'",
+                key.type_name()
+            ));
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, bytes: &[u8]) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        self.out.push(b'"');
+        for &b in bytes {
+            match b {
+                b'"' => self.out.extend_from_slice(b"\\\""),
+                b'\\' => self.out.extend_from_slice(b"\\\\"),
+                b'\n' => self.out.extend_from_slice(b"\\n"),
+                b'\r' => self.out.extend_from_slice(b"\\r"),
+                b'\t' => self.out.extend_from_slice(b"\\t"),
+                0x08 => self.out.extend_from_slice(b"\\b"),
+                0x0c => self.out.extend_from_slice(b"\\f"),
+                0..0x20 | 0x7f => {
+                    self.out.extend_from_slice(b"\\u00");
+                    self.out.push(HEX[(b >> 4) as usize]);
+                    self.out.push(HEX[(b & 0xf) as usize]);
+                }
+                _ => self.out.push(b),
+            }
+        }
+        self.out.push(b'"');
+    }
+
+    fn table(&mut self, value: &LuaValue, depth: usize) -> Result<(), String> {
+        if depth > self.max_depth {
+            return Err(nesting_error(self.max_depth));
+        }
+        let table = value.as_table().expect("checked table");
+        let entries = table.iter_all();
+
+        // An array has exactly the keys 1..n.
+        let mut max_index = 0i64;
+        let mut is_array = true;
+        for (k, _) in &entries {
+            match k.as_integer_strict() {
+                Some(i) if i >= 1 => max_index = max_index.max(i),
+                _ => {
+                    is_array = false;
+                    break;
+                }
+            }
+        }
+        if is_array && max_index as usize != entries.len() {
+            is_array = false;
+        }
+
+        if entries.is_empty() {
+            self.out
+                .extend_from_slice(if self.empty_as_object { b"{}" } else { b"[]" });
+            return Ok(());
+        }
+
+        if is_array {
+            self.out.push(b'[');
+            for i in 1..=max_index {
+                if i > 1 {
+                    self.out.push(b',');
+                }
+                let item = table.raw_geti(i).unwrap_or_default();
+                self.value(&item, depth)?;
+            }
+            self.out.push(b']');
+            return Ok(());
+        }
+
+        self.out.push(b'{');
+        for (i, (k, v)) in entries.iter().enumerate() {
+            if i > 0 {
+                self.out.push(b',');
+            }
+            self.key(k)?;
+            self.out.push(b':');
+            self.value(v, depth)?;
+        }
+        self.out.push(b'}');
+        Ok(())
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn nesting_error(max_depth: usize) -> String {
+    format!("excessive nesting (more than {max_depth} levels)")
+}
+
+// ===== decode =====
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    pos: usize,
+    null_as_nil: bool,
+    max_depth: usize,
+}
+
+/// json.decode(s [, opts]) -> value
+fn json_decode(l: &mut LuaState) -> LuaResult<usize> {
+    let Some(s) = l.get_arg(1).filter(|v| v.is_string()) else {
+        return Err(argerror(l, 1, "string expected"));
+    };
+    let opts = check_opts(l, 2)?;
+    let null_as_nil = opt_field(l, opts, "null_as_nil")?.is_truthy();
+    let max_depth = max_depth_opt(l, opts)?;
+
+    // The argument stays on the stack, so its bytes live for the call.
+    let input = s.as_bytes().unwrap_or_default();
+    let mut decoder = Decoder {
+        input,
+        pos: 0,
+        null_as_nil,
+        max_depth,
+    };
+    let value = decoder.document(l)?;
+    l.push_value(value)?;
+    Ok(1)
+}
+
+impl Decoder<'_> {
+    fn document(&mut self, l: &mut LuaState) -> LuaResult<LuaValue> {
+        self.skip_ws();
+        let value = self.value(l, 0)?;
+        self.skip_ws();
+        if self.pos < self.input.len() {
+            return Err(self.error(l, "unexpected trailing character"));
+        }
+        Ok(value)
+    }
+
+    fn error(&self, l: &mut LuaState, what: &str) -> crate::lua_vm::LuaError {
+        let consumed = &self.input[..self.pos.min(self.input.len())];
+        let line = consumed.iter().filter(|&&b| b == b'\n').count() + 1;
+        let col = self.pos
+            - consumed
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |p| p + 1)
+            + 1;
+        let msg = match self.input.get(self.pos) {
+            Some(&c) if c.is_ascii_graphic() => {
+                format!(
+                    "json.decode: {what} '{}' at line {line} col {col}",
+                    c as char
+                )
+            }
+            Some(&c) => format!("json.decode: {what} '\\{c}' at line {line} col {col}"),
+            None => format!("json.decode: unexpected end of input at line {line} col {col}"),
+        };
+        l.error(msg)
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn value(&mut self, l: &mut LuaState, depth: usize) -> LuaResult<LuaValue> {
+        match self.peek() {
+            Some(b'{') => self.object(l, depth + 1),
+            Some(b'[') => self.array(l, depth + 1),
+            Some(b'"') => {
+                let bytes = self.string(l)?;
+                l.create_bytes(&bytes)
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(l),
+            Some(b't') => self.literal(l, b"true", LuaValue::boolean(true)),
+            Some(b'f') => self.literal(l, b"false", LuaValue::boolean(false)),
+            Some(b'n') => {
+                let null = if self.null_as_nil {
+                    LuaValue::nil()
+                } else {
+                    json_null()
+                };
+                self.literal(l, b"null", null)
+            }
+            _ => Err(self.error(l, "unexpected character")),
+        }
+    }
+
+    fn literal(&mut self, l: &mut LuaState, word: &[u8], value: LuaValue) -> LuaResult<LuaValue> {
+        for &expected in word {
+            if self.peek() != Some(expected) {
+                return Err(self.error(l, "unexpected character"));
+            }
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn enter(&self, l: &mut LuaState, depth: usize) -> LuaResult<()> {
+        if depth > self.max_depth {
+            return Err(l.error(format!(
+                "json.decode: excessive nesting (more than {} levels)",
+                self.max_depth
+            )));
+        }
+        Ok(())
+    }
+
+    /// Run `f` with `value` kept on the stack. Tables and keys being decoded
+    /// are otherwise only reachable from Rust until they are stored, and a
+    /// collection may run while their contents are created.
+    fn anchored<T>(
+        &mut self,
+        l: &mut LuaState,
+        value: LuaValue,
+        f: impl FnOnce(&mut Self, &mut LuaState) -> LuaResult<T>,
+    ) -> LuaResult<T> {
+        let top = l.get_top();
+        l.push_value(value)?;
+        let result = f(self, l);
+        l.set_top_raw(top);
+        result
+    }
+
+    fn array(&mut self, l: &mut LuaState, depth: usize) -> LuaResult<LuaValue> {
+        self.enter(l, depth)?;
+        self.pos += 1; // '['
+        let table = l.create_table(0, 0)?;
+        self.anchored(l, table, |this, l| this.array_items(l, &table, depth))?;
+        Ok(table)
+    }
+
+    fn array_items(&mut self, l: &mut LuaState, table: &LuaValue, depth: usize) -> LuaResult<()> {
+        self.skip_ws();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(());
+        }
+        let mut index = 1;
+        loop {
+            self.skip_ws();
+            let item = self.value(l, depth)?;
+            if !item.is_nil() {
+                l.raw_seti(table, index, item);
+            }
+            index += 1;
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(self.error(l, "expected ',' or ']' but found")),
+            }
+        }
+    }
+
+    fn object(&mut self, l: &mut LuaState, depth: usize) -> LuaResult<LuaValue> {
+        self.enter(l, depth)?;
+        self.pos += 1; // '{'
+        let table = l.create_table(0, 0)?;
+        self.anchored(l, table, |this, l| this.object_items(l, &table, depth))?;
+        Ok(table)
+    }
+
+    fn object_items(&mut self, l: &mut LuaState, table: &LuaValue, depth: usize) -> LuaResult<()> {
+        self.skip_ws();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            self.skip_ws();
+            if self.peek() != Some(b'"') {
+                return Err(self.error(l, "expected string key but found"));
+            }
+            let key_bytes = self.string(l)?;
+            self.skip_ws();
+            if self.peek() != Some(b':') {
+                return Err(self.error(l, "expected ':' but found"));
+            }
+            self.pos += 1;
+            self.skip_ws();
+            let key = l.create_bytes(&key_bytes)?;
+            let item = self.anchored(l, key, |this, l| this.value(l, depth))?;
+            if !item.is_nil() {
+                l.raw_set(table, key, item);
+            }
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return Err(self.error(l, "expected ',' or '}' but found")),
+            }
+        }
+    }
+
+    fn string(&mut self, l: &mut LuaState) -> LuaResult<Vec<u8>> {
+        self.pos += 1; // opening quote
+        let mut out = Vec::new();
+        loop {
+            let start = self.pos;
+            while let Some(&b) = self.input.get(self.pos)
+                && b != b'"'
+                && b != b'\\'
+                && b >= 0x20
+            {
+                self.pos += 1;
+            }
+            out.extend_from_slice(&self.input[start..self.pos]);
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    self.escape(l, &mut out)?;
+                }
+                Some(_) => return Err(self.error(l, "control character in string")),
+                None => return Err(self.error(l, "unterminated string")),
+            }
+        }
+    }
+
+    fn escape(&mut self, l: &mut LuaState, out: &mut Vec<u8>) -> LuaResult<()> {
+        let c = match self.peek() {
+            Some(b'"') => b'"',
+            Some(b'\\') => b'\\',
+            Some(b'/') => b'/',
+            Some(b'b') => 0x08,
+            Some(b'f') => 0x0c,
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b't') => b'\t',
+            Some(b'u') => {
+                self.pos += 1;
+                let mut cp = self.hex4(l)?;
+                if (0xD800..0xDC00).contains(&cp) {
+                    // High surrogate: must be followed by \uDC00-\uDFFF.
+                    if self.input.get(self.pos..self.pos + 2) != Some(b"\\u") {
+                        return Err(self.error(l, "invalid unicode escape"));
+                    }
+                    self.pos += 2;
+                    let low = self.hex4(l)?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error(l, "invalid unicode escape"));
+                    }
+                    cp = 0x10000 + ((cp - 0xD800) << 10) + (low - 0xDC00);
+                } else if (0xDC00..0xE000).contains(&cp) {
+                    return Err(self.error(l, "invalid unicode escape"));
+                }
+                let ch = char::from_u32(cp).expect("valid scalar value");
+                let mut buf = [0u8; 4];
+                out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                return Ok(());
+            }
+            _ => return Err(self.error(l, "invalid escape")),
+        };
+        self.pos += 1;
+        out.push(c);
+        Ok(())
+    }
+
+    fn hex4(&mut self, l: &mut LuaState) -> LuaResult<u32> {
+        let mut cp = 0;
+        for _ in 0..4 {
+            let digit = self.peek().and_then(|b| (b as char).to_digit(16));
+            let Some(digit) = digit else {
+                return Err(self.error(l, "invalid unicode escape"));
+            };
+            cp = cp * 16 + digit;
+            self.pos += 1;
+        }
+        Ok(cp)
+    }
+
+    fn number(&mut self, l: &mut LuaState) -> LuaResult<LuaValue> {
+        let start = self.pos;
+        let mut is_float = false;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.digits(),
+            _ => return Err(self.error(l, "invalid number near")),
+        }
+        if self.peek() == Some(b'.') {
+            is_float = true;
+            self.pos += 1;
+            if !self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                return Err(self.error(l, "invalid number near"));
+            }
+            self.digits();
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            is_float = true;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                return Err(self.error(l, "invalid number near"));
+            }
+            self.digits();
+        }
+        // The scanned range is ASCII by construction.
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap_or_default();
+        if !is_float && let Ok(i) = text.parse::<i64>() {
+            return Ok(LuaValue::integer(i));
+        }
+        Ok(LuaValue::float(text.parse::<f64>().unwrap_or_default()))
+    }
+
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+    }
+}
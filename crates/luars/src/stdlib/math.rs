@@ -9,21 +9,19 @@ use crate::lua_vm::LuaResult;
 use crate::lua_vm::LuaState;
 use crate::lua_vm::{LuaError, LuaRng};
 use crate::platform_time;
+use crate::stdlib::basic::parse_number::parse_lua_number;
+use crate::stdlib::debug::{arg_typeerror, argerror};
 
 /// Check that argument at position `n` is a number, with proper error message.
 /// SAFETY: push_c_frame guarantees EXTRA_STACK (5) slots above frame_top,
 /// so arg slot `n` (1-based) is always readable.
 #[inline(always)]
-fn checknumber(l: &mut LuaState, n: usize, fname: &str) -> Result<f64, LuaError> {
+fn checknumber(l: &mut LuaState, n: usize) -> Result<f64, LuaError> {
     let v = l.get_arg(n).unwrap_or_default();
     if let Some(f) = v.as_number() {
         return Ok(f);
     }
-    let t = crate::stdlib::debug::objtypename(l, &v);
-    Err(l.error(format!(
-        "bad argument #{} to '{}' (number expected, got {})",
-        n, fname, t
-    )))
+    Err(arg_typeerror(l, n, "number", &v))
 }
 
 pub fn create_math_lib() -> LibraryModule {
@@ -67,8 +65,9 @@ pub fn create_math_lib() -> LibraryModule {
 fn math_abs(l: &mut LuaState) -> LuaResult<usize> {
     let value = l.get_arg(1).unwrap_or_default();
 
-    // Fast path: preserve integer type
-    if let Some(i) = value.as_integer() {
+    // Integers stay integers (abs(mininteger) wraps to itself); integral
+    // floats such as -3.0 or -0.0 must stay floats
+    if let Some(i) = value.as_integer_strict() {
         l.push_value(LuaValue::integer(i.wrapping_abs()))?;
         return Ok(1);
     }
@@ -78,17 +77,17 @@ fn math_abs(l: &mut LuaState) -> LuaResult<usize> {
         return Ok(1);
     }
 
-    Err(l.error("bad argument #1 to 'abs' (number expected)".to_string()))
+    Err(argerror(l, 1, "number expected"))
 }
 
 fn math_acos(l: &mut LuaState) -> LuaResult<usize> {
-    let x = checknumber(l, 1, "acos")?;
+    let x = checknumber(l, 1)?;
     l.push_value(LuaValue::float(x.acos()))?;
     Ok(1)
 }
 
 fn math_asin(l: &mut LuaState) -> LuaResult<usize> {
-    let x = checknumber(l, 1, "asin")?;
+    let x = checknumber(l, 1)?;
     l.push_value(LuaValue::float(x.asin()))?;
     Ok(1)
 }
@@ -96,9 +95,9 @@ fn math_asin(l: &mut LuaState) -> LuaResult<usize> {
 fn math_atan(l: &mut LuaState) -> LuaResult<usize> {
     let y = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'atan' (number expected)".to_string()))?
+        .ok_or_else(|| argerror(l, 1, "number expected"))?
         .as_number()
-        .ok_or_else(|| l.error("bad argument #1 to 'atan' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "number expected"))?;
     let x = l.get_arg(2).and_then(|v| v.as_number()).unwrap_or(1.0);
     l.push_value(LuaValue::float(y.atan2(x)))?;
     Ok(1)
@@ -124,23 +123,23 @@ fn math_ceil(l: &mut LuaState) -> LuaResult<usize> {
         return Ok(1);
     }
 
-    Err(l.error("bad argument #1 to 'ceil' (number expected)".to_string()))
+    Err(argerror(l, 1, "number expected"))
 }
 
 fn math_cos(l: &mut LuaState) -> LuaResult<usize> {
-    let x = checknumber(l, 1, "cos")?;
+    let x = checknumber(l, 1)?;
     l.push_value(LuaValue::float(x.cos()))?;
     Ok(1)
 }
 
 fn math_deg(l: &mut LuaState) -> LuaResult<usize> {
-    let x = checknumber(l, 1, "deg")?;
+    let x = checknumber(l, 1)?;
     l.push_value(LuaValue::float(x.to_degrees()))?;
     Ok(1)
 }
 
 fn math_exp(l: &mut LuaState) -> LuaResult<usize> {
-    let x = checknumber(l, 1, "exp")?;
+    let x = checknumber(l, 1)?;
     l.push_value(LuaValue::float(x.exp()))?;
     Ok(1)
 }
@@ -165,23 +164,23 @@ fn math_floor(l: &mut LuaState) -> LuaResult<usize> {
         return Ok(1);
     }
 
-    Err(l.error("bad argument #1 to 'floor' (number expected)".to_string()))
+    Err(argerror(l, 1, "number expected"))
 }
 
 fn math_fmod(l: &mut LuaState) -> LuaResult<usize> {
     let arg1 = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'fmod' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "number expected"))?;
     let arg2 = l
         .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'fmod' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 2, "number expected"))?;
 
     // If both arguments are raw integers (not float-that-happens-to-be-integral), do integer fmod
     if arg1.is_integer() && arg2.is_integer() {
         let a = arg1.as_integer_strict().unwrap();
         let b = arg2.as_integer_strict().unwrap();
         if b == 0 {
-            return Err(l.error("bad argument #2 to 'fmod' (zero)".to_string()));
+            return Err(argerror(l, 2, "zero"));
         }
         // C Lua uses luaV_mod for integers: a % b with sign adjustment
         // But math.fmod for integers just uses C's fmod semantics (truncated division remainder)
@@ -195,26 +194,29 @@ fn math_fmod(l: &mut LuaState) -> LuaResult<usize> {
 
     let x = arg1
         .as_number()
-        .ok_or_else(|| l.error("bad argument #1 to 'fmod' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "number expected"))?;
     let y = arg2
         .as_number()
-        .ok_or_else(|| l.error("bad argument #2 to 'fmod' (number expected)".to_string()))?;
-    if y == 0.0 {
-        return Err(l.error("bad argument #2 to 'fmod' (zero)".to_string()));
-    }
+        .ok_or_else(|| argerror(l, 2, "number expected"))?;
+    // Float remainder follows C fmod: a zero divisor gives nan, not an error
     l.push_value(LuaValue::float(x % y))?;
     Ok(1)
 }
 
 fn math_log(l: &mut LuaState) -> LuaResult<usize> {
-    let x = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'log' (number expected)".to_string()))?
-        .as_number()
-        .ok_or_else(|| l.error("bad argument #1 to 'log' (number expected)".to_string()))?;
-    let base = l.get_arg(2).and_then(|v| v.as_number());
+    let x = checknumber(l, 1)?;
 
-    let result = if let Some(b) = base { x.log(b) } else { x.ln() };
+    let result = if l.get_arg(2).is_none_or(|v| v.is_nil()) {
+        x.ln()
+    } else {
+        // Bases 2 and 10 use the dedicated functions, which are exact on
+        // powers of the base
+        match checknumber(l, 2)? {
+            2.0 => x.log2(),
+            10.0 => x.log10(),
+            base => x.ln() / base.ln(),
+        }
+    };
 
     l.push_value(LuaValue::float(result))?;
     Ok(1)
@@ -294,10 +296,10 @@ fn math_max(l: &mut LuaState) -> LuaResult<usize> {
         let a = l.get_arg(1).unwrap_or_default();
         let b = l.get_arg(2).unwrap_or_default();
         if a.as_number().is_none() {
-            return Err(l.error("bad argument #1 to 'max' (number expected)".to_string()));
+            return Err(argerror(l, 1, "number expected"));
         }
         if b.as_number().is_none() {
-            return Err(l.error("bad argument #2 to 'max' (number expected)".to_string()));
+            return Err(argerror(l, 2, "number expected"));
         }
         let result = if lua_num_lt(&a, &b) { b } else { a };
         l.push_value(result)?;
@@ -308,16 +310,16 @@ fn math_max(l: &mut LuaState) -> LuaResult<usize> {
     let first = l.get_arg(1).unwrap_or_default();
     let _ = first
         .as_number()
-        .ok_or_else(|| l.error("bad argument #1 to 'max' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "number expected"))?;
     let mut max_arg = first;
 
     for i in 2..=argc {
         let arg = l
             .get_arg(i)
-            .ok_or_else(|| l.error(format!("bad argument #{} to 'max' (number expected)", i)))?;
+            .ok_or_else(|| argerror(l, i, "number expected"))?;
         let _ = arg
             .as_number()
-            .ok_or_else(|| l.error(format!("bad argument #{} to 'max' (number expected)", i)))?;
+            .ok_or_else(|| argerror(l, i, "number expected"))?;
         if lua_num_lt(&max_arg, &arg) {
             max_arg = arg;
         }
@@ -339,10 +341,10 @@ fn math_min(l: &mut LuaState) -> LuaResult<usize> {
         let a = l.get_arg(1).unwrap_or_default();
         let b = l.get_arg(2).unwrap_or_default();
         if a.as_number().is_none() {
-            return Err(l.error("bad argument #1 to 'min' (number expected)".to_string()));
+            return Err(argerror(l, 1, "number expected"));
         }
         if b.as_number().is_none() {
-            return Err(l.error("bad argument #2 to 'min' (number expected)".to_string()));
+            return Err(argerror(l, 2, "number expected"));
         }
         let result = if lua_num_lt(&b, &a) { b } else { a };
         l.push_value(result)?;
@@ -353,16 +355,16 @@ fn math_min(l: &mut LuaState) -> LuaResult<usize> {
     let first = l.get_arg(1).unwrap_or_default();
     let _ = first
         .as_number()
-        .ok_or_else(|| l.error("bad argument #1 to 'min' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "number expected"))?;
     let mut min_arg = first;
 
     for i in 2..=argc {
         let arg = l
             .get_arg(i)
-            .ok_or_else(|| l.error(format!("bad argument #{} to 'min' (number expected)", i)))?;
+            .ok_or_else(|| argerror(l, i, "number expected"))?;
         let _ = arg
             .as_number()
-            .ok_or_else(|| l.error(format!("bad argument #{} to 'min' (number expected)", i)))?;
+            .ok_or_else(|| argerror(l, i, "number expected"))?;
         if lua_num_lt(&arg, &min_arg) {
             min_arg = arg;
         }
@@ -373,30 +375,25 @@ fn math_min(l: &mut LuaState) -> LuaResult<usize> {
 }
 
 fn math_modf(l: &mut LuaState) -> LuaResult<usize> {
-    let x = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'modf' (number expected)".to_string()))?
-        .as_number()
-        .ok_or_else(|| l.error("bad argument #1 to 'modf' (number expected)".to_string()))?;
-    let int_part = x.trunc();
-    let frac_part = if x.is_infinite() { 0.0 } else { x - int_part };
-
-    // Return integer part as integer if it fits
-    if !x.is_nan()
-        && !x.is_infinite()
-        && int_part >= i64::MIN as f64
-        && int_part < -(i64::MIN as f64)
-    {
-        l.push_value(LuaValue::integer(int_part as i64))?;
-    } else {
-        l.push_value(LuaValue::float(int_part))?;
+    // An integer is its own integral part
+    if let Some(i) = l.get_arg(1).and_then(|v| v.as_integer_strict()) {
+        l.push_value(LuaValue::integer(i))?;
+        l.push_value(LuaValue::float(0.0))?;
+        return Ok(2);
     }
+
+    // A float keeps its subtype: the integral part is a float, like C modf
+    let x = checknumber(l, 1)?;
+    let int_part = x.trunc();
+    // Infinities have no fractional part; nan propagates to both results
+    let frac_part = if x == int_part { 0.0 } else { x - int_part };
+    l.push_value(LuaValue::float(int_part))?;
     l.push_value(LuaValue::float(frac_part))?;
     Ok(2)
 }
 
 fn math_rad(l: &mut LuaState) -> LuaResult<usize> {
-    let x = checknumber(l, 1, "rad")?;
+    let x = checknumber(l, 1)?;
     l.push_value(LuaValue::float(x.to_radians()))?;
     Ok(1)
 }
@@ -419,13 +416,9 @@ fn math_random(l: &mut LuaState) -> LuaResult<usize> {
             let rv = l.global_state_mut().rng.next_rand();
             let up = l
                 .get_arg(1)
-                .ok_or_else(|| {
-                    l.error("bad argument #1 to 'random' (number expected)".to_string())
-                })?
+                .ok_or_else(|| argerror(l, 1, "number expected"))?
                 .as_integer()
-                .ok_or_else(|| {
-                    l.error("bad argument #1 to 'random' (number expected, got float)".to_string())
-                })?;
+                .ok_or_else(|| argerror(l, 1, "number expected, got float"))?;
             if up == 0 {
                 // random(0): return raw random integer
                 l.push_value(LuaValue::integer(rv as i64))?;
@@ -433,7 +426,7 @@ fn math_random(l: &mut LuaState) -> LuaResult<usize> {
             }
             // random(n): return random integer in [1, n]
             if up < 1 {
-                return Err(l.error("bad argument #1 to 'random' (interval is empty)".to_string()));
+                return Err(argerror(l, 1, "interval is empty"));
             }
             let result = project(rv, 1, up as u64)?;
             l.push_value(LuaValue::integer(result))?;
@@ -443,24 +436,16 @@ fn math_random(l: &mut LuaState) -> LuaResult<usize> {
             let rv = l.global_state_mut().rng.next_rand();
             let low = l
                 .get_arg(1)
-                .ok_or_else(|| {
-                    l.error("bad argument #1 to 'random' (number expected)".to_string())
-                })?
+                .ok_or_else(|| argerror(l, 1, "number expected"))?
                 .as_integer()
-                .ok_or_else(|| {
-                    l.error("bad argument #1 to 'random' (number expected, got float)".to_string())
-                })?;
+                .ok_or_else(|| argerror(l, 1, "number expected, got float"))?;
             let up = l
                 .get_arg(2)
-                .ok_or_else(|| {
-                    l.error("bad argument #2 to 'random' (number expected)".to_string())
-                })?
+                .ok_or_else(|| argerror(l, 2, "number expected"))?
                 .as_integer()
-                .ok_or_else(|| {
-                    l.error("bad argument #2 to 'random' (number expected, got float)".to_string())
-                })?;
+                .ok_or_else(|| argerror(l, 2, "number expected, got float"))?;
             if low > up {
-                return Err(l.error("bad argument #2 to 'random' (interval is empty)".to_string()));
+                return Err(argerror(l, 2, "interval is empty"));
             }
             let result = project(rv, low as u64, up as u64)?;
             l.push_value(LuaValue::integer(result))?;
@@ -491,9 +476,10 @@ fn math_randomseed(l: &mut LuaState) -> LuaResult<usize> {
         let time = platform_time::unix_nanos();
         (time as i64, 0i64)
     } else {
-        let seed1 = l.get_arg(1).and_then(|v| v.as_integer()).ok_or_else(|| {
-            l.error("bad argument #1 to 'randomseed' (number expected)".to_string())
-        })?;
+        let seed1 = l
+            .get_arg(1)
+            .and_then(|v| v.as_integer())
+            .ok_or_else(|| argerror(l, 1, "number expected"))?;
         let seed2 = l.get_arg(2).and_then(|v| v.as_integer()).unwrap_or(0);
         (seed1, seed2)
     };
@@ -507,19 +493,19 @@ fn math_randomseed(l: &mut LuaState) -> LuaResult<usize> {
 }
 
 fn math_sin(l: &mut LuaState) -> LuaResult<usize> {
-    let x = checknumber(l, 1, "sin")?;
+    let x = checknumber(l, 1)?;
     l.push_value(LuaValue::float(x.sin()))?;
     Ok(1)
 }
 
 fn math_sqrt(l: &mut LuaState) -> LuaResult<usize> {
-    let x = checknumber(l, 1, "sqrt")?;
+    let x = checknumber(l, 1)?;
     l.push_value(LuaValue::float(x.sqrt()))?;
     Ok(1)
 }
 
 fn math_tan(l: &mut LuaState) -> LuaResult<usize> {
-    let x = checknumber(l, 1, "tan")?;
+    let x = checknumber(l, 1)?;
     l.push_value(LuaValue::float(x.tan()))?;
     Ok(1)
 }
@@ -527,19 +513,19 @@ fn math_tan(l: &mut LuaState) -> LuaResult<usize> {
 fn math_tointeger(l: &mut LuaState) -> LuaResult<usize> {
     let val = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'tointeger' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
 
     let result = if let Some(i) = val.as_integer() {
         LuaValue::integer(i)
     } else if let Some(f) = val.as_number() {
         float_to_integer(f)
-    } else if let Some(s) = val.as_str() {
-        // Try to parse string as integer
-        let s_str = s.trim();
-        if let Ok(i) = s_str.parse::<i64>() {
+    } else if val.is_string() {
+        // Strings convert like lua_tointegerx: any numeral, then the float
+        // must be a whole number
+        let n = parse_lua_number(val.as_str().unwrap_or(""));
+        if let Some(i) = n.as_integer_strict() {
             LuaValue::integer(i)
-        } else if let Ok(f) = s_str.parse::<f64>() {
-            // String is a float, check if it's a whole number
+        } else if let Some(f) = n.as_float() {
             float_to_integer(f)
         } else {
             LuaValue::nil()
@@ -593,7 +579,7 @@ fn float_to_integer(f: f64) -> LuaValue {
 fn math_type(l: &mut LuaState) -> LuaResult<usize> {
     let val = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'type' (value expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "value expected"))?;
 
     let cs = &l.global_state_mut().const_strings;
     let result = match val.kind() {
@@ -612,15 +598,15 @@ fn math_type(l: &mut LuaState) -> LuaResult<usize> {
 fn math_ult(l: &mut LuaState) -> LuaResult<usize> {
     let m_value = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'ult' (integer expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "integer expected"))?;
     let Some(m) = m_value.as_integer() else {
-        return Err(l.error("bad argument #1 to 'ult' (integer expected)".to_string()));
+        return Err(argerror(l, 1, "integer expected"));
     };
     let n_value = l
         .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'ult' (integer expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 2, "integer expected"))?;
     let Some(n) = n_value.as_integer() else {
-        return Err(l.error("bad argument #2 to 'ult' (integer expected)".to_string()));
+        return Err(argerror(l, 2, "integer expected"));
     };
     // Unsigned less than
     let result = (m as u64) < (n as u64);
@@ -633,7 +619,7 @@ fn math_frexp(l: &mut LuaState) -> LuaResult<usize> {
     let x = l
         .get_arg(1)
         .and_then(|v| v.as_float().or_else(|| v.as_integer().map(|i| i as f64)))
-        .ok_or_else(|| l.error("bad argument #1 to 'frexp' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "number expected"))?;
 
     if x == 0.0 {
         l.push_value(LuaValue::float(0.0))?;
@@ -673,11 +659,11 @@ fn math_ldexp(l: &mut LuaState) -> LuaResult<usize> {
     let m = l
         .get_arg(1)
         .and_then(|v| v.as_float().or_else(|| v.as_integer().map(|i| i as f64)))
-        .ok_or_else(|| l.error("bad argument #1 to 'ldexp' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "number expected"))?;
     let e = l
         .get_arg(2)
         .and_then(|v| v.as_integer())
-        .ok_or_else(|| l.error("bad argument #2 to 'ldexp' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 2, "number expected"))?;
 
     // Use the ldexp helper from parse_number to handle large exponents
     let mut result = m;
@@ -1,12 +1,17 @@
 // Lua 5.5 Standard Libraries Implementation
 
+pub mod auxlib;
 pub mod basic;
 pub mod coroutine;
 pub mod debug;
 pub mod io;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod math;
 pub mod os;
 pub mod package;
+#[cfg(feature = "profiler")]
+pub mod profiler;
 mod sort_table;
 pub mod string;
 pub mod table;
@@ -24,6 +29,163 @@ pub enum Stdlib {
     Utf8,
     Coroutine,
     Debug,
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "profiler")]
+    Profiler,
 
     All,
 }
+
+impl Stdlib {
+    /// Every individual library, in the order `Stdlib::All` opens them.
+    const LIBS: &'static [Stdlib] = &[
+        Stdlib::Package,
+        Stdlib::Basic,
+        Stdlib::String,
+        Stdlib::Table,
+        Stdlib::Math,
+        Stdlib::Io,
+        Stdlib::Os,
+        Stdlib::Utf8,
+        Stdlib::Coroutine,
+        Stdlib::Debug,
+        #[cfg(feature = "json")]
+        Stdlib::Json,
+        #[cfg(feature = "profiler")]
+        Stdlib::Profiler,
+    ];
+
+    fn bit(self) -> u32 {
+        match self {
+            Stdlib::Io => 1 << 0,
+            Stdlib::Os => 1 << 1,
+            Stdlib::Math => 1 << 2,
+            Stdlib::String => 1 << 3,
+            Stdlib::Table => 1 << 4,
+            Stdlib::Basic => 1 << 5,
+            Stdlib::Package => 1 << 6,
+            Stdlib::Utf8 => 1 << 7,
+            Stdlib::Coroutine => 1 << 8,
+            Stdlib::Debug => 1 << 9,
+            #[cfg(feature = "json")]
+            Stdlib::Json => 1 << 10,
+            #[cfg(feature = "profiler")]
+            Stdlib::Profiler => 1 << 11,
+            Stdlib::All => Stdlib::LIBS.iter().fold(0, |bits, lib| bits | lib.bit()),
+        }
+    }
+}
+
+/// How opening a library treats a library table that already exists, e.g.
+/// because it was opened before.
+///
+/// Either way the existing table is kept, so `string` and every
+/// `local fmt = string.format` taken from it stay valid; only its entries
+/// are filled in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenMode {
+    /// Only add entries that are missing, keeping what scripts patched.
+    /// Libraries already opened on the VM are skipped entirely.
+    #[default]
+    FillMissing,
+    /// Reset every entry to its standard value.
+    Overwrite,
+}
+
+/// A combination of standard libraries, built with `|`:
+///
+/// ```ignore
+/// lua.open_stdlib(Stdlib::Basic | Stdlib::String | Stdlib::Table)?;
+/// let all_but_io = StdlibSet::from(Stdlib::All) - Stdlib::Io - Stdlib::Os;
+/// ```
+///
+/// Anything that takes a set also takes a single [`Stdlib`].
+#[derive(Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct StdlibSet(u32);
+
+impl StdlibSet {
+    /// The set with no libraries.
+    pub const fn empty() -> Self {
+        StdlibSet(0)
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every library in `lib` is in the set (for `Stdlib::All`,
+    /// whether the set is complete).
+    pub fn contains(self, lib: impl Into<StdlibSet>) -> bool {
+        let other = lib.into();
+        self.0 & other.0 == other.0
+    }
+
+    /// The individual libraries in the set, in the order they are opened.
+    pub fn iter(self) -> impl Iterator<Item = Stdlib> {
+        Stdlib::LIBS
+            .iter()
+            .copied()
+            .filter(move |lib| self.0 & lib.bit() != 0)
+    }
+}
+
+impl From<Stdlib> for StdlibSet {
+    fn from(lib: Stdlib) -> Self {
+        StdlibSet(lib.bit())
+    }
+}
+
+impl FromIterator<Stdlib> for StdlibSet {
+    fn from_iter<I: IntoIterator<Item = Stdlib>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(StdlibSet::empty(), |set, lib| set | lib)
+    }
+}
+
+impl std::fmt::Debug for StdlibSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Into<StdlibSet>> std::ops::BitOr<T> for StdlibSet {
+    type Output = StdlibSet;
+    fn bitor(self, rhs: T) -> StdlibSet {
+        StdlibSet(self.0 | rhs.into().0)
+    }
+}
+
+impl<T: Into<StdlibSet>> std::ops::BitOr<T> for Stdlib {
+    type Output = StdlibSet;
+    fn bitor(self, rhs: T) -> StdlibSet {
+        StdlibSet::from(self) | rhs
+    }
+}
+
+impl<T: Into<StdlibSet>> std::ops::BitAnd<T> for StdlibSet {
+    type Output = StdlibSet;
+    fn bitand(self, rhs: T) -> StdlibSet {
+        StdlibSet(self.0 & rhs.into().0)
+    }
+}
+
+impl<T: Into<StdlibSet>> std::ops::BitAnd<T> for Stdlib {
+    type Output = StdlibSet;
+    fn bitand(self, rhs: T) -> StdlibSet {
+        StdlibSet::from(self) & rhs
+    }
+}
+
+impl<T: Into<StdlibSet>> std::ops::Sub<T> for StdlibSet {
+    type Output = StdlibSet;
+    fn sub(self, rhs: T) -> StdlibSet {
+        StdlibSet(self.0 & !rhs.into().0)
+    }
+}
+
+impl<T: Into<StdlibSet>> std::ops::BitOrAssign<T> for StdlibSet {
+    fn bitor_assign(&mut self, rhs: T) {
+        self.0 |= rhs.into().0;
+    }
+}
@@ -5,8 +5,13 @@
 use crate::lib_registry::LibraryModule;
 use crate::lua_value::LuaValue;
 use crate::lua_vm::{LuaResult, LuaState};
+use crate::platform_path::{create_temp_file, path_from_bytes, path_to_bytes};
 use crate::platform_time;
+use crate::stdlib::auxlib::{check_string, opt_integer, opt_string};
+use crate::stdlib::debug::argerror;
+use crate::stdlib::io::file_result_error;
 use chrono::{DateTime, Datelike, Local, TimeZone, Timelike, Utc};
+use std::path::Path;
 
 pub fn create_os_lib() -> LibraryModule {
     crate::lib_module!("os", {
@@ -272,7 +277,7 @@ fn os_date(l: &mut LuaState) -> LuaResult<usize> {
         if let Some(n) = t.as_number() {
             n as i64
         } else {
-            return Err(l.error("bad argument #2 to 'date' (number expected)".to_string()));
+            return Err(argerror(l, 2, "number expected"));
         }
     } else {
         platform_time::unix_secs() as i64
@@ -283,7 +288,7 @@ fn os_date(l: &mut LuaState) -> LuaResult<usize> {
         if let Some(s) = f.as_str() {
             s.to_string()
         } else {
-            return Err(l.error("bad argument #1 to 'date' (string expected)".to_string()));
+            return Err(argerror(l, 1, "string expected"));
         }
     } else {
         "%c".to_string() // Default to standard date/time format
@@ -495,8 +500,17 @@ fn format_date_string(dt: DateTime<Local>, format: &str) -> Result<String, Strin
     Ok(result)
 }
 
-fn os_exit(_l: &mut LuaState) -> LuaResult<usize> {
-    std::process::exit(0);
+/// os.exit([code [, close]]): `true` or no code is success, `false` is
+/// failure, an integer is the exit status. Standard output is flushed
+/// first, as C's `exit` would; the state itself is not closed.
+fn os_exit(l: &mut LuaState) -> LuaResult<usize> {
+    let code = match l.get_arg(1).and_then(|v| v.as_boolean()) {
+        Some(true) => 0,
+        Some(false) => 1,
+        None => opt_integer(l, 1, 0)? as i32,
+    };
+    std::io::Write::flush(&mut std::io::stdout()).ok();
+    std::process::exit(code);
 }
 
 fn os_difftime(l: &mut LuaState) -> LuaResult<usize> {
@@ -575,86 +589,116 @@ fn os_execute(l: &mut LuaState) -> LuaResult<usize> {
     }
 }
 
+/// os.getenv(varname) - the variable's value, or nil when it is unset,
+/// hidden by the host's env filter or not a valid variable name.
 fn os_getenv(l: &mut LuaState) -> LuaResult<usize> {
-    let varname = l
-        .get_arg(1)
-        .and_then(|v| v.as_str().map(|s| s.to_string()))
-        .ok_or_else(|| l.error("getenv: argument 1 must be a string".to_string()))?;
-
-    match std::env::var(&varname) {
-        Ok(value) => {
-            let result = l.create_string(&value)?;
+    let name = check_string(l, 1)?;
+    let name = name.as_bytes().unwrap_or_default();
+    // std::env::var_os may panic on an empty name or one containing '=' or
+    // NUL; no such variable can exist, so those read as unset
+    let valid = !name.is_empty() && !name.contains(&b'=') && !name.contains(&0);
+    let value = if valid && l.global_state().env_visible(name) {
+        path_from_bytes(name)
+            .ok()
+            .and_then(|name| std::env::var_os(name.as_os_str()))
+    } else {
+        None
+    };
+    match value {
+        Some(value) => {
+            let result = l.create_bytes(&path_to_bytes(Path::new(&value)))?;
             l.push_value(result)?;
-            Ok(1)
-        }
-        Err(_) => {
-            l.push_value(LuaValue::nil())?;
-            Ok(1)
         }
+        None => l.push_value(LuaValue::nil())?,
     }
+    Ok(1)
 }
 
 fn os_remove(l: &mut LuaState) -> LuaResult<usize> {
     let filename = l
         .get_arg(1)
-        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .and_then(|v| v.as_bytes().map(|s| s.to_vec()))
         .ok_or_else(|| l.error("remove: argument 1 must be a string".to_string()))?;
 
-    match std::fs::remove_file(&filename) {
-        Ok(_) => {
+    // C's remove() deletes files and, on POSIX, empty directories
+    let result = path_from_bytes(&filename).and_then(|path| {
+        std::fs::remove_file(&path).or_else(|e| {
+            if cfg!(not(windows)) && path.is_dir() {
+                std::fs::remove_dir(&path)
+            } else {
+                Err(e)
+            }
+        })
+    });
+    match result {
+        Ok(()) => {
             l.push_value(LuaValue::boolean(true))?;
             Ok(1)
         }
-        Err(e) => {
-            let err_msg = l.create_string(&format!("{}", e))?;
-            l.push_value(LuaValue::nil())?;
-            l.push_value(err_msg)?;
-            Ok(2)
-        }
+        Err(e) => file_result_error(l, &filename, &e),
     }
 }
 
 fn os_rename(l: &mut LuaState) -> LuaResult<usize> {
     let oldname = l
         .get_arg(1)
-        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .and_then(|v| v.as_bytes().map(|s| s.to_vec()))
         .ok_or_else(|| l.error("rename: argument 1 must be a string".to_string()))?;
     let newname = l
         .get_arg(2)
-        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .and_then(|v| v.as_bytes().map(|s| s.to_vec()))
         .ok_or_else(|| l.error("rename: argument 2 must be a string".to_string()))?;
 
-    match std::fs::rename(&oldname, &newname) {
-        Ok(_) => {
+    let result = path_from_bytes(&oldname).and_then(|old| {
+        let new = path_from_bytes(&newname)?;
+        // The Windows C runtime's rename() refuses to replace an existing
+        // file, while std::fs::rename replaces it
+        if cfg!(windows) && new.exists() {
+            const ERROR_ALREADY_EXISTS: i32 = 183;
+            return Err(std::io::Error::from_raw_os_error(ERROR_ALREADY_EXISTS));
+        }
+        std::fs::rename(&old, &new)
+    });
+    match result {
+        Ok(()) => {
             l.push_value(LuaValue::boolean(true))?;
             Ok(1)
         }
-        Err(e) => {
-            let err_msg = l.create_string(&format!("{}", e))?;
-            l.push_value(LuaValue::nil())?;
-            l.push_value(err_msg)?;
-            Ok(2)
-        }
+        Err(e) => file_result_error(l, &oldname, &e),
     }
 }
 
+/// os.setlocale([locale [, category]]) - only the "C" locale exists here,
+/// so querying returns "C", asking for "C", "POSIX" or the native locale
+/// ("") succeeds with "C", and any other locale fails with nil.
 fn os_setlocale(l: &mut LuaState) -> LuaResult<usize> {
-    // Stub implementation - just return the requested locale or "C"
-    let locale = l
-        .get_arg(1)
-        .and_then(|v| v.as_str().map(|s| s.to_string()))
-        .unwrap_or_else(|| "C".to_string());
-
-    let result = l.create_string(&locale)?;
-    l.push_value(result)?;
+    const CATEGORIES: [&str; 6] = ["all", "collate", "ctype", "monetary", "numeric", "time"];
+    let locale = opt_string(l, 1)?;
+    if let Some(category) = opt_string(l, 2)? {
+        let category = category.as_bytes().unwrap_or_default();
+        if !CATEGORIES.iter().any(|c| c.as_bytes() == category) {
+            let msg = format!("invalid option '{}'", String::from_utf8_lossy(category));
+            return Err(argerror(l, 2, &msg));
+        }
+    }
+    let is_c = match locale {
+        None => true,
+        Some(locale) => matches!(locale.as_bytes(), Some(b"" | b"C" | b"POSIX")),
+    };
+    if is_c {
+        let result = l.create_string("C")?;
+        l.push_value(result)?;
+    } else {
+        l.push_value(LuaValue::nil())?;
+    }
     Ok(1)
 }
 
 fn os_tmpname(l: &mut LuaState) -> LuaResult<usize> {
-    let timestamp = platform_time::unix_nanos();
-
-    let tmpname = format!("/tmp/lua_tmp_{}", timestamp);
-    let result = l.create_string(&tmpname)?;
+    let Ok(path) = create_temp_file() else {
+        return Err(l.error("unable to generate a unique filename".to_string()));
+    };
+    let result = l.create_bytes(&path_to_bytes(&path))?;
     l.push_value(result)?;
     Ok(1)
 }
@@ -3,7 +3,67 @@
 
 use crate::lib_registry::LibraryModule;
 use crate::lua_value::{LuaValue, UpvalueStore};
-use crate::lua_vm::{LuaResult, LuaState};
+use crate::lua_vm::{LoadOrigin, LuaResult, LuaState};
+use crate::stdlib::debug::argerror;
+
+/// Directory separator used by the default paths and `package.config`
+/// (C Lua's `LUA_DIRSEP`).
+#[cfg(windows)]
+pub const LUA_DIRSEP: &str = "\\";
+/// Directory separator used by the default paths and `package.config`
+/// (C Lua's `LUA_DIRSEP`).
+#[cfg(not(windows))]
+pub const LUA_DIRSEP: &str = "/";
+
+/// Initial `package.path`, also what `;;` in `LUA_PATH` expands to.
+///
+/// Mirrors luaconf.h: the install tree next to the executable (`!`, replaced
+/// when the library opens), then the current directory.
+#[cfg(windows)]
+pub const LUA_PATH_DEFAULT: &str = "!\\lua\\?.lua;!\\lua\\?\\init.lua;!\\?.lua;!\\?\\init.lua;\
+     !\\..\\share\\lua\\5.5\\?.lua;!\\..\\share\\lua\\5.5\\?\\init.lua;\
+     .\\?.lua;.\\?\\init.lua";
+/// Initial `package.path`, also what `;;` in `LUA_PATH` expands to.
+///
+/// Mirrors luaconf.h: the `/usr/local` share and lib trees for this Lua
+/// version, then the current directory.
+#[cfg(not(windows))]
+pub const LUA_PATH_DEFAULT: &str = "/usr/local/share/lua/5.5/?.lua;/usr/local/share/lua/5.5/?/init.lua;\
+     /usr/local/lib/lua/5.5/?.lua;/usr/local/lib/lua/5.5/?/init.lua;\
+     ./?.lua;./?/init.lua";
+
+/// Initial `package.cpath`, also what `;;` in `LUA_CPATH` expands to.
+#[cfg(windows)]
+pub const LUA_CPATH_DEFAULT: &str = "!\\?.dll;!\\..\\lib\\lua\\5.5\\?.dll;!\\loadall.dll;.\\?.dll";
+/// Initial `package.cpath`, also what `;;` in `LUA_CPATH` expands to.
+#[cfg(not(windows))]
+pub const LUA_CPATH_DEFAULT: &str =
+    "/usr/local/lib/lua/5.5/?.so;/usr/local/lib/lua/5.5/loadall.so;./?.so;./?.dll;./?.dylib";
+
+/// `package.config`: directory separator, path separator, substitution
+/// mark, executable-directory mark and the `luaopen_` ignore mark, one per
+/// line.
+pub const LUA_PACKAGE_CONFIG: &str = if cfg!(windows) {
+    "\\\n;\n?\n!\n-"
+} else {
+    "/\n;\n?\n!\n-"
+};
+
+/// Replace the executable-directory mark `!` with the directory holding the
+/// running executable, as C Lua's `setprogdir` does on Windows.
+#[cfg(windows)]
+fn expand_exec_dir(path: &str) -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.to_string_lossy().into_owned()))
+        .map(|dir| path.replace('!', &dir))
+        .unwrap_or_else(|| path.to_string())
+}
+
+#[cfg(not(windows))]
+fn expand_exec_dir(path: &str) -> String {
+    path.to_string()
+}
 
 pub fn create_package_lib() -> LibraryModule {
     crate::lib_module!("package", {
@@ -35,14 +95,12 @@ pub fn init_package_fields(l: &mut LuaState) -> LuaResult<()> {
     // Create all values
     let loaded_table = l.create_table(0, 0)?;
     let preload_table = l.create_table(0, 0)?;
-    let path_value = l.create_string("./?.lua;./?/init.lua")?;
-    let cpath_value = l.create_string("./?.so;./?.dll;./?.dylib")?;
-
-    #[cfg(windows)]
-    let config_str = "\\\n;\n?\n!\n-";
-    #[cfg(not(windows))]
-    let config_str = "/\n;\n?\n!\n-";
-    let config_value = l.create_string(config_str)?;
+    let option = &l.global_state().safe_option;
+    let path = expand_exec_dir(option.package_path.as_deref().unwrap_or(LUA_PATH_DEFAULT));
+    let cpath = expand_exec_dir(option.package_cpath.as_deref().unwrap_or(LUA_CPATH_DEFAULT));
+    let path_value = l.create_string(&path)?;
+    let cpath_value = l.create_string(&cpath)?;
+    let config_value = l.create_string(LUA_PACKAGE_CONFIG)?;
 
     // Create searchers array
     let searchers_table_value = l.create_table(4, 0)?;
@@ -145,9 +203,7 @@ fn searcher_lua(l: &mut LuaState) -> LuaResult<usize> {
         return Err(l.error("'package.path' must be a string".to_string()));
     };
 
-    // Search for the file, using platform directory separator
-    let dirsep = std::path::MAIN_SEPARATOR_STR;
-    let result = search_path(modname, path_str, ".", dirsep)?;
+    let result = search_path(modname, path_str, ".", LUA_DIRSEP)?;
 
     match result {
         Some(filepath) => {
@@ -161,7 +217,7 @@ fn searcher_lua(l: &mut LuaState) -> LuaResult<usize> {
                 "\n\tno file '{}'",
                 path_str
                     .split(';')
-                    .map(|template| { template.replace('?', &modname.replace('.', "/")) })
+                    .map(|template| { template.replace('?', &modname.replace('.', LUA_DIRSEP)) })
                     .collect::<Vec<_>>()
                     .join("'\n\tno file '")
             );
@@ -187,7 +243,9 @@ fn lua_file_loader(l: &mut LuaState) -> LuaResult<usize> {
         return Err(l.error("file path must be a string".to_string()));
     };
 
-    let proto = l.load_proto_from_file(filepath_str)?;
+    let proto = l
+        .load_audited_file(filepath_str, LoadOrigin::Require)
+        .map_err(|msg| l.error(msg))?;
 
     // Create a function from the chunk with _ENV upvalue
     let vm = l.global_state_mut();
@@ -258,7 +316,7 @@ fn searcher_c(l: &mut LuaState) -> LuaResult<usize> {
         "\n\tno file '{}'",
         cpath_str
             .split(';')
-            .map(|template| { template.replace('?', &modname.replace('.', "/")) })
+            .map(|template| { template.replace('?', &modname.replace('.', LUA_DIRSEP)) })
             .collect::<Vec<_>>()
             .join("'\n\tno file '")
     );
@@ -306,7 +364,7 @@ fn searcher_c_all_in_one(l: &mut LuaState) -> LuaResult<usize> {
         "\n\tno file '{}'",
         cpath_str
             .split(';')
-            .map(|template| { template.replace('?', &root_modname.replace('.', "/")) })
+            .map(|template| { template.replace('?', &root_modname.replace('.', LUA_DIRSEP)) })
             .collect::<Vec<_>>()
             .join("'\n\tno file '")
     );
@@ -342,17 +400,17 @@ fn package_loadlib(l: &mut LuaState) -> LuaResult<usize> {
 fn package_searchpath(l: &mut LuaState) -> LuaResult<usize> {
     let name_val = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'searchpath' (string expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "string expected"))?;
     let path_val = l
         .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'searchpath' (string expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 2, "string expected"))?;
 
     let Some(name_str) = name_val.as_str() else {
-        return Err(l.error("bad argument #1 to 'searchpath' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     };
 
     let Some(path_str) = path_val.as_str() else {
-        return Err(l.error("bad argument #2 to 'searchpath' (string expected)".to_string()));
+        return Err(argerror(l, 2, "string expected"));
     };
 
     // Optional sep and rep arguments
@@ -366,15 +424,10 @@ fn package_searchpath(l: &mut LuaState) -> LuaResult<usize> {
 
     let rep_val = l.get_arg(4);
 
-    #[cfg(windows)]
-    let default_rep = "\\";
-    #[cfg(not(windows))]
-    let default_rep = "/";
-
     let rep = if let Some(rep_val) = &rep_val {
-        rep_val.as_str().unwrap_or(default_rep)
+        rep_val.as_str().unwrap_or(LUA_DIRSEP)
     } else {
-        default_rep
+        LUA_DIRSEP
     };
 
     match search_path(name_str, path_str, sep, rep)? {
@@ -0,0 +1,75 @@
+// Profiler library (optional, `profiler` feature)
+// Implements: start, stop, reset, running, report
+//
+// Script-side access to GlobalState's profiler. `report` returns one table
+// per function, most exclusive time first, with the fields of
+// `FunctionProfile`.
+
+use crate::lib_registry::LibraryModule;
+use crate::lua_value::LuaValue;
+use crate::lua_vm::{LuaResult, LuaState};
+
+pub fn create_profiler_lib() -> LibraryModule {
+    crate::lib_module!("profiler", {
+        "start" => profiler_start,
+        "stop" => profiler_stop,
+        "reset" => profiler_reset,
+        "running" => profiler_running,
+        "report" => profiler_report,
+    })
+}
+
+/// profiler.start() - Start recording calls on every thread
+fn profiler_start(l: &mut LuaState) -> LuaResult<usize> {
+    l.start_profiler();
+    Ok(0)
+}
+
+/// profiler.stop() - Stop recording; running functions are charged up to now
+fn profiler_stop(l: &mut LuaState) -> LuaResult<usize> {
+    l.stop_profiler();
+    Ok(0)
+}
+
+/// profiler.reset() - Discard what was recorded
+fn profiler_reset(l: &mut LuaState) -> LuaResult<usize> {
+    l.global_state_mut().reset_profile();
+    Ok(0)
+}
+
+/// profiler.running() - Whether the profiler is recording
+fn profiler_running(l: &mut LuaState) -> LuaResult<usize> {
+    let running = l.global_state().is_profiling();
+    l.push_value(LuaValue::boolean(running))?;
+    Ok(1)
+}
+
+/// profiler.report() - Array of {source, line_defined, calls, inclusive_ns,
+/// exclusive_ns}, most exclusive time first
+fn profiler_report(l: &mut LuaState) -> LuaResult<usize> {
+    let report = l.global_state().profile_report();
+    let result = l.create_table(report.len(), 0)?;
+    for (i, entry) in report.into_iter().enumerate() {
+        let row = l.create_table(0, 5)?;
+
+        let key = l.create_string("source")?;
+        let source = l.create_string(&entry.source)?;
+        l.raw_set(&row, key, source);
+
+        let key = l.create_string("line_defined")?;
+        l.raw_set(&row, key, LuaValue::integer(entry.line_defined as i64));
+
+        let key = l.create_string("calls")?;
+        l.raw_set(&row, key, LuaValue::integer(entry.calls as i64));
+
+        let key = l.create_string("inclusive_ns")?;
+        l.raw_set(&row, key, LuaValue::integer(entry.inclusive_ns as i64));
+
+        let key = l.create_string("exclusive_ns")?;
+        l.raw_set(&row, key, LuaValue::integer(entry.exclusive_ns as i64));
+
+        l.raw_seti(&result, i as i64 + 1, row);
+    }
+    l.push_value(result)?;
+    Ok(1)
+}
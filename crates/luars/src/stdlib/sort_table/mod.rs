@@ -24,7 +24,7 @@ pub fn table_sort(l: &mut LuaState) -> LuaResult<usize> {
 
     // C Lua: luaL_argcheck(L, n < INT_MAX, 1, "array too big");
     if len >= i32::MAX as i64 {
-        return Err(l.error("bad argument #1 to 'sort' (array too big)".to_string()));
+        return Err(crate::stdlib::debug::argerror(l, 1, "array too big"));
     }
 
     if len <= 1 {
@@ -134,11 +134,7 @@ fn sort_buffer(
 
             // All strings
             if buf[0].is_string() {
-                buf.sort_unstable_by(|a, b| {
-                    let sa = a.as_bytes().unwrap_or(&[]);
-                    let sb = b.as_bytes().unwrap_or(&[]);
-                    sa.cmp(sb)
-                });
+                buf.sort_unstable_by(|a, b| a.str_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
                 return Ok(());
             }
         }
@@ -6,38 +6,13 @@ mod pattern;
 mod string_format;
 
 use crate::lib_registry::LibraryModule;
+use crate::lua_value::lua_float_to_string;
 use crate::lua_value::{LuaValue, chunk_serializer};
 use crate::lua_vm::lua_limits::MAX_STRING_SIZE;
 use crate::lua_vm::{LuaResult, LuaState};
+use crate::stdlib::auxlib::{check_integer, check_string, opt_integer, opt_string};
 use crate::stdlib::debug;
 
-/// Mirrors luaL_checkinteger: convert a LuaValue to integer, producing
-/// appropriate error messages like C Lua.
-fn value_to_integer(v: &LuaValue) -> Result<i64, &'static str> {
-    if let Some(i) = v.as_integer() {
-        return Ok(i);
-    }
-    if let Some(f) = v.as_number() {
-        if f == f.floor() && f.is_finite() && f >= (i64::MIN as f64) && f < (i64::MAX as f64) {
-            return Ok(f as i64);
-        }
-        return Err("number has no integer representation");
-    }
-    if let Some(s) = v.as_str() {
-        let s = s.trim();
-        if let Ok(i) = s.parse::<i64>() {
-            return Ok(i);
-        }
-        if let Ok(f) = s.parse::<f64>() {
-            if f == f.floor() && f.is_finite() && f >= (i64::MIN as f64) && f < (i64::MAX as f64) {
-                return Ok(f as i64);
-            }
-            return Err("number has no integer representation");
-        }
-    }
-    Err("number expected")
-}
-
 pub fn create_string_lib() -> LibraryModule {
     crate::lib_module!("string", {
         "byte" => string_byte,
@@ -69,16 +44,10 @@ fn create_string_or_binary(l: &mut LuaState, bytes: &[u8]) -> LuaResult<LuaValue
 /// string.byte(s [, i [, j]]) - Return byte values
 /// Supports both string and binary types
 fn string_byte(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'string.byte' (string expected)".to_string()))?;
-
-    let i = l.get_arg(2).and_then(|v| v.as_integer()).unwrap_or(1);
-    let j = l.get_arg(3).and_then(|v| v.as_integer()).unwrap_or(i);
-
-    let Some(bytes) = s_value.as_bytes() else {
-        return Err(l.error("bad argument #1 to 'string.byte' (string expected)".to_string()));
-    };
+    let s_value = check_string(l, 1)?;
+    let i = opt_integer(l, 2, 1)?;
+    let j = opt_integer(l, 3, i)?;
+    let bytes = s_value.as_bytes().unwrap_or_default();
 
     let len = bytes.len() as i64;
 
@@ -111,17 +80,12 @@ fn string_byte(l: &mut LuaState) -> LuaResult<usize> {
         return Ok(2);
     }
 
-    // Slow path: multiple returns
-    let mut count = 0;
-    for idx in start..=end {
-        if idx >= 1 && idx <= len {
-            let byte = bytes[(idx - 1) as usize];
-            l.push_value(LuaValue::integer(byte as i64))?;
-            count += 1;
-        }
-    }
-
-    Ok(count)
+    // Multiple returns: reserve the whole slice once (luaL_checkstack)
+    let slice = &bytes[(start - 1) as usize..end as usize];
+    l.push_values(
+        slice.iter().map(|&byte| LuaValue::integer(byte as i64)),
+        "string slice too long",
+    )
 }
 
 /// string.char(...) - Convert bytes to string
@@ -134,18 +98,9 @@ fn string_char(l: &mut LuaState) -> LuaResult<usize> {
         let mut buf = [0u8; 256];
         let mut all_ascii = true;
         for (i, slot) in buf.iter_mut().enumerate().take(nargs) {
-            let arg = l.get_arg(i + 1).unwrap_or_default();
-            let Some(byte) = arg.as_integer() else {
-                return Err(l.error(format!(
-                    "bad argument #{} to 'string.char' (number expected)",
-                    i + 1
-                )));
-            };
+            let byte = check_integer(l, i + 1)?;
             if !(0..=255).contains(&byte) {
-                return Err(l.error(format!(
-                    "bad argument #{} to 'string.char' (value out of range)",
-                    i + 1
-                )));
+                return Err(debug::argerror(l, i + 1, "value out of range"));
             }
             let b = byte as u8;
             *slot = b;
@@ -169,18 +124,9 @@ fn string_char(l: &mut LuaState) -> LuaResult<usize> {
     let mut bytes = Vec::with_capacity(nargs);
     let mut all_ascii = true;
     for i in 0..nargs {
-        let arg = l.get_arg(i + 1).unwrap_or_default();
-        let Some(byte) = arg.as_integer() else {
-            return Err(l.error(format!(
-                "bad argument #{} to 'string.char' (number expected)",
-                i + 1
-            )));
-        };
+        let byte = check_integer(l, i + 1)?;
         if !(0..=255).contains(&byte) {
-            return Err(l.error(format!(
-                "bad argument #{} to 'string.char' (value out of range)",
-                i + 1
-            )));
+            return Err(debug::argerror(l, i + 1, "value out of range"));
         }
         let b = byte as u8;
         bytes.push(b);
@@ -203,12 +149,12 @@ fn string_char(l: &mut LuaState) -> LuaResult<usize> {
 fn string_dump(l: &mut LuaState) -> LuaResult<usize> {
     let func_value = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'dump' (function expected)".to_string()))?;
+        .ok_or_else(|| debug::argerror(l, 1, "function expected"))?;
     let strip = l.get_arg(2).map(|v| v.is_truthy()).unwrap_or(false);
 
     // Get the function ID
     let Some(func_obj) = func_value.as_lua_function() else {
-        return Err(l.error("bad argument #1 to 'dump' (function expected)".to_string()));
+        return Err(debug::argerror(l, 1, "function expected"));
     };
 
     // Check if it's a Lua function (not a C function)
@@ -229,122 +175,64 @@ fn string_dump(l: &mut LuaState) -> LuaResult<usize> {
 /// string.len(s) - Return string length in bytes
 /// Supports both string and binary types
 fn string_len(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'string.len' (string expected)".to_string()))?;
-
-    let len = if let Some(bytes) = s_value.as_bytes() {
-        bytes.len()
-    } else {
-        return Err(l.error("bad argument #1 to 'string.len' (string expected)".to_string()));
-    };
+    let s_value = check_string(l, 1)?;
+    let len = s_value.as_bytes().map_or(0, <[u8]>::len);
 
     l.push_value(LuaValue::integer(len as i64))?;
     Ok(1)
 }
 
 /// string.lower(s) - Convert to lowercase
-/// OPTIMIZED: ASCII stack-buffer fast path, avoids heap allocation for short strings
 fn string_lower(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l.get_arg(1).ok_or_else(|| {
-        l.error("bad argument #1 to 'string.lower' (string expected)".to_string())
-    })?;
-    let Some(s) = s_value.as_str() else {
-        return Err(l.error("bad argument #1 to 'string.lower' (string expected)".to_string()));
-    };
-
-    // Simple byte-by-byte loop matching C Lua's str_lower.
-    // Lua's string.lower is byte-oriented (not Unicode-aware).
-    let bytes = s.as_bytes();
-    let len = bytes.len();
-    if len <= 256 {
-        let mut buf = [0u8; 256];
-        buf[..len].copy_from_slice(bytes);
-        buf[..len].make_ascii_lowercase();
-        // SAFETY: ASCII bytes remain valid ASCII (valid UTF-8) after lowercase
-        let result_str = std::str::from_utf8(&buf[..len]).unwrap();
-        let result = l.create_string(result_str)?;
-        l.push_value(result)?;
-    } else {
-        let mut buf = bytes.to_vec();
-        buf.make_ascii_lowercase();
-        // SAFETY: ASCII bytes remain valid UTF-8 after lowercase
-        let result_str = String::from_utf8(buf).unwrap();
-        let result = l.create_string(&result_str)?;
-        l.push_value(result)?;
-    }
-    Ok(1)
+    string_case(l, <[u8]>::make_ascii_lowercase)
 }
 
 /// string.upper(s) - Convert to uppercase
-/// Simple byte-by-byte loop matching C Lua's str_upper.
 fn string_upper(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l.get_arg(1).ok_or_else(|| {
-        l.error("bad argument #1 to 'string.upper' (string expected)".to_string())
-    })?;
-    let Some(s) = s_value.as_str() else {
-        return Err(l.error("bad argument #1 to 'string.upper' (string expected)".to_string()));
-    };
+    string_case(l, <[u8]>::make_ascii_uppercase)
+}
+
+/// Shared body of lower/upper, matching C Lua's str_lower/str_upper under the
+/// C locale: byte-wise, only A-Z/a-z change, every other byte (including
+/// non-UTF-8 data) passes through untouched.
+/// OPTIMIZED: stack buffer for short strings avoids a heap allocation
+#[inline]
+fn string_case(l: &mut LuaState, map: fn(&mut [u8])) -> LuaResult<usize> {
+    let s_value = check_string(l, 1)?;
+    let bytes = s_value.as_bytes().unwrap_or_default();
 
-    let bytes = s.as_bytes();
     let len = bytes.len();
-    if len <= 256 {
+    let result = if len <= 256 {
         let mut buf = [0u8; 256];
         buf[..len].copy_from_slice(bytes);
-        buf[..len].make_ascii_uppercase();
-        // SAFETY: ASCII bytes remain valid ASCII (valid UTF-8) after uppercase
-        let result_str = std::str::from_utf8(&buf[..len]).unwrap();
-        let result = l.create_string(result_str)?;
-        l.push_value(result)?;
+        map(&mut buf[..len]);
+        l.create_bytes(&buf[..len])?
     } else {
         let mut buf = bytes.to_vec();
-        buf.make_ascii_uppercase();
-        // SAFETY: ASCII bytes remain valid UTF-8 after uppercase
-        let result_str = String::from_utf8(buf).unwrap();
-        let result = l.create_string(&result_str)?;
-        l.push_value(result)?;
-    }
+        map(&mut buf);
+        l.create_binary(buf)?
+    };
+    l.push_value(result)?;
     Ok(1)
 }
 
 /// string.rep(s, n [, sep]) - Repeat string
 /// OPTIMIZED: Avoid input cloning, pre-allocate result, use byte slices directly
 fn string_rep(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'string.rep' (string expected)".to_string()))?;
-
+    let s_value = check_string(l, 1)?;
     // Get raw byte slice WITHOUT cloning to owned Vec
-    let Some(s_bytes) = s_value.as_bytes() else {
-        return Err(l.error("bad argument #1 to 'string.rep' (string expected)".to_string()));
-    };
+    let s_bytes = s_value.as_bytes().unwrap_or_default();
     let input_is_text = s_value.as_str().is_some();
 
-    // Get parameters
-    let n_value = l.get_arg(2);
-    let sep_value = l.get_arg(3);
-
-    let Some(n_value) = n_value else {
-        return Err(l.error("bad argument #2 to 'string.rep' (number expected)".to_string()));
-    };
-    let n = match value_to_integer(&n_value) {
-        Ok(i) => i,
-        Err(msg) => return Err(l.error(format!("bad argument #2 to 'string.rep' ({})", msg))),
-    };
+    let n = check_integer(l, 2)?;
+    let sep_value = opt_string(l, 3)?;
 
     if n <= 0 {
-        let empty = l.create_string("")?;
+        let empty = l.global_state_mut().const_strings.str_empty;
         l.push_value(empty)?;
         return Ok(1);
     }
 
-    let s_len = s_bytes.len() as i64;
-
-    // Check for overflow before multiplying
-    if s_len > 0 && n > MAX_STRING_SIZE / s_len {
-        return Err(l.error("resulting string too large".to_string()));
-    }
-
     // Get separator bytes WITHOUT cloning
     // Bind sep_value to extend its lifetime past the borrow
     let sep_owned = sep_value;
@@ -355,12 +243,19 @@ fn string_rep(l: &mut LuaState) -> LuaResult<usize> {
         &[]
     };
 
+    // (len + sep_len) * n - sep_len, checked before anything is allocated:
+    // the result must fit the string size cap and the VM's memory limit.
+    let s_len = s_bytes.len() as i64;
     let sep_len = sep_bytes.len() as i64;
-    let sep_total = sep_len.saturating_mul(n - 1);
-    let total_size = s_len.saturating_mul(n).saturating_add(sep_total);
-
-    if total_size > MAX_STRING_SIZE {
-        return Err(l.error("resulting string too large".to_string()));
+    let total_size = match (s_len + sep_len).checked_mul(n) {
+        Some(total) if total - sep_len <= MAX_STRING_SIZE => total - sep_len,
+        _ => return Err(l.error("resulting string too large".to_string())),
+    };
+    l.reserve_memory(total_size as usize)?;
+    if total_size == 0 {
+        let empty = l.global_state_mut().const_strings.str_empty;
+        l.push_value(empty)?;
+        return Ok(1);
     }
 
     // Pre-allocate result with exact capacity
@@ -392,13 +287,8 @@ fn string_rep(l: &mut LuaState) -> LuaResult<usize> {
 /// string.reverse(s) - Reverse string
 /// OPTIMIZED: Skip UTF-8 validation for ASCII strings (reversed ASCII is still valid UTF-8)
 fn string_reverse(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l.get_arg(1).ok_or_else(|| {
-        l.error("bad argument #1 to 'string.reverse' (string expected)".to_string())
-    })?;
-
-    let Some(s_bytes) = s_value.as_bytes() else {
-        return Err(l.error("bad argument #1 to 'string.reverse' (string expected)".to_string()));
-    };
+    let s_value = check_string(l, 1)?;
+    let s_bytes = s_value.as_bytes().unwrap_or_default();
 
     let len = s_bytes.len();
     let is_ascii = s_bytes.is_ascii();
@@ -431,30 +321,10 @@ fn string_reverse(l: &mut LuaState) -> LuaResult<usize> {
 
 /// string.sub(s, i [, j]) - Extract substring
 fn string_sub(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l
-        .get_arg(1)
-        .ok_or_else(|| debug::argerror(l, 1, "string expected"))?;
-
-    let Some(s_bytes) = s_value.as_bytes() else {
-        return Err(debug::arg_typeerror(l, 1, "string", &s_value));
-    };
-
-    let i_value = l
-        .get_arg(2)
-        .ok_or_else(|| debug::argerror(l, 2, "number expected"))?;
-    let i = match value_to_integer(&i_value) {
-        Ok(i) => i,
-        Err(msg) => return Err(debug::argerror(l, 2, msg)),
-    };
-
-    let j = l
-        .get_arg(3)
-        .map(|v| match value_to_integer(&v) {
-            Ok(i) => Ok(i),
-            Err(msg) => Err(debug::argerror(l, 3, msg)),
-        })
-        .transpose()?
-        .unwrap_or(-1);
+    let s_value = check_string(l, 1)?;
+    let s_bytes = s_value.as_bytes().unwrap_or_default();
+    let i = check_integer(l, 2)?;
+    let j = opt_integer(l, 3, -1)?;
 
     // Get string length and compute byte indices
     let (start_byte, end_byte) = {
@@ -483,7 +353,7 @@ fn string_sub(l: &mut LuaState) -> LuaResult<usize> {
     } else if start_byte == 0 && end_byte == s_bytes.len() {
         s_value
     } else {
-        l.create_bytes(&s_bytes[start_byte..end_byte])?
+        l.create_substring(&s_value, start_byte..end_byte)?
     };
     l.push_value(result_value)?;
     Ok(1)
@@ -492,29 +362,12 @@ fn string_sub(l: &mut LuaState) -> LuaResult<usize> {
 /// string.find(s, pattern [, init [, plain]]) - Find pattern
 /// ULTRA-OPTIMIZED: Avoid string cloning in hot path
 fn string_find(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'find' (string expected)".to_string()))?;
+    let s_value = check_string(l, 1)?;
+    let s_bytes = s_value.as_str_bytes().unwrap_or_default();
+    let pattern_value = check_string(l, 2)?;
+    let pat_bytes = pattern_value.as_str_bytes().unwrap_or_default();
 
-    // Get string data - handle both string and binary types
-    let s_bytes = if let Some(b) = s_value.as_str_bytes() {
-        b
-    } else {
-        return Err(l.error("bad argument #1 to 'find' (string expected)".to_string()));
-    };
-
-    let pattern_value = l
-        .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'find' (string expected)".to_string()))?;
-
-    // Get pattern data - handle both string and binary types
-    let pat_bytes = if let Some(b) = pattern_value.as_str_bytes() {
-        b
-    } else {
-        return Err(l.error("bad argument #2 to 'find' (string expected)".to_string()));
-    };
-
-    let init = l.get_arg(3).and_then(|v| v.as_integer()).unwrap_or(1);
+    let init = opt_integer(l, 3, 1)?;
     let plain = l.get_arg(4).map(|v| v.is_truthy()).unwrap_or(false);
 
     // Convert Lua 1-based index (with negative support) to Rust 0-based index
@@ -566,6 +419,7 @@ fn string_find(l: &mut LuaState) -> LuaResult<usize> {
         // Complex pattern matching
         match pattern::find(s_bytes, pat_bytes, start_pos) {
             Ok(Some((start, end, captures))) => {
+                l.check_stack_msg(2 + captures.len(), "too many captures")?;
                 l.push_value(LuaValue::integer((start + 1) as i64))?;
                 l.push_value(LuaValue::integer(end as i64))?;
 
@@ -587,28 +441,19 @@ fn string_find(l: &mut LuaState) -> LuaResult<usize> {
                 l.push_value(LuaValue::nil())?;
                 Ok(1)
             }
-            Err(e) => Err(l.error(format!("invalid pattern: {}", e))),
+            Err(e) => Err(l.error(e)),
         }
     }
 }
 
 /// string.match(s, pattern [, init]) - Match pattern
 fn string_match(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'match' (string expected)".to_string()))?;
-    let Some(s_bytes) = s_value.as_str_bytes() else {
-        return Err(l.error("bad argument #1 to 'match' (string expected)".to_string()));
-    };
-
-    let pattern_value = l
-        .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'match' (string expected)".to_string()))?;
-    let Some(pat_bytes) = pattern_value.as_str_bytes() else {
-        return Err(l.error("bad argument #2 to 'match' (string expected)".to_string()));
-    };
+    let s_value = check_string(l, 1)?;
+    let s_bytes = s_value.as_str_bytes().unwrap_or_default();
+    let pattern_value = check_string(l, 2)?;
+    let pat_bytes = pattern_value.as_str_bytes().unwrap_or_default();
 
-    let init = l.get_arg(3).and_then(|v| v.as_integer()).unwrap_or(1);
+    let init = opt_integer(l, 3, 1)?;
     let start_pos = if init > 0 {
         (init - 1) as usize
     } else if init < 0 {
@@ -631,6 +476,7 @@ fn string_match(l: &mut LuaState) -> LuaResult<usize> {
                 Ok(1)
             } else {
                 let ncaps = captures.len();
+                l.check_stack_msg(ncaps, "too many captures")?;
                 for cap in &captures {
                     match cap {
                         pattern::CaptureValue::Substring(s, e) => {
@@ -649,37 +495,26 @@ fn string_match(l: &mut LuaState) -> LuaResult<usize> {
             l.push_value(LuaValue::nil())?;
             Ok(1)
         }
-        Err(e) => Err(l.error(format!("invalid pattern: {}", e))),
+        Err(e) => Err(l.error(e)),
     }
 }
 
 /// string.gsub(s, pattern, repl [, n]) - Global substitution
-/// NOTE: Only string replacement is currently implemented
-/// TODO: Function and table replacement need protected_call support in LuaState API
 fn string_gsub(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'gsub' (string expected)".to_string()))?;
-
-    let s_bytes = s_value
-        .as_str_bytes()
-        .ok_or_else(|| l.error("bad argument #1 to 'gsub' (string expected)".to_string()))?;
-
-    let pattern_value = l
-        .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'gsub' (string expected)".to_string()))?;
-    let pat_bytes = pattern_value
-        .as_str_bytes()
-        .ok_or_else(|| l.error("bad argument #2 to 'gsub' (string expected)".to_string()))?;
-
-    let repl_value = l
-        .get_arg(3)
-        .ok_or_else(|| l.error("bad argument #3 to 'gsub' (value expected)".to_string()))?;
+    let s_value = check_string(l, 1)?;
+    let s_bytes = s_value.as_str_bytes().unwrap_or_default();
+    let pattern_value = check_string(l, 2)?;
+    let pat_bytes = pattern_value.as_str_bytes().unwrap_or_default();
+
+    let mut repl_value = l.get_arg(3).unwrap_or_default();
+    if repl_value.is_number() {
+        repl_value = check_string(l, 3)?;
+    }
 
-    let max = l
-        .get_arg(4)
-        .and_then(|v| v.as_integer())
-        .map(|n| n as usize);
+    let max = match l.get_arg(4) {
+        Some(v) if !v.is_nil() => Some(check_integer(l, 4)?.max(0) as usize),
+        _ => None,
+    };
 
     // String replacement
     if let Some(repl_bytes) = repl_value.as_str_bytes() {
@@ -692,122 +527,70 @@ fn string_gsub(l: &mut LuaState) -> LuaResult<usize> {
             }
             Err(e) => Err(l.error(e)),
         }
-    } else if repl_value.is_function() {
-        let matches = match pattern::find_all_matches(s_bytes, pat_bytes, 0, max) {
-            Ok(m) => m,
-            Err(e) => return Err(l.error(format!("invalid pattern: {}", e))),
+    } else if repl_value.is_table() || l.is_callable(&repl_value) {
+        // The replacement can run any Lua code, including another gsub on
+        // this string, so match against owned copies and keep only offsets
+        // across each call.
+        let subject = s_bytes.to_vec();
+        let pat = pat_bytes.to_vec();
+        let mut cursor = match pattern::MatchCursor::new(&pat, 0) {
+            Ok(c) => c,
+            Err(e) => return Err(l.error(e)),
         };
         let mut result: Vec<u8> = Vec::new();
         let mut last_end = 0;
         let mut count = 0;
+        // Reused across matches so each replacement call needs no new Vec
+        let mut args: Vec<LuaValue> = Vec::new();
+
+        while max.is_none_or(|max| count < max) {
+            let m = match cursor.next_match(&subject, &pat) {
+                Ok(Some(m)) => m,
+                Ok(None) => break,
+                Err(e) => return Err(l.error(e)),
+            };
+            result.extend_from_slice(&subject[last_end..m.start]);
 
-        for m in &matches {
-            result.extend_from_slice(&s_bytes[last_end..m.start]);
-
-            let args = if m.captures.is_empty() {
-                vec![create_string_or_binary(l, &s_bytes[m.start..m.end])?]
+            args.clear();
+            if m.captures.is_empty() {
+                args.push(create_string_or_binary(l, &subject[m.start..m.end])?);
             } else {
-                let mut captures = vec![];
                 for cap in &m.captures {
                     match cap {
                         pattern::CaptureValue::Substring(start, end) => {
-                            captures.push(create_string_or_binary(l, &s_bytes[*start..*end])?)
+                            args.push(create_string_or_binary(l, &subject[*start..*end])?)
                         }
                         pattern::CaptureValue::Position(p) => {
-                            captures.push(LuaValue::integer(*p as i64))
+                            args.push(LuaValue::integer(*p as i64))
                         }
                     }
                 }
-                captures
-            };
-
-            match l.pcall(repl_value, args) {
-                Ok((success, results)) => {
-                    if success {
-                        if results.is_empty()
-                            || results[0].is_nil()
-                            || results[0] == LuaValue::boolean(false)
-                        {
-                            // No return value, nil, or false: use original match
-                            result.extend_from_slice(&s_bytes[m.start..m.end]);
-                        } else if let Some(s) = results[0].as_str_bytes() {
-                            result.extend_from_slice(s);
-                        } else if let Some(n) = results[0].as_integer() {
-                            result.extend_from_slice(n.to_string().as_bytes());
-                        } else if let Some(n) = results[0].as_number() {
-                            result.extend_from_slice(n.to_string().as_bytes());
-                        } else {
-                            return Err(l.error(format!(
-                                "invalid replacement value (a {})",
-                                results[0].type_name()
-                            )));
-                        }
-                    } else {
-                        return Err(l.error(format!(
-                            "error calling replacement function: {}",
-                            results
-                                .first()
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("unknown error")
-                        )));
-                    }
-                }
-                Err(e) => return Err(e),
             }
 
-            last_end = m.end;
-            count += 1;
-        }
-
-        result.extend_from_slice(&s_bytes[last_end..]);
-
-        let result_val = create_string_or_binary(l, &result)?;
-        l.push_value(result_val)?;
-        l.push_value(LuaValue::integer(count as i64))?;
-        Ok(2)
-    } else if repl_value.is_table() {
-        // Table replacement
-        let matches = match pattern::find_all_matches(s_bytes, pat_bytes, 0, max) {
-            Ok(m) => m,
-            Err(e) => return Err(l.error(format!("invalid pattern: {}", e))),
-        };
-        let mut result: Vec<u8> = Vec::new();
-        let mut last_end = 0;
-        let mut count = 0;
-
-        for m in &matches {
-            // Copy text before match
-            result.extend_from_slice(&s_bytes[last_end..m.start]);
-
-            // Table lookup
-            let key = if m.captures.is_empty() {
-                // No captures, use whole match as key
-                create_string_or_binary(l, &s_bytes[m.start..m.end])?
+            // Tables are indexed by the first capture (even when they have a
+            // `__call`, as in C Lua); any other callable gets every capture
+            let value = if !repl_value.is_table() {
+                l.call_value(repl_value, &args)?
+                    .first()
+                    .copied()
+                    .unwrap_or_default()
             } else {
-                // Use first capture as key
-                match m.captures.get(0).unwrap() {
-                    pattern::CaptureValue::Substring(start, end) => {
-                        create_string_or_binary(l, &s_bytes[*start..*end])?
-                    }
-                    pattern::CaptureValue::Position(p) => LuaValue::integer(*p as i64),
-                }
+                l.table_get(&repl_value, &args[0])?.unwrap_or_default()
             };
 
-            let lookup_result = l.table_get(&repl_value, &key)?.unwrap_or(LuaValue::nil());
-
-            if lookup_result.is_nil() || lookup_result == LuaValue::boolean(false) {
-                // nil or false means no replacement, use original match
-                result.extend_from_slice(&s_bytes[m.start..m.end]);
-            } else if let Some(s) = lookup_result.as_str_bytes() {
+            if value.is_nil() || value == LuaValue::boolean(false) {
+                // nil or false keeps the original match
+                result.extend_from_slice(&subject[m.start..m.end]);
+            } else if let Some(s) = value.as_str_bytes() {
                 result.extend_from_slice(s);
-            } else if let Some(n) = lookup_result.as_integer() {
-                result.extend_from_slice(n.to_string().as_bytes());
-            } else if let Some(n) = lookup_result.as_number() {
+            } else if let Some(n) = value.as_integer_strict() {
                 result.extend_from_slice(n.to_string().as_bytes());
+            } else if let Some(n) = value.as_number() {
+                result.extend_from_slice(lua_float_to_string(n).as_bytes());
             } else {
                 return Err(l.error(format!(
                     "invalid replacement value (a {})",
-                    lookup_result.type_name()
+                    value.type_name()
                 )));
             }
 
@@ -815,15 +598,19 @@ fn string_gsub(l: &mut LuaState) -> LuaResult<usize> {
             count += 1;
         }
 
-        // Copy remaining text
-        result.extend_from_slice(&s_bytes[last_end..]);
+        result.extend_from_slice(&subject[last_end..]);
 
         let result_val = create_string_or_binary(l, &result)?;
         l.push_value(result_val)?;
         l.push_value(LuaValue::integer(count as i64))?;
         Ok(2)
     } else {
-        Err(l.error("bad argument #3 to 'gsub' (string/function/table expected)".to_string()))
+        Err(debug::arg_typeerror(
+            l,
+            3,
+            "string/function/table",
+            &repl_value,
+        ))
     }
 }
 
@@ -831,28 +618,18 @@ fn string_gsub(l: &mut LuaState) -> LuaResult<usize> {
 /// Usage: for capture in string.gmatch(s, pattern) do ... end
 /// OPTIMIZED: Lazy iterator — matches one at a time instead of pre-computing all
 fn string_gmatch(l: &mut LuaState) -> LuaResult<usize> {
-    let s_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'gmatch' (string expected)".to_string()))?;
-
-    let pattern_value = l
-        .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'gmatch' (string expected)".to_string()))?;
-
-    // Validate args
-    let s_bytes = s_value
-        .as_str_bytes()
-        .ok_or_else(|| l.error("bad argument #1 to 'gmatch' (string expected)".to_string()))?;
-    let _pat_bytes = pattern_value
-        .as_str_bytes()
-        .ok_or_else(|| l.error("bad argument #2 to 'gmatch' (string expected)".to_string()))?;
-
-    if let Err(e) = pattern::validate(_pat_bytes) {
-        return Err(l.error(format!("invalid pattern: {}", e)));
+    let s_value = check_string(l, 1)?;
+    let s_bytes = s_value.as_str_bytes().unwrap_or_default();
+    let pattern_value = check_string(l, 2)?;
+    let pat_bytes = pattern_value.as_str_bytes().unwrap_or_default();
+
+    // A leading '^' is not an anchor here: gmatch_find matches it literally
+    if let Err(e) = pattern::validate(pat_bytes) {
+        return Err(l.error(e));
     }
 
     // Handle init parameter (3rd arg, default 1, can be negative)
-    let init = l.get_arg(3).and_then(|v| v.as_integer()).unwrap_or(1);
+    let init = opt_integer(l, 3, 1)?;
     let start_pos = if init > 0 {
         (init - 1) as usize
     } else if init < 0 {
@@ -922,7 +699,7 @@ fn gmatch_iterator_lazy(l: &mut LuaState) -> LuaResult<usize> {
             break; // past end of string
         }
 
-        match pattern::find_assume_valid(s_bytes, pat_bytes, search_pos) {
+        match pattern::gmatch_find(s_bytes, pat_bytes, search_pos) {
             Ok(Some((start, end, captures))) => {
                 // Skip if match end equals lastmatch (C Lua: e != gm->lastmatch)
                 if lastmatch >= 0 && end == lastmatch as usize {
@@ -945,6 +722,7 @@ fn gmatch_iterator_lazy(l: &mut LuaState) -> LuaResult<usize> {
                     return Ok(1);
                 } else {
                     let ncaps = captures.len();
+                    l.check_stack_msg(ncaps, "too many captures")?;
                     for cap in &captures {
                         match cap {
                             pattern::CaptureValue::Substring(s, e) => {
@@ -960,7 +738,7 @@ fn gmatch_iterator_lazy(l: &mut LuaState) -> LuaResult<usize> {
                 }
             }
             Ok(None) => break,
-            Err(e) => return Err(l.error(format!("invalid pattern: {}", e))),
+            Err(e) => return Err(l.error(e)),
         }
     }
 
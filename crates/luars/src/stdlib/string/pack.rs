@@ -17,6 +17,10 @@
 
 use crate::LuaValue;
 use crate::lua_vm::{LuaResult, LuaState};
+use crate::stdlib::auxlib::{check_integer, check_number, check_string, opt_integer};
+use crate::stdlib::basic::parse_number::parse_lua_number;
+use crate::stdlib::debug::arg_typeerror;
+use crate::stdlib::debug::argerror;
 
 /// Endianness for pack/unpack operations
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -165,14 +169,25 @@ fn checked_add_size(current: usize, add: usize) -> Result<usize, String> {
     Ok(current + add)
 }
 
+/// A numeric argument for the 8-byte integer formats, which also keep the
+/// bits of floats: numeric strings are converted like luaL_checknumber does.
+fn number_arg(l: &mut LuaState, idx: usize) -> LuaResult<LuaValue> {
+    let v = l.get_arg(idx).unwrap_or_default();
+    if v.is_number() {
+        return Ok(v);
+    }
+    match parse_lua_number(v.as_str().unwrap_or("")) {
+        n if n.is_number() => Ok(n),
+        _ => Err(arg_typeerror(l, idx, "number", &v)),
+    }
+}
+
 /// string.pack(fmt, v1, v2, ...) - Pack values into binary string
 pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
-    let fmt_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'pack' (string expected)".to_string()))?;
+    let fmt_value = check_string(l, 1)?;
 
     let Some(fmt_str) = fmt_value.as_str() else {
-        return Err(l.error("bad argument #1 to 'pack' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     };
     let fmt_str = fmt_str.to_string();
 
@@ -192,12 +207,7 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let n = l
-                    .get_arg(value_idx)
-                    .and_then(|v| v.as_integer())
-                    .ok_or_else(|| {
-                        l.error("bad argument to 'pack' (number expected)".to_string())
-                    })?;
+                let n = check_integer(l, value_idx)?;
                 result.push((n & 0xFF) as u8);
                 value_idx += 1;
             }
@@ -207,12 +217,7 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let n = l
-                    .get_arg(value_idx)
-                    .and_then(|v| v.as_integer())
-                    .ok_or_else(|| {
-                        l.error("bad argument to 'pack' (number expected)".to_string())
-                    })?;
+                let n = check_integer(l, value_idx)?;
                 result.push((n & 0xFF) as u8);
                 value_idx += 1;
             }
@@ -223,12 +228,7 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let n = l
-                    .get_arg(value_idx)
-                    .and_then(|v| v.as_integer())
-                    .ok_or_else(|| {
-                        l.error("bad argument to 'pack' (number expected)".to_string())
-                    })? as i16;
+                let n = check_integer(l, value_idx)? as i16;
                 result.extend_from_slice(&endianness.to_bytes(n));
                 value_idx += 1;
             }
@@ -239,12 +239,7 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let n = l
-                    .get_arg(value_idx)
-                    .and_then(|v| v.as_integer())
-                    .ok_or_else(|| {
-                        l.error("bad argument to 'pack' (number expected)".to_string())
-                    })? as u16;
+                let n = check_integer(l, value_idx)? as u16;
                 result.extend_from_slice(&endianness.to_bytes(n));
                 value_idx += 1;
             }
@@ -280,12 +275,7 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let val = l
-                    .get_arg(value_idx)
-                    .and_then(|v| v.as_integer())
-                    .ok_or_else(|| {
-                        l.error("bad argument to 'pack' (number expected)".to_string())
-                    })?;
+                let val = check_integer(l, value_idx)?;
 
                 // For size < 8, check if value fits in the specified number of bytes
                 if size < 8 {
@@ -364,12 +354,7 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let val_i64 = l
-                    .get_arg(value_idx)
-                    .and_then(|v| v.as_integer())
-                    .ok_or_else(|| {
-                        l.error("bad argument to 'pack' (number expected)".to_string())
-                    })?;
+                let val_i64 = check_integer(l, value_idx)?;
 
                 // Check for negative values in unsigned format
                 if val_i64 < 0 {
@@ -429,12 +414,7 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let n = l
-                    .get_arg(value_idx)
-                    .and_then(|v| v.as_number())
-                    .ok_or_else(|| {
-                        l.error("bad argument to 'pack' (number expected)".to_string())
-                    })? as f32;
+                let n = check_number(l, value_idx)? as f32;
                 result.extend_from_slice(&endianness.to_bytes(n));
                 value_idx += 1;
             }
@@ -445,12 +425,7 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let n = l
-                    .get_arg(value_idx)
-                    .and_then(|v| v.as_number())
-                    .ok_or_else(|| {
-                        l.error("bad argument to 'pack' (number expected)".to_string())
-                    })?;
+                let n = check_number(l, value_idx)?;
                 result.extend_from_slice(&endianness.to_bytes(n));
                 value_idx += 1;
             }
@@ -461,17 +436,14 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let value = l.get_arg(value_idx).ok_or_else(|| {
-                    l.error("bad argument to 'pack' (number expected)".to_string())
-                })?;
+                let value = number_arg(l, value_idx)?;
                 let n = if let Some(i) = value.as_integer() {
                     i
-                } else if let Some(f) = value.as_number() {
+                } else {
+                    let f = value.as_number().unwrap_or_default();
                     // For float input that doesn't fit in i64, preserve as IEEE754 bit pattern
                     // This allows pack/unpack round-trip to maintain equality for large floats
                     f.to_bits() as i64
-                } else {
-                    return Err(l.error("bad argument to 'pack' (number expected)".to_string()));
                 };
                 result.extend_from_slice(&endianness.to_bytes(n));
                 value_idx += 1;
@@ -483,19 +455,16 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let value = l.get_arg(value_idx).ok_or_else(|| {
-                    l.error("bad argument to 'pack' (number expected)".to_string())
-                })?;
+                let value = number_arg(l, value_idx)?;
                 let n = if let Some(i) = value.as_integer() {
                     // Check for negative values
                     if i < 0 {
                         return Err(l.error("unsigned overflow".to_string()));
                     }
                     i as u64
-                } else if let Some(f) = value.as_number() {
-                    f.to_bits()
                 } else {
-                    return Err(l.error("bad argument to 'pack' (number expected)".to_string()));
+                    let f = value.as_number().unwrap_or_default();
+                    f.to_bits()
                 };
                 result.extend_from_slice(&endianness.to_bytes(n));
                 value_idx += 1;
@@ -506,12 +475,11 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let value = l.get_arg(value_idx).ok_or_else(|| {
-                    l.error("bad argument to 'pack' (number expected)".to_string())
-                })?;
+                let value = number_arg(l, value_idx)?;
                 let n = if let Some(i) = value.as_integer() {
                     i as u64
-                } else if let Some(f) = value.as_number() {
+                } else {
+                    let f = value.as_number().unwrap_or_default();
                     // For float input: apply modulo 2^64 then cast to u64
                     const TWO_POW_64: f64 = 18446744073709551616.0; // 2^64
                     let mut reduced = f % TWO_POW_64;
@@ -519,8 +487,6 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                         reduced += TWO_POW_64;
                     }
                     reduced as u64
-                } else {
-                    return Err(l.error("bad argument to 'pack' (number expected)".to_string()));
                 };
                 result.extend_from_slice(&endianness.to_bytes(n));
                 value_idx += 1;
@@ -531,12 +497,7 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let n = l
-                    .get_arg(value_idx)
-                    .and_then(|v| v.as_number())
-                    .ok_or_else(|| {
-                        l.error("bad argument to 'pack' (number expected)".to_string())
-                    })?;
+                let n = check_number(l, value_idx)?;
                 result.extend_from_slice(&endianness.to_bytes(n));
                 value_idx += 1;
             }
@@ -546,12 +507,8 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let s_value = l.get_arg(value_idx).ok_or_else(|| {
-                    l.error("bad argument to 'pack' (string expected)".to_string())
-                })?;
-                let Some(bytes) = s_value.as_bytes() else {
-                    return Err(l.error("bad argument to 'pack' (string expected)".to_string()));
-                };
+                let s_value = check_string(l, value_idx)?;
+                let bytes = s_value.as_bytes().unwrap_or_default();
                 // Check for embedded null characters
                 if bytes.contains(&0) {
                     return Err(l.error("string contains zeros".to_string()));
@@ -585,12 +542,8 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let s_value = l.get_arg(value_idx).ok_or_else(|| {
-                    l.error("bad argument to 'pack' (string expected)".to_string())
-                })?;
-                let Some(bytes) = s_value.as_bytes() else {
-                    return Err(l.error("bad argument to 'pack' (string expected)".to_string()));
-                };
+                let s_value = check_string(l, value_idx)?;
+                let bytes = s_value.as_bytes().unwrap_or_default();
 
                 // Check if string is too long for the specified size
                 if bytes.len() > size {
@@ -629,12 +582,8 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
                 if value_idx > argc {
                     return Err(l.error("bad argument to 'pack' (not enough values)".to_string()));
                 }
-                let s_value = l.get_arg(value_idx).ok_or_else(|| {
-                    l.error("bad argument to 'pack' (string expected)".to_string())
-                })?;
-                let Some(bytes) = s_value.as_bytes() else {
-                    return Err(l.error("bad argument to 'pack' (string expected)".to_string()));
-                };
+                let s_value = check_string(l, value_idx)?;
+                let bytes = s_value.as_bytes().unwrap_or_default();
                 let str_len = bytes.len();
 
                 // Check if string length fits in the specified size
@@ -818,12 +767,10 @@ pub fn string_pack(l: &mut LuaState) -> LuaResult<usize> {
 
 /// string.packsize(fmt) - Return size of packed data
 pub fn string_packsize(l: &mut LuaState) -> LuaResult<usize> {
-    let fmt_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'packsize' (string expected)".to_string()))?;
+    let fmt_value = check_string(l, 1)?;
 
     let Some(fmt_str) = fmt_value.as_str() else {
-        return Err(l.error("bad argument #1 to 'packsize' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     };
     let fmt_str = fmt_str.to_string();
 
@@ -1031,23 +978,16 @@ pub fn string_packsize(l: &mut LuaState) -> LuaResult<usize> {
 
 /// string.unpack(fmt, s [, pos]) - Unpack binary string
 pub fn string_unpack(l: &mut LuaState) -> LuaResult<usize> {
-    let fmt_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'unpack' (string expected)".to_string()))?;
+    let fmt_value = check_string(l, 1)?;
 
     let Some(fmt_str) = fmt_value.as_str() else {
-        return Err(l.error("bad argument #1 to 'unpack' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     };
 
-    let s_value = l
-        .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'unpack' (string expected)".to_string()))?;
-
-    let Some(bytes) = s_value.as_bytes() else {
-        return Err(l.error("bad argument #2 to 'unpack' (string expected)".to_string()));
-    };
+    let s_value = check_string(l, 2)?;
+    let bytes = s_value.as_bytes().unwrap_or_default();
 
-    let pos_arg = l.get_arg(3).and_then(|v| v.as_integer()).unwrap_or(1);
+    let pos_arg = opt_integer(l, 3, 1)?;
 
     // Handle negative indices (count from end)
     let pos = if pos_arg < 0 {
@@ -1678,6 +1618,7 @@ pub fn string_unpack(l: &mut LuaState) -> LuaResult<usize> {
 
     // Push all results
     let result_count = results.len();
+    l.check_stack_msg(result_count + 1, "too many results")?;
     for result in results {
         l.push_value(result)?;
     }
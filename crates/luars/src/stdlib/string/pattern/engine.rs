@@ -90,11 +90,9 @@ fn validate_set(pat: &[u8], i: usize) -> Result<usize, String> {
         j += 1;
     }
     while j < pat.len() && pat[j] != b']' {
+        // skip an escape; a '%' ending the pattern leaves the set unclosed
         if pat[j] == b'%' {
-            j += 1; // skip escape
-            if j >= pat.len() {
-                return Err("malformed pattern (ends with '%')".to_string());
-            }
+            j += 1;
         }
         j += 1;
     }
@@ -360,7 +358,8 @@ fn match_optional(ms: &mut MatchState, si: usize, pp: usize, rp: usize) -> Optio
 fn match_open_capture(ms: &mut MatchState, si: usize, pp: usize) -> Option<usize> {
     let n = ms.num_captures;
     if n >= LUA_MAXCAPTURES {
-        return None; // too many captures
+        ms.error = Some("too many captures".to_string());
+        return None;
     }
     ms.captures[n] = Capture {
         start: si,
@@ -403,6 +402,7 @@ fn match_close_capture(ms: &mut MatchState, si: usize, pp: usize) -> Option<usiz
 fn match_position_capture(ms: &mut MatchState, si: usize, pp: usize) -> Option<usize> {
     let n = ms.num_captures;
     if n >= LUA_MAXCAPTURES {
+        ms.error = Some("too many captures".to_string());
         return None;
     }
     ms.captures[n] = Capture {
@@ -421,7 +421,8 @@ fn match_position_capture(ms: &mut MatchState, si: usize, pp: usize) -> Option<u
 /// Balanced match %bxy
 fn match_balanced(ms: &mut MatchState, si: usize, pp: usize) -> Option<usize> {
     if pp + 3 >= ms.pat.len() {
-        return None; // malformed %b
+        ms.error = Some("malformed pattern (missing arguments to '%b')".to_string());
+        return None;
     }
     let open = ms.pat[pp + 2];
     let close = ms.pat[pp + 3];
@@ -452,7 +453,8 @@ fn match_balanced(ms: &mut MatchState, si: usize, pp: usize) -> Option<usize> {
 fn match_frontier(ms: &mut MatchState, si: usize, pp: usize) -> Option<usize> {
     // pp points to '%', pp+1 is 'f', pp+2 should be '['
     if pp + 2 >= ms.pat.len() || ms.pat[pp + 2] != b'[' {
-        return None; // malformed %f
+        ms.error = Some("missing '[' after '%f' in pattern".to_string());
+        return None;
     }
     let set_start = pp + 2; // points to '['
     let set_end = element_end(ms.pat, set_start); // past ']'
@@ -610,16 +612,18 @@ pub fn find(
     pat_bytes: &[u8],
     init: usize,
 ) -> Result<Option<(usize, usize, CaptureResults)>, String> {
-    find_impl(text, pat_bytes, init, true)
+    find_impl(text, pat_bytes, init, true, true)
 }
 
+/// `find` for `string.gmatch`, with a pattern already validated. As in
+/// lstrlib's gmatch_aux, a leading '^' is an ordinary character here.
 #[inline]
-pub fn find_assume_valid(
+pub fn gmatch_find(
     text: &[u8],
     pat_bytes: &[u8],
     init: usize,
 ) -> Result<Option<(usize, usize, CaptureResults)>, String> {
-    find_impl(text, pat_bytes, init, false)
+    find_impl(text, pat_bytes, init, false, false)
 }
 
 fn find_impl(
@@ -627,6 +631,7 @@ fn find_impl(
     pat_bytes: &[u8],
     init: usize,
     should_validate: bool,
+    allow_anchor: bool,
 ) -> Result<Option<(usize, usize, CaptureResults)>, String> {
     if init > text.len() {
         return Ok(None);
@@ -647,7 +652,7 @@ fn find_impl(
         validate_pattern(pat_bytes)?;
     }
 
-    let pp_start = if !pat_bytes.is_empty() && pat_bytes[0] == b'^' {
+    let pp_start = if allow_anchor && !pat_bytes.is_empty() && pat_bytes[0] == b'^' {
         1
     } else {
         0
@@ -673,85 +678,61 @@ fn find_impl(
     }
 }
 
-/// Find all matches of pattern in text (for gmatch/gsub).
-/// Operates on raw bytes (Lua semantics: each byte is a "character").
-pub fn find_all_matches(
-    text: &[u8],
-    pat_bytes: &[u8],
-    init: usize,
-    max: Option<usize>,
-) -> Result<Vec<MatchInfo>, String> {
-    // FAST PATH: plain non-empty pattern — use byte slice search loop
-    if is_plain_pattern(pat_bytes) && !pat_bytes.is_empty() {
-        return find_all_matches_plain(text, pat_bytes, init, max);
-    }
-
-    validate_pattern(pat_bytes)?;
-
-    let pp_start = if !pat_bytes.is_empty() && pat_bytes[0] == b'^' {
-        1
-    } else {
-        0
-    };
-    let anchored = pp_start == 1;
-
-    let mut matches = Vec::new();
-    let mut ms = MatchState::new(text, pat_bytes);
-    let mut si = init;
-    let mut last_was_nonempty = false;
-
-    while si <= text.len() {
-        if let Some(max_count) = max
-            && matches.len() >= max_count
-        {
-            break;
-        }
-
-        ms.reset();
-        if let Some(end_ci) = match_impl(&mut ms, si, pp_start) {
-            check_captures(&ms)?;
-            let is_empty = end_ci == si;
-
-            // Skip empty match right after non-empty match
-            if is_empty && last_was_nonempty {
-                if si < text.len() {
-                    si += 1;
-                }
-                last_was_nonempty = false;
-                continue;
-            }
-
-            let caps = extract_captures(&ms);
-            matches.push(MatchInfo {
-                start: si,
-                end: end_ci,
-                captures: caps,
-            });
+/// Resumable gsub match loop (the `while (n < max_s)` loop of lstrlib's
+/// str_gsub). Only offsets are kept between calls, so the caller can run Lua
+/// code between matches and pass the subject again for the next one.
+pub struct MatchCursor {
+    pos: usize,
+    last_match: Option<usize>,
+    pp_start: usize,
+    done: bool,
+}
 
-            if is_empty {
-                if si < text.len() {
-                    si += 1;
-                } else {
-                    break;
-                }
-                last_was_nonempty = false;
-            } else {
-                si = end_ci;
-                last_was_nonempty = true;
-            }
-        } else {
+impl MatchCursor {
+    pub fn new(pat_bytes: &[u8], init: usize) -> Result<Self, String> {
+        validate_pattern(pat_bytes)?;
+        Ok(Self {
+            pos: init,
+            last_match: None,
+            pp_start: usize::from(pat_bytes.first() == Some(&b'^')),
+            done: false,
+        })
+    }
+
+    /// Next match at or after the cursor. Text the cursor steps over is not
+    /// reported: callers copy `text[previous end..match.start]` themselves.
+    pub fn next_match(
+        &mut self,
+        text: &[u8],
+        pat_bytes: &[u8],
+    ) -> Result<Option<MatchInfo>, String> {
+        let mut ms = MatchState::new(text, pat_bytes);
+        while !self.done && self.pos <= text.len() {
+            let si = self.pos;
+            ms.reset();
+            let end = match_impl(&mut ms, si, self.pp_start);
             if let Some(err) = ms.error.take() {
                 return Err(err);
             }
-            if anchored || si >= text.len() {
-                break;
+            // An anchored pattern gets a single attempt
+            self.done = self.pp_start == 1;
+            match end {
+                Some(end_ci) if Some(end_ci) != self.last_match => {
+                    check_captures(&ms)?;
+                    self.pos = end_ci;
+                    self.last_match = Some(end_ci);
+                    return Ok(Some(MatchInfo {
+                        start: si,
+                        end: end_ci,
+                        captures: extract_captures(&ms),
+                    }));
+                }
+                _ if si < text.len() => self.pos += 1,
+                _ => self.done = true,
             }
-            si += 1;
-            last_was_nonempty = false;
         }
+        Ok(None)
     }
-
-    Ok(matches)
 }
 
 /// Global substitution with string replacement.
@@ -836,6 +817,10 @@ pub fn gsub(
                 si = end_ci;
                 last_was_nonempty = true;
             }
+            // An anchored pattern gets a single attempt
+            if anchored {
+                break;
+            }
         } else {
             if let Some(err) = ms.error.take() {
                 return Err(err);
@@ -965,37 +950,6 @@ fn gsub_plain(
     Ok((result, count))
 }
 
-/// Fast plain-text find_all_matches using byte slice search.
-fn find_all_matches_plain(
-    text: &[u8],
-    pattern: &[u8],
-    init: usize,
-    max: Option<usize>,
-) -> Result<Vec<MatchInfo>, String> {
-    let mut matches = Vec::new();
-    let mut pos = init;
-    while pos <= text.len() {
-        if let Some(max_n) = max
-            && matches.len() >= max_n
-        {
-            break;
-        }
-        if let Some(found) = find_bytes_in_slice(&text[pos..], pattern) {
-            let start = pos + found;
-            let end = start + pattern.len();
-            matches.push(MatchInfo {
-                start,
-                end,
-                captures: CaptureResults::new(),
-            });
-            pos = end;
-        } else {
-            break;
-        }
-    }
-    Ok(matches)
-}
-
 /// Handle %0 and %% substitution for plain patterns (bytes version).
 #[inline]
 fn substitute_plain_bytes(
@@ -12,6 +12,4 @@
 mod class;
 mod engine;
 
-pub use engine::{
-    CaptureValue, find, find_all_matches, find_assume_valid, gsub, is_plain_pattern, validate,
-};
+pub use engine::{CaptureValue, MatchCursor, find, gmatch_find, gsub, is_plain_pattern, validate};
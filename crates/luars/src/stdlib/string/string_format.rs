@@ -1,9 +1,14 @@
-use crate::{LuaResult, LuaValue, lua_vm::LuaState, stdlib::basic::lua_float_to_string};
+use crate::{LuaResult, LuaValue, lua_float_to_string, lua_value::format_g, lua_vm::LuaState};
 /// Optimized string.format implementation
 /// Reduced from 400+ lines to ~200 lines with better performance
 /// Uses std::fmt::Write for zero-allocation formatting directly to buffer
 use std::fmt::Write as FmtWrite;
 
+use crate::stdlib::auxlib::{check_integer, check_number, check_string};
+
+/// Size of lstrlib's conversion-spec buffer (MAX_FORMAT).
+const MAX_FORMAT: usize = 32;
+
 #[derive(Clone, Copy, Default, PartialEq, Eq)]
 struct FormatSpec {
     left_align: bool,
@@ -18,9 +23,7 @@ struct FormatSpec {
 /// string.format(formatstring, ...) - Format with various specifiers
 pub fn string_format(l: &mut LuaState) -> LuaResult<usize> {
     // Get format string
-    let format_str_value = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'format' (string expected)".to_string()))?;
+    let format_str_value = check_string(l, 1)?;
 
     // Get format string as bytes — avoid cloning.
     let fmt_bytes = format_str_value
@@ -70,13 +73,15 @@ pub fn string_format(l: &mut LuaState) -> LuaResult<usize> {
             let c = fmt_bytes[pos];
             if matches!(c, b'-' | b'+' | b' ' | b'#' | b'0' | b'1'..=b'9' | b'.') {
                 pos += 1;
-                if pos - flags_start > 200 {
-                    return Err(l.error("invalid format (too long)".to_string()));
-                }
             } else {
                 break;
             }
         }
+        // Same bound as lstrlib's getformat: the spec plus its conversion
+        // character must fit the C format buffer
+        if pos - flags_start + 1 >= MAX_FORMAT - 10 {
+            return Err(l.error("invalid conversion (format too long)".to_string()));
+        }
         let spec = parse_format_spec(&fmt_bytes[flags_start..pos]);
 
         // Get format character
@@ -91,12 +96,26 @@ pub fn string_format(l: &mut LuaState) -> LuaResult<usize> {
 
         // Get argument
         if arg_index > arg_count {
-            return Err(l.error(format!(
-                "bad argument #{} to 'format' (no value)",
-                arg_index
-            )));
-        }
-        let arg = l.get_arg(arg_index).unwrap_or_default();
+            return Err(crate::stdlib::debug::argerror(l, arg_index, "no value"));
+        }
+        // Numeric conversions take numeric strings too (luaL_checkinteger /
+        // luaL_checknumber), so convert those arguments up front.
+        let arg = match fmt_char {
+            'c' => {
+                let code = check_integer(l, arg_index)?;
+                if !(0..=255).contains(&code) {
+                    return Err(crate::stdlib::debug::argerror(
+                        l,
+                        arg_index,
+                        "value out of range",
+                    ));
+                }
+                LuaValue::integer(code)
+            }
+            'd' | 'i' | 'o' | 'u' | 'x' | 'X' => LuaValue::integer(check_integer(l, arg_index)?),
+            'a' | 'A' | 'e' | 'E' | 'f' | 'g' | 'G' => LuaValue::float(check_number(l, arg_index)?),
+            _ => l.get_arg(arg_index).unwrap_or_default(),
+        };
         arg_index += 1;
 
         // Format based on type
@@ -237,17 +256,16 @@ fn validate_format(spec: &FormatSpec, fmt_char: char, l: &mut LuaState) -> LuaRe
 
 // Helper functions - all inline for performance
 
+// Numeric arguments were already checked and converted by the caller.
 #[inline]
 fn get_num(arg: &LuaValue, _l: &LuaState) -> Result<f64, String> {
-    arg.as_number()
-        .or_else(|| arg.as_integer().map(|i| i as f64))
+    arg.as_float()
         .ok_or_else(|| "bad argument to 'format' (number expected)".to_string())
 }
 
 #[inline]
 fn get_int(arg: &LuaValue, _l: &LuaState) -> Result<i64, String> {
-    arg.as_integer()
-        .or_else(|| arg.as_number().map(|n| n as i64))
+    arg.as_integer_strict()
         .ok_or_else(|| "bad argument to 'format' (number expected)".to_string())
 }
 
@@ -259,10 +277,6 @@ fn format_char(
     l: &mut LuaState,
 ) -> LuaResult<()> {
     let num = get_int(arg, l).map_err(|e| l.error(e))?;
-    if !(0..=255).contains(&num) {
-        return Err(l.error("bad argument to 'format' (value out of range for %c)".to_string()));
-    }
-
     let ch = num as u8 as char;
 
     if let Some(w) = spec.width {
@@ -761,59 +775,14 @@ fn format_auto(
     let num = get_num(arg, l).map_err(|e| l.error(e))?;
     let precision = spec.precision.unwrap_or(6).max(1);
 
-    // Determine if we should use scientific notation
-    // %g uses scientific notation if exponent < -4 or >= precision
-    let abs_num = num.abs();
-    let use_scientific = if abs_num == 0.0 {
-        false
+    let mut result = if num.is_finite() {
+        format_g(num, precision)
     } else {
-        let exponent = abs_num.log10().floor() as i32;
-        exponent < -4 || exponent >= precision as i32
-    };
-
-    let mut result = if use_scientific {
-        // Use scientific notation
-        let exp_char = if upper { 'E' } else { 'e' };
-        let formatted = format!("{:.prec$e}", num, prec = precision - 1);
-
-        // Fix exponent format
-        if let Some(exp_pos) = formatted.find(exp_char) {
-            let (mantissa, exponent) = formatted.split_at(exp_pos);
-            let exp_str = &exponent[1..];
-
-            let (sign, exp_digits) = if exp_str.starts_with('+') || exp_str.starts_with('-') {
-                let s = exp_str.chars().next().unwrap();
-                (s.to_string(), &exp_str[1..])
-            } else {
-                ("+".to_string(), exp_str)
-            };
-
-            let exp_num = exp_digits.parse::<i32>().unwrap_or(0);
-            let formatted_exp = format!("{:03}", exp_num);
-
-            let mut res = format!("{}{}{}{}", mantissa, exp_char, sign, formatted_exp);
-            // Remove trailing zeros from mantissa
-            if res.contains('.')
-                && let Some(e_pos) = res.find(exp_char)
-            {
-                let mantissa_part = &res[..e_pos];
-                let exp_part = &res[e_pos..];
-                let trimmed = mantissa_part.trim_end_matches('0').trim_end_matches('.');
-                res = format!("{}{}", trimmed, exp_part);
-            }
-            res
-        } else {
-            formatted
-        }
-    } else {
-        // Use fixed notation
-        let mut res = format!("{:.prec$}", num, prec = precision - 1);
-        // Remove trailing zeros
-        if res.contains('.') {
-            res = res.trim_end_matches('0').trim_end_matches('.').to_string();
-        }
-        res
+        lua_float_to_string(num)
     };
+    if upper {
+        result.make_ascii_uppercase();
+    }
 
     // Add sign
     if !result.starts_with('-') {
@@ -824,6 +793,21 @@ fn format_auto(
         }
     }
 
+    // Apply width
+    if let Some(w) = spec.width
+        && result.len() < w
+    {
+        let padding = w - result.len();
+        if spec.left_align {
+            result.push_str(&" ".repeat(padding));
+        } else if spec.zero_pad && num.is_finite() {
+            let sign_len = usize::from(result.starts_with(['-', '+', ' ']));
+            result.insert_str(sign_len, &"0".repeat(padding));
+        } else {
+            result.insert_str(0, &" ".repeat(padding));
+        }
+    }
+
     buf.push_str(&result);
     Ok(())
 }
@@ -984,20 +968,10 @@ fn format_quoted(buf: &mut String, arg: &LuaValue, l: &mut LuaState) -> LuaResul
 
 /// Format pointer (%p) - shows address for tables/functions, "(null)" for others
 fn format_pointer(buf: &mut String, arg: &LuaValue, spec: &FormatSpec) -> LuaResult<()> {
-    use crate::lua_value::LuaValueKind;
-
-    // Format the pointer value first
-    let ptr_str = match arg.kind() {
-        LuaValueKind::String
-        | LuaValueKind::Table
-        | LuaValueKind::Function
-        | LuaValueKind::CFunction
-        | LuaValueKind::Userdata
-        | LuaValueKind::Thread => {
-            let ptr = arg.raw_ptr_repr() as usize;
-            format!("0x{:x}", ptr)
-        }
-        _ => "(null)".to_string(),
+    // Format the pointer value first; the same address tostring shows
+    let ptr_str = match arg.identity() {
+        Some(ptr) => format!("0x{:x}", ptr),
+        None => "(null)".to_string(),
     };
 
     // Apply width formatting if specified
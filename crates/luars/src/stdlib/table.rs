@@ -1,10 +1,12 @@
 // Table library
-// Implements: concat, insert, move, pack, remove, sort, unpack
+// Implements: concat, create, insert, move, pack, remove, sort, unpack
 
 use crate::lib_registry::LibraryModule;
-use crate::lua_value::LuaValue;
+use crate::lua_value::lua_float_to_string;
+use crate::lua_value::{LuaRawTable, LuaValue};
 use crate::lua_vm::{LuaResult, LuaState};
-use crate::stdlib::basic::lua_float_to_string;
+use crate::stdlib::auxlib::{check_any, check_integer, check_table, opt_integer, opt_string};
+use crate::stdlib::debug::argerror;
 use crate::stdlib::sort_table::table_sort;
 
 pub fn create_table_lib() -> LibraryModule {
@@ -20,39 +22,32 @@ pub fn create_table_lib() -> LibraryModule {
     })
 }
 
+/// Largest part size table.create preallocates; bigger requests fail
+/// with "table overflow" instead of attempting a multi-gigabyte allocation.
+const MAX_CREATE_SIZE: i64 = 1 << 26;
+
 /// table.create(narray [, nhash]) - Create a pre-allocated table (Lua 5.5)
+///
+/// The array part gets exactly `narray` slots and the hash part the next
+/// power of two >= `nhash` nodes, so filling those ranges never rehashes.
 fn table_create(l: &mut LuaState) -> LuaResult<usize> {
-    let narray = l.get_arg(1).and_then(|v| v.as_integer()).unwrap_or(0);
-
-    let nhash = l.get_arg(2).and_then(|v| v.as_integer()).unwrap_or(0);
+    let narray = check_integer(l, 1)?;
+    let nhash = opt_integer(l, 2, 0)?;
 
-    // Validate arguments
-    if narray < 0 {
-        return Err(l.error("bad argument #1 to 'create' (out of range)".to_string()));
+    // ltablib's tcreate: both sizes must fit an unsigned int
+    if !(0..=i32::MAX as i64).contains(&narray) {
+        return Err(argerror(l, 1, "out of range"));
     }
-    if nhash < 0 {
-        return Err(l.error("bad argument #2 to 'create' (out of range)".to_string()));
-    }
-
-    // Check for overflow (INT_MAX in Lua is i32::MAX)
-    if narray > i32::MAX as i64 {
-        return Err(l.error("bad argument #1 to 'create' (out of range)".to_string()));
+    if !(0..=i32::MAX as i64).contains(&nhash) {
+        return Err(argerror(l, 2, "out of range"));
     }
-    if nhash > i32::MAX as i64 {
-        return Err(l.error("bad argument #2 to 'create' (out of range)".to_string()));
-    }
-
-    // Limit to reasonable sizes to avoid allocation panics
-    let max_safe = 1 << 24; // ~16M elements
-    let na = std::cmp::min(narray as usize, max_safe);
-    let nh = if nhash as usize > max_safe {
+    if narray > MAX_CREATE_SIZE || nhash > MAX_CREATE_SIZE {
         return Err(l.error("table overflow".to_string()));
-    } else {
-        nhash as usize
-    };
+    }
 
-    // Create table with pre-allocated sizes
-    let table = l.create_table(na, nh)?;
+    let (na, nh) = (narray as u32, nhash as u32);
+    l.reserve_memory(LuaRawTable::alloc_bytes(na, nh))?;
+    let table = l.create_table(na as usize, nh as usize)?;
     l.push_value(table)?;
     Ok(1)
 }
@@ -64,44 +59,23 @@ fn table_create(l: &mut LuaState) -> LuaResult<usize> {
 /// 2. Strings + numbers (no binary): single String allocation, itoa/float formatting
 /// 3. Has binary: Vec<u8> buffer, try UTF-8 conversion at the end
 fn table_concat(l: &mut LuaState) -> LuaResult<usize> {
-    let table_val = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'concat' (table expected)".to_string()))?;
+    let table_val = check_table(l, 1)?;
 
-    let sep_value = l.get_arg(2);
-    let sep_owned: Vec<u8>;
-    let sep_text_owned: String;
+    // The separator stays anchored in its argument slot
+    let sep_value = opt_string(l, 2)?;
     let (sep_bytes, sep_text): (&[u8], Option<&str>) = match &sep_value {
-        Some(v) => {
-            if v.is_nil() {
-                (&[], Some(""))
-            } else if let Some(bytes) = v.as_bytes() {
-                sep_owned = bytes.to_vec();
-                if let Some(s) = v.as_str() {
-                    sep_text_owned = s.to_string();
-                    (&sep_owned, Some(&sep_text_owned))
-                } else {
-                    (&sep_owned, None)
-                }
-            } else {
-                return Err(l.error("bad argument #2 to 'concat' (string expected)".to_string()));
-            }
-        }
+        Some(v) => (v.as_bytes().unwrap_or_default(), v.as_str()),
         None => (&[], Some("")),
     };
 
-    if !table_val.is_table() {
-        return Err(l.error("bad argument #1 to 'concat' (table expected)".to_string()));
-    }
-
     let has_meta = table_val
         .as_table_mut()
         .map(|t| t.has_metatable())
         .unwrap_or(true);
 
-    let i = l.get_arg(3).and_then(|v| v.as_integer()).unwrap_or(1);
-    let j = match l.get_arg(4).and_then(|v| v.as_integer()) {
-        Some(j) => j,
+    let i = opt_integer(l, 3, 1)?;
+    let j = match l.get_arg(4).filter(|v| !v.is_nil()) {
+        Some(_) => check_integer(l, 4)?,
         None => {
             if has_meta {
                 l.obj_len(&table_val)?
@@ -113,7 +87,7 @@ fn table_concat(l: &mut LuaState) -> LuaResult<usize> {
 
     // If i > j, return empty string immediately
     if i > j {
-        let result = l.create_string("")?;
+        let result = l.global_state_mut().const_strings.str_empty;
         l.push_value(result)?;
         return Ok(1);
     }
@@ -135,7 +109,7 @@ fn table_concat(l: &mut LuaState) -> LuaResult<usize> {
             if let Some(bytes) = value.as_bytes() {
                 total_len += bytes.len();
                 needs_bytes |= value.as_str().is_none();
-            } else if value.as_integer().is_some() {
+            } else if value.as_integer_strict().is_some() {
                 total_len += 20; // max i64 string width
                 has_numbers = true;
             } else if value.as_number().is_some() {
@@ -160,7 +134,7 @@ fn table_concat(l: &mut LuaState) -> LuaResult<usize> {
                 let value = table.raw_geti(idx).unwrap_or(LuaValue::nil());
                 if let Some(bytes) = value.as_bytes() {
                     buf.extend_from_slice(bytes);
-                } else if let Some(ival) = value.as_integer() {
+                } else if let Some(ival) = value.as_integer_strict() {
                     let mut itoa_buf = itoa::Buffer::new();
                     buf.extend_from_slice(itoa_buf.format(ival).as_bytes());
                 } else if let Some(f) = value.as_number() {
@@ -193,7 +167,7 @@ fn table_concat(l: &mut LuaState) -> LuaResult<usize> {
                 let value = table.raw_geti(idx).unwrap_or(LuaValue::nil());
                 if let Some(s) = value.as_str() {
                     result.push_str(s);
-                } else if let Some(ival) = value.as_integer() {
+                } else if let Some(ival) = value.as_integer_strict() {
                     let mut itoa_buf = itoa::Buffer::new();
                     result.push_str(itoa_buf.format(ival));
                 } else if let Some(f) = value.as_number() {
@@ -214,7 +188,7 @@ fn table_concat(l: &mut LuaState) -> LuaResult<usize> {
             if let Some(bytes) = value.as_bytes() {
                 total_len += bytes.len();
                 needs_bytes |= value.as_str().is_none();
-            } else if value.as_integer().is_some() {
+            } else if value.as_integer_strict().is_some() {
                 total_len += 20;
             } else if value.as_number().is_some() {
                 total_len += 24;
@@ -233,7 +207,7 @@ fn table_concat(l: &mut LuaState) -> LuaResult<usize> {
                 let value = l.table_geti(&table_val, idx)?;
                 if let Some(bytes) = value.as_bytes() {
                     buf.extend_from_slice(bytes);
-                } else if let Some(ival) = value.as_integer() {
+                } else if let Some(ival) = value.as_integer_strict() {
                     let mut itoa_buf = itoa::Buffer::new();
                     buf.extend_from_slice(itoa_buf.format(ival).as_bytes());
                 } else if let Some(f) = value.as_number() {
@@ -252,7 +226,7 @@ fn table_concat(l: &mut LuaState) -> LuaResult<usize> {
                 let value = l.table_geti(&table_val, idx)?;
                 if let Some(s) = value.as_str() {
                     result.push_str(s);
-                } else if let Some(ival) = value.as_integer() {
+                } else if let Some(ival) = value.as_integer_strict() {
                     let mut itoa_buf = itoa::Buffer::new();
                     result.push_str(itoa_buf.format(ival));
                 } else if let Some(f) = value.as_number() {
@@ -269,15 +243,9 @@ fn table_concat(l: &mut LuaState) -> LuaResult<usize> {
 
 /// table.insert(list, [pos,] value) - Insert element
 fn table_insert(l: &mut LuaState) -> LuaResult<usize> {
-    let table_val = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'insert' (table expected)".to_string()))?;
+    let table_val = check_table(l, 1)?;
     let argc = l.arg_count();
 
-    if !table_val.is_table() {
-        return Err(l.error("bad argument #1 to 'insert' (table expected)".to_string()));
-    }
-
     // Fast path: no metatable → use raw operations (avoids obj_len overhead)
     let has_meta = table_val
         .as_table_mut()
@@ -286,9 +254,7 @@ fn table_insert(l: &mut LuaState) -> LuaResult<usize> {
 
     if argc == 2 {
         // table.insert(list, value) - append at end
-        let value = l
-            .get_arg(2)
-            .ok_or_else(|| l.error("bad argument #2 to 'insert' (value expected)".to_string()))?;
+        let value = check_any(l, 2)?;
         if has_meta {
             let len = l.obj_len(&table_val)?;
             l.table_seti(&table_val, len.wrapping_add(1), value)?;
@@ -311,22 +277,13 @@ fn table_insert(l: &mut LuaState) -> LuaResult<usize> {
         }
     } else if argc == 3 {
         // table.insert(list, pos, value)
-        let pos = l
-            .get_arg(2)
-            .ok_or_else(|| l.error("bad argument #2 to 'insert' (number expected)".to_string()))?
-            .as_integer()
-            .ok_or_else(|| l.error("bad argument #2 to 'insert' (number expected)".to_string()))?;
-
-        let value = l
-            .get_arg(3)
-            .ok_or_else(|| l.error("bad argument #3 to 'insert' (value expected)".to_string()))?;
+        let pos = check_integer(l, 2)?;
+        let value = check_any(l, 3)?;
 
         if has_meta {
             let len = l.obj_len(&table_val)?;
             if pos < 1 || pos > len + 1 {
-                return Err(
-                    l.error("bad argument #2 to 'insert' (position out of bounds)".to_string())
-                );
+                return Err(argerror(l, 2, "position out of bounds"));
             }
             // Shift elements up
             let mut i = len;
@@ -341,17 +298,23 @@ fn table_insert(l: &mut LuaState) -> LuaResult<usize> {
             let table = table_val.as_table_mut().unwrap();
             let len = table.len() as i64;
             if pos < 1 || pos > len + 1 {
-                return Err(
-                    l.error("bad argument #2 to 'insert' (position out of bounds)".to_string())
-                );
+                return Err(argerror(l, 2, "position out of bounds"));
             }
-            // Shift elements up using raw access
+            // Shift elements up using raw access. Opening t[len+1] first
+            // lets the array part grow, so the rest is usually one memmove;
+            // elements living in the hash part are moved one by one.
             let mut total_delta: isize = 0;
-            let mut i = len;
-            while i >= pos {
-                let val = table.raw_geti(i).unwrap_or(LuaValue::nil());
-                total_delta += table.raw_seti(i + 1, val);
-                i -= 1;
+            if pos <= len {
+                let last = table.raw_geti(len).unwrap_or(LuaValue::nil());
+                total_delta += table.raw_seti(len + 1, last);
+                if !table.shift_array_slots(pos, len - 1, true) {
+                    let mut i = len - 1;
+                    while i >= pos {
+                        let val = table.raw_geti(i).unwrap_or(LuaValue::nil());
+                        total_delta += table.raw_seti(i + 1, val);
+                        i -= 1;
+                    }
+                }
             }
             total_delta += table.raw_seti(pos, value);
             if total_delta != 0
@@ -374,13 +337,7 @@ fn table_insert(l: &mut LuaState) -> LuaResult<usize> {
 
 /// table.remove(list [, pos]) - Remove element
 fn table_remove(l: &mut LuaState) -> LuaResult<usize> {
-    let table_val = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'remove' (table expected)".to_string()))?;
-
-    if !table_val.is_table() {
-        return Err(l.error("bad argument #1 to 'remove' (table expected)".to_string()));
-    }
+    let table_val = check_table(l, 1)?;
 
     let has_meta = table_val
         .as_table_mut()
@@ -395,7 +352,7 @@ fn table_remove(l: &mut LuaState) -> LuaResult<usize> {
         let pos = l.get_arg(2).and_then(|v| v.as_integer()).unwrap_or(len);
 
         if has_pos_arg && pos != len && (pos < 1 || pos > len.wrapping_add(1)) {
-            return Err(l.error("bad argument #2 to 'remove' (position out of bounds)".to_string()));
+            return Err(argerror(l, 2, "position out of bounds"));
         }
 
         let removed = l.table_geti(&table_val, pos)?;
@@ -414,13 +371,17 @@ fn table_remove(l: &mut LuaState) -> LuaResult<usize> {
         let pos = l.get_arg(2).and_then(|v| v.as_integer()).unwrap_or(len);
 
         if has_pos_arg && pos != len && (pos < 1 || pos > len.wrapping_add(1)) {
-            return Err(l.error("bad argument #2 to 'remove' (position out of bounds)".to_string()));
+            return Err(argerror(l, 2, "position out of bounds"));
         }
 
         let removed = table.raw_geti(pos).unwrap_or(LuaValue::nil());
 
-        // Shift elements down using raw access
+        // Shift elements down using raw access: one memmove when
+        // t[pos..len] is in the array part, element by element otherwise
         let mut i = pos;
+        if pos < len && table.shift_array_slots(pos + 1, len, false) {
+            i = len;
+        }
         while i < len {
             let next_val = table.raw_geti(i + 1).unwrap_or(LuaValue::nil());
             table.raw_seti(i, next_val);
@@ -435,38 +396,16 @@ fn table_remove(l: &mut LuaState) -> LuaResult<usize> {
 
 /// table.move(a1, f, e, t [, a2]) - Move elements
 fn table_move(l: &mut LuaState) -> LuaResult<usize> {
-    let src_val = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'move' (table expected)".to_string()))?;
-
-    let f = l
-        .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'move' (number expected)".to_string()))?
-        .as_integer()
-        .ok_or_else(|| l.error("bad argument #2 to 'move' (number expected)".to_string()))?;
-
-    let e = l
-        .get_arg(3)
-        .ok_or_else(|| l.error("bad argument #3 to 'move' (number expected)".to_string()))?
-        .as_integer()
-        .ok_or_else(|| l.error("bad argument #3 to 'move' (number expected)".to_string()))?;
-
-    let t = l
-        .get_arg(4)
-        .ok_or_else(|| l.error("bad argument #4 to 'move' (number expected)".to_string()))?
-        .as_integer()
-        .ok_or_else(|| l.error("bad argument #4 to 'move' (number expected)".to_string()))?;
-
-    let dst_value = l.get_arg(5).unwrap_or(src_val);
-
-    if !src_val.is_table() {
-        return Err(l.error("bad argument #1 to 'move' (table expected)".to_string()));
-    }
-
-    // Validate destination is a table
-    if !dst_value.is_table() {
-        return Err(l.error("bad argument #5 to 'move' (table expected)".to_string()));
-    }
+    let f = check_integer(l, 2)?;
+    let e = check_integer(l, 3)?;
+    let t = check_integer(l, 4)?;
+
+    // Same order as tmove: the integers first, then the tables
+    let src_val = check_table(l, 1)?;
+    let dst_value = match l.get_arg(5) {
+        Some(v) if !v.is_nil() => check_table(l, 5)?,
+        _ => src_val,
+    };
 
     // Validation matching C Lua's tmove (ltablib.c):
     // 1. n = (unsigned)(e - f); if n >= LUA_MAXINTEGER error "too many"
@@ -581,13 +520,7 @@ fn table_pack(l: &mut LuaState) -> LuaResult<usize> {
 
 /// table.unpack(list [, i [, j]]) - Unpack table into values
 fn table_unpack(l: &mut LuaState) -> LuaResult<usize> {
-    let table_val = l
-        .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'unpack' (table expected)".to_string()))?;
-
-    if !table_val.is_table() {
-        return Err(l.error("bad argument #1 to 'unpack' (table expected)".to_string()));
-    }
+    let table_val = check_table(l, 1)?;
 
     let has_meta = table_val
         .as_table_mut()
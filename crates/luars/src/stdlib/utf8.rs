@@ -5,6 +5,7 @@ use crate::lib_registry::LibraryModule;
 use crate::lua_value::LuaValue;
 use crate::lua_vm::LuaResult;
 use crate::lua_vm::LuaState;
+use crate::stdlib::debug::argerror;
 
 pub fn create_utf8_lib() -> LibraryModule {
     let mut module = crate::lib_module!("utf8", {
@@ -108,9 +109,9 @@ fn encode_utf8_extended(x: u32) -> Vec<u8> {
 fn utf8_len(l: &mut LuaState) -> LuaResult<usize> {
     let s_value = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'len' (string expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "string expected"))?;
     let Some(bytes) = s_value.as_bytes() else {
-        return Err(l.error("bad argument #1 to 'len' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     };
 
     let len = bytes.len();
@@ -129,14 +130,12 @@ fn utf8_len(l: &mut LuaState) -> LuaResult<usize> {
         posi
     } > len as i64
     {
-        return Err(
-            l.error("bad argument #2 to 'len' (initial position out of bounds)".to_string())
-        );
+        return Err(argerror(l, 2, "initial position out of bounds"));
     }
     // luaL_argcheck: --posj < len
     posj -= 1;
     if posj >= len as i64 {
-        return Err(l.error("bad argument #3 to 'len' (final position out of bounds)".to_string()));
+        return Err(argerror(l, 3, "final position out of bounds"));
     }
 
     let mut n: i64 = 0;
@@ -197,9 +196,9 @@ fn utf8_char(l: &mut LuaState) -> LuaResult<usize> {
 fn utf8_codes(l: &mut LuaState) -> LuaResult<usize> {
     let s_value = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'codes' (string expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "string expected"))?;
     if s_value.as_bytes().is_none() {
-        return Err(l.error("bad argument #1 to 'codes' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     }
 
     // Create state table: {string = s, position = 0}
@@ -277,10 +276,10 @@ fn utf8_codes_iterator(l: &mut LuaState) -> LuaResult<usize> {
 fn utf8_codepoint(l: &mut LuaState) -> LuaResult<usize> {
     let s_value = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'codepoint' (string expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "string expected"))?;
     // Accept both string and binary values
     let Some(bytes) = s_value.as_bytes() else {
-        return Err(l.error("bad argument #1 to 'codepoint' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     };
 
     let len = bytes.len();
@@ -293,14 +292,16 @@ fn utf8_codepoint(l: &mut LuaState) -> LuaResult<usize> {
 
     // luaL_argcheck
     if posi < 1 {
-        return Err(l.error("bad argument #2 to 'codepoint' (out of bounds)".to_string()));
+        return Err(argerror(l, 2, "out of bounds"));
     }
     if pose > len as i64 {
-        return Err(l.error("bad argument #3 to 'codepoint' (out of bounds)".to_string()));
+        return Err(argerror(l, 3, "out of bounds"));
     }
     if posi > pose {
         return Ok(0); // empty interval
     }
+    // At most one code point per byte
+    l.check_stack_msg((pose - posi) as usize + 1, "string slice too long")?;
 
     let mut count = 0;
     let se = pose as usize; // end byte (1-based inclusive → byte index)
@@ -324,16 +325,16 @@ fn utf8_codepoint(l: &mut LuaState) -> LuaResult<usize> {
 fn utf8_offset(l: &mut LuaState) -> LuaResult<usize> {
     let s_value = l
         .get_arg(1)
-        .ok_or_else(|| l.error("bad argument #1 to 'offset' (string expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 1, "string expected"))?;
     let Some(bytes) = s_value.as_bytes() else {
-        return Err(l.error("bad argument #1 to 'offset' (string expected)".to_string()));
+        return Err(argerror(l, 1, "string expected"));
     };
 
     let n_value = l
         .get_arg(2)
-        .ok_or_else(|| l.error("bad argument #2 to 'offset' (number expected)".to_string()))?;
+        .ok_or_else(|| argerror(l, 2, "number expected"))?;
     let Some(n) = n_value.as_integer() else {
-        return Err(l.error("bad argument #2 to 'offset' (number expected)".to_string()));
+        return Err(argerror(l, 2, "number expected"));
     };
 
     let len = bytes.len();
@@ -352,7 +353,7 @@ fn utf8_offset(l: &mut LuaState) -> LuaResult<usize> {
         posi
     } > len as i64
     {
-        return Err(l.error("bad argument #3 to 'offset' (position out of bounds)".to_string()));
+        return Err(argerror(l, 3, "position out of bounds"));
     }
 
     let mut n = n;
@@ -1,17 +1,23 @@
 // Test module organization
 pub mod test_async;
 pub mod test_basic;
+pub mod test_bytecode_builder;
 pub mod test_control_flow;
 pub mod test_coroutine;
 pub mod test_io; // IO tests use test_data directory
+#[cfg(feature = "json")]
+pub mod test_json;
 pub mod test_math;
 pub mod test_metamethods;
 pub mod test_miri_smoke;
 pub mod test_operators;
 pub mod test_os; // OS library tests
 pub mod test_package;
+#[cfg(feature = "profiler")]
+pub mod test_profiler;
 pub mod test_string;
 pub mod test_syntax;
+pub mod test_syntax_tree;
 pub mod test_table;
 pub mod test_userdata;
 pub mod test_utf8;
@@ -21,7 +27,9 @@ pub mod test_advanced_calls;
 pub mod test_api_proposals;
 pub mod test_c_functions;
 pub mod test_functions;
+pub mod test_gc_barrier;
 pub mod test_gc_metamethods;
+pub mod test_gc_stats;
 pub mod test_rclosure;
 #[cfg(feature = "sandbox")]
 pub mod test_sandbox;
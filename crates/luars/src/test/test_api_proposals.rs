@@ -497,6 +497,123 @@ fn test_open_stdlibs() {
     assert_eq!(results[0].as_str(), Some("HELLO"));
 }
 
+#[test]
+fn test_open_stdlib_set() {
+    let libs = Stdlib::Basic | Stdlib::String | Stdlib::Table;
+    assert!(libs.contains(Stdlib::String));
+    assert!(libs.contains(Stdlib::Basic | Stdlib::Table));
+    assert!(!libs.contains(Stdlib::Math));
+    assert!(!libs.contains(Stdlib::All));
+    assert_eq!(
+        libs.iter().collect::<Vec<_>>(),
+        [Stdlib::Basic, Stdlib::String, Stdlib::Table]
+    );
+    assert_eq!(format!("{libs:?}"), "{Basic, String, Table}");
+    assert_eq!(libs & (Stdlib::String | Stdlib::Io), Stdlib::String.into());
+    assert_eq!(libs - Stdlib::Basic - Stdlib::Table, Stdlib::String.into());
+    assert!(StdlibSet::from(Stdlib::All).contains(Stdlib::Debug | Stdlib::Package));
+    assert!((StdlibSet::from(Stdlib::All) - Stdlib::All).is_empty());
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(libs).unwrap();
+    let results = vm
+        .main_state()
+        .execute("return ('x'):rep(3), table.concat({1, 2}), math, io")
+        .unwrap();
+    assert_eq!(results[0].as_str(), Some("xxx"));
+    assert_eq!(results[1].as_str(), Some("12"));
+    assert!(results[2].is_nil());
+    assert!(results[3].is_nil());
+}
+
+// ============================
+// SafeOption builder, LuaValue Debug format
+// ============================
+
+#[test]
+fn test_safe_option_builder() {
+    let option = SafeOption::builder()
+        .max_memory_mb(256)
+        .max_call_depth(200)
+        .strict_globals(true)
+        .build()
+        .unwrap();
+    assert_eq!(option.max_memory_limit, 256 * 1024 * 1024);
+    assert_eq!(option.max_call_depth, 200);
+    assert!(option.strict_globals);
+    // Everything not set keeps the default
+    let defaults = SafeOption::default();
+    assert_eq!(option.max_stack_size, defaults.max_stack_size);
+    assert_eq!(option.max_c_stack_depth, 200);
+    assert_eq!(defaults.max_stack_size, 1_000_000);
+    assert_eq!(defaults.max_call_depth, 1024);
+
+    let err = SafeOption::builder().max_memory_mb(0).build().unwrap_err();
+    assert_eq!(err.field, "max_memory_limit");
+    let err = SafeOption::builder()
+        .max_stack_size(10)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "max_stack_size must be between 40 and 2147483647 (got 10)"
+    );
+    let err = SafeOption::builder()
+        .max_c_stack_depth(1_000_000)
+        .build()
+        .unwrap_err();
+    assert_eq!(err.field, "max_c_stack_depth");
+    assert!(SafeOption::builder().max_call_depth(0).build().is_err());
+    let err = SafeOption::builder()
+        .max_coroutine_stack(1)
+        .build()
+        .unwrap_err();
+    assert_eq!(err.field, "max_coroutine_stack");
+
+    let mut vm = GlobalState::new(SafeOption::builder().max_call_depth(50).build().unwrap());
+    vm.open_stdlib(Stdlib::Basic).unwrap();
+    let results = vm
+        .main_state()
+        .execute(
+            "local function f(n) return n == 0 and 0 or 1 + f(n - 1) end \
+             return pcall(f, 10), pcall(f, 100)",
+        )
+        .unwrap();
+    assert_eq!(results[0].as_boolean(), Some(true));
+    assert_eq!(results[1].as_boolean(), Some(false));
+}
+
+#[test]
+fn test_lua_value_debug_format() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::Basic).unwrap();
+    let state = vm.main_state();
+    let results = state
+        .execute(r#"return nil, true, 42, 1.0, -0.5, "a\"b", "\xff\0", {}, print"#)
+        .unwrap();
+    let formatted: Vec<String> = results.iter().map(|v| format!("{v:?}")).collect();
+    assert_eq!(
+        formatted[..7],
+        [
+            "Nil",
+            "Boolean(true)",
+            "Integer(42)",
+            "Float(1.0)",
+            "Float(-0.5)",
+            r#"String("a\"b")"#,
+            r#"String(b"\xff\x00")"#,
+        ]
+    );
+    assert!(formatted[7].starts_with("Table#"), "{}", formatted[7]);
+    assert!(formatted[8].starts_with("CFunction#"), "{}", formatted[8]);
+    // The identity is the object's, not the handle's
+    assert_eq!(formatted[7], format!("{:?}", results[7].clone()));
+    assert_eq!(
+        format!("{:?}", results),
+        format!("[{}]", formatted.join(", "))
+    );
+}
+
 // ============================
 // P11: dofile (tested with a temp file)
 // ============================
@@ -4,10 +4,15 @@
 // from Lua code via the AsyncThread mechanism.
 
 use crate::lua_value::LuaUserdata;
-use crate::lua_vm::async_thread::AsyncReturnValue;
+use crate::lua_vm::async_thread::{AsyncReturnValue, async_sentinel_value};
 use crate::*;
+use std::cell::Cell;
 use std::fmt;
+use std::future::Future;
 use std::pin::Pin;
+use std::rc::Rc;
+use std::task::Poll;
+use std::time::Duration;
 
 /// Helper: create a VM with stdlib loaded
 fn new_vm() -> Pin<Box<GlobalState>> {
@@ -332,6 +337,147 @@ async fn test_create_async_thread_directly() {
     assert_eq!(results[0].as_integer(), Some(49));
 }
 
+// ============ Cancellation ============
+
+/// Register `never()`, an async function that never completes. `dropped`
+/// turns true once its future has been dropped.
+fn register_never(vm: &mut GlobalState) -> Rc<Cell<bool>> {
+    struct Guard(Rc<Cell<bool>>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    let dropped = Rc::new(Cell::new(false));
+    let flag = dropped.clone();
+    vm.main_state()
+        .register_async("never", move |_args| {
+            let guard = Guard(flag.clone());
+            async move {
+                let _guard = guard;
+                std::future::pending::<()>().await;
+                Ok(vec![])
+            }
+        })
+        .unwrap();
+    vm.main_state()
+        .register_function("task_count", |state| {
+            let count = state.global_state().async_task_count();
+            state.push_value(LuaValue::integer(count as i64))?;
+            Ok(1)
+        })
+        .unwrap();
+    dropped
+}
+
+#[test]
+fn test_coroutine_close_cancels_pending_future() {
+    let mut vm = new_vm();
+    let dropped = register_never(&mut vm);
+
+    // A plain coroutine resumed from Lua keeps the future it yielded on
+    let results = vm
+        .main_state()
+        .execute(
+            r#"
+            local co = coroutine.create(function() return never() end)
+            coroutine.resume(co)
+            local before = task_count()
+            local closed = coroutine.close(co)
+            return before, closed, task_count(), coroutine.status(co)
+        "#,
+        )
+        .unwrap();
+    assert_eq!(results[0].as_integer(), Some(1));
+    assert_eq!(results[1].as_boolean(), Some(true));
+    assert_eq!(results[2].as_integer(), Some(0));
+    assert_eq!(results[3].as_str(), Some("dead"));
+    assert!(dropped.get());
+    assert_eq!(vm.async_task_count(), 0);
+}
+
+#[test]
+fn test_collected_coroutine_drops_pending_future() {
+    let mut vm = new_vm();
+    let dropped = register_never(&mut vm);
+
+    vm.main_state()
+        .execute("coroutine.resume(coroutine.create(never))")
+        .unwrap();
+    assert_eq!(vm.async_task_count(), 1);
+    vm.main_state().execute("collectgarbage()").unwrap();
+    assert!(dropped.get());
+    assert_eq!(vm.async_task_count(), 0);
+}
+
+#[tokio::test]
+async fn test_abort_async_tasks_fails_awaiting_thread() {
+    let mut vm = new_vm();
+    let dropped = register_never(&mut vm);
+
+    let chunk = vm.main_state().compile_chunk("return never()").unwrap();
+    let mut thread = Box::pin(vm.main_state().create_async_thread(chunk, vec![]).unwrap());
+    let first = std::future::poll_fn(|cx| Poll::Ready(thread.as_mut().poll(cx))).await;
+    assert!(first.is_pending());
+    assert_eq!(vm.async_task_count(), 1);
+
+    assert_eq!(vm.abort_async_tasks(), 1);
+    assert!(dropped.get());
+    assert_eq!(vm.async_task_count(), 0);
+    let err = thread.await.unwrap_err();
+    let msg = vm.main_state().get_error_message(err);
+    assert!(msg.contains("async task cancelled"), "{msg}");
+    assert_eq!(vm.abort_async_tasks(), 0);
+}
+
+#[tokio::test]
+async fn test_async_task_timeout_fails_awaiting_thread() {
+    let mut vm = new_vm();
+    let dropped = register_never(&mut vm);
+    vm.set_async_task_timeout(Some(Duration::from_millis(20)));
+
+    let chunk = vm.main_state().compile_chunk("return never()").unwrap();
+    let thread = vm.main_state().create_async_thread(chunk, vec![]).unwrap();
+    let err = tokio::time::timeout(Duration::from_secs(5), thread)
+        .await
+        .expect("timed task was never woken")
+        .unwrap_err();
+    let msg = vm.main_state().get_error_message(err);
+    assert!(msg.contains("async task timed out"), "{msg}");
+    assert!(dropped.get());
+    assert_eq!(vm.async_task_count(), 0);
+}
+
+#[tokio::test]
+async fn test_per_task_timeout_overrides_default() {
+    let mut vm = new_vm();
+    vm.set_async_task_timeout(Some(Duration::from_secs(3600)));
+    vm.main_state()
+        .register_function("hang", |state| {
+            state.set_pending_future_with_timeout(
+                Box::pin(async {
+                    std::future::pending::<()>().await;
+                    Ok(vec![])
+                }),
+                Some(Duration::from_millis(20)),
+            );
+            state.do_yield(vec![async_sentinel_value()])?;
+            Ok(0)
+        })
+        .unwrap();
+
+    let chunk = vm.main_state().compile_chunk("return hang()").unwrap();
+    let thread = vm.main_state().create_async_thread(chunk, vec![]).unwrap();
+    let err = tokio::time::timeout(Duration::from_secs(5), thread)
+        .await
+        .expect("timed task was never woken")
+        .unwrap_err();
+    let msg = vm.main_state().get_error_message(err);
+    assert!(msg.contains("async task timed out"), "{msg}");
+    assert_eq!(vm.async_task_count(), 0);
+}
+
 // ============ Async UserData return tests ============
 
 /// Test struct returned from async functions
@@ -55,6 +55,58 @@ fn test_tonumber() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_tonumber_numeral_grammar() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // Same cases the lexer, coercion and %d go through; results are
+    // compared with math.type so 16 and 16.0 differ.
+    let result = vm.main_state().execute(
+        r#"
+        local accepted = {
+            {"0x1p4", 16.0}, {"0X.8", 0.5}, {"  0x10  ", 16}, {"0x1P-2", 0.25},
+            {"0xA.8p1", 21.0}, {"0x.1", 0.0625}, {"-0x10", -16}, {"+0x10", 16},
+            {"0xffffffffffffffff", -1}, {"0x10000000000000001", 1},
+            {"1e2", 100.0}, {"+1e5", 100000.0}, {"5.", 5.0}, {".5", 0.5},
+            {"1.5E-2", 0.015}, {"\t1\n", 1}, {"\v\f 7 \r", 7},
+            {"9223372036854775807", math.maxinteger},
+            {"-9223372036854775808", math.mininteger},
+            {"9223372036854775808", 9223372036854775808.0},
+            {"-0x8000000000000000", math.mininteger},
+            {"1e500", math.huge}, {"-1e500", -math.huge},
+        }
+        for _, case in ipairs(accepted) do
+            local s, expected = case[1], case[2]
+            local got = tonumber(s)
+            assert(got == expected and math.type(got) == math.type(expected), s)
+            assert(math.type(s + 0) == math.type(expected), s)
+        end
+
+        local rejected = {
+            "", " ", "inf", "-inf", "nan", "infinity", "1_000", "0x1_0",
+            "1e", "1e+", "0x", "0x.", "0x1p", "0x1p+", ".", ".e1", "- 1",
+            "1 x", "1.2.3", "0b101", "1f", "1L", "1\0", "0x1.8q", "\u{a0}1",
+        }
+        for _, s in ipairs(rejected) do
+            assert(tonumber(s) == nil, s)
+            assert(not pcall(function() return s + 0 end), s)
+        end
+
+        assert(string.format("%d", "0x10") == "16")
+        assert(string.format("%x", " 255.0 ") == "ff")
+        assert(string.format("%.1f", "0x1p-1") == "0.5")
+        assert(not pcall(string.format, "%d", "1_0"))
+        assert(select(2, pcall(string.format, "%d", "3.5")):find("no integer representation"))
+        assert(select(2, pcall(string.format, "%d", 3.5)):find("no integer representation"))
+        assert(math.tointeger("0x10") == 16 and math.tointeger(" 8.0 ") == 8)
+        assert(math.tointeger("8.5") == nil and math.tointeger("inf") == nil)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_tostring() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -326,6 +378,70 @@ fn test_load() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_load_reader_must_return_strings() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        for _, bad in ipairs({42, {}, true}) do
+            -- first call, and after pieces have accumulated
+            for _, pieces in ipairs({{}, {"return ", "1"}}) do
+                local i = 0
+                local f, err = load(function()
+                    i = i + 1
+                    if i <= #pieces then return pieces[i] end
+                    return bad
+                end)
+                assert(f == nil and err == "reader function must return a string", err)
+            end
+        end
+
+        local f, err = load(function() error("broken") end)
+        assert(f == nil and string.find(err, "broken"), err)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_load_reader_can_yield() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        -- The resumer feeds the source one piece at a time
+        local co = coroutine.wrap(function()
+            local f, err = load(function() return coroutine.yield("more") end, "=stream", "t", {x = 20})
+            assert(f, err)
+            return f()
+        end)
+        assert(co() == "more")
+        assert(co("local a = ") == "more")
+        assert(co("x * 2 ") == "more")
+        assert(co("return a + 2") == "more")
+        assert(co(nil) == 42)
+
+        -- Errors after a yield still make load return nil, message
+        co = coroutine.wrap(function()
+            return load(function() coroutine.yield() error("late") end)
+        end)
+        co()
+        local f, err = co()
+        assert(f == nil and string.find(err, "late"), err)
+
+        -- Outside a coroutine the yield is a reader error
+        f, err = load(function() coroutine.yield() end)
+        assert(f == nil and string.find(err, "outside a coroutine"), err)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_string_dump_load_binary_constant() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -407,6 +523,100 @@ fn test_dofile_rejects_binary_when_bytecode_loading_disabled() {
     let _ = std::fs::remove_file(path);
 }
 
+#[test]
+fn test_load_hook_audits_every_load_path() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let unique = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir =
+        std::env::temp_dir().join(format!("luars-load-hook-{}-{}", std::process::id(), unique));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("audited_mod.lua"), "return 'module'").unwrap();
+    std::fs::write(dir.join("text.lua"), "return 'text file'").unwrap();
+    let mut builder_vm = GlobalState::new(SafeOption::default());
+    let chunk = builder_vm.main_state().compile_chunk("return 42").unwrap();
+    std::fs::write(
+        dir.join("binary.luac"),
+        serialize_chunk(&chunk, false).unwrap(),
+    )
+    .unwrap();
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let sink = log.clone();
+    vm.set_load_hook(move |event| {
+        sink.borrow_mut().push((
+            event.origin,
+            event.mode,
+            event.chunkname.to_string(),
+            event.caller.clone(),
+        ));
+        match event.mode {
+            LoadMode::Binary => LoadDecision::Deny("binary chunks are not allowed".to_string()),
+            LoadMode::Text => LoadDecision::Allow,
+        }
+    });
+
+    let dir_str = dir.to_str().unwrap().replace('\\', "/");
+    let script = format!(
+        r#"local dir = "{dir_str}"
+assert(load("return 1", "=inline")() == 1)
+local f, err = load(string.dump(function() end))
+assert(f == nil and err == "binary chunks are not allowed")
+assert(loadfile(dir .. "/text.lua")() == "text file")
+f, err = loadfile(dir .. "/binary.luac")
+assert(f == nil and err == "binary chunks are not allowed")
+assert(dofile(dir .. "/text.lua") == "text file")
+local ok, msg = pcall(dofile, dir .. "/binary.luac")
+assert(not ok and msg:find("binary chunks are not allowed", 1, true))
+package.path = dir .. "/?.lua"
+assert(require("audited_mod") == "module")
+"#
+    );
+    let func = vm.main_state().load_with_name(&script, "=audit").unwrap();
+    let result = vm.main_state().call_args(func, &[]);
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+
+    let log = log.borrow();
+    let summary: Vec<_> = log
+        .iter()
+        .map(|(origin, mode, _, caller)| {
+            let caller = caller.as_ref().unwrap();
+            assert_eq!(caller.source, "audit");
+            (*origin, *mode, caller.line)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (LoadOrigin::Load, LoadMode::Text, 2),
+            (LoadOrigin::Load, LoadMode::Binary, 3),
+            (LoadOrigin::LoadFile, LoadMode::Text, 5),
+            (LoadOrigin::LoadFile, LoadMode::Binary, 6),
+            (LoadOrigin::DoFile, LoadMode::Text, 8),
+            (LoadOrigin::DoFile, LoadMode::Binary, 9),
+            (LoadOrigin::Require, LoadMode::Text, 12),
+        ]
+    );
+    assert_eq!(log[0].2, "=inline");
+    assert!(log[6].2.starts_with('@') && log[6].2.ends_with("audited_mod.lua"));
+
+    // Chunks the host loads itself are not audited
+    vm.main_state().execute("return 1").unwrap();
+    vm.main_state()
+        .dofile(dir.join("text.lua").to_str().unwrap())
+        .unwrap();
+    assert_eq!(log.len(), 7);
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
 #[test]
 fn test_warn() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -436,3 +646,281 @@ fn test_collectgarbage() {
 
     assert!(result.is_ok());
 }
+
+#[test]
+fn test_collectgarbage_count_tracks_allocations() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local function count()
+            collectgarbage()
+            collectgarbage()
+            return collectgarbage("count")
+        end
+
+        -- 1000 distinct strings of 1000 bytes each, about 977 KB of payload
+        local base = count()
+        local strings = {}
+        for i = 1, 1000 do strings[i] = string.rep("x", 996) .. string.format("%04d", i) end
+        local held = count() - base
+        assert(held > 977 and held < 1300, held)
+        strings = nil
+        local released = count() - base
+        assert(released < 16, released)
+
+        -- a deep coroutine's stack is charged to the thread
+        base = count()
+        local co = coroutine.create(function()
+            local function dive(n)
+                if n == 0 then coroutine.yield() return 0 end
+                return 1 + dive(n - 1)
+            end
+            dive(900)
+        end)
+        coroutine.resume(co)
+        local grown = count() - base
+        assert(grown > 20, grown)
+        co = nil
+        assert(count() - base < 16)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_memory_limit_runs_emergency_collection() {
+    let option = SafeOption {
+        max_memory_limit: 4 * 1024 * 1024,
+        ..Default::default()
+    };
+    let mut vm = GlobalState::new(option);
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // With the collector stopped only emergency collections reclaim memory,
+    // so 20 MB of garbage must fit under a 4 MB limit
+    let result = vm.main_state().execute(
+        r#"
+        collectgarbage("stop")
+        for i = 1, 20000 do
+            local s = string.rep("x", 1000) .. i
+        end
+        collectgarbage("restart")
+        assert(collectgarbage("count") < 4 * 1024)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+
+    // Live data past the limit still fails once a collection cannot help,
+    // and the VM recovers once that data is released
+    let result = vm.main_state().execute(
+        r#"
+        local ok, err = pcall(function()
+            local keep = {}
+            for i = 1, 8000 do
+                keep[i] = string.rep("x", 1000) .. i
+            end
+        end)
+        assert(not ok and err:find("memory"), err)
+        collectgarbage()
+        assert(#string.rep("y", 100000) == 100000)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+
+    // A single allocation larger than the whole limit is refused up front,
+    // not let through to be collected later
+    let emergencies = vm.gc_stats_struct().emergency_collections;
+    let huge = vec![b'x'; 200 * 1024 * 1024];
+    assert!(matches!(
+        vm.main_state().create_bytes(&huge),
+        Err(LuaError::OutOfMemory)
+    ));
+    drop(huge);
+    let stats = vm.gc_stats_struct();
+    assert_eq!(stats.emergency_collections, emergencies);
+    assert!(stats.bytes_allocated < 4 * 1024 * 1024);
+    let result = vm.main_state().execute(
+        r#"
+        local mb = string.rep("x", 1024 * 1024)
+        local ok, err = pcall(function() return mb .. mb .. mb .. mb .. mb end)
+        assert(not ok and err:find("memory"), err)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_emergency_collection_spares_values_held_by_native_code() {
+    let option = SafeOption {
+        max_memory_limit: 16 * 1024 * 1024,
+        ..Default::default()
+    };
+    let mut vm = GlobalState::new(option);
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // With the collector stopped, the limit is crossed while gsub holds its
+    // first capture only in Rust and creates the second one
+    let result = vm.main_state().execute(
+        r#"
+        collectgarbage("stop")
+        local mb = 1024 * 1024
+        local subject = string.rep("a", 100) .. string.rep("b", mb)
+        for _ = 1, 40 do
+            local calls = 0
+            subject:gsub("(a+)(b+)", function(a, b)
+                calls = calls + 1
+                -- New strings would take over a capture that was collected
+                for i = 1, 1000 do local _ = string.rep("c", 100) .. i end
+                assert(a == string.rep("a", 100))
+                assert(#b == mb and b:sub(1, 1) == "b")
+            end)
+            assert(calls == 1)
+        end
+        collectgarbage("restart")
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(vm.gc_stats_struct().emergency_collections > 0);
+}
+
+#[test]
+fn test_float_to_string_conformance() {
+    // Lua 5.5 tostringbuffFloat: "%.15g", "%.17g" if that does not read back,
+    // ".0" appended to integral-looking results
+    let cases: &[(f64, &str)] = &[
+        (0.0, "0.0"),
+        (-0.0, "-0.0"),
+        (1.0, "1.0"),
+        (-1.5, "-1.5"),
+        (3.0, "3.0"),
+        (100.0, "100.0"),
+        (0.1, "0.1"),
+        (0.1 + 0.2, "0.30000000000000004"),
+        (1.0 / 3.0, "0.33333333333333331"),
+        (std::f64::consts::PI, "3.1415926535897931"),
+        (0.0001, "0.0001"),
+        (0.00001, "1e-05"),
+        (123456.789, "123456.789"),
+        (1e14, "100000000000000.0"),
+        (123456789012345.0, "123456789012345.0"),
+        (1e15, "1e+15"),
+        (1e16, "1e+16"),
+        (2f64.powi(53), "9007199254740992.0"),
+        (2f64.powi(53) + 2.0, "9007199254740994.0"),
+        (2f64.powi(63), "9.2233720368547758e+18"),
+        (-2f64.powi(63), "-9.2233720368547758e+18"),
+        (1e100, "1e+100"),
+        (-1e-100, "-1e-100"),
+        (1.7976931348623157e308, "1.7976931348623157e+308"),
+        (5e-324, "4.94065645841247e-324"),
+        (1e-310, "9.99999999999997e-311"),
+        (2.5e-8, "2.5e-08"),
+        (f64::INFINITY, "inf"),
+        (f64::NEG_INFINITY, "-inf"),
+        (f64::NAN.copysign(1.0), "nan"),
+        (f64::NAN.copysign(-1.0), "-nan"),
+    ];
+    for &(n, expected) in cases {
+        assert_eq!(crate::lua_float_to_string(n), expected, "formatting {n:e}");
+        assert_eq!(LuaValue::float(n).to_string(), expected, "Display of {n:e}");
+    }
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        for _, x in ipairs({3.0, -0.0, 0.1 + 0.2, 1e15, 2^53, 1e100, 1/0, -1/0, 2^-20}) do
+            local s = tostring(x)
+            assert(x .. "" == s)
+            assert(string.format("%s", x) == s)
+            assert(string.format("%5s", x):gsub("^ +", "") == s)
+            assert(("x"):gsub("x", function() return x end) == s)
+            assert(("x"):gsub("x", {x = x}) == s)
+        end
+        assert(tostring(3.0) == "3.0" and tostring(3) == "3")
+        assert(string.format("%g %g %G %g", 1e20, 1e-5, 1e-20, 100000) == "1e+20 1e-05 1E-20 100000")
+        assert(string.format("%.3g|%10.3g|%-6g|%06g", math.pi, 2/3, 2.5, -1.5) == "3.14|     0.667|2.5   |-001.5")
+        assert(tostring(0/0):match("^%-?nan$"))
+    "#,
+    );
+    assert!(result.is_ok(), "float formatting mismatch: {:?}", result);
+}
+
+#[test]
+fn test_huge_result_counts_raise_catchable_errors() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r##"
+        local big = string.rep("a", 2000000)
+        local function fails(expected, f, ...)
+            local ok, msg = pcall(f, ...)
+            assert(not ok, expected)
+            assert(type(msg) == "string" and msg:find(expected, 1, true), msg)
+        end
+
+        fails("string slice too long", string.byte, big, 1, -1)
+        fails("string slice too long", utf8.codepoint, big, 1, -1)
+        fails("too many results to unpack", table.unpack, {}, 1, 1e8)
+        fails("too many results", string.unpack, string.rep("B", 1500000), big)
+
+        -- select copies its varargs: 400k arguments, their copy, and the results
+        local t = {}
+        for i = 1, 400000 do t[i] = i end
+        local function g(...) return select(1, ...) end
+        fails("too many results", function() return g(table.unpack(t)) end)
+        t = nil
+
+        -- The VM is intact afterwards
+        collectgarbage()
+        assert(select("#", string.byte(big, 1, 1000)) == 1000)
+        assert(select("#", table.unpack({1, 2, 3})) == 3)
+        assert(select(2, "a", "b", "c") == "b")
+        local i, j, k, v = string.find("key=val", "(%w+)=(%w+)")
+        assert(i == 1 and j == 7 and k == "key" and v == "val")
+    "##,
+    );
+    assert!(result.is_ok(), "huge result counts: {:?}", result);
+}
+
+#[test]
+fn test_tostring_identity_is_stable_and_unique() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local makers = {
+            function() return {} end,
+            function() local x = {} return function() return x end end,
+            function() return string.gmatch("a", "a") end,
+            function() return coroutine.create(function() end) end,
+            function() return setmetatable({}, {__name = "Named"}) end,
+        }
+        local objs, names, seen = {}, {}, {}
+        for i = 1, 5000 do
+            local o = makers[i % #makers + 1]()
+            local s = tostring(o)
+            assert(s == tostring(o), s)
+            assert(not seen[s], "two live objects print as " .. s)
+            assert(s:match(": (0x%x+)$") == string.format("%p", o), s)
+            seen[s] = true
+            objs[i], names[i] = o, s
+        end
+        assert(tostring(print) == tostring(print))
+        assert(tostring(print) == "function: " .. string.format("%p", print))
+        assert(tostring(coroutine.running()) == tostring(coroutine.running()))
+
+        -- Garbage allocated and collected around the survivors
+        for _ = 1, 20000 do local _ = {} end
+        collectgarbage()
+        collectgarbage()
+        for i, o in ipairs(objs) do
+            assert(tostring(o) == names[i], names[i])
+        end
+    "#,
+    );
+    assert!(result.is_ok(), "tostring identity: {:?}", result);
+}
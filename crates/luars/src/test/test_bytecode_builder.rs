@@ -0,0 +1,217 @@
+// Tests for the unstable BytecodeBuilder API
+use crate::lua_vm::SafeOption;
+use crate::unstable::BytecodeBuilder;
+use crate::{GlobalState, Instruction, LuaValue, OpCode, Stdlib};
+
+// TM_ADD / TM_SUB, the metamethod selectors MMBIN* take in C
+const TM_ADD: u32 = 6;
+const TM_SUB: u32 = 7;
+
+fn sc(i: i32) -> u32 {
+    (i + Instruction::OFFSET_SC) as u32
+}
+
+/// function fib(n) if n < 2 then return n end return fib(n-1) + fib(n-2) end
+/// return fib(20)
+#[test]
+fn test_builder_fibonacci() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+
+    let proto = {
+        let mut b = BytecodeBuilder::new(&mut vm, "=fib");
+        let fib_name = b.add_string_constant("fib").unwrap();
+
+        b.set_line(1);
+        b.open_function(1, false).unwrap();
+        assert_eq!(b.add_upvalue("_ENV", false, 0).unwrap(), 0);
+        let n = b.open_local("n").unwrap();
+        assert_eq!(n, 0);
+        let k = b.add_string_constant("fib").unwrap();
+        b.emit_abc(OpCode::LtI, n, sc(2), 0, false).unwrap();
+        let skip = b.jump().unwrap();
+        b.ret(n, Some(1)).unwrap();
+        let target = b.pc();
+        b.patch_jump(skip, target).unwrap();
+        b.set_line(2);
+        for (reg, delta) in [(1, -1), (2, -2)] {
+            b.emit_abc(OpCode::GetTabUp, reg, 0, k, false).unwrap();
+            b.emit_abc(OpCode::AddI, reg + 1, n, sc(delta), false)
+                .unwrap();
+            b.emit_abc(OpCode::MmBinI, n, sc(-delta), TM_SUB, false)
+                .unwrap();
+            b.emit_abc(OpCode::Call, reg, 2, 2, false).unwrap();
+        }
+        b.emit_abc(OpCode::Add, 1, 1, 2, false).unwrap();
+        b.emit_abc(OpCode::MmBin, 1, 2, TM_ADD, false).unwrap();
+        b.ret(1, Some(1)).unwrap();
+        b.close_local().unwrap();
+        b.set_line(3);
+        let fib = b.close_function().unwrap();
+
+        b.set_line(4);
+        b.emit_abx(OpCode::Closure, 0, fib).unwrap();
+        b.emit_abc(OpCode::SetTabUp, 0, fib_name, 0, false).unwrap();
+        b.emit_abc(OpCode::GetTabUp, 0, 0, fib_name, false).unwrap();
+        b.load_integer(1, 20).unwrap();
+        b.emit_abc(OpCode::Call, 0, 2, 2, false).unwrap();
+        b.ret(0, Some(1)).unwrap();
+        b.finish().unwrap()
+    };
+
+    assert_eq!(proto.child_protos.len(), 1);
+    let child = &proto.child_protos[0].as_ref().data;
+    assert_eq!(child.max_stack_size, 4);
+    assert_eq!(child.locals[0].name, "n");
+    assert_eq!((child.linedefined, child.lastlinedefined), (1, 3));
+
+    let state = vm.main_state();
+    let func = state.load_proto(proto).unwrap();
+    let results = state.call_args(func, &[]).unwrap();
+    assert_eq!(results[0].as_integer(), Some(6765));
+
+    // The hand-built function is an ordinary Lua function to the runtime
+    state
+        .execute(
+            r#"
+        assert(fib(10) == 55)
+        local info = debug.getinfo(fib, "S")
+        assert(info.source == "=fib" and info.linedefined == 1 and info.lastlinedefined == 3)
+        assert(debug.getlocal(fib, 1) == "n")
+        assert(debug.getupvalue(fib, 1) == "_ENV")
+    "#,
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_builder_constants_and_extraarg() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    let mut b = BytecodeBuilder::new(&mut vm, "=k");
+
+    let one = b.add_constant(LuaValue::integer(1)).unwrap();
+    let one_float = b.add_constant(LuaValue::float(1.0)).unwrap();
+    let zero = b.add_constant(LuaValue::float(0.0)).unwrap();
+    let neg_zero = b.add_constant(LuaValue::float(-0.0)).unwrap();
+    let s = b.add_string_constant("x").unwrap();
+    assert_ne!(one, one_float);
+    assert_ne!(zero, neg_zero);
+    assert_eq!(b.add_constant(LuaValue::integer(1)).unwrap(), one);
+    assert_eq!(b.add_string_constant("x").unwrap(), s);
+    assert_eq!(b.add_constant(LuaValue::boolean(true)).unwrap(), 5);
+    assert!(b.add_constant(LuaValue::nil()).is_ok());
+
+    // Large integers go through a constant, large tables through EXTRAARG
+    let pc = b.load_integer(0, i64::MAX).unwrap();
+    b.new_table(1, 5000, 3).unwrap();
+    b.ret(0, Some(1)).unwrap();
+    let proto = b.finish().unwrap();
+    assert_eq!(proto.code[pc].get_opcode(), OpCode::LoadK);
+    let newtable = proto.code[pc + 1];
+    assert_eq!(newtable.get_opcode(), OpCode::NewTable);
+    assert!(newtable.get_k());
+    assert_eq!(newtable.get_vb(), 3);
+    assert_eq!(
+        newtable.get_vc() + proto.code[pc + 2].get_ax() * (Instruction::MAX_V_C + 1),
+        5000
+    );
+    // RETURN1 of a vararg chunk becomes RETURN with the parameter count in C
+    let ret = *proto.code.last().unwrap();
+    assert_eq!(ret.get_opcode(), OpCode::Return);
+    assert_eq!((ret.get_b(), ret.get_c()), (2, 1));
+
+    let state = vm.main_state();
+    let func = state.load_proto(proto).unwrap();
+    let results = state.call_args(func, &[]).unwrap();
+    assert_eq!(results[0].as_integer(), Some(i64::MAX));
+}
+
+#[test]
+fn test_builder_rejects_invalid_bytecode() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    let mut b = BytecodeBuilder::new(&mut vm, "=bad");
+
+    // Operand and format checks happen at emission
+    let err = b.emit_abc(OpCode::Move, 256, 0, 0, false).unwrap_err();
+    assert!(err.contains("operand A of Move out of range"), "{err}");
+    let err = b.emit_abc(OpCode::LoadK, 0, 0, 0, false).unwrap_err();
+    assert!(err.contains("LoadK is not an IABC instruction"), "{err}");
+    let err = b.emit_asbx(OpCode::LoadI, 0, 1 << 20).unwrap_err();
+    assert!(err.contains("operand sBx of LoadI out of range"), "{err}");
+    assert!(b.add_upvalue("x", true, 0).is_err());
+    assert!(b.close_function().is_err());
+
+    // Whole-function checks happen when the function is finished
+    b.emit_abx(OpCode::LoadK, 0, 3).unwrap();
+    b.ret(0, Some(1)).unwrap();
+    let err = b.finish().unwrap_err();
+    assert!(err.contains("constant index out of range"), "{err}");
+
+    let mut b = BytecodeBuilder::new(&mut vm, "=bad");
+    b.emit_abc(OpCode::Add, 0, 0, 0, false).unwrap();
+    b.ret(0, Some(1)).unwrap();
+    let err = b.finish().unwrap_err();
+    assert!(err.contains("missing MmBin after Add"), "{err}");
+
+    let mut b = BytecodeBuilder::new(&mut vm, "=bad");
+    b.emit_sj(OpCode::Jmp, 10).unwrap();
+    b.ret(0, Some(0)).unwrap();
+    let err = b.finish().unwrap_err();
+    assert!(err.contains("jump out of range"), "{err}");
+
+    let mut b = BytecodeBuilder::new(&mut vm, "=bad");
+    b.open_function(0, false).unwrap();
+    b.emit_abc(OpCode::LoadNil, 0, 0, 0, false).unwrap();
+    let err = b.close_function().unwrap_err();
+    assert!(err.contains("does not end with a return"), "{err}");
+}
+
+fn closure_chunk(vm: &mut GlobalState) -> crate::LuaProto {
+    let mut b = BytecodeBuilder::new(vm, "=nested");
+    b.open_function(0, false).unwrap();
+    b.load_integer(0, 7).unwrap();
+    b.ret(0, Some(1)).unwrap();
+    let child = b.close_function().unwrap();
+    b.emit_abx(OpCode::Closure, 0, child).unwrap();
+    b.ret(0, Some(1)).unwrap();
+    b.finish().unwrap()
+}
+
+#[test]
+fn test_load_proto_verifies_nested_prototypes() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    let state = vm.main_state();
+
+    // A prototype changed after it was built is checked again on load,
+    // nested ones included
+    let proto = closure_chunk(state.global_state_mut());
+    proto.child_protos[0].as_mut_ref().data.code[0] = Instruction::create_abx(OpCode::LoadK, 0, 9);
+    let err = state.load_proto(proto).unwrap_err();
+    let msg = state.get_error_msg(err);
+    assert!(msg.contains("constant index out of range"), "{msg}");
+
+    let mut proto = closure_chunk(state.global_state_mut());
+    proto.max_stack_size = 1000;
+    let err = state.load_proto(proto).unwrap_err();
+    let msg = state.get_error_msg(err);
+    assert!(msg.contains("frame too large"), "{msg}");
+
+    let proto = closure_chunk(state.global_state_mut());
+    let func = state.load_proto(proto).unwrap();
+    assert!(state.call_args(func, &[]).unwrap()[0].is_function());
+}
+
+#[test]
+fn test_load_proto_honors_allow_load_bytecode() {
+    let mut vm = GlobalState::new(
+        SafeOption::builder()
+            .allow_load_bytecode(false)
+            .build()
+            .unwrap(),
+    );
+    let proto = closure_chunk(&mut vm);
+    let state = vm.main_state();
+    let err = state.load_proto(proto).unwrap_err();
+    let msg = state.get_error_msg(err);
+    assert!(msg.contains("bytecode loading is disabled"), "{msg}");
+}
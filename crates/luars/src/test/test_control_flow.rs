@@ -93,6 +93,56 @@ fn test_repeat_until() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_repeat_until_scope() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        -- the condition sees the body's locals
+        local tries = 0
+        local function try() tries = tries + 1; return tries >= 3 end
+        repeat local ok = try() until ok
+        assert(tries == 3)
+
+        -- each iteration's locals are fresh upvalues, also when read by the condition
+        local fs, i = {}, 0
+        repeat
+            i = i + 1
+            local j = i * 10
+            fs[#fs + 1] = function() return j end
+        until fs[#fs]() >= 30
+        assert(fs[1]() == 10 and fs[2]() == 20 and fs[3]() == 30)
+
+        -- break closes the body's upvalues and leaves the loop
+        local k = 0
+        repeat
+            local x = k
+            k = k + 1
+            fs[k] = function() return x end
+            if k == 2 then break end
+        until k > 5
+        assert(k == 2 and fs[1]() == 0 and fs[2]() == 1)
+
+        -- nested repeats with shadowed names
+        local out, a = {}, 0
+        repeat
+            local a = a + 1
+            local b = 0
+            repeat
+                local a = a * 10
+                b = b + 1
+                out[#out + 1] = a + b
+            until a + b > 11
+        until a >= 1
+        assert(table.concat(out, ",") == "11,12" and a == 0)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_numeric_for() {
     let mut vm = GlobalState::new(SafeOption::default());
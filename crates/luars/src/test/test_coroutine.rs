@@ -350,3 +350,396 @@ fn test_coroutine_traceback_contains_suspend_and_recursive_frames() {
         }
     }
 }
+
+#[test]
+fn test_coroutine_error_traceback_shows_coroutine_frames() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local function c3() error("boom") end
+        local function c2() c3() end
+        local function c1() c2() end
+
+        -- resumed through coroutine.resume, then re-raised
+        local co = coroutine.create(c1)
+        local function m2()
+          local ok, err = coroutine.resume(co)
+          error(err, 0)
+        end
+        local function m1() m2() end
+        local _, resumed = xpcall(m1, debug.traceback)
+
+        -- resumed through a coroutine.wrap function
+        local w = coroutine.wrap(c1)
+        local function n2() w() end
+        local function n1() n2() end
+        local _, wrapped = xpcall(n1, debug.traceback)
+
+        -- a coroutine resumed from inside another coroutine
+        local outer = coroutine.wrap(function () coroutine.wrap(c1)() end)
+        local _, nested = xpcall(outer, debug.traceback)
+
+        -- an unrelated error does not pick up the coroutine's frames
+        local _, other = xpcall(function () error("other") end, debug.traceback)
+
+        return resumed, wrapped, nested, other
+    "#,
+    );
+    let values = result.unwrap();
+    let traces: Vec<&str> = values.iter().map(|v| v.as_str().unwrap()).collect();
+
+    let lines = |trace: &str| -> Vec<String> {
+        trace
+            .lines()
+            .skip_while(|line| *line != "stack traceback:")
+            .skip(1)
+            .map(|line| line.trim().to_string())
+            .collect()
+    };
+    let position = |lines: &[String], needle: &str| -> usize {
+        lines
+            .iter()
+            .position(|line| line.contains(needle))
+            .unwrap_or_else(|| panic!("no {needle:?} in {lines:#?}"))
+    };
+
+    let resumed = lines(traces[0]);
+    let order: Vec<usize> = [
+        "upvalue 'c3'",
+        "upvalue 'c2'",
+        "function <chunk:4>",
+        "(coroutine boundary)",
+        "upvalue 'm2'",
+        "function <chunk:12>",
+    ]
+    .iter()
+    .map(|needle| position(&resumed, needle))
+    .collect();
+    assert!(order.is_sorted(), "{resumed:#?}");
+    assert!(resumed[0].contains("'error'"), "{resumed:#?}");
+
+    let wrapped = lines(traces[1]);
+    let order: Vec<usize> = [
+        "upvalue 'c3'",
+        "upvalue 'c2'",
+        "(coroutine boundary)",
+        "upvalue 'w'",
+        "upvalue 'n2'",
+    ]
+    .iter()
+    .map(|needle| position(&wrapped, needle))
+    .collect();
+    assert!(order.is_sorted(), "{wrapped:#?}");
+
+    let nested = lines(traces[2]);
+    let boundaries: Vec<usize> = nested
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| *line == "(coroutine boundary)")
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(boundaries.len(), 2, "{nested:#?}");
+    assert!(position(&nested, "upvalue 'c3'") < boundaries[0]);
+
+    assert!(!traces[3].contains("coroutine boundary"), "{}", traces[3]);
+}
+
+#[test]
+fn test_full_error_includes_coroutine_frames() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let err = vm
+        .main_state()
+        .execute(
+            r#"
+        local function inner() error("deep") end
+        local w = coroutine.wrap(function () inner() end)
+        w()
+    "#,
+        )
+        .unwrap_err();
+    let full = vm.main_state().get_full_error(err);
+    let message = full.message();
+    let inner = message.find("in upvalue 'inner'").expect(message);
+    let boundary = message.find("(coroutine boundary)").expect(message);
+    let main = message.find("in main chunk").expect(message);
+    assert!(inner < boundary && boundary < main, "{message}");
+}
+
+#[test]
+fn test_coroutine_functions_check_their_arguments() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local function msg(f, ...)
+            local ok, err = pcall(f, ...)
+            assert(not ok)
+            return err
+        end
+        assert(msg(coroutine.resume) ==
+            "bad argument #1 to 'coroutine.resume' (thread expected, got no value)")
+        assert(msg(coroutine.resume, 42) ==
+            "bad argument #1 to 'coroutine.resume' (thread expected, got number)")
+        assert(msg(coroutine.status, {}) ==
+            "bad argument #1 to 'coroutine.status' (thread expected, got table)")
+        assert(msg(coroutine.close, nil) ==
+            "bad argument #1 to 'coroutine.close' (thread expected, got nil)")
+        assert(msg(coroutine.isyieldable, "x") ==
+            "bad argument #1 to 'coroutine.isyieldable' (thread expected, got string)")
+        assert(msg(coroutine.create, io.stdout) ==
+            "bad argument #1 to 'coroutine.create' (function expected, got FILE*)")
+        assert(msg(coroutine.wrap, coroutine.running()) ==
+            "bad argument #1 to 'coroutine.wrap' (function expected, got thread)")
+
+        -- Running and normal coroutines, the main thread included, fail the
+        -- resume itself with a plain message
+        local main = coroutine.running()
+        local r = {coroutine.resume(main)}
+        assert(#r == 2 and r[1] == false and r[2] == "cannot resume non-suspended coroutine")
+        local co
+        co = coroutine.create(function()
+            local inner = coroutine.create(function()
+                return coroutine.status(co), coroutine.status(main), coroutine.resume(co)
+            end)
+            local self_ok, self_err = coroutine.resume(co)
+            assert(not self_ok and self_err == "cannot resume non-suspended coroutine")
+            return coroutine.resume(inner)
+        end)
+        local ok, inner_ok, st, main_st, normal_ok, normal_err = coroutine.resume(co)
+        assert(ok)
+        assert(inner_ok and st == "normal" and main_st == "normal")
+        assert(not normal_ok and normal_err == "cannot resume non-suspended coroutine")
+        assert(select(2, coroutine.resume(co)) == "cannot resume dead coroutine")
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_coroutine_functions_with_every_value_kind() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    // Every library function against every kind of value and every thread
+    // state must return or raise a Lua error, never panic
+    let result = vm.main_state().execute(
+        r#"
+        local suspended = coroutine.create(function() coroutine.yield() end)
+        coroutine.resume(suspended)
+        local dead = coroutine.create(function() end)
+        coroutine.resume(dead)
+        local errored = coroutine.create(function() error("boom") end)
+        coroutine.resume(errored)
+        local values = {
+            nil, false, true, 0, -1, 1.5, 0/0, "", "thread", {}, print,
+            function() end, io.stdout, coroutine.running(), suspended, dead,
+            errored, coroutine.create(print), coroutine.wrap(print),
+        }
+        local n = 19
+        local names = {}
+        for name in pairs(coroutine) do
+            if name ~= "yield" then names[#names + 1] = name end
+        end
+        table.sort(names)
+        local calls = 0
+        for _, name in ipairs(names) do
+            local f = coroutine[name]
+            pcall(f)
+            for i = 1, n do
+                pcall(f, values[i])
+                pcall(f, values[i], values[i])
+                calls = calls + 1
+            end
+            -- and from inside a running coroutine
+            local co = coroutine.create(function()
+                for i = 1, n do pcall(f, values[i]) end
+                pcall(f, coroutine.running())
+            end)
+            assert(coroutine.resume(co))
+        end
+        assert(calls == #names * n)
+        assert(not pcall(coroutine.yield))
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_idle_coroutines_are_small() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local f = function() coroutine.yield() end
+        local t = {}
+        for i = 1, 100000 do t[i] = false end
+        collectgarbage()
+        collectgarbage()
+        local before = collectgarbage("count")
+        for i = 1, 100000 do t[i] = coroutine.create(f) end
+        collectgarbage()
+        collectgarbage()
+        local per_coroutine = (collectgarbage("count") - before) * 1024 / 100000
+        return per_coroutine
+    "#,
+    );
+    let per_coroutine = result.unwrap()[0].as_number().unwrap();
+    // 312 bytes measured: the state plus the one stack slot holding the
+    // function. The sandbox adds its per-thread limits.
+    let mut limit = 320.0;
+    if cfg!(feature = "sandbox") {
+        limit += 56.0;
+    }
+    assert!(
+        per_coroutine <= limit,
+        "{per_coroutine} bytes per idle coroutine"
+    );
+}
+
+#[test]
+fn test_coroutine_stack_cap_is_separate() {
+    let option = SafeOption {
+        max_coroutine_stack: 2000,
+        ..Default::default()
+    };
+    let mut vm = GlobalState::new(option);
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local function rec(n)
+            local a, b, c, d, e, f, g, h = 1, 2, 3, 4, 5, 6, 7, 8
+            if n == 0 then return 0 end
+            return rec(n - 1) + 1
+        end
+
+        -- The main thread is limited by max_stack_size only
+        assert(rec(500) == 500)
+
+        local co = coroutine.create(function() return rec(500) end)
+        local ok, msg = coroutine.resume(co)
+        assert(not ok)
+        assert(msg:find("coroutine stack overflow", 1, true), msg)
+        assert(coroutine.status(co) == "dead")
+
+        -- Shallow recursion still fits, and the error is catchable inside
+        local ok2, depth = coroutine.resume(coroutine.create(function()
+            local ok, err = pcall(rec, 500)
+            assert(not ok and err:find("coroutine stack overflow", 1, true))
+            return rec(50)
+        end))
+        assert(ok2 and depth == 50, depth)
+    "#,
+    );
+    assert!(result.is_ok(), "coroutine stack cap: {:?}", result);
+}
+
+#[test]
+fn test_coroutine_stack_shrinks_on_yield() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let co = vm
+        .main_state()
+        .execute(
+            r#"
+            local function rec(n)
+                local a, b, c, d, e, f, g, h = 1, 2, 3, 4, 5, 6, 7, 8
+                if n == 0 then return 0 end
+                return rec(n - 1) + 1
+            end
+            return coroutine.create(function()
+                rec(1000)
+                coroutine.yield()
+                return rec(10)
+            end)
+        "#,
+        )
+        .unwrap()[0];
+
+    let (finished, _) = vm.resume_thread(co, vec![]).unwrap();
+    assert!(!finished);
+    let capacity = co.as_thread_mut().unwrap().stack.capacity();
+    assert!(capacity < 256, "suspended stack keeps {capacity} slots");
+
+    let (finished, values) = vm.resume_thread(co, vec![]).unwrap();
+    assert!(finished);
+    assert_eq!(values[0].as_integer(), Some(10));
+}
+
+#[test]
+fn test_abandoned_coroutines_are_collected() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    // A fresh 1MB string per call, built on the Rust side: string.rep is
+    // too slow for this many megabytes in an unoptimised test build
+    vm.register_function("megabyte", |state| {
+        let tag = state.get_arg(1).and_then(|v| v.as_integer()).unwrap_or(0);
+        let mut bytes = vec![b'x'; 1 << 20];
+        bytes[..8].copy_from_slice(&tag.to_le_bytes());
+        let s = state.create_bytes(&bytes)?;
+        state.push_value(s)?;
+        Ok(1)
+    })
+    .unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        collectgarbage("collect")
+        local base = collectgarbage("count")
+        local peak = base
+        for i = 1, 100000 do
+            local co = coroutine.create(function()
+                local big = megabyte(i)
+                local function keep() return big end
+                coroutine.yield(#big)
+                return keep()
+            end)
+            assert(select(2, coroutine.resume(co)) == 1 << 20)
+            if i % 1000 == 0 then
+                peak = math.max(peak, collectgarbage("count"))
+            end
+        end
+        collectgarbage("collect")
+        local after = collectgarbage("count")
+        return base, peak, after
+    "#,
+    );
+    let values = result.unwrap();
+    let (base, peak, after) = (
+        values[0].as_number().unwrap(),
+        values[1].as_number().unwrap(),
+        values[2].as_number().unwrap(),
+    );
+    // Never more than a few hundred coroutines' worth alive at once
+    assert!(
+        peak - base < 512.0 * 1024.0,
+        "peak {peak} KB over base {base} KB"
+    );
+    assert!(
+        after - base < 64.0,
+        "{after} KB after collect, base {base} KB"
+    );
+}
+
+#[test]
+fn test_collected_coroutine_does_not_close_variables() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local closed = false
+        local co = coroutine.create(function()
+            local x <close> = setmetatable({}, { __close = function() closed = true end })
+            coroutine.yield()
+        end)
+        coroutine.resume(co)
+        local probe = setmetatable({ co }, { __mode = "v" })
+        co = nil
+        collectgarbage("collect")
+        assert(probe[1] == nil, "suspended coroutine not collected")
+        -- as in PUC Lua, only coroutine.close runs pending __close
+        assert(closed == false)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result.err());
+}
@@ -0,0 +1,24 @@
+-- Profiler fixture: `hot` does nearly all the work, `cold` almost none.
+
+local function hot(n)
+    local s = 0
+    for i = 1, n do
+        s = s + i % 7
+    end
+    return s
+end
+
+local function cold(x)
+    return x + 1
+end
+
+local function driver()
+    local total = 0
+    for i = 1, 200 do
+        total = total + hot(2000) + cold(i)
+    end
+    return total
+end
+
+local result = driver()
+return result
@@ -0,0 +1,15 @@
+-- 日本語のコメント: multibyte text before any code
+local greeting = "héllo, wörld ✓"
+local x = 1 --[[ ünïcödé
+long comment ]] local y = x
+do
+    local x = "shadow ☃"
+    print(x, y)
+end
+local function outer(x)
+    return function()
+        return x .. greeting
+    end
+end
+for x = 1, 3 do print(x) end
+print(x, undefined_global)
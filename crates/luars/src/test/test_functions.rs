@@ -73,6 +73,123 @@ fn test_function_tail_call() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_function_tail_call_pads_missing_params() {
+    // The main chunk's frame is small, so padding the parameters needs the
+    // stack grown first
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm
+        .main_state()
+        .execute("return (function(a, b, c, d, e, f, g) return g end)()");
+    assert!(result.unwrap()[0].is_nil());
+}
+
+#[test]
+fn test_function_tail_call_empty_varargs() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm
+        .main_state()
+        .execute("return (function(a, b, c, d, e, f, g, h, i, j, k, ...) return (...) end)()");
+    assert!(result.unwrap()[0].is_nil());
+}
+
+#[test]
+fn test_function_tail_call_adjusts_results() {
+    // C functions, __call objects and Lua functions in tail position, from
+    // fixed-arity and vararg callers, with 0, 1, 2 and all results wanted
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local function two() return 1, 2 end
+        local callable = setmetatable({}, { __call = function(self, a, b) return a, b end })
+        local chained = setmetatable({}, { __call = callable })
+        local ccallable = setmetatable({}, { __call = rawequal })
+
+        local cases = {
+            print = { {}, function(a) return print(a) end,
+                          function(...) return print(...) end },
+            pcall = { { true, 1, 2 }, function(a) return pcall(two) end,
+                                      function(...) return pcall(two) end },
+            call = { { "a", "b" }, function(a) return callable(a, "b") end,
+                                   function(...) return callable(..., "b") end },
+            chain = { { chained, "a" }, function(a) return chained(a, "b") end,
+                                        function(...) return chained(..., "b") end },
+            ccall = { { true }, function(a) return ccallable(ccallable) end,
+                                function(...) return ccallable(ccallable) end },
+            lua = { { 1, 2 }, function(a) return two() end,
+                              function(...) return two() end },
+        }
+        for name, case in pairs(cases) do
+            local e = case[1]
+            for i = 2, 3 do
+                local f = case[i]
+                local sentinel = "keep"
+                local packed = table.pack(f("a", "x", "y", "z"))
+                assert(packed.n == #e, name .. ": got " .. packed.n .. " results")
+                for k = 1, #e do assert(packed[k] == e[k], name .. ": result " .. k) end
+                f("a", "x")
+                local one = f("a")
+                assert(one == e[1], name .. ": one result")
+                local p, q = f("a", "x", "y")
+                assert(p == e[1] and q == e[2], name .. ": two results")
+                local t = { f("a"), "after" }
+                assert(t[1] == e[1] and t[2] == "after", name .. ": constructor")
+                assert(sentinel == "keep", name .. ": caller frame clobbered")
+            end
+        end
+
+        local big = {}
+        for i = 1, 300 do big[i] = i end
+        local function many(...) return table.unpack(big) end
+        assert(select('#', many(table.unpack(big))) == 300)
+        local function closing(x)
+            local c <close> = setmetatable({}, { __close = function() end })
+            return select(2, x, "b", "c")
+        end
+        local b, c = closing("a")
+        assert(b == "b" and c == "c")
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+
+    let result = vm
+        .main_state()
+        .execute("return select(2, 'a', 'b')")
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].as_str(), Some("b"));
+}
+
+#[test]
+fn test_function_main_chunk_returns_to_host() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    // The outermost frame hands every result to the embedder: a fixed list,
+    // an open call result after a tbc variable, and nothing at all
+    let ints = |values: Vec<crate::LuaValue>| -> Vec<Option<i64>> {
+        values.iter().map(|v| v.as_integer()).collect()
+    };
+    let result = vm.main_state().execute("return 1, nil, 3").unwrap();
+    assert_eq!(ints(result), vec![Some(1), None, Some(3)]);
+
+    let result = vm
+        .main_state()
+        .execute(
+            r#"
+            local closed = setmetatable({}, {__close = function() end})
+            local x <close> = closed
+            return table.unpack({1, 2, 3, 4, 5, 6, 7, 8, 9, 10})
+            "#,
+        )
+        .unwrap();
+    assert_eq!(ints(result), (1..=10).map(Some).collect::<Vec<_>>());
+
+    assert!(vm.main_state().execute("return").unwrap().is_empty());
+}
+
 #[test]
 fn test_function_vararg_basic() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -154,6 +271,55 @@ fn test_function_vararg_select() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_function_vararg_nil_holes() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local function border(t, n)
+            local b = #t
+            return (b == 0 or t[b] ~= nil) and t[b + 1] == nil and b <= n
+        end
+
+        local function check(...)
+            local n = select('#', ...)
+            local t = {...}
+            local p = table.pack(...)
+            assert(p.n == n)
+            for i = 1, n do
+                assert(t[i] == select(i, ...) and p[i] == t[i])
+            end
+            assert(border(t, n))
+            local prefixed = {"x", ...}
+            for i = 1, n do assert(prefixed[i + 1] == t[i]) end
+            return ...
+        end
+
+        check(1, nil, 3)
+        check(nil, nil, 3)
+        check(1, nil, nil)
+
+        -- forwarding ... keeps holes and trailing nils
+        local function pass(...) return select('#', check(...)), check(...) end
+        local n, a, b, c = pass(1, nil, 3)
+        assert(n == 3 and a == 1 and b == nil and c == 3)
+        assert(select('#', pass(nil, nil)) == 3)
+
+        -- more values than one SETLIST batch, with holes throughout
+        local src = {}
+        for i = 1, 130 do if i % 7 ~= 0 then src[i] = i end end
+        local big = check(table.unpack(src, 1, 130))
+        assert(big == 1 and select('#', check(table.unpack(src, 1, 130))) == 130)
+
+        local function fmt(...) return string.format("%s %s %s", ...) end
+        assert(fmt(1, nil, 3) == "1 nil 3")
+        assert(fmt(nil, nil, nil) == "nil nil nil")
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_function_nested_calls() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -456,3 +622,85 @@ fn test_local_after_nested_function_stays_local() {
     );
     assert!(result.is_ok(), "Error: {:?}", result.err());
 }
+
+#[test]
+fn test_execute_returns_every_chunk_result() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let state = vm.main_state();
+
+    let results = state.execute("return 1, nil, 3, nil").unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].as_integer(), Some(1));
+    assert!(results[1].is_nil() && results[3].is_nil());
+    assert_eq!(results[2].as_integer(), Some(3));
+
+    assert!(state.execute("local x = 1").unwrap().is_empty());
+    assert_eq!(
+        state.execute_single("return 7, 8").unwrap().as_integer(),
+        Some(7)
+    );
+    assert!(state.execute_single("local x = 1").unwrap().is_nil());
+}
+
+#[test]
+fn test_function_statement_with_field_chain() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local M = {sub = {child = {}}}
+        function M.sub.f(x) return x * 2 end
+        function M.sub.child.g() return "g" end
+        function M.sub.child:method(x) return self, x end
+        assert(M.sub.f(4) == 8)
+        assert(M.sub.child.g() == "g")
+        local s, x = M.sub.child:method(5)
+        assert(s == M.sub.child and x == 5)
+        assert(debug.getinfo(M.sub.child.method, "u").nparams == 2)
+        assert(debug.getlocal(M.sub.child.method, 1) == "self")
+
+        function M.sub.child:broken() return self.missing.x end
+        local ok, err = pcall(M.sub.child.broken, M.sub.child)
+        assert(not ok and err:find("field 'missing'"), err)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_function_statement_nil_intermediate_field() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let state = vm.main_state();
+    let err = state
+        .execute("local M = {}\nfunction M.sub.f() end")
+        .unwrap_err();
+    let msg = state.get_error_msg(err);
+    assert!(
+        msg.contains("attempt to index a nil value (field 'sub')"),
+        "{msg}"
+    );
+}
+
+#[test]
+fn test_traceback_names_function_by_definition() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let results = vm
+        .main_state()
+        .execute(
+            r#"
+        local M = {sub = {child = {}}}
+        function M.sub.f() return debug.traceback() end
+        function M.sub.child:m() local tb = M.sub.f() return tb end
+        return M.sub.child:m(), select(2, xpcall(M.sub.f, debug.traceback))
+    "#,
+        )
+        .unwrap();
+    let direct = results[0].as_str().unwrap();
+    assert!(direct.contains("in function 'M.sub.f'"), "{direct}");
+    assert!(direct.contains("in function 'M.sub.child:m'"), "{direct}");
+    let from_c = results[1].as_str().unwrap();
+    assert!(from_c.contains("in function 'M.sub.f'"), "{from_c}");
+}
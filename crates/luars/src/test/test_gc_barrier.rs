@@ -0,0 +1,143 @@
+// GC write barrier stress tests: a count hook runs a small incremental step
+// before every instruction, so every store lands in the middle of a cycle.
+use crate::lua_vm::LUA_MASKCOUNT;
+use crate::*;
+
+/// Count hook: pay off the debt so each hook call runs one GC step (a young
+/// collection in generational mode).
+fn gc_step_hook(l: &mut LuaState) -> LuaResult<usize> {
+    l.global_state_mut().gc.set_debt(0);
+    l.check_gc_safe_point();
+    Ok(0)
+}
+
+/// Store a fresh table and string into `t` from Rust, through the same
+/// helpers host code uses.
+fn rust_store(l: &mut LuaState) -> LuaResult<usize> {
+    let t = l.get_arg(1).unwrap_or_default();
+    let i = l.get_arg(2).and_then(|v| v.as_integer()).unwrap_or(0);
+    let value = l.create_table(0, 1)?;
+    let s = l.create_string(&format!("rust{i}"))?;
+    l.raw_set(&value, s, LuaValue::integer(i));
+    let key = l.create_string(&format!("k{}", i % 17))?;
+    l.raw_set(&t, key, value);
+    let s = l.create_string(&format!("rust{i}"))?;
+    l.raw_seti(&t, 100 + i % 23, s);
+    Ok(0)
+}
+
+const WORKLOAD: &str = r#"
+    local rounds, mode = ...
+    collectgarbage(mode)
+    collectgarbage("param", "stepsize", 64)
+
+    -- Old objects: survive many cycles before the mutations start
+    local old = {}
+    local list = {}
+    local oldmt = {}
+    local holder = setmetatable({}, oldmt)
+    local ops = 0
+    local function keep() return old end
+    local getslot, setslot = (function()
+        local slot
+        return function() return slot end, function(v) slot = v end
+    end)()
+    local getjoined = (function()
+        local slot
+        return function() return slot end
+    end)()
+    for _ = 1, 3 do collectgarbage() end
+
+    local function check(t, i, w)
+        assert(t.tag == "t" .. i, w)
+        assert(#t.items == 2 and t.items[2] == i, w)
+    end
+
+    for i = 1, rounds do
+        local fresh = {tag = "t" .. i, items = {"x" .. i, i}}
+        old[i % 31] = fresh                          -- SETI / SETTABLE
+        old["s" .. (i % 13)] = "v" .. i              -- new string key and value
+        holder.field = fresh                         -- SETFIELD
+        G_SLOT = {i}                                 -- SETTABUP on _ENV
+        rawset(old, "raw" .. (i % 7), {i})           -- rawset
+        table.insert(list, 1, {i})                   -- table library stores
+        if #list > 40 then table.remove(list) end
+        oldmt.__index = {i}                          -- store into a metatable
+        if i % 50 == 0 then setmetatable(holder, {__index = fresh}) end
+        rust_store(old, i)                           -- Rust raw_set / raw_seti
+
+        -- Closing an upvalue over a fresh object held by an old closure
+        local up = {i}
+        local get = function() return up end
+        old.closure = get
+        up = {tag = "t" .. i, items = {"y", i}}
+        check(get(), i, "closure")
+
+        -- Stores into closed upvalues of old closures (SETUPVAL) and
+        -- debug.setupvalue
+        setslot({tag = "t" .. i, items = {"z", i}})
+        check(getslot(), i, "setupval")
+        debug.setupvalue(getslot, 1, {tag = "t" .. i, items = {"w", i}})
+        check(getslot(), i, "debug.setupvalue")
+
+        -- debug.upvaluejoin: an old closure adopts a fresh upvalue
+        do
+            local slot = {tag = "t" .. i, items = {"j", i}}
+            debug.upvaluejoin(getjoined, 1, function() return slot end, 1)
+        end
+        check(getjoined(), i, "debug.upvaluejoin")
+        old = keep()
+
+        -- Coroutine stacks and package.loaded
+        local co = coroutine.wrap(function(v) local w = coroutine.yield({v}) return {w} end)
+        co(i)
+        package.loaded["stress" .. (i % 5)] = co({i})
+
+        check(old[i % 31], i, "old")
+        check(holder.field, i, "holder")
+        assert(G_SLOT[1] == i and old["s" .. (i % 13)] == "v" .. i)
+        assert(old["k" .. (i % 17)]["rust" .. i] == i)
+        assert(list[1][1] == i)
+        ops = ops + 1
+    end
+    return ops
+"#;
+
+fn run_workload(mode: &str, rounds: i64, hook_count: i32) {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let state = vm.main_state();
+    state
+        .set_global_value("rust_store", LuaValue::cfunction(rust_store))
+        .unwrap();
+    let mode = state.create_string(mode).unwrap();
+    let func = state.load(WORKLOAD).unwrap();
+    state.set_hook(LuaValue::cfunction(gc_step_hook), LUA_MASKCOUNT, hook_count);
+    let result = state.call(func, vec![LuaValue::integer(rounds), mode]);
+    state.set_hook(LuaValue::nil(), 0, 0);
+    let results = match result {
+        Ok(results) => results,
+        Err(e) => panic!("{}", state.get_error_msg(e)),
+    };
+    assert_eq!(results[0].as_integer(), Some(rounds));
+}
+
+#[test]
+fn test_gc_barriers_with_step_per_instruction() {
+    run_workload("incremental", 2_000, 1);
+}
+
+#[test]
+fn test_gc_barriers_generational() {
+    run_workload("generational", 400, 8);
+}
+
+/// Long run meant for a sanitizer build, e.g.
+/// `RUSTFLAGS=-Zsanitizer=address cargo +nightly test --target
+/// x86_64-unknown-linux-gnu -p luars --lib -- --ignored gc_barriers`.
+#[test]
+#[ignore]
+fn test_gc_barriers_long_run() {
+    run_workload("incremental", 200_000, 1);
+    run_workload("generational", 50_000, 8);
+}
@@ -2,7 +2,11 @@
 
 #[cfg(test)]
 mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
     use crate::lua_vm::{GlobalState, SafeOption};
+    use crate::{LuaResult, LuaState, LuaUserdata};
 
     #[test]
     fn test_gc_metamethod() {
@@ -254,4 +258,159 @@ mod tests {
             Err(e) => panic!("Error: {}", e),
         }
     }
+
+    struct DropProbe {
+        drops: Rc<Cell<u32>>,
+        panics: bool,
+    }
+
+    impl Drop for DropProbe {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+            if self.panics {
+                panic!("DropProbe dropped without being closed");
+            }
+        }
+    }
+
+    crate::impl_simple_userdata!(DropProbe, "DropProbe");
+
+    thread_local! {
+        static CLOSE_LOG: RefCell<Vec<i64>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn log_close(l: &mut LuaState) -> LuaResult<usize> {
+        let n = l.get_arg(1).and_then(|v| v.as_integer()).unwrap_or(0);
+        CLOSE_LOG.with(|log| log.borrow_mut().push(n));
+        Ok(0)
+    }
+
+    #[test]
+    fn test_close_runs_finalizers_newest_first() {
+        let mut vm = GlobalState::new(SafeOption::default());
+        vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+        vm.register_function("log_close", log_close).unwrap();
+        CLOSE_LOG.with(|log| log.borrow_mut().clear());
+
+        let code = r#"
+            keep = {}
+            local mt = {__gc = function(o) log_close(o.n) end}
+            for i = 1, 5 do
+                keep[i] = setmetatable({n = i}, mt)
+                -- churn so earlier cycles separate and sweep the finobj list
+                for _ = 1, 50 do setmetatable({n = 0}, {__gc = function() end}) end
+                collectgarbage()
+            end
+            setmetatable({}, {__gc = function() error("boom") end})
+        "#;
+        vm.main_state().execute(code).unwrap();
+
+        let report = vm.close().unwrap_err();
+        assert_eq!(report.finalizer_errors.len(), 1);
+        assert!(report.finalizer_errors[0].contains("boom"));
+        assert!(report.drop_panics.is_empty());
+        CLOSE_LOG.with(|log| assert_eq!(*log.borrow(), vec![5, 4, 3, 2, 1]));
+    }
+
+    #[test]
+    fn test_close_drops_userdata_despite_panicking_drop() {
+        let mut vm = GlobalState::new(SafeOption::default());
+        vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+        let good = Rc::new(Cell::new(0));
+        let bad = Rc::new(Cell::new(0));
+
+        let state = vm.main_state();
+        let keep = state.create_table(0, 0).unwrap();
+        for i in 0..30 {
+            let panics = i % 10 == 3;
+            let probe = DropProbe {
+                drops: if panics { bad.clone() } else { good.clone() },
+                panics,
+            };
+            let ud = state.create_userdata(LuaUserdata::new(probe)).unwrap();
+            state.raw_seti(&keep, i + 1, ud);
+        }
+        state.set_global_value("keep", keep).unwrap();
+
+        let report = vm.close().unwrap_err();
+        assert!(report.finalizer_errors.is_empty());
+        assert_eq!(report.drop_panics.len(), 3);
+        assert!(report.drop_panics[0].contains("without being closed"));
+        assert_eq!(good.get(), 27);
+        assert_eq!(bad.get(), 3);
+    }
+
+    fn rust_collect(l: &mut LuaState) -> LuaResult<usize> {
+        l.collect_garbage()?;
+        Ok(0)
+    }
+
+    #[test]
+    fn test_finalizers_cannot_reenter_the_collector() {
+        let mut vm = GlobalState::new(SafeOption::default());
+        vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+        vm.register_function("rust_collect", rust_collect).unwrap();
+
+        let code = r#"
+            collectgarbage("incremental")
+            local live, finalized, refused = {}, 0, 0
+            local mt = {}
+            mt.__gc = function(o)
+                finalized = finalized + 1
+                local data = {o.id, tostring(o.id), {o.id}}
+                -- Nested requests, from Rust or Lua, are refused (fail)
+                -- instead of reentering the collector
+                rust_collect()
+                if collectgarbage() == nil and collectgarbage("step") == nil then
+                    refused = refused + 1
+                end
+                if o.id % 3 == 0 then
+                    live[#live + 1] = {obj = o, data = data}
+                end
+            end
+
+            local function check()
+                for _, r in ipairs(live) do
+                    local id = r.obj.id
+                    assert(r.data[1] == id and r.data[2] == tostring(id))
+                    assert(r.data[3][1] == id and r.obj.payload[1] == "p" .. id)
+                end
+            end
+
+            for cycle = 1, 3000 do
+                for i = 1, 4 do
+                    local id = cycle * 10 + i
+                    setmetatable({id = id, payload = {"p" .. id}}, mt)
+                end
+                if cycle % 2 == 0 then
+                    assert(collectgarbage() == 0)
+                else
+                    collectgarbage("step", 0)
+                end
+                if #live > 64 then
+                    check()
+                    live = {}
+                end
+            end
+            collectgarbage()
+            collectgarbage()
+            check()
+            return finalized, refused
+        "#;
+
+        let results = vm.main_state().execute(code).unwrap();
+        let finalized = results[0].as_integer().unwrap();
+        assert!(finalized > 10_000, "only {finalized} finalizers ran");
+        assert_eq!(results[1].as_integer(), Some(finalized));
+    }
+
+    #[test]
+    fn test_close_clean_state() {
+        let mut vm = GlobalState::new(SafeOption::default());
+        vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+        vm.main_state()
+            .execute("setmetatable({}, {__gc = function() end})")
+            .unwrap();
+        assert!(vm.close().is_ok());
+    }
 }
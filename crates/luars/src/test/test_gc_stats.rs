@@ -0,0 +1,154 @@
+// Tests for GcStats and the GC event hook
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::lua_vm::{GlobalState, SafeOption};
+    use crate::{GcCollectionKind, GcEvent};
+
+    fn new_vm() -> std::pin::Pin<Box<GlobalState>> {
+        let mut vm = GlobalState::new(SafeOption::default());
+        vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+        vm
+    }
+
+    fn record_events(vm: &mut GlobalState) -> Rc<RefCell<Vec<GcEvent>>> {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let sink = events.clone();
+        vm.set_gc_event_hook(move |event| sink.borrow_mut().push(event));
+        events
+    }
+
+    /// Every Start is followed by the End of the same kind.
+    fn assert_paired(events: &[GcEvent]) {
+        assert!(
+            events.len().is_multiple_of(2),
+            "unpaired events: {events:?}"
+        );
+        for pair in events.chunks(2) {
+            match (pair[0], pair[1]) {
+                (
+                    GcEvent::Start { kind, bytes },
+                    GcEvent::End {
+                        kind: end_kind,
+                        bytes_before,
+                        ..
+                    },
+                ) => {
+                    assert_eq!(kind, end_kind, "{pair:?}");
+                    assert_eq!(bytes, bytes_before, "{pair:?}");
+                }
+                _ => panic!("events out of order: {pair:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_gc_stats_move_across_collect() {
+        let mut vm = new_vm();
+        vm.main_state().execute("collectgarbage()").unwrap();
+        let before = vm.gc_stats_struct();
+        assert_eq!(before.full_collections, 1);
+
+        vm.main_state()
+            .execute(
+                r#"
+                garbage = {}
+                for i = 1, 2000 do garbage[i] = {i, tostring(i)} end
+                "#,
+            )
+            .unwrap();
+        let grown = vm.gc_stats_struct();
+        assert!(grown.total_allocated > before.total_allocated);
+
+        vm.main_state()
+            .execute("garbage = nil; collectgarbage()")
+            .unwrap();
+        let after = vm.gc_stats_struct();
+        assert_eq!(after.full_collections, 2);
+        assert_eq!(after.collections(GcCollectionKind::Full), 2);
+        assert!(after.bytes_reclaimed > before.bytes_reclaimed);
+        assert!(after.bytes_allocated < grown.bytes_allocated);
+        assert_eq!(after.old_gen_size, 0);
+        assert!(after.young_gen_size > 0);
+        assert_eq!(
+            after.threshold as isize - after.bytes_allocated as isize,
+            after.debt
+        );
+        assert!(vm.gc_stats().contains("Full collections: 2"));
+    }
+
+    #[test]
+    fn test_gc_event_hook_fires_in_pairs() {
+        let mut vm = new_vm();
+        let events = record_events(&mut vm);
+
+        vm.main_state()
+            .execute(
+                r#"
+                local function churn()
+                    for i = 1, 20000 do local t = {i, {}} end
+                end
+                collectgarbage()
+                churn()
+                collectgarbage("generational")
+                churn()
+                collectgarbage()
+                collectgarbage("incremental")
+                churn()
+                "#,
+            )
+            .unwrap();
+        vm.clear_gc_event_hook();
+        vm.main_state().execute("collectgarbage()").unwrap();
+
+        let events = events.borrow();
+        assert_paired(&events);
+        let ends = |kind| {
+            events
+                .iter()
+                .filter(|e| matches!(e, GcEvent::End { kind: k, .. } if *k == kind))
+                .count()
+        };
+        assert_eq!(ends(GcCollectionKind::Full), 2);
+        assert!(ends(GcCollectionKind::Incremental) > 0);
+        assert!(ends(GcCollectionKind::Minor) > 0);
+
+        let stats = vm.gc_stats_struct();
+        assert_eq!(stats.full_collections, 3);
+        assert!(stats.minor_collections >= ends(GcCollectionKind::Minor));
+    }
+
+    #[test]
+    fn test_gc_event_hook_reports_emergency_collection() {
+        let mut vm = GlobalState::new(SafeOption {
+            max_memory_limit: 4 * 1024 * 1024,
+            ..SafeOption::default()
+        });
+        vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+        let events = record_events(&mut vm);
+
+        vm.main_state()
+            .execute(
+                r#"
+                collectgarbage("stop")
+                for i = 1, 200 do local s = string.rep("x", 64 * 1024) .. i end
+                "#,
+            )
+            .unwrap();
+
+        let events = events.borrow();
+        assert_paired(&events);
+        assert!(
+            events.iter().any(|e| matches!(
+                e,
+                GcEvent::End { kind: GcCollectionKind::Emergency, bytes_before, bytes_after, .. }
+                    if bytes_after < bytes_before
+            )),
+            "{events:?}"
+        );
+        assert!(vm.gc_stats_struct().emergency_collections > 0);
+    }
+}
@@ -526,3 +526,137 @@ fn test_io_popen_write_mode() {
 
     assert!(result.is_ok(), "Error: {:?}", result);
 }
+
+#[test]
+fn test_io_open_modes() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local name = os.tmpname()
+        os.remove(name)
+
+        -- failure: nil, "<name>: <strerror>", errno
+        for _, m in ipairs{"r", "rb", "r+", "r+b", "rb+"} do
+            local f, msg, errno = io.open(name, m)
+            assert(f == nil and msg:find(name, 1, true) == 1, msg)
+            assert(not msg:find("os error"), msg)
+            assert(math.type(errno) == "integer" and errno ~= 0)
+        end
+
+        for _, m in ipairs{"w", "wb", "a", "ab", "r", "rb", "r+", "r+b", "rb+",
+                           "w+", "w+b", "wb+", "a+", "a+b", "ab+"} do
+            assert(io.open(name, m)):close()
+        end
+        -- "+" and "b" may come in either order, "+" at most once
+        for _, m in ipairs{"", "x", "rw", "+", "b", "r++", "rb++", "r+b+", "r+bk", " r"} do
+            local ok, err = pcall(io.open, name, m)
+            assert(not ok and err:find("invalid mode"), m)
+        end
+
+        -- writing a file opened for reading fails with a result, not an error
+        local f = assert(io.open(name, "w")); f:write("hello"); f:close()
+        f = assert(io.open(name, "r"))
+        local r, msg, errno = f:write("x")
+        assert(r == nil and type(msg) == "string" and math.type(errno) == "integer")
+        f:close()
+
+        -- append mode writes at the end even after a seek
+        f = assert(io.open(name, "a+"))
+        f:seek("set", 0)
+        assert(f:read(2) == "he")
+        f:write("!")
+        f:seek("set", 0)
+        assert(f:read("a") == "hello!")
+        f:close()
+
+        -- r+ keeps contents and writes at the current position
+        f = assert(io.open(name, "r+b"))
+        assert(f:read(1) == "h")
+        f:write("E")
+        f:seek("set")
+        assert(f:read("a") == "hEllo!")
+        f:close()
+
+        -- w+ truncates
+        f = assert(io.open(name, "w+"))
+        assert(f:read("a") == "")
+        f:write("abc")
+        f:seek("set")
+        assert(f:read("a") == "abc")
+        f:close()
+
+        os.remove(name)
+        "#,
+    );
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+}
+
+#[test]
+fn test_io_default_files() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local input, output = os.tmpname(), os.tmpname()
+        local f = assert(io.open(input, "w"))
+        f:write("first line\n", "42 3.5\n", "rest\n")
+        f:close()
+
+        assert(io.input() == io.stdin and io.output() == io.stdout)
+
+        -- redirect both defaults by name
+        local inp = io.input(input)
+        assert(io.type(inp) == "file" and io.input() == inp)
+        local out = io.output(output)
+        assert(io.output() == out)
+
+        assert(io.read("l") == "first line")
+        local a, b = io.read("n", "n")
+        assert(a == 42 and b == 3.5)
+        io.read("l")
+        for line in io.lines() do io.write("<", line, ">\n") end
+        assert(io.read("l") == nil)
+
+        -- io.close() with no argument closes the default output
+        assert(io.close())
+        assert(io.type(out) == "closed file")
+        io.output(io.stdout)
+        io.input(io.stdin)
+
+        f = assert(io.open(output, "r"))
+        assert(f:read("a") == "<rest>\n")
+        f:close()
+
+        -- setting a default by handle
+        f = assert(io.open(output, "w"))
+        assert(io.output(f) == f)
+        io.write("via handle")
+        io.output(io.stdout)
+        f:close()
+        assert(io.lines(output)() == "via handle")
+
+        -- opening by name raises instead of returning nil
+        os.remove(input)
+        local ok, err = pcall(io.input, input)
+        assert(not ok and err:find("cannot open file '" .. input .. "' (", 1, true), err)
+        ok, err = pcall(io.lines, input)
+        assert(not ok and err:find("No such file", 1, true), err)
+        assert(io.input() == io.stdin)
+
+        -- standard files cannot be closed
+        for _, std in ipairs{io.stdin, io.stdout, io.stderr} do
+            local r, msg = std:close()
+            assert(r == nil and msg == "cannot close standard file")
+            assert(io.type(std) == "file")
+        end
+
+        os.remove(output)
+        "#,
+    );
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+}
@@ -0,0 +1,153 @@
+// Tests for the optional json library
+use crate::*;
+
+fn run(code: &str) {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(code);
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_json_encode() {
+    run(r#"
+        assert(json.encode(1) == "1")
+        assert(json.encode(-7) == "-7")
+        assert(json.encode(1.5) == "1.5")
+        assert(json.encode(2.0) == "2.0")
+        assert(json.encode(true) == "true")
+        assert(json.encode(nil) == "null")
+        assert(json.encode(json.null) == "null")
+        assert(json.encode("a\"b\\c\n\t\1") == [["a\"b\\c\n\t\u0001"]])
+        assert(json.encode({1, 2, 3}) == "[1,2,3]")
+        assert(json.encode({}) == "[]")
+        assert(json.encode({}, {empty_as_object = true}) == "{}")
+        assert(json.encode({1, json.null, 3}) == "[1,null,3]")
+        assert(json.encode({x = 1}) == [[{"x":1}]])
+        assert(json.encode({[1] = "a", [3] = "c"}) == [[{"1":"a","3":"c"}]]
+            or json.encode({[1] = "a", [3] = "c"}) == [[{"3":"c","1":"a"}]])
+        assert(json.encode({{}, {a = {}}}, {empty_as_object = true}) == [=[[{},{"a":{}}]]=])
+    "#);
+}
+
+#[test]
+fn test_json_encode_errors() {
+    run(r#"
+        local ok, err = pcall(json.encode, print)
+        assert(not ok and err:find("cannot encode value of type 'function'"))
+        ok, err = pcall(json.encode, 0/0)
+        assert(not ok and err:find("non%-finite"))
+        ok, err = pcall(json.encode, {[true] = 1})
+        assert(not ok and err:find("table key must be a string or number"))
+        local t = {}
+        t[1] = t
+        ok, err = pcall(json.encode, t)
+        assert(not ok and err:find("excessive nesting"))
+        ok, err = pcall(json.encode, {{{1}}}, {max_depth = 2})
+        assert(not ok and err:find("more than 2 levels"))
+        assert(json.encode({{{1}}}, {max_depth = 3}) == "[[[1]]]")
+        local deep = {}
+        local c = deep
+        for _ = 1, 199 do c[1] = {} c = c[1] end
+        assert(#json.encode(deep) == 400)
+        ok, err = pcall(json.encode, deep, {max_depth = 1000})
+        assert(ok)
+    "#);
+}
+
+#[test]
+fn test_json_decode() {
+    run(r#"
+        assert(json.decode("42") == 42 and math.type(json.decode("42")) == "integer")
+        assert(json.decode("-1.5e2") == -150.0)
+        assert(math.type(json.decode("1.0")) == "float")
+        assert(math.type(json.decode("99999999999999999999")) == "float")
+        assert(json.decode(" true ") == true)
+        assert(json.decode("null") == json.null)
+        assert(json.decode("null", {null_as_nil = true}) == nil)
+        assert(json.decode([["a\"\\\/\b\f\n\r\t"]]) == "a\"\\/\b\f\n\r\t")
+        assert(json.decode([["é世😀"]]) == "é世😀")
+
+        local t = json.decode([[ {"a": [1, 2, {"b": null}], "c": "d"} ]])
+        assert(t.a[1] == 1 and t.a[2] == 2 and t.a[3].b == json.null and t.c == "d")
+        t = json.decode("[1, null, 3]", {null_as_nil = true})
+        assert(t[1] == 1 and t[2] == nil and t[3] == 3)
+    "#);
+}
+
+#[test]
+fn test_json_decode_errors() {
+    run(r#"
+        local function fails(s, pattern, opts)
+            local ok, err = pcall(json.decode, s, opts)
+            assert(not ok, s)
+            assert(err:find(pattern), err)
+        end
+        fails('{\n  "a": 1,\n  "b" 2\n}', "expected ':' but found '2' at line 3 col 7")
+        fails("[1, 2", "unexpected end of input at line 1 col 6")
+        fails("[1,]", "unexpected character ']' at line 1 col 4")
+        fails("01", "unexpected trailing character '1' at line 1 col 2")
+        fails("tru", "unexpected end of input")
+        fails('"abc', "unexpected end of input")
+        fails('"\\x"', "invalid escape 'x'")
+        fails('"\\ud800"', "invalid unicode escape")
+        fails("{1: 2}", "expected string key but found '1'")
+        fails("[[[1]]]", "more than 2 levels", {max_depth = 2})
+        fails(string.rep("[", 100000), "more than 200 levels")
+        assert(json.decode(string.rep("[", 200) .. string.rep("]", 200)))
+        local ok, err = pcall(json.decode, 1)
+        assert(not ok and err:find("bad argument #1"))
+    "#);
+}
+
+#[test]
+fn test_json_round_trip() {
+    run(r#"
+        local doc = {
+            name = "luars", version = 5.5, tags = {"lua", "rust"},
+            nested = {deep = {deeper = {1, 2.5, "three", false}}},
+            text = "line1\nline2 \"quoted\" \\ tab\t ctl\31 é",
+            min = math.mininteger, max = math.maxinteger,
+            tiny = 5e-324, huge = 1.7976931348623157e308,
+        }
+        local function same(a, b)
+            if type(a) ~= type(b) then return false end
+            if type(a) ~= "table" then
+                return a == b and math.type(a) == math.type(b)
+            end
+            for k, v in pairs(a) do if not same(v, b[k]) then return false end end
+            for k in pairs(b) do if a[k] == nil then return false end end
+            return true
+        end
+        local text = json.encode(doc)
+        assert(same(json.decode(text), doc), text)
+        assert(json.encode(json.decode(text)) == json.encode(json.decode(json.encode(json.decode(text)))))
+    "#);
+}
+
+#[test]
+fn test_json_decode_under_memory_pressure() {
+    let option = SafeOption {
+        max_memory_limit: 16 * 1024 * 1024,
+        ..Default::default()
+    };
+    let mut vm = GlobalState::new(option);
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // The limit is crossed while the decoder fills arrays and objects
+    let result = vm.main_state().execute(
+        r#"
+        collectgarbage("stop")
+        local mb = 1024 * 1024
+        local text = '[{"a":1},"' .. string.rep("x", mb) .. '"]'
+        for _ = 1, 40 do
+            local value = json.decode(text)
+            assert(value[1].a == 1)
+            assert(#value[2] == mb)
+        end
+        collectgarbage("restart")
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+    assert!(vm.gc_stats_struct().emergency_collections > 0);
+}
@@ -244,3 +244,88 @@ fn test_math_ult() {
 
     assert!(result.is_ok());
 }
+
+#[test]
+fn test_math_edge_inputs_match_reference() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // Expected results are those of the reference implementation
+    let result = vm.main_state().execute(
+        r#"
+        local minint, inf, nan = math.mininteger, math.huge, 0/0
+        local function show(v)
+            if v ~= v then return "nan" end
+            return math.type(v) .. " " .. tostring(v)
+        end
+        local function check(name, args, ...)
+            local got = table.pack(math[name](table.unpack(args, 1, args.n)))
+            local want = table.pack(...)
+            assert(got.n == want.n, name)
+            for i = 1, want.n do
+                local msg = string.format("math.%s #%d: got %s, want %s",
+                    name, i, show(got[i]), want[i])
+                assert(show(got[i]) == want[i], msg)
+            end
+        end
+        local function args(...) return table.pack(...) end
+
+        check("abs", args(minint), "integer -9223372036854775808")
+        check("abs", args(-0.0), "float 0.0")
+        check("abs", args(-3.0), "float 3.0")
+        check("abs", args(-inf), "float inf")
+        check("abs", args(nan), "nan")
+
+        check("floor", args(minint), "integer -9223372036854775808")
+        check("floor", args(-0.0), "integer 0")
+        check("floor", args(-3.7), "integer -4")
+        check("floor", args(2.0^63), "float 9.2233720368547758e+18")
+        check("floor", args(-2.0^63), "integer -9223372036854775808")
+        check("floor", args(inf), "float inf")
+        check("floor", args(nan), "nan")
+
+        check("ceil", args(minint), "integer -9223372036854775808")
+        check("ceil", args(-0.5), "integer 0")
+        check("ceil", args(3.2), "integer 4")
+        check("ceil", args(-inf), "float -inf")
+        check("ceil", args(nan), "nan")
+
+        check("fmod", args(minint, -1), "integer 0")
+        check("fmod", args(-5, 3), "integer -2")
+        check("fmod", args(-0.0, 1), "float -0.0")
+        check("fmod", args(5.5, -2), "float 1.5")
+        check("fmod", args(1, inf), "float 1.0")
+        check("fmod", args(inf, 1), "nan")
+        check("fmod", args(1, 0.0), "nan")
+        check("fmod", args(nan, 1), "nan")
+        assert(not pcall(math.fmod, 1, 0))
+
+        check("modf", args(minint), "integer -9223372036854775808", "float 0.0")
+        check("modf", args(3.5), "float 3.0", "float 0.5")
+        check("modf", args(-2.5), "float -2.0", "float -0.5")
+        check("modf", args(-0.0), "float -0.0", "float 0.0")
+        check("modf", args(inf), "float inf", "float 0.0")
+        check("modf", args(-inf), "float -inf", "float 0.0")
+        check("modf", args(nan), "nan", "nan")
+
+        check("log", args(8, 2), "float 3.0")
+        check("log", args(1000, 10), "float 3.0")
+        check("log", args(0), "float -inf")
+        check("log", args(-1), "nan")
+        check("log", args(1, nil), "float 0.0")
+        assert(not pcall(math.log, 1, {}))
+
+        check("sqrt", args(-0.0), "float -0.0")
+        check("sqrt", args(inf), "float inf")
+        check("sqrt", args(-1), "nan")
+        check("exp", args(-inf), "float 0.0")
+        check("exp", args(inf), "float inf")
+
+        assert(math.pi == 3.141592653589793)
+        assert(string.format("%.14g", math.pi) == "3.1415926535898")
+        assert(math.type(math.huge) == "float" and math.huge == 1/0)
+    "#,
+    );
+
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+}
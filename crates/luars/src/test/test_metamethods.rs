@@ -366,6 +366,58 @@ fn test_tostring_metamethod() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_tostring_metamethod_errors() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local raising = setmetatable({}, {__tostring = function() error("boom") end})
+        local numeric = setmetatable({}, {__tostring = function() return 42 end})
+
+        for _, f in ipairs({print, tostring, function(v) return string.format("%s", v) end}) do
+            local ok, msg = pcall(f, raising)
+            assert(not ok and string.find(msg, "boom"), msg)
+            ok, msg = pcall(f, numeric)
+            assert(not ok and string.find(msg, "'__tostring' must return a string"), msg)
+        end
+
+        -- Concatenation never calls __tostring
+        local ok, msg = pcall(function() return "x" .. raising end)
+        assert(not ok and string.find(msg, "attempt to concatenate a table value"), msg)
+        assert(not string.find(msg, "boom"), msg)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_error_object_message_uses_tostring() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let state = vm.main_state();
+    let mut message = |code: &str| {
+        let err = state.execute(code).unwrap_err();
+        state.get_full_error(err).message
+    };
+
+    let msg = message(r#"error(setmetatable({}, {__tostring = function() return "custom" end}))"#);
+    assert!(msg.starts_with("custom"), "{msg}");
+    let msg = message(r#"error(setmetatable({}, {__tostring = function() error("boom") end}))"#);
+    assert!(msg.starts_with("(error object is a table value)"), "{msg}");
+    let msg = message(r#"error(setmetatable({}, {__tostring = function() return {} end}))"#);
+    assert!(msg.starts_with("(error object is a table value)"), "{msg}");
+    let msg = message("error({})");
+    assert!(msg.starts_with("(error object is a table value)"), "{msg}");
+    let msg = message("error(true)");
+    assert!(
+        msg.starts_with("(error object is a boolean value)"),
+        "{msg}"
+    );
+    let msg = message("error('plain')");
+    assert!(msg.contains("plain"), "{msg}");
+}
+
 #[test]
 fn test_len_metamethod() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -775,6 +827,48 @@ fn test_ipairs_stops_at_nil() {
     assert!(result.is_ok(), "ipairs stops at nil failed: {:?}", result);
 }
 
+#[test]
+fn test_ipairs_proxy_holes_and_overflow() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        -- own keys win over __index; iteration stops at the first nil
+        local backing = {10, 20, nil, 40}
+        local proxy = setmetatable({[2] = 99}, { __index = backing })
+        local seen = {}
+        for i, v in ipairs(proxy) do seen[i] = v end
+        assert(#seen == 2 and seen[1] == 10 and seen[2] == 99)
+
+        -- a metatable without __index keeps raw semantics
+        local plain = setmetatable({1, 2, nil, 4}, { __tostring = tostring })
+        local n = 0
+        for _ in ipairs(plain) do n = n + 1 end
+        assert(n == 2)
+
+        -- adding __index later must be picked up
+        getmetatable(plain).__index = function(_, k) if k == 3 then return 3 end end
+        n = 0
+        for _ in ipairs(plain) do n = n + 1 end
+        assert(n == 4)
+
+        -- the control variable wraps at maxinteger instead of erroring
+        local f, s = ipairs({})
+        assert(f(s, math.maxinteger) == nil)
+        local wrap = setmetatable({}, { __index = function(_, k) return k end })
+        local k, v = f(wrap, math.maxinteger)
+        assert(k == math.mininteger and v == math.mininteger)
+    "#,
+    );
+
+    assert!(
+        result.is_ok(),
+        "ipairs proxy iteration failed: {:?}",
+        result
+    );
+}
+
 #[test]
 fn test_pairs_with_pairs_metamethod() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -808,6 +902,32 @@ fn test_pairs_with_pairs_metamethod() {
     assert!(result.is_ok(), "pairs with __pairs failed: {:?}", result);
 }
 
+#[test]
+fn test_pairs_metamethod_results_are_padded() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local backing = {a = 1}
+        local proxy = setmetatable({}, {__pairs = function() return next, backing end})
+        local f, s, c, closing = pairs(proxy)
+        assert(f == next and s == backing and c == nil and closing == nil)
+        assert(select('#', pairs(proxy)) == 4)
+
+        local n = 0
+        for k, v in pairs(proxy) do n = n + 1; assert(k == "a" and v == 1) end
+        assert(n == 1 and rawget(proxy, "a") == nil)
+
+        -- extra results beyond the fourth are dropped
+        local wide = setmetatable({}, {__pairs = function() return next, {}, nil, nil, "extra" end})
+        assert(select('#', pairs(wide)) == 4)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_pairs_with_tbc_variable() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -949,3 +1069,464 @@ fn test_pairs_yield_multiple_times_in_pairs_metamethod() {
         result
     );
 }
+
+#[test]
+fn test_global_reads_with_env_metatable() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        -- the same GETTABUP instructions run against several _ENV tables
+        local code = [[
+            local s = 0
+            for i = 1, 10 do s = s + (x or 0) + math.floor(1.5) end
+            return s, y
+        ]]
+        local misses = 0
+        local env = setmetatable({}, {__index = function(_, k)
+            misses = misses + 1
+            if k == "math" then return math end
+        end})
+        local f = load(code, "env", "t", env)
+
+        -- absent keys go through __index
+        local s, y = f()
+        assert(s == 10 and y == nil and misses == 21, misses)
+
+        -- present keys are raw hits and skip __index
+        misses = 0
+        env.x = 2
+        env.math = math
+        s, y = f()
+        assert(s == 30 and y == nil and misses == 1, misses)
+
+        -- grow the table so the cached node positions go stale
+        for i = 1, 100 do env["g" .. i] = i end
+        env.y = "y"
+        s, y = f()
+        assert(s == 30 and y == "y" and misses == 1, misses)
+
+        -- a key set back to nil must fall back to __index again
+        env.x = nil
+        s = f()
+        assert(s == 10 and misses == 11, misses)
+
+        -- a different _ENV for the same prototype
+        local g = load(code, "env", "t", {x = 5, math = {floor = function() return 3 end}})
+        assert(g() == 80)
+        s = f()
+        assert(s == 10 and misses == 21, misses)
+
+        -- closures of one prototype reading through different _ENV upvalues
+        local function mk(_ENV) return function() return x end end
+        local a = mk({x = "a"})
+        local b = mk(setmetatable({}, {__index = {x = "b"}}))
+        for _ = 1, 3 do assert(a() == "a" and b() == "b") end
+    "#,
+    );
+
+    assert!(
+        result.is_ok(),
+        "global reads with _ENV metatable failed: {:?}",
+        result
+    );
+}
+
+#[test]
+fn test_absent_metamethod_cache_invalidation() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // Each read below first caches "event absent" on the metatable, then the
+    // event is added through a different store path.
+    let result = vm.main_state().execute(
+        r#"
+        local mt = {}
+        local t = setmetatable({}, mt)
+        assert(t.x == nil)
+        rawset(mt, "__index", function() return 1 end)
+        assert(t.x == 1, "rawset")
+
+        mt = {}
+        t = setmetatable({}, mt)
+        assert(t.x == nil)
+        local k = "__index"
+        mt[k] = {x = 2}
+        assert(t.x == 2, "settable")
+
+        mt = {}
+        t = setmetatable({}, mt)
+        assert(not pcall(function() return t + 1 end))
+        mt.__add = function() return 3 end
+        assert(t + 1 == 3, "setfield")
+
+        mt = {}
+        t = setmetatable({}, mt)
+        assert(#t == 0)
+        mt.__len = function() return 4 end
+        assert(#t == 4, "len")
+
+        -- the key already exists with a nil value
+        mt = {__index = false}
+        t = setmetatable({}, mt)
+        mt.__index = nil
+        assert(t.x == nil)
+        mt.__index = {x = 5}
+        assert(t.x == 5, "dead key")
+    "#,
+    );
+
+    assert!(
+        result.is_ok(),
+        "metamethod cache invalidation failed: {:?}",
+        result
+    );
+}
+
+#[test]
+fn test_argument_count_same_on_every_call_path() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    // A Rust closure reporting what both argument views of its frame see
+    let rcount = vm
+        .create_closure(|state: &mut LuaState| -> LuaResult<usize> {
+            let n = state.arg_count();
+            assert_eq!(state.get_args().len(), n);
+            assert!(state.get_arg(n + 1).is_none());
+            state.push_value(LuaValue::integer(n as i64))?;
+            Ok(1)
+        })
+        .unwrap();
+    vm.set_global("rcount", rcount).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local function lcount(...) return select('#', ...) end
+        local function pcount(...) return table.pack(...).n end
+        for _, count in ipairs({lcount, pcount, rcount}) do
+            assert(count(1, nil, 3) == 3)
+            assert(count() == 0)
+
+            -- __call inserts the object exactly once, before the arguments
+            local callable = setmetatable({}, {__call = count})
+            assert(callable(1, nil, 3) == 4)
+            assert(callable() == 1)
+            local function tail(...) return callable(...) end
+            assert(tail(1, 2) == 3)
+            assert(select(2, pcall(callable, 1, nil)) == 3)
+            assert(select(2, xpcall(callable, print, 1)) == 2)
+            assert(coroutine.wrap(function(...) return callable(...) end)(1) == 2)
+            -- a __call chain inserts each object once
+            local outer = setmetatable({}, {__call = callable})
+            assert(outer(1) == 3)
+
+            -- metamethods get their operands, whatever kind of callable they
+            -- are (a table __index is indexed instead, so it is not covered)
+            assert(setmetatable({}, {__index = count}).x == 2)
+            for _, mm in ipairs({count, callable}) do
+                local o = setmetatable({}, {
+                    __add = mm, __unm = mm, __len = mm, __concat = mm, __lt = mm, __eq = mm,
+                })
+                local extra = mm == callable and 1 or 0
+                assert(o + 1 == 2 + extra)
+                assert(-o == 2 + extra)
+                assert(#o == 2 + extra)
+                assert(o .. "x" == 2 + extra)
+                assert(o < o)
+                assert(o == setmetatable({}, getmetatable(o)))
+            end
+            local n = 0
+            local o = setmetatable({}, {__newindex = function(...) n = count(...) end})
+            o.x = 1
+            assert(n == 3)
+
+            assert(select(2, pcall(count, 1, nil, 3)) == 3)
+        end
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+
+    // Host-side calls go through the same frame setup
+    let state = vm.main_state();
+    let rcount = state.get_global_value("rcount").unwrap().unwrap();
+    let args = [LuaValue::integer(1), LuaValue::nil()];
+    let results = state.call_args(rcount, &args).unwrap();
+    assert_eq!(results[0].as_integer(), Some(2));
+    let (ok, results) = state.pcall_args(rcount, &args).unwrap();
+    assert!(ok);
+    assert_eq!(results[0].as_integer(), Some(2));
+    let callable = state
+        .execute_single("return setmetatable({}, {__call = rcount})")
+        .unwrap();
+    let results = state.call_args(callable, &args).unwrap();
+    assert_eq!(results[0].as_integer(), Some(3));
+    let (ok, results) = state.pcall_args(callable, &args).unwrap();
+    assert!(ok);
+    assert_eq!(results[0].as_integer(), Some(3));
+}
+
+#[test]
+fn test_callable_tables_accepted_by_stdlib_call_sites() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        -- the real function sees every object of the chain before the
+        -- arguments, so each one works on the trailing values only
+        local function last(...) return (select(select('#', ...), ...)) end
+        local function last2(...)
+            local n = select('#', ...)
+            return select(n - 1, ...)
+        end
+        local function wrap(f)
+            local one = setmetatable({}, {__call = f})
+            return {one, setmetatable({}, {__call = one})}
+        end
+
+        for _, f in ipairs(wrap(function(...) return "ok", last(...) end)) do
+            local ok, a, b = pcall(f, 1)
+            assert(ok and a == "ok" and b == 1)
+            ok, a, b = xpcall(f, print, 2)
+            assert(ok and a == "ok" and b == 2)
+        end
+
+        for _, h in ipairs(wrap(function(...) return "handled: " .. last(...) end)) do
+            local ok, msg = xpcall(error, h, "boom", 0)
+            assert(not ok and msg == "handled: boom")
+        end
+
+        for _, cmp in ipairs(wrap(function(...)
+            local a, b = last2(...)
+            return a > b
+        end)) do
+            local t = {3, 1, 4, 1, 5, 9, 2, 6}
+            table.sort(t, cmp)
+            assert(table.concat(t, ",") == "9,6,5,4,3,2,1,1")
+        end
+
+        for _, iter in ipairs(wrap(function(...)
+            local i = last(...)
+            if i < 3 then return i + 1 end
+        end)) do
+            local sum = 0
+            for i in iter, nil, 0 do sum = sum + i end
+            assert(sum == 6)
+        end
+
+        -- a table replacement is indexed even when it has __call, as in C Lua
+        local repl = setmetatable({a = "A"}, {__call = function() return "X" end})
+        assert(string.gsub("ab", "%w", repl) == "Ab")
+
+        -- the chain is bounded like the CALL opcode's
+        local deep = function() return "deep" end
+        for _ = 1, 20 do deep = setmetatable({}, {__call = deep}) end
+        local ok, msg = pcall(deep)
+        assert(not ok and msg:find("'__call' chain too long"))
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+
+    let state = vm.main_state();
+    let callable = state
+        .execute_single(
+            "return setmetatable({}, {__call = setmetatable({}, {__call = function() end})})",
+        )
+        .unwrap();
+    assert!(state.is_callable(&callable));
+    let plain = state.execute_single("return setmetatable({}, {})").unwrap();
+    assert!(!state.is_callable(&plain));
+    assert!(!state.is_callable(&LuaValue::integer(1)));
+}
+
+#[test]
+fn test_index_and_newindex_chains_are_bounded() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local function cycle(n, event)
+            local ts = {}
+            for i = 1, n do ts[i] = {} end
+            for i = 1, n do setmetatable(ts[i], {[event] = ts[i % n + 1]}) end
+            return ts[1]
+        end
+
+        for _, n in ipairs({2, 3}) do
+            local t = cycle(n, "__index")
+            -- field, variable, integer and method lookups all hit the bound
+            for _, get in ipairs({
+                function() return t.x end,
+                function() local k = {} return t[k] end,
+                function() return t[10] end,
+                function() return t:m() end,
+            }) do
+                local ok, msg = pcall(get)
+                assert(not ok and msg:find("'__index' chain too long; possible loop"), msg)
+            end
+
+            local s = cycle(n, "__newindex")
+            for _, set in ipairs({
+                function() s.x = 1 end,
+                function() s[{}] = 1 end,
+                function() s[10] = 1 end,
+            }) do
+                local ok, msg = pcall(set)
+                assert(not ok and msg:find("'__newindex' chain too long; possible loop"), msg)
+            end
+            assert(rawget(t, "x") == nil and next(s) == nil)
+        end
+
+        -- legitimate deep chains still resolve
+        local base = {v = 42}
+        local t = base
+        for _ = 1, 1500 do t = setmetatable({}, {__index = t}) end
+        assert(t.v == 42 and t.missing == nil)
+        local sink = {}
+        local s = sink
+        for _ = 1, 1500 do s = setmetatable({}, {__newindex = s}) end
+        s.v = 7
+        assert(rawget(sink, "v") == 7 and rawget(s, "v") == nil)
+
+        -- the VM is still usable after the errors
+        assert(select(2, pcall(function() return cycle(2, "__index").x end)):find("chain too long"))
+        assert(string.rep("ab", 3) == "ababab")
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+
+    // The Rust-side accessors are bounded the same way
+    let state = vm.main_state();
+    let looped = state
+        .execute_single(
+            "local a, b = {}, {} \
+             setmetatable(a, {__index = b, __newindex = b}) \
+             setmetatable(b, {__index = a, __newindex = a}) \
+             return a",
+        )
+        .unwrap();
+    let key = state.create_string("x").unwrap();
+    let err = state.table_get(&looped, &key).unwrap_err();
+    assert!(
+        state
+            .get_error_msg(err)
+            .contains("'__index' chain too long; possible loop")
+    );
+    let err = state
+        .table_set(&looped, key, LuaValue::integer(1))
+        .unwrap_err();
+    assert!(
+        state
+            .get_error_msg(err)
+            .contains("'__newindex' chain too long; possible loop")
+    );
+    assert_eq!(
+        state.execute_single("return 1 + 1").unwrap(),
+        LuaValue::integer(2)
+    );
+}
+
+#[test]
+fn test_index_metamethods_see_original_key_type() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local seen = {}
+        local function kind(k) return math.type(k) or type(k) end
+        local function record(op)
+            return function(_, k)
+                seen[#seen + 1] = op .. " " .. kind(k)
+                return k
+            end
+        end
+
+        -- tables: GETI, GETTABLE (integer/float/string register), GETFIELD
+        local t = setmetatable({}, { __index = record("get"), __newindex = record("set") })
+        local i, f, s = 5, 2.0, "name"
+        assert(t[5] == 5 and math.type(t[5]) == "integer")
+        assert(t[i] == 5 and t[f] == 2.0 and t[s] == "name" and t.name == "name")
+        t[5] = 1; t[i] = 1; t[f] = 1; t.name = 1
+        assert(table.concat(seen, ",") == "get integer,get integer,get integer,get float," ..
+            "get string,get string,set integer,set integer,set float,set string", table.concat(seen, ","))
+
+        -- through an __index table chain the key is not stringified either
+        local chain = setmetatable({}, { __index = setmetatable({}, { __index = record("chain") }) })
+        seen = {}
+        assert(chain[7] == 7 and chain[i] == 5)
+        assert(table.concat(seen, ",") == "chain integer,chain integer")
+
+        -- strings: the string library is __index, so integer keys miss
+        assert(("hello")[1] == nil and ("hello")[i] == nil and ("hello").x == nil)
+        assert(("hello").len == string.len)
+        local smt = getmetatable("")
+        local lib = smt.__index
+        smt.__index = record("str")
+        seen = {}
+        local str = "abc"
+        local ok = pcall(function() return str[1], str[i], str[f], str.x end)
+        smt.__index = lib
+        assert(ok)
+        assert(table.concat(seen, ",") == "str integer,str integer,str float,str string")
+        local ok2, err = pcall(function() str[1] = 2 end)
+        assert(not ok2 and err:find("attempt to index a string value"), err)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_self_referential_metatables() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        -- A table that is its own __index table: the lookup revisits the same
+        -- table until MAXTAGLOOP, as luaV_finishget does, and fails cleanly
+        local mt = {}
+        mt.__index = mt
+        setmetatable(mt, mt)
+        local ok, err = pcall(function() return mt.missing end)
+        assert(not ok and err:find("'__index' chain too long; possible loop"), err)
+        ok, err = pcall(function() return mt[1] end)
+        assert(not ok and err:find("'__index' chain too long"), err)
+        assert(rawget(mt, "missing") == nil and mt.__index == mt)
+
+        mt.__newindex = mt
+        ok, err = pcall(function() mt.y = 5 end)
+        assert(not ok and err:find("'__newindex' chain too long; possible loop"), err)
+        assert(rawget(mt, "y") == nil)
+
+        -- function metamethods living on the table they serve
+        local s = setmetatable({ v = 9 }, nil)
+        setmetatable(s, s)
+        s.__index = function(self, k) return rawget(self, "v") end
+        assert(s.anything == 9)
+
+        local w = {}
+        setmetatable(w, w)
+        w.__newindex = function(self, k, v)
+            rawset(self, k, v * 2)
+            self.__newindex = nil   -- present key: plain write
+            self.other = v          -- no __newindex any more: plain write
+        end
+        w.a = 1
+        assert(w.a == 2 and w.other == 1)
+
+        -- __newindex writing back into the table being written
+        local t = setmetatable({}, { __newindex = function(self, k, v)
+            rawset(self, k, v)
+            if #k < 3 then self[k .. "2"] = v end
+        end })
+        t.a = 1
+        assert(t.a == 1 and t.a2 == 1 and t.a22 == 1)
+
+        -- a metamethod that detaches its own metatable mid-lookup
+        local g = {}
+        setmetatable(g, g)
+        g.__index = function(self) setmetatable(self, nil) return "once" end
+        assert(g.x == "once" and g.x == nil)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
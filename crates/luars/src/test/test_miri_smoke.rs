@@ -98,3 +98,74 @@ fn test_miri_pow_metamethod_regression() {
 
     assert!(result.is_ok(), "Error: {:?}", result.err());
 }
+
+/// C function that forces the stack Vec to move to a new allocation
+fn force_stack_realloc(state: &mut LuaState) -> LuaResult<usize> {
+    let needed = state.stack.capacity() + 1;
+    state.grow_stack(needed)?;
+    Ok(0)
+}
+
+/// Generic-for iterator that reallocates the stack on every step:
+/// yields 1, 2, 3 and then nil.
+fn realloc_iter(state: &mut LuaState) -> LuaResult<usize> {
+    let control = state.get_arg(2).and_then(|v| v.as_integer()).unwrap_or(0);
+    force_stack_realloc(state)?;
+    if control >= 3 {
+        state.push_value(LuaValue::nil())?;
+    } else {
+        state.push_value(LuaValue::integer(control + 1))?;
+    }
+    Ok(1)
+}
+
+#[test]
+fn test_miri_stack_realloc_keeps_frames_valid() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    vm.set_global("grow", LuaValue::cfunction(force_stack_realloc))
+        .unwrap();
+    vm.set_global("realloc_iter", LuaValue::cfunction(realloc_iter))
+        .unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local function nested(depth)
+            if depth == 0 then
+                local a, b, c = 1, 2, 3
+                grow()
+                a, b, c = a + 10, b + 20, c + 30
+                return a + b + c
+            end
+            local before = depth * 2
+            local r = nested(depth - 1)
+            local after = before + r
+            return after
+        end
+        assert(nested(40) == 66 + 40 * 41)
+
+        local sum = 0
+        for i in realloc_iter, nil, 0 do
+            local x = i * 2
+            sum = sum + x
+        end
+        assert(sum == 12)
+
+        -- a call hook that grows the stack before the callee touches registers
+        local function callee(p, q)
+            local r = p * q
+            return r + 1
+        end
+        debug.sethook(function() grow() end, "c")
+        local v = callee(6, 7)
+        debug.sethook()
+        assert(v == 43)
+        "#,
+    );
+
+    assert!(
+        result.is_ok(),
+        "stack reallocation corrupted frame: {:?}",
+        result.err()
+    );
+}
@@ -25,6 +25,66 @@ fn test_arithmetic_operators() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_floor_division_and_modulo_signs() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // Expected values are PUC Lua 5.5 outputs. Each case runs on locals (VM
+    // fast paths), on literals (constant folding) and on numeric strings.
+    let result = vm.main_state().execute(
+        r#"
+        local cases = {
+            -- a, b, a // b, a % b
+            { 7, 2, 3, 1 }, { 7, -2, -4, -1 }, { -7, 2, -4, 1 }, { -7, -2, 3, -1 },
+            { 7.0, 2.0, 3.0, 1.0 }, { 7.0, -2.0, -4.0, -1.0 },
+            { -7.0, 2.0, -4.0, 1.0 }, { -7.0, -2.0, 3.0, -1.0 },
+            { math.mininteger, -1, math.mininteger, 0 },
+            { 1, math.huge, 0.0, 1.0 }, { -1, math.huge, -0.0, math.huge },
+        }
+        local function same(x, y)
+            return x == y and math.type(x) == math.type(y)
+        end
+        for i, c in ipairs(cases) do
+            local a, b, q, r = c[1], c[2], c[3], c[4]
+            assert(same(a // b, q), "// case " .. i)
+            assert(same(a % b, r), "% case " .. i)
+            local fq, fr = load(string.format("return %q // %q, %q %% %q", a, b, a, b))()
+            assert(same(fq, q) and same(fr, r), "folded case " .. i)
+            if b ~= math.huge then
+                assert(same(tostring(a) // tostring(b), q), "string // case " .. i)
+                assert(same(tostring(a) % tostring(b), r), "string % case " .. i)
+            end
+        end
+        assert(same((-9223372036854775807 - 1) // -1, math.mininteger))
+
+        -- integer zero divisors raise; float ones follow IEEE
+        local zero = 0
+        for _, f in ipairs({
+            function() return 1 // zero end,
+            function() return "1" // "0" end,
+        }) do
+            local ok, err = pcall(f)
+            assert(not ok and err:find("attempt to perform 'n//0'"), err)
+        end
+        for _, f in ipairs({
+            function() return 1 % zero end,
+            function() return "1" % "0" end,
+        }) do
+            local ok, err = pcall(f)
+            assert(not ok and err:find("attempt to perform 'n%%0'"), err)
+        end
+        assert(1 // 0.0 == math.huge and -1 // 0.0 == -math.huge)
+        local nan = 1 % 0.0
+        assert(nan ~= nan)
+        -- math.fmod truncates instead
+        assert(math.fmod(-7, 2) == -1 and math.fmod(7, -2) == 1)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_comparison_operators() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -265,12 +265,113 @@ fn test_os_tmpname() {
         local name = os.tmpname()
         assert(type(name) == "string")
         assert(#name > 0)
+        -- like mkstemp, the name is claimed by creating the file
+        assert(io.open(name, "r")):close()
+        assert(os.tmpname() ~= name)
+        assert(os.remove(name))
         "#,
     );
 
     assert!(result.is_ok(), "Error: {:?}", result);
 }
 
+#[test]
+fn test_os_file_errors_return_triple() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local name = os.tmpname()
+        assert(os.remove(name))
+
+        -- nil, "<filename>: <strerror>", errno, as luaL_fileresult returns
+        local ok, msg, errno = os.remove(name)
+        assert(ok == nil and msg:find(name, 1, true) == 1, msg)
+        assert(not msg:find("os error"), msg)
+        assert(errno == 2) -- ENOENT
+        ok, msg, errno = os.rename(name, name .. ".new")
+        assert(ok == nil and msg:find(name, 1, true) == 1, msg)
+        assert(errno == 2)
+        "#,
+    );
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_os_filename_bytes_are_the_path() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let dir = env::temp_dir();
+
+    // Filenames need not be UTF-8: the Lua string's bytes are the path
+    let result = vm.main_state().execute(&format!(
+        r#"
+        local dir = [==[{}]==]
+        local name = dir .. "/lua_\xff\xfe_" .. os.time() .. ".txt"
+        local f = assert(io.open(name, "w"))
+        f:write("bytes")
+        f:close()
+        for line in io.lines(name) do assert(line == "bytes") end
+        assert(os.rename(name, name .. ".new"))
+        assert(io.open(name, "r") == nil)
+        local ok, msg = os.remove(name)
+        assert(ok == nil and msg:find(name, 1, true) == 1)
+        assert(os.remove(name .. ".new"))
+        "#,
+        dir.display()
+    ));
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+}
+
+#[cfg(windows)]
+#[test]
+fn test_os_non_ascii_filename_windows() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let dir = env::temp_dir();
+
+    let result = vm.main_state().execute(&format!(
+        r#"
+        local dir = [==[{}]==]
+        local name = dir .. "\\lua_ñandú_数据_" .. os.time() .. ".txt"
+        local other = name .. ".new"
+        local f = assert(io.open(name, "w"))
+        f:write("ñ")
+        f:close()
+        f = assert(io.open(name, "r"))
+        assert(f:read("a") == "ñ")
+
+        -- an open file cannot be removed on Windows
+        local ok, msg, errno = os.remove(name)
+        assert(ok == nil and msg:find(name, 1, true) == 1, msg)
+        assert(errno == 13, errno) -- EACCES
+        f:close()
+
+        assert(os.rename(name, other))
+        assert(io.open(name, "r") == nil)
+        -- rename() does not replace an existing file
+        assert(io.open(name, "w")):close()
+        ok, msg, errno = os.rename(name, other)
+        assert(ok == nil and msg:find(name, 1, true) == 1, msg)
+        assert(errno == 17, errno) -- EEXIST
+        assert(os.remove(name))
+        assert(os.remove(other))
+
+        -- bytes that are not UTF-8 cannot name a Windows file
+        ok, msg, errno = io.open(dir .. "\\lua_\xff.txt", "w")
+        assert(ok == nil and msg:find("not valid UTF%-8"), msg)
+        assert(errno == 22) -- EINVAL
+        "#,
+        dir.display()
+    ));
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+}
+
 #[test]
 fn test_os_exit() {
     // Note: We don't actually test os.exit() as it would terminate the process
@@ -319,15 +420,77 @@ fn test_os_setlocale() {
 
     let result = vm.main_state().execute(
         r#"
-        -- Query current locale
-        local loc = os.setlocale(nil)
-        assert(loc == nil or type(loc) == "string")
-        
-        -- Try to set to C locale
-        local c_loc = os.setlocale("C")
-        assert(c_loc == nil or c_loc == "C")
+        -- Only the C locale exists: queries report it, asking for it (or
+        -- the native one) succeeds and anything else fails with nil
+        assert(os.setlocale() == "C")
+        assert(os.setlocale(nil, "numeric") == "C")
+        assert(os.setlocale("C") == "C")
+        assert(os.setlocale("POSIX", "all") == "C")
+        assert(os.setlocale("") == "C")
+        assert(os.setlocale("de_DE.UTF-8") == nil)
+        assert(os.setlocale("en_US", "time") == nil)
+
+        local ok, err = pcall(os.setlocale, "C", "bogus")
+        assert(not ok and err:find("bad argument #2 to 'os.setlocale' %(invalid option 'bogus'%)"), err)
+        "#,
+    );
+
+    assert!(result.is_ok(), "Error: {:?}", result);
+}
+
+#[test]
+fn test_os_getenv_invalid_names() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        assert(os.getenv("") == nil)
+        assert(os.getenv("PATH=x") == nil)
+        assert(os.getenv("=") == nil)
+        assert(os.getenv("PA\0TH") == nil)
+        assert(os.getenv("\255\254") == nil)
+        local ok, err = pcall(os.getenv)
+        assert(not ok and err:find("string expected, got no value"), err)
+        local ok, err = pcall(os.getenv, {})
+        assert(not ok and err:find("string expected, got table"), err)
         "#,
     );
 
     assert!(result.is_ok(), "Error: {:?}", result);
 }
+
+#[test]
+fn test_os_getenv_filter() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    // Cargo sets CARGO_MANIFEST_DIR for the test process
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let dir = vm.create_string(&dir).unwrap();
+    vm.set_global("dir", dir).unwrap();
+
+    vm.main_state()
+        .execute(r#"assert(os.getenv("CARGO_MANIFEST_DIR") == dir)"#)
+        .unwrap();
+
+    vm.set_env_filter(|name| !name.starts_with("CARGO_"));
+    vm.main_state()
+        .execute(
+            r#"
+            assert(os.getenv("CARGO_MANIFEST_DIR") == nil)
+            assert(os.getenv("NONEXISTENT_VAR_12345") == nil)
+            "#,
+        )
+        .unwrap();
+
+    // Names the filter never sees still read as unset
+    vm.set_env_filter(|_| true);
+    vm.main_state()
+        .execute(r#"assert(os.getenv("\255") == nil and os.getenv("A=B") == nil)"#)
+        .unwrap();
+
+    vm.clear_env_filter();
+    vm.main_state()
+        .execute(r#"assert(os.getenv("CARGO_MANIFEST_DIR") == dir)"#)
+        .unwrap();
+}
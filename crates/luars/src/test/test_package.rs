@@ -67,6 +67,26 @@ fn test_package_path() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_package_path_from_safe_option() {
+    let option = SafeOption::builder()
+        .package_path("vendor/?.lua")
+        .package_cpath("vendor/?.so")
+        .build()
+        .unwrap();
+    let mut vm = GlobalState::new(option);
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        assert(package.path == "vendor/?.lua")
+        assert(package.cpath == "vendor/?.so")
+    "#,
+    );
+
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+}
+
 #[test]
 fn test_package_cpath() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -273,3 +293,126 @@ fn test_install_library_supports_preload_modules() {
 
     assert!(result.is_ok(), "Error: {:?}", result.err());
 }
+
+#[test]
+fn test_reopen_stdlib_keeps_patched_entries() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    assert_eq!(vm.opened_stdlibs(), crate::stdlib::Stdlib::All.into());
+
+    vm.main_state()
+        .execute(
+            r#"
+        original_format = string.format
+        string_table = string
+        string.format = function() return "patched" end
+        stdout = io.stdout
+    "#,
+        )
+        .unwrap();
+
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    vm.open_stdlib_with(crate::stdlib::Stdlib::String, OpenMode::FillMissing)
+        .unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        assert(string == string_table)
+        assert(package.loaded.string == string_table)
+        assert(string.format() == "patched")
+        assert(("%d"):rep(2) == "%d%d")
+    "#,
+    );
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+
+    vm.open_stdlib_with(
+        crate::stdlib::Stdlib::String | crate::stdlib::Stdlib::Io,
+        OpenMode::Overwrite,
+    )
+    .unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        assert(string == string_table)
+        assert(string.format == original_format)
+        assert(("%d"):format(7) == "7")
+        assert(io.stdout == stdout)
+    "#,
+    );
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+}
+
+#[test]
+fn test_open_stdlib_ignores_foreign_library_tables() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::Basic | crate::stdlib::Stdlib::Package)
+        .unwrap();
+    vm.main_state()
+        .execute(
+            r#"
+        math = { answer = 42 }
+        math_table = math
+        package.loaded.string = { answer = 42 }
+        string_table = package.loaded.string
+    "#,
+        )
+        .unwrap();
+
+    vm.open_stdlib(crate::stdlib::Stdlib::Math | crate::stdlib::Stdlib::String)
+        .unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        assert(math ~= math_table and math.answer == nil)
+        assert(math.floor(1.5) == 1)
+        assert(require("math") == math)
+        assert(string ~= string_table and string.answer == nil)
+        assert(require("string") == string)
+        assert(("x"):rep(2) == "xx")
+    "#,
+    );
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+}
+
+#[test]
+fn test_stdlib_bookkeeping_not_reachable_by_name() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        for k, v in pairs(debug.getregistry()) do
+            if type(k) == "string" and type(v) == "table" and rawget(v, "math") == math then
+                assert(k == "_LOADED", "stdlib tables exposed under " .. k)
+            end
+        end
+    "#,
+    );
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+}
+
+#[test]
+fn test_reopen_basic_keeps_globals_and_initial_state() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    vm.main_state()
+        .execute(
+            r#"
+        print = function() return "patched" end
+        package.path = "custom/?.lua"
+        package.loaded.custom = true
+    "#,
+        )
+        .unwrap();
+
+    vm.open_stdlib_with(
+        crate::stdlib::Stdlib::Basic | crate::stdlib::Stdlib::Package,
+        OpenMode::Overwrite,
+    )
+    .unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        assert(print() == nil)
+        assert(package.loaded._G == _G)
+        assert(package.path == "custom/?.lua")
+        assert(package.loaded.custom == true)
+    "#,
+    );
+    assert!(result.is_ok(), "Error: {:?}", result.err());
+}
@@ -0,0 +1,162 @@
+// Tests for the profiler (`profiler` feature)
+use crate::*;
+
+const FIXTURE: &str = include_str!("test_data/profile_hot.lua");
+
+fn find(report: &[FunctionProfile], line_defined: usize) -> &FunctionProfile {
+    report
+        .iter()
+        .find(|p| p.line_defined == line_defined)
+        .unwrap_or_else(|| panic!("no function at line {line_defined} in {report:#?}"))
+}
+
+#[test]
+fn test_profile_report_finds_hot_function() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    vm.start_profiler();
+    assert!(vm.is_profiling());
+    let chunk = vm
+        .main_state()
+        .load_with_name(FIXTURE, "@profile_hot.lua")
+        .unwrap();
+    vm.main_state().call(chunk, vec![]).unwrap();
+    vm.stop_profiler();
+    assert!(!vm.is_profiling());
+
+    let report = vm.profile_report();
+    let hot = find(&report, 3);
+    let cold = find(&report, 11);
+    let driver = find(&report, 15);
+    let main = find(&report, 0);
+
+    assert_eq!(report[0], *hot, "{report:#?}");
+    assert_eq!(hot.source, "profile_hot.lua");
+    assert_eq!(
+        (hot.calls, cold.calls, driver.calls, main.calls),
+        (200, 200, 1, 1)
+    );
+    assert!(hot.exclusive_ns > 10 * cold.exclusive_ns, "{report:#?}");
+    assert!(hot.inclusive_ns >= hot.exclusive_ns);
+    assert!(driver.inclusive_ns >= hot.inclusive_ns + cold.inclusive_ns);
+    assert!(driver.exclusive_ns < driver.inclusive_ns);
+    assert!(main.inclusive_ns >= driver.inclusive_ns);
+
+    vm.reset_profile();
+    assert!(vm.profile_report().is_empty());
+}
+
+#[test]
+fn test_profile_counts_recursion_once_for_inclusive_time() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    vm.main_state()
+        .execute(
+            r#"
+        function fib(n)
+            if n < 2 then return n end
+            return fib(n - 1) + fib(n - 2)
+        end
+        function run() local n = fib(15) return n end
+    "#,
+        )
+        .unwrap();
+
+    vm.start_profiler();
+    vm.main_state().execute("run()").unwrap();
+    vm.stop_profiler();
+
+    let report = vm.profile_report();
+    let fib = find(&report, 2);
+    let run = find(&report, 6);
+    assert_eq!(fib.calls, 1973);
+    assert_eq!(run.calls, 1);
+    assert!(fib.inclusive_ns <= run.inclusive_ns, "{report:#?}");
+    assert!(fib.exclusive_ns <= fib.inclusive_ns);
+}
+
+#[test]
+fn test_profiler_is_off_until_started() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    vm.main_state()
+        .execute("local function f() end for _ = 1, 10 do f() end")
+        .unwrap();
+    assert!(!vm.is_profiling());
+    assert!(vm.profile_report().is_empty());
+}
+
+#[test]
+fn test_profiler_library() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local function step(n) return n + 1 end
+        local function busy() local s = 0 for i = 1, 5000 do s = s + i end return s end
+
+        assert(profiler.running() == false)
+        profiler.start()
+        assert(profiler.running())
+        -- A debug hook set meanwhile does not switch recording off
+        debug.sethook(function() end, "c")
+        debug.sethook()
+        local co = coroutine.wrap(function()
+            for i = 1, 3 do coroutine.yield(step(i)) end
+        end)
+        for _ = 1, 3 do co() end
+        for _ = 1, 20 do busy() end
+        profiler.stop()
+        assert(profiler.running() == false)
+
+        local report = profiler.report()
+        local by_line = {}
+        for i, entry in ipairs(report) do
+            by_line[entry.line_defined] = entry
+            if i > 1 then
+                assert(report[i - 1].exclusive_ns >= entry.exclusive_ns)
+            end
+        end
+        assert(by_line[2].calls == 3, "calls made in a coroutine are counted")
+        assert(by_line[3].calls == 20)
+        assert(type(by_line[3].source) == "string")
+        assert(by_line[3].inclusive_ns >= by_line[3].exclusive_ns)
+
+        profiler.reset()
+        assert(#profiler.report() == 0)
+        busy()
+        assert(#profiler.report() == 0, "nothing is recorded once stopped")
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_profiler_tells_apart_protos_reusing_an_address() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    vm.start_profiler();
+    vm.main_state()
+        .execute(
+            r#"
+        for i = 1, 50 do
+            load("return 1", "=chunk" .. i)()
+            collectgarbage()
+        end
+    "#,
+        )
+        .unwrap();
+    vm.stop_profiler();
+
+    let report = vm.profile_report();
+    for i in 1..=50 {
+        let source = format!("chunk{i}");
+        let entries: Vec<_> = report.iter().filter(|p| p.source == source).collect();
+        assert_eq!(entries.len(), 1, "{source}: {report:#?}");
+        assert_eq!(entries[0].calls, 1, "{source}: {report:#?}");
+    }
+}
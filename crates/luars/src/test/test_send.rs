@@ -111,3 +111,41 @@ fn lua_ref_can_be_shared() {
     h1.join().unwrap();
     h2.join().unwrap();
 }
+
+/// A userdata over an `Arc<Mutex<T>>` aliases the value and treats a lock
+/// held by the host as a conflict.
+#[test]
+fn shared_mutex_userdata_locks_per_access() {
+    use std::sync::{Arc, Mutex};
+
+    let mut vm = GlobalState::new(crate::SafeOption::default());
+    vm.open_stdlib(crate::Stdlib::All).unwrap();
+    let value = Arc::new(Mutex::new(crate::OpaqueUserData::new(0_i32)));
+    let state = vm.main_state();
+    let a = state.create_userdata_shared_mutex(value.clone()).unwrap();
+    state.set_global_value("a", a).unwrap();
+    let b = state.create_userdata_shared_mutex(value.clone()).unwrap();
+    state.set_global_value("b", b).unwrap();
+
+    let results = vm.main_state().execute("return a == b").unwrap();
+    assert_eq!(results[0].as_boolean(), Some(true));
+
+    let guard = value.lock().unwrap();
+    let results = vm
+        .main_state()
+        .execute("return pcall(function() return a.missing end)")
+        .unwrap();
+    assert_eq!(results[0].as_boolean(), Some(false));
+    assert!(
+        results[1]
+            .as_str()
+            .unwrap()
+            .contains("value is currently borrowed")
+    );
+    drop(guard);
+
+    vm.main_state()
+        .execute("a, b = nil, nil; collectgarbage()")
+        .unwrap();
+    assert_eq!(Arc::strong_count(&value), 1);
+}
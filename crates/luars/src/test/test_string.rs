@@ -1,4 +1,5 @@
 // Tests for string library functions
+use crate::lua_value::LuaStrRepr;
 use crate::*;
 
 #[test]
@@ -47,6 +48,61 @@ fn test_string_gsub_function() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_string_gsub_replacement_reenters_vm() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        -- rxi json.lua: escape_char and the string decoder both index tables
+        -- (one with a metatable) from inside gsub replacement functions
+        local escape_char_map = {
+            ["\\"] = "\\", ["\""] = "\"", ["\b"] = "b", ["\f"] = "f",
+            ["\n"] = "n", ["\r"] = "r", ["\t"] = "t",
+        }
+        local escape_char_map_inv = setmetatable({ ["/"] = "/" }, {
+            __index = function(t, k)
+                for raw, esc in pairs(escape_char_map) do
+                    if esc == k then rawset(t, k, raw) return raw end
+                end
+            end,
+        })
+        local function escape_char(c)
+            return "\\" .. (escape_char_map[c] or string.format("u%04x", c:byte()))
+        end
+        local function encode_string(val)
+            return '"' .. val:gsub('[%c"\\]', escape_char) .. '"'
+        end
+        local function decode_string(str)
+            local s = str:sub(2, -2):gsub("\\u(%x%x%x%x)", function(hex)
+                return utf8.char(tonumber(hex, 16))
+            end)
+            return (s:gsub("\\(.)", escape_char_map_inv))
+        end
+        local text = 'say "hi"\n\tand \1 / bye\\'
+        local encoded = encode_string(text)
+        assert(encoded == [["say \"hi\"\n\tand \u0001 / bye\\"]], encoded)
+        assert(decode_string(encoded) == text)
+
+        -- A replacement that runs gsub on the very same subject string
+        local subject = "a1b2c3"
+        local out, n = subject:gsub("%a", function(c)
+            local digits = subject:gsub("%d", function(d) return d + 1 end)
+            return c:upper() .. #digits
+        end)
+        assert(out == "A61B62C63" and n == 3, out)
+
+        -- Errors from the replacement propagate unchanged
+        local err = {}
+        local ok, e = pcall(string.gsub, "x", "x", function() error(err) end)
+        assert(not ok and e == err)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_string_len() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -94,6 +150,41 @@ fn test_string_upper_lower() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_string_case_is_bytewise() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        -- only ASCII letters change; UTF-8 and invalid bytes pass through
+        assert(string.upper("caf\u{e9}") == "CAF\u{e9}")
+        assert(string.lower("\xC9T\xC9") == "\xC9t\xC9")
+        assert(string.upper("a\0b\255") == "A\0B\255")
+        assert(string.len("caf\u{e9}") == #"caf\u{e9}" and #"caf\u{e9}" == 5)
+        local long = string.rep("x\xff", 200)
+        assert(long:upper() == string.rep("X\xff", 200))
+
+        math.randomseed(653)
+        for _ = 1, 500 do
+            local bytes = {}
+            for i = 1, math.random(0, 40) do bytes[i] = math.random(0, 255) end
+            local s = string.char(table.unpack(bytes))
+            local up, low = s:upper(), s:lower()
+            assert(#up == #s and #low == #s and string.len(up) == #s)
+            assert(up:lower() == low and low:upper() == up)
+            for i = 1, #s do
+                local b = bytes[i]
+                local isletter = (b >= 65 and b <= 90) or (b >= 97 and b <= 122)
+                assert(isletter or (up:byte(i) == b and low:byte(i) == b))
+            end
+        end
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_string_rep() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -103,10 +194,48 @@ fn test_string_rep() {
         r#"
         assert(string.rep("ab", 3) == "ababab")
         assert(string.rep("x", 0) == "")
+        assert(string.rep("x", -5, "-") == "")
+
+        -- separator goes between copies only
+        assert(string.rep("ab", 3, "-") == "ab-ab-ab")
+        assert(string.rep("ab", 1, "-") == "ab")
+        assert(string.rep("", 3, ",") == ",,")
+        assert(string.rep("x", 3, "") == "xxx")
+        assert(string.rep("", 2^40) == "")
+
+        -- the size is checked before allocating
+        local ok, err = pcall(string.rep, "x", 2^40)
+        assert(not ok and err:find("resulting string too large"))
+        ok, err = pcall(string.rep, "ab", math.maxinteger, "-")
+        assert(not ok and err:find("resulting string too large"))
     "#,
     );
 
-    assert!(result.is_ok());
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_string_rep_respects_memory_limit() {
+    let option = SafeOption {
+        max_memory_limit: 4 * 1024 * 1024,
+        ..Default::default()
+    };
+    let mut vm = GlobalState::new(option);
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // 64 MB is well under the string size cap but over the memory limit,
+    // so it must fail up front instead of being built
+    let result = vm.main_state().execute(
+        r#"
+        local ok, err = pcall(string.rep, "x", 64 * 1024 * 1024)
+        assert(not ok and err:find("memory"), err)
+        ok, err = pcall(string.rep, "x", 32 * 1024 * 1024, "y")
+        assert(not ok and err:find("memory"), err)
+        assert(#string.rep("x", 1024 * 1024, "") == 1024 * 1024)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
 }
 
 #[test]
@@ -139,6 +268,32 @@ fn test_string_byte_char() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_string_byte_clamps_range() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        assert(select('#', string.byte("abc", 0)) == 0)
+        assert(select('#', string.byte("abc", 4)) == 0)
+        assert(select('#', string.byte("abc", 3, 2)) == 0)
+        assert(select('#', string.byte("", 1, -1)) == 0)
+        assert(string.byte("abc", -1) == 99)
+        local a, b, c = string.byte("abc", -10, 10)
+        assert(a == 97 and b == 98 and c == 99)
+        a, b, c = string.byte("abc", math.mininteger, math.maxinteger)
+        assert(a == 97 and b == 98 and c == 99)
+        assert(string.byte("abc", "2") == 98 and string.byte("abc", 2.0) == 98)
+        assert(string.byte("abc", nil, 2) == 97)
+        local ok, err = pcall(string.byte, "abc", 1.5)
+        assert(not ok and err:find("number has no integer representation"))
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_string_format() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -161,6 +316,136 @@ fn test_string_format() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_string_format_conversions() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // Specifier table from the PUC test suite (strings.lua)
+    let result = vm.main_state().execute(
+        r#"
+        local function check(fmt, msg, ...)
+            local ok, err = pcall(string.format, fmt, ...)
+            assert(not ok and string.find(err, msg, 1, true), err)
+        end
+
+        -- integer conversions take integral floats and numeric strings
+        assert(string.format("%d", 3.0) == "3")
+        assert(string.format("%i", -2^53) == "-9007199254740992")
+        assert(string.format("%d", "10") == "10")
+        assert(string.format("%x", "0x10") == "10")
+        assert(string.format("%x", 0.0) == "0")
+        assert(string.format("%02x", 0.0) == "00")
+        assert(string.format("%o", 8.0) == "10")
+        assert(string.format("%X", 255.0) == "FF")
+        assert(string.format("%08X", 0xFFFFFFFF) == "FFFFFFFF")
+        assert(string.format("%+08d", 31501) == "+0031501")
+        assert(string.format("%+08d", -30927) == "-0030927")
+        assert(string.format("%x", math.maxinteger) == "7fffffffffffffff")
+        assert(string.format("%x", math.mininteger) == "8000000000000000")
+        assert(string.format("%d", math.mininteger) == "-9223372036854775808")
+        assert(string.format("%u", 0xffffffff) == "4294967295")
+        assert(string.format("%u", -1) == "18446744073709551615")
+        assert(string.format("%o", 0xABCD) == "125715")
+        assert(string.format("%#12o", 10) == "         012")
+        assert(string.format("%#10x", 100) == "      0x64")
+        assert(string.format("%#-17X", 100) == "0X64             ")
+        assert(string.format("%013i", -100) == "-000000000100")
+        assert(string.format("%2.5d", -100) == "-00100")
+        assert(string.format("%.u", 0) == "")
+        assert(string.format("%+#014.0f", 100) == "+000000000100.")
+        assert(string.format("%-16c", 97) == "a               ")
+        assert(string.format("%1c%-c%-1c%c", 34, 48, 90, 100) == '"0Zd')
+        assert(string.format("%+.3G", 1.5) == "+1.5")
+        assert(string.format("%.0s", "alo") == "")
+        assert(string.format("%.s", "alo") == "")
+        assert(string.format("%%%d %010d", 10, 23) == "%10 0000000023")
+        assert(string.format("%s %.4s", false, true) == "false true")
+        local t = setmetatable({}, { __tostring = function() return "hello" end })
+        assert(string.format("%s %.10s", t, t) == "hello hello")
+        assert(#string.format("%99d", 1) == 99)
+        assert(#string.format("%.99f", 1) == 101)
+
+        check("%d", "bad argument #2 to 'string.format' (number has no integer representation)", 3.5)
+        check("%x", "bad argument #2 to 'string.format' (number has no integer representation)", "3.5")
+        check("%d", "bad argument #2 to 'string.format' (number expected, got table)", {})
+        check("%a %d", "bad argument #3 to 'string.format' (number has no integer representation)", 1, 2.5)
+        check("%c", "bad argument #2 to 'string.format' (value out of range)", 256)
+        check("%c", "bad argument #2 to 'string.format' (value out of range)", -1)
+        check("%d %d", "bad argument #3 to 'string.format' (no value)", 10)
+
+        local aux = string.rep("0", 600)
+        check("%100.3d", "invalid conversion", 10)
+        check("%1" .. aux .. ".3d", "too long", 10)
+        check("%1.100d", "invalid conversion", 10)
+        check("%10.1" .. aux .. "004d", "too long", 10)
+        check("%" .. aux .. "d", "too long", 10)
+        check("%-----------------------d", "invalid conversion", 10)
+        check("%t", "invalid conversion", 10)
+        check("%010c", "invalid conversion", 10)
+        check("%.10c", "invalid conversion", 10)
+        check("%0.34s", "invalid conversion", 10)
+        check("%#i", "invalid conversion", 10)
+        check("%3.1p", "invalid conversion", 10)
+        check("%0.s", "invalid conversion", 10)
+        check("%10q", "cannot have modifiers", 10)
+        check("%F", "invalid conversion", 10)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_string_char_byte_ranges() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // 100k bytes of pseudo-random binary data, zeros included
+    let mut seed: u32 = 0x2545_f491;
+    let data: Vec<u8> = (0..100_000)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as u8
+        })
+        .collect();
+    let state = vm.main_state();
+    let blob = state.create_bytes(&data).unwrap();
+    state.set_global_value("blob", blob).unwrap();
+
+    let result = state.execute(
+        r##"
+        local function check(msg, ...)
+            local ok, err = pcall(string.char, ...)
+            assert(not ok and string.find(err, msg, 1, true), err)
+        end
+
+        assert(string.char() == "")
+        assert(string.char(0, 255) == "\0\255")
+        assert(#string.char(0, 0, 0) == 3)
+        assert(string.char(65.0, 66) == "AB")
+        assert(string.char("67") == "C")
+        check("bad argument #1 to 'string.char' (value out of range)", 256)
+        check("bad argument #1 to 'string.char' (value out of range)", -1)
+        check("bad argument #3 to 'string.char' (value out of range)", 1, 2, 256)
+        check("bad argument #2 to 'string.char' (number has no integer representation)", 1, 65.5)
+        check("bad argument #1 to 'string.char' (number expected, got table)", {})
+        local ok, err = pcall(load("return string.char(300)"))
+        assert(not ok and string.find(err, "bad argument #1 to 'char' (value out of range)", 1, true), err)
+
+        assert(#blob == 100000 and string.find(blob, "\0", 1, true))
+        assert(string.char(string.byte(blob, 1, -1)) == blob)
+        local t = { string.byte(blob, 1, #blob) }
+        assert(#t == 100000 and t[1] == string.byte(blob) and t[100000] == string.byte(blob, -1))
+        assert(string.char(table.unpack(t)) == blob)
+        assert(select("#", string.byte(blob, 10, 9)) == 0)
+        assert(select("#", string.byte(blob, -5, 100000000)) == 5)
+    "##,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_string_find() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -296,3 +581,198 @@ fn test_string_pack_short_byte_string_equality() {
     }
     assert!(result.is_ok());
 }
+
+#[test]
+fn test_string_library_argument_coercion() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        -- numbers where strings are expected, in canonical tostring form
+        assert(string.len(123) == 3)
+        assert(string.len(1.5) == 3)
+        assert(string.upper(42) == "42")
+        assert(string.lower(2^63) == tostring(2^63):lower())
+        assert(string.upper(-0.0) == "-0.0")
+        assert(string.reverse(123) == "321")
+        assert(string.rep(7, 2) == "77")
+        assert(string.rep("x", 3, 0) == "x0x0x")
+        assert(string.sub(12345, 2, 3) == "23")
+        assert(string.byte(65) == 54)
+        assert(string.find(12345, 34) == 3)
+        assert(string.match(12.5, "%d+") == "12")
+        assert(string.gmatch(123, "%d")() == "1")
+        assert(string.gsub(12345, 3, 9) == "12945")
+        assert(string.gsub("abc", "b", 1.5) == "a1.5c")
+        assert(string.format(12) == "12")
+        assert(string.unpack("b", 65) == 54)
+        assert(table.concat({1, 2.0, "x"}, 0) == "102.00x")
+
+        -- numeric strings and integral floats where integers are expected
+        assert(string.rep("x", "3") == "xxx")
+        assert(("x"):rep(2.0) == "xx")
+        assert(string.sub("hello", "2", 3.0) == "el")
+        assert(string.sub("hello", 2, nil) == "ello")
+        assert(string.byte("abc", "2") == 98)
+        assert(string.char("65", 66.0) == "AB")
+        assert(string.find("abcabc", "b", "3") == 5)
+        assert(string.gsub("aaa", "a", "b", "2") == "bba")
+        assert(string.format("%d %x %5.1f", "10", 255.0, "2.25") == "10 ff   2.2")
+        assert(string.pack("i4", "7") == string.pack("i4", 7))
+        assert(string.unpack("b", "xA", "2") == 65)
+        assert(table.concat({"a", "b", "c"}, "", "2", 3.0) == "bc")
+
+        -- genuinely wrong types keep luaL_typeerror's message shape
+        local function check(expected, f, ...)
+            local ok, msg = pcall(f, ...)
+            assert(not ok and string.find(msg, expected, 1, true), msg)
+        end
+        check("bad argument #1 to 'string.len' (string expected, got boolean)", string.len, true)
+        check("(string expected, got no value)", string.upper)
+        check("bad argument #2 to 'string.find' (string expected, got table)", string.find, "x", {})
+        check("bad argument #3 to 'string.gsub' (string/function/table expected, got boolean)",
+              string.gsub, "x", "x", true)
+        check("bad argument #2 to 'string.rep' (number expected, got string)", string.rep, "x", "a")
+        check("bad argument #2 to 'string.rep' (number has no integer representation)",
+              string.rep, "x", 1.5)
+        check("bad argument #2 to 'string.sub' (number expected, got table)", string.sub, "x", {})
+        check("(number has no integer representation)", string.sub, "x", "1.5")
+        check("bad argument #2 to 'string.format' (number expected, got boolean)",
+              string.format, "%d", true)
+        check("bad argument #2 to 'string.pack' (number expected, got string)",
+              string.pack, "i4", "x")
+        check("bad argument #2 to 'table.concat' (string expected, got boolean)",
+              table.concat, {}, true)
+        check("bad argument #1 to 'io.write' (string expected, got boolean)", io.write, true)
+        check("bad argument #1 to 'write' (string expected, got table)",
+              function() return io.stdout:write({}) end)
+        "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_string_sub_shares_long_strings() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let results = vm
+        .main_state()
+        .execute(
+            r#"
+        local function cut()
+            local parent = string.rep("0123456789", 100) .. "héllo wörld"
+            return parent:sub(101, 400), parent:sub(-300), parent:sub(-300, -12)
+        end
+        local slice, accented, split = cut()
+        collectgarbage()
+        collectgarbage()
+
+        -- The slices outlive the string they were cut from
+        assert(#slice == 300 and slice == string.rep("0123456789", 30))
+        assert(accented:sub(-13) == "héllo wörld")
+        local t = { [string.rep("0123456789", 30)] = "found" }
+        assert(t[slice] == "found")
+        assert(slice:sub(1, 100) == string.rep("0123456789", 10))
+        collectgarbage()
+        return slice, accented, split, ("x"):rep(100):sub(2, 30),
+            string.rep("ab", 5000):sub(1, 100)
+        "#,
+        )
+        .unwrap();
+    let state = vm.main_state();
+
+    // The three slices are all that is left of the collected parent
+    let slice = results[0].as_string_ptr().unwrap();
+    let shared = &slice.as_ref().data.str;
+    let LuaStrRepr::Shared { buf, start, len } = shared else {
+        panic!("long substring was copied: {shared:?}");
+    };
+    assert_eq!((*start, *len, buf.ref_count()), (100, 300, 3));
+    assert_eq!(buf.len(), 1_000 + "héllo wörld".len());
+
+    // UTF-8 validity follows the slice boundaries
+    assert!(results[1].as_str().unwrap().ends_with("héllo wörld"));
+    assert_eq!(results[2].as_str(), None);
+    assert_eq!(state.get_string_bytes(&results[2]).unwrap().len(), 289);
+
+    // A long slice of a small part of its parent is copied, so it does not
+    // keep the whole parent buffer alive
+    let small = results[4].as_string_ptr().unwrap();
+    let small = &small.as_ref().data.str;
+    let LuaStrRepr::Shared { buf, start, len } = small else {
+        panic!("long string without a shareable buffer: {small:?}");
+    };
+    assert_eq!((*start, *len, buf.len(), buf.ref_count()), (0, 100, 100, 1));
+    assert_eq!(small.as_bytes(), "ab".repeat(50).as_bytes());
+
+    // Short results are interned as before
+    assert!(results[3].is_short_string());
+    assert_eq!(state.get_string_bytes(&results[3]), Some(&[b'x'; 29][..]));
+    assert_eq!(state.get_string_bytes(&LuaValue::integer(1)), None);
+}
+
+#[test]
+fn test_malformed_patterns() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local cases = {
+            { "[a", "malformed pattern (missing ']')" },
+            { "[]", "malformed pattern (missing ']')" },
+            { "[^]", "malformed pattern (missing ']')" },
+            { "[a%]", "malformed pattern (missing ']')" },
+            { "[a%", "malformed pattern (missing ']')" },
+            { "x%f[a", "malformed pattern (missing ']')" },
+            { "%", "malformed pattern (ends with '%')" },
+            { "a%", "malformed pattern (ends with '%')" },
+            { "%b", "malformed pattern (missing arguments to '%b')" },
+            { "%ba", "malformed pattern (missing arguments to '%b')" },
+            { "%f", "missing '[' after '%f' in pattern" },
+            { "%fa", "missing '[' after '%f' in pattern" },
+            { "(.", "unfinished capture" },
+            { ".)", "invalid pattern capture" },
+            { "(a)%2", "invalid capture index %2" },
+            { string.rep("()", 33), "too many captures" },
+        }
+        local function check(f, ...)
+            local ok, msg = pcall(f, ...)
+            return not ok and msg
+        end
+        for _, case in ipairs(cases) do
+            local p, expected = case[1], case[2]
+            for name, f in pairs({
+                find = function() return string.find("a", p) end,
+                match = function() return string.match("a", p) end,
+                gmatch = function() return string.gmatch("a", p)() end,
+                gsub = function() return string.gsub("a", p, "x") end,
+            }) do
+                local msg = check(f)
+                assert(msg and msg:find(expected, 1, true),
+                       name .. "(" .. p .. "): " .. tostring(msg))
+                -- The message is PUC's, with nothing in front but the position
+                assert(msg:sub(-#expected) == expected, msg)
+            end
+        end
+
+        -- gmatch has no anchor: a leading '^' matches itself, as in PUC 5.5
+        local found = {}
+        for m in string.gmatch("^a^a a", "^a") do found[#found + 1] = m end
+        assert(#found == 2 and found[1] == "^a" and found[2] == "^a", #found)
+        assert(string.gmatch("abc", "^a")() == nil)
+        assert(string.gmatch("x^yz", "^(.)")() == "y")
+        -- find, match and gsub keep anchoring
+        assert(string.find("abc", "^b") == nil)
+        local replaced, n = string.gsub("aaa", "^a", "b")
+        assert(replaced == "baa" and n == 1, replaced)
+        -- a '^' past the start is a plain character
+        assert(string.gmatch("a^b", "%^b")() == "^b")
+        "#,
+    );
+    if let Err(e) = result {
+        panic!("{}", vm.main_state().get_error_message(e));
+    }
+}
@@ -63,6 +63,57 @@ fn test_swap_variables() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_multiple_assignment_order() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local a = {1, 2, 3}
+        local i, j = 2, 2
+        a[i], a[j] = a[j], a[i]
+        assert(a[1] == 1 and a[2] == 2 and a[3] == 3)
+        i, j = 1, 3
+        a[i], a[j] = a[j], a[i]
+        assert(a[1] == 3 and a[2] == 2 and a[3] == 1)
+
+        -- stores happen right to left, once per target
+        local log = {}
+        local p = setmetatable({}, {__newindex = function(t, k, v)
+            log[#log + 1] = k .. "=" .. v
+            rawset(t, k, v)
+        end})
+        p.x, p.y = 1, 2
+        assert(table.concat(log, " ") == "y=2 x=1")
+
+        -- nothing is stored if the right-hand side raises
+        local x, y = 1, 2
+        assert(not pcall(function() x, y = 5, error("boom") end))
+        assert(x == 1 and y == 2)
+
+        -- table and key operands are evaluated left to right before the values
+        local order = {}
+        local function g(s) order[#order + 1] = s; return s end
+        local q = {}
+        q[g("k1")], q[g("k2")] = g("v1"), g("v2")
+        assert(table.concat(order, " ") == "k1 k2 v1 v2")
+        assert(q.k1 == "v1" and q.k2 == "v2")
+
+        -- a key uses the variable's value from before the assignment
+        local idx, arr = 1, {}
+        idx, arr[idx] = idx + 1, 20
+        assert(idx == 2 and arr[1] == 20 and arr[2] == nil)
+
+        -- extra values are discarded, missing ones become nil
+        local m, n = 1, 2, 3
+        assert(m == 1 and n == 2)
+        local u, v = (function() return 7 end)()
+        assert(u == 7 and v == nil)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 // === Vararg Tests ===
 
 #[test]
@@ -664,3 +715,408 @@ fn test_relational_comparison() {
     );
     assert!(result.is_ok());
 }
+
+// === Register Limit Tests ===
+
+#[test]
+fn test_register_limit_call_arguments() {
+    // Registers: x = R0, sentinel = R1, f = R2, callee = R3, arguments from R4.
+    // 251 arguments fill the frame up to R254, the last addressable register.
+    fn source(nargs: usize) -> String {
+        let args = vec!["x"; nargs].join(", ");
+        format!(
+            "local x = 1\n\
+             local sentinel = 42\n\
+             local function f(...) return select('#', ...) end\n\
+             return f({args}), sentinel"
+        )
+    }
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let state = vm.main_state();
+
+    for nargs in [1, 200, 250, 251] {
+        let results = state.execute(&source(nargs)).unwrap();
+        assert_eq!(results[0].as_integer(), Some(nargs as i64));
+        assert_eq!(results[1].as_integer(), Some(42));
+    }
+
+    for nargs in [252, 300] {
+        let err = state.execute(&source(nargs)).unwrap_err();
+        let msg = state.get_error_msg(err);
+        assert!(
+            msg.contains("function or expression needs too many registers"),
+            "unexpected error: {msg}"
+        );
+    }
+}
+
+const GLOBALS_FIXTURE: &str = r#"
+    global print, counter
+    global <const> LIMIT = 3
+    counter = 0
+    for i = 1, LIMIT do counter = counter + i end
+    return counter
+"#;
+
+fn globals_vm(strict_globals: bool) -> std::pin::Pin<Box<GlobalState>> {
+    let option = SafeOption {
+        strict_globals,
+        ..Default::default()
+    };
+    let mut vm = GlobalState::new(option);
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    vm
+}
+
+#[test]
+fn test_global_declarations_in_both_modes() {
+    for strict in [false, true] {
+        let mut vm = globals_vm(strict);
+        let state = vm.main_state();
+        let results = state.execute(GLOBALS_FIXTURE).unwrap();
+        assert_eq!(results[0].as_integer(), Some(6), "strict = {strict}");
+
+        // A chunk without declarations is global-by-default only when not strict
+        let result = state.execute("undeclared = 1; return undeclared");
+        if strict {
+            let msg = state.get_error_msg(result.unwrap_err());
+            assert!(msg.contains("variable 'undeclared' not declared"), "{msg}");
+        } else {
+            assert!(result.is_ok());
+        }
+
+        // `global *` restores global-by-default inside its scope
+        assert!(state.execute("global *; y = 2; return y").is_ok());
+
+        // Assigning a const global is rejected in both modes
+        let err = state.execute("global <const> K = 1; K = 2").unwrap_err();
+        assert!(
+            state
+                .get_error_msg(err)
+                .contains("attempt to assign to const variable 'K'")
+        );
+    }
+}
+
+#[test]
+fn test_strict_globals_with_load_env() {
+    let mut vm = globals_vm(true);
+    let result = vm.main_state().execute(
+        r#"
+        global load, assert, type, string, _G
+
+        -- declared names resolve through the env given to load
+        local env = {}
+        local f = assert(load("global x; x = 10; return x", "c", "t", env))
+        assert(f() == 10 and env.x == 10 and _G.x == nil)
+
+        -- the check is done at compile time, whatever the env
+        local g, msg = load("return y", "c", "t", {y = 1})
+        assert(g == nil and string.find(msg, "variable 'y' not declared", 1, true), msg)
+
+        -- undeclared names inside nested functions are rejected too
+        g, msg = load("global print; return function() return z end", "c", "t", {})
+        assert(g == nil and type(msg) == "string")
+        "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_const_locals() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local function rejects(code, name)
+            local f, msg = load(code)
+            assert(f == nil, code)
+            assert(msg:find("attempt to assign to const variable '" .. name .. "'", 1, true), msg)
+        end
+
+        rejects("local pi <const> = 3.14159; pi = 1", "pi")
+        rejects("local pi <const> = 3.14159; local x; pi, x = 1, 2", "pi")
+        rejects("local pi <const> = 3.14159; return function() pi = 1 end", "pi")
+        rejects("for i = 1, 10 do i = 2 end", "i")
+        rejects("for k, v in pairs({}) do k = 1 end", "k")
+        rejects("local t <close> = nil; t = 1", "t")
+
+        -- a loop variable may shadow a const local
+        local pi <const> = 3.14159
+        local sum = 0
+        for pi = 1, 10 do sum = sum + pi end
+        assert(sum == 55 and pi == 3.14159)
+
+        -- captured consts behave as values or upvalues
+        local t <const> = {}
+        local get_pi = function() return pi end
+        local get_t = function() return t end
+        assert(get_pi() == 3.14159 and get_t() == t)
+
+        -- compile-time constants are folded
+        local n <const> = 2
+        assert(pi * n == 6.28318)
+        "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_numeral_literals() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local x = 0x1p-2
+        assert(x == 0.25 and math.type(x) == "float")
+        assert(0x.8 == 0.5 and 0xA.8p1 == 21.0 and 0X1P+4 == 16.0)
+        assert(0xep1 == 28.0)
+        assert(0x10 == 16 and math.type(0x10) == "integer")
+        assert(0xffffffffffffffff == -1)
+        assert(3. == 3.0 and .5 == 0.5 and 1e2 == 100.0 and 1E-1 == 0.1)
+        assert(math.type(9223372036854775807) == "integer")
+        assert(math.type(9223372036854775808) == "float")
+        assert(1 .. 2 == "12")
+
+        local function malformed(src, near)
+            local f, err = load("return " .. src)
+            assert(not f, src)
+            assert(err:find("malformed number near '" .. near .. "'", 1, true), err)
+        end
+        malformed("0x1_0", "0x1_")
+        malformed("1e", "1e")
+        malformed("1e+", "1e+")
+        malformed("0x1p", "0x1p")
+        malformed("3.4.5", "3.4.5")
+        malformed("1..2", "1..2")
+        malformed("3x", "3x")
+        malformed("0xep-p", "0xep-p")
+        malformed("08abc", "08abc")
+        assert(select(2, load("return 0xe-")):find("near <eof>"))
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_string_literal_escapes() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        assert("\x41\x62\x7a" == "Abz" and "\65\066\0677" == "ABC7")
+        assert("\0001" == "\0" .. "1" and #"\255\0" == 2)
+        assert("\u{41}\u{0000000000041}" == "AA")
+        assert("\u{1F600}" == "\xF0\x9F\x98\x80")
+        assert("\u{7FF}\u{FFFF}" == "\xDF\xBF\xEF\xBF\xBF")
+        assert("\u{110000}" == "\xF4\x90\x80\x80")
+        assert("\u{7FFFFFFF}" == "\xFD\xBF\xBF\xBF\xBF\xBF")
+        assert("abc\z
+                def\z   " == "abcdef")
+        assert(load("return 'x\\z\u{A0}y'")() == "x\u{A0}y")
+        assert("a\
+b" == "a\nb")
+        assert(load("return 'a\\\r\nb'")() == "a\nb")
+
+        assert([[
+x]] == "x" and [==[
+]]]=]]==] == "]]]=]")
+        assert(load("return [[\r\nx]]")() == "x")
+        assert(load("return [[\n\n\rx]]")() == "\nx")
+        assert([=[a[[b]]c]=] == "a[[b]]c")
+
+        local function lexerror(src, msg, line)
+            local f, err = load(src)
+            assert(not f, src)
+            assert(err:find(msg, 1, true), err)
+            if line then assert(err:find(":" .. line .. ":", 1, true), err) end
+        end
+        lexerror([[return "\u{80000000}"]], "UTF-8 value too large near '\\u{80000000'")
+        lexerror([[return "\u{100000000}"]], "UTF-8 value too large")
+        lexerror([[return "\u{}"]], "hexadecimal digit expected in unicode escape")
+        lexerror([[return "\u{12"]], "hexadecimal digit expected in unicode escape near '\\u{12\"'")
+        lexerror([[return "\u{12]], "unfinished unicode escape")
+        lexerror([[return "\u12"]], "missing '{' in unicode escape")
+        lexerror([[return "\x4g"]], "hexadecimal digit expected near '\\x4g'")
+        lexerror([[return "\256"]], "decimal escape too large near '\\256")
+        lexerror([[return "abc\]], "unfinished string near <eof>")
+        lexerror([[return "abc\z]], "unfinished string near <eof>")
+        lexerror("x = 1\nreturn 'a\\qb'", "invalid escape sequence near '\\q'", 2)
+        lexerror("return 'abc\nd'", "unfinished string near ''abc'")
+        lexerror("return [=x]=]", "invalid long string delimiter near '[='")
+        lexerror("return [==[abc]=]", "unfinished long string or comment near <eof>")
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_labels_and_lines_in_constant_false_branches() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        if false then
+            goto skip
+            error("unreachable")
+            ::skip::
+        elseif nil then
+            ::again:: goto again
+        end
+        while false do ::top:: goto top end
+        local n = 0
+        repeat
+            if false then goto done end
+            n = n + 1
+            ::done::
+        until n == 3
+        assert(n == 3)
+        "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+
+    // Statements under a constant false condition still get code and lines
+    let chunk = vm.compile("if false then\n  x = 1\nend\n").unwrap();
+    assert!(chunk.line_info.contains(&2), "{:?}", chunk.line_info);
+}
+
+#[test]
+fn test_compile_options_disable_folding() {
+    const SOURCE: &str = r#"return 2^10, -1, ~0, 7 // 2, "a" .. "b" .. "c""#;
+    const FOLDABLE: [&[OpCode]; 5] = [
+        &[OpCode::Pow, OpCode::PowK],
+        &[OpCode::Unm],
+        &[OpCode::BNot],
+        &[OpCode::IDiv, OpCode::IDivK],
+        &[OpCode::Concat],
+    ];
+    let has_op = |chunk: &LuaProto, ops: &[OpCode]| {
+        chunk
+            .code
+            .iter()
+            .any(|&i| ops.contains(&Instruction::get_opcode(i)))
+    };
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    let folded = vm.compile(SOURCE).unwrap();
+    for ops in FOLDABLE {
+        assert!(!has_op(&folded, ops), "{:?} was not folded", ops);
+    }
+
+    vm.set_compile_options(CompileOptions::unoptimized());
+    let unfolded = vm.compile(SOURCE).unwrap();
+    for ops in FOLDABLE {
+        assert!(has_op(&unfolded, ops), "{:?} was folded", ops);
+    }
+
+    // Same results either way, also for chunks loaded from Lua
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let results = vm.main_state().execute(SOURCE).unwrap();
+    assert_eq!(results[0], LuaValue::float(1024.0));
+    assert_eq!(results[1], LuaValue::integer(-1));
+    assert_eq!(results[2], LuaValue::integer(-1));
+    assert_eq!(results[3], LuaValue::integer(3));
+    assert_eq!(results[4].as_str(), Some("abc"));
+    let result = vm.main_state().execute(
+        r#"
+        local f = load("return -(2^63) // 1, 'x' .. 1 .. 'y'")
+        local a, b = f()
+        assert(a == math.mininteger * 1.0 and b == "x1y")
+        "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_constants_are_shared_by_value() {
+    // Each distinct constant is stored once per function, however it was
+    // spelled; integers and floats of equal value stay apart
+    const SOURCE: &str = r#"
+        local t = { name = "x", other = 'x', [[x]], "\120", "x" }
+        local a, b, c, d = 1000000, 1000000.0, 1000000, 1e6
+        return t.name .. t.other, math.type(a), math.type(b), c, d
+    "#;
+    let mut vm = GlobalState::new(SafeOption::default());
+    let proto = vm.compile(SOURCE).unwrap();
+    let count = |value: LuaValue| {
+        proto
+            .constants
+            .iter()
+            .filter(|k| k.kind() == value.kind() && **k == value)
+            .count()
+    };
+    let x = vm.create_string("x").unwrap();
+    assert_eq!(count(x), 1);
+    assert_eq!(count(LuaValue::integer(1000000)), 1);
+    assert_eq!(count(LuaValue::float(1000000.0)), 1);
+
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let results = vm.main_state().execute(SOURCE).unwrap();
+    assert_eq!(results[0].as_str(), Some("xx"));
+    assert_eq!(results[1].as_str(), Some("integer"));
+    assert_eq!(results[2].as_str(), Some("float"));
+    assert_eq!(results[3], LuaValue::integer(1000000));
+    assert!(results[4].is_float());
+}
+
+#[test]
+fn test_register_limit_right_nested_expressions() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+    let state = vm.main_state();
+
+    // `g + (g + (...))` holds one temporary per level; 300 levels stop at
+    // the parser's nesting limit long before the frame runs out.
+    let nested_sum = |depth: usize| {
+        let mut expr = String::from("g");
+        for _ in 0..depth {
+            expr = format!("g + ({expr})");
+        }
+        format!("g = 1\nlocal sentinel = 42\nreturn {expr}, sentinel")
+    };
+    for depth in [1, 50, 90] {
+        let results = state.execute(&nested_sum(depth)).unwrap();
+        assert_eq!(results[0].as_integer(), Some(depth as i64 + 1));
+        assert_eq!(results[1].as_integer(), Some(42));
+    }
+    let err = state.execute(&nested_sum(300)).unwrap_err();
+    let msg = state.get_error_msg(err);
+    assert!(msg.contains("too many syntax levels"), "{msg}");
+
+    // Each nested call keeps the callee and seven arguments live, eight
+    // registers per level: 31 levels fit in the frame, 32 do not.
+    let nested_calls = |depth: usize| {
+        let mut expr = String::from("g");
+        for _ in 0..depth {
+            expr = format!("f(g, g, g, g, g, g, g, {expr})");
+        }
+        format!(
+            "g = 1\n\
+             local sentinel = 42\n\
+             local function f(...)\n\
+               local s = 0\n\
+               for i = 1, select('#', ...) do s = s + select(i, ...) end\n\
+               return s\n\
+             end\n\
+             return {expr}, sentinel"
+        )
+    };
+    for depth in [1, 31] {
+        let results = state.execute(&nested_calls(depth)).unwrap();
+        assert_eq!(results[0].as_integer(), Some(depth as i64 * 7 + 1));
+        assert_eq!(results[1].as_integer(), Some(42));
+    }
+    let err = state.execute(&nested_calls(32)).unwrap_err();
+    let msg = state.get_error_msg(err);
+    assert!(
+        msg.contains("function or expression needs too many registers"),
+        "{msg}"
+    );
+}
@@ -0,0 +1,266 @@
+// Tests for the editor-tooling lexer and syntax tree (luars::syntax)
+use crate::syntax::*;
+use crate::*;
+
+const FIXTURE: &str = include_str!("test_data/syntax_tree.lua");
+
+/// Byte offset of the `nth` (0-based) occurrence of `needle` in `source`.
+fn offset_of(source: &str, needle: &str, nth: usize) -> usize {
+    source
+        .match_indices(needle)
+        .nth(nth)
+        .unwrap_or_else(|| panic!("{needle:?} #{nth} not in source"))
+        .0
+}
+
+fn text(source: &str, span: SourceRange) -> &str {
+    &source[span.start_offset..span.end_offset()]
+}
+
+#[test]
+fn test_token_spans_and_positions_over_multibyte_text() {
+    let tokens: Vec<Token> = Lexer::new(FIXTURE).tokens().collect();
+    assert!(tokens.iter().all(|t| !t.kind.is_trivia()));
+
+    // The first token comes after a comment full of multibyte characters
+    let first = tokens[0];
+    assert_eq!(first.kind, LuaTokenKind::TkLocal);
+    assert_eq!(first.span.start_offset, FIXTURE.find('\n').unwrap() + 1);
+    assert_eq!(first.start, Position::new(2, 1));
+
+    // Columns count characters, spans count bytes
+    let string = tokens
+        .iter()
+        .find(|t| t.kind == LuaTokenKind::TkString)
+        .unwrap();
+    assert_eq!(text(FIXTURE, string.span), "\"héllo, wörld ✓\"");
+    assert_eq!(string.span.length, "\"héllo, wörld ✓\"".len());
+    assert_eq!(string.start, Position::new(2, 18));
+    assert_eq!(string.end, Position::new(2, 34));
+
+    // `local y` follows a long comment spanning a line break
+    let y = tokens
+        .iter()
+        .find(|t| t.kind == LuaTokenKind::TkName && text(FIXTURE, t.span) == "y")
+        .unwrap();
+    assert_eq!(y.start, Position::new(4, 23));
+    let snowman = tokens
+        .iter()
+        .find(|t| text(FIXTURE, t.span) == "\"shadow ☃\"")
+        .unwrap();
+    assert_eq!(snowman.start, Position::new(6, 15));
+    assert_eq!(snowman.end, Position::new(6, 25));
+
+    // Positions and offsets convert both ways
+    let lines = LineIndex::new(FIXTURE);
+    for token in &tokens {
+        assert_eq!(
+            lines.offset(FIXTURE, token.start),
+            Some(token.span.start_offset)
+        );
+        assert_eq!(
+            lines.offset(FIXTURE, token.end),
+            Some(token.span.end_offset())
+        );
+    }
+
+    // With trivia, the tokens tile the whole source
+    let all: Vec<Token> = Lexer::new(FIXTURE).with_trivia(true).tokens().collect();
+    let rebuilt: String = all.iter().map(|t| text(FIXTURE, t.span)).collect();
+    assert_eq!(rebuilt, FIXTURE);
+    let comments: Vec<&str> = all
+        .iter()
+        .filter(|t| {
+            matches!(
+                t.kind,
+                LuaTokenKind::TkShortComment | LuaTokenKind::TkLongComment
+            )
+        })
+        .map(|t| text(FIXTURE, t.span))
+        .collect();
+    assert_eq!(
+        comments[0],
+        "-- 日本語のコメント: multibyte text before any code"
+    );
+    assert_eq!(comments[1], "--[[ ünïcödé\nlong comment ]]");
+}
+
+#[test]
+fn test_lexer_goes_on_after_a_bad_token() {
+    let source = "local s = \"unfinished\nlocal t = 1";
+    let mut lexer = Lexer::new(source);
+    let tokens: Vec<Token> = lexer.tokens().collect();
+    assert_eq!(lexer.errors().len(), 1);
+    assert!(lexer.errors()[0].message.contains("unfinished string"));
+    assert_eq!(lexer.errors()[0].start, Position::new(1, 11));
+    let t = tokens.iter().find(|t| text(source, t.span) == "t").unwrap();
+    assert_eq!(t.start, Position::new(2, 7));
+}
+
+#[test]
+fn test_resolution_of_shadowed_locals() {
+    let tree = parse(FIXTURE);
+    assert!(!tree.has_errors(), "{:?}", tree.errors);
+
+    // The declaration of `name`, written at the start of `needle`
+    let decl_at = |needle: &str, name: &str| {
+        let offset = offset_of(FIXTURE, needle, 0);
+        let id = tree
+            .declarations
+            .iter()
+            .position(|d| d.span.start_offset == offset)
+            .unwrap_or_else(|| panic!("no declaration at {needle:?}"));
+        assert_eq!(tree.declaration(id).name, name);
+        id
+    };
+    // What the name at the start of `needle` refers to
+    let use_at = |needle: &str| {
+        tree.reference_at(offset_of(FIXTURE, needle, 0))
+            .unwrap_or_else(|| panic!("no reference at {needle:?}"))
+            .resolution
+    };
+
+    let greeting = decl_at("greeting =", "greeting");
+    let top_x = decl_at("x = 1 --", "x");
+    let y = decl_at("y = x", "y");
+    let do_x = decl_at("x = \"shadow", "x");
+    let outer = decl_at("outer(x)", "outer");
+    let param_x = decl_at("x)\n    return function", "x");
+    let for_x = decl_at("x = 1, 3", "x");
+    assert_eq!(tree.declaration(param_x).kind, DeclKind::Parameter);
+    assert_eq!(tree.declaration(for_x).kind, DeclKind::ForVariable);
+
+    // `local y = x` sees the x declared just before
+    assert_eq!(use_at("x\ndo"), Resolution::Local(top_x));
+    // Inside `do`, the inner x shadows the outer one; y is still visible
+    assert_eq!(use_at("x, y)"), Resolution::Local(do_x));
+    assert_eq!(use_at("y)"), Resolution::Local(y));
+    // The nested function reaches the parameter and the chunk's local as upvalues
+    assert_eq!(use_at("x .."), Resolution::Upvalue(param_x));
+    assert_eq!(use_at("greeting\n"), Resolution::Upvalue(greeting));
+    assert_eq!(use_at("x) end"), Resolution::Local(for_x));
+    // After the loop and the block, x is the chunk's local again
+    assert_eq!(use_at("x, undefined"), Resolution::Local(top_x));
+    assert_eq!(use_at("undefined_global"), Resolution::Global(None));
+    assert_eq!(use_at("print"), Resolution::Global(None));
+
+    // Find-definition from a use and from the declaration itself
+    assert_eq!(
+        tree.definition_at(offset_of(FIXTURE, "x ..", 0)),
+        Some(param_x)
+    );
+    assert_eq!(
+        tree.definition_at(offset_of(FIXTURE, "outer", 0)),
+        Some(outer)
+    );
+    assert_eq!(tree.references_to(top_x).count(), 2);
+    assert_eq!(tree.references_to(greeting).count(), 1);
+
+    // The tree mirrors the chunk's statements
+    let kinds: Vec<&str> = tree
+        .block
+        .stats
+        .iter()
+        .map(|s| match &s.kind {
+            StatKind::Local { .. } => "local",
+            StatKind::Do(_) => "do",
+            StatKind::LocalFunction { .. } => "local function",
+            StatKind::NumericFor { .. } => "for",
+            StatKind::Call(_) => "call",
+            _ => "other",
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "local",
+            "local",
+            "local",
+            "do",
+            "local function",
+            "for",
+            "call"
+        ]
+    );
+    let StatKind::LocalFunction { func, .. } = &tree.block.stats[4].kind else {
+        unreachable!()
+    };
+    assert!(text(FIXTURE, func.span).starts_with("function outer(x)"));
+    assert!(text(FIXTURE, func.span).ends_with("end\nend"));
+}
+
+#[test]
+fn test_method_self_and_global_declarations() {
+    let source = "local t = {}\n\
+                  function t:get(k) return self[k] end\n\
+                  global print, count <const>\n\
+                  print(count, missing)\n";
+    let tree = parse(source);
+    let self_use = tree.reference_at(offset_of(source, "self", 0)).unwrap();
+    let Resolution::Local(self_decl) = self_use.resolution else {
+        panic!("self resolved to {:?}", self_use.resolution);
+    };
+    assert_eq!(tree.declaration(self_decl).kind, DeclKind::SelfParameter);
+    assert_eq!(
+        tree.reference_at(offset_of(source, "t:get", 0))
+            .unwrap()
+            .resolution,
+        Resolution::Local(0)
+    );
+
+    let print = tree.reference_at(offset_of(source, "print", 1)).unwrap();
+    let Resolution::Global(Some(print_decl)) = print.resolution else {
+        panic!("print resolved to {:?}", print.resolution);
+    };
+    assert_eq!(tree.declaration(print_decl).kind, DeclKind::Global);
+    let count = tree
+        .declarations
+        .iter()
+        .find(|d| d.name == "count")
+        .unwrap();
+    assert_eq!(count.attrib, Some(Attrib::Const));
+
+    // A named global declaration voids global-by-default, as in the compiler
+    assert_eq!(tree.errors.len(), 1);
+    assert_eq!(tree.errors[0].message, "variable 'missing' not declared");
+    assert_eq!(tree.errors[0].start, Position::new(4, 14));
+    let mut vm = GlobalState::new(SafeOption::default());
+    let err = vm.main_state().execute(source).unwrap_err();
+    let msg = vm.main_state().get_error_message(err);
+    assert!(msg.contains("variable 'missing' not declared"), "{msg}");
+}
+
+#[test]
+fn test_parse_recovers_from_errors() {
+    let source = "local a = 1\n\
+                  local b = (a +\n\
+                  local c = a\n\
+                  function f( end\n\
+                  print(c) )\n\
+                  end\n\
+                  local d = c";
+    let tree = parse(source);
+    assert!(tree.errors.len() >= 3, "{:?}", tree.errors);
+    assert_eq!(tree.errors[0].start.line, 3);
+
+    // Everything after the errors is still parsed and resolved
+    let c = tree
+        .declarations
+        .iter()
+        .position(|d| d.name == "c")
+        .unwrap();
+    assert_eq!(
+        tree.reference_at(offset_of(source, "c)", 0))
+            .unwrap()
+            .resolution,
+        Resolution::Local(c)
+    );
+    assert_eq!(
+        tree.reference_at(offset_of(source, "= c", 0) + 2)
+            .unwrap()
+            .resolution,
+        Resolution::Local(c)
+    );
+    assert!(tree.declarations.iter().any(|d| d.name == "d"));
+    assert!(tree.block.stats.iter().any(|s| s.kind == StatKind::Error));
+}
@@ -42,6 +42,58 @@ fn test_table_remove() {
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_table_insert_remove_boundaries() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        -- positions must be within [1, #t + 1]
+        local t = {1, 2, 3}
+        local ok, err = pcall(table.insert, t, 0, "x")
+        assert(not ok and err:find("bad argument #2 to 'table.insert' %(position out of bounds%)"))
+        ok, err = pcall(table.insert, t, 5, "x")
+        assert(not ok and err:find("position out of bounds"))
+        ok, err = pcall(table.insert, t, 1, 2, 3)
+        assert(not ok and err:find("wrong number of arguments to 'insert'"))
+        table.insert(t, 4, "end")
+        table.insert(t, "app")
+        assert(table.concat(t, ",") == "1,2,3,end,app")
+
+        -- inserting shifts up, removing shifts down, leaving no holes
+        local s = {}
+        for i = 1, 1000 do s[i] = i end
+        table.insert(s, 1, 0)
+        table.insert(s, 500, "mid")
+        assert(#s == 1002 and s[1] == 0 and s[2] == 1 and s[500] == "mid" and s[501] == 499)
+        assert(s[1002] == 1000)
+        assert(table.remove(s, 500) == "mid" and table.remove(s, 1) == 0)
+        assert(#s == 1000 and s[1001] == nil)
+        for i = 1, 1000 do assert(s[i] == i) end
+
+        -- removal bounds: empty tables return t[0], position 0 is an error otherwise
+        assert(table.remove({}) == nil)
+        local z = {[0] = "zero"}
+        assert(table.remove(z) == "zero" and z[0] == nil)
+        ok, err = pcall(table.remove, {1}, 0)
+        assert(not ok and err == "bad argument #2 to 'table.remove' (position out of bounds)")
+        assert(table.remove({1}, 2) == nil)
+
+        -- a sequence whose border lies in the hash part
+        local h = {}
+        for i = 20, 1, -1 do h[i] = i end
+        table.insert(h, 1, 0)
+        assert(#h == 21)
+        for i = 1, 21 do assert(h[i] == i - 1) end
+        assert(table.remove(h, 1) == 0 and #h == 20 and h[21] == nil)
+        for i = 1, 20 do assert(h[i] == i) end
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_table_concat() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -488,3 +540,328 @@ fn test_table_newindex_counting() {
 
     assert!(result.is_ok(), "newindex counting failed: {:?}", result);
 }
+
+#[test]
+fn test_table_constructor_evaluation_order() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local log = {}
+        local function logger(name, ...)
+            local rets = table.pack(...)
+            return function()
+                log[#log + 1] = name
+                return table.unpack(rets, 1, rets.n)
+            end
+        end
+        local a, b, c = logger("a", 1, 10), logger("b", 2), logger("c", 3, 30)
+        local d, e, f = logger("d", "k"), logger("e", 5), logger("f", 6, 7, 8)
+
+        local t = {a(), x = b(), c(), [d()] = e(), f()}
+        assert(table.concat(log, ",") == "a,b,c,d,e,f")
+        -- only the last positional expression is expanded
+        assert(t[1] == 1 and t[2] == 3 and t[3] == 6 and t[4] == 7 and t[5] == 8)
+        assert(#t == 5 and t.x == 2 and t.k == 5)
+
+        -- a keyed field's key is evaluated before its value
+        log = {}
+        local proxy = setmetatable({}, {__index = function(_, k)
+            log[#log + 1] = "index " .. k
+            return k
+        end})
+        local t2 = {[proxy.key] = e(), a()}
+        assert(table.concat(log, ",") == "index key,e,a")
+        assert(t2.key == 5 and t2[1] == 1 and t2[2] == 10)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_mixed_integer_float_keys() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        -- 2 and 2.0 are the same key, whichever is used to store or read
+        local t = {}
+        t[1] = "a"; t[2.0] = "b"; t[3] = "c"
+        assert(t[2] == "b" and rawget(t, 2) == "b")
+        t[2] = "B"
+        assert(t[2.0] == "B" and #t == 3)
+        assert(table.concat(t, ",") == "a,B,c")
+        local n = 0
+        for k, v in pairs(t) do
+            assert(math.type(k) == "integer")
+            n = n + 1
+        end
+        assert(n == 3)
+        local c = {[1.0] = 1, [2] = 2, [3.0] = 3}
+        assert(#c == 3 and table.concat(c, "") == "123")
+
+        -- -0.0 is stored as integer 0
+        local z = {}
+        z[-0.0] = true
+        assert(z[0] and math.type(next(z)) == "integer")
+
+        -- 2^63 has no integer value and must not alias maxinteger
+        local big = {}
+        big[2^63] = "f"
+        big[math.maxinteger] = "i"
+        assert(big[2^63] == "f" and big[math.maxinteger] == "i")
+        assert(big[math.mininteger] == nil and big[-2^63] == nil)
+
+        -- nil and NaN are rejected on every set path
+        local ok, err = pcall(function() t[0/0] = 1 end)
+        assert(not ok and err:find("table index is NaN"))
+        ok, err = pcall(function() return {[0/0] = 1} end)
+        assert(not ok and err:find("table index is NaN"))
+        ok, err = pcall(function() local k; t[k] = 1 end)
+        assert(not ok and err:find("table index is nil"))
+        ok, err = pcall(rawset, t, nil, 1)
+        assert(not ok and err:find("table index is nil"))
+        ok, err = pcall(rawset, t, 0/0, 1)
+        assert(not ok and err:find("table index is NaN"))
+        assert(t[0/0] == nil and rawget(t, 0/0) == nil)
+    "#,
+    );
+
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_hash_part_bounded_under_insert_delete_churn() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        collectgarbage(); collectgarbage()
+        local base = collectgarbage("count")
+        local cache = {}
+        local peak = 0
+        for i = 1, 1000000 do
+            cache[i + 0.5] = i
+            if i > 100 then cache[i - 99.5] = nil end
+            if i % 100000 == 0 then
+                collectgarbage()
+                peak = math.max(peak, collectgarbage("count") - base)
+            end
+        end
+        -- 100 live entries need a 128-node hash part; allow for rehash slack
+        assert(peak < 64, "cache grew to " .. peak .. " KB")
+
+        -- A table that was filled and then emptied gives its nodes back
+        local t = {}
+        for i = 1, 50000 do t["k" .. i] = i end
+        collectgarbage()
+        local full = collectgarbage("count")
+        for i = 1, 50000 do t["k" .. i] = nil end
+        collectgarbage(); collectgarbage()
+        local emptied = collectgarbage("count")
+        assert(full - emptied > 1024, full .. " KB -> " .. emptied .. " KB")
+        t.again = 1
+        assert(t.again == 1 and next(t) == "again")
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_gc_keeps_dead_keys_during_traversal() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // Clearing fields during pairs() is allowed; a collection in the middle
+    // must not rebuild the table under the traversal.
+    let result = vm.main_state().execute(
+        r#"
+        local t = {}
+        for i = 1, 1000 do t["k" .. i] = i end
+        local seen = 0
+        for k, v in pairs(t) do
+            assert(t[k] == v)
+            t[k] = nil
+            seen = seen + 1
+            if seen % 7 == 0 then collectgarbage() end
+        end
+        assert(seen == 1000 and next(t) == nil)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_insert_through_newindex_during_traversal() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // A proxy whose __newindex adds keys to the table being traversed: once
+    // the insertions move entries around, next() reports the key as invalid
+    // instead of silently skipping the keys that moved.
+    let result = vm.main_state().execute(
+        r#"
+        local backing = {}
+        for i = 1, 64 do backing["k" .. i] = i end
+        local proxy = setmetatable({}, {
+            __newindex = function(_, k, v) rawset(backing, k, v) end,
+        })
+        local added = 0
+        local ok, err = pcall(function()
+            for k in pairs(backing) do
+                proxy["new" .. k] = true
+                added = added + 1
+            end
+        end)
+        assert(not ok and string.find(err, "invalid key to 'next'", 1, true), err)
+
+        -- Nothing was lost, and a fresh traversal sees each key once
+        local seen, count = {}, 0
+        for k in pairs(backing) do
+            assert(not seen[k])
+            seen[k] = true
+            count = count + 1
+        end
+        assert(count == 64 + added, count)
+        for i = 1, 64 do assert(backing["k" .. i] == i) end
+
+        -- Insertions outside a traversal move entries too, but a later
+        -- next(t, k) is not a resumed traversal and must still work
+        local t = {}
+        for i = 1, 10 do t["k" .. i] = i end
+        local ok, k, v = pcall(next, t, "k3")
+        assert(ok, k)
+        assert(k == nil or t[k] == v)
+        -- Neither does a finished traversal make later ones fail
+        for _ in pairs(t) do end
+        for i = 11, 40 do t["k" .. i] = i end
+        assert(pcall(next, t, "k3"))
+
+        -- Growing a hash part down to a single node used to write through a
+        -- null node array
+        for i = 1, 100 do
+            local t = {}
+            t[2], t[3], t[4] = true, true, true
+            t.a = true
+            t.a = nil
+            t["k" .. i] = true
+            assert(t["k" .. i] and t[2] and t[3] and t[4])
+        end
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_table_insert_error_names_call_site() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    // The position is the calling Lua line, and the name is how the caller
+    // spelled the function
+    let result = vm.main_state().execute(
+        r#"
+        local function check(src, expected)
+            local ok, err = pcall(load(src, "=t"))
+            assert(not ok and err == expected, err)
+        end
+        check("table.insert(nil, 1)",
+            "t:1: bad argument #1 to 'insert' (table expected, got nil)")
+        check("local t = {insert = table.insert}\nt.insert(t, 2.5, 'x')",
+            "t:2: bad argument #2 to 'insert' (number has no integer representation)")
+        check("local t = {insert = table.insert}\n\nt:insert(2.5, 'x')",
+            "t:3: bad argument #1 to 'insert' (number has no integer representation)")
+        check("local push = table.insert\npush(nil, 'x')",
+            "t:2: bad argument #1 to 'push' (table expected, got nil)")
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_table_create() {
+    let option = SafeOption {
+        max_memory_limit: 64 * 1024 * 1024,
+        ..Default::default()
+    };
+    let mut vm = GlobalState::new(option);
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local t = table.create(3, 2)
+        assert(next(t) == nil and #t == 0)
+        t[1], t[2], t[3], t.x = "a", "b", "c", 1
+        assert(#t == 3 and t.x == 1)
+        assert(next(table.create(0)) == nil)
+        assert(type(table.create("10", 2.0)) == "table")
+
+        local function check(msg, ...)
+            local ok, err = pcall(table.create, ...)
+            assert(not ok and err:find(msg, 1, true), err)
+        end
+        check("bad argument #1 to 'table.create' (out of range)", -1)
+        check("bad argument #2 to 'table.create' (out of range)", 1, -1)
+        check("bad argument #1 to 'table.create' (out of range)", 1 << 40)
+        check("bad argument #1 to 'table.create' (number expected, got no value)")
+        check("bad argument #1 to 'table.create' (number has no integer representation)", 1.5)
+        check("bad argument #2 to 'table.create' (number expected, got table)", 1, {})
+        check("table overflow", (1 << 26) + 1)
+        check("table overflow", 0, (1 << 26) + 1)
+        -- under the cap but over the memory limit: fails before allocating
+        check("memory", 1 << 26)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+#[test]
+fn test_presized_table_fills_without_rehash() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(crate::stdlib::Stdlib::All).unwrap();
+
+    let parts = |t: &LuaValue| {
+        let table = t.as_table().unwrap();
+        (
+            table.impl_table.array as usize,
+            table.impl_table.asize,
+            table.hash_size(),
+        )
+    };
+
+    // From Lua: hash part rounded up to a power of two
+    let t = vm
+        .main_state()
+        .execute_single("return table.create(1000, 100)")
+        .unwrap();
+    vm.set_global("t", t).unwrap();
+    let before = parts(&t);
+    assert_eq!((before.1, before.2), (1000, 128));
+    vm.main_state()
+        .execute(
+            r#"
+            for i = 1, 1000 do rawset(t, i, i) end
+            for i = 1, 128 do t["k" .. i] = i end
+        "#,
+        )
+        .unwrap();
+    assert_eq!(parts(&t), before);
+
+    // From Rust
+    let state = vm.main_state();
+    let table = state.create_table_with_capacity(1000, 100).unwrap();
+    let t = table.value();
+    let before = parts(&t);
+    for i in 1..=1000 {
+        table.raw_seti(i, i).unwrap();
+    }
+    for i in 1..=128 {
+        table.raw_set(format!("k{i}"), i).unwrap();
+    }
+    assert_eq!(parts(&t), before);
+    assert_eq!(table.raw_len().unwrap(), 1000);
+}
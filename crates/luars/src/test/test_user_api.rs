@@ -115,6 +115,57 @@ fn test_lua_state_safe_create_refs_survive_gc() {
     assert_eq!(table.get("name").unwrap().as_str(), Some("rooted"));
 }
 
+#[test]
+fn test_stale_raw_value_after_gc_is_rejected() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    let state = vm.main_state();
+
+    // Raw values returned to Rust are not GC roots
+    let results = state.execute("return {1, 2, 3}, {4}").unwrap();
+    let stale = state.global_state().weak_value(results[0]);
+    let anchored = state.to_table_ref(results[1]).unwrap();
+    drop(results);
+    assert!(state.check_value_alive(&stale).unwrap().is_table());
+
+    state.collect_garbage().unwrap();
+
+    let err = state.check_value_alive(&stale).unwrap_err();
+    let msg = state.get_error_msg(err);
+    assert!(msg.contains("collected table"), "unexpected error: {msg}");
+    let anchored_weak = state.global_state().weak_value(anchored.to_value());
+    assert!(state.check_value_alive(&anchored_weak).is_ok());
+    let number = state.global_state().weak_value(LuaValue::integer(1));
+    assert!(state.check_value_alive(&number).is_ok());
+    assert_eq!(anchored.geti(1).unwrap().as_integer(), Some(4));
+}
+
+#[test]
+fn test_stale_value_is_not_revived_by_slot_reuse() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    let state = vm.main_state();
+
+    let table = state.create_table(0, 0).unwrap();
+    let address = table.raw_ptr_repr();
+    let stale = state.global_state().weak_value(table);
+    state.collect_garbage().unwrap();
+    assert!(!state.global_state().is_value_alive(&stale));
+
+    // A new table in the freed slot must not make the old handle valid.
+    let mut reused = false;
+    let mut keep = Vec::new();
+    for _ in 0..1024 {
+        let table = state.create_table(0, 0).unwrap();
+        keep.push(state.to_table_ref(table).unwrap());
+        if table.raw_ptr_repr() == address {
+            reused = true;
+            break;
+        }
+    }
+    assert!(reused, "no new table reused the freed slot");
+    assert!(!state.global_state().is_value_alive(&stale));
+    assert!(stale.get(state.global_state()).is_none());
+}
+
 #[test]
 fn test_lua_state_create_userdata_handle_survives_gc() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -388,6 +439,93 @@ fn test_userdata_ref_from_lua_and_mutate() {
     assert_eq!(results[0].as_integer(), Some(20));
 }
 
+#[test]
+fn test_values_from_another_vm_are_rejected() {
+    let mut vm_a = GlobalState::new(SafeOption::default());
+    vm_a.open_stdlib(Stdlib::All).unwrap();
+    let mut vm_b = GlobalState::new(SafeOption::default());
+    vm_b.open_stdlib(Stdlib::All).unwrap();
+
+    let userdata = vm_a
+        .create_userdata(crate::lua_value::LuaUserdata::new(ApiCounter { count: 3 }))
+        .unwrap();
+    let table = vm_a.create_table(0, 0).unwrap();
+    vm_a.set_global("counter", userdata).unwrap();
+
+    let err = vm_b.set_global("counter", userdata).unwrap_err();
+    let msg = vm_b.main_state().get_error_msg(err);
+    assert!(msg.contains("value belongs to a different VM"), "{msg}");
+    assert!(vm_b.get_global("counter").unwrap().is_none());
+
+    let state = vm_b.main_state();
+    let func = state.load("return ...").unwrap();
+    let err = state.call_args(func, &[userdata]).unwrap_err();
+    let msg = state.get_error_msg(err);
+    assert!(msg.contains("value belongs to a different VM"), "{msg}");
+    let err = state.pcall_args(func, &[table]).unwrap_err();
+    let msg = state.get_error_msg(err);
+    assert!(msg.contains("value belongs to a different VM"), "{msg}");
+
+    let globals = state.global_state().global;
+    let key = state.create_string("t").unwrap();
+    assert!(state.table_set(&globals, key, table).is_err());
+    let foreign = state.global_state().weak_value(table);
+    assert!(state.check_value_alive(&foreign).is_err());
+
+    // Every other way of resolving a foreign value refuses it as well.
+    assert!(state.table_get(&table, &key).is_err());
+    assert!(state.table_get(&globals, &table).is_err());
+    assert!(state.raw_get(&table, &key).is_none());
+    assert!(!state.raw_set(&globals, key, table));
+    assert!(!state.raw_seti(&table, 1, key));
+    assert!(state.raw_geti(&table, 1).is_none());
+    assert!(state.stack_set(0, table).is_err());
+    assert!(state.obj_len(&table).is_err());
+    assert!(state.to_string(&userdata).is_err());
+    assert!(state.to_table_ref(table).is_none());
+    assert!(state.create_upvalue_closed(table).is_err());
+    let gs = state.global_state_mut();
+    let err = gs.table_pairs(&table).unwrap_err();
+    let msg = gs.main_state().get_error_msg(err);
+    assert!(msg.contains("value belongs to a different VM"), "{msg}");
+    assert!(gs.table_length(&table).is_err());
+    assert!(gs.create_thread(userdata).is_err());
+    assert!(gs.create_c_closure(|_| Ok(0), vec![table]).is_err());
+    assert!(gs.registry_set("foreign", table).is_err());
+    assert!(gs.registry_get("foreign").unwrap().is_none());
+
+    // Table handles report foreign keys and values instead of dropping them.
+    let own_table = vm_b.create_table(0, 0).unwrap();
+    let own_ref = vm_b.to_table_ref(own_table).unwrap();
+    assert!(own_ref.set_value(table, LuaValue::integer(1)).is_err());
+    assert!(own_ref.rawset_typed("t", table).is_err());
+    assert!(own_ref.rawseti_typed(1, userdata).is_err());
+    assert!(own_ref.rawget_typed::<_, LuaValue>(table).is_err());
+    assert!(own_ref.pairs().unwrap().is_empty());
+    let foreign_ref = vm_a.to_table_ref(table).unwrap();
+    assert!(own_ref.set_metatable(Some(&foreign_ref)).is_err());
+    assert!(!own_ref.has_metatable());
+
+    // Handing a foreign value to Lua from a host function raises a Lua error.
+    vm_b.register_function("leak", move |state| {
+        state.push_value(userdata)?;
+        Ok(1)
+    })
+    .unwrap();
+    let results = vm_b.main_state().execute("return pcall(leak)").unwrap();
+    assert_eq!(results[0].as_boolean(), Some(false));
+    let msg = results[1].as_str().unwrap_or_default().to_string();
+    assert!(msg.contains("value belongs to a different VM"), "{msg}");
+
+    // Both VMs keep working with their own values.
+    let results = vm_a.main_state().execute("return counter.count").unwrap();
+    assert_eq!(results[0].as_integer(), Some(3));
+    let own = vm_b.create_table(0, 0).unwrap();
+    vm_b.set_global("own", own).unwrap();
+    let results = vm_b.main_state().execute("return type(own)").unwrap();
+    assert_eq!(results[0].as_str(), Some("table"));
+}
+
 // ============================================================================
 // OpaqueUserData tests
 // ============================================================================
@@ -598,3 +736,188 @@ fn test_get_global_nonexistent() {
     assert!(vm.get_global_table("nonexistent").unwrap().is_none());
     assert!(vm.get_global_function("nonexistent").unwrap().is_none());
 }
+
+// ============================================================================
+// Inspection API tests
+// ============================================================================
+
+#[test]
+fn test_inspect_globals_and_tables() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+    let state = vm.main_state();
+    state
+        .execute(
+            r#"
+            data = { 10, 20, 30, name = "x", [2.5] = true }
+            setmetatable(data, { __pairs = function() error("no metamethods") end })
+            "#,
+        )
+        .unwrap();
+
+    let globals: Vec<String> = state.iter_globals().map(|(k, _)| k.to_string()).collect();
+    assert!(globals.iter().any(|k| k == "data"));
+    assert!(globals.iter().any(|k| k == "print"));
+
+    let data = state.get_global_value("data").unwrap().unwrap();
+    let entries: Vec<(LuaValue, LuaValue)> = state.iter_table(&data).collect();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[0].0, LuaValue::integer(1));
+    assert_eq!(entries[2].1, LuaValue::integer(30));
+    assert!(
+        entries
+            .iter()
+            .any(|(k, v)| k.to_string() == "name" && v.to_string() == "x")
+    );
+    assert_eq!(state.iter_table(&LuaValue::integer(1)).count(), 0);
+
+    // The walk is live, over both parts of a table with a rehashed hash part
+    state
+        .execute("big = {} for i = 1, 300 do big['k' .. i] = i; big[i] = i end")
+        .unwrap();
+    let big = state.get_global_value("big").unwrap().unwrap();
+    let mut sum = 0;
+    for (_, v) in state.iter_table(&big) {
+        sum += v.as_integer().unwrap();
+    }
+    assert_eq!(sum, 2 * (300 * 301 / 2));
+}
+
+#[test]
+fn test_inspect_function_info() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+    let state = vm.main_state();
+    state
+        .execute(
+            r#"
+            local count, step = 0, 2
+            function bump(a, b, ...)
+                count = count + step
+                return count
+            end
+            "#,
+        )
+        .unwrap();
+
+    let bump = state.get_global_value("bump").unwrap().unwrap();
+    let info = state.function_info(&bump).unwrap();
+    assert_eq!(info.params, 2);
+    assert!(info.is_vararg);
+    assert_eq!(info.upvalue_names, vec!["count", "step"]);
+    assert_eq!(
+        info.upvalues,
+        vec![LuaValue::integer(0), LuaValue::integer(2)]
+    );
+    assert_eq!(info.line_range, (3, 6));
+
+    let print = state.get_global_value("print").unwrap().unwrap();
+    let info = state.function_info(&print).unwrap();
+    assert_eq!(info.source, "[C]");
+    assert_eq!(info.line_range, (-1, -1));
+
+    assert!(state.function_info(&LuaValue::nil()).is_none());
+}
+
+#[test]
+fn test_inspect_suspended_thread_stack() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+    let state = vm.main_state();
+    state
+        .execute(
+            r#"
+            local function work(limit)
+                local total = 0
+                for i = 1, limit do
+                    total = total + i
+                    coroutine.yield(total)
+                end
+            end
+            co = coroutine.create(work)
+            coroutine.resume(co, 3)
+            coroutine.resume(co)
+            fresh = coroutine.create(work)
+            "#,
+        )
+        .unwrap();
+
+    let co = state.get_global_value("co").unwrap().unwrap();
+    let frames = state.thread_stack(&co);
+    assert_eq!(frames.len(), 2, "{:?}", frames);
+    assert_eq!(frames[0].what, "C");
+    let work = &frames[1];
+    assert_eq!(work.what, "Lua");
+    assert_eq!(work.current_line, 6);
+    let local = |name: &str| work.locals.iter().find(|(n, _)| n == name).map(|(_, v)| *v);
+    assert_eq!(local("limit"), Some(LuaValue::integer(3)));
+    assert_eq!(local("total"), Some(LuaValue::integer(3)));
+    assert_eq!(local("i"), Some(LuaValue::integer(2)));
+
+    // The coroutine is still usable after being inspected.
+    state
+        .execute("assert(select(2, coroutine.resume(co)) == 6)")
+        .unwrap();
+
+    let fresh = state.get_global_value("fresh").unwrap().unwrap();
+    assert!(state.thread_stack(&fresh).is_empty());
+    assert!(state.thread_stack(&LuaValue::nil()).is_empty());
+}
+
+#[test]
+fn test_interrupt_hook_stops_runaway_code() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+
+    let stop = Rc::new(Cell::new(false));
+    let polls = Rc::new(Cell::new(0_u32));
+    let (hook_stop, hook_polls) = (stop.clone(), polls.clone());
+    vm.set_interrupt_hook(move || {
+        hook_polls.set(hook_polls.get() + 1);
+        if hook_polls.get() >= 10 {
+            hook_stop.set(true);
+        }
+        if hook_stop.get() {
+            Err("interrupted".to_string())
+        } else {
+            Ok(())
+        }
+    });
+
+    let state = vm.main_state();
+    let func = state.load_with_name("while true do end", "=loop").unwrap();
+    let err = state.call_args(func, &[]).unwrap_err();
+    assert_eq!(state.get_error_msg(err), "loop:1: interrupted");
+
+    // Catching the error does not help: the hook keeps being polled,
+    // including inside coroutines and after allocations trigger the GC
+    let func = state
+        .load(
+            r#"
+            while true do
+                pcall(function()
+                    local co = coroutine.wrap(function()
+                        while true do local t = {} end
+                    end)
+                    co()
+                end)
+            end
+            "#,
+        )
+        .unwrap();
+    let err = state.call_args(func, &[]).unwrap_err();
+    assert!(state.get_error_msg(err).contains("interrupted"));
+
+    vm.clear_interrupt_hook();
+    let polled = polls.get();
+    let result = vm
+        .main_state()
+        .execute("local n = 0 for i = 1, 100000 do n = n + i end return n")
+        .unwrap();
+    assert_eq!(result[0].as_integer(), Some(5000050000));
+    assert_eq!(polls.get(), polled);
+    assert_eq!(vm.main_state().hook_mask(), 0);
+}
@@ -1198,6 +1198,189 @@ fn test_userdata_arithmetic_preserves_type() {
     assert_eq!(results[2].as_number(), Some(6.0));
 }
 
+#[test]
+fn test_userdata_shared_type_metatable() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+    vm.register_type_of::<Vec2>("Vec2").unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local a, b = Vec2.new(0, 0), Vec2.new(10, 20)
+        local mt = getmetatable(a)
+        assert(type(mt) == "table" and mt == getmetatable(b))
+        assert(mt == debug.getregistry().Vec2 and mt.__name == "Vec2")
+
+        -- Lua-side extensions are visible to every instance
+        function mt.__index.lerp(p, q, t)
+            return Vec2.new(p.x + (q.x - p.x) * t, p.y + (q.y - p.y) * t)
+        end
+        local m = a:lerp(b, 0.5)
+        assert(m.x == 5 and m.y == 10)
+        assert(getmetatable(m) == mt)
+        assert(getmetatable(a + b) == mt)
+
+        -- Rust fields win over Lua additions
+        mt.__index.x = "shadowed"
+        assert(b.x == 10)
+
+        local ok, err = pcall(setmetatable, a, {})
+        assert(not ok and err:find("cannot change a protected metatable"), err)
+        assert(getmetatable(a) == mt)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+
+    // The Rust side sees the same table, and can lift the protection
+    let mt = vm.userdata_metatable::<Vec2>().unwrap();
+    assert!(vm.set_userdata_metatable_protected::<Vec2>(false));
+    vm.set_global("host_mt", mt).unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        local v = Vec2.new(1, 2)
+        assert(getmetatable(v) == host_mt)
+        local other = { __index = { tag = function() return "custom" end } }
+        setmetatable(v, other)
+        assert(getmetatable(v) == other and v:tag() == "custom" and v.x == 1)
+
+        -- __metatable set by the host still guards getmetatable and setmetatable
+        host_mt.__metatable = "locked"
+        local w = Vec2.new(3, 4)
+        assert(getmetatable(w) == "locked")
+        assert(not pcall(setmetatable, w, nil))
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+// ==================== __concat tests ====================
+
+/// Test type for `..` — `Concat` joins its `Display` text with the other operand
+#[derive(LuaUserData)]
+#[lua_impl(Display, Concat)]
+struct Counter {
+    pub count: i64,
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Counter({})", self.count)
+    }
+}
+
+/// Second concatenable type, to mix two userdata types in one expression
+#[derive(LuaUserData)]
+#[lua_impl(Display, Concat)]
+struct Label {
+    pub text: String,
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}>", self.text)
+    }
+}
+
+fn setup_concat_vm() -> Pin<Box<GlobalState>> {
+    let mut vm = setup_point_vm();
+    let state = vm.main_state();
+    let counter = state
+        .create_userdata(LuaUserdata::new(Counter { count: 7 }))
+        .unwrap();
+    state.set_global_value("counter_ud", counter).unwrap();
+    let label = state
+        .create_userdata(LuaUserdata::new(Label { text: "hp".into() }))
+        .unwrap();
+    state.set_global_value("label_ud", label).unwrap();
+    vm
+}
+
+#[test]
+fn test_concat_userdata_either_side() {
+    let mut vm = setup_concat_vm();
+    let results = vm
+        .main_state()
+        .execute(r#"return counter_ud .. "!", "score: " .. counter_ud"#)
+        .unwrap();
+    assert_eq!(results[0].as_str(), Some("Counter(7)!"));
+    assert_eq!(results[1].as_str(), Some("score: Counter(7)"));
+}
+
+#[test]
+fn test_concat_two_userdata_types() {
+    let mut vm = setup_concat_vm();
+    let results = vm
+        .main_state()
+        .execute(r#"return label_ud .. counter_ud, counter_ud .. " " .. label_ud"#)
+        .unwrap();
+    assert_eq!(results[0].as_str(), Some("<hp>Counter(7)"));
+    assert_eq!(results[1].as_str(), Some("Counter(7) <hp>"));
+}
+
+#[test]
+fn test_concat_userdata_chained_with_numbers() {
+    let mut vm = setup_concat_vm();
+    let results = vm
+        .main_state()
+        .execute(r#"return 1 .. counter_ud .. 2.5, "n=" .. 3 .. counter_ud .. 10"#)
+        .unwrap();
+    assert_eq!(results[0].as_str(), Some("1Counter(7)2.5"));
+    assert_eq!(results[1].as_str(), Some("n=3Counter(7)10"));
+}
+
+#[test]
+fn test_concat_metamethod_wins_over_trait() {
+    let mut vm = setup_concat_vm();
+    vm.open_stdlib(Stdlib::Debug).unwrap();
+    let results = vm
+        .main_state()
+        .execute(
+            r#"
+        local mt = { __concat = function(a, b)
+            return "mm(" .. tostring(type(a)) .. "," .. tostring(type(b)) .. ")"
+        end }
+        debug.setmetatable(counter_ud, mt)
+        return counter_ud .. "!", 1 .. counter_ud, label_ud .. counter_ud
+    "#,
+        )
+        .unwrap();
+    assert_eq!(results[0].as_str(), Some("mm(userdata,string)"));
+    assert_eq!(results[1].as_str(), Some("mm(number,userdata)"));
+    // label_ud has no __concat of its own, so the right operand's applies
+    assert_eq!(results[2].as_str(), Some("mm(userdata,userdata)"));
+}
+
+#[test]
+fn test_concat_error_names_the_userdata() {
+    let mut vm = setup_concat_vm();
+    let results = vm
+        .main_state()
+        .execute(
+            r#"
+        local ok1, e1 = pcall(function() return "pos: " .. p end)
+        local ok2, e2 = pcall(function() return p .. 1 end)
+        local ok3, e3 = pcall(function() return counter_ud .. {} end)
+        return ok1, e1, ok2, e2, ok3, e3
+    "#,
+        )
+        .unwrap();
+    assert_eq!(results[0].as_boolean(), Some(false));
+    let e1 = results[1].as_str().unwrap();
+    assert!(
+        e1.ends_with("attempt to concatenate a userdata value (global 'p')"),
+        "{e1}"
+    );
+    assert_eq!(results[2].as_boolean(), Some(false));
+    let e2 = results[3].as_str().unwrap();
+    assert!(
+        e2.ends_with("attempt to concatenate a userdata value (global 'p')"),
+        "{e2}"
+    );
+    assert_eq!(results[4].as_boolean(), Some(false));
+    let e3 = results[5].as_str().unwrap();
+    assert!(e3.contains("attempt to concatenate"), "{e3}");
+}
+
 // ==================== #[lua(iter)] tests ====================
 
 /// A simple list wrapper — demonstrates `#[lua(iter)]` for Vec iteration via pairs()
@@ -1268,6 +1451,39 @@ fn test_userdata_pairs_iteration() {
     assert_eq!(results[5].as_integer(), Some(50)); // last value
 }
 
+#[test]
+fn test_userdata_pairs_metamethod_wins_over_lua_next() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(stdlib::Stdlib::Basic).unwrap();
+    vm.open_stdlib(stdlib::Stdlib::Table).unwrap();
+    vm.register_type_of::<NumberList>("NumberList").unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local list = NumberList.new("n")
+        list:push(1)
+        list:push(2)
+
+        local seen = {}
+        for k, v in pairs(list) do seen[#seen + 1] = k .. "=" .. v end
+        assert(table.concat(seen, " ") == "1=1 2=2")
+
+        -- a Lua-side __pairs on the type's metatable takes precedence
+        getmetatable(list).__pairs = function(self)
+            local i = self:size() + 1
+            return function()
+                i = i - 1
+                if i > 0 then return i, "rev" end
+            end, self
+        end
+        seen = {}
+        for k, v in pairs(list) do seen[#seen + 1] = k .. "=" .. v end
+        assert(table.concat(seen, " ") == "2=rev 1=rev")
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
 #[test]
 fn test_userdata_pairs_empty() {
     let mut vm = GlobalState::new(SafeOption::default());
@@ -1810,6 +2026,113 @@ fn test_delegated_pow_direct_call() {
     }
 }
 
+// ==================== Integer indexing ====================
+
+#[derive(LuaUserData)]
+#[lua(index_with = "get_at", newindex_with = "set_at", len = "len")]
+struct Pixels {
+    pub width: i64,
+    data: Vec<u8>,
+}
+
+impl Pixels {
+    fn get_at(&self, idx: i64) -> Option<UdValue> {
+        let i = usize::try_from(idx).ok()?.checked_sub(1)?;
+        self.data.get(i).map(|&b| UdValue::Integer(b as i64))
+    }
+
+    fn set_at(&mut self, idx: i64, value: UdValue) -> Option<Result<(), String>> {
+        let i = usize::try_from(idx).ok()?.checked_sub(1)?;
+        let slot = self.data.get_mut(i)?;
+        Some(match value.to_integer() {
+            Some(v @ 0..=255) => {
+                *slot = v as u8;
+                Ok(())
+            }
+            _ => Err("pixel value must be an integer in 0..255".to_string()),
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+#[lua_methods]
+impl Pixels {}
+
+fn pixels_vm() -> Pin<Box<GlobalState>> {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+    vm.register_type_of::<Pixels>("Pixels").unwrap();
+    let img = vm
+        .main_state()
+        .create_userdata(LuaUserdata::new(Pixels {
+            width: 2,
+            data: vec![10, 20, 30, 40],
+        }))
+        .unwrap();
+    vm.set_global("img", img).unwrap();
+    vm
+}
+
+#[test]
+fn test_userdata_integer_index_get_set() {
+    let mut vm = pixels_vm();
+    let results = vm
+        .main_state()
+        .execute(
+            r#"
+            local sum = 0
+            for i = 1, #img do sum = sum + img[i] end
+            for i = 1, #img do img[i] = i end
+            local k = 3
+            return sum, img[1], img[k], img[4], img.width
+            "#,
+        )
+        .unwrap();
+    let ints: Vec<i64> = results.iter().map(|v| v.as_integer().unwrap()).collect();
+    assert_eq!(ints, vec![100, 1, 3, 4, 2]);
+}
+
+#[test]
+fn test_userdata_integer_index_falls_through_to_metatable() {
+    let mut vm = pixels_vm();
+    // Out-of-range reads reach __index, and writes __newindex; float and
+    // string keys are not integer keys
+    let results = vm
+        .main_state()
+        .execute(
+            r#"
+            local log = {}
+            local mt = getmetatable(img)
+            setmetatable(mt.__index, {
+                __index = function(_, k) return "missing " .. tostring(k) end,
+            })
+            mt.__newindex = function(_, k, v) log[#log + 1] = k .. "=" .. v end
+            img[9] = 1
+            img[0] = 2
+            return img[5], img[1.5], img["1"], table.concat(log, ",")
+            "#,
+        )
+        .unwrap();
+    assert_eq!(results[0].as_str(), Some("missing 5"));
+    assert_eq!(results[1].as_str(), Some("missing 1.5"));
+    assert_eq!(results[2].as_str(), Some("missing 1"));
+    assert_eq!(results[3].as_str(), Some("9=1,0=2"));
+}
+
+#[test]
+fn test_userdata_integer_index_set_error() {
+    let mut vm = pixels_vm();
+    let err = vm.main_state().execute("img[1] = 300").unwrap_err();
+    let msg = vm.main_state().get_error_msg(err);
+    assert!(
+        msg.contains("pixel value must be an integer in 0..255"),
+        "{msg}"
+    );
+}
+
 // ==================== #[lua_methods] reference parameter tests ====================
 
 #[test]
@@ -2401,3 +2724,615 @@ fn test_sub_ref_nested_gc_propagation() {
     // Should not crash — may return nil or error
     assert!(result.is_ok() || result.is_err());
 }
+
+/// Userdata owning a heap buffer, reported to the GC through `size_hint`
+struct Buffer {
+    bytes: Vec<u8>,
+}
+
+impl UserDataTrait for Buffer {
+    fn type_name(&self) -> &'static str {
+        "Buffer"
+    }
+
+    fn size_hint(&self) -> usize {
+        std::mem::size_of::<Self>() + self.bytes.capacity()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn test_userdata_size_hint_counts_toward_gc() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(stdlib::Stdlib::Basic).unwrap();
+
+    let count = |vm: &mut GlobalState| {
+        let results = vm
+            .main_state()
+            .execute("collectgarbage(); collectgarbage(); return collectgarbage('count')")
+            .unwrap();
+        results[0].as_number().unwrap()
+    };
+
+    let before = count(&mut vm);
+    let buffer = Buffer {
+        bytes: vec![0; 1 << 20],
+    };
+    let state = vm.main_state();
+    let ud_val = state.create_userdata(LuaUserdata::new(buffer)).unwrap();
+    state.set_global_value("buf", ud_val).unwrap();
+    let held = count(&mut vm);
+    assert!(held - before >= 1024.0, "{before} -> {held}");
+
+    vm.main_state().execute("buf = nil").unwrap();
+    let after = count(&mut vm);
+    assert!(after - before < 64.0, "{before} -> {after}");
+}
+
+// ==================== Field observers ====================
+
+#[derive(LuaUserData)]
+struct AppConfig {
+    pub level: i64,
+    pub name: String,
+    pub secret: String,
+}
+
+#[lua_methods]
+impl AppConfig {
+    pub fn new() -> Self {
+        AppConfig {
+            level: 1,
+            name: "app".to_string(),
+            secret: "hunter2".to_string(),
+        }
+    }
+}
+
+#[test]
+fn test_userdata_field_observers() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let sink = log.clone();
+    let options = TypeOptions::new()
+        .on_set(move |l, _obj, key, old, new| {
+            // Collect mid-write: the operands must survive.
+            l.collect_garbage().map_err(|e| format!("{e:?}"))?;
+            sink.borrow_mut().push(format!("{key}: {old} -> {new}"));
+            let frozen = l
+                .get_global_as::<bool>("frozen")
+                .map_err(|e| format!("{e:?}"))?
+                .unwrap_or(false);
+            if frozen {
+                return Err(format!("config is frozen, cannot set '{key}'"));
+            }
+            Ok(if key == "name" {
+                SetAction::Skip
+            } else {
+                SetAction::Allow
+            })
+        })
+        .on_get(|l, _obj, key, value| {
+            if key == "secret" {
+                return l.create_string("***").map_err(|e| format!("{e:?}"));
+            }
+            Ok(value)
+        });
+    vm.register_type_of_with::<AppConfig>("AppConfig", options)
+        .unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local cfg = AppConfig.new()
+        cfg.level = 5
+        assert(cfg.level == 5)
+        cfg.name = "other"
+        assert(cfg.name == "app")
+        assert(cfg.secret == "***")
+        local secret = cfg.secret
+        assert(secret == "***")
+
+        frozen = true
+        local ok, err = pcall(function() cfg.level = 9 end)
+        assert(not ok)
+        assert(string.find(err, "config is frozen, cannot set 'level'", 1, true), err)
+        assert(cfg.level == 5)
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(
+        *log.borrow(),
+        ["level: 1 -> 5", "name: app -> other", "level: 5 -> 9"]
+    );
+}
+
+// ==================== #[lua_methods] export rules ====================
+
+#[derive(LuaUserData)]
+struct Calculator {
+    pub value: i64,
+}
+
+#[lua_methods]
+impl Calculator {
+    /// Also callable as `Calculator(v)`
+    #[lua(constructor)]
+    pub fn new(value: i64) -> Self {
+        Calculator { value }
+    }
+
+    #[lua(static)]
+    pub fn zero() -> Self {
+        Calculator { value: 0 }
+    }
+
+    #[lua(static, rename = "max")]
+    pub fn max_value() -> i64 {
+        i64::MAX
+    }
+
+    /// Private associated function: never on the type table
+    #[allow(unused)]
+    fn internal_validate() -> bool {
+        true
+    }
+
+    #[allow(unused)]
+    pub(crate) fn crate_only(&self) -> i64 {
+        self.value
+    }
+
+    /// Renamed so it does not collide with the `value` field
+    #[lua(rename = "get")]
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    #[lua(name = "add")]
+    pub fn add_to(&mut self, n: i64) {
+        self.value += n;
+    }
+
+    #[allow(unused)]
+    #[lua(skip)]
+    pub fn rust_only(&self) -> i64 {
+        self.value
+    }
+}
+
+const CALCULATOR_SCRIPT: &str = r#"
+    local c = Calculator(40)
+    assert(c.value == 40 and c:get() == 40)
+    c:add(2)
+    assert(c.value == 42)
+    assert(Calculator.new(1).value == 1)
+    assert(Calculator.zero().value == 0)
+    assert(Calculator.max() == math.maxinteger)
+    assert(Calculator.max_value == nil)
+    assert(Calculator.internal_validate == nil)
+    assert(c.crate_only == nil and c.rust_only == nil)
+    assert(c.add_to == nil)
+    local ok, err = pcall(Calculator, "x")
+    assert(not ok and err:find("bad argument #2", 1, true), err)
+"#;
+
+#[test]
+fn test_lua_methods_export_rules() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+    vm.register_type_of::<Calculator>("Calculator").unwrap();
+    let result = vm.main_state().execute(CALCULATOR_SCRIPT);
+    assert!(result.is_ok(), "{:?}", result);
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+    vm.main_state()
+        .register_type_of::<Calculator>("Calculator")
+        .unwrap();
+    let result = vm.main_state().execute(CALCULATOR_SCRIPT);
+    assert!(result.is_ok(), "{:?}", result);
+
+    // Without #[lua(constructor)] the type table is not callable
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+    vm.register_type_of::<Point>("Point").unwrap();
+    let result = vm.main_state().execute(
+        r#"
+        assert(getmetatable(Point) == nil)
+        assert(not pcall(Point, 1, 2))
+    "#,
+    );
+    assert!(result.is_ok(), "{:?}", result);
+}
+
+// ==================== Type registry ====================
+
+fn plugin_types() -> TypeRegistry {
+    let mut geometry = TypeRegistry::new();
+    geometry
+        .add::<Point>("geometry", "Point")
+        .unwrap()
+        .add::<Calculator>("geometry", "Calculator")
+        .unwrap();
+    let mut config = TypeRegistry::new();
+    config
+        .add_with::<AppConfig>(
+            "config",
+            "AppConfig",
+            TypeOptions::new().on_set(|_, _, _, _, _| Ok(SetAction::Skip)),
+        )
+        .unwrap();
+
+    let mut types = TypeRegistry::new();
+    types.merge(&geometry).unwrap();
+    types.merge(&config).unwrap();
+    types
+}
+
+#[test]
+fn test_type_registry_installs_every_type() {
+    let types = plugin_types();
+    assert_eq!(types.len(), 3);
+    let names: Vec<(&str, &str)> = types.entries().map(|e| (e.source(), e.name())).collect();
+    assert_eq!(
+        names,
+        [
+            ("geometry", "Point"),
+            ("geometry", "Calculator"),
+            ("config", "AppConfig")
+        ]
+    );
+    assert!(types.get("AppConfig").unwrap().has_observers());
+    assert!(!types.get("Point").unwrap().has_observers());
+
+    // The same registry serves any number of VMs
+    for _ in 0..2 {
+        let mut vm = GlobalState::new(SafeOption::default());
+        vm.open_stdlib(Stdlib::All).unwrap();
+        vm.register_types(&types).unwrap();
+        let result = vm.main_state().execute(
+            r#"
+            assert(Point.new(3, 4):distance() == 5)
+            assert(Calculator(41).value == 41)
+            local cfg = AppConfig.new()
+            cfg.level = 9
+            assert(cfg.level == 1)
+        "#,
+        );
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    let mut lua = Lua::new_with_types(SafeOption::default(), &types).unwrap();
+    lua.open_stdlib(Stdlib::All).unwrap();
+    lua.load("assert(Calculator.zero().value == 0)")
+        .exec()
+        .unwrap();
+}
+
+#[test]
+fn test_type_registry_conflicts_name_both_sources() {
+    let mut types = plugin_types();
+
+    let mut other = TypeRegistry::new();
+    other.add::<Vec2>("physics", "Point").unwrap();
+    let err = types.merge(&other).unwrap_err();
+    assert_eq!(err.existing.source, "geometry");
+    assert_eq!(err.added.source, "physics");
+    let msg = err.to_string();
+    assert!(msg.contains("'Point'"), "{msg}");
+    assert!(
+        msg.contains("by 'geometry'") && msg.contains("by 'physics'"),
+        "{msg}"
+    );
+
+    // The same Rust type under a second name is a conflict too
+    let err = types.add::<Point>("physics", "Vector").unwrap_err();
+    assert_eq!(err.existing.name, "Point");
+    let msg = err.to_string();
+    assert!(msg.contains("'Point' by 'geometry'"), "{msg}");
+    assert!(msg.contains("'Vector' by 'physics'"), "{msg}");
+
+    assert_eq!(types.len(), 3);
+}
+
+#[test]
+fn test_userdata_tostring_identity() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(stdlib::Stdlib::All).unwrap();
+    let state = vm.main_state();
+    for (name, id) in [("a", 1), ("b", 2)] {
+        let ud = state
+            .create_userdata(LuaUserdata::new(SimpleHandle { id }))
+            .unwrap();
+        state.set_global_value(name, ud).unwrap();
+    }
+    let result = state.execute(
+        r#"
+        local sa, sb = tostring(a), tostring(b)
+        assert(sa == tostring(a) and sb == tostring(b))
+        assert(sa ~= sb, sa)
+        assert(sa:match("(0x%x+)$") == string.format("%p", a), sa)
+        collectgarbage()
+        assert(tostring(a) == sa and tostring(b) == sb)
+    "#,
+    );
+    assert!(result.is_ok(), "userdata identity: {:?}", result);
+}
+
+/// Array-like userdata answering integer keys in `1..=len` through the
+/// trait; everything else falls through to the metatable
+struct Cells {
+    values: Vec<i64>,
+}
+
+impl UserDataTrait for Cells {
+    fn type_name(&self) -> &'static str {
+        "Cells"
+    }
+
+    fn get_index(&self, idx: i64) -> Option<UdValue> {
+        let i = usize::try_from(idx).ok()?.checked_sub(1)?;
+        self.values.get(i).map(|v| UdValue::Integer(*v))
+    }
+
+    fn set_index(&mut self, idx: i64, value: UdValue) -> Option<Result<(), String>> {
+        let i = usize::try_from(idx).ok()?.checked_sub(1)?;
+        let slot = self.values.get_mut(i)?;
+        Some(match value {
+            UdValue::Integer(v) => {
+                *slot = v;
+                Ok(())
+            }
+            _ => Err("Cells only hold integers".to_string()),
+        })
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn test_userdata_index_keys_keep_their_type() {
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(Stdlib::All).unwrap();
+    let state = vm.main_state();
+    let cells = state
+        .create_userdata(LuaUserdata::new(Cells {
+            values: vec![10, 20, 30],
+        }))
+        .unwrap();
+    state.set_global_value("cells", cells).unwrap();
+
+    let result = vm.main_state().execute(
+        r#"
+        local seen = {}
+        local function kind(k) return math.type(k) or type(k) end
+        debug.setmetatable(cells, {
+            __index = function(u, k)
+                assert(u == cells)
+                seen[#seen + 1] = "get " .. kind(k) .. " " .. tostring(k)
+                return k
+            end,
+            __newindex = function(_, k, v)
+                seen[#seen + 1] = "set " .. kind(k) .. " " .. tostring(k)
+            end,
+        })
+
+        -- integer keys reach get_index/set_index whatever the opcode
+        local i, f = 2, 3.0
+        assert(cells[1] == 10 and cells[i] == 20 and cells[3] == 30)
+        cells[1] = 11; cells[i] = 22
+        assert(cells[1] == 11 and cells[2] == 22)
+        assert(#seen == 0)
+
+        -- misses go to the metamethods with the original key value
+        local n, s = 9, "name"
+        assert(cells[4] == 4)        -- GETI
+        assert(cells[n] == 9)        -- GETTABLE, integer register
+        assert(cells[f] == 3.0)      -- GETTABLE, float register
+        assert(cells.name == "name") -- GETFIELD
+        assert(cells[s] == "name")   -- GETTABLE, string register
+        cells[4] = 1                 -- SETI
+        cells[n] = 1                 -- SETTABLE
+        cells.name = 1               -- SETFIELD
+        assert(table.concat(seen, ",") == table.concat({
+            "get integer 4", "get integer 9", "get float 3.0", "get string name",
+            "get string name", "set integer 4", "set integer 9", "set string name",
+        }, ","), table.concat(seen, ","))
+
+        local ok, err = pcall(function() cells[1] = "x" end)
+        assert(not ok and err:find("Cells only hold integers"), err)
+    "#,
+    );
+    if let Err(e) = result {
+        panic!("{}", vm.main_state().get_error_msg(e));
+    }
+}
+
+#[test]
+fn test_shared_userdata_aliases_one_rust_value() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(stdlib::Stdlib::All).unwrap();
+    let point = Rc::new(RefCell::new(Point::new(1.0, 2.0)));
+
+    let state = vm.main_state();
+    let a = state.create_userdata_shared(point.clone()).unwrap();
+    state.set_global_value("a", a).unwrap();
+    let b = state.create_userdata_shared(point.clone()).unwrap();
+    state.set_global_value("b", b).unwrap();
+    assert_eq!(Rc::strong_count(&point), 3);
+
+    let results = vm
+        .main_state()
+        .execute(
+            r#"
+            a.x = 5
+            a:translate(1, 1)
+            return b.x, b.y, b:distance() > 0, a == b, rawequal(a, b), tostring(b)
+            "#,
+        )
+        .unwrap();
+    assert_eq!(results[0].as_number(), Some(6.0));
+    assert_eq!(results[1].as_number(), Some(3.0));
+    assert_eq!(results[2].as_boolean(), Some(true));
+    assert_eq!(results[3].as_boolean(), Some(true));
+    assert_eq!(results[4].as_boolean(), Some(false));
+    assert_eq!(results[5].as_str(), Some("Point(6, 3)"));
+    assert_eq!(point.borrow().x, 6.0);
+
+    // Rust-side writes show through both values
+    point.borrow_mut().y = 10.0;
+    let results = vm.main_state().execute("return a.y, b.y").unwrap();
+    assert_eq!(results[0].as_number(), Some(10.0));
+    assert_eq!(results[1].as_number(), Some(10.0));
+
+    // Another handle with equal contents is a different object
+    let other = Rc::new(RefCell::new(Point::new(6.0, 10.0)));
+    let c = vm.main_state().create_userdata_shared(other).unwrap();
+    vm.main_state().set_global_value("c", c).unwrap();
+    let results = vm.main_state().execute("return a == c").unwrap();
+    assert_eq!(results[0].as_boolean(), Some(false));
+
+    // Dropping the Lua values releases their handles
+    vm.main_state()
+        .execute("a, b, c = nil, nil, nil; collectgarbage()")
+        .unwrap();
+    assert_eq!(Rc::strong_count(&point), 1);
+}
+
+#[test]
+fn test_shared_userdata_reports_borrow_conflicts() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(stdlib::Stdlib::All).unwrap();
+    let point = Rc::new(RefCell::new(Point::new(1.0, 2.0)));
+    let state = vm.main_state();
+    let p = state.create_userdata_shared(point.clone()).unwrap();
+    state.set_global_value("p", p).unwrap();
+
+    let check = r#"
+        local ok1, e1 = pcall(function() return p.x end)
+        local ok2, e2 = pcall(function() p.x = 3 end)
+        local ok3, e3 = pcall(function() return p:distance() end)
+        local ok4, e4 = pcall(function() p:translate(1, 1) end)
+        return ok1, e1, ok2, e2, ok3, e3, ok4, e4
+    "#;
+
+    // The host holds the value mutably: every access conflicts
+    let guard = point.borrow_mut();
+    let results = vm.main_state().execute(check).unwrap();
+    for pair in results.chunks(2) {
+        assert_eq!(pair[0].as_boolean(), Some(false));
+        let msg = pair[1].as_str().unwrap();
+        assert!(msg.contains("value is currently borrowed"), "{msg}");
+    }
+    drop(guard);
+
+    // The host only reads: Lua may read too, but not write
+    let guard = point.borrow();
+    let results = vm.main_state().execute(check).unwrap();
+    assert_eq!(results[0].as_boolean(), Some(true));
+    assert_eq!(results[2].as_boolean(), Some(false));
+    assert!(
+        results[3]
+            .as_str()
+            .unwrap()
+            .contains("value is currently borrowed")
+    );
+    assert_eq!(results[4].as_boolean(), Some(true));
+    assert_eq!(results[6].as_boolean(), Some(false));
+    assert!(
+        results[7]
+            .as_str()
+            .unwrap()
+            .contains("value is currently borrowed")
+    );
+    drop(guard);
+
+    let results = vm.main_state().execute(check).unwrap();
+    assert!(
+        results
+            .iter()
+            .step_by(2)
+            .all(|ok| ok.as_boolean() == Some(true))
+    );
+    assert_eq!(point.borrow().x, 4.0);
+}
+
+#[test]
+fn test_shared_userdata_sub_references_check_borrows() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut vm = GlobalState::new(SafeOption::default());
+    vm.open_stdlib(stdlib::Stdlib::All).unwrap();
+    let entity = Rc::new(RefCell::new(Entity::new("hero".into(), 1.0, 2.0, 10)));
+    let state = vm.main_state();
+    let e = state.create_userdata_shared(entity.clone()).unwrap();
+    state.set_global_value("e", e).unwrap();
+    vm.main_state()
+        .execute("pos = e.pos; mpos = e:get_pos_mut()")
+        .unwrap();
+
+    let check = r#"
+        local ok1, e1 = pcall(function() return pos.x end)
+        local ok2, e2 = pcall(function() pos.x = 7 end)
+        local ok3, e3 = pcall(function() mpos:translate(1, 1) end)
+        return ok1, e1, ok2, e2, ok3, e3
+    "#;
+
+    // The host holds the value mutably: sub-references conflict as well
+    let guard = entity.borrow_mut();
+    let results = vm.main_state().execute(check).unwrap();
+    for pair in results.chunks(2) {
+        assert_eq!(pair[0].as_boolean(), Some(false));
+        let msg = pair[1].as_str().unwrap();
+        assert!(msg.contains("value is currently borrowed"), "{msg}");
+    }
+    drop(guard);
+
+    // The host only reads: reading through them works, writing does not
+    let guard = entity.borrow();
+    let results = vm.main_state().execute(check).unwrap();
+    assert_eq!(results[0].as_boolean(), Some(true));
+    assert_eq!(results[1].as_number(), Some(1.0));
+    assert_eq!(results[2].as_boolean(), Some(false));
+    assert_eq!(results[4].as_boolean(), Some(false));
+    assert!(
+        results[5]
+            .as_str()
+            .unwrap()
+            .contains("value is currently borrowed")
+    );
+    drop(guard);
+
+    let results = vm.main_state().execute(check).unwrap();
+    assert!(
+        results
+            .iter()
+            .step_by(2)
+            .all(|ok| ok.as_boolean() == Some(true))
+    );
+    assert_eq!(entity.borrow().pos.x, 8.0);
+    assert_eq!(entity.borrow().pos.y, 3.0);
+}
@@ -0,0 +1,92 @@
+//! Heap allocations made by hot library calls, counted by a global
+//! allocator. Calls that only return pre-interned constant strings (type
+//! names, "nil"/"true"/"false", coroutine states) or strings that already
+//! exist (a `__tostring` result) must not allocate at all.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use luars::{Lua, LuaApi, LuaFunction, SafeOption, Stdlib};
+
+struct CountingAlloc;
+
+thread_local! {
+    // Per thread, so tests running in parallel do not see each other's
+    // allocations.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const ITERATIONS: i64 = 10_000;
+
+/// Allocations made by calling `body` (a function of the loop counter)
+/// `ITERATIONS` times, after one warm-up run.
+fn allocations_in_loop(setup: &str, body: &str) -> usize {
+    let mut lua = Lua::new(SafeOption::default());
+    lua.open_stdlib(Stdlib::All).unwrap();
+    let run: LuaFunction = lua
+        .load(&format!(
+            "{setup}\nreturn function(n) local r for i = 1, n do r = {body} end return r end"
+        ))
+        .eval()
+        .unwrap();
+    run.call::<_, ()>(ITERATIONS).unwrap();
+    lua.gc_stop();
+    let before = ALLOCATIONS.with(Cell::get);
+    run.call::<_, ()>(ITERATIONS).unwrap();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn constant_strings_do_not_allocate() {
+    let cases = [
+        ("local x = {}", "type(x)"),
+        ("local x = nil", "type(x)"),
+        ("local x = print", "type(x)"),
+        ("local x = 1.5", "math.type(x)"),
+        ("local x = nil", "tostring(x)"),
+        ("", "tostring(i % 2 == 0)"),
+        ("local s = 'already a string'", "tostring(s)"),
+        ("local co = coroutine.create(print)", "coroutine.status(co)"),
+        (
+            "local t = setmetatable({}, {__tostring = function() return 'obj' end})",
+            "tostring(t)",
+        ),
+        (
+            "local t = setmetatable({}, {__metatable = 'locked'})",
+            "getmetatable(t)",
+        ),
+    ];
+    // Calling into the loop itself allocates a fixed couple of times
+    let baseline = allocations_in_loop("", "i");
+    for (setup, body) in cases {
+        let allocations = allocations_in_loop(setup, body);
+        assert!(
+            allocations <= baseline,
+            "{body}: {allocations} allocations over {ITERATIONS} calls"
+        );
+    }
+}
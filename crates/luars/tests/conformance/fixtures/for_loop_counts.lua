@@ -0,0 +1,55 @@
+-- Numeric for loops: iteration counts and final control values must match
+-- lvm.c exactly, including float accumulation, NaN and infinite operands.
+
+local inf, nan = math.huge, 0/0
+
+local function run(init, limit, step)
+  local n, last = 0, nil
+  for x = init, limit, step do
+    n = n + 1
+    last = x
+    if n > 100 then break end
+  end
+  local shown = last == nil and "-" or string.format("%.17g", last)
+  print(tostring(init), tostring(limit), tostring(step), n, shown, math.type(last))
+end
+
+local cases = {
+  -- float accumulation drift
+  {1, 2, 0.1}, {0, 1, 0.1}, {1, 0, -0.1}, {0.1, 1, 0.1}, {0, 0.3, 0.1},
+  {1, 2, 1/3}, {-1, 1, 0.2}, {1, -1, -0.2}, {0, 1e-9, 1e-10}, {3, 1, -0.7},
+  {0.1, 0.35, 0.05}, {1e15, 1e15 + 10, 2.5}, {2^53, 2^53 + 4, 1.0},
+  -- exact float steps and mixed integer/float operands
+  {1, 3, 0.5}, {1.5, 3, 1}, {3, 1.5, -1}, {1, 3.5, 1}, {3, 0.5, -1},
+  {0.0, 10, 3}, {1, 1, 0.5}, {1, 1, -0.5}, {-0.0, 0.0, 1}, {1.0, 3, 1},
+  -- NaN operands
+  {1, nan, 1}, {1, nan, -1}, {1.0, nan, 1}, {1.0, nan, -1}, {nan, 1, 1},
+  {nan, 1, -1}, {1, 10, nan}, {10, 1, nan},
+  -- infinite operands
+  {1, inf, 1e308}, {1, -inf, -1e308}, {1, 2, inf}, {1, 2, -inf}, {2, 1, -inf},
+  {-inf, 0, 1e308}, {1, inf, 1}, {1, -inf, -1}, {1, inf, -1}, {1, -inf, 1},
+  {inf, inf, 1}, {-inf, -inf, -1.0},
+  -- limits beyond the integer range
+  {math.maxinteger - 2, inf, 1}, {math.mininteger + 2, -inf, -1},
+  {1, math.maxinteger + 0.0, 2^62}, {1, 2^63, 2^62}, {1, -2^63, -1},
+  {math.maxinteger, math.maxinteger, 1.0}, {1, 2, 2^-1074},
+  -- numeric strings
+  {"1", 2, 0.25}, {1, "2", 0.5}, {1, 3, "0.5"},
+}
+
+for _, c in ipairs(cases) do
+  run(c[1], c[2], c[3])
+end
+
+-- Constant operands go through the same opcodes
+local n = 0
+for _ = 1, 0/0 do n = n + 1 end
+for _ = 1.0, 0/0 do n = n + 10 end
+for _ = 1, 2, 1/0 do n = n + 100 end
+print(n)
+
+print(pcall(run, 1, 2, 0.0))
+print(pcall(run, 1.0, 2, 0))
+print(pcall(run, 1, 2, -0.0))
+print(pcall(run, 1, {}, 1))
+print(pcall(run, 1, 2, "x"))
@@ -0,0 +1,7 @@
+-- string.format("%c") must emit the raw byte, like string.char.
+-- (strings.lua: the "\0%c\0%c%x\0" check is skipped upstream here)
+
+local s = string.format("%c", 228)
+print(#s, s:byte(1, -1))
+print(s == string.char(228))
+print(string.format("\0%c\0%c%x\0", 0xe4, string.byte("b"), 140) == "\0\xe4\0b8c\0")
@@ -0,0 +1,8 @@
+-- string.format("%q") output must match lstrlib's addliteral byte for byte.
+
+-- newlines are escaped as a backslash followed by a real newline
+print(string.format("%q", "a\nb"))
+-- floats use hexadecimal notation so they read back exactly
+print(string.format("%q", 0.5), string.format("%q", 1.0))
+-- mininteger has no decimal literal, so it is written in hex
+print(string.format("%q", math.mininteger))
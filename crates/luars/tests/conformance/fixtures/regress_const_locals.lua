@@ -0,0 +1,8 @@
+-- Assigning to a <const> local is a compile error; shadowing is not.
+
+print(load("local x <const> = 1; x = 2"))
+print(load("local x <const> = 1; local function f() x = 2 end"))
+
+local x <const> = 10
+do local x = 20; x = x + 1; print(x) end
+print(x)
@@ -0,0 +1,8 @@
+-- Table constructors evaluate each key before its value, in source order.
+
+local log = {}
+local function v(x) log[#log + 1] = x; return x end
+
+local t = { [v("k1")] = v("v1"), v("p1"), [v("k2")] = v("v2"), v("p2") }
+print(table.concat(log, " "))
+print(t.k1, t.k2, t[1], t[2])
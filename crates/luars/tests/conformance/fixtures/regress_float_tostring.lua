@@ -0,0 +1,7 @@
+-- Floats use the shortest of %.15g/%.17g that reads back, plus ".0" when integral.
+
+print(1.0, -0.0, 0.1, 1/3, 100.0 * 3)
+print(1e15, 1e16, 2^53, 2^63, -2^63)
+print(1/0, -1/0, 3.14159265358979)
+print(123456789012.0, 1e100, 5e-324 > 0)
+print(tostring(1e300 * 10), 7 // 2.0, 2^-1074 == 5e-324)
@@ -0,0 +1,17 @@
+-- ipairs goes through __index and stops at the first nil.
+
+local backing = {10, 20, 30}
+local proxy = setmetatable({}, { __index = backing })
+for i, v in ipairs(proxy) do print(i, v) end
+
+local evens = setmetatable({}, {
+  __index = function(_, k) if k <= 3 then return k * 2 end end,
+})
+local out = {}
+for _, v in ipairs(evens) do out[#out + 1] = v end
+print(table.concat(out, ","))
+
+local holes = setmetatable({1, nil, 3}, { __index = {} })
+local count = 0
+for _ in ipairs(holes) do count = count + 1 end
+print(count)
@@ -0,0 +1,15 @@
+-- All right-hand sides are evaluated before any store happens.
+
+local a, b = 1, 2
+a, b = b, a
+print(a, b)
+
+local t = {}
+local i = 1
+i, t[i] = i + 1, 20
+print(i, t[1], t[2])
+
+local x, y, z = (function() return 1, 2, 3 end)()
+print(x, y, z)
+local p, q, r = 1, (function() return 2, 3 end)()
+print(p, q, r)
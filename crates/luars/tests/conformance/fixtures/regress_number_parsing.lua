@@ -0,0 +1,28 @@
+-- One str2number grammar for tonumber, string coercion, the lexer and %d:
+-- hex floats, signs and whitespace are numerals; '_', inf/nan and trailing
+-- garbage are not.
+
+local function show(s)
+  local n = tonumber(s)
+  print(string.format("[%s]", s), math.type(n), n)
+end
+
+for _, s in ipairs{"0x1p4", "0X.8", "  0x10  ", "0x1P-2", "0xA.8p1", "-0x10",
+                   "+0x10", "0xffffffffffffffff", "+1e5", "5.", ".5", "1.5E2",
+                   "9223372036854775807", "-9223372036854775808", "1e500"} do
+  show(s)
+end
+
+for _, s in ipairs{"", "inf", "nan", "1_000", "0x1_0", "1e", "0x", "0x1p",
+                   ".", "- 1", "1 x", "1.2.3", "0b101"} do
+  show(s)
+end
+
+print(math.type(tonumber("9223372036854775808")), tonumber("-0x8000000000000000"))
+print("0x1p4" + 0, "1.0" + 1, -"2.0", "10" // "3", " 0x.8 " * 2)
+print(0x1p-2, 0x.8, 0xA.8p1, 0xep1, 3., .5, 1e2)
+print(string.format("%d %x %.1f", "0x10", " 255.0 ", "0x1p-1"))
+print(math.tointeger("0x10"), math.tointeger("8.0"), math.tointeger("8.5"))
+print(select(2, load("return 0x1_0")))
+print(select(2, load("return 3.4.5")))
+print(select(2, load("return 1e+")))
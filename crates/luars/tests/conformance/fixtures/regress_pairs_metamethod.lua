@@ -0,0 +1,15 @@
+-- __pairs results are truncated/padded to exactly the for-in triple.
+
+local t = setmetatable({}, {
+  __pairs = function()
+    local i = 0
+    return function(_, k)
+      i = i + 1
+      if i <= 2 then return i, i * 100 end
+    end
+  end,
+})
+for k, v in pairs(t) do print(k, v) end
+
+local f, s, c = pairs(setmetatable({}, { __pairs = function() return next end }))
+print(f == next, s, c)
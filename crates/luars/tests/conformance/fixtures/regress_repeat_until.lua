@@ -0,0 +1,25 @@
+-- The until condition sees the body's locals, including captured ones.
+
+local n = 0
+repeat
+  n = n + 1
+  local done = n >= 3
+until done
+print(n)
+
+local fs = {}
+local k = 0
+repeat
+  k = k + 1
+  local captured = k * 10
+  fs[#fs + 1] = function() return captured end
+until captured >= 30
+print(#fs, fs[1](), fs[2](), fs[3]())
+
+k = 0
+repeat
+  k = k + 1
+  if k == 2 then break end
+  local unused = k
+until unused > 5
+print(k)
@@ -0,0 +1,123 @@
+-- OP_RETURN in all its forms: fixed counts (B > 0), everything up to top
+-- after a multi-result call (B == 0), the k flag closing upvalues and
+-- to-be-closed variables, and vararg frames whose extra arguments sit
+-- below the base. Callers want 0, 1, 2 or all results (MULTRET).
+
+local function show(...)
+  local n = select("#", ...)
+  local t = {}
+  for i = 1, n do t[i] = tostring((select(i, ...))) end
+  return n .. ":" .. table.concat(t, ",")
+end
+
+local function many(n)
+  local t = {}
+  for i = 1, n do t[i] = i end
+  return table.unpack(t, 1, n)
+end
+
+local fixed = {
+  r0 = function() return end,
+  r1 = function() return 1 end,
+  r2 = function() return 1, 2 end,
+  r3 = function() return 1, nil, 3 end,
+  rcall = function() return many(5) end,
+  rmixed = function() return 0, many(3) end,
+  rparen = function() return (many(3)) end,
+  rnil = function() return nil end,
+}
+
+local vararg = {
+  r0 = function(...) return end,
+  r1 = function(...) return ... end,
+  r2 = function(a, ...) return a, ... end,
+  rsel = function(...) return select(2, ...) end,
+  rcall = function(...) return many(select("#", ...)) end,
+  rmixed = function(a, ...) return ..., a end,
+  rtable = function(...) local t = {...}; return #t, ... end,
+}
+
+local names = {}
+for k in pairs(fixed) do names[#names + 1] = k end
+table.sort(names)
+for _, k in ipairs(names) do
+  local f = fixed[k]
+  local a = f()
+  local b, c = f()
+  print("fixed", k, show(f()), tostring(a), tostring(b) .. "/" .. tostring(c), show((f())), show(f(), "x"))
+end
+
+names = {}
+for k in pairs(vararg) do names[#names + 1] = k end
+table.sort(names)
+for _, k in ipairs(names) do
+  local f = vararg[k]
+  for _, args in ipairs({{n = 0}, {1, n = 1}, {1, 2, n = 2}, {1, nil, 3, nil, n = 4}, {1, 2, 3, 4, 5, 6, 7, n = 7}}) do
+    local a = f(table.unpack(args, 1, args.n))
+    local b, c = f(table.unpack(args, 1, args.n))
+    print("vararg", k, args.n, show(f(table.unpack(args, 1, args.n))), tostring(a), tostring(b) .. "/" .. tostring(c))
+  end
+end
+
+-- many results through several call depths, and into table constructors
+local function depth(n, ...)
+  if n == 0 then return ... end
+  return depth(n - 1, ...)
+end
+local function depth_nontail(n, ...)
+  if n == 0 then return ... end
+  local r = {depth_nontail(n - 1, ...)}
+  return table.unpack(r, 1, select("#", ...))
+end
+print(show(depth(10, 1, 2, 3)), show(depth_nontail(10, 1, nil, 3)))
+print(select("#", many(250)), (select(250, many(250))))
+print(#{many(100)}, #{many(100), "x"}, #{"x", many(100)})
+
+-- k flag: a returned local captured by a closure, and to-be-closed
+-- variables closed after the results are computed
+local function counter()
+  local n = 0
+  local function inc() n = n + 1; return n end
+  return inc, inc(), n
+end
+local inc, first, seen = counter()
+print(first, seen, inc(), inc())
+
+local log = {}
+local function closer(name)
+  return setmetatable({}, {__close = function() log[#log + 1] = name end})
+end
+local function with_tbc(...)
+  local a <close> = closer("a")
+  local b <close> = closer("b")
+  return select("#", ...), ...
+end
+print(show(with_tbc(1, 2, 3)), table.concat(log, ","))
+
+log = {}
+local function tbc_many()
+  local x <close> = closer("x")
+  return many(5)
+end
+print(show(tbc_many()), table.concat(log, ","))
+
+log = {}
+local function tbc_vararg(...)
+  local x <close> = closer("v")
+  local captured = ...
+  local get = function() return captured end
+  return get, ...
+end
+local get, r1, r2 = tbc_vararg("p", "q")
+print(get(), r1, r2, table.concat(log, ","))
+
+-- returns from methods, metamethods and pcall
+local obj = {v = 7}
+function obj:get(...) return self.v, ... end
+print(show(obj:get()), show(obj:get(1, 2)))
+local mt = {__call = function(self, ...) return "called", ... end,
+            __index = function(t, k) return k, "ignored" end}
+local callable = setmetatable({}, mt)
+print(show(callable(1, 2)), show(callable.key))
+print(show(pcall(many, 4)), show(pcall(function(...) return ... end)))
+print(show(coroutine.wrap(function(...) return ... end)(1, nil, nil)))
@@ -0,0 +1,55 @@
+-- String ordering is byte-wise (l_strcmp in the C locale): uppercase sorts
+-- before lowercase, NUL is an ordinary byte, and a proper prefix sorts first.
+-- Two strings never consult __lt/__le, even when the string metatable has them.
+
+local function show(s)
+  return '"' .. s:gsub("[^%w ]", function(c) return string.format("\\%03d", c:byte()) end) .. '"'
+end
+
+local pairs_ = {
+  {"a", "b"}, {"Z", "a"}, {"a", "Z"}, {"A", "a"}, {"abc", "abd"},
+  {"", "a"}, {"", ""}, {"a", "a"}, {"ab", "a"}, {"a", "ab"},
+  {"a\0b", "a\0c"}, {"a\0", "a"}, {"a", "a\0"}, {"\0", ""}, {"\0\0", "\0\1"},
+  {"\127", "\128"}, {"\200", "\100"}, {"\255", "\254\255"}, {"é", "z"},
+  {"10", "9"}, {"-1", "1"}, {" ", "\t"},
+}
+
+for _, p in ipairs(pairs_) do
+  local a, b = p[1], p[2]
+  print(show(a), show(b), a < b, a <= b, a > b, a >= b)
+end
+
+-- strings differing only after a long shared prefix
+local prefix = string.rep("x", 1024)
+local s1, s2 = prefix .. "a", prefix .. "b"
+print(s1 < s2, s2 < s1, s1 <= s1, prefix < s1, s1 < prefix)
+print(prefix .. "\0" < prefix .. "\1", prefix .. "\0" > prefix)
+
+-- a string compared with itself, short and long
+local long = string.rep("long string ", 10)
+print(long < long, long <= long, ("x"):rep(3) <= "xxx")
+
+-- metamethods on the string metatable are not consulted for two strings
+local mt = getmetatable("")
+mt.__lt = function() error("__lt called") end
+mt.__le = function() error("__le called") end
+print(pcall(function() return "a" < "b", "b" <= "a" end))
+mt.__lt, mt.__le = nil, nil
+
+-- mixing a string with a number is still an error
+print(pcall(function() return "1" < 2 end))
+print(pcall(function() return 1 <= "2" end))
+
+-- table.sort with and without a comparator agrees with the operators
+local words = {"banana", "Apple", "apple", "", "a\0z", "a", "Banana", "a\0", "\255", "0"}
+local sorted = {table.unpack(words)}
+table.sort(sorted)
+local out = {}
+for i, w in ipairs(sorted) do out[i] = show(w) end
+print(table.concat(out, " "))
+
+local desc = {table.unpack(words)}
+table.sort(desc, function(a, b) return a > b end)
+out = {}
+for i, w in ipairs(desc) do out[i] = show(w) end
+print(table.concat(out, " "))
@@ -0,0 +1,167 @@
+//! Conformance harness: runs every `fixtures/*.lua` in a fresh VM and compares
+//! what it prints against the matching `.expected` file.
+//!
+//! Fixtures are adapted chunks of the official test suite plus one regression
+//! file per fixed bug. Files listed in `known_failures.txt` are expected to
+//! fail; the test fails when that list is out of date in either direction,
+//! so fixing a bug means deleting its entry and a regression can't hide.
+//!
+//! Regenerate an expectation with the reference interpreter:
+//!
+//! ```text
+//! lua5.5 fixtures/foo.lua > fixtures/foo.expected
+//! ```
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use luars::{Lua, LuaApi, SafeOption, Stdlib};
+
+/// Per-file limits. The count hook installed before each fixture fires every
+/// `HOOK_INTERVAL` instructions and aborts the chunk once either budget is
+/// spent; the memory limit is the VM's own.
+const INSTRUCTION_BUDGET: u64 = 200_000_000;
+const HOOK_INTERVAL: u64 = 1000;
+const TIME_LIMIT_SECS: f64 = 20.0;
+const MEMORY_LIMIT: isize = 256 * 1024 * 1024;
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/fixtures")
+}
+
+fn run_fixture(path: &Path) -> Result<String, String> {
+    let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+
+    let mut option = SafeOption::default();
+    option.max_memory_limit = MEMORY_LIMIT;
+    let mut lua = Lua::new(option);
+    lua.open_stdlib(Stdlib::All).map_err(|e| format!("{e:?}"))?;
+
+    let output = Rc::new(RefCell::new(String::new()));
+    let sink = output.clone();
+    lua.register_function("__capture", move |s: String| {
+        sink.borrow_mut().push_str(&s);
+    })
+    .map_err(|e| format!("{e:?}"))?;
+
+    let setup = format!(
+        r#"
+        local capture = __capture
+        __capture = nil
+        local concat, select, tostring, type = table.concat, select, tostring, type
+
+        function print(...)
+            local n = select('#', ...)
+            local parts = {{}}
+            for i = 1, n do parts[i] = tostring((select(i, ...))) end
+            capture(concat(parts, "\t", 1, n) .. "\n")
+        end
+
+        local stdout = io.stdout
+        function io.write(...)
+            for i = 1, select('#', ...) do
+                local v = select(i, ...)
+                capture(type(v) == "number" and tostring(v) or v)
+            end
+            return stdout
+        end
+
+        local clock, start, ticks = os.clock, os.clock(), 0
+        debug.sethook(function()
+            ticks = ticks + 1
+            if ticks > {max_ticks} then error("instruction budget exhausted", 0) end
+            if clock() - start > {limit} then error("time limit exceeded", 0) end
+        end, "", {interval})
+        "#,
+        max_ticks = INSTRUCTION_BUDGET / HOOK_INTERVAL,
+        limit = TIME_LIMIT_SECS,
+        interval = HOOK_INTERVAL,
+    );
+    lua.execute(&setup)
+        .map_err(|e| lua.get_error_message(e).to_string())?;
+
+    let result = lua.load(&source).set_name(format!("@{name}")).exec();
+    let captured = output.borrow().clone();
+    match result {
+        Ok(()) => Ok(captured),
+        Err(e) => Err(format!(
+            "{}\n--- output before the error ---\n{captured}",
+            lua.get_error_message(e)
+        )),
+    }
+}
+
+fn check_fixture(path: &Path) -> Result<(), String> {
+    let expected_path = path.with_extension("expected");
+    let expected = fs::read_to_string(&expected_path)
+        .map_err(|e| format!("cannot read {}: {e}", expected_path.display()))?;
+    let actual = run_fixture(path)?;
+    if actual == expected {
+        return Ok(());
+    }
+    let line = actual
+        .lines()
+        .zip(expected.lines())
+        .position(|(a, e)| a != e)
+        .unwrap_or_else(|| actual.lines().count().min(expected.lines().count()));
+    Err(format!(
+        "output differs at line {}\n--- expected ---\n{expected}--- actual ---\n{actual}",
+        line + 1
+    ))
+}
+
+fn known_failures() -> BTreeSet<String> {
+    let list = fs::read_to_string(fixtures_dir().join("../known_failures.txt"))
+        .expect("cannot read known_failures.txt");
+    list.lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn conformance() {
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(fixtures_dir())
+        .expect("cannot read fixtures directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "no conformance fixtures found");
+
+    let known = known_failures();
+    let names: BTreeSet<String> = fixtures
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+
+    let mut problems = Vec::new();
+    for stale in known.difference(&names) {
+        problems.push(format!(
+            "{stale}: listed in known_failures.txt but no such fixture"
+        ));
+    }
+
+    for path in &fixtures {
+        let name = path.file_name().unwrap().to_string_lossy();
+        match (check_fixture(path), known.contains(name.as_ref())) {
+            (Ok(()), false) | (Err(_), true) => {}
+            (Ok(()), true) => problems.push(format!(
+                "{name}: now passes; remove it from known_failures.txt"
+            )),
+            (Err(msg), false) => problems.push(format!("{name}: {msg}")),
+        }
+    }
+
+    assert!(
+        problems.is_empty(),
+        "{} conformance problem(s):\n\n{}",
+        problems.len(),
+        problems.join("\n\n")
+    );
+}
@@ -0,0 +1,20 @@
+use luars::{LuaUserData, lua_methods};
+
+#[derive(LuaUserData)]
+struct Counter {
+    pub count: i64,
+}
+
+#[lua_methods]
+impl Counter {
+    pub fn get(&self) -> i64 {
+        self.count
+    }
+
+    #[lua(rename = "get")]
+    pub fn get_twice(&self) -> i64 {
+        self.count * 2
+    }
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use luars::{LuaUserData, lua_methods};
+
+#[derive(LuaUserData)]
+struct Counter {
+    pub count: i64,
+}
+
+#[lua_methods]
+impl Counter {
+    // `c.count` would always read the field, never this method
+    pub fn count(&self) -> i64 {
+        self.count
+    }
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use luars::{LuaUserData, lua_methods};
+
+#[derive(LuaUserData)]
+struct Counter {
+    #[lua(name = "total")]
+    pub count: i64,
+}
+
+#[lua_methods]
+impl Counter {
+    #[lua(rename = "total")]
+    pub fn sum(&self) -> i64 {
+        self.count
+    }
+}
+
+fn main() {}
@@ -0,0 +1,21 @@
+use luars::{LuaUserData, lua_methods};
+
+#[derive(LuaUserData)]
+struct Counter {
+    pub count: i64,
+}
+
+#[lua_methods]
+impl Counter {
+    #[lua(constructor)]
+    pub fn new() -> Self {
+        Counter { count: 0 }
+    }
+
+    #[lua(constructor)]
+    pub fn with(count: i64) -> Self {
+        Counter { count }
+    }
+}
+
+fn main() {}
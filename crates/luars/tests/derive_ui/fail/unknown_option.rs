@@ -0,0 +1,16 @@
+use luars::{LuaUserData, lua_methods};
+
+#[derive(LuaUserData)]
+struct Counter {
+    pub count: i64,
+}
+
+#[lua_methods]
+impl Counter {
+    #[lua(hidden)]
+    pub fn get(&self) -> i64 {
+        self.count
+    }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+// Compile-fail tests for the diagnostics of `#[derive(LuaUserData)]` and
+// `#[lua_methods]`. Regenerate the .stderr files with `TRYBUILD=overwrite`.
+
+#[test]
+fn derive_ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/derive_ui/fail/*.rs");
+}
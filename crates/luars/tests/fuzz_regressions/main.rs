@@ -0,0 +1,128 @@
+//! Inputs found by the fuzz targets in `fuzz/`, minimized and kept so the
+//! bugs they exposed stay fixed.
+//!
+//! `source/*.lua` are loaded as text and `bytecode/*.luac` as binary
+//! chunks; each must produce exactly the message (or `ok`) in its
+//! `.expected` file instead of panicking or running unchecked bytecode.
+//! Adding a regression means dropping the minimized input next to the
+//! others and recording what the reference behavior prints for it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use luars::{Lua, LuaApi, SafeOption, Stdlib};
+
+/// Instructions a loaded chunk may run before the count hook stops it.
+const INSTRUCTION_BUDGET: u64 = 1_000_000;
+const HOOK_INTERVAL: u64 = 1000;
+
+fn regressions_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fuzz_regressions")
+}
+
+fn new_lua() -> Lua {
+    let mut lua = Lua::new(
+        SafeOption::builder()
+            .allow_load_bytecode(true)
+            .build()
+            .unwrap(),
+    );
+    lua.open_stdlib(Stdlib::All).unwrap();
+    lua
+}
+
+/// Load `input` in `mode` and run it under an instruction budget. Returns
+/// `ok` or the message `load` or the call failed with.
+fn run_input(input: &[u8], mode: &str) -> Result<String, String> {
+    let mut lua = new_lua();
+    let vm = lua.global_state_mut();
+    let chunk = vm.create_bytes(input).map_err(|e| format!("{e:?}"))?;
+    vm.set_global("chunk", chunk)
+        .map_err(|e| format!("{e:?}"))?;
+
+    let script = format!(
+        r#"
+        local f, err = load(chunk, "=input", "{mode}")
+        if not f then return err end
+        local ticks = 0
+        debug.sethook(function()
+            ticks = ticks + 1
+            if ticks > {max_ticks} then error("instruction budget exhausted", 0) end
+        end, "", {interval})
+        local ok, msg = pcall(f)
+        debug.sethook()
+        return ok and "ok" or tostring(msg)
+        "#,
+        max_ticks = INSTRUCTION_BUDGET / HOOK_INTERVAL,
+        interval = HOOK_INTERVAL,
+    );
+    lua.load(&script)
+        .eval::<String>()
+        .map_err(|e| lua.get_error_message(e).to_string())
+}
+
+fn check_dir(subdir: &str, ext: &str, mode: &str, problems: &mut Vec<String>) {
+    let mut inputs: Vec<PathBuf> = fs::read_dir(regressions_dir().join(subdir))
+        .unwrap_or_else(|e| panic!("cannot read {subdir}: {e}"))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == ext))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "no regression inputs in {subdir}");
+
+    for path in &inputs {
+        let name = format!("{subdir}/{}", path.file_name().unwrap().to_string_lossy());
+        let expected_path = path.with_extension("expected");
+        let expected = match fs::read_to_string(&expected_path) {
+            Ok(s) => s,
+            Err(e) => {
+                problems.push(format!("{name}: cannot read .expected: {e}"));
+                continue;
+            }
+        };
+        let input = fs::read(path).unwrap();
+        match run_input(&input, mode) {
+            Ok(actual) if actual == expected.trim_end_matches('\n') => {}
+            Ok(actual) => problems.push(format!(
+                "{name}:\n--- expected ---\n{expected}--- actual ---\n{actual}"
+            )),
+            Err(msg) => problems.push(format!("{name}: harness error: {msg}")),
+        }
+    }
+}
+
+#[test]
+fn fuzz_regressions() {
+    let mut problems = Vec::new();
+    check_dir("source", "lua", "t", &mut problems);
+    check_dir("bytecode", "luac", "b", &mut problems);
+    assert!(
+        problems.is_empty(),
+        "{} fuzz regression(s):\n\n{}",
+        problems.len(),
+        problems.join("\n\n")
+    );
+}
+
+/// `load` gives extra upvalues of a binary chunk nil, but a file chunk is
+/// called with only `_ENV`, so `loadfile` refuses anything needing more.
+#[test]
+fn file_chunk_with_extra_upvalues_is_rejected() {
+    let mut lua = new_lua();
+    let msg = lua
+        .load(
+            r#"
+            local a, b = 1, 2
+            local path = os.tmpname()
+            local f = assert(io.open(path, "wb"))
+            f:write(string.dump(function() return a + b end))
+            f:close()
+            local fn, err = loadfile(path)
+            os.remove(path)
+            return fn and "loaded" or err
+            "#,
+        )
+        .eval::<String>()
+        .unwrap();
+    assert_eq!(msg, "main chunk must have at most one upvalue (_ENV)");
+}
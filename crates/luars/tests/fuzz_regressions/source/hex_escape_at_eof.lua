@@ -0,0 +1 @@
+x = '\x
\ No newline at end of file
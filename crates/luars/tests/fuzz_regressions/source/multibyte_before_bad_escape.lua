@@ -0,0 +1 @@
+x = "𤓓𤓓𤓓\u{10FFFF-- x"
@@ -1,5 +1,8 @@
+use luars::CompileOptions;
 use luars::Lua;
 use luars::LuaApi;
+use luars::LuaResult;
+use luars::LuaTable;
 use luars::LuaValue;
 use luars::SafeOption;
 use luars::Stdlib;
@@ -16,6 +19,7 @@ fn print_usage() {
     eprintln!("  -e stat   execute string 'stat'");
     eprintln!("  -i        enter interactive mode after executing 'script'");
     eprintln!("  -l mod    require library 'mod' into global 'mod'");
+    eprintln!("  -l g=mod  require library 'mod' into global 'g'");
     eprintln!("  -v        show version information");
     eprintln!("  -E        ignore environment variables");
     eprintln!("  -W        turn warnings on");
@@ -28,13 +32,18 @@ fn print_version() {
     println!("{}", COPYRIGHT);
 }
 
+/// A `-e` or `-l` option; they run in command-line order.
+enum RunArg {
+    Execute(String),
+    Require(String),
+}
+
 #[derive(Default)]
 struct Options {
-    execute_strings: Vec<String>,
+    run_args: Vec<RunArg>,
     interactive: bool,
     script_file: Option<String>,
     script_args: Vec<String>,
-    require_modules: Vec<String>,
     show_version: bool,
     read_stdin: bool,
     ignore_env: bool,
@@ -59,15 +68,46 @@ fn lua_main_stack_size() -> usize {
     }
 }
 
-fn default_safe_option() -> SafeOption {
-    SafeOption {
-        max_stack_size: env_usize("LUARS_MAX_STACK_SIZE").unwrap_or(1_000_000),
-        max_call_depth: env_usize("LUARS_MAX_CALL_DEPTH").unwrap_or(1024),
-        max_c_stack_depth: env_usize("LUARS_MAX_C_STACK_DEPTH").unwrap_or(200),
-        max_memory_limit: env_usize("LUARS_MAX_MEMORY_LIMIT")
-            .map(|value| value.min(isize::MAX as usize) as isize)
-            .unwrap_or(4096 * 1024 * 1024),
-        allow_load_bytecode: true,
+fn default_safe_option(ignore_env: bool) -> SafeOption {
+    // LUA_PATH_5_5 / LUA_PATH (and the cpath pair) seed package.path, unless -E
+    let env_path = |versioned: &str, plain: &str, default: &str| {
+        if ignore_env {
+            return None;
+        }
+        env::var(versioned)
+            .or_else(|_| env::var(plain))
+            .ok()
+            .map(|value| resolve_env_path(&value, default))
+    };
+    let mut option = SafeOption::default();
+    if let Some(slots) = env_usize("LUARS_MAX_STACK_SIZE") {
+        option.max_stack_size = slots;
+    }
+    if let Some(slots) = env_usize("LUARS_MAX_COROUTINE_STACK") {
+        option.max_coroutine_stack = slots;
+    }
+    if let Some(frames) = env_usize("LUARS_MAX_CALL_DEPTH") {
+        option.max_call_depth = frames;
+    }
+    if let Some(depth) = env_usize("LUARS_MAX_C_STACK_DEPTH") {
+        option.max_c_stack_depth = depth;
+    }
+    option.max_memory_limit = env_usize("LUARS_MAX_MEMORY_LIMIT")
+        .map(|value| value.min(isize::MAX as usize) as isize)
+        .unwrap_or(4096 * 1024 * 1024);
+    option.compile_options = default_compile_options();
+    option.package_path = env_path("LUA_PATH_5_5", "LUA_PATH", luars::LUA_PATH_DEFAULT);
+    option.package_cpath = env_path("LUA_CPATH_5_5", "LUA_CPATH", luars::LUA_CPATH_DEFAULT);
+    option
+}
+
+/// Each optimization can be switched off with `LUARS_<NAME>=0`, to bisect a
+/// suspected miscompilation without rebuilding.
+fn default_compile_options() -> CompileOptions {
+    let enabled = |name: &str| env_usize(name) != Some(0);
+    CompileOptions {
+        constant_folding: enabled("LUARS_CONSTANT_FOLDING"),
+        concat_folding: enabled("LUARS_CONCAT_FOLDING"),
     }
 }
 
@@ -87,7 +127,7 @@ fn parse_args() -> Result<Options, String> {
                     if i >= args.len() {
                         return Err("'-e' needs argument".to_string());
                     }
-                    opts.execute_strings.push(args[i].clone());
+                    opts.run_args.push(RunArg::Execute(args[i].clone()));
                 }
                 "-i" => {
                     opts.interactive = true;
@@ -97,7 +137,7 @@ fn parse_args() -> Result<Options, String> {
                     if i >= args.len() {
                         return Err("'-l' needs argument".to_string());
                     }
-                    opts.require_modules.push(args[i].clone());
+                    opts.run_args.push(RunArg::Require(args[i].clone()));
                 }
                 "-v" => {
                     opts.show_version = true;
@@ -112,8 +152,10 @@ fn parse_args() -> Result<Options, String> {
                     stop_options = true;
                 }
                 "-" => {
+                    // Like a script name: the rest are the script's arguments
                     opts.read_stdin = true;
-                    stop_options = true;
+                    opts.script_args = args[i + 1..].to_vec();
+                    break;
                 }
                 _ => {
                     return Err(format!("unrecognized option '{}'", arg));
@@ -156,9 +198,50 @@ fn setup_arg_table(vm: &mut Lua, exe_path: &str, script_name: Option<&str>, args
     let _ = vm.globals().set("arg", &arg_table);
 }
 
-fn require_module(vm: &mut Lua, module: &str) -> Result<(), String> {
-    let code = format!("{} = require('{}')", module, module);
-    vm.execute(&code).map_err(|e| {
+/// Split a `-l` argument into (global path, module name), like lua.c's
+/// dolibrary: `g=mod` binds to `g` verbatim; a plain name drops anything after
+/// the `-` ignore mark and binds along its dotted components.
+fn parse_library_arg(arg: &str) -> (Vec<&str>, &str) {
+    match arg.split_once('=') {
+        Some((global, module)) => (vec![global], module),
+        None => {
+            let global = arg.split_once('-').map_or(arg, |(name, _)| name);
+            (global.split('.').collect(), arg)
+        }
+    }
+}
+
+fn require_module(vm: &mut Lua, arg: &str) -> Result<(), String> {
+    let (path, module) = parse_library_arg(arg);
+    let load = |vm: &mut Lua| -> LuaResult<()> {
+        let value: LuaValue = vm.call_global1("require", module)?;
+        let (last, parents) = path.split_last().expect("split yields a component");
+        let Some((root, rest)) = parents.split_first() else {
+            return vm.set_global(last, value);
+        };
+        // Nested binding: reuse tables already there (require may have made
+        // them) and create the missing ones, so `socket.core` is reachable.
+        let mut table = match vm.get_global::<LuaTable>(root) {
+            Ok(Some(table)) => table,
+            _ => {
+                let table = vm.create_table()?;
+                vm.set_global(root, &table)?;
+                table
+            }
+        };
+        for name in rest {
+            table = match table.get::<Option<LuaTable>>(*name) {
+                Ok(Some(child)) => child,
+                _ => {
+                    let child = vm.create_table()?;
+                    table.set(*name, &child)?;
+                    child
+                }
+            };
+        }
+        table.set(*last, value)
+    };
+    load(vm).map_err(|e| {
         format!(
             "failed to load module '{}': {}",
             module,
@@ -167,24 +250,32 @@ fn require_module(vm: &mut Lua, module: &str) -> Result<(), String> {
     })
 }
 
-fn execute_file(vm: &mut Lua, filename: &str) -> Result<(), String> {
-    let code =
-        fs::read_to_string(filename).map_err(|e| format!("cannot open {}: {}", filename, e))?;
-
-    vm.load(&code)
-        .set_name(format!("@{}", filename))
-        .exec()
-        .map_err(|e| vm.get_error_message(e).to_string())
+fn execute_file(vm: &mut Lua, filename: &str, args: &[String]) -> Result<(), String> {
+    let code = fs::read(filename).map_err(|e| format!("cannot open {}: {}", filename, e))?;
+    execute_bytes(vm, &code, &format!("@{}", filename), args)
 }
 
-fn execute_stdin(vm: &mut Lua) -> Result<(), String> {
-    let mut code = String::new();
+/// `lua -`: stdin is read as raw bytes, so it may carry a binary chunk
+/// as well as a script with a `#!` line.
+fn execute_stdin(vm: &mut Lua, args: &[String]) -> Result<(), String> {
+    let mut code = Vec::new();
     io::stdin()
-        .read_to_string(&mut code)
+        .read_to_end(&mut code)
         .map_err(|e| format!("error reading stdin: {}", e))?;
+    execute_bytes(vm, &code, "=stdin", args)
+}
 
-    vm.execute(&code)
-        .map_err(|e| format!("stdin: {}", vm.get_error_message(e)))
+/// Run a script's contents with the script arguments as its `...`.
+fn execute_bytes(
+    vm: &mut Lua,
+    code: &[u8],
+    chunk_name: &str,
+    args: &[String],
+) -> Result<(), String> {
+    let result = vm
+        .load_file_bytes(code, chunk_name)
+        .and_then(|func| func.call::<_, ()>(args.to_vec()));
+    result.map_err(|e| vm.get_error_message(e).to_string())
 }
 
 fn run_repl(vm: &mut Lua) {
@@ -233,10 +324,10 @@ fn run_repl(vm: &mut Lua) {
 
         match vm.load(&code_to_run).eval_multi::<Vec<LuaValue>>() {
             Ok(results) => {
-                if let Some(first) = results.into_iter().next()
-                    && !first.is_nil()
-                {
-                    println!("{:?}", first);
+                // Echo like lua.c's l_print: every result, tab separated
+                if !results.is_empty() {
+                    let line: Vec<String> = results.iter().map(|v| v.to_string()).collect();
+                    println!("{}", line.join("\t"));
                 }
                 incomplete.clear();
             }
@@ -356,6 +447,93 @@ pub fn run_interpreter() {
     }
 }
 
+/// Where `lua_main` is in a run. The stages go in lua.c's order; the
+/// first failing one skips the others up to `Interactive`, which still
+/// runs with `-i`, and makes the process exit with 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// LUA_INIT_5_5 or LUA_INIT
+    Init,
+    /// The `-e` and `-l` options, by index
+    Args(usize),
+    /// The script file or stdin
+    Script,
+    Interactive,
+    Done,
+}
+
+impl Options {
+    fn has_execute(&self) -> bool {
+        self.run_args
+            .iter()
+            .any(|arg| matches!(arg, RunArg::Execute(_)))
+    }
+
+    /// No script and no `-e`: lua.c reads from the console then.
+    fn wants_repl(&self) -> bool {
+        self.interactive || (!self.has_execute() && self.script_file.is_none() && !self.read_stdin)
+    }
+}
+
+/// Run LUA_INIT_5_5 (or LUA_INIT): `@file` runs a file, anything else is
+/// Lua source. Errors name the variable that was used.
+fn run_lua_init(vm: &mut Lua) -> Result<(), String> {
+    let Some((var, init)) = ["LUA_INIT_5_5", "LUA_INIT"]
+        .into_iter()
+        .find_map(|var| Some((var, env::var(var).ok()?)))
+    else {
+        return Ok(());
+    };
+    let result = match init.strip_prefix('@') {
+        Some(filename) => execute_file(vm, filename, &[]),
+        None => vm
+            .load(&init)
+            .set_name(format!("={var}"))
+            .exec()
+            .map_err(|e| vm.get_error_message(e).to_string()),
+    };
+    result.map_err(|e| format!("error in {var}: {e}"))
+}
+
+fn run_arg(vm: &mut Lua, arg: &RunArg) -> Result<(), String> {
+    match arg {
+        RunArg::Execute(code) => vm
+            .load(code)
+            .set_name("=(command line)")
+            .exec()
+            .map_err(|e| vm.get_error_message(e).to_string()),
+        RunArg::Require(module) => require_module(vm, module),
+    }
+}
+
+fn run_script(vm: &mut Lua, opts: &Options) -> Result<(), String> {
+    let Some(filename) = &opts.script_file else {
+        if opts.read_stdin {
+            return execute_stdin(vm, &opts.script_args);
+        }
+        return Ok(());
+    };
+    // Set package.path to include the script's directory. The directory
+    // is kept as the OS spells it (drive letters, UNC prefixes and
+    // backslashes included) and only escaped for the Lua string literal.
+    if let Some(parent) = std::path::Path::new(filename).parent() {
+        let parent_str = parent.to_string_lossy();
+        let dir = if parent_str.is_empty() {
+            "."
+        } else {
+            parent_str.as_ref()
+        };
+        let sep = luars::LUA_DIRSEP;
+        let prefix = format!("{dir}{sep}?.lua;{dir}{sep}?{sep}init.lua;");
+        let set_path = format!(
+            "package.path = '{}' .. package.path",
+            prefix.replace('\\', "\\\\").replace('\'', "\\'")
+        );
+        let _ = vm.execute(&set_path);
+    }
+    execute_file(vm, filename, &opts.script_args)
+}
+
 fn lua_main() -> i32 {
     let opts = match parse_args() {
         Ok(opts) => opts,
@@ -369,16 +547,21 @@ fn lua_main() -> i32 {
     // Show version if requested
     if opts.show_version {
         print_version();
-        if opts.execute_strings.is_empty() && opts.script_file.is_none() && !opts.read_stdin {
+        if !opts.has_execute() && opts.script_file.is_none() && !opts.read_stdin {
             return 0;
         }
     }
 
     // Create the high-level runtime first so the CLI stays on the public embedding API.
-    let safe_option = default_safe_option();
+    let safe_option = default_safe_option(opts.ignore_env);
 
     let mut lua = Lua::new(safe_option);
     lua.open_stdlib(Stdlib::All).unwrap();
+    // -E hides the LUA_* variables from scripts as well
+    if opts.ignore_env {
+        lua.global_state_mut()
+            .set_env_filter(|name| !name.starts_with("LUA_"));
+    }
 
     // Install the built-in debugger through the high-level embedding API.
     lua.install_library(luars_debugger::Library::default())
@@ -390,58 +573,6 @@ fn lua_main() -> i32 {
         let _ = vm.set_global("DEBUG", LuaValue::boolean(true));
     }
 
-    // Handle -E: apply environment variables to package.path/cpath BEFORE -E blocks them
-    if !opts.ignore_env {
-        // Override package.path from LUA_PATH_5_5 or LUA_PATH
-        if let Some(env_path) = env::var("LUA_PATH_5_5")
-            .ok()
-            .or_else(|| env::var("LUA_PATH").ok())
-        {
-            let default_path = "./?.lua;./?/init.lua";
-            let resolved = resolve_env_path(&env_path, default_path);
-            let code = format!(
-                "package.path = '{}'",
-                resolved.replace('\\', "\\\\").replace('\'', "\\'")
-            );
-            let _ = vm.execute(&code);
-        }
-        // Override package.cpath from LUA_CPATH_5_5 or LUA_CPATH
-        if let Some(env_cpath) = env::var("LUA_CPATH_5_5")
-            .ok()
-            .or_else(|| env::var("LUA_CPATH").ok())
-        {
-            let default_cpath = "./?.so;./?.dll;./?.dylib";
-            let resolved = resolve_env_path(&env_cpath, default_cpath);
-            let code = format!(
-                "package.cpath = '{}'",
-                resolved.replace('\\', "\\\\").replace('\'', "\\'")
-            );
-            let _ = vm.execute(&code);
-        }
-    }
-
-    // Handle LUA_INIT (unless -E)
-    if !opts.ignore_env
-        && let Some(init) = env::var("LUA_INIT_5_5")
-            .ok()
-            .or_else(|| env::var("LUA_INIT").ok())
-    {
-        if let Some(filename) = init.strip_prefix('@') {
-            // Execute file
-            if let Err(e) = execute_file(vm, filename) {
-                eprintln!("lua: {}", e);
-                return 1;
-            }
-        } else {
-            // Execute string
-            if let Err(e) = vm.execute(&init) {
-                let error_msg = vm.get_error_message(e);
-                eprintln!("lua: {}", error_msg);
-                return 1;
-            }
-        }
-    }
-
     // Handle -W: turn warnings on
     if opts.warnings_on {
         let _ = vm.execute("warn('@on')");
@@ -449,64 +580,43 @@ fn lua_main() -> i32 {
 
     // Setup arg table
     let exe_path = env::args().next().unwrap_or_else(|| "lua".to_string());
-    setup_arg_table(
-        vm,
-        &exe_path,
-        opts.script_file.as_deref(),
-        &opts.script_args,
-    );
-
-    // Require modules
-    for module in &opts.require_modules {
-        if let Err(e) = require_module(vm, module) {
-            eprintln!("lua: {}", e);
-            return 1;
-        }
-    }
-
-    // Execute all -e strings in order (they share the same VM state)
-    for code in &opts.execute_strings {
-        if let Err(e) = vm.execute(code) {
-            let error_msg = vm.get_error_message(e);
-            eprintln!("lua: Runtime Error: {}", error_msg);
-            return 1;
-        }
-    }
-
-    // Execute script file or stdin
-    if let Some(filename) = &opts.script_file {
-        // Set package.path to include the script's directory
-        if let Some(parent) = std::path::Path::new(filename).parent() {
-            let parent_str = parent.to_string_lossy();
-            let dir = if parent_str.is_empty() {
-                ".".to_string()
-            } else {
-                parent_str.to_string()
-            };
-            // Prepend script dir to package.path
-            let set_path = format!(
-                "package.path = '{dir}/?.lua;{dir}/?/init.lua;' .. package.path",
-                dir = dir.replace('\\', "/")
-            );
-            let _ = vm.execute(&set_path);
-        }
-        if let Err(e) = execute_file(vm, filename) {
-            eprintln!("lua: {}", e);
-            return 1;
-        }
-    } else if opts.read_stdin
-        && let Err(e) = execute_stdin(vm)
-    {
-        eprintln!("lua: {}", e);
-        return 1;
-    }
+    let script_name = if opts.read_stdin {
+        Some("-")
+    } else {
+        opts.script_file.as_deref()
+    };
+    setup_arg_table(vm, &exe_path, script_name, &opts.script_args);
 
-    // Enter interactive mode if requested or if no script was provided
-    if opts.interactive
-        || (opts.execute_strings.is_empty() && opts.script_file.is_none() && !opts.read_stdin)
-    {
-        run_repl(vm);
+    let mut status = 0;
+    let mut stage = if opts.ignore_env {
+        Stage::Args(0)
+    } else {
+        Stage::Init
+    };
+    while stage != Stage::Done {
+        let (result, next) = match stage {
+            Stage::Init => (run_lua_init(vm), Stage::Args(0)),
+            Stage::Args(i) => match opts.run_args.get(i) {
+                Some(arg) => (run_arg(vm, arg), Stage::Args(i + 1)),
+                None => (Ok(()), Stage::Script),
+            },
+            Stage::Script => (run_script(vm, &opts), Stage::Interactive),
+            Stage::Interactive => {
+                if opts.interactive || (status == 0 && opts.wants_repl()) {
+                    run_repl(vm);
+                }
+                (Ok(()), Stage::Done)
+            }
+            Stage::Done => unreachable!(),
+        };
+        stage = match result {
+            Ok(()) => next,
+            Err(e) => {
+                eprintln!("lua: {}", e);
+                status = 1;
+                Stage::Interactive
+            }
+        };
     }
-
-    0
+    status
 }
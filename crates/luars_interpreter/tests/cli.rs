@@ -0,0 +1,312 @@
+//! Runs the `lua` binary against the modules in `tests/fixtures`.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+fn lua_command(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_lua"));
+    command
+        .args(args)
+        .env("LUA_PATH", format!("{}/?.lua", fixtures().display()))
+        .env_remove("LUA_PATH_5_5")
+        .env_remove("LUA_INIT")
+        .env_remove("LUA_INIT_5_5");
+    command
+}
+
+fn run(args: &[&str]) -> Output {
+    lua_command(args).output().expect("failed to spawn lua")
+}
+
+/// Run `lua args...` with `input` piped into its stdin.
+fn run_with_stdin(args: &[&str], input: &[u8]) -> Output {
+    let mut child = lua_command(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn lua");
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().expect("failed to wait for lua")
+}
+
+fn stdout_of(args: &[&str]) -> String {
+    let output = run(args);
+    assert!(
+        output.status.success(),
+        "lua {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn require_binds_module_name() {
+    let out = stdout_of(&["-l", "greet", "-e", "print(greet.hello('cli'))"]);
+    assert_eq!(out, "hello cli\n");
+}
+
+#[test]
+fn require_with_explicit_global() {
+    let out = stdout_of(&[
+        "-l",
+        "g=greet",
+        "-e",
+        "print(g.hello('x'), greet, package.loaded.greet == g)",
+    ]);
+    assert_eq!(out, "hello x\tnil\ttrue\n");
+}
+
+#[test]
+fn require_dotted_module_binds_nested() {
+    let out = stdout_of(&["-l", "socket.core", "-e", "print(socket.core.version)"]);
+    assert_eq!(out, "core-1\n");
+
+    let out = stdout_of(&["-l", "sc=socket.core", "-e", "print(sc.version, socket)"]);
+    assert_eq!(out, "core-1\tnil\n");
+}
+
+#[test]
+fn require_goes_through_package_loaded() {
+    let out = stdout_of(&[
+        "-l",
+        "a=counter",
+        "-l",
+        "b=counter",
+        "-e",
+        "print(loads, a == b)",
+    ]);
+    assert_eq!(out, "1\ttrue\n");
+}
+
+#[test]
+fn require_missing_module_fails() {
+    let output = run(&["-l", "no_such_module", "-e", "print('unreachable')"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no_such_module"), "{}", stderr);
+}
+
+#[test]
+fn stdin_script_skips_shebang_and_bom() {
+    let script = std::fs::read(fixtures().join("stdin_script.lua")).unwrap();
+    let output = run_with_stdin(&["-", "a", "b"], &script);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "-\t2\ta\tb\nstdin\t4\n"
+    );
+
+    let mut with_bom = b"\xEF\xBB\xBF".to_vec();
+    with_bom.extend_from_slice(&script);
+    let output = run_with_stdin(&["-"], &with_bom);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "-\t0\nstdin\t4\n");
+}
+
+#[test]
+fn stdin_runs_binary_chunk() {
+    let path = fixtures().join("stdin_script.lua");
+    let dump = format!(
+        "io.write(string.dump(assert(loadfile([==[{}]==]))))",
+        path.display()
+    );
+    let output = run(&["-e", &dump]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout.first(), Some(&0x1B));
+
+    let output = run_with_stdin(&["-", "x"], &output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("-\t1\tx\n"), "{}", stdout);
+}
+
+#[test]
+fn stdin_errors_name_the_chunk_stdin() {
+    let output = run_with_stdin(&["-"], b"\n\nerror('boom')\n");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("lua: stdin:3: boom"), "{}", stderr);
+}
+
+#[test]
+fn luarocks_style_path_setup() {
+    let script = fixtures().join("luarocks_paths.lua");
+    let tree = fixtures().join("rocks");
+    let out = stdout_of(&["-E", script.to_str().unwrap(), tree.to_str().unwrap()]);
+    assert_eq!(
+        out,
+        "default tree\ttrue\ndeduplicated\ttrue\nsearchpath\ttrue\n\
+         require\trockmod\nsearcher\tvirtual\t:rocks:\nstdlib\ttrue\ttrue\n"
+    );
+}
+
+#[test]
+fn lua_path_env_expands_default() {
+    let output = lua_command(&["-e", "print(package.path)"])
+        .env("LUA_PATH_5_5", "first/?.lua;;last/?.lua")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("first/?.lua;{};last/?.lua\n", luars::LUA_PATH_DEFAULT)
+    );
+
+    let output = lua_command(&["-E", "-e", "print(package.path)"])
+        .env("LUA_PATH_5_5", "ignored/?.lua")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}\n", luars::LUA_PATH_DEFAULT)
+    );
+}
+
+#[test]
+fn ignore_env_hides_lua_variables_from_getenv() {
+    let script = "print(os.getenv('LUA_SECRET'), os.getenv('LUARS_CLI_TEST_VISIBLE'))";
+    let output = lua_command(&["-e", script])
+        .env("LUA_SECRET", "s3cret")
+        .env("LUARS_CLI_TEST_VISIBLE", "yes")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "s3cret\tyes\n");
+
+    let output = lua_command(&["-E", "-e", script])
+        .env("LUA_SECRET", "s3cret")
+        .env("LUARS_CLI_TEST_VISIBLE", "yes")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "nil\tyes\n");
+}
+
+#[test]
+fn success_exits_zero() {
+    let output = run(&["-e", "x = 1", "-e", "print(x + 1)"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn os_exit_code_propagates() {
+    let output = run(&[
+        "-e",
+        "io.write('bye') os.exit(3)",
+        "-e",
+        "print('unreachable')",
+    ]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "bye");
+
+    assert_eq!(run(&["-e", "os.exit(true)"]).status.code(), Some(0));
+    assert_eq!(run(&["-e", "os.exit(false)"]).status.code(), Some(1));
+    assert_eq!(run(&["-e", "os.exit()"]).status.code(), Some(0));
+}
+
+#[test]
+fn script_error_exits_one_with_traceback_on_stderr() {
+    let dir = std::env::temp_dir().join(format!("luars_cli_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("fails.lua");
+    std::fs::write(
+        &script,
+        "print('before')\nlocal function f() error('boom') end\nf()\n",
+    )
+    .unwrap();
+    let output = run(&[script.to_str().unwrap()]);
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("lua: "), "{}", stderr);
+    assert!(stderr.contains("fails.lua:2: boom"), "{}", stderr);
+    assert!(stderr.contains("stack traceback:"), "{}", stderr);
+}
+
+#[test]
+fn first_failing_stage_stops_the_rest() {
+    let output = run(&[
+        "-e",
+        "print('first')",
+        "-e",
+        "error('second')",
+        "-l",
+        "greet",
+        "-e",
+        "print('third')",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "first\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("lua: (command line):1: second"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn error_then_interactive_still_enters_repl() {
+    let output = run_with_stdin(&["-e", "error('early')", "-i"], b"print('in repl')\n");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("> in repl"), "{}", stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("early"), "{}", stderr);
+
+    // Without -i, a failed run does not fall into the REPL
+    let output = run_with_stdin(&["-l", "no_such_module"], b"print('in repl')\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn lua_init_errors_name_the_variable() {
+    let output = lua_command(&["-e", "print('unreachable')"])
+        .env("LUA_INIT", "error('bad init')")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("lua: error in LUA_INIT: "), "{}", stderr);
+    assert!(stderr.contains("bad init"), "{}", stderr);
+
+    let output = lua_command(&["-e", "print('unreachable')"])
+        .env("LUA_INIT", "print('plain')")
+        .env("LUA_INIT_5_5", "@no_such_init_file.lua")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("lua: error in LUA_INIT_5_5: cannot open no_such_init_file.lua"),
+        "{}",
+        stderr
+    );
+
+    // -E skips LUA_INIT altogether
+    let output = lua_command(&["-E", "-e", "print('ran')"])
+        .env("LUA_INIT", "error('bad init')")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ran\n");
+}
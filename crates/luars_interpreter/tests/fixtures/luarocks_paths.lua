@@ -0,0 +1,55 @@
+-- The path handling luarocks does at startup (core/cfg.lua, core/path.lua),
+-- run against a rocks tree given as arg[1].
+local tree = assert(arg[1], "usage: luarocks_paths.lua <tree>")
+
+-- cfg.lua: platform marks come from package.config
+local dir_sep, path_sep, path_mark, exec_mark, ignore_mark =
+    package.config:match("^(.)\n(.)\n(.)\n(.)\n(.)$")
+assert(dir_sep and ignore_mark == "-", "malformed package.config")
+local lua_version = _VERSION:match(" (5%.%d+)$")
+
+-- path.lua: a rock tree's module directory
+local function deploy_lua_dir(root)
+    return table.concat({ root, "share", "lua", lua_version }, dir_sep)
+end
+
+-- util.cleanup_path: drop duplicate entries, keeping the first
+local function cleanup_path(list)
+    local seen, out = {}, {}
+    for part in list:gmatch("[^" .. path_sep .. "]+") do
+        if not seen[part] then
+            seen[part] = true
+            out[#out + 1] = part
+        end
+    end
+    return table.concat(out, path_sep)
+end
+
+-- The default path already names the conventional tree for this version
+local conventional = "share" .. dir_sep .. "lua" .. dir_sep .. lua_version
+print("default tree", package.path:find(conventional, 1, true) ~= nil)
+
+local lua_dir = deploy_lua_dir(tree)
+local entries = {
+    lua_dir .. dir_sep .. path_mark .. ".lua",
+    lua_dir .. dir_sep .. path_mark .. dir_sep .. "init.lua",
+}
+local new_path = table.concat(entries, path_sep) .. path_sep .. package.path
+package.path = cleanup_path(new_path .. path_sep .. new_path)
+print("deduplicated", select(2, package.path:gsub(path_sep, "")) + 1
+    == select(2, new_path:gsub(path_sep, "")) + 1)
+
+print("searchpath", package.searchpath("rockmod", package.path) == entries[1]:gsub("%?", "rockmod"))
+print("require", require("rockmod").name)
+
+-- loader.lua: an extra searcher added to the table require consults
+table.insert(package.searchers, 2, function(name)
+    if name == "rocks.virtual" then
+        return function() return "virtual" end, ":rocks:"
+    end
+    return "\n\tno rock '" .. name .. "'"
+end)
+print("searcher", require("rocks.virtual"))
+
+-- Standard libraries resolve from package.loaded without a search
+print("stdlib", require("string") == string, require("table") == table)
@@ -0,0 +1 @@
+return { version = "core-1" }
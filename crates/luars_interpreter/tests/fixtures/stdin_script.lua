@@ -0,0 +1,4 @@
+#!/usr/bin/env luars
+-- run through stdin by cli.rs
+print(arg[0], select("#", ...), ...)
+print(debug.getinfo(1, "Sl").short_src, debug.getinfo(1, "l").currentline)
@@ -12,6 +12,9 @@ pub fn init() {
 #[wasm_bindgen]
 pub struct LuaWasm {
     lua: Lua,
+    /// Return a lone result from `execute` / `doString` / `eval` as itself
+    /// rather than as a one-element array.
+    unwrap_single: bool,
 }
 
 #[wasm_bindgen]
@@ -22,60 +25,69 @@ impl LuaWasm {
         let mut lua = Lua::new(SafeOption::default());
         lua.open_stdlib(Stdlib::All)
             .map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
-        Ok(LuaWasm { lua })
+        Ok(LuaWasm {
+            lua,
+            unwrap_single: true,
+        })
     }
 
-    /// Execute Lua source code. Returns the first result as a string,
-    /// or throws on error.
+    /// Choose how `execute`, `doString` and `eval` return their results.
+    ///
+    /// When `true` (the default), a single result is returned as itself,
+    /// no result as `undefined`, and several results as an Array. When
+    /// `false`, the results are always returned as an Array.
+    #[wasm_bindgen(js_name = setUnwrapSingle)]
+    pub fn set_unwrap_single(&mut self, unwrap: bool) {
+        self.unwrap_single = unwrap;
+    }
+
+    /// Execute Lua source code and return its results formatted as strings
+    /// (see `setUnwrapSingle`), or throw on error.
+    ///
+    /// ```js
+    /// lua.execute('return 1 + 1');        // "2"
+    /// lua.execute('return 1, nil, "x"');  // ["1", "nil", "x"]
+    /// ```
     #[wasm_bindgen]
-    pub fn execute(&mut self, code: &str) -> Result<String, JsValue> {
-        match self.lua.load(code).eval::<LuaValue>() {
-            Ok(result) => Ok(format!("{}", result)),
-            Err(e) => {
-                let msg = self.lua.get_error_message(e);
-                Err(JsValue::from_str(msg.message()))
-            }
-        }
+    pub fn execute(&mut self, code: &str) -> Result<JsValue, JsValue> {
+        let results = self.run(code)?;
+        let values = results
+            .iter()
+            .map(|v| JsValue::from_str(&v.to_string()))
+            .collect();
+        Ok(self.pack_results(values))
     }
 
     /// Execute Lua code and return **all** results as a JS Array.
     /// Each Lua value is converted to its JS equivalent (tables → objects/arrays, etc.).
     #[wasm_bindgen(js_name = executeMulti)]
     pub fn execute_multi(&mut self, code: &str) -> Result<JsValue, JsValue> {
-        match self.lua.eval_multi::<Vec<LuaValue>>(code) {
-            Ok(results) => Ok(self.lua_results_to_js_array(&results)),
-            Err(e) => {
-                let msg = self.lua.get_error_message(e);
-                Err(JsValue::from_str(msg.message()))
-            }
-        }
+        let results = self.run(code)?;
+        Ok(self.lua_results_to_js_array(&results))
     }
 
-    /// Execute Lua code and return the first result as a JS value
-    /// (with full table conversion).
+    /// Execute Lua code and return its results as JS values (with full
+    /// table conversion; see `setUnwrapSingle`).
     #[wasm_bindgen(js_name = doString)]
     pub fn do_string(&mut self, code: &str) -> Result<JsValue, JsValue> {
-        match self.lua.load(code).eval::<LuaValue>() {
-            Ok(first) => Ok(conversion::lua_to_js(self.lua.global_state_mut(), &first)),
-            Err(e) => {
-                let msg = self.lua.get_error_message(e);
-                Err(JsValue::from_str(msg.message()))
-            }
-        }
+        let results = self.run(code)?;
+        let values = self.lua_results_to_js(&results);
+        Ok(self.pack_results(values))
     }
 
-    /// Evaluate a Lua **expression** (auto-prepends `return`).
-    /// Returns the result as a JS value with full type conversion.
+    /// Evaluate a Lua **expression list** (auto-prepends `return`).
+    /// Returns the results as JS values with full type conversion
+    /// (see `setUnwrapSingle`).
+    ///
+    /// ```js
+    /// lua.eval('2 + 2');              // 4
+    /// lua.eval('1, nil, 3');          // [1, null, 3]
+    /// ```
     #[wasm_bindgen]
     pub fn eval(&mut self, expr: &str) -> Result<JsValue, JsValue> {
-        let code = format!("return {}", expr);
-        match self.lua.load(&code).eval::<LuaValue>() {
-            Ok(first) => Ok(conversion::lua_to_js(self.lua.global_state_mut(), &first)),
-            Err(e) => {
-                let msg = self.lua.get_error_message(e);
-                Err(JsValue::from_str(msg.message()))
-            }
-        }
+        let results = self.run(&format!("return {}", expr))?;
+        let values = self.lua_results_to_js(&results);
+        Ok(self.pack_results(values))
     }
 
     /// Compile Lua source without executing it. Returns `true` on success.
@@ -249,6 +261,32 @@ impl LuaWasm {
         arr.into()
     }
 
+    /// Run a chunk and collect every result, nils included.
+    fn run(&mut self, code: &str) -> Result<Vec<LuaValue>, JsValue> {
+        self.lua
+            .load(code)
+            .eval_multi::<Vec<LuaValue>>()
+            .map_err(|e| {
+                let msg = self.lua.get_error_message(e);
+                JsValue::from_str(msg.message())
+            })
+    }
+
+    fn lua_results_to_js(&mut self, results: &[LuaValue]) -> Vec<JsValue> {
+        results
+            .iter()
+            .map(|v| conversion::lua_to_js(self.lua.global_state_mut(), v))
+            .collect()
+    }
+
+    /// Shape converted results according to `unwrap_single`.
+    fn pack_results(&self, mut values: Vec<JsValue>) -> JsValue {
+        if self.unwrap_single && values.len() <= 1 {
+            return values.pop().unwrap_or(JsValue::UNDEFINED);
+        }
+        values.into_iter().collect::<js_sys::Array>().into()
+    }
+
     // ── Output capture ──────────────────────────────────────────────
 
     /// Redirect Lua `print()` to a JavaScript callback.
@@ -95,6 +95,7 @@ pub enum StatusCode {
     BadRequest = 400,
     NotFound = 404,
     InternalServerError = 500,
+    ServiceUnavailable = 503,
 }
 
 impl StatusCode {
@@ -104,6 +105,7 @@ impl StatusCode {
             StatusCode::BadRequest => "Bad Request",
             StatusCode::NotFound => "Not Found",
             StatusCode::InternalServerError => "Internal Server Error",
+            StatusCode::ServiceUnavailable => "Service Unavailable",
         }
     }
 }
@@ -162,6 +164,15 @@ impl HttpResponse {
         }
     }
 
+    /// Create a 503 Service Unavailable response.
+    pub fn unavailable(msg: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::ServiceUnavailable,
+            headers: vec![("Content-Type".into(), "text/plain; charset=utf-8".into())],
+            body: msg.into(),
+        }
+    }
+
     /// Add a header.
     pub fn with_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.headers.push((key.into(), value.into()));
@@ -1,21 +1,57 @@
-//! High-level Lua runtime setup for the HTTP example.
+//! Lua side of the HTTP example: worker VM setup and request dispatch.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use luars::{
-    Lua, LuaApi, LuaAsyncApi, LuaFunction, LuaResult, LuaSandboxApi, SafeOption, SandboxConfig,
-    Stdlib,
+    Lua, LuaApi, LuaAsyncApi, LuaFunction, LuaResult, LuaSandboxApi, SandboxConfig, Stdlib, VmPool,
 };
 
 use crate::async_io;
+use crate::http::{HttpRequest, HttpResponse, StatusCode};
+
+const HANDLER_KEY: &str = "http.handler";
+/// Raised in a worker VM whose handler runs past the request timeout.
+const TIMEOUT_MESSAGE: &str = "request handler timeout";
+/// Raised by the VM when an async host function runs past the timeout.
+const TASK_TIMEOUT_MESSAGE: &str = "async task timed out";
+/// Raised in a worker whose client went away before the handler finished.
+const DISCONNECTED_MESSAGE: &str = "client disconnected";
 
-pub struct LuaRuntime {
-    lua: Lua,
-    handler: LuaFunction,
+thread_local! {
+    // Each pool worker drives its VM's async host functions on its own
+    // single-threaded runtime.
+    static WORKER_RUNTIME: tokio::runtime::Runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to create tokio runtime for worker");
 }
 
-pub fn create_runtime(lua_script: &str) -> LuaResult<Box<LuaRuntime>> {
-    let mut lua = Lua::new(SafeOption::default());
+/// Build a pool whose workers each run `lua_script` in a sandbox. A request
+/// whose handler is still running `timeout` after dispatch is aborted, and
+/// so is an async host call (sleep, file I/O) that takes longer than that.
+pub fn create_pool(
+    workers: usize,
+    lua_script: &str,
+    timeout: Duration,
+) -> Result<VmPool, luars::LuaFullError> {
+    let script = lua_script.to_string();
+    VmPool::builder()
+        .workers(workers)
+        .thread_name("lua-worker")
+        .timeout(timeout, TIMEOUT_MESSAGE)
+        .init(move |lua| init_worker(lua, &script, timeout))
+        .build()
+}
+
+fn init_worker(lua: &mut Lua, lua_script: &str, timeout: Duration) -> LuaResult<()> {
+    // The pool timeout only interrupts running Lua code; a worker blocked
+    // on a hung host future needs the task timeout as well.
+    lua.global_state_mut().set_async_task_timeout(Some(timeout));
     lua.open_stdlib(Stdlib::All)?;
-    async_io::register_all(&mut lua)?;
+    async_io::register_all(lua)?;
 
     let mut sandbox = SandboxConfig::default();
     for name in ["sleep", "read_file", "write_file", "time", "env", "log"] {
@@ -26,31 +62,104 @@ pub fn create_runtime(lua_script: &str) -> LuaResult<Box<LuaRuntime>> {
         .load_sandboxed(lua_script, &sandbox)
         .set_name("handler.lua")
         .eval()?;
+    lua.registry_set(HANDLER_KEY, handler)
+}
+
+/// Run `request` through the Lua handler on one of the pool's workers.
+///
+/// Dropping the returned future (the client disconnected) cancels the
+/// handler: the worker drops its async host futures and takes the next job.
+pub async fn handle(pool: &VmPool, request: HttpRequest) -> HttpResponse {
+    let request_line = (request.method.clone(), request.path.clone());
+    // Never sent: the worker sees the channel close when this future is
+    // dropped, which is how a disconnect reaches it.
+    let (_connected, disconnected) = tokio::sync::oneshot::channel::<()>();
+    let result = pool
+        .run(move |lua| {
+            let handler: LuaFunction = lua
+                .registry_get(HANDLER_KEY)?
+                .expect("handler is installed by init_worker");
+            let args = (
+                request.method,
+                request.path,
+                request.query.unwrap_or_default(),
+                headers_to_json(&request.headers),
+                request.body,
+            );
+            let finished = WORKER_RUNTIME.with(|rt| {
+                rt.block_on(async {
+                    tokio::select! {
+                        biased;
+                        _ = disconnected => None,
+                        result = lua.call_async::<_, (u16, String, String)>(&handler, args) => {
+                            Some(result)
+                        }
+                    }
+                })
+            });
+            finished.unwrap_or_else(|| {
+                // The handler's coroutine is abandoned; don't leave its
+                // host futures running until it is collected.
+                lua.global_state_mut().abort_async_tasks();
+                Err(lua.main_state().error(DISCONNECTED_MESSAGE.to_string()))
+            })
+        })
+        .await;
+
+    match result {
+        Ok((status, content_type, body)) => {
+            let status = match status {
+                200 => StatusCode::Ok,
+                400 => StatusCode::BadRequest,
+                404 => StatusCode::NotFound,
+                _ => StatusCode::InternalServerError,
+            };
+            HttpResponse {
+                status,
+                headers: vec![("Content-Type".into(), content_type)],
+                body,
+            }
+        }
+        Err(e)
+            if e.message.contains(TIMEOUT_MESSAGE) || e.message.contains(TASK_TIMEOUT_MESSAGE) =>
+        {
+            eprintln!("{} {}: {TIMEOUT_MESSAGE}", request_line.0, request_line.1);
+            HttpResponse::unavailable(TIMEOUT_MESSAGE)
+        }
+        Err(e) => {
+            eprintln!("Lua error: {e}");
+            HttpResponse::error(format!("Lua error: {e}"))
+        }
+    }
+}
 
-    Ok(Box::new(LuaRuntime { lua, handler }))
+/// Number of async host calls still in flight across all workers.
+pub async fn async_task_count(pool: &VmPool) -> Result<usize, luars::LuaFullError> {
+    let total = Arc::new(AtomicUsize::new(0));
+    let counter = total.clone();
+    pool.broadcast(move |lua| {
+        let count = lua.global_state_mut().async_task_count();
+        counter.fetch_add(count, Ordering::Relaxed);
+        Ok(())
+    })
+    .await?;
+    Ok(total.load(Ordering::Relaxed))
 }
 
-impl LuaRuntime {
-    pub async fn call_handler(
-        &mut self,
-        method: &str,
-        path: &str,
-        query: Option<&str>,
-        headers_json: &str,
-        body: &str,
-    ) -> LuaResult<(u16, String, String)> {
-        let handler = self.handler.clone();
-        self.lua
-            .call_async(
-                &handler,
-                (
-                    method.to_string(),
-                    path.to_string(),
-                    query.unwrap_or_default().to_string(),
-                    headers_json.to_string(),
-                    body.to_string(),
-                ),
-            )
-            .await
+fn headers_to_json(headers: &HashMap<String, String>) -> String {
+    let mut json = String::from("{");
+    let mut first = true;
+    for (k, v) in headers {
+        if !first {
+            json.push(',');
+        }
+        first = false;
+        json.push('"');
+        json.push_str(&k.replace('"', "\\\""));
+        json.push_str("\":\"");
+        json.push_str(&v.replace('"', "\\\""));
+        json.push('"');
     }
+    json.push('}');
+    json
 }
@@ -3,18 +3,23 @@
 mod async_io;
 mod http;
 mod lua_runtime;
-mod vm_pool;
 
 use std::path::PathBuf;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
 
 const DEFAULT_SCRIPT: &str = include_str!("lua/handler.lua");
+/// How long a request handler may run before it is aborted with a 503.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Reports how many async host calls the workers have in flight.
+const TASKS_PATH: &str = "/_tasks";
 
 struct Config {
     port: u16,
     workers: usize,
     script: Option<PathBuf>,
+    timeout: Duration,
 }
 
 impl Config {
@@ -22,6 +27,7 @@ impl Config {
         let mut port = 8080_u16;
         let mut workers = num_cpus();
         let mut script = None;
+        let mut timeout = DEFAULT_TIMEOUT;
         let args: Vec<String> = std::env::args().collect();
         let mut index = 1;
 
@@ -45,6 +51,16 @@ impl Config {
                         script = Some(PathBuf::from(value));
                     }
                 }
+                "--timeout" | "-t" => {
+                    index += 1;
+                    if let Some(value) = args.get(index) {
+                        timeout = value
+                            .parse()
+                            .ok()
+                            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                            .unwrap_or(DEFAULT_TIMEOUT);
+                    }
+                }
                 _ => {}
             }
             index += 1;
@@ -54,6 +70,7 @@ impl Config {
             port,
             workers,
             script,
+            timeout,
         }
     }
 }
@@ -72,7 +89,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None => DEFAULT_SCRIPT.to_owned(),
     };
 
-    let pool = std::sync::Arc::new(vm_pool::VmPool::new(config.workers, &script));
+    let pool = std::sync::Arc::new(lua_runtime::create_pool(
+        config.workers,
+        &script,
+        config.timeout,
+    )?);
     let listener = TcpListener::bind(("127.0.0.1", config.port)).await?;
 
     println!(
@@ -80,6 +101,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         config.port
     );
     println!("workers: {}", pool.worker_count());
+    println!("request timeout: {:?}", config.timeout);
 
     loop {
         let (mut stream, peer_addr) = listener.accept().await?;
@@ -95,7 +117,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 async fn handle_connection(
     stream: &mut tokio::net::TcpStream,
-    pool: &vm_pool::VmPool,
+    pool: &luars::VmPool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut buffer = vec![0_u8; 65_536];
     let read = stream.read(&mut buffer).await?;
@@ -105,7 +127,20 @@ async fn handle_connection(
 
     let raw = String::from_utf8_lossy(&buffer[..read]);
     let response = match http::HttpRequest::parse(&raw) {
-        Some(request) => pool.handle(request).await,
+        Some(request) if request.path == TASKS_PATH => {
+            match lua_runtime::async_task_count(pool).await {
+                Ok(count) => http::HttpResponse::ok(count.to_string()),
+                Err(e) => http::HttpResponse::error(e.to_string()),
+            }
+        }
+        Some(request) => {
+            // Stop the handler if the client hangs up before it answers.
+            let mut probe = [0_u8; 1];
+            tokio::select! {
+                response = lua_runtime::handle(pool, request) => response,
+                Ok(0) | Err(_) = stream.read(&mut probe) => return Ok(()),
+            }
+        }
         None => http::HttpResponse {
             status: http::StatusCode::BadRequest,
             headers: vec![("Content-Type".into(), "text/plain; charset=utf-8".into())],
@@ -0,0 +1,43 @@
+//! Spawns the server binary and checks that a client hanging up while its
+//! handler waits on an async host call frees the worker and cancels the call.
+
+mod common;
+
+use std::io::Write;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use common::{free_port, get, get_status, start_server};
+
+const SCRIPT: &str = r#"
+return function(method, path)
+    if path == "/slow" then
+        sleep(5)
+    end
+    return 200, "text/plain; charset=utf-8", "ok"
+end
+"#;
+
+#[test]
+fn client_disconnect_frees_worker_and_cancels_tasks() {
+    let port = free_port();
+    // One worker, so a request stuck behind the abandoned one would show
+    let _server = start_server(port, 1, 30.0, SCRIPT);
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream
+        .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    // Let the handler reach sleep() before hanging up
+    std::thread::sleep(Duration::from_millis(300));
+    drop(stream);
+
+    let started = Instant::now();
+    assert_eq!(get_status(port, "/"), 200);
+    let elapsed = started.elapsed();
+    assert!(
+        elapsed < Duration::from_secs(2),
+        "worker was still busy: {elapsed:?}"
+    );
+    assert_eq!(get(port, "/_tasks"), (200, "0".to_string()));
+}
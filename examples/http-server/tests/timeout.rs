@@ -0,0 +1,46 @@
+//! Spawns the server binary and checks that a runaway handler is cut off
+//! with a 503 while other requests keep being served.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{free_port, get_status, start_server};
+
+const SCRIPT: &str = r#"
+return function(method, path)
+    if path == "/hang" then
+        while true do end
+    end
+    return 200, "text/plain; charset=utf-8", "ok"
+end
+"#;
+
+#[test]
+fn hanging_handler_gets_503_while_others_are_served() {
+    let port = free_port();
+    let timeout = Duration::from_millis(500);
+    let _server = start_server(port, 2, timeout.as_secs_f64(), SCRIPT);
+
+    let started = Instant::now();
+    let hanging = std::thread::spawn(move || (get_status(port, "/hang"), started.elapsed()));
+    let normal = std::thread::spawn(move || (get_status(port, "/"), started.elapsed()));
+
+    let (hang_status, hang_elapsed) = hanging.join().unwrap();
+    let (normal_status, normal_elapsed) = normal.join().unwrap();
+    assert_eq!(hang_status, 503);
+    assert_eq!(normal_status, 200);
+    assert!(
+        normal_elapsed < timeout,
+        "normal request took {normal_elapsed:?}"
+    );
+    assert!(
+        hang_elapsed < timeout + Duration::from_secs(2),
+        "hanging request took {hang_elapsed:?}"
+    );
+
+    // The worker that ran the hanging handler serves again
+    for _ in 0..4 {
+        assert_eq!(get_status(port, "/"), 200);
+    }
+}
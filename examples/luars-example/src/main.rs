@@ -1,4 +1,6 @@
-use luars::{Lua, LuaApi, LuaResult, LuaUserData, SafeOption, Stdlib, lua_methods};
+use luars::{
+    Lua, LuaApi, LuaFunction, LuaResult, LuaUserData, SafeOption, Stdlib, lua_methods,
+};
 
 #[derive(LuaUserData)]
 struct Counter {
@@ -57,5 +59,31 @@ fn main() -> LuaResult<()> {
     println!("slug: {slug}");
     println!("host: {host}");
     println!("count: {count}");
+
+    // Game-loop pattern: compile the update function once, then call the
+    // rooted handle every frame instead of recompiling source each tick.
+    let update: LuaFunction = lua
+        .load(
+            r#"
+            local position, speed = 0.0, 3.0
+            return function(dt)
+                position = position + speed * dt
+                return position, position >= 10.0
+            end
+            "#,
+        )
+        .set_name("update.lua")
+        .eval()?;
+
+    let dt = 1.0 / 60.0;
+    let mut frames = 0;
+    loop {
+        frames += 1;
+        let (position, arrived): (f64, bool) = update.call(dt)?;
+        if arrived {
+            println!("arrived at {position:.2} after {frames} frames");
+            break;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,47 @@
+//! A plugin exposing 2D shapes. The host only sees `types()`.
+
+use luars::{LuaUserData, TypeRegistry, TypeRegistryError, lua_methods};
+
+#[derive(LuaUserData)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[lua_methods]
+impl Point {
+    #[lua(constructor)]
+    pub fn new(x: f64, y: f64) -> Self {
+        Point { x, y }
+    }
+
+    pub fn distance(&self, other: &Point) -> f64 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
+    }
+}
+
+#[derive(LuaUserData)]
+pub struct Rect {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[lua_methods]
+impl Rect {
+    #[lua(constructor)]
+    pub fn new(width: f64, height: f64) -> Self {
+        Rect { width, height }
+    }
+
+    pub fn area(&self) -> f64 {
+        self.width * self.height
+    }
+}
+
+pub fn types() -> Result<TypeRegistry, TypeRegistryError> {
+    let mut types = TypeRegistry::new();
+    types
+        .add::<Point>("geometry", "Point")?
+        .add::<Rect>("geometry", "Rect")?;
+    Ok(types)
+}
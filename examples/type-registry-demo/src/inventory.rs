@@ -0,0 +1,37 @@
+//! A plugin exposing a stock counter whose quantity cannot go negative.
+
+use luars::{LuaUserData, SetAction, TypeOptions, TypeRegistry, TypeRegistryError, lua_methods};
+
+#[derive(LuaUserData)]
+pub struct Stock {
+    pub sku: String,
+    pub quantity: i64,
+}
+
+#[lua_methods]
+impl Stock {
+    #[lua(constructor)]
+    pub fn new(sku: String, quantity: i64) -> Self {
+        Stock { sku, quantity }
+    }
+
+    pub fn take(&mut self, n: i64) -> bool {
+        if n > self.quantity {
+            return false;
+        }
+        self.quantity -= n;
+        true
+    }
+}
+
+pub fn types() -> Result<TypeRegistry, TypeRegistryError> {
+    let options = TypeOptions::new().on_set(|_, _, key, _, new| {
+        if key == "quantity" && new.as_integer().is_some_and(|n| n < 0) {
+            return Err("quantity cannot be negative".to_string());
+        }
+        Ok(SetAction::Allow)
+    });
+    let mut types = TypeRegistry::new();
+    types.add_with::<Stock>("inventory", "Stock", options)?;
+    Ok(types)
+}
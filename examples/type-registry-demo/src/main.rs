@@ -0,0 +1,42 @@
+//! Installs the userdata types of two plugin modules without naming any of
+//! them: each plugin hands over a `TypeRegistry`, the host merges them and
+//! creates VMs from the result.
+
+mod geometry;
+mod inventory;
+
+use luars::{Lua, LuaApi, SafeOption, Stdlib, TypeRegistry};
+
+const SCRIPT: &str = r#"
+    local a, b = Point(0, 0), Point(3, 4)
+    print("distance", a:distance(b))
+    print("area", Rect(2, 5):area())
+
+    local stock = Stock("widget", 10)
+    print("take 4", stock:take(4), stock.quantity)
+    print("set -1", pcall(function() stock.quantity = -1 end))
+"#;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut types = TypeRegistry::new();
+    types.merge(&geometry::types()?)?;
+    types.merge(&inventory::types()?)?;
+    for entry in types.entries() {
+        println!("{} from {} ({})", entry.name(), entry.source(), entry.rust_type());
+    }
+
+    // Every VM gets the same set of types
+    for _ in 0..2 {
+        let mut lua = Lua::new_with_types(SafeOption::default(), &types)?;
+        lua.open_stdlib(Stdlib::All)?;
+        lua.load(SCRIPT).exec()?;
+    }
+
+    // A second plugin claiming `Point` is reported with both sources
+    let mut clash = TypeRegistry::new();
+    clash.add::<inventory::Stock>("warehouse", "Point")?;
+    if let Err(e) = types.merge(&clash) {
+        println!("error: {e}");
+    }
+    Ok(())
+}
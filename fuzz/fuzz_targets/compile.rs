@@ -0,0 +1,17 @@
+//! Arbitrary text through the compiler. Whatever the input, compiling must
+//! end in a chunk or a syntax error message, never a panic or a stack
+//! overflow.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use luars::{GlobalState, SafeOption};
+
+fuzz_target!(|data: &[u8]| {
+    // `load` rejects non-UTF-8 text before it reaches the compiler
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut vm = GlobalState::new(SafeOption::default());
+    let _ = vm.compile_with_name(source, "=fuzz");
+});
@@ -0,0 +1,40 @@
+//! Arbitrary bytes through `load(..., "b")`: the deserializer and verifier
+//! must reject anything the executor cannot run safely, and whatever they
+//! accept is run on an instruction budget.
+//!
+//! Seed the corpus with real dumps so the fuzzer starts past the header:
+//!
+//! ```text
+//! lua -e 'io.write(string.dump(load(io.read("a"))))' < script.lua > corpus/load_bytecode/script
+//! ```
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use luars::{Lua, LuaApi, LuaSandboxApi, SafeOption, SandboxConfig, Stdlib};
+
+const INSTRUCTION_LIMIT: u64 = 100_000;
+const MEMORY_LIMIT: isize = 1024 * 1024;
+
+fuzz_target!(|data: &[u8]| {
+    let mut lua = Lua::new(SafeOption::builder().allow_load_bytecode(true).build().unwrap());
+    lua.open_stdlib(Stdlib::All).unwrap();
+
+    let vm = lua.global_state_mut();
+    let chunk = vm.create_bytes(data).unwrap();
+    // Anchored in the globals so a collection cannot free it before use
+    vm.set_global("chunk", chunk).unwrap();
+
+    let config = SandboxConfig::default()
+        .allow_loading()
+        .with_global("chunk", chunk)
+        .with_instruction_limit(INSTRUCTION_LIMIT)
+        .with_memory_limit(MEMORY_LIMIT);
+    let _ = lua.execute_sandboxed(
+        r#"
+        local f = load(chunk, "=fuzz", "b")
+        if f then pcall(f) end
+        "#,
+        &config,
+    );
+});
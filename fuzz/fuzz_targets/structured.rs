@@ -0,0 +1,394 @@
+//! Programs generated from a small Lua grammar, run with an instruction
+//! budget and a tiny memory limit. Unlike raw bytes these get past the
+//! parser, so they exercise the compiler's code generation and the
+//! executor's metamethod, error and out-of-memory paths.
+
+#![no_main]
+
+use std::fmt::{self, Write};
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use luars::{Lua, LuaApi, LuaSandboxApi, SafeOption, SandboxConfig, Stdlib};
+
+const INSTRUCTION_LIMIT: u64 = 50_000;
+const MEMORY_LIMIT: isize = 64 * 1024;
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Name {
+    A,
+    B,
+    C,
+    T,
+    F,
+    Mt,
+    Pcall,
+    Setmetatable,
+    Select,
+    Tostring,
+    Pairs,
+    Ipairs,
+    Error,
+    String,
+    Table,
+    Math,
+}
+
+impl Name {
+    fn as_str(self) -> &'static str {
+        match self {
+            Name::A => "a",
+            Name::B => "b",
+            Name::C => "c",
+            Name::T => "t",
+            Name::F => "f",
+            Name::Mt => "mt",
+            Name::Pcall => "pcall",
+            Name::Setmetatable => "setmetatable",
+            Name::Select => "select",
+            Name::Tostring => "tostring",
+            Name::Pairs => "pairs",
+            Name::Ipairs => "ipairs",
+            Name::Error => "error",
+            Name::String => "string",
+            Name::Table => "table",
+            Name::Math => "math",
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+enum Key {
+    Index,
+    Newindex,
+    Call,
+    Add,
+    Concat,
+    Len,
+    Eq,
+    Lt,
+    Close,
+    Gc,
+    Insert,
+    Rep,
+    Sub,
+}
+
+impl Key {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Key::Index => "__index",
+            Key::Newindex => "__newindex",
+            Key::Call => "__call",
+            Key::Add => "__add",
+            Key::Concat => "__concat",
+            Key::Len => "__len",
+            Key::Eq => "__eq",
+            Key::Lt => "__lt",
+            Key::Close => "__close",
+            Key::Gc => "__gc",
+            Key::Insert => "insert",
+            Key::Rep => "rep",
+            Key::Sub => "sub",
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    IDiv,
+    Mod,
+    Pow,
+    Concat,
+    Eq,
+    Lt,
+    Le,
+    And,
+    Or,
+    BAnd,
+    Shl,
+}
+
+impl BinOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::IDiv => "//",
+            BinOp::Mod => "%",
+            BinOp::Pow => "^",
+            BinOp::Concat => "..",
+            BinOp::Eq => "==",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+            BinOp::BAnd => "&",
+            BinOp::Shl => "<<",
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+enum UnOp {
+    Neg,
+    Not,
+    Len,
+    BNot,
+}
+
+impl UnOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UnOp::Neg => "-",
+            UnOp::Not => "not ",
+            UnOp::Len => "#",
+            UnOp::BNot => "~",
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+enum Expr {
+    Nil,
+    True,
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Vararg,
+    Name(Name),
+    Field(Box<Expr>, Key),
+    Index(Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    Method(Box<Expr>, Key, Vec<Expr>),
+    Function(Vec<Name>, bool, Block),
+    Table(Vec<Expr>, Vec<(Key, Expr)>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+    Unary(UnOp, Box<Expr>),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Stat {
+    Local(Name, Option<Expr>, bool),
+    Assign(Expr, Expr),
+    Call(Expr, Vec<Expr>),
+    If(Expr, Block, Block),
+    While(Expr, Block),
+    NumericFor(Name, Expr, Expr, Option<Expr>, Block),
+    GenericFor(Name, Name, Expr, Block),
+    Do(Block),
+    Break,
+    Return(Vec<Expr>),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Block(Vec<Stat>);
+
+fn write_list(out: &mut String, exprs: &[Expr]) -> fmt::Result {
+    for (i, e) in exprs.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        e.write(out)?;
+    }
+    Ok(())
+}
+
+fn write_params(out: &mut String, params: &[Name], vararg: bool) {
+    let mut names: Vec<&str> = params.iter().map(|p| p.as_str()).collect();
+    if vararg {
+        names.push("...");
+    }
+    out.push_str(&names.join(", "));
+}
+
+impl Expr {
+    fn write(&self, out: &mut String) -> fmt::Result {
+        match self {
+            Expr::Nil => out.push_str("nil"),
+            Expr::True => out.push_str("true"),
+            Expr::Int(i) => write!(out, "{}", i)?,
+            Expr::Float(f) if f.is_nan() => out.push_str("(0/0)"),
+            Expr::Float(f) if f.is_infinite() => {
+                out.push_str(if *f > 0.0 { "(1/0)" } else { "(-1/0)" })
+            }
+            Expr::Float(f) => write!(out, "{:?}", f)?,
+            // Rust's debug escapes (\n, \", \u{..}) are all valid in Lua
+            Expr::Str(s) => write!(out, "{:?}", s)?,
+            Expr::Vararg => out.push_str("..."),
+            Expr::Name(n) => out.push_str(n.as_str()),
+            Expr::Field(e, k) => {
+                out.push('(');
+                e.write(out)?;
+                write!(out, ").{}", k.as_str())?;
+            }
+            Expr::Index(e, k) => {
+                out.push('(');
+                e.write(out)?;
+                out.push_str(")[");
+                k.write(out)?;
+                out.push(']');
+            }
+            Expr::Call(f, args) => {
+                out.push('(');
+                f.write(out)?;
+                out.push_str(")(");
+                write_list(out, args)?;
+                out.push(')');
+            }
+            Expr::Method(obj, k, args) => {
+                out.push('(');
+                obj.write(out)?;
+                write!(out, "):{}(", k.as_str())?;
+                write_list(out, args)?;
+                out.push(')');
+            }
+            Expr::Function(params, vararg, body) => {
+                out.push_str("function(");
+                write_params(out, params, *vararg);
+                out.push_str(")\n");
+                body.write(out)?;
+                out.push_str("end");
+            }
+            Expr::Table(items, fields) => {
+                // The positional part always has one item, so every named
+                // field can follow a separator
+                out.push_str("{nil");
+                for item in items {
+                    out.push_str(", ");
+                    item.write(out)?;
+                }
+                for (k, v) in fields {
+                    write!(out, "; {} = ", k.as_str())?;
+                    v.write(out)?;
+                }
+                out.push('}');
+            }
+            Expr::Binary(l, op, r) => {
+                out.push('(');
+                l.write(out)?;
+                write!(out, " {} ", op.as_str())?;
+                r.write(out)?;
+                out.push(')');
+            }
+            Expr::Unary(op, e) => {
+                write!(out, "({}", op.as_str())?;
+                e.write(out)?;
+                out.push(')');
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Stat {
+    fn write(&self, out: &mut String) -> fmt::Result {
+        match self {
+            Stat::Local(n, init, close) => {
+                write!(out, "local {}", n.as_str())?;
+                if *close {
+                    out.push_str(" <close>");
+                }
+                if let Some(e) = init {
+                    out.push_str(" = ");
+                    e.write(out)?;
+                }
+            }
+            Stat::Assign(target, value) => {
+                // Only names and indexing are assignable
+                match target {
+                    Expr::Name(_) | Expr::Field(..) | Expr::Index(..) => target.write(out)?,
+                    _ => out.push_str("a"),
+                }
+                out.push_str(" = ");
+                value.write(out)?;
+            }
+            Stat::Call(f, args) => {
+                out.push('(');
+                f.write(out)?;
+                out.push_str(")(");
+                write_list(out, args)?;
+                out.push(')');
+            }
+            Stat::If(cond, then, otherwise) => {
+                out.push_str("if ");
+                cond.write(out)?;
+                out.push_str(" then\n");
+                then.write(out)?;
+                out.push_str("else\n");
+                otherwise.write(out)?;
+                out.push_str("end");
+            }
+            Stat::While(cond, body) => {
+                out.push_str("while ");
+                cond.write(out)?;
+                out.push_str(" do\n");
+                body.write(out)?;
+                out.push_str("end");
+            }
+            Stat::NumericFor(n, start, limit, step, body) => {
+                write!(out, "for {} = ", n.as_str())?;
+                start.write(out)?;
+                out.push_str(", ");
+                limit.write(out)?;
+                if let Some(step) = step {
+                    out.push_str(", ");
+                    step.write(out)?;
+                }
+                out.push_str(" do\n");
+                body.write(out)?;
+                out.push_str("end");
+            }
+            Stat::GenericFor(k, v, iter, body) => {
+                write!(out, "for {}, {} in ", k.as_str(), v.as_str())?;
+                iter.write(out)?;
+                out.push_str(" do\n");
+                body.write(out)?;
+                out.push_str("end");
+            }
+            Stat::Do(body) => {
+                out.push_str("do\n");
+                body.write(out)?;
+                out.push_str("end");
+            }
+            Stat::Break => out.push_str("break"),
+            Stat::Return(values) => {
+                // `return` must end its block
+                out.push_str("do return ");
+                write_list(out, values)?;
+                out.push_str(" end");
+            }
+        }
+        out.push('\n');
+        Ok(())
+    }
+}
+
+impl Block {
+    fn write(&self, out: &mut String) -> fmt::Result {
+        for stat in &self.0 {
+            stat.write(out)?;
+        }
+        Ok(())
+    }
+}
+
+fuzz_target!(|program: Block| {
+    let mut source = String::new();
+    if program.write(&mut source).is_err() {
+        return;
+    }
+
+    let mut lua = Lua::new(SafeOption::default());
+    lua.open_stdlib(Stdlib::All).unwrap();
+    let config = SandboxConfig::default()
+        .with_instruction_limit(INSTRUCTION_LIMIT)
+        .with_memory_limit(MEMORY_LIMIT);
+    let _ = lua.execute_sandboxed(&source, &config);
+});